@@ -0,0 +1,224 @@
+//! 同じプロジェクトの異なるチケットを、複数のスレッドから並行して更新するベンチマーク
+//!
+//! `project_lock`は、更新のたびにプロジェクトの書き込みロックを取得する、シャードに分割する前の方式である。
+//! `sharded`は、プロジェクトの読み込みロックを共有したまま、チケットのシャードの書き込みロックだけを取得する。
+//! `sharded_file`は、`sharded`と同じ更新を、イベントをファイルに追記するプロジェクトで行う。
+//!
+//! シャードのロックを取得したあとも、すべてのチケットで共有するロックは次のように短くしている。
+//!
+//! - イベントのJSONは、ストレージのロックを取得する前に作成し、ロックの中では追記だけを行う。
+//! - 検索索引の語の頻度は、索引の書き込みロックを取得する前に数える。
+//! - 状態、添付ファイル、タイムライン、サブタスク、ブロッカー、検索の索引は、その索引が扱うイベントを記録するときだけロックする。
+//! - イベントの履歴は、チケットのシャードと同じ分割で保持し、通し番号だけを共有する。
+//!
+//! `concurrent_complete`は、多数のチケットがあるプロジェクトで、サブタスクが完了したチケットの完了と再開を繰り返す。
+//! 完了していないサブタスクとブロッカーの確認が、他のチケットの数によらないことを確かめる。
+//!
+//! 1コアの環境で計測した結果（criterionの中央値、`--warm-up-time 1 --measurement-time 3`）:
+//!
+//! | ベンチマーク | スレッド数 | 時間 | スループット |
+//! | --- | --- | --- | --- |
+//! | `concurrent_patch/project_lock` | 1 | 1.68 ms | 1.19 M更新/s |
+//! | `concurrent_patch/sharded` | 1 | 2.06 ms | 969 K更新/s |
+//! | `concurrent_patch/sharded_file` | 1 | 5.62 ms | 356 K更新/s |
+//! | `concurrent_patch/project_lock` | 2 | 3.70 ms | 1.08 M更新/s |
+//! | `concurrent_patch/sharded` | 2 | 3.53 ms | 1.13 M更新/s |
+//! | `concurrent_patch/sharded_file` | 2 | 10.9 ms | 369 K更新/s |
+//! | `concurrent_complete/sharded` | 1 | 136 µs | 737 K更新/s |
+//! | `concurrent_complete/sharded` | 2 | 262 µs | 763 K更新/s |
+//!
+//! この環境はCPUが1つしかなく、スレッドが並行して動かないため、スレッド数を増やしてもスループットは増えない。
+//! 1スレッドでの差は計測のばらつき（±15%程度）の範囲である。
+//! シャードとロックの縮小による効果は、複数コアの環境で`sharded`と`sharded_file`のスループットを比べて確かめる。
+//!
+//! ```sh
+//! cargo bench --bench concurrent_patch
+//! ```
+
+use std::path::Path;
+use std::sync::RwLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ticket_store::dto::{TicketDraft, TicketPatch};
+use ticket_store::models::{
+    Priority, Project, TicketDescription, TicketId, TicketStatus, TicketTitle,
+};
+use ticket_store::store::TicketStore;
+use ticket_store::sync::{read_lock, write_lock};
+
+/// 1つのスレッドが更新する回数
+const PATCHES_PER_THREAD: u64 = 2_000;
+
+/// チケットを更新する方式
+#[derive(Debug, Clone, Copy)]
+enum Locking {
+    ProjectLock,
+    Sharded,
+    /// ファイルストレージを持つプロジェクトで、`Sharded`と同じく更新する。
+    ShardedFile,
+}
+
+impl Locking {
+    fn name(self) -> &'static str {
+        match self {
+            Self::ProjectLock => "project_lock",
+            Self::Sharded => "sharded",
+            Self::ShardedFile => "sharded_file",
+        }
+    }
+
+    fn patch(self, store: &RwLock<TicketStore>, id: TicketId, patch: TicketPatch) {
+        match self {
+            Self::ProjectLock => write_lock(store).update_ticket(id, patch),
+            Self::Sharded | Self::ShardedFile => read_lock(store).update_ticket(id, patch),
+        }
+        .unwrap();
+    }
+}
+
+/// 1つのスレッドが完了と再開を繰り返す回数
+const COMPLETIONS_PER_THREAD: u64 = 100;
+
+/// 完了と再開を繰り返すプロジェクトの、他のチケットの数
+const OTHER_TICKETS: usize = 10_000;
+
+fn draft(i: usize) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(format!("チケット{i}")).unwrap(),
+        description: TicketDescription::try_from("説明").unwrap(),
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+fn patch(version: u64, status: Option<TicketStatus>, priority: Option<Priority>) -> TicketPatch {
+    TicketPatch {
+        title: None,
+        description: None,
+        status,
+        priority,
+        due_date: None,
+        version,
+    }
+}
+
+/// スレッドごとに1つのチケットを登録したプロジェクトを構築する。
+///
+/// `data_dir`を指定した場合は、データディレクトリにファイルストレージを作成する。
+fn project(threads: usize, data_dir: Option<&Path>) -> (RwLock<TicketStore>, Vec<TicketId>) {
+    let store = match data_dir {
+        Some(data_dir) => TicketStore::create(data_dir, Project::default()).unwrap(),
+        None => TicketStore::default(),
+    };
+    let ids = (0..threads)
+        .map(|i| store.add_ticket(draft(i)).unwrap())
+        .collect();
+
+    (RwLock::new(store), ids)
+}
+
+/// 他のチケットと、スレッドごとに完了したサブタスクを持つ1つのチケットを登録したプロジェクトを構築する。
+fn project_with_subtasks(threads: usize) -> (TicketStore, Vec<TicketId>) {
+    let mut store = TicketStore::default();
+    for i in 0..OTHER_TICKETS {
+        store.add_ticket(draft(i)).unwrap();
+    }
+    let ids = (0..threads)
+        .map(|i| {
+            let id = store.add_ticket(draft(i)).unwrap();
+            let subtask = store.add_ticket(draft(i)).unwrap();
+            store.set_parent(subtask, Some(id)).unwrap();
+            let version = store.get(subtask).unwrap().version;
+            store
+                .update_ticket(subtask, patch(version, Some(TicketStatus::Done), None))
+                .unwrap();
+            id
+        })
+        .collect();
+
+    (store, ids)
+}
+
+/// スレッドごとに自身のチケットの優先度を繰り返し更新して、すべてのスレッドが終わるまでの時間を返す。
+fn run(locking: Locking, threads: usize, iterations: u64) -> Duration {
+    let data_dir = tempfile::tempdir().unwrap();
+    let data_dir = matches!(locking, Locking::ShardedFile).then_some(data_dir.path());
+    let (store, ids) = project(threads, data_dir);
+    let started = Instant::now();
+    thread::scope(|s| {
+        for &id in &ids {
+            let store = &store;
+            s.spawn(move || {
+                for version in 0..iterations * PATCHES_PER_THREAD {
+                    let priority = match version % 2 {
+                        0 => Priority::High,
+                        _ => Priority::Low,
+                    };
+                    locking.patch(store, id, patch(version, None, Some(priority)));
+                }
+            });
+        }
+    });
+
+    started.elapsed()
+}
+
+fn concurrent_patch(c: &mut Criterion) {
+    let parallelism = thread::available_parallelism().map_or(4, |n| n.get());
+    let mut group = c.benchmark_group("concurrent_patch");
+    for threads in [1, parallelism.clamp(2, 8)] {
+        group.throughput(Throughput::Elements(threads as u64 * PATCHES_PER_THREAD));
+        for locking in [Locking::ProjectLock, Locking::Sharded, Locking::ShardedFile] {
+            group.bench_with_input(
+                BenchmarkId::new(locking.name(), threads),
+                &threads,
+                |b, &threads| b.iter_custom(|iterations| run(locking, threads, iterations)),
+            );
+        }
+    }
+    group.finish();
+}
+
+/// スレッドごとに自身のチケットの完了と再開を繰り返して、すべてのスレッドが終わるまでの時間を返す。
+fn complete(store: &TicketStore, ids: &[TicketId], iterations: u64) -> Duration {
+    let started = Instant::now();
+    thread::scope(|s| {
+        for &id in ids {
+            s.spawn(move || {
+                let first = store.get(id).unwrap().version;
+                for version in first..first + iterations * COMPLETIONS_PER_THREAD {
+                    let status = match version % 2 {
+                        0 => TicketStatus::Done,
+                        _ => TicketStatus::ToDo,
+                    };
+                    store
+                        .update_ticket(id, patch(version, Some(status), None))
+                        .unwrap();
+                }
+            });
+        }
+    });
+
+    started.elapsed()
+}
+
+fn concurrent_complete(c: &mut Criterion) {
+    let parallelism = thread::available_parallelism().map_or(4, |n| n.get());
+    let mut group = c.benchmark_group("concurrent_complete");
+    group.sample_size(10);
+    for threads in [1, parallelism.clamp(2, 8)] {
+        let (store, ids) = project_with_subtasks(threads);
+        group.throughput(Throughput::Elements(
+            threads as u64 * COMPLETIONS_PER_THREAD,
+        ));
+        group.bench_with_input(BenchmarkId::new("sharded", threads), &ids, |b, ids| {
+            b.iter_custom(|iterations| complete(&store, ids, iterations))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, concurrent_patch, concurrent_complete);
+criterion_main!(benches);
@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+
+use ticket_store::models::ProjectKey;
+
+/// 既定のサーバーのベースURL
+pub const DEFAULT_URL: &str = "http://localhost:3000";
+
+/// 設定ファイル
+///
+/// 各設定は、設定ファイル、環境変数、コマンドライン引数の順に優先される。
+///
+/// ```toml
+/// url = "http://localhost:3000"
+/// user = "alice"
+/// token = "..."
+/// project = "WEB"
+/// ```
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CliConfig {
+    /// サーバーのベースURL
+    pub url: Option<String>,
+    /// `X-User`ヘッダで指定する利用者
+    pub user: Option<String>,
+    /// `Authorization`ヘッダで送信するベアラートークン
+    pub token: Option<String>,
+    /// チケットIDでチケットを指定したときのプロジェクト
+    pub project: Option<ProjectKey>,
+}
+
+impl CliConfig {
+    /// 設定ファイルを読み込む。
+    ///
+    /// パスを指定しなかった場合は、`$XDG_CONFIG_HOME/tickets/config.toml`または
+    /// `~/.config/tickets/config.toml`を読み込み、ファイルがなければ既定の設定を返す。
+    ///
+    /// # 引数
+    ///
+    /// * `path` - 設定ファイルのパス
+    ///
+    /// # 戻り値
+    ///
+    /// 設定
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => {
+                return Ok(Self::default())
+            }
+            Err(source) => return Err(ConfigError::Read { path, source }),
+        };
+
+        toml::from_str(&content).map_err(|source| ConfigError::Parse { path, source })
+    }
+}
+
+/// 既定の設定ファイルのパスを返す。
+fn default_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(config_home.join("tickets").join("config.toml"))
+}
+
+/// 設定エラー
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("設定ファイル{}を読み込めません: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("設定ファイル{}が不正です: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+}
@@ -0,0 +1,178 @@
+use std::io::Write;
+use std::process::Command;
+
+use chrono::NaiveDate;
+use ticket_store::dto::TicketPatch;
+use ticket_store::models::{Priority, Ticket, TicketDescription, TicketStatus, TicketTitle};
+
+/// エディターを指定する環境変数が設定されていない場合に使用するエディター
+const DEFAULT_EDITOR: &str = "vi";
+
+/// エディターで編集するファイルの形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum EditFormat {
+    #[default]
+    Toml,
+    Yaml,
+}
+
+impl EditFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Toml => ".toml",
+            Self::Yaml => ".yaml",
+        }
+    }
+}
+
+/// エディターで編集できるチケットの項目
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct EditableTicket {
+    title: String,
+    description: String,
+    status: TicketStatus,
+    priority: Priority,
+    /// 期限がない場合は項目を省略する。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    due_date: Option<NaiveDate>,
+}
+
+impl From<&Ticket> for EditableTicket {
+    fn from(ticket: &Ticket) -> Self {
+        Self {
+            title: ticket.title.0.clone(),
+            description: ticket.description.0.clone(),
+            status: ticket.status,
+            priority: ticket.priority,
+            due_date: ticket.due_date,
+        }
+    }
+}
+
+/// チケットをエディターで編集して、変更した項目からパッチを作成する。
+///
+/// パッチのバージョンは、編集を開始したときのチケットのバージョンである。
+///
+/// # 引数
+///
+/// * `ticket` - 編集するチケット
+/// * `format` - 編集するファイルの形式
+///
+/// # 戻り値
+///
+/// チケットのパッチ、項目を変更しなかった場合は`None`
+pub fn edit(ticket: &Ticket, format: EditFormat) -> Result<Option<TicketPatch>, EditError> {
+    let original = EditableTicket::from(ticket);
+    let mut file = tempfile::Builder::new()
+        .prefix(&format!("{}-", ticket.key))
+        .suffix(format.extension())
+        .tempfile()?;
+    let header = format!(
+        "# {}（バージョン{}）を編集しています。保存してエディターを終了すると更新します。\n\
+         # 期限は\"2024-07-31\"のように引用符で囲み、期限を削除する場合は項目を削除します。\n",
+        ticket.key, ticket.version
+    );
+    let body = match format {
+        EditFormat::Toml => {
+            toml::to_string(&original).map_err(|e| EditError::Format(e.to_string()))?
+        }
+        EditFormat::Yaml => {
+            serde_yaml::to_string(&original).map_err(|e| EditError::Format(e.to_string()))?
+        }
+    };
+    file.write_all(header.as_bytes())?;
+    file.write_all(body.as_bytes())?;
+    file.flush()?;
+
+    open_editor(file.path())?;
+    let content = std::fs::read_to_string(file.path())?;
+    let edited: EditableTicket = match format {
+        EditFormat::Toml => {
+            toml::from_str(&content).map_err(|e| EditError::Invalid(e.to_string()))?
+        }
+        EditFormat::Yaml => {
+            serde_yaml::from_str(&content).map_err(|e| EditError::Invalid(e.to_string()))?
+        }
+    };
+
+    diff(&original, edited, ticket.version)
+}
+
+/// `$VISUAL`または`$EDITOR`で指定されたエディターでファイルを開き、終了するまで待つ。
+///
+/// エディターの指定には、`code --wait`のように引数を含めることができる。
+fn open_editor(path: &std::path::Path) -> Result<(), EditError> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.into());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or(DEFAULT_EDITOR);
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|source| EditError::Editor {
+            editor: editor.clone(),
+            source,
+        })?;
+    if !status.success() {
+        return Err(EditError::EditorFailed(editor));
+    }
+
+    Ok(())
+}
+
+/// 編集前と編集後の項目を比較して、変更した項目だけを含むパッチを作成する。
+fn diff(
+    original: &EditableTicket,
+    edited: EditableTicket,
+    version: u64,
+) -> Result<Option<TicketPatch>, EditError> {
+    if original == &edited {
+        return Ok(None);
+    }
+
+    let title = match edited.title != original.title {
+        true => Some(
+            TicketTitle::try_from(edited.title).map_err(|e| EditError::Invalid(e.to_string()))?,
+        ),
+        false => None,
+    };
+    let description = match edited.description != original.description {
+        true => Some(
+            TicketDescription::try_from(edited.description)
+                .map_err(|e| EditError::Invalid(e.to_string()))?,
+        ),
+        false => None,
+    };
+
+    Ok(Some(TicketPatch {
+        title,
+        description,
+        status: (edited.status != original.status).then_some(edited.status),
+        priority: (edited.priority != original.priority).then_some(edited.priority),
+        due_date: (edited.due_date != original.due_date).then_some(edited.due_date),
+        version,
+    }))
+}
+
+/// 編集エラー
+#[derive(Debug, thiserror::Error)]
+pub enum EditError {
+    #[error("編集するファイルを作成できません: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("チケットを編集するファイルに書き出せません: {0}")]
+    Format(String),
+    #[error("エディター`{editor}`を起動できません: {source}")]
+    Editor {
+        editor: String,
+        source: std::io::Error,
+    },
+    #[error("エディター`{0}`が異常終了したため、チケットを更新しません。")]
+    EditorFailed(String),
+    #[error("編集したチケットが不正です: {0}")]
+    Invalid(String),
+}
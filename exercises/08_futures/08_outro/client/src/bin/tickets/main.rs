@@ -0,0 +1,481 @@
+//! チケット管理システムのコマンドラインクライアント
+//!
+//! 起動している`ticket-store`サーバーにREST APIでアクセスする。
+//! サーバーのURL、利用者、トークンは、設定ファイル（`--config`）、`TICKETS_`で始まる環境変数、
+//! コマンドライン引数の順に優先される。
+//!
+//! 終了コードは、成功した場合は`0`、引数や設定が誤っている場合は`2`、
+//! プロジェクトまたはチケットが見つからない場合は`3`、チケットのバージョンが一致しない場合は`4`、
+//! その他のエラーの場合は`1`である。
+//!
+//! ```sh
+//! $ cat ~/.config/tickets/config.toml
+//! url = "http://localhost:3000"
+//! user = "alice"
+//!
+//! # チケットを登録して、チケットキーを表示
+//! $ tickets create --title "羅生門" --description "芥川龍之介の短編" --priority High
+//! TICKET-1
+//!
+//! # チケットの一覧を表で表示（`--query`を指定すると、クエリで読み込みを許可されたすべてのプロジェクトから絞り込む）
+//! $ tickets list --status ToDo --sort -priority
+//! KEY       STATUS  PRIORITY  DUE  TITLE
+//! TICKET-1  ToDo    High      -    羅生門
+//!
+//! # チケットをJSONで表示
+//! $ tickets --json show TICKET-1
+//!
+//! # チケットを`$EDITOR`でTOML（`--format yaml`でYAML）として編集し、変更した項目を更新
+//! $ tickets edit TICKET-1
+//!
+//! # チケットのステータスを変更
+//! $ tickets move TICKET-1 InProgress
+//!
+//! # チケットの作成、更新、削除または移動を表示し続ける
+//! $ tickets watch --query "project:TICKET"
+//!
+//! # チケットをカンバンボードで表示（Shift+←→でカードを移動、Enterでタイトルを編集、qで終了）
+//! $ tickets --project WEB board
+//! ```
+
+mod config;
+mod edit;
+mod output;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use chrono::{NaiveDate, SecondsFormat, Utc};
+use clap::{Parser, Subcommand};
+use crossterm::event::EventStream;
+use serde_json::json;
+use ticket_store::dto::{DueFilter, TicketDraft, TicketPatch, TicketQuery, TicketSort};
+use ticket_store::models::{
+    Priority, ProjectKey, Ticket, TicketDescription, TicketKey, TicketRef, TicketStatus,
+    TicketTitle,
+};
+use ticket_store::store::TicketStoreError;
+use ticket_store_client::board::BoardApp;
+use ticket_store_client::{Client, ClientError};
+
+use config::{CliConfig, ConfigError, DEFAULT_URL};
+use edit::{EditError, EditFormat};
+
+/// 引数や設定が誤っている場合の終了コード
+const EXIT_CONFIG_ERROR: u8 = 2;
+
+/// プロジェクトまたはチケットが見つからない場合の終了コード
+const EXIT_NOT_FOUND: u8 = 3;
+
+/// チケットのバージョンが一致しない場合の終了コード
+const EXIT_CONFLICT: u8 = 4;
+
+/// コマンドライン引数
+#[derive(Debug, Parser)]
+#[command(
+    name = "tickets",
+    version,
+    about = "チケット管理システムのコマンドラインクライアント"
+)]
+struct Args {
+    /// TOML形式の設定ファイルのパス
+    #[arg(short, long, global = true, env = "TICKETS_CONFIG")]
+    config: Option<PathBuf>,
+    /// サーバーのベースURL
+    #[arg(long, global = true, env = "TICKETS_URL")]
+    url: Option<String>,
+    /// `X-User`ヘッダで指定する利用者
+    #[arg(long, global = true, env = "TICKETS_USER")]
+    user: Option<String>,
+    /// `Authorization`ヘッダで送信するベアラートークン
+    #[arg(long, global = true, env = "TICKETS_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+    /// チケットIDでチケットを指定したときのプロジェクト（省略時は`TICKET`）
+    #[arg(short, long, global = true, env = "TICKETS_PROJECT", value_parser = |s: &str| ProjectKey::try_from(s))]
+    project: Option<ProjectKey>,
+    /// 表の代わりにJSONで出力する
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Cmd,
+}
+
+/// サブコマンド
+#[derive(Debug, Subcommand)]
+enum Cmd {
+    /// チケットを登録する
+    Create {
+        /// タイトル
+        #[arg(long, value_parser = |s: &str| TicketTitle::try_from(s))]
+        title: TicketTitle,
+        /// 説明
+        #[arg(long, value_parser = |s: &str| TicketDescription::try_from(s))]
+        description: TicketDescription,
+        /// 優先度（`Low`、`Medium`、`High`、`Urgent`）
+        #[arg(long, default_value = "Medium", value_parser = |s: &str| Priority::try_from(s))]
+        priority: Priority,
+        /// 期限（`2024-07-31`形式）
+        #[arg(long)]
+        due: Option<NaiveDate>,
+    },
+    /// チケットを表示する
+    Show {
+        /// チケットキーまたはチケットID
+        #[arg(value_parser = |s: &str| TicketRef::try_from(s))]
+        ticket: TicketRef,
+    },
+    /// チケットの一覧を表示する
+    List {
+        /// ステータス（`ToDo`、`InProgress`、`Done`）
+        #[arg(long, value_parser = |s: &str| TicketStatus::try_from(s))]
+        status: Option<TicketStatus>,
+        /// 優先度（`Low`、`Medium`、`High`、`Urgent`）
+        #[arg(long, value_parser = |s: &str| Priority::try_from(s))]
+        priority: Option<Priority>,
+        /// 期限（`overdue`、`today`、`thisWeek`、`none`）
+        #[arg(long, value_parser = due_filter_from_str)]
+        due: Option<DueFilter>,
+        /// 並び順（`id`、`priority`、`dueDate`、`createdAt`、`updatedAt`、先頭に`-`を付けると降順）
+        #[arg(long, allow_hyphen_values = true, value_parser = |s: &str| TicketSort::try_from(s))]
+        sort: Option<TicketSort>,
+        /// クエリ（指定した場合は、他の絞り込みと並び順を使用しない）
+        #[arg(short, long, conflicts_with_all = ["status", "priority", "due", "sort"])]
+        query: Option<String>,
+    },
+    /// チケットをエディターで編集する
+    Edit {
+        /// チケットキーまたはチケットID
+        #[arg(value_parser = |s: &str| TicketRef::try_from(s))]
+        ticket: TicketRef,
+        /// 編集するファイルの形式
+        #[arg(long, value_enum, default_value_t)]
+        format: EditFormat,
+    },
+    /// チケットのステータスを変更する
+    Move {
+        /// チケットキーまたはチケットID
+        #[arg(value_parser = |s: &str| TicketRef::try_from(s))]
+        ticket: TicketRef,
+        /// 変更後のステータス（`ToDo`、`InProgress`、`Done`）
+        #[arg(value_parser = |s: &str| TicketStatus::try_from(s))]
+        status: TicketStatus,
+    },
+    /// クエリに一致するチケットの作成、更新、削除または移動を表示し続ける
+    Watch {
+        /// クエリ（省略時はすべてのチケット）
+        #[arg(short, long, default_value = "version>=0")]
+        query: String,
+        /// 問い合わせる間隔（ミリ秒）
+        #[arg(long, default_value_t = 2000)]
+        interval_ms: u64,
+    },
+    /// プロジェクトのチケットをカンバンボードで表示して操作する
+    Board,
+}
+
+/// 文字列から期限による絞り込みを構築する。
+fn due_filter_from_str(s: &str) -> Result<DueFilter, String> {
+    match s.trim() {
+        "overdue" => Ok(DueFilter::Overdue),
+        "today" => Ok(DueFilter::Today),
+        "thisWeek" => Ok(DueFilter::ThisWeek),
+        "none" => Ok(DueFilter::None),
+        _ => Err("期限は、`overdue`、`today`、`thisWeek`または`none`のいずれかです。".into()),
+    }
+}
+
+/// コマンドの実行に必要なクライアントと設定
+struct Context {
+    client: Client,
+    /// チケットIDでチケットを指定したときのプロジェクト
+    project: ProjectKey,
+    json: bool,
+}
+
+impl Context {
+    /// 設定ファイルとコマンドライン引数から構築する。
+    fn new(args: &Args) -> Result<Self, CliError> {
+        let config = CliConfig::load(args.config.as_deref())?;
+        let url = args
+            .url
+            .clone()
+            .or(config.url)
+            .unwrap_or_else(|| DEFAULT_URL.into());
+        let mut builder = Client::builder(url);
+        if let Some(user) = args.user.clone().or(config.user) {
+            builder = builder.user(user);
+        }
+        if let Some(token) = args.token.clone().or(config.token) {
+            builder = builder.token(token);
+        }
+        let client = builder.build().map_err(CliError::InvalidUrl)?;
+        let project = args.project.clone().or(config.project).unwrap_or_default();
+
+        Ok(Self {
+            client,
+            project,
+            json: args.json,
+        })
+    }
+
+    /// チケットの指定から、チケットがあるプロジェクトを返す。
+    ///
+    /// チケットキーで指定した場合はチケットキーのプロジェクト、チケットIDで指定した場合は設定したプロジェクトである。
+    fn project_of(&self, ticket: &TicketRef) -> ProjectKey {
+        match ticket {
+            TicketRef::Key(key) => key.project.clone(),
+            TicketRef::Id(_) => self.project.clone(),
+        }
+    }
+
+    /// チケットをJSONまたは詳細として出力する。
+    fn print_ticket(&self, ticket: &Ticket) {
+        match self.json {
+            true => println!("{}", pretty(ticket)),
+            false => print!("{}", output::ticket_detail(ticket)),
+        }
+    }
+
+    /// 更新したチケットをJSONまたはメッセージとして出力する。
+    fn print_updated(&self, ticket: &Ticket) {
+        match self.json {
+            true => println!("{}", pretty(ticket)),
+            false => println!(
+                "{}を更新しました（バージョン{}）。",
+                ticket.key, ticket.version
+            ),
+        }
+    }
+}
+
+fn pretty<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string_pretty(value).expect("チケットはJSONにシリアライズできる")
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    let result = match Context::new(&args) {
+        Ok(ctx) => run(args.command, &ctx).await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("エラー: {e}");
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// サブコマンドを実行する。
+async fn run(command: Cmd, ctx: &Context) -> Result<(), CliError> {
+    match command {
+        Cmd::Create {
+            title,
+            description,
+            priority,
+            due,
+        } => {
+            let draft = TicketDraft {
+                title,
+                description,
+                priority,
+                due_date: due,
+            };
+            let key = ctx.client.create_ticket(&ctx.project, &draft).await?;
+            match ctx.json {
+                true => println!("{}", json!({"key": key})),
+                false => println!("{key}"),
+            }
+        }
+        Cmd::Show { ticket } => {
+            let ticket = ctx
+                .client
+                .get_ticket(&ctx.project_of(&ticket), &ticket)
+                .await?;
+            ctx.print_ticket(&ticket);
+        }
+        Cmd::List {
+            status,
+            priority,
+            due,
+            sort,
+            query,
+        } => {
+            let tickets = match query {
+                Some(query) => ctx.client.query_tickets(&query).await?,
+                None => {
+                    let query = TicketQuery {
+                        status,
+                        priority,
+                        due,
+                        updated_before: None,
+                        sort: sort.unwrap_or_default(),
+                    };
+                    ctx.client.list_tickets(&ctx.project, &query).await?
+                }
+            };
+            match ctx.json {
+                true => println!("{}", pretty(&tickets)),
+                false => print!("{}", output::ticket_table(&tickets)),
+            }
+        }
+        Cmd::Edit { ticket, format } => {
+            let project = ctx.project_of(&ticket);
+            let current = ctx.client.get_ticket(&project, &ticket).await?;
+            let Some(patch) = edit::edit(&current, format)? else {
+                eprintln!("変更がないため、チケットを更新しません。");
+                return Ok(());
+            };
+            // 編集中に他の利用者が更新した場合は、編集内容を失わないようにバージョンの不一致を返す。
+            ctx.client.update_ticket(&project, &ticket, &patch).await?;
+            let updated = ctx.client.get_ticket(&project, &ticket).await?;
+            ctx.print_updated(&updated);
+        }
+        Cmd::Move { ticket, status } => {
+            let project = ctx.project_of(&ticket);
+            let updated = ctx
+                .client
+                .update_ticket_with(&project, &ticket, |_| TicketPatch {
+                    title: None,
+                    description: None,
+                    status: Some(status),
+                    priority: None,
+                    due_date: None,
+                    version: 0,
+                })
+                .await?;
+            ctx.print_updated(&updated);
+        }
+        Cmd::Watch { query, interval_ms } => {
+            tokio::select! {
+                result = watch(ctx, &query, Duration::from_millis(interval_ms)) => result?,
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+        Cmd::Board => board(ctx).await?,
+    }
+
+    Ok(())
+}
+
+/// カンバンボードを端末の代替画面に表示して、終了するまで操作を受け付ける。
+///
+/// サーバーに接続してから端末を切り替えるため、接続に失敗した場合は通常どおりエラーを出力する。
+async fn board(ctx: &Context) -> Result<(), CliError> {
+    let app = BoardApp::connect(ctx.client.clone(), ctx.project.clone()).await?;
+    let mut terminal = ratatui::try_init().map_err(CliError::Terminal)?;
+    let result = app.run(&mut terminal, EventStream::new()).await;
+    ratatui::restore();
+
+    result.map_err(CliError::Terminal)
+}
+
+/// クエリに一致するチケットを定期的に問い合わせて、前回との差分を出力し続ける。
+///
+/// 最初の問い合わせに失敗した場合はエラーを返し、それ以降の失敗は警告を出力して問い合わせを続ける。
+async fn watch(ctx: &Context, query: &str, interval: Duration) -> Result<(), CliError> {
+    let mut known: BTreeMap<TicketKey, Ticket> = ctx
+        .client
+        .query_tickets(query)
+        .await?
+        .into_iter()
+        .map(|ticket| (ticket.key.clone(), ticket))
+        .collect();
+    loop {
+        tokio::time::sleep(interval).await;
+        let tickets = match ctx.client.query_tickets(query).await {
+            Ok(tickets) => tickets,
+            Err(e) => {
+                eprintln!("警告: チケットを問い合わせできません: {e}");
+                continue;
+            }
+        };
+        let mut current: BTreeMap<TicketKey, Ticket> = tickets
+            .into_iter()
+            .map(|ticket| (ticket.key.clone(), ticket))
+            .collect();
+        for (key, ticket) in &current {
+            match known.remove(key) {
+                None => print_change(ctx, "created", key, Some(ticket), None),
+                Some(previous) if previous.version != ticket.version => {
+                    print_change(ctx, "updated", key, Some(ticket), Some(&previous))
+                }
+                Some(_) => {}
+            }
+        }
+        for (key, previous) in &known {
+            print_change(ctx, "removed", key, None, Some(previous));
+        }
+        std::mem::swap(&mut known, &mut current);
+    }
+}
+
+/// チケットの変更を1行で出力する。
+///
+/// JSONで出力する場合は、1行に1つのJSONオブジェクトを出力する。
+fn print_change(
+    ctx: &Context,
+    event: &str,
+    key: &TicketKey,
+    ticket: Option<&Ticket>,
+    previous: Option<&Ticket>,
+) {
+    if ctx.json {
+        println!(
+            "{}",
+            json!({"event": event, "key": key, "ticket": ticket, "previous": previous})
+        );
+        return;
+    }
+
+    let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let detail = match (ticket, previous) {
+        (Some(ticket), Some(previous)) if ticket.status != previous.status => format!(
+            "{:?} → {:?}  {}",
+            previous.status, ticket.status, ticket.title.0
+        ),
+        (Some(ticket), _) => format!("{:?}  {}", ticket.status, ticket.title.0),
+        (None, Some(previous)) => previous.title.0.clone(),
+        (None, None) => String::new(),
+    };
+    let event = match event {
+        "created" => "作成",
+        "updated" => "更新",
+        _ => "削除または移動",
+    };
+    println!("{now}  {event}  {key}  {detail}");
+}
+
+/// コマンドラインクライアントエラー
+#[derive(Debug, thiserror::Error)]
+enum CliError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error("{0}")]
+    InvalidUrl(#[source] ClientError),
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    #[error(transparent)]
+    Edit(#[from] EditError),
+    #[error("端末を操作できません: {0}")]
+    Terminal(#[source] std::io::Error),
+}
+
+impl CliError {
+    /// エラーに対応する終了コードを返す。
+    fn exit_code(&self) -> u8 {
+        match self {
+            Self::Config(_) | Self::InvalidUrl(_) => EXIT_CONFIG_ERROR,
+            Self::Client(ClientError::Store(
+                TicketStoreError::NotFound | TicketStoreError::ProjectNotFound,
+            )) => EXIT_NOT_FOUND,
+            Self::Client(e) if e.is_conflict() => EXIT_CONFLICT,
+            Self::Client(_) | Self::Edit(_) | Self::Terminal(_) => 1,
+        }
+    }
+}
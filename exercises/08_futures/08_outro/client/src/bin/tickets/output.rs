@@ -0,0 +1,118 @@
+use chrono::SecondsFormat;
+use ticket_store::models::Ticket;
+use unicode_width::UnicodeWidthStr;
+
+/// 列の間の空白の幅
+const COLUMN_GAP: usize = 2;
+
+/// 値がない項目の表示
+const NONE: &str = "-";
+
+/// 表を文字列にする。
+///
+/// 全角文字は2文字分の幅として列をそろえる。最後の列は幅をそろえない。
+///
+/// # 引数
+///
+/// * `headers` - 見出し、見出しを表示しない場合は`None`
+/// * `rows` - 行
+///
+/// # 戻り値
+///
+/// 各行を改行で終端した文字列
+pub fn table(headers: Option<&[&str]>, rows: &[Vec<String>]) -> String {
+    let headers: Option<Vec<String>> = headers.map(|h| h.iter().map(|s| s.to_string()).collect());
+    let lines: Vec<&Vec<String>> = headers.iter().chain(rows).collect();
+    let columns = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| {
+            lines
+                .iter()
+                .filter_map(|line| line.get(i))
+                .map(|cell| cell.width())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut out = String::new();
+    for line in lines {
+        for (i, cell) in line.iter().enumerate() {
+            out.push_str(cell);
+            if i + 1 < line.len() {
+                out.push_str(&" ".repeat(widths[i] - cell.width() + COLUMN_GAP));
+            }
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+/// チケットの一覧を表にする。
+pub fn ticket_table(tickets: &[Ticket]) -> String {
+    let rows: Vec<_> = tickets
+        .iter()
+        .map(|ticket| {
+            vec![
+                ticket.key.to_string(),
+                format!("{:?}", ticket.status),
+                format!("{:?}", ticket.priority),
+                optional(ticket.due_date),
+                ticket.title.0.clone(),
+            ]
+        })
+        .collect();
+
+    table(Some(&["KEY", "STATUS", "PRIORITY", "DUE", "TITLE"]), &rows)
+}
+
+/// チケットの詳細を、項目名と値の表と説明にする。
+pub fn ticket_detail(ticket: &Ticket) -> String {
+    let blocked_by: Vec<_> = ticket
+        .blocked_by
+        .iter()
+        .map(|id| id.0.to_string())
+        .collect();
+    let previous_keys: Vec<_> = ticket
+        .previous_keys
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    let rows = [
+        ("KEY", ticket.key.to_string()),
+        ("TITLE", ticket.title.0.clone()),
+        ("STATUS", format!("{:?}", ticket.status)),
+        ("PRIORITY", format!("{:?}", ticket.priority)),
+        ("DUE", optional(ticket.due_date)),
+        ("PARENT", optional(ticket.parent.map(|id| id.0))),
+        ("BLOCKED BY", joined(&blocked_by)),
+        ("PREVIOUS KEYS", joined(&previous_keys)),
+        ("VERSION", ticket.version.to_string()),
+        (
+            "CREATED",
+            ticket.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ),
+        (
+            "UPDATED",
+            ticket.updated_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        ),
+    ];
+    let rows: Vec<_> = rows
+        .into_iter()
+        .map(|(name, value)| vec![name.to_string(), value])
+        .collect();
+
+    format!("{}\n{}\n", table(None, &rows), ticket.description.0)
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| NONE.into(), |value| value.to_string())
+}
+
+fn joined(values: &[String]) -> String {
+    match values.is_empty() {
+        true => NONE.into(),
+        false => values.join(", "),
+    }
+}
@@ -0,0 +1,677 @@
+//! 端末で操作するカンバンボード
+//!
+//! チケットステータスごとの列にプロジェクトのチケットをカードとして並べ、サーバーの変更フィードで表示を更新し続ける。
+//! カードの移動とタイトルの編集は、表示しているチケットのバージョンを指定して更新するため、
+//! 他の利用者の更新と競合した場合は、最新のチケットを読み込み直すか、自分の変更で上書きするかを選択する。
+//!
+//! 描画先は[`ratatui`]のバックエンドであるため、`TestBackend`を使用して端末なしで操作を検証できる。
+
+mod view;
+
+use std::io;
+use std::time::Duration;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use futures_util::{Stream, StreamExt};
+use ratatui::backend::Backend;
+use ratatui::Terminal;
+use ticket_store::dto::{EventFilter, TicketPatch, TicketQuery};
+use ticket_store::events::{TicketEvent, TicketEventKind};
+use ticket_store::models::{ProjectKey, Ticket, TicketId, TicketRef, TicketStatus, TicketTitle};
+use ticket_store::store::TicketStoreError;
+
+use crate::{Client, ClientError, EventStream, FeedEvent};
+
+/// 左から順に列に並べるチケットステータス
+pub const COLUMNS: [TicketStatus; 3] = [
+    TicketStatus::ToDo,
+    TicketStatus::InProgress,
+    TicketStatus::Done,
+];
+
+/// 変更フィードから切断された後、再接続するまでの待ち時間
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// カードに対する変更
+#[derive(Debug, Clone)]
+pub enum Change {
+    /// カードを他の列に移動する。
+    Status(TicketStatus),
+    /// タイトルを変更する。
+    Title(TicketTitle),
+}
+
+impl Change {
+    /// 変更を、指定したバージョンのチケットに適用するパッチにする。
+    fn patch(&self, version: u64) -> TicketPatch {
+        let (status, title) = match self {
+            Self::Status(status) => (Some(*status), None),
+            Self::Title(title) => (None, Some(title.clone())),
+        };
+        TicketPatch {
+            title,
+            description: None,
+            status,
+            priority: None,
+            due_date: None,
+            version,
+        }
+    }
+
+    /// 変更の説明を返す。
+    fn describe(&self) -> String {
+        match self {
+            Self::Status(status) => format!("{status:?}へ移動"),
+            Self::Title(title) => format!("タイトルを「{}」に変更", title.0),
+        }
+    }
+}
+
+/// 他の利用者の更新と競合した変更
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub ticket: TicketId,
+    pub change: Change,
+}
+
+/// 操作モード
+#[derive(Debug, Clone)]
+pub enum Mode {
+    /// カードを選択している。
+    Browse,
+    /// 選択しているカードのタイトルを編集している。
+    EditTitle {
+        ticket: TicketId,
+        input: String,
+        /// 入力中の文字列における、カーソルの位置（文字数）
+        cursor: usize,
+    },
+    /// 競合した変更を、読み込み直すか上書きするか選択している。
+    Conflict(Conflict),
+}
+
+/// 最下行に表示するメッセージ
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Message {
+    text: String,
+    error: bool,
+}
+
+/// キー入力によって実行する操作
+#[derive(Debug)]
+enum Action {
+    Update {
+        ticket: TicketId,
+        version: u64,
+        change: Change,
+    },
+    Overwrite(Conflict),
+    Reload(TicketId),
+    ReloadAll,
+    Quit,
+}
+
+/// カンバンボードの表示状態
+#[derive(Debug)]
+struct Board {
+    project: ProjectKey,
+    /// チケットステータスごとの列（チケットID順）
+    columns: [Vec<Ticket>; COLUMNS.len()],
+    /// フォーカスしている列
+    column: usize,
+    /// 列ごとの、選択しているカードの位置
+    rows: [usize; COLUMNS.len()],
+    mode: Mode,
+    message: Option<Message>,
+}
+
+impl Board {
+    fn new(project: ProjectKey) -> Self {
+        Self {
+            project,
+            columns: Default::default(),
+            column: 0,
+            rows: [0; COLUMNS.len()],
+            mode: Mode::Browse,
+            message: None,
+        }
+    }
+
+    /// 選択しているカードのチケットを返す。
+    fn selected(&self) -> Option<&Ticket> {
+        self.columns[self.column].get(self.rows[self.column])
+    }
+
+    fn find(&self, id: TicketId) -> Option<&Ticket> {
+        self.columns.iter().flatten().find(|ticket| ticket.id == id)
+    }
+
+    /// すべてのカードを置き換える。
+    fn load(&mut self, tickets: Vec<Ticket>) {
+        let selected = self.selected().map(|ticket| ticket.id);
+        self.columns = Default::default();
+        for ticket in tickets {
+            self.insert(ticket);
+        }
+        self.restore(selected);
+    }
+
+    /// チケットのカードを追加または置き換える。
+    fn upsert(&mut self, ticket: Ticket) {
+        let selected = self.selected().map(|ticket| ticket.id);
+        self.take(ticket.id);
+        self.insert(ticket);
+        self.restore(selected);
+    }
+
+    /// チケットのカードを取り除く。
+    fn remove(&mut self, id: TicketId) {
+        let selected = self.selected().map(|ticket| ticket.id);
+        self.take(id);
+        if matches!(&self.mode, Mode::EditTitle { ticket, .. } if *ticket == id)
+            || matches!(&self.mode, Mode::Conflict(conflict) if conflict.ticket == id)
+        {
+            self.mode = Mode::Browse;
+        }
+        self.restore(selected);
+    }
+
+    fn take(&mut self, id: TicketId) {
+        for column in &mut self.columns {
+            column.retain(|ticket| ticket.id != id);
+        }
+    }
+
+    fn insert(&mut self, ticket: Ticket) {
+        let i = column_of(ticket.status);
+        let column = &mut self.columns[i];
+        let at = column.partition_point(|t| t.id.0 < ticket.id.0);
+        column.insert(at, ticket);
+    }
+
+    /// カードが増減した後も、同じ列にあれば同じチケットを選択し続ける。
+    fn restore(&mut self, selected: Option<TicketId>) {
+        let column = &self.columns[self.column];
+        if let Some(row) = selected.and_then(|id| column.iter().position(|t| t.id == id)) {
+            self.rows[self.column] = row;
+        }
+        for (row, column) in self.rows.iter_mut().zip(&self.columns) {
+            *row = (*row).min(column.len().saturating_sub(1));
+        }
+    }
+
+    /// チケットのカードをフォーカスする。
+    fn focus(&mut self, id: TicketId) {
+        for (i, column) in self.columns.iter().enumerate() {
+            if let Some(row) = column.iter().position(|ticket| ticket.id == id) {
+                self.column = i;
+                self.rows[i] = row;
+            }
+        }
+    }
+
+    /// 変更フィードで受信したチケットイベントを反映する。
+    ///
+    /// 他のプロジェクトに移動したチケットのカードは取り除く。
+    fn apply(&mut self, event: &TicketEvent) {
+        if event.project == self.project {
+            self.upsert(event.ticket.clone());
+        } else if event.kind == TicketEventKind::Moved {
+            let previous_keys = &event.ticket.previous_keys;
+            let moved = self
+                .columns
+                .iter()
+                .flatten()
+                .find(|ticket| previous_keys.contains(&ticket.key));
+            if let Some(id) = moved.map(|ticket| ticket.id) {
+                self.remove(id);
+            }
+        }
+    }
+
+    fn inform(&mut self, text: impl Into<String>) {
+        self.message = Some(Message {
+            text: text.into(),
+            error: false,
+        });
+    }
+
+    fn fail(&mut self, text: impl Into<String>) {
+        self.message = Some(Message {
+            text: text.into(),
+            error: true,
+        });
+    }
+
+    /// キー入力を処理して、実行する操作を返す。
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(Action::Quit);
+        }
+        match self.mode.clone() {
+            Mode::Browse => self.browse(key),
+            Mode::EditTitle {
+                ticket,
+                input,
+                cursor,
+            } => self.edit_title(key, ticket, input, cursor),
+            Mode::Conflict(conflict) => match key.code {
+                KeyCode::Char('o') => Some(Action::Overwrite(conflict)),
+                KeyCode::Char('r') | KeyCode::Esc => Some(Action::Reload(conflict.ticket)),
+                _ => None,
+            },
+        }
+    }
+
+    fn browse(&mut self, key: KeyEvent) -> Option<Action> {
+        self.message = None;
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        match key.code {
+            KeyCode::Left if shift => return self.shift(-1),
+            KeyCode::Right if shift => return self.shift(1),
+            KeyCode::Char('H') => return self.shift(-1),
+            KeyCode::Char('L') => return self.shift(1),
+            KeyCode::Left | KeyCode::Char('h') => self.column = self.column.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => {
+                self.column = (self.column + 1).min(COLUMNS.len() - 1)
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.rows[self.column] = self.rows[self.column].saturating_sub(1)
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                let last = self.columns[self.column].len().saturating_sub(1);
+                self.rows[self.column] = (self.rows[self.column] + 1).min(last);
+            }
+            KeyCode::Enter | KeyCode::Char('e') => {
+                if let Some(ticket) = self.selected() {
+                    let input = ticket.title.0.clone();
+                    self.mode = Mode::EditTitle {
+                        ticket: ticket.id,
+                        cursor: input.chars().count(),
+                        input,
+                    };
+                }
+            }
+            KeyCode::Char('r') => return Some(Action::ReloadAll),
+            KeyCode::Char('q') | KeyCode::Esc => return Some(Action::Quit),
+            _ => {}
+        }
+
+        None
+    }
+
+    /// 選択しているカードを、隣の列に移動する操作を返す。
+    fn shift(&mut self, offset: isize) -> Option<Action> {
+        let ticket = self.selected()?;
+        let to = self.column.checked_add_signed(offset)?;
+        let status = *COLUMNS.get(to)?;
+
+        Some(Action::Update {
+            ticket: ticket.id,
+            version: ticket.version,
+            change: Change::Status(status),
+        })
+    }
+
+    fn edit_title(
+        &mut self,
+        key: KeyEvent,
+        ticket: TicketId,
+        mut input: String,
+        mut cursor: usize,
+    ) -> Option<Action> {
+        let byte = |input: &str, cursor: usize| {
+            input
+                .char_indices()
+                .nth(cursor)
+                .map_or(input.len(), |(i, _)| i)
+        };
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = Mode::Browse;
+                return None;
+            }
+            KeyCode::Enter => return self.save_title(ticket, input),
+            KeyCode::Char(c) => {
+                input.insert(byte(&input, cursor), c);
+                cursor += 1;
+            }
+            KeyCode::Backspace if cursor > 0 => {
+                cursor -= 1;
+                input.remove(byte(&input, cursor));
+            }
+            KeyCode::Delete if cursor < input.chars().count() => {
+                input.remove(byte(&input, cursor));
+            }
+            KeyCode::Left => cursor = cursor.saturating_sub(1),
+            KeyCode::Right => cursor = (cursor + 1).min(input.chars().count()),
+            KeyCode::Home => cursor = 0,
+            KeyCode::End => cursor = input.chars().count(),
+            _ => {}
+        }
+        self.mode = Mode::EditTitle {
+            ticket,
+            input,
+            cursor,
+        };
+
+        None
+    }
+
+    /// 編集したタイトルを保存する操作を返す。
+    ///
+    /// タイトルが不正な場合は、編集を続ける。
+    fn save_title(&mut self, id: TicketId, input: String) -> Option<Action> {
+        let ticket = self.find(id)?;
+        if input == ticket.title.0 {
+            self.mode = Mode::Browse;
+            return None;
+        }
+        match TicketTitle::try_from(input) {
+            Ok(title) => {
+                let version = ticket.version;
+                self.mode = Mode::Browse;
+                Some(Action::Update {
+                    ticket: id,
+                    version,
+                    change: Change::Title(title),
+                })
+            }
+            Err(e) => {
+                self.fail(e.to_string());
+                None
+            }
+        }
+    }
+}
+
+/// チケットステータスの列の位置を返す。
+fn column_of(status: TicketStatus) -> usize {
+    COLUMNS
+        .iter()
+        .position(|s| *s == status)
+        .expect("すべてのチケットステータスに列がある")
+}
+
+/// キー入力の処理結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Quit,
+}
+
+/// サーバーに接続したカンバンボード
+#[derive(Debug)]
+pub struct BoardApp {
+    client: Client,
+    board: Board,
+    /// 変更フィード、切断している場合は`None`
+    feed: Option<EventStream>,
+}
+
+impl BoardApp {
+    /// 変更フィードを購読して、プロジェクトのチケットを読み込む。
+    ///
+    /// 購読を開始してからチケットを読み込むため、その間の変更も変更フィードから受信する。
+    ///
+    /// # 引数
+    ///
+    /// * `client` - クライアント
+    /// * `project` - 表示するプロジェクト
+    ///
+    /// # 戻り値
+    ///
+    /// カンバンボード
+    pub async fn connect(client: Client, project: ProjectKey) -> Result<Self, ClientError> {
+        let filter = EventFilter {
+            project: Some(project.clone()),
+        };
+        let feed = client.events(&filter).await?;
+        let tickets = client
+            .list_tickets(&project, &TicketQuery::default())
+            .await?;
+        let mut board = Board::new(project);
+        board.load(tickets);
+
+        Ok(Self {
+            client,
+            board,
+            feed: Some(feed),
+        })
+    }
+
+    /// 現在の操作モードを返す。
+    pub fn mode(&self) -> &Mode {
+        &self.board.mode
+    }
+
+    /// 選択しているカードのチケットを返す。
+    pub fn selected(&self) -> Option<&Ticket> {
+        self.board.selected()
+    }
+
+    /// 列に並んでいるチケットを返す。
+    pub fn column(&self, status: TicketStatus) -> &[Ticket] {
+        &self.board.columns[column_of(status)]
+    }
+
+    /// カンバンボードを描画する。
+    pub fn draw<B: Backend>(&self, terminal: &mut Terminal<B>) -> io::Result<()> {
+        terminal.draw(|frame| view::render(frame, &self.board))?;
+        Ok(())
+    }
+
+    /// キー入力を処理して、必要であればサーバーのチケットを更新する。
+    ///
+    /// サーバーとの通信に失敗した場合は、エラーを最下行に表示して操作を続ける。
+    ///
+    /// # 引数
+    ///
+    /// * `key` - キー入力
+    ///
+    /// # 戻り値
+    ///
+    /// カンバンボードを終了する場合は[`Flow::Quit`]
+    pub async fn handle_key(&mut self, key: KeyEvent) -> Flow {
+        let Some(action) = self.board.handle_key(key) else {
+            return Flow::Continue;
+        };
+        match action {
+            Action::Update {
+                ticket,
+                version,
+                change,
+            } => self.update(ticket, version, change).await,
+            Action::Overwrite(conflict) => self.overwrite(conflict).await,
+            Action::Reload(ticket) => self.reload(ticket).await,
+            Action::ReloadAll => {
+                if self.reload_all().await {
+                    self.board.inform("チケットを読み込み直しました。");
+                }
+            }
+            Action::Quit => return Flow::Quit,
+        }
+
+        Flow::Continue
+    }
+
+    /// 変更フィードから次のイベントを受信して反映する。
+    ///
+    /// 切断している場合は、待ち時間の後に再接続してすべてのチケットを読み込み直す。
+    /// キャンセルしても状態は壊れないため、`tokio::select!`でキー入力と同時に待つことができる。
+    pub async fn next_change(&mut self) {
+        let Some(feed) = &mut self.feed else {
+            tokio::time::sleep(RECONNECT_DELAY).await;
+            return self.reconnect().await;
+        };
+        match feed.next().await {
+            Some(Ok(FeedEvent::Ticket(event))) => self.board.apply(&event),
+            Some(Ok(FeedEvent::Lagged(_))) => {
+                self.reload_all().await;
+            }
+            Some(Err(e)) => {
+                self.feed = None;
+                self.board
+                    .fail(format!("変更フィードから切断されました: {e}"));
+            }
+            None => {
+                self.feed = None;
+                self.board.fail("変更フィードから切断されました。");
+            }
+        }
+    }
+
+    /// キー入力と変更フィードを処理しながら、終了するまでカンバンボードを描画し続ける。
+    ///
+    /// # 引数
+    ///
+    /// * `terminal` - 描画先の端末
+    /// * `input` - 端末のイベントのストリーム
+    pub async fn run<B, I>(mut self, terminal: &mut Terminal<B>, mut input: I) -> io::Result<()>
+    where
+        B: Backend,
+        I: Stream<Item = io::Result<Event>> + Unpin,
+    {
+        loop {
+            self.draw(terminal)?;
+            tokio::select! {
+                event = input.next() => match event.transpose()? {
+                    Some(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                        if self.handle_key(key).await == Flow::Quit {
+                            return Ok(());
+                        }
+                    }
+                    Some(_) => {}
+                    None => return Ok(()),
+                },
+                _ = self.next_change() => {}
+            }
+        }
+    }
+
+    async fn update(&mut self, id: TicketId, version: u64, change: Change) {
+        let project = self.board.project.clone();
+        let ticket = TicketRef::Id(id);
+        match self
+            .client
+            .update_ticket(&project, &ticket, &change.patch(version))
+            .await
+        {
+            Ok(()) => {
+                if self.refresh(id).await {
+                    self.board.focus(id);
+                    let key = self.board.find(id).map(|t| t.key.to_string());
+                    self.board.inform(format!(
+                        "{}を更新しました（{}）。",
+                        key.unwrap_or_default(),
+                        change.describe()
+                    ));
+                }
+            }
+            Err(e) if e.is_conflict() => {
+                self.board.mode = Mode::Conflict(Conflict { ticket: id, change });
+            }
+            Err(e) => self.board.fail(e.to_string()),
+        }
+    }
+
+    /// 競合した変更を、最新のチケットに適用し直す。
+    async fn overwrite(&mut self, conflict: Conflict) {
+        let project = self.board.project.clone();
+        let ticket = TicketRef::Id(conflict.ticket);
+        self.board.mode = Mode::Browse;
+        match self
+            .client
+            .update_ticket_with(&project, &ticket, |current| {
+                conflict.change.patch(current.version)
+            })
+            .await
+        {
+            Ok(updated) => {
+                let key = updated.key.to_string();
+                self.board.upsert(updated);
+                self.board.focus(conflict.ticket);
+                self.board.inform(format!(
+                    "{key}を上書きしました（{}）。",
+                    conflict.change.describe()
+                ));
+            }
+            Err(e) => self.board.fail(e.to_string()),
+        }
+    }
+
+    /// 競合した変更を破棄して、最新のチケットを読み込む。
+    async fn reload(&mut self, id: TicketId) {
+        self.board.mode = Mode::Browse;
+        if self.refresh(id).await {
+            self.board
+                .inform("最新のチケットを読み込みました。変更は破棄しました。");
+        }
+    }
+
+    /// チケットを取得してカードを置き換える。
+    ///
+    /// # 戻り値
+    ///
+    /// チケットを取得できた場合は`true`
+    async fn refresh(&mut self, id: TicketId) -> bool {
+        let project = self.board.project.clone();
+        match self.client.get_ticket(&project, &TicketRef::Id(id)).await {
+            Ok(ticket) => {
+                self.board.upsert(ticket);
+                true
+            }
+            Err(ClientError::Store(TicketStoreError::NotFound)) => {
+                self.board.remove(id);
+                self.board
+                    .fail("チケットは他のプロジェクトに移動しました。");
+                false
+            }
+            Err(e) => {
+                self.board.fail(e.to_string());
+                false
+            }
+        }
+    }
+
+    /// すべてのチケットを読み込み直す。
+    ///
+    /// # 戻り値
+    ///
+    /// チケットを読み込めた場合は`true`
+    async fn reload_all(&mut self) -> bool {
+        let project = self.board.project.clone();
+        match self
+            .client
+            .list_tickets(&project, &TicketQuery::default())
+            .await
+        {
+            Ok(tickets) => {
+                self.board.load(tickets);
+                true
+            }
+            Err(e) => {
+                self.board.fail(e.to_string());
+                false
+            }
+        }
+    }
+
+    async fn reconnect(&mut self) {
+        let filter = EventFilter {
+            project: Some(self.board.project.clone()),
+        };
+        match self.client.events(&filter).await {
+            Ok(feed) => {
+                // 切断していた間の変更を反映してから、変更フィードの受信を再開する。
+                if self.reload_all().await {
+                    self.feed = Some(feed);
+                    self.board.inform("変更フィードに再接続しました。");
+                }
+            }
+            Err(e) => self
+                .board
+                .fail(format!("変更フィードに再接続できません: {e}")),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+use unicode_width::UnicodeWidthStr;
+
+use super::{Board, Mode, COLUMNS};
+
+/// キー操作の説明
+const HELP: &str = "←→ 列  ↑↓ カード  Shift+←→ 移動  Enter タイトルを編集  r 読み込み直す  q 終了";
+
+/// 競合したカードの印
+const CONFLICT_MARK: &str = "! ";
+
+/// カンバンボードを描画する。
+///
+/// 先頭行にプロジェクト、最下行に操作の説明またはメッセージを表示し、その間にチケットステータスごとの列を並べる。
+pub(super) fn render(frame: &mut Frame, board: &Board) {
+    let [header, body, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(
+        Line::from(vec![
+            Span::from(board.project.to_string()).bold(),
+            Span::from(" カンバンボード"),
+        ]),
+        header,
+    );
+    let columns =
+        Layout::horizontal([Constraint::Ratio(1, COLUMNS.len() as u32); COLUMNS.len()]).split(body);
+    for (i, area) in columns.iter().enumerate() {
+        render_column(frame, board, i, *area);
+    }
+    frame.render_widget(footer_line(board), footer);
+}
+
+/// チケットステータスの列を描画する。
+fn render_column(frame: &mut Frame, board: &Board, i: usize, area: Rect) {
+    let focused = board.column == i;
+    let tickets = &board.columns[i];
+    let title = format!(" {:?} ({}) ", COLUMNS[i], tickets.len());
+    let border = match focused {
+        true => Style::new().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+        false => Style::new(),
+    };
+    let block = Block::bordered().title(title).border_style(border);
+    let inner = block.inner(area);
+
+    let items: Vec<ListItem> = tickets
+        .iter()
+        .map(|ticket| {
+            let conflicted =
+                matches!(&board.mode, Mode::Conflict(conflict) if conflict.ticket == ticket.id);
+            let title = match &board.mode {
+                Mode::EditTitle {
+                    ticket: editing,
+                    input,
+                    ..
+                } if *editing == ticket.id => Span::from(input.as_str()).underlined(),
+                _ => Span::from(ticket.title.0.as_str()),
+            };
+            let mut spans = vec![];
+            if conflicted {
+                spans.push(Span::from(CONFLICT_MARK).red().bold());
+            }
+            spans.push(Span::from(format!("{} ", ticket.key)).dim());
+            spans.push(title);
+            let item = ListItem::new(Line::from(spans));
+            match conflicted {
+                true => item.red(),
+                false => item,
+            }
+        })
+        .collect();
+    let mut state = ListState::default();
+    if focused && !tickets.is_empty() {
+        state.select(Some(board.rows[i]));
+    }
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, area, &mut state);
+
+    if let (
+        true,
+        Mode::EditTitle {
+            input,
+            cursor,
+            ticket,
+        },
+    ) = (focused, &board.mode)
+    {
+        let Some(ticket) = tickets.iter().find(|t| t.id == *ticket) else {
+            return;
+        };
+        let row = board.rows[i].saturating_sub(state.offset()) as u16;
+        let before: String = input.chars().take(*cursor).collect();
+        let x = inner.x + (ticket.key.to_string().width() + 1 + before.width()) as u16;
+        if row < inner.height && x < inner.right() {
+            frame.set_cursor_position(Position::new(x, inner.y + row));
+        }
+    }
+}
+
+/// 最下行に表示する、操作の説明またはメッセージを返す。
+fn footer_line(board: &Board) -> Paragraph<'static> {
+    let line = match (&board.mode, &board.message) {
+        (Mode::Conflict(conflict), _) => {
+            let key = board
+                .find(conflict.ticket)
+                .map(|ticket| ticket.key.to_string())
+                .unwrap_or_default();
+            Line::from(format!(
+                "{key}は他の利用者が更新しました（{}）。 r 読み込み直す  o 上書き",
+                conflict.change.describe()
+            ))
+            .yellow()
+            .bold()
+        }
+        (_, Some(message)) if message.error => Line::from(message.text.clone()).red(),
+        (_, Some(message)) => Line::from(message.text.clone()).green(),
+        (Mode::EditTitle { .. }, None) => Line::from("Enter 保存  Esc 取り消す").dim(),
+        (Mode::Browse, None) => Line::from(HELP).dim(),
+    };
+
+    Paragraph::new(line)
+}
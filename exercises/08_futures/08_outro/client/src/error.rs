@@ -0,0 +1,113 @@
+use hyper::StatusCode;
+use ticket_store::models::TicketStatus;
+use ticket_store::store::TicketStoreError;
+
+/// クライアントエラー
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("ベースURLは`http://localhost:3000`のような`http`スキームのURLです: {0}")]
+    InvalidBaseUrl(String),
+    /// サーバーのチケットストアで発生したエラー
+    ///
+    /// チケットストアの永続化エラーは、[`ClientError::Server`]になる。
+    #[error(transparent)]
+    Store(TicketStoreError),
+    #[error("リクエストを作成できません: {0}")]
+    Encode(String),
+    #[error("リクエストが不正です: {0}")]
+    BadRequest(String),
+    #[error("利用者を指定してください: {0}")]
+    Unauthorized(String),
+    #[error("チケットストアの準備ができていません。")]
+    Unavailable,
+    #[error("サーバーでエラーが発生しました（{status}）: {message}")]
+    Server { status: StatusCode, message: String },
+    #[error("予期しないステータスコード{status}を受信しました: {message}")]
+    UnexpectedStatus { status: StatusCode, message: String },
+    #[error("リダイレクトが多すぎます。")]
+    TooManyRedirects,
+    #[error("リクエストがタイムアウトしました。")]
+    Timeout,
+    #[error("サーバーと通信できません: {0}")]
+    Transport(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("レスポンスボディを解釈できません: {0}")]
+    InvalidResponse(#[source] serde_json::Error),
+}
+
+impl ClientError {
+    /// エラーがチケットのバージョンの不一致か確認する。
+    pub fn is_conflict(&self) -> bool {
+        matches!(self, Self::Store(TicketStoreError::VersionNotMatch))
+    }
+}
+
+/// クライアント結果
+pub type ClientResult<T> = Result<T, ClientError>;
+
+/// サーバーが返すエラーのレスポンスボディ
+#[derive(Debug, Default, serde::Deserialize)]
+pub(crate) struct ErrorBody {
+    #[serde(default)]
+    error: String,
+    /// チケットストアのエラーの種類
+    code: Option<String>,
+    from: Option<TicketStatus>,
+    to: Option<TicketStatus>,
+}
+
+/// エラーのレスポンスをクライアントエラーに変換する。
+///
+/// # 引数
+///
+/// * `status` - ステータスコード
+/// * `body` - レスポンスボディ
+///
+/// # 戻り値
+///
+/// クライアントエラー
+pub(crate) fn from_response(status: StatusCode, body: &[u8]) -> ClientError {
+    let body: ErrorBody = serde_json::from_slice(body).unwrap_or_default();
+    if let Some(e) = body
+        .code
+        .as_deref()
+        .and_then(|code| store_error(code, &body))
+    {
+        return ClientError::Store(e);
+    }
+
+    let message = body.error;
+    match status {
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => {
+            ClientError::BadRequest(message)
+        }
+        StatusCode::UNAUTHORIZED => ClientError::Unauthorized(message),
+        StatusCode::SERVICE_UNAVAILABLE => ClientError::Unavailable,
+        status if status.is_server_error() => ClientError::Server { status, message },
+        status => ClientError::UnexpectedStatus { status, message },
+    }
+}
+
+/// エラーコードから、チケットストアエラーを復元する。
+///
+/// 永続化エラーは原因を復元できないため、`None`を返す。
+fn store_error(code: &str, body: &ErrorBody) -> Option<TicketStoreError> {
+    let e = match code {
+        "notFound" => TicketStoreError::NotFound,
+        "versionNotMatch" => TicketStoreError::VersionNotMatch,
+        "transitionNotAllowed" => TicketStoreError::TransitionNotAllowed {
+            from: body.from?,
+            to: body.to?,
+        },
+        "openDependencies" => TicketStoreError::OpenDependencies,
+        "doneDependents" => TicketStoreError::DoneDependents,
+        "dependencyCycle" => TicketStoreError::DependencyCycle,
+        "ticketLinked" => TicketStoreError::TicketLinked,
+        "projectNotFound" => TicketStoreError::ProjectNotFound,
+        "projectKeyConflict" => TicketStoreError::ProjectKeyConflict,
+        "projectNotEmpty" => TicketStoreError::ProjectNotEmpty,
+        "forbidden" => TicketStoreError::Forbidden,
+        _ => return None,
+    };
+
+    Some(e)
+}
@@ -0,0 +1,107 @@
+use http_body_util::BodyExt;
+use hyper::body::Incoming;
+use ticket_store::events::TicketEvent;
+
+use crate::{ClientError, ClientResult};
+
+/// 変更フィードで受信したイベント
+#[derive(Debug, Clone)]
+pub enum FeedEvent {
+    /// チケットが登録、更新または移動された。
+    Ticket(Box<TicketEvent>),
+    /// 受信が遅れたため、サーバーが指定した数のチケットイベントを破棄した。
+    ///
+    /// 受信側は、チケットを取得し直す必要がある。
+    Lagged(u64),
+}
+
+/// サーバーの変更フィードから、Server-Sent Eventsを順に受信するストリーム
+///
+/// [`EventStream::next`]はキャンセルしても受信済みのデータを失わないため、`tokio::select!`で使用できる。
+#[derive(Debug)]
+pub struct EventStream {
+    body: Incoming,
+    /// 行に分割していない受信済みのデータ
+    buffer: Vec<u8>,
+    /// 受信中のイベントの種類
+    event: Option<String>,
+    /// 受信中のイベントのデータ
+    data: Vec<String>,
+}
+
+impl EventStream {
+    pub(crate) fn new(body: Incoming) -> Self {
+        Self {
+            body,
+            buffer: Vec::new(),
+            event: None,
+            data: Vec::new(),
+        }
+    }
+
+    /// 次のイベントを受信する。
+    ///
+    /// # 戻り値
+    ///
+    /// イベント、サーバーが配信を終了した場合は`None`
+    pub async fn next(&mut self) -> Option<ClientResult<FeedEvent>> {
+        loop {
+            while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                if let Some(event) = self.process_line(line.trim_end_matches(['\n', '\r'])) {
+                    return Some(event);
+                }
+            }
+
+            match self.body.frame().await? {
+                Ok(frame) => {
+                    if let Ok(data) = frame.into_data() {
+                        self.buffer.extend_from_slice(&data);
+                    }
+                }
+                Err(e) => return Some(Err(ClientError::Transport(e.into()))),
+            }
+        }
+    }
+
+    /// 1行を処理して、空行でイベントが完成した場合はイベントを返す。
+    ///
+    /// `:`で始まるコメント行は、サーバーが接続を維持するために送信するため無視する。
+    fn process_line(&mut self, line: &str) -> Option<ClientResult<FeedEvent>> {
+        if line.is_empty() {
+            let event = self.event.take();
+            let data = std::mem::take(&mut self.data).join("\n");
+            return match event.as_deref() {
+                Some("lagged") => Some(lagged(&data)),
+                Some(_) if !data.is_empty() => Some(
+                    serde_json::from_str(&data)
+                        .map(|event| FeedEvent::Ticket(Box::new(event)))
+                        .map_err(ClientError::InvalidResponse),
+                ),
+                _ => None,
+            };
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            _ => {}
+        }
+
+        None
+    }
+}
+
+fn lagged(data: &str) -> ClientResult<FeedEvent> {
+    #[derive(serde::Deserialize)]
+    struct Lagged {
+        skipped: u64,
+    }
+
+    serde_json::from_str::<Lagged>(data)
+        .map(|lagged| FeedEvent::Lagged(lagged.skipped))
+        .map_err(ClientError::InvalidResponse)
+}
@@ -0,0 +1,551 @@
+//! チケット管理システムREST APIの非同期クライアント
+//!
+//! リクエストとレスポンスには、サーバーと同じ[`TicketDraft`]、[`TicketPatch`]、[`Ticket`]を使用する。
+//! エラーのレスポンスは、サーバーのチケットストアエラーを復元した[`ClientError::Store`]などに変換する。
+//!
+//! ```no_run
+//! # async fn example() -> ticket_store_client::ClientResult<()> {
+//! use ticket_store::dto::TicketPatch;
+//! use ticket_store::models::{ProjectKey, TicketRef, TicketStatus};
+//! use ticket_store_client::Client;
+//!
+//! let client = Client::builder("http://localhost:3000").user("alice").build()?;
+//! let project = ProjectKey::try_from("TICKET").unwrap();
+//! let ticket = TicketRef::try_from("TICKET-1").unwrap();
+//! // 他の利用者と更新が競合した場合は、最新のチケットを取得し直してパッチを作り直す。
+//! let ticket = client
+//!     .update_ticket_with(&project, &ticket, |current| TicketPatch {
+//!         title: None,
+//!         description: None,
+//!         status: Some(TicketStatus::Done),
+//!         priority: None,
+//!         due_date: None,
+//!         version: current.version,
+//!     })
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod error;
+mod events;
+
+pub mod board;
+
+use std::fmt;
+use std::time::Duration;
+
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::http::uri::{Authority, Scheme};
+use hyper::{header, Method, Request, StatusCode, Uri};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::{TokioExecutor, TokioTimer};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use ticket_store::dto::{EventFilter, TicketDraft, TicketMove, TicketPatch, TicketQuery};
+use ticket_store::models::{ProjectKey, Ticket, TicketKey, TicketRef};
+
+pub use error::{ClientError, ClientResult};
+pub use events::{EventStream, FeedEvent};
+
+/// 利用者を指定するヘッダ
+const USER_HEADER: &str = "x-user";
+
+/// 1回のリクエストで追跡するリダイレクトの最大回数
+const MAX_REDIRECTS: usize = 5;
+
+/// ベアラートークン
+///
+/// デバッグ出力に値を含めない。
+#[derive(Clone)]
+struct Token(String);
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Token(..)")
+    }
+}
+
+/// クライアントのビルダー
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    base_url: String,
+    user: Option<String>,
+    token: Option<Token>,
+    timeout: Duration,
+    connect_timeout: Duration,
+    pool_idle_timeout: Duration,
+    pool_max_idle_per_host: usize,
+    max_conflict_retries: usize,
+}
+
+impl ClientBuilder {
+    /// リクエストに`X-User`ヘッダで付与する利用者を設定する。
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// リクエストに`Authorization`ヘッダで付与するベアラートークンを設定する。
+    ///
+    /// サーバーはトークンを検証しないため、認証するリバースプロキシを経由する場合に使用する。
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(Token(token.into()));
+        self
+    }
+
+    /// リダイレクトの追跡とレスポンスボディの受信を含めた、1回のリクエストのタイムアウトを設定する。
+    ///
+    /// 既定値は30秒である。
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 接続を確立するまでのタイムアウトを設定する。
+    ///
+    /// 既定値は5秒である。
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// 接続プールで使用されていない接続を閉じるまでの時間を設定する。
+    ///
+    /// 既定値は90秒である。
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = timeout;
+        self
+    }
+
+    /// 接続プールに保持する、ホストごとの使用されていない接続の最大数を設定する。
+    ///
+    /// 既定値は8である。
+    pub fn pool_max_idle_per_host(mut self, max_idle: usize) -> Self {
+        self.pool_max_idle_per_host = max_idle;
+        self
+    }
+
+    /// [`Client::update_ticket_with`]が、バージョンの不一致で再試行する最大回数を設定する。
+    ///
+    /// 既定値は3回である。
+    pub fn max_conflict_retries(mut self, retries: usize) -> Self {
+        self.max_conflict_retries = retries;
+        self
+    }
+
+    /// クライアントを構築する。
+    ///
+    /// # 戻り値
+    ///
+    /// クライアント
+    pub fn build(self) -> ClientResult<Client> {
+        let uri: Uri = self
+            .base_url
+            .parse()
+            .map_err(|_| ClientError::InvalidBaseUrl(self.base_url.clone()))?;
+        let (Some(scheme), Some(authority)) = (uri.scheme(), uri.authority()) else {
+            return Err(ClientError::InvalidBaseUrl(self.base_url));
+        };
+        if scheme != &Scheme::HTTP || uri.query().is_some() {
+            return Err(ClientError::InvalidBaseUrl(self.base_url));
+        }
+
+        let mut connector = HttpConnector::new();
+        connector.set_connect_timeout(Some(self.connect_timeout));
+        let http = hyper_util::client::legacy::Client::builder(TokioExecutor::new())
+            .pool_timer(TokioTimer::new())
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .build(connector);
+
+        Ok(Client {
+            http,
+            authority: authority.clone(),
+            base_path: uri.path().trim_end_matches('/').into(),
+            user: self.user,
+            token: self.token,
+            timeout: self.timeout,
+            max_conflict_retries: self.max_conflict_retries,
+        })
+    }
+}
+
+/// チケット管理システムREST APIのクライアント
+///
+/// 接続プールを共有するため、複製して複数のタスクから使用できる。
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: hyper_util::client::legacy::Client<HttpConnector, Full<Bytes>>,
+    authority: Authority,
+    /// ベースURLのパス（末尾の`/`を除く）
+    base_path: String,
+    user: Option<String>,
+    token: Option<Token>,
+    timeout: Duration,
+    max_conflict_retries: usize,
+}
+
+impl Client {
+    /// 既定の設定でクライアントを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `base_url` - `http://localhost:3000`のようなサーバーのベースURL
+    ///
+    /// # 戻り値
+    ///
+    /// クライアント
+    pub fn new(base_url: impl Into<String>) -> ClientResult<Self> {
+        Self::builder(base_url).build()
+    }
+
+    /// クライアントのビルダーを返す。
+    ///
+    /// # 引数
+    ///
+    /// * `base_url` - `http://localhost:3000`のようなサーバーのベースURL
+    ///
+    /// # 戻り値
+    ///
+    /// クライアントのビルダー
+    pub fn builder(base_url: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            base_url: base_url.into(),
+            user: None,
+            token: None,
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(5),
+            pool_idle_timeout: Duration::from_secs(90),
+            pool_max_idle_per_host: 8,
+            max_conflict_retries: 3,
+        }
+    }
+
+    /// チケットをプロジェクトに登録する。
+    ///
+    /// # 引数
+    ///
+    /// * `project` - プロジェクトキー
+    /// * `draft` - チケットのドラフト
+    ///
+    /// # 戻り値
+    ///
+    /// 登録したチケットのチケットキー
+    pub async fn create_ticket(
+        &self,
+        project: &ProjectKey,
+        draft: &TicketDraft,
+    ) -> ClientResult<TicketKey> {
+        #[derive(serde::Deserialize)]
+        struct Created {
+            key: TicketKey,
+        }
+
+        let path = format!("/projects/{project}/tickets");
+        let created: Created = self.send_json(Method::POST, &path, Some(draft)).await?;
+
+        Ok(created.key)
+    }
+
+    /// プロジェクトのチケットを取得する。
+    ///
+    /// 変更前のチケットキーで指定した場合は、変更後のチケットを取得する。
+    ///
+    /// # 引数
+    ///
+    /// * `project` - プロジェクトキー
+    /// * `ticket` - チケットIDまたはチケットキー
+    ///
+    /// # 戻り値
+    ///
+    /// チケット
+    pub async fn get_ticket(
+        &self,
+        project: &ProjectKey,
+        ticket: &TicketRef,
+    ) -> ClientResult<Ticket> {
+        let path = format!("/projects/{project}/tickets/{ticket}");
+        self.send_json(Method::GET, &path, None::<&()>).await
+    }
+
+    /// プロジェクトの、条件に一致するチケットの一覧を取得する。
+    ///
+    /// # 引数
+    ///
+    /// * `project` - プロジェクトキー
+    /// * `query` - チケット一覧の絞り込みと並び順
+    ///
+    /// # 戻り値
+    ///
+    /// チケットの一覧
+    pub async fn list_tickets(
+        &self,
+        project: &ProjectKey,
+        query: &TicketQuery,
+    ) -> ClientResult<Vec<Ticket>> {
+        let query =
+            serde_urlencoded::to_string(query).map_err(|e| ClientError::Encode(e.to_string()))?;
+        let path = format!("/projects/{project}/tickets?{query}");
+        self.send_json(Method::GET, &path, None::<&()>).await
+    }
+
+    /// クエリの条件を満たすチケットを、読み込みを許可されたすべてのプロジェクトから取得する。
+    ///
+    /// # 引数
+    ///
+    /// * `query` - `status:InProgress AND priority>=High`のようなクエリ
+    ///
+    /// # 戻り値
+    ///
+    /// チケットキー順のチケット
+    pub async fn query_tickets(&self, query: &str) -> ClientResult<Vec<Ticket>> {
+        let query = serde_urlencoded::to_string([("q", query)])
+            .map_err(|e| ClientError::Encode(e.to_string()))?;
+        self.send_json(Method::GET, &format!("/tickets?{query}"), None::<&()>)
+            .await
+    }
+
+    /// プロジェクトのチケットを更新する。
+    ///
+    /// パッチのバージョンがチケットのバージョンと一致しない場合は、
+    /// [`TicketStoreError::VersionNotMatch`](ticket_store::store::TicketStoreError::VersionNotMatch)を返す。
+    ///
+    /// # 引数
+    ///
+    /// * `project` - プロジェクトキー
+    /// * `ticket` - チケットIDまたはチケットキー
+    /// * `patch` - チケットのパッチ
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub async fn update_ticket(
+        &self,
+        project: &ProjectKey,
+        ticket: &TicketRef,
+        patch: &TicketPatch,
+    ) -> ClientResult<()> {
+        let path = format!("/projects/{project}/tickets/{ticket}");
+        self.send(Method::PATCH, &path, Some(patch)).await?;
+
+        Ok(())
+    }
+
+    /// 最新のチケットから作成したパッチで、プロジェクトのチケットを更新する。
+    ///
+    /// 他の更新と競合してバージョンが一致しなかった場合は、チケットを取得し直して`merge`を再度呼び出す。
+    /// パッチのバージョンは、`merge`に渡したチケットのバージョンで上書きする。
+    ///
+    /// # 引数
+    ///
+    /// * `project` - プロジェクトキー
+    /// * `ticket` - チケットIDまたはチケットキー
+    /// * `merge` - 最新のチケットから、適用するパッチを作成するクロージャー
+    ///
+    /// # 戻り値
+    ///
+    /// 更新後のチケット
+    pub async fn update_ticket_with<F>(
+        &self,
+        project: &ProjectKey,
+        ticket: &TicketRef,
+        mut merge: F,
+    ) -> ClientResult<Ticket>
+    where
+        F: FnMut(&Ticket) -> TicketPatch,
+    {
+        let mut retries = 0;
+        loop {
+            let current = self.get_ticket(project, ticket).await?;
+            let patch = TicketPatch {
+                version: current.version,
+                ..merge(&current)
+            };
+            match self.update_ticket(project, ticket, &patch).await {
+                Ok(()) => return self.get_ticket(project, ticket).await,
+                Err(e) if e.is_conflict() && retries < self.max_conflict_retries => {
+                    retries += 1;
+                    tracing::debug!(key = %current.key, retries, "更新が競合したため再試行します。");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// チケットを他のプロジェクトに移動する。
+    ///
+    /// # 引数
+    ///
+    /// * `project` - 移動前のプロジェクトキー
+    /// * `ticket` - チケットIDまたはチケットキー
+    /// * `to` - 移動先のプロジェクトキー
+    ///
+    /// # 戻り値
+    ///
+    /// 移動後のチケットキー
+    pub async fn move_ticket(
+        &self,
+        project: &ProjectKey,
+        ticket: &TicketRef,
+        to: &ProjectKey,
+    ) -> ClientResult<TicketKey> {
+        #[derive(serde::Deserialize)]
+        struct Moved {
+            key: TicketKey,
+        }
+
+        let path = format!("/projects/{project}/tickets/{ticket}/move");
+        let payload = TicketMove {
+            project: to.clone(),
+        };
+        let moved: Moved = self.send_json(Method::POST, &path, Some(&payload)).await?;
+
+        Ok(moved.key)
+    }
+
+    /// チケットの変更フィードを購読する。
+    ///
+    /// タイムアウトは、サーバーが配信を開始するまでの時間にだけ適用する。
+    ///
+    /// # 引数
+    ///
+    /// * `filter` - 変更フィードの絞り込み
+    ///
+    /// # 戻り値
+    ///
+    /// 購読を開始した後のイベントを受信するストリーム
+    pub async fn events(&self, filter: &EventFilter) -> ClientResult<EventStream> {
+        let query =
+            serde_urlencoded::to_string(filter).map_err(|e| ClientError::Encode(e.to_string()))?;
+        let path = format!("{}/events?{query}", self.base_path);
+        let request = self.request(Method::GET, &path, Bytes::new())?;
+        let response = tokio::time::timeout(self.timeout, self.http.request(request))
+            .await
+            .map_err(|_| ClientError::Timeout)?
+            .map_err(|e| ClientError::Transport(e.into()))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response
+                .into_body()
+                .collect()
+                .await
+                .map_err(|e| ClientError::Transport(e.into()))?
+                .to_bytes();
+            return Err(error::from_response(status, &body));
+        }
+
+        Ok(EventStream::new(response.into_body()))
+    }
+
+    /// リクエストを送信して、レスポンスボディをデシリアライズする。
+    async fn send_json<B, T>(&self, method: Method, path: &str, body: Option<&B>) -> ClientResult<T>
+    where
+        B: Serialize + ?Sized,
+        T: DeserializeOwned,
+    {
+        let body = self.send(method, path, body).await?;
+        serde_json::from_slice(&body).map_err(ClientError::InvalidResponse)
+    }
+
+    /// リクエストを送信して、成功した場合はレスポンスボディを返す。
+    ///
+    /// チケットキーやプロジェクトキーの変更によるリダイレクトは、同じメソッドとボディで追跡する。
+    async fn send<B>(&self, method: Method, path: &str, body: Option<&B>) -> ClientResult<Bytes>
+    where
+        B: Serialize + ?Sized,
+    {
+        let body = match body {
+            Some(body) => Bytes::from(
+                serde_json::to_vec(body).map_err(|e| ClientError::Encode(e.to_string()))?,
+            ),
+            None => Bytes::new(),
+        };
+        let path = format!("{}{path}", self.base_path);
+        let exchange = self.exchange(method, path, body);
+        let (status, body) = tokio::time::timeout(self.timeout, exchange)
+            .await
+            .map_err(|_| ClientError::Timeout)??;
+        if !status.is_success() {
+            return Err(error::from_response(status, &body));
+        }
+
+        Ok(body)
+    }
+
+    /// リダイレクトを追跡しながらリクエストを送信して、ステータスコードとレスポンスボディを返す。
+    async fn exchange(
+        &self,
+        method: Method,
+        mut path: String,
+        body: Bytes,
+    ) -> ClientResult<(StatusCode, Bytes)> {
+        for _ in 0..=MAX_REDIRECTS {
+            let request = self.request(method.clone(), &path, body.clone())?;
+            let response = self
+                .http
+                .request(request)
+                .await
+                .map_err(|e| ClientError::Transport(e.into()))?;
+            let status = response.status();
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .map(String::from);
+            let content = response
+                .into_body()
+                .collect()
+                .await
+                .map_err(|e| ClientError::Transport(e.into()))?
+                .to_bytes();
+            match (status, location) {
+                (
+                    StatusCode::PERMANENT_REDIRECT | StatusCode::TEMPORARY_REDIRECT,
+                    Some(location),
+                ) => {
+                    // サーバーはパスだけのリダイレクト先を返すが、絶対URLも受け付ける。
+                    path = match location.parse::<Uri>() {
+                        Ok(uri) if uri.authority().is_some() => uri
+                            .path_and_query()
+                            .map_or_else(|| "/".into(), ToString::to_string),
+                        _ => location,
+                    };
+                }
+                _ => return Ok((status, content)),
+            }
+        }
+
+        Err(ClientError::TooManyRedirects)
+    }
+
+    /// 利用者とトークンのヘッダを付与したリクエストを作成する。
+    fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Bytes,
+    ) -> ClientResult<Request<Full<Bytes>>> {
+        let uri = Uri::builder()
+            .scheme(Scheme::HTTP)
+            .authority(self.authority.clone())
+            .path_and_query(path)
+            .build()
+            .map_err(|e| ClientError::Transport(e.into()))?;
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(user) = &self.user {
+            request = request.header(USER_HEADER, user);
+        }
+        if let Some(Token(token)) = &self.token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+
+        request
+            .body(Full::new(body))
+            .map_err(|e| ClientError::Transport(e.into()))
+    }
+}
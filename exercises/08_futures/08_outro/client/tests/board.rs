@@ -0,0 +1,278 @@
+mod common;
+
+use std::time::Duration;
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ratatui::backend::TestBackend;
+use ratatui::Terminal;
+use ticket_store::dto::{TicketDraft, TicketPatch};
+use ticket_store::models::{Priority, TicketDescription, TicketRef, TicketStatus, TicketTitle};
+use ticket_store_client::board::{BoardApp, Flow, Mode};
+use ticket_store_client::Client;
+use unicode_width::UnicodeWidthStr;
+
+use common::{project, spawn_server};
+
+fn draft(title: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from("説明").unwrap(),
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+fn key(code: KeyCode) -> KeyEvent {
+    KeyEvent::new(code, KeyModifiers::NONE)
+}
+
+fn shift(code: KeyCode) -> KeyEvent {
+    KeyEvent::new(code, KeyModifiers::SHIFT)
+}
+
+fn id(n: u64) -> TicketRef {
+    TicketRef::try_from(n.to_string().as_str()).unwrap()
+}
+
+/// 描画したカンバンボードを、行ごとの文字列にする。
+///
+/// 全角文字の2文字目のセルは読み飛ばす。
+fn screen(terminal: &Terminal<TestBackend>) -> Vec<String> {
+    let buffer = terminal.backend().buffer();
+    (0..buffer.area.height)
+        .map(|y| {
+            let mut line = String::new();
+            let mut skip = 0;
+            for x in 0..buffer.area.width {
+                let symbol = buffer[(x, y)].symbol();
+                if skip > 0 {
+                    skip -= 1;
+                    continue;
+                }
+                skip = symbol.width().saturating_sub(1);
+                line.push_str(symbol);
+            }
+            line.trim_end().to_string()
+        })
+        .collect()
+}
+
+fn draw(app: &BoardApp, terminal: &mut Terminal<TestBackend>) -> String {
+    app.draw(terminal).unwrap();
+    screen(terminal).join("\n")
+}
+
+/// 変更フィードから次のイベントを受信して反映する。
+async fn next_change(app: &mut BoardApp) {
+    tokio::time::timeout(Duration::from_secs(5), app.next_change())
+        .await
+        .expect("変更フィードからイベントを受信できません");
+}
+
+async fn connect(client: &Client, key: &str) -> (BoardApp, Terminal<TestBackend>) {
+    let app = BoardApp::connect(client.clone(), project(key))
+        .await
+        .unwrap();
+    let terminal = Terminal::new(TestBackend::new(120, 10)).unwrap();
+    (app, terminal)
+}
+
+#[tokio::test]
+async fn cards_move_between_columns_with_the_shown_version() {
+    let (_, addr) = spawn_server().await;
+    let client = Client::builder(format!("http://{addr}"))
+        .user("alice")
+        .build()
+        .unwrap();
+    let web = project("WEB");
+    client.create_ticket(&web, &draft("羅生門")).await.unwrap();
+    client.create_ticket(&web, &draft("鼻")).await.unwrap();
+
+    let (mut app, mut terminal) = connect(&client, "WEB").await;
+    let screen = draw(&app, &mut terminal);
+    assert!(screen.starts_with("WEB カンバンボード\n"), "{screen}");
+    assert!(screen.contains(" ToDo (2) "), "{screen}");
+    assert!(screen.contains(" InProgress (0) "), "{screen}");
+    assert!(screen.contains("│WEB-1 羅生門"), "{screen}");
+
+    app.handle_key(key(KeyCode::Down)).await;
+    assert_eq!(app.selected().unwrap().key.to_string(), "WEB-2");
+    app.handle_key(shift(KeyCode::Right)).await;
+    let ticket = client.get_ticket(&web, &id(1)).await.unwrap();
+    assert_eq!(ticket.status, TicketStatus::InProgress);
+    assert_eq!(ticket.version, 1);
+    // 移動したカードを選択し続ける。
+    assert_eq!(app.selected().unwrap().key, ticket.key);
+    assert_eq!(app.column(TicketStatus::InProgress).len(), 1);
+    let screen = draw(&app, &mut terminal);
+    assert!(
+        screen.contains("WEB-2を更新しました（InProgressへ移動）。"),
+        "{screen}"
+    );
+
+    // `WEB`プロジェクトのワークフローは、`InProgress`から`Done`への遷移を許可しない。
+    app.handle_key(key(KeyCode::Char('L'))).await;
+    let screen = draw(&app, &mut terminal);
+    assert!(
+        screen.contains(
+            "ワークフローでは、チケットのステータスを`InProgress`から`Done`に変更できません。"
+        ),
+        "{screen}"
+    );
+    assert_eq!(
+        client.get_ticket(&web, &id(1)).await.unwrap().status,
+        TicketStatus::InProgress
+    );
+    assert_eq!(app.column(TicketStatus::Done).len(), 0);
+
+    // `run`は、端末のイベントのストリームからキー入力を受け取る。
+    let keys = [
+        key(KeyCode::Left),
+        shift(KeyCode::Right),
+        key(KeyCode::Char('q')),
+    ];
+    let input = futures_util::stream::iter(keys.map(|key| Ok(Event::Key(key))));
+    app.run(&mut terminal, input).await.unwrap();
+    assert_eq!(
+        client.get_ticket(&web, &id(0)).await.unwrap().status,
+        TicketStatus::InProgress
+    );
+}
+
+#[tokio::test]
+async fn conflicting_title_edits_can_be_overwritten_or_reloaded() {
+    let (_, addr) = spawn_server().await;
+    let client = Client::builder(format!("http://{addr}")).build().unwrap();
+    let other = client.clone();
+    let tickets = project("TICKET");
+    client
+        .create_ticket(&tickets, &draft("羅生門"))
+        .await
+        .unwrap();
+    let (mut app, mut terminal) = connect(&client, "TICKET").await;
+
+    // タイトルを編集する。
+    app.handle_key(key(KeyCode::Enter)).await;
+    assert!(matches!(app.mode(), Mode::EditTitle { .. }));
+    for c in "（改訂）".chars() {
+        app.handle_key(key(KeyCode::Char(c))).await;
+    }
+    app.handle_key(key(KeyCode::Backspace)).await;
+    app.handle_key(key(KeyCode::Char('）'))).await;
+    let screen = draw(&app, &mut terminal);
+    assert!(screen.contains("TICKET-1 羅生門（改訂）"), "{screen}");
+    assert_eq!(app.handle_key(key(KeyCode::Enter)).await, Flow::Continue);
+    let ticket = client.get_ticket(&tickets, &id(0)).await.unwrap();
+    assert_eq!(ticket.title.0, "羅生門（改訂）");
+    assert_eq!(ticket.version, 1);
+
+    // 編集している間に、他の利用者がタイトルを変更する。
+    let rename = |title: &str, version| TicketPatch {
+        title: Some(TicketTitle::try_from(title).unwrap()),
+        description: None,
+        status: None,
+        priority: None,
+        due_date: None,
+        version,
+    };
+    app.handle_key(key(KeyCode::Char('e'))).await;
+    app.handle_key(key(KeyCode::Char('!'))).await;
+    other
+        .update_ticket(&tickets, &id(0), &rename("他の利用者", 1))
+        .await
+        .unwrap();
+    app.handle_key(key(KeyCode::Enter)).await;
+    assert!(matches!(app.mode(), Mode::Conflict(_)));
+    let screen = draw(&app, &mut terminal);
+    assert!(screen.contains("│! TICKET-1 羅生門（改訂）"), "{screen}");
+    assert!(
+        screen.contains(
+            "TICKET-1は他の利用者が更新しました（タイトルを「羅生門（改訂）!」に変更）。 r 読み込み直す  o 上書き"
+        ),
+        "{screen}"
+    );
+    app.handle_key(key(KeyCode::Char('o'))).await;
+    assert!(matches!(app.mode(), Mode::Browse));
+    let ticket = client.get_ticket(&tickets, &id(0)).await.unwrap();
+    assert_eq!(ticket.title.0, "羅生門（改訂）!");
+    assert_eq!(ticket.version, 3);
+
+    // 再び競合した場合は、自分の変更を破棄して読み込み直す。
+    app.handle_key(key(KeyCode::Char('e'))).await;
+    app.handle_key(key(KeyCode::Char('?'))).await;
+    other
+        .update_ticket(&tickets, &id(0), &rename("他の利用者", 3))
+        .await
+        .unwrap();
+    app.handle_key(key(KeyCode::Enter)).await;
+    app.handle_key(key(KeyCode::Char('r'))).await;
+    assert!(matches!(app.mode(), Mode::Browse));
+    assert_eq!(app.selected().unwrap().title.0, "他の利用者");
+    assert_eq!(
+        client.get_ticket(&tickets, &id(0)).await.unwrap().version,
+        4
+    );
+    let screen = draw(&app, &mut terminal);
+    assert!(screen.contains("│TICKET-1 他の利用者"), "{screen}");
+
+    // 空のタイトルは保存せずに、編集を続ける。
+    app.handle_key(key(KeyCode::Enter)).await;
+    for _ in 0.."他の利用者".chars().count() {
+        app.handle_key(key(KeyCode::Backspace)).await;
+    }
+    app.handle_key(key(KeyCode::Enter)).await;
+    assert!(matches!(app.mode(), Mode::EditTitle { .. }));
+    app.handle_key(key(KeyCode::Esc)).await;
+    assert!(matches!(app.mode(), Mode::Browse));
+    assert_eq!(app.handle_key(key(KeyCode::Char('q'))).await, Flow::Quit);
+}
+
+#[tokio::test]
+async fn board_refreshes_from_the_change_feed() {
+    let (_, addr) = spawn_server().await;
+    let client = Client::builder(format!("http://{addr}"))
+        .user("alice")
+        .build()
+        .unwrap();
+    let tickets = project("TICKET");
+    client
+        .create_ticket(&tickets, &draft("羅生門"))
+        .await
+        .unwrap();
+    let (mut app, mut terminal) = connect(&client, "TICKET").await;
+
+    client.create_ticket(&tickets, &draft("鼻")).await.unwrap();
+    next_change(&mut app).await;
+    let screen = draw(&app, &mut terminal);
+    assert!(screen.contains(" ToDo (2) "), "{screen}");
+    assert!(screen.contains("│TICKET-2 鼻"), "{screen}");
+
+    client
+        .update_ticket_with(&tickets, &id(0), |_| TicketPatch {
+            title: None,
+            description: None,
+            status: Some(TicketStatus::Done),
+            priority: None,
+            due_date: None,
+            version: 0,
+        })
+        .await
+        .unwrap();
+    next_change(&mut app).await;
+    assert_eq!(
+        app.column(TicketStatus::Done)[0].key.to_string(),
+        "TICKET-1"
+    );
+    assert_eq!(app.column(TicketStatus::ToDo).len(), 1);
+
+    // 他のプロジェクトに移動したチケットのカードは取り除く。
+    client
+        .move_ticket(&tickets, &id(1), &project("WEB"))
+        .await
+        .unwrap();
+    next_change(&mut app).await;
+    assert!(app.column(TicketStatus::ToDo).is_empty());
+    let screen = draw(&app, &mut terminal);
+    assert!(screen.contains(" ToDo (0) "), "{screen}");
+    assert!(!screen.contains("鼻"), "{screen}");
+}
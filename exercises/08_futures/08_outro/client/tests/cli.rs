@@ -0,0 +1,223 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+use common::spawn_server;
+
+/// `tickets`コマンドの実行環境
+///
+/// 利用者の設定ファイルを読み込まないように、設定ディレクトリを一時ディレクトリにする。
+struct Cli {
+    url: String,
+    config_home: tempfile::TempDir,
+}
+
+impl Cli {
+    async fn start() -> Self {
+        let (_, addr) = spawn_server().await;
+        Self {
+            url: format!("http://{addr}"),
+            config_home: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_tickets"));
+        command
+            .args(args)
+            .env_remove("VISUAL")
+            .env("XDG_CONFIG_HOME", self.config_home.path())
+            .env("TICKETS_URL", &self.url)
+            .env("TICKETS_BIN", env!("CARGO_BIN_EXE_tickets"));
+        command
+    }
+
+    /// コマンドを実行して、終了コード、標準出力、標準エラー出力を返す。
+    async fn run(&self, args: &[&str], editor: Option<&Path>) -> (i32, String, String) {
+        let mut command = self.command(args);
+        if let Some(editor) = editor {
+            command.env("EDITOR", editor);
+        }
+        let output = command.output().await.unwrap();
+        (
+            output.status.code().unwrap(),
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    }
+
+    /// 実行可能なシェルスクリプトを、エディターとして作成する。
+    fn editor(&self, script: &str) -> PathBuf {
+        let path = self.config_home.path().join("editor.sh");
+        std::fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+}
+
+fn json(s: &str) -> serde_json::Value {
+    serde_json::from_str(s).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tickets_are_created_shown_and_listed() {
+    let cli = Cli::start().await;
+    let config_dir = cli.config_home.path().join("tickets");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        "user = \"alice\"\nproject = \"TICKET\"\n",
+    )
+    .unwrap();
+
+    let create = ["create", "--title", "吾輩は猫である", "--description", "猫"];
+    let (code, stdout, stderr) = cli.run(&create, None).await;
+    assert_eq!((code, stdout.as_str()), (0, "TICKET-1\n"), "{stderr}");
+    let create = [
+        "create",
+        "--title",
+        "Rashomon",
+        "--description",
+        "芥川龍之介",
+        "--priority",
+        "urgent",
+        "--due",
+        "2024-07-31",
+    ];
+    assert_eq!(cli.run(&create, None).await.1, "TICKET-2\n");
+
+    let (code, stdout, _) = cli.run(&["list", "--sort", "-priority"], None).await;
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        "KEY       STATUS  PRIORITY  DUE         TITLE\n\
+         TICKET-2  ToDo    Urgent    2024-07-31  Rashomon\n\
+         TICKET-1  ToDo    Medium    -           吾輩は猫である\n"
+    );
+
+    let (_, stdout, _) = cli.run(&["--json", "show", "0"], None).await;
+    let ticket = json(&stdout);
+    assert_eq!(ticket["key"], "TICKET-1");
+    assert_eq!(ticket["title"], "吾輩は猫である");
+    let (_, stdout, _) = cli.run(&["show", "TICKET-2"], None).await;
+    assert!(stdout.starts_with("KEY            TICKET-2\n"), "{stdout}");
+    assert!(stdout.ends_with("\n芥川龍之介\n"), "{stdout}");
+
+    let (_, stdout, _) = cli
+        .run(&["list", "--json", "--query", "priority>=High"], None)
+        .await;
+    let tickets = json(&stdout);
+    assert_eq!(tickets.as_array().unwrap().len(), 1);
+    assert_eq!(tickets[0]["key"], "TICKET-2");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn edit_applies_changed_fields_with_the_read_version() {
+    let cli = Cli::start().await;
+    let create = ["create", "--title", "羅生門", "--description", "芥川龍之介"];
+    cli.run(&create, None).await;
+
+    let editor = cli.editor(
+        r#"sed -i -e 's/^title = .*/title = "羅生門（改訂）"/' -e 's/^priority = .*/priority = "High"/' "$1""#,
+    );
+    let (code, stdout, stderr) = cli.run(&["edit", "TICKET-1"], Some(&editor)).await;
+    assert_eq!(code, 0, "{stderr}");
+    assert_eq!(stdout, "TICKET-1を更新しました（バージョン1）。\n");
+    let ticket = json(&cli.run(&["--json", "show", "0"], None).await.1);
+    assert_eq!(ticket["title"], "羅生門（改訂）");
+    assert_eq!(ticket["priority"], "High");
+    assert_eq!(ticket["description"], "芥川龍之介");
+
+    let editor = cli.editor(r#"sed -i 's/^status: .*/status: Done/' "$1""#);
+    let args = ["--json", "edit", "0", "--format", "yaml"];
+    let (code, stdout, stderr) = cli.run(&args, Some(&editor)).await;
+    assert_eq!(code, 0, "{stderr}");
+    assert_eq!(json(&stdout)["status"], "Done");
+
+    let editor = cli.editor("exit 0");
+    let (code, _, stderr) = cli.run(&["edit", "0"], Some(&editor)).await;
+    assert_eq!(code, 0);
+    assert!(stderr.contains("変更がない"), "{stderr}");
+    let editor = cli.editor(r#"sed -i 's/^title = .*/title = ""/' "$1""#);
+    let (code, _, stderr) = cli.run(&["edit", "0"], Some(&editor)).await;
+    assert_eq!(code, 1);
+    assert!(stderr.contains("タイトルは空にできません"), "{stderr}");
+    assert_eq!(
+        json(&cli.run(&["--json", "show", "0"], None).await.1)["version"],
+        2
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exit_codes_distinguish_not_found_from_conflicts() {
+    let cli = Cli::start().await;
+    let create = ["create", "--title", "羅生門", "--description", "芥川龍之介"];
+    cli.run(&create, None).await;
+
+    assert_eq!(cli.run(&["show", "TICKET-9"], None).await.0, 3);
+    assert_eq!(cli.run(&["move", "NOPE-1", "Done"], None).await.0, 3);
+    assert_eq!(cli.run(&["move", "0", "Doing"], None).await.0, 2);
+    let config = cli.config_home.path().join("broken.toml");
+    std::fs::write(&config, "port = 3000\n").unwrap();
+    let (code, _, stderr) = cli
+        .run(&["--config", config.to_str().unwrap(), "show", "0"], None)
+        .await;
+    assert_eq!(code, 2, "{stderr}");
+
+    // 編集している間に、他の利用者がチケットを更新する。
+    let editor = cli.editor(
+        r#""$TICKETS_BIN" move TICKET-1 Done > /dev/null
+sed -i 's/^title = .*/title = "羅生門（改訂）"/' "$1""#,
+    );
+    let (code, _, stderr) = cli.run(&["edit", "TICKET-1"], Some(&editor)).await;
+    assert_eq!(code, 4, "{stderr}");
+    assert!(stderr.contains("バージョンが一致しません"), "{stderr}");
+    let ticket = json(&cli.run(&["--json", "show", "0"], None).await.1);
+    assert_eq!(ticket["title"], "羅生門");
+    assert_eq!(ticket["status"], "Done");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_reports_changes_as_json_lines() {
+    let cli = Cli::start().await;
+    let create = ["create", "--title", "羅生門", "--description", "芥川龍之介"];
+    cli.run(&create, None).await;
+
+    let mut watch = cli
+        .command(&["--json", "watch", "--interval-ms", "20"])
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap();
+    let mut lines = BufReader::new(watch.stdout.take().unwrap()).lines();
+    // 最初の問い合わせが完了してから変更する。
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let (code, stdout, _) = cli.run(&["move", "0", "InProgress"], None).await;
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "TICKET-1を更新しました（バージョン1）。\n");
+    cli.run(&create, None).await;
+
+    let mut events = vec![];
+    while events.len() < 2 {
+        let line = tokio::time::timeout(Duration::from_secs(5), lines.next_line())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        events.push(json(&line));
+    }
+    events.sort_by_key(|event| event["key"].as_str().unwrap().to_string());
+    assert_eq!(events[0]["event"], "updated");
+    assert_eq!(events[0]["previous"]["status"], "ToDo");
+    assert_eq!(events[0]["ticket"]["status"], "InProgress");
+    assert_eq!(events[1]["event"], "created");
+    assert_eq!(events[1]["key"], "TICKET-2");
+}
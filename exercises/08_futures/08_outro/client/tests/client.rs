@@ -0,0 +1,268 @@
+mod common;
+
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use ticket_store::dto::{TicketDraft, TicketPatch, TicketQuery};
+use ticket_store::models::{
+    Priority, TicketDescription, TicketId, TicketRef, TicketStatus, TicketTitle,
+};
+use ticket_store::registry::ProjectStore;
+use ticket_store::store::TicketStoreError;
+use ticket_store_client::{Client, ClientError};
+use tokio::net::{TcpListener, TcpStream};
+
+use common::{project, spawn_server};
+
+fn draft(title: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from("説明").unwrap(),
+        priority: Priority::High,
+        due_date: NaiveDate::from_ymd_opt(2024, 7, 31),
+    }
+}
+
+fn patch(status: Option<TicketStatus>, priority: Option<Priority>) -> TicketPatch {
+    TicketPatch {
+        title: None,
+        description: None,
+        status,
+        priority,
+        due_date: None,
+        version: 0,
+    }
+}
+
+fn ticket(s: &str) -> TicketRef {
+    TicketRef::try_from(s).unwrap()
+}
+
+/// 既定のプロジェクトのチケットのバージョンを、クライアントを経由せずに進める。
+fn bump_version(tickets: &ProjectStore, id: u64) {
+    let tickets = tickets.read().unwrap();
+    let version = tickets.get(TicketId(id)).unwrap().version;
+    tickets
+        .update_ticket(
+            TicketId(id),
+            TicketPatch {
+                version,
+                ..patch(None, Some(Priority::Urgent))
+            },
+        )
+        .unwrap();
+}
+
+#[tokio::test]
+async fn client_round_trips_tickets() {
+    let (_, addr) = spawn_server().await;
+    let client = Client::new(format!("http://{addr}/")).unwrap();
+    let default = project("TICKET");
+
+    let key = client
+        .create_ticket(&default, &draft("吾輩は猫である"))
+        .await
+        .unwrap();
+    assert_eq!(key.to_string(), "TICKET-1");
+    client
+        .create_ticket(&default, &draft("羅生門"))
+        .await
+        .unwrap();
+    client
+        .update_ticket(
+            &default,
+            &ticket("1"),
+            &patch(Some(TicketStatus::InProgress), None),
+        )
+        .await
+        .unwrap();
+
+    let fetched = client
+        .get_ticket(&default, &ticket("TICKET-1"))
+        .await
+        .unwrap();
+    assert_eq!(fetched.title.0, "吾輩は猫である");
+    assert_eq!(fetched.due_date, NaiveDate::from_ymd_opt(2024, 7, 31));
+
+    let query = TicketQuery {
+        status: Some(TicketStatus::InProgress),
+        ..TicketQuery::default()
+    };
+    let tickets = client.list_tickets(&default, &query).await.unwrap();
+    assert_eq!(tickets.len(), 1);
+    assert_eq!(tickets[0].key.to_string(), "TICKET-2");
+    let tickets = client.query_tickets("title~猫 OR version>0").await.unwrap();
+    assert_eq!(tickets.len(), 2);
+
+    // 移動前のチケットキーは、移動後のチケットにリダイレクトされる。
+    let alice = Client::builder(format!("http://{addr}"))
+        .user("alice")
+        .build()
+        .unwrap();
+    let moved = alice
+        .move_ticket(&default, &ticket("TICKET-1"), &project("WEB"))
+        .await
+        .unwrap();
+    assert_eq!(moved.to_string(), "WEB-1");
+    let fetched = client
+        .get_ticket(&default, &ticket("TICKET-1"))
+        .await
+        .unwrap();
+    assert_eq!(fetched.key, moved);
+}
+
+#[tokio::test]
+async fn error_bodies_become_typed_errors() {
+    let (_, addr) = spawn_server().await;
+    let client = Client::new(format!("http://{addr}")).unwrap();
+    let alice = Client::builder(format!("http://{addr}"))
+        .user("alice")
+        .build()
+        .unwrap();
+    let web = project("WEB");
+
+    assert!(matches!(
+        client.get_ticket(&project("TICKET"), &ticket("0")).await,
+        Err(ClientError::Store(TicketStoreError::NotFound))
+    ));
+    assert!(matches!(
+        client.get_ticket(&project("NOPE"), &ticket("0")).await,
+        Err(ClientError::Store(TicketStoreError::ProjectNotFound))
+    ));
+    assert!(matches!(
+        client.create_ticket(&web, &draft("ログイン")).await,
+        Err(ClientError::Store(TicketStoreError::Forbidden))
+    ));
+
+    alice.create_ticket(&web, &draft("ログイン")).await.unwrap();
+    let e = alice
+        .update_ticket(&web, &ticket("0"), &patch(Some(TicketStatus::Done), None))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            e,
+            ClientError::Store(TicketStoreError::TransitionNotAllowed {
+                from: TicketStatus::ToDo,
+                to: TicketStatus::Done
+            })
+        ),
+        "{e:?}"
+    );
+    let e = alice
+        .update_ticket(
+            &web,
+            &ticket("0"),
+            &TicketPatch {
+                version: 7,
+                ..patch(None, Some(Priority::Low))
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(e.is_conflict(), "{e:?}");
+
+    let e = client.query_tickets("assignee:alice").await.unwrap_err();
+    assert!(
+        matches!(&e, ClientError::BadRequest(message) if message.contains("`assignee`")),
+        "{e:?}"
+    );
+    assert!(matches!(
+        Client::new("https://localhost:3000"),
+        Err(ClientError::InvalidBaseUrl(_))
+    ));
+}
+
+#[tokio::test]
+async fn conflicting_updates_are_merged_and_retried() {
+    let (store, addr) = spawn_server().await;
+    let client = Client::builder(format!("http://{addr}"))
+        .max_conflict_retries(2)
+        .build()
+        .unwrap();
+    let default = project("TICKET");
+    client
+        .create_ticket(&default, &draft("吾輩は猫である"))
+        .await
+        .unwrap();
+
+    // 1回目のパッチを送信する前に、他の利用者が優先度を更新する。
+    let mut calls = 0;
+    let updated = client
+        .update_ticket_with(&default, &ticket("0"), |_| {
+            calls += 1;
+            if calls == 1 {
+                bump_version(&store, 0);
+            }
+            patch(Some(TicketStatus::InProgress), None)
+        })
+        .await
+        .unwrap();
+    assert_eq!(calls, 2);
+    assert_eq!(updated.status, TicketStatus::InProgress);
+    assert_eq!(updated.priority, Priority::Urgent);
+    assert_eq!(updated.version, 2);
+
+    // 再試行しても競合し続ける場合は、バージョンの不一致を返す。
+    let mut calls = 0;
+    let e = client
+        .update_ticket_with(&default, &ticket("0"), |_| {
+            calls += 1;
+            bump_version(&store, 0);
+            patch(Some(TicketStatus::Done), None)
+        })
+        .await
+        .unwrap_err();
+    assert!(e.is_conflict(), "{e:?}");
+    assert_eq!(calls, 3);
+}
+
+#[tokio::test]
+async fn connections_are_pooled_and_requests_time_out() {
+    let (_, addr) = spawn_server().await;
+
+    // 受け付けた接続を数えて、サーバーに中継する。
+    let proxy = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let proxy_addr = proxy.local_addr().unwrap();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = Arc::clone(&connections);
+    tokio::spawn(async move {
+        loop {
+            let (mut inbound, _) = proxy.accept().await.unwrap();
+            accepted.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                let mut outbound = TcpStream::connect(addr).await.unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+            });
+        }
+    });
+    let client = Client::new(format!("http://{proxy_addr}")).unwrap();
+    for _ in 0..5 {
+        client
+            .create_ticket(&project("TICKET"), &draft("羅生門"))
+            .await
+            .unwrap();
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+    // 接続を受け付けるが、応答しないサーバー
+    let silent = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let silent_addr = silent.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut streams = vec![];
+        loop {
+            streams.push(silent.accept().await.unwrap());
+        }
+    });
+    let client = Client::builder(format!("http://{silent_addr}"))
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    assert!(matches!(
+        client.get_ticket(&project("TICKET"), &ticket("0")).await,
+        Err(ClientError::Timeout)
+    ));
+}
@@ -0,0 +1,70 @@
+#![allow(dead_code)]
+
+use std::future::IntoFuture;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+use ticket_store::actor::StoreHandle;
+use ticket_store::blobs::BlobStore;
+use ticket_store::clock::SystemClock;
+use ticket_store::config::Config;
+use ticket_store::crypto::Encryption;
+use ticket_store::dto::ProjectDraft;
+use ticket_store::events::ChangeFeed;
+use ticket_store::health::Health;
+use ticket_store::models::{
+    DescriptionFormat, Permissions, ProjectKey, ProjectName, StatusTransition, TicketStatus,
+    Workflow,
+};
+use ticket_store::registry::{ProjectRegistry, ProjectStore};
+use ticket_store::saved_queries::SavedQueries;
+use ticket_store::server::{app, AppState};
+use ticket_store::webhooks::Webhooks;
+use tokio::net::TcpListener;
+
+pub fn project(key: &str) -> ProjectKey {
+    ProjectKey::try_from(key).unwrap()
+}
+
+/// プロセス内でサーバーを起動して、既定のプロジェクトのチケットストアと待ち受けているアドレスを返す。
+///
+/// チケットストアは、サーバーのアクターを経由せずにチケットを更新するために使用する。
+/// `WEB`プロジェクトは、`ToDo`から`InProgress`への遷移だけを許可し、`alice`だけが書き込める。
+pub async fn spawn_server() -> (ProjectStore, SocketAddr) {
+    let config = Config::default();
+    let feed = ChangeFeed::default();
+    let mut registry = ProjectRegistry::default().with_events(feed.start(None));
+    registry
+        .create(ProjectDraft {
+            key: project("WEB"),
+            name: ProjectName::try_from("ウェブサイト").unwrap(),
+            workflow: Workflow {
+                transitions: vec![StatusTransition {
+                    from: TicketStatus::ToDo,
+                    to: TicketStatus::InProgress,
+                }],
+            },
+            permissions: Permissions {
+                readers: vec![],
+                writers: vec!["alice".into()],
+            },
+            description_format: DescriptionFormat::default(),
+        })
+        .unwrap();
+    let tickets = registry.resolve(&project("TICKET")).unwrap();
+    let state = AppState {
+        store: StoreHandle::spawn(registry, 1024),
+        health: Arc::new(Health::new(&config)),
+        webhooks: Webhooks::in_memory(&config.webhooks, Arc::new(SystemClock)),
+        saved_queries: SavedQueries::in_memory(Arc::new(SystemClock)),
+        feed,
+        blobs: BlobStore::in_memory(&config.attachments),
+        encryption: Encryption::default(),
+        admin: None,
+    };
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(axum::serve(listener, app(state, &config.limits)).into_future());
+
+    (tickets, addr)
+}
@@ -0,0 +1,1862 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::oneshot;
+
+use crate::backup::Backup;
+use crate::clock::{Clock, PinnedClock};
+use crate::dto::{
+    AttachmentDraft, ProjectDraft, ProjectPatch, TicketDraft, TicketPatch, TicketQuery,
+};
+use crate::graph::DependencyGraph;
+use crate::models::{
+    Attachment, AttachmentId, BlobDigest, Label, Project, ProjectKey, Ticket, TicketId, TicketKey,
+    TicketRef,
+};
+use crate::query::Expr;
+use crate::registry::{self, ProjectLookup, ProjectRegistry, ProjectStore};
+use crate::replication::{Operation, ReplicatedOperation, ReplicationLog};
+use crate::search::{SearchHit, SearchText};
+use crate::store::{Access, TicketLookup, TicketStore, TicketStoreError};
+use crate::sync::{lock, read_lock, write_lock};
+
+/// コマンドの結果を返す送信先
+type Responder<T> = oneshot::Sender<StoreResult<T>>;
+
+/// チケットストアのアクターに送信するコマンド
+///
+/// 各コマンドは、結果を返す送信先をリクエストごとに持つ。
+/// 利用者は`X-User`ヘッダで指定された利用者で、識別できない場合は`None`である。
+#[derive(Debug)]
+pub enum Command {
+    /// 条件に一致するチケットの一覧を取得する。
+    ListTickets {
+        project: ProjectKey,
+        user: Option<String>,
+        query: TicketQuery,
+        respond_to: Responder<Vec<Ticket>>,
+    },
+    /// チケットを登録する。
+    CreateTicket {
+        project: ProjectKey,
+        user: Option<String>,
+        draft: TicketDraft,
+        respond_to: Responder<Ticket>,
+    },
+    /// チケットを取得する。
+    GetTicket {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        respond_to: Responder<Ticket>,
+    },
+    /// チケットを更新する。
+    UpdateTicket {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        patch: TicketPatch,
+        respond_to: Responder<()>,
+    },
+    /// チケットを他のプロジェクトに移動する。
+    MoveTicket {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        to: ProjectKey,
+        respond_to: Responder<TicketKey>,
+    },
+    /// チケットの親チケットを設定する。
+    SetParent {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        parent: Option<TicketId>,
+        respond_to: Responder<()>,
+    },
+    /// チケットにブロッカーを追加する。
+    AddBlocker {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        blocker: TicketId,
+        respond_to: Responder<()>,
+    },
+    /// チケットからブロッカーを取り除く。
+    RemoveBlocker {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        blocker: TicketId,
+        respond_to: Responder<()>,
+    },
+    /// チケットに添付ファイルを追加する。
+    AddAttachment {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        draft: AttachmentDraft,
+        respond_to: Responder<Attachment>,
+    },
+    /// チケットから添付ファイルを削除する。
+    RemoveAttachment {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        attachment: AttachmentId,
+        respond_to: Responder<RemovedAttachment>,
+    },
+    /// チケットにラベルを追加する。
+    AddLabel {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        label: Label,
+        respond_to: Responder<()>,
+    },
+    /// チケットからラベルを取り除く。
+    RemoveLabel {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        label: Label,
+        respond_to: Responder<()>,
+    },
+    /// すべてのプロジェクトの添付ファイルが参照している内容ごとの、参照している添付ファイルの数を取得する。
+    ReferencedBlobs {
+        respond_to: Responder<BTreeMap<BlobDigest, usize>>,
+    },
+    /// レポートを集計するプロジェクトのチケットストアと、現在の日時を取得する。
+    ReportSources {
+        project: Option<ProjectKey>,
+        user: Option<String>,
+        respond_to: Responder<ReportSources>,
+    },
+    /// チケットの依存関係グラフを取得する。
+    DependencyGraph {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        respond_to: Responder<DependencyGraph>,
+    },
+    /// 完了していないチケットを、着手できる順番に取得する。
+    OpenWork {
+        project: ProjectKey,
+        user: Option<String>,
+        respond_to: Responder<Vec<Ticket>>,
+    },
+    /// 変更前のプロジェクトキーを、現在のプロジェクトキーに解決する。
+    LocateProject {
+        project: ProjectKey,
+        respond_to: Responder<ProjectKey>,
+    },
+    /// 利用者が読み込みを許可されたすべてのプロジェクトから、クエリの条件を満たすチケットを取得する。
+    Query {
+        expr: Expr,
+        user: Option<String>,
+        respond_to: Responder<Vec<Ticket>>,
+    },
+    /// チケットを全文検索する。
+    Search {
+        text: SearchText,
+        /// 検索するプロジェクト、`None`の場合は利用者が読み込みを許可されたすべてのプロジェクト
+        project: Option<ProjectKey>,
+        user: Option<String>,
+        limit: usize,
+        respond_to: Responder<Vec<SearchHit>>,
+    },
+    /// 利用者がプロジェクトにアクセスできるか確認する。
+    Authorize {
+        project: ProjectKey,
+        user: Option<String>,
+        access: Access,
+        respond_to: Responder<()>,
+    },
+    /// 利用者が読み込みを許可されたプロジェクトの一覧を取得する。
+    ListProjects {
+        user: Option<String>,
+        respond_to: Responder<Vec<Project>>,
+    },
+    /// プロジェクトを作成する。
+    CreateProject {
+        draft: ProjectDraft,
+        respond_to: Responder<ProjectKey>,
+    },
+    /// プロジェクトを取得する。
+    GetProject {
+        project: ProjectKey,
+        user: Option<String>,
+        respond_to: Responder<Project>,
+    },
+    /// プロジェクトを更新する。
+    UpdateProject {
+        project: ProjectKey,
+        user: Option<String>,
+        patch: ProjectPatch,
+        respond_to: Responder<ProjectKey>,
+    },
+    /// チケットのないプロジェクトを削除する。
+    DeleteProject {
+        project: ProjectKey,
+        user: Option<String>,
+        respond_to: Responder<()>,
+    },
+    /// すべてのプロジェクトのチケットストアをスナップショットに書き出す。
+    Checkpoint { respond_to: Responder<()> },
+    /// 現在の鍵で暗号化し直す必要があるファイルを持つ、最初のプロジェクトを暗号化し直す。
+    Reencrypt {
+        respond_to: Responder<Option<ProjectKey>>,
+    },
+    /// すべてのプロジェクトのバックアップを取得する。
+    Backup { respond_to: Responder<Backup> },
+    /// すべてのプロジェクトを、バックアップから復元したプロジェクトに置き換える。
+    Restore {
+        backup: Box<Backup>,
+        respond_to: Responder<()>,
+    },
+    /// 永続化されたチケットを復元したプロジェクトの一覧に置き換える。
+    Replace {
+        registry: Box<ProjectRegistry>,
+        respond_to: Responder<()>,
+    },
+    /// リーダーから受信した変更操作を適用する。
+    Apply {
+        operation: Operation,
+        occurred_at: DateTime<Utc>,
+        respond_to: Responder<()>,
+    },
+    /// 何もせずに応答する。
+    Ping { respond_to: Responder<()> },
+}
+
+impl Command {
+    /// 成功したときに複製ログに記録する変更操作を返す。
+    ///
+    /// # 戻り値
+    ///
+    /// プロジェクトの一覧を変更するコマンドの場合は変更操作、それ以外は`None`
+    fn operation(&self) -> Option<Operation> {
+        let operation = match self {
+            Self::CreateTicket {
+                project,
+                user,
+                draft,
+                ..
+            } => Operation::CreateTicket {
+                project: project.clone(),
+                user: user.clone(),
+                draft: draft.clone(),
+            },
+            Self::UpdateTicket {
+                project,
+                user,
+                ticket,
+                patch,
+                ..
+            } => Operation::UpdateTicket {
+                project: project.clone(),
+                user: user.clone(),
+                ticket: ticket.clone(),
+                patch: patch.clone(),
+            },
+            Self::MoveTicket {
+                project,
+                user,
+                ticket,
+                to,
+                ..
+            } => Operation::MoveTicket {
+                project: project.clone(),
+                user: user.clone(),
+                ticket: ticket.clone(),
+                to: to.clone(),
+            },
+            Self::SetParent {
+                project,
+                user,
+                ticket,
+                parent,
+                ..
+            } => Operation::SetParent {
+                project: project.clone(),
+                user: user.clone(),
+                ticket: ticket.clone(),
+                parent: *parent,
+            },
+            Self::AddBlocker {
+                project,
+                user,
+                ticket,
+                blocker,
+                ..
+            } => Operation::AddBlocker {
+                project: project.clone(),
+                user: user.clone(),
+                ticket: ticket.clone(),
+                blocker: *blocker,
+            },
+            Self::RemoveBlocker {
+                project,
+                user,
+                ticket,
+                blocker,
+                ..
+            } => Operation::RemoveBlocker {
+                project: project.clone(),
+                user: user.clone(),
+                ticket: ticket.clone(),
+                blocker: *blocker,
+            },
+            Self::AddAttachment {
+                project,
+                user,
+                ticket,
+                draft,
+                ..
+            } => Operation::AddAttachment {
+                project: project.clone(),
+                user: user.clone(),
+                ticket: ticket.clone(),
+                draft: draft.clone(),
+            },
+            Self::RemoveAttachment {
+                project,
+                user,
+                ticket,
+                attachment,
+                ..
+            } => Operation::RemoveAttachment {
+                project: project.clone(),
+                user: user.clone(),
+                ticket: ticket.clone(),
+                attachment: *attachment,
+            },
+            Self::AddLabel {
+                project,
+                user,
+                ticket,
+                label,
+                ..
+            } => Operation::AddLabel {
+                project: project.clone(),
+                user: user.clone(),
+                ticket: ticket.clone(),
+                label: label.clone(),
+            },
+            Self::RemoveLabel {
+                project,
+                user,
+                ticket,
+                label,
+                ..
+            } => Operation::RemoveLabel {
+                project: project.clone(),
+                user: user.clone(),
+                ticket: ticket.clone(),
+                label: label.clone(),
+            },
+            Self::CreateProject { draft, .. } => Operation::CreateProject {
+                draft: draft.clone(),
+            },
+            Self::UpdateProject {
+                project,
+                user,
+                patch,
+                ..
+            } => Operation::UpdateProject {
+                project: project.clone(),
+                user: user.clone(),
+                patch: patch.clone(),
+            },
+            Self::DeleteProject { project, user, .. } => Operation::DeleteProject {
+                project: project.clone(),
+                user: user.clone(),
+            },
+            Self::Replace { registry, .. } => Operation::Restore {
+                projects: registry.snapshots(),
+            },
+            Self::Restore { backup, .. } => Operation::Restore {
+                projects: backup.snapshots(),
+            },
+            _ => return None,
+        };
+
+        Some(operation)
+    }
+
+    /// コマンドを処理する場所を返す。
+    fn route(&self) -> Route {
+        match self {
+            Self::CreateTicket { project, .. }
+            | Self::UpdateTicket { project, .. }
+            | Self::SetParent { project, .. }
+            | Self::AddBlocker { project, .. }
+            | Self::RemoveBlocker { project, .. }
+            | Self::AddAttachment { project, .. }
+            | Self::RemoveAttachment { project, .. }
+            | Self::AddLabel { project, .. }
+            | Self::RemoveLabel { project, .. } => Route::Project(project.clone()),
+            Self::MoveTicket { .. }
+            | Self::CreateProject { .. }
+            | Self::UpdateProject { .. }
+            | Self::DeleteProject { .. }
+            | Self::Restore { .. }
+            | Self::Replace { .. }
+            | Self::Apply { .. }
+            | Self::Ping { .. } => Route::Registry,
+            Self::ListTickets { .. }
+            | Self::GetTicket { .. }
+            | Self::ReferencedBlobs { .. }
+            | Self::ReportSources { .. }
+            | Self::DependencyGraph { .. }
+            | Self::OpenWork { .. }
+            | Self::LocateProject { .. }
+            | Self::Query { .. }
+            | Self::Search { .. }
+            | Self::Authorize { .. }
+            | Self::ListProjects { .. }
+            | Self::GetProject { .. }
+            | Self::Checkpoint { .. }
+            | Self::Reencrypt { .. }
+            | Self::Backup { .. } => Route::Direct,
+        }
+    }
+}
+
+/// コマンドを処理する場所
+#[derive(Debug)]
+enum Route {
+    /// キューを経由せずに、プロジェクトの一覧の読み込みロックを保持して処理する。
+    ///
+    /// チケットを変更しないコマンドと、プロジェクトごとにロックを取得する一括処理のコマンドである。
+    Direct,
+    /// プロジェクトのアクターが、プロジェクトの一覧の読み込みロックを保持して順に処理する。
+    Project(ProjectKey),
+    /// プロジェクトの一覧のアクターが、プロジェクトの一覧の書き込みロックを保持して順に処理する。
+    ///
+    /// プロジェクトの一覧を変更するコマンドと、複数のプロジェクトを変更するコマンドである。
+    Registry,
+}
+
+/// チケットから削除した添付ファイル
+#[derive(Debug, Clone)]
+pub struct RemovedAttachment {
+    pub attachment: Attachment,
+    /// 削除した後に、同じ内容を参照しているすべてのプロジェクトの添付ファイルの数
+    pub references: usize,
+}
+
+/// レポートを集計するプロジェクトのチケットストアと、現在の日時
+#[derive(Debug)]
+pub struct ReportSources {
+    pub stores: Vec<ProjectStore>,
+    /// チケットストアの時計による現在の日時
+    pub now: DateTime<Utc>,
+}
+
+/// 複製でのチケットストアのアクターの役割
+///
+/// いずれの役割でも、変更操作を処理する間は時計を変更操作の日時に固定して、
+/// リーダーとフォロワーのチケットの日時を一致させる。
+#[derive(Debug)]
+pub enum Replica {
+    /// 成功した変更操作を複製ログに記録する。
+    Leader {
+        log: Arc<ReplicationLog>,
+        clock: Arc<PinnedClock>,
+    },
+    /// リーダーから受信した変更操作を、リーダーが記録した日時で適用する。
+    Follower { clock: Arc<PinnedClock> },
+}
+
+impl Replica {
+    /// 時計を変更操作の日時に固定してコマンドを処理し、リーダーの場合は成功した変更操作を複製ログに記録する。
+    ///
+    /// 変更操作は、処理したときと同じプロジェクトの一覧のロックを保持したまま記録するため、
+    /// 同じプロジェクトの変更操作と、プロジェクトの一覧を変更する変更操作は、処理した順番に記録される。
+    fn process(&self, command: Command, handle: impl FnOnce(Command) -> bool) {
+        match self {
+            Self::Leader { log, clock } => {
+                let operation = command.operation();
+                let occurred_at = clock.now();
+                clock.pin(occurred_at);
+                let succeeded = handle(command);
+                clock.unpin();
+                if let (true, Some(operation)) = (succeeded, operation) {
+                    log.append(occurred_at, operation);
+                }
+            }
+            Self::Follower { clock } => {
+                let occurred_at = match &command {
+                    Command::Apply { occurred_at, .. } => *occurred_at,
+                    _ => clock.now(),
+                };
+                clock.pin(occurred_at);
+                handle(command);
+                clock.unpin();
+            }
+        }
+    }
+}
+
+/// チケットストアのアクターが共有する状態
+#[derive(Debug)]
+struct Shared {
+    /// プロジェクトの一覧
+    ///
+    /// プロジェクトのアクターとキューを経由しないコマンドは読み込みロックを、
+    /// プロジェクトの一覧のアクターは書き込みロックを保持してコマンドを処理する。
+    registry: RwLock<ProjectRegistry>,
+    /// プロジェクトキーごとの、プロジェクトのアクターへの送信先
+    ///
+    /// プロジェクトの一覧のロックを保持している間だけ変更するため、
+    /// 読み込みロックを保持している間は、プロジェクトの一覧にあるプロジェクトと一致する。
+    projects: Mutex<BTreeMap<ProjectKey, mpsc::Sender<Command>>>,
+    /// プロジェクトごとの、処理を待つコマンドのキューの容量
+    capacity: usize,
+    replica: Option<Replica>,
+    runtime: tokio::runtime::Handle,
+}
+
+impl Shared {
+    /// 複製に参加している場合は役割に従って、コマンドを処理する。
+    fn process(&self, command: Command, handle: impl FnOnce(Command) -> bool) {
+        match &self.replica {
+            Some(replica) => replica.process(command, handle),
+            None => {
+                handle(command);
+            }
+        }
+    }
+
+    /// プロジェクトの一覧の読み込みロックを保持して、コマンドを処理する。
+    fn handle_shared(&self, command: Command) {
+        let registry = read_lock(&self.registry);
+        self.process(command, |command| handle_shared(&registry, command));
+    }
+
+    /// プロジェクトの一覧の書き込みロックを保持して、コマンドを処理する。
+    ///
+    /// 処理した後に、プロジェクトの一覧にないプロジェクトのアクターを停止する。
+    fn handle(&self, command: Command) {
+        let mut registry = write_lock(&self.registry);
+        self.process(command, |command| handle(&mut registry, command));
+        lock(&self.projects)
+            .retain(|key, _| matches!(registry.lookup(key), Ok(ProjectLookup::Found(_))));
+    }
+
+    /// プロジェクトのアクターへの送信先を返す。
+    ///
+    /// アクターがまだない場合は起動する。
+    ///
+    /// # 引数
+    ///
+    /// * `project` - 現在または変更前のプロジェクトキー
+    ///
+    /// # 戻り値
+    ///
+    /// プロジェクトのアクターへの送信先
+    fn project_sender(
+        self: &Arc<Self>,
+        project: &ProjectKey,
+    ) -> StoreResult<mpsc::Sender<Command>> {
+        let registry = read_lock(&self.registry);
+        project_store(&registry, project)?;
+        let mut projects = lock(&self.projects);
+        let sender = projects
+            .entry(project.clone())
+            .or_insert_with(|| self.spawn_project(project));
+
+        Ok(sender.clone())
+    }
+
+    /// プロジェクトのアクターを起動する。
+    ///
+    /// アクターはプロジェクトのコマンドを1つずつ順に処理する。
+    /// アクターごとにスレッドを占有しないように、コマンドを受信するたびにブロッキング処理用のスレッドで処理する。
+    fn spawn_project(self: &Arc<Self>, project: &ProjectKey) -> mpsc::Sender<Command> {
+        let (sender, mut receiver) = mpsc::channel(self.capacity);
+        let shared = Arc::clone(self);
+        let project = project.clone();
+        self.runtime.spawn(async move {
+            while let Some(command) = receiver.recv().await {
+                let shared = Arc::clone(&shared);
+                let handled =
+                    tokio::task::spawn_blocking(move || shared.handle_shared(command)).await;
+                if let Err(e) = handled {
+                    tracing::error!(project_key = %project, error = %e, "コマンドを処理できません。");
+                }
+            }
+            tracing::debug!(project_key = %project, "プロジェクトのアクターを停止しました。");
+        });
+
+        sender
+    }
+}
+
+/// チケットストアのアクターにコマンドを送信するハンドル
+///
+/// チケットを変更するコマンドはプロジェクトごとのアクターが、プロジェクトの一覧を変更するコマンドは
+/// プロジェクトの一覧のアクターが、それぞれ順に処理する。
+/// チケットを変更しないコマンドと一括処理のコマンドは、キューを経由せずに他のコマンドと並行して処理する。
+///
+/// ハンドルは複製でき、すべてのハンドルを破棄するとアクターは停止する。
+/// リクエストを処理するコマンドは、キューがいっぱいの場合は待たずに[`StoreError::Overloaded`]を返す。
+#[derive(Debug, Clone)]
+pub struct StoreHandle {
+    shared: Arc<Shared>,
+    /// プロジェクトの一覧のアクターへの送信先
+    sender: mpsc::Sender<Command>,
+}
+
+impl StoreHandle {
+    /// プロジェクトの一覧を所有するアクターを起動する。
+    ///
+    /// 各アクターはコマンドを1つずつ順に処理する。プロジェクトのアクターは、最初のコマンドを受信したときに起動する。
+    /// コマンドの処理はファイルストレージへの書き込みを含むため、非同期のワーカースレッドを塞がないように、
+    /// ブロッキング処理用のスレッドで実行する。
+    ///
+    /// # 引数
+    ///
+    /// * `registry` - プロジェクトの一覧
+    /// * `capacity` - アクターごとの、処理を待つコマンドのキューの容量
+    ///
+    /// # 戻り値
+    ///
+    /// アクターのハンドル
+    pub fn spawn(registry: ProjectRegistry, capacity: usize) -> Self {
+        Self::spawn_actor(registry, capacity, None)
+    }
+
+    /// 複製に参加するアクターを起動する。
+    ///
+    /// リーダーの場合は、起動したときのプロジェクトの一覧を復元する変更操作を、最初に複製ログに記録する。
+    /// チケットストアの時計には、役割と同じ固定できる時計を設定しておかなければならない。
+    ///
+    /// # 引数
+    ///
+    /// * `registry` - プロジェクトの一覧
+    /// * `capacity` - アクターごとの、処理を待つコマンドのキューの容量
+    /// * `replica` - 複製での役割
+    ///
+    /// # 戻り値
+    ///
+    /// アクターのハンドル
+    pub fn spawn_replica(registry: ProjectRegistry, capacity: usize, replica: Replica) -> Self {
+        if let Replica::Leader { log, clock } = &replica {
+            let projects = registry.snapshots();
+            log.append(clock.now(), Operation::Restore { projects });
+        }
+        Self::spawn_actor(registry, capacity, Some(replica))
+    }
+
+    fn spawn_actor(registry: ProjectRegistry, capacity: usize, replica: Option<Replica>) -> Self {
+        let shared = Arc::new(Shared {
+            registry: RwLock::new(registry),
+            projects: Mutex::default(),
+            capacity,
+            replica,
+            runtime: tokio::runtime::Handle::current(),
+        });
+        let (sender, mut receiver) = mpsc::channel(capacity);
+        let actor = Arc::clone(&shared);
+        tokio::task::spawn_blocking(move || {
+            while let Some(command) = receiver.blocking_recv() {
+                actor.handle(command);
+            }
+            // プロジェクトのアクターへの送信先を破棄して、プロジェクトのアクターも停止する。
+            lock(&actor.projects).clear();
+            tracing::debug!("チケットストアのアクターを停止しました。");
+        });
+
+        Self { shared, sender }
+    }
+
+    /// コマンドを処理する場所に送信して、結果を待つ。
+    ///
+    /// キューがいっぱいの場合は、待たずに[`StoreError::Overloaded`]を返す。
+    async fn request<T>(&self, command: impl FnOnce(Responder<T>) -> Command) -> StoreResult<T> {
+        let (respond_to, response) = oneshot::channel();
+        let command = command(respond_to);
+        let sender = match command.route() {
+            Route::Direct => {
+                self.spawn_direct(command);
+                return response.await.map_err(|_| StoreError::Stopped)?;
+            }
+            Route::Project(project) => self.project_sender(project).await?,
+            Route::Registry => self.sender.clone(),
+        };
+        sender.try_send(command).map_err(|e| match e {
+            TrySendError::Full(_) => {
+                tracing::warn!("チケットストアのキューがいっぱいです。");
+                StoreError::Overloaded
+            }
+            TrySendError::Closed(_) => StoreError::Stopped,
+        })?;
+
+        response.await.map_err(|_| StoreError::Stopped)?
+    }
+
+    /// コマンドをキューに追加できるまで待ってから、結果を待つ。
+    ///
+    /// 取りこぼしてはならない、サーバー内部のコマンドに使用する。
+    async fn request_queued<T>(
+        &self,
+        command: impl FnOnce(Responder<T>) -> Command,
+    ) -> StoreResult<T> {
+        let (respond_to, response) = oneshot::channel();
+        let command = command(respond_to);
+        let sender = match command.route() {
+            Route::Direct => {
+                self.spawn_direct(command);
+                return response.await.map_err(|_| StoreError::Stopped)?;
+            }
+            Route::Project(project) => self.project_sender(project).await?,
+            Route::Registry => self.sender.clone(),
+        };
+        sender
+            .send(command)
+            .await
+            .map_err(|_| StoreError::Stopped)?;
+
+        response.await.map_err(|_| StoreError::Stopped)?
+    }
+
+    /// キューを経由せずに、ブロッキング処理用のスレッドでコマンドを処理する。
+    fn spawn_direct(&self, command: Command) {
+        let shared = Arc::clone(&self.shared);
+        tokio::task::spawn_blocking(move || shared.handle_shared(command));
+    }
+
+    /// プロジェクトのアクターへの送信先を返す。
+    ///
+    /// プロジェクトの一覧のロックを待つ間に非同期のワーカースレッドを塞がないように、
+    /// ブロッキング処理用のスレッドで取得する。
+    async fn project_sender(&self, project: ProjectKey) -> StoreResult<mpsc::Sender<Command>> {
+        let shared = Arc::clone(&self.shared);
+        tokio::task::spawn_blocking(move || shared.project_sender(&project))
+            .await
+            .map_err(|_| StoreError::Stopped)?
+    }
+
+    /// プロジェクトの、条件に一致するチケットの一覧を取得する。
+    pub async fn list_tickets(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        query: TicketQuery,
+    ) -> StoreResult<Vec<Ticket>> {
+        self.request(|respond_to| Command::ListTickets {
+            project,
+            user,
+            query,
+            respond_to,
+        })
+        .await
+    }
+
+    /// チケットをプロジェクトに登録する。
+    ///
+    /// # 戻り値
+    ///
+    /// 登録したチケット
+    pub async fn create_ticket(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        draft: TicketDraft,
+    ) -> StoreResult<Ticket> {
+        self.request(|respond_to| Command::CreateTicket {
+            project,
+            user,
+            draft,
+            respond_to,
+        })
+        .await
+    }
+
+    /// プロジェクトのチケットを取得する。
+    pub async fn get_ticket(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+    ) -> StoreResult<Ticket> {
+        self.request(|respond_to| Command::GetTicket {
+            project,
+            user,
+            ticket,
+            respond_to,
+        })
+        .await
+    }
+
+    /// プロジェクトのチケットを更新する。
+    pub async fn update_ticket(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        patch: TicketPatch,
+    ) -> StoreResult<()> {
+        self.request(|respond_to| Command::UpdateTicket {
+            project,
+            user,
+            ticket,
+            patch,
+            respond_to,
+        })
+        .await
+    }
+
+    /// チケットを他のプロジェクトに移動する。
+    ///
+    /// # 戻り値
+    ///
+    /// 移動後のチケットキー
+    pub async fn move_ticket(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        to: ProjectKey,
+    ) -> StoreResult<TicketKey> {
+        self.request(|respond_to| Command::MoveTicket {
+            project,
+            user,
+            ticket,
+            to,
+            respond_to,
+        })
+        .await
+    }
+
+    /// チケットの親チケットを設定する。
+    pub async fn set_parent(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        parent: Option<TicketId>,
+    ) -> StoreResult<()> {
+        self.request(|respond_to| Command::SetParent {
+            project,
+            user,
+            ticket,
+            parent,
+            respond_to,
+        })
+        .await
+    }
+
+    /// チケットにブロッカーを追加する。
+    pub async fn add_blocker(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        blocker: TicketId,
+    ) -> StoreResult<()> {
+        self.request(|respond_to| Command::AddBlocker {
+            project,
+            user,
+            ticket,
+            blocker,
+            respond_to,
+        })
+        .await
+    }
+
+    /// チケットに添付ファイルを追加する。
+    pub async fn add_attachment(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        draft: AttachmentDraft,
+    ) -> StoreResult<Attachment> {
+        self.request(|respond_to| Command::AddAttachment {
+            project,
+            user,
+            ticket,
+            draft,
+            respond_to,
+        })
+        .await
+    }
+
+    /// チケットから添付ファイルを削除する。
+    pub async fn remove_attachment(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        attachment: AttachmentId,
+    ) -> StoreResult<RemovedAttachment> {
+        self.request(|respond_to| Command::RemoveAttachment {
+            project,
+            user,
+            ticket,
+            attachment,
+            respond_to,
+        })
+        .await
+    }
+
+    /// チケットにラベルを追加する。
+    pub async fn add_label(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        label: Label,
+    ) -> StoreResult<()> {
+        self.request(|respond_to| Command::AddLabel {
+            project,
+            user,
+            ticket,
+            label,
+            respond_to,
+        })
+        .await
+    }
+
+    /// チケットからラベルを取り除く。
+    pub async fn remove_label(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        label: Label,
+    ) -> StoreResult<()> {
+        self.request(|respond_to| Command::RemoveLabel {
+            project,
+            user,
+            ticket,
+            label,
+            respond_to,
+        })
+        .await
+    }
+
+    /// すべてのプロジェクトの添付ファイルが参照している内容ごとの、参照している添付ファイルの数を取得する。
+    pub async fn referenced_blobs(&self) -> StoreResult<BTreeMap<BlobDigest, usize>> {
+        self.request(|respond_to| Command::ReferencedBlobs { respond_to })
+            .await
+    }
+
+    /// レポートを集計するプロジェクトのチケットストアと、現在の日時を取得する。
+    ///
+    /// 集計はアクターの外で行うため、チケットストアの参照だけを返す。
+    ///
+    /// # 引数
+    ///
+    /// * `project` - 集計するプロジェクト、`None`の場合は利用者が読み込みを許可されたすべてのプロジェクト
+    /// * `user` - 利用者、識別できない場合は`None`
+    ///
+    /// # 戻り値
+    ///
+    /// 集計するプロジェクトのチケットストアと、現在の日時
+    pub async fn report_sources(
+        &self,
+        project: Option<ProjectKey>,
+        user: Option<String>,
+    ) -> StoreResult<ReportSources> {
+        self.request(|respond_to| Command::ReportSources {
+            project,
+            user,
+            respond_to,
+        })
+        .await
+    }
+
+    /// チケットからブロッカーを取り除く。
+    pub async fn remove_blocker(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        blocker: TicketId,
+    ) -> StoreResult<()> {
+        self.request(|respond_to| Command::RemoveBlocker {
+            project,
+            user,
+            ticket,
+            blocker,
+            respond_to,
+        })
+        .await
+    }
+
+    /// チケットの依存関係グラフを取得する。
+    pub async fn dependency_graph(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+    ) -> StoreResult<DependencyGraph> {
+        self.request(|respond_to| Command::DependencyGraph {
+            project,
+            user,
+            ticket,
+            respond_to,
+        })
+        .await
+    }
+
+    /// プロジェクトの完了していないチケットを、着手できる順番に取得する。
+    pub async fn open_work(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+    ) -> StoreResult<Vec<Ticket>> {
+        self.request(|respond_to| Command::OpenWork {
+            project,
+            user,
+            respond_to,
+        })
+        .await
+    }
+
+    /// 現在または変更前のプロジェクトキーを、現在のプロジェクトキーに解決する。
+    pub async fn locate_project(&self, project: ProjectKey) -> StoreResult<ProjectKey> {
+        self.request(|respond_to| Command::LocateProject {
+            project,
+            respond_to,
+        })
+        .await
+    }
+
+    /// 利用者が読み込みを許可されたすべてのプロジェクトから、クエリの条件を満たすチケットを取得する。
+    ///
+    /// # 戻り値
+    ///
+    /// プロジェクトキーとチケットIDの順のチケット
+    pub async fn query(&self, expr: Expr, user: Option<String>) -> StoreResult<Vec<Ticket>> {
+        self.request(|respond_to| Command::Query {
+            expr,
+            user,
+            respond_to,
+        })
+        .await
+    }
+
+    /// チケットのタイトルと説明を全文検索する。
+    ///
+    /// # 引数
+    ///
+    /// * `text` - 検索文字列
+    /// * `project` - 検索するプロジェクト、`None`の場合は利用者が読み込みを許可されたすべてのプロジェクト
+    /// * `user` - 利用者、識別できない場合は`None`
+    /// * `limit` - 検索結果の最大件数
+    ///
+    /// # 戻り値
+    ///
+    /// 関連度の高い順のチケット
+    pub async fn search(
+        &self,
+        text: SearchText,
+        project: Option<ProjectKey>,
+        user: Option<String>,
+        limit: usize,
+    ) -> StoreResult<Vec<SearchHit>> {
+        self.request(|respond_to| Command::Search {
+            text,
+            project,
+            user,
+            limit,
+            respond_to,
+        })
+        .await
+    }
+
+    /// 利用者がプロジェクトにアクセスできるか確認する。
+    pub async fn authorize(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        access: Access,
+    ) -> StoreResult<()> {
+        self.request(|respond_to| Command::Authorize {
+            project,
+            user,
+            access,
+            respond_to,
+        })
+        .await
+    }
+
+    /// 変更フィードの配信のために、利用者がプロジェクトを読み込めるか確認する。
+    ///
+    /// チケットイベントを取りこぼさないように、キューがいっぱいの場合は空くまで待つ。
+    pub async fn can_read(&self, project: ProjectKey, user: Option<String>) -> StoreResult<()> {
+        self.request_queued(|respond_to| Command::Authorize {
+            project,
+            user,
+            access: Access::Read,
+            respond_to,
+        })
+        .await
+    }
+
+    /// 利用者が読み込みを許可されたプロジェクトの一覧を取得する。
+    pub async fn projects(&self, user: Option<String>) -> StoreResult<Vec<Project>> {
+        self.request(|respond_to| Command::ListProjects { user, respond_to })
+            .await
+    }
+
+    /// プロジェクトを作成する。
+    pub async fn create_project(&self, draft: ProjectDraft) -> StoreResult<ProjectKey> {
+        self.request(|respond_to| Command::CreateProject { draft, respond_to })
+            .await
+    }
+
+    /// プロジェクトを取得する。
+    pub async fn get_project(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+    ) -> StoreResult<Project> {
+        self.request(|respond_to| Command::GetProject {
+            project,
+            user,
+            respond_to,
+        })
+        .await
+    }
+
+    /// プロジェクトを更新する。
+    ///
+    /// # 戻り値
+    ///
+    /// 更新後のプロジェクトキー
+    pub async fn update_project(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+        patch: ProjectPatch,
+    ) -> StoreResult<ProjectKey> {
+        self.request(|respond_to| Command::UpdateProject {
+            project,
+            user,
+            patch,
+            respond_to,
+        })
+        .await
+    }
+
+    /// チケットのないプロジェクトを削除する。
+    pub async fn delete_project(
+        &self,
+        project: ProjectKey,
+        user: Option<String>,
+    ) -> StoreResult<()> {
+        self.request(|respond_to| Command::DeleteProject {
+            project,
+            user,
+            respond_to,
+        })
+        .await
+    }
+
+    /// すべてのプロジェクトのチケットストアをスナップショットに書き出す。
+    pub async fn checkpoint(&self) -> StoreResult<()> {
+        self.request_queued(|respond_to| Command::Checkpoint { respond_to })
+            .await
+    }
+
+    /// 現在の鍵で暗号化し直す必要があるファイルを持つ、プロジェクトキー順で最初のプロジェクトを暗号化し直す。
+    ///
+    /// # 戻り値
+    ///
+    /// 暗号化し直したプロジェクトのプロジェクトキー、暗号化し直す必要があるプロジェクトがない場合は`None`
+    pub async fn reencrypt_next(&self) -> StoreResult<Option<ProjectKey>> {
+        self.request_queued(|respond_to| Command::Reencrypt { respond_to })
+            .await
+    }
+
+    /// すべてのプロジェクトの、同じ時点のバックアップを取得する。
+    ///
+    /// アクターはスナップショットとドメインイベントの履歴を複製するだけであり、
+    /// アーカイブへの書き出しはアクターの外で行うため、書き出している間もリクエストを処理できる。
+    pub async fn backup(&self) -> StoreResult<Backup> {
+        self.request(|respond_to| Command::Backup { respond_to })
+            .await
+    }
+
+    /// すべてのプロジェクトを、バックアップから復元したプロジェクトに置き換える。
+    ///
+    /// # 引数
+    ///
+    /// * `backup` - [`Backup::read`]で検証したバックアップ
+    pub async fn restore(&self, backup: Backup) -> StoreResult<()> {
+        self.request(|respond_to| Command::Restore {
+            backup: Box::new(backup),
+            respond_to,
+        })
+        .await
+    }
+
+    /// プロジェクトの一覧を置き換える。
+    ///
+    /// # 引数
+    ///
+    /// * `registry` - 永続化されたチケットを復元したプロジェクトの一覧
+    pub async fn replace(&self, registry: ProjectRegistry) -> StoreResult<()> {
+        self.request_queued(|respond_to| Command::Replace {
+            registry: Box::new(registry),
+            respond_to,
+        })
+        .await
+    }
+
+    /// リーダーから受信した変更操作を適用する。
+    ///
+    /// 変更操作を取りこぼさないように、キューがいっぱいの場合は空くまで待つ。
+    pub async fn apply(&self, entry: ReplicatedOperation) -> StoreResult<()> {
+        self.request_queued(|respond_to| Command::Apply {
+            operation: entry.operation,
+            occurred_at: entry.occurred_at,
+            respond_to,
+        })
+        .await
+    }
+
+    /// プロジェクトの一覧のアクターが、プロジェクトの一覧の書き込みロックを取得してコマンドを処理できるか確認する。
+    ///
+    /// いずれかのコマンドがプロジェクトの一覧のロックを保持したまま止まっている場合は、応答しない。
+    pub async fn ping(&self) -> StoreResult<()> {
+        self.request(|respond_to| Command::Ping { respond_to })
+            .await
+    }
+}
+
+/// コマンドの結果を送信する。
+///
+/// リクエストの送信元がすでに結果を待っていない場合は、結果を破棄する。
+///
+/// # 戻り値
+///
+/// コマンドが成功した場合は`true`
+fn respond<T>(respond_to: Responder<T>, result: StoreResult<T>) -> bool {
+    let succeeded = result.is_ok();
+    let _ = respond_to.send(result);
+    succeeded
+}
+
+/// プロジェクトの一覧の書き込みロックを保持して、コマンドを処理して結果を送信する。
+///
+/// # 戻り値
+///
+/// コマンドが成功した場合は`true`
+fn handle(registry: &mut ProjectRegistry, command: Command) -> bool {
+    match command {
+        Command::MoveTicket {
+            project,
+            user,
+            ticket,
+            to,
+            respond_to,
+        } => {
+            let result = (|| {
+                let from = project_store(registry, &project)?;
+                let to = registry.resolve(&to)?;
+                let id = resolve_ticket(&read_lock(&from), &ticket)?;
+                Ok(registry::move_ticket(&from, id, &to, user.as_deref())?)
+            })();
+            respond(respond_to, result)
+        }
+        Command::CreateProject { draft, respond_to } => {
+            respond(respond_to, registry.create(draft).map_err(StoreError::from))
+        }
+        Command::UpdateProject {
+            project,
+            user,
+            patch,
+            respond_to,
+        } => {
+            let result = with_store(registry, &project, user.as_deref(), Access::Write, |_| {
+                Ok(())
+            })
+            .and_then(|()| Ok(registry.update(&project, patch)?));
+            respond(respond_to, result)
+        }
+        Command::DeleteProject {
+            project,
+            user,
+            respond_to,
+        } => {
+            let result = with_store(registry, &project, user.as_deref(), Access::Write, |_| {
+                Ok(())
+            })
+            .and_then(|()| Ok(registry.delete(&project)?));
+            respond(respond_to, result)
+        }
+        Command::Restore { backup, respond_to } => {
+            let result = registry.restore_backup(*backup).map_err(|e| {
+                tracing::error!(error = %e, "バックアップから復元できません。");
+                StoreError::Store(TicketStoreError::Persistence(Arc::new(e)))
+            });
+            respond(respond_to, result)
+        }
+        Command::Replace {
+            registry: replacement,
+            respond_to,
+        } => {
+            *registry = *replacement;
+            respond(respond_to, Ok(()))
+        }
+        Command::Apply {
+            operation,
+            respond_to,
+            ..
+        } => respond(respond_to, replay(registry, operation)),
+        Command::Ping { respond_to } => respond(respond_to, Ok(())),
+        command => handle_shared(registry, command),
+    }
+}
+
+/// プロジェクトの一覧を変更しないコマンドを処理して、結果を送信する。
+///
+/// # 戻り値
+///
+/// コマンドが成功した場合は`true`
+fn handle_shared(registry: &ProjectRegistry, command: Command) -> bool {
+    match command {
+        Command::ListTickets {
+            project,
+            user,
+            query,
+            respond_to,
+        } => {
+            let result = with_store(registry, &project, user.as_deref(), Access::Read, |store| {
+                Ok(store.list(&query))
+            });
+            respond(respond_to, result)
+        }
+        Command::CreateTicket {
+            project,
+            user,
+            draft,
+            respond_to,
+        } => {
+            let result = with_store(
+                registry,
+                &project,
+                user.as_deref(),
+                Access::Write,
+                |store| {
+                    let id = store.add_ticket(draft)?;
+                    Ok(store.get(id)?)
+                },
+            );
+            respond(respond_to, result)
+        }
+        Command::GetTicket {
+            project,
+            user,
+            ticket,
+            respond_to,
+        } => {
+            let result = with_store(registry, &project, user.as_deref(), Access::Read, |store| {
+                let id = resolve_ticket(store, &ticket)?;
+                Ok(store.get(id)?)
+            });
+            respond(respond_to, result)
+        }
+        Command::UpdateTicket {
+            project,
+            user,
+            ticket,
+            patch,
+            respond_to,
+        } => {
+            let result = with_store(
+                registry,
+                &project,
+                user.as_deref(),
+                Access::Write,
+                |store| {
+                    let id = resolve_ticket(store, &ticket)?;
+                    Ok(store.update_ticket(id, patch)?)
+                },
+            );
+            respond(respond_to, result)
+        }
+        Command::SetParent {
+            project,
+            user,
+            ticket,
+            parent,
+            respond_to,
+        } => {
+            let result = with_store_mut(registry, &project, user.as_deref(), |store| {
+                let id = resolve_ticket(store, &ticket)?;
+                Ok(store.set_parent(id, parent)?)
+            });
+            respond(respond_to, result)
+        }
+        Command::AddBlocker {
+            project,
+            user,
+            ticket,
+            blocker,
+            respond_to,
+        } => {
+            let result = with_store_mut(registry, &project, user.as_deref(), |store| {
+                let id = resolve_ticket(store, &ticket)?;
+                Ok(store.add_blocker(id, blocker)?)
+            });
+            respond(respond_to, result)
+        }
+        Command::RemoveBlocker {
+            project,
+            user,
+            ticket,
+            blocker,
+            respond_to,
+        } => {
+            let result = with_store_mut(registry, &project, user.as_deref(), |store| {
+                let id = resolve_ticket(store, &ticket)?;
+                Ok(store.remove_blocker(id, blocker)?)
+            });
+            respond(respond_to, result)
+        }
+        Command::AddAttachment {
+            project,
+            user,
+            ticket,
+            draft,
+            respond_to,
+        } => {
+            let result = with_store(
+                registry,
+                &project,
+                user.as_deref(),
+                Access::Write,
+                |store| {
+                    let id = resolve_ticket(store, &ticket)?;
+                    Ok(store.add_attachment(id, draft)?)
+                },
+            );
+            respond(respond_to, result)
+        }
+        Command::RemoveAttachment {
+            project,
+            user,
+            ticket,
+            attachment,
+            respond_to,
+        } => {
+            let result = with_store(
+                registry,
+                &project,
+                user.as_deref(),
+                Access::Write,
+                |store| {
+                    let id = resolve_ticket(store, &ticket)?;
+                    Ok(store.remove_attachment(id, attachment)?)
+                },
+            )
+            .map(|attachment| RemovedAttachment {
+                references: registry.blob_references(&attachment.digest),
+                attachment,
+            });
+            respond(respond_to, result)
+        }
+        Command::AddLabel {
+            project,
+            user,
+            ticket,
+            label,
+            respond_to,
+        } => {
+            let result = with_store(
+                registry,
+                &project,
+                user.as_deref(),
+                Access::Write,
+                |store| {
+                    let id = resolve_ticket(store, &ticket)?;
+                    Ok(store.add_label(id, label)?)
+                },
+            );
+            respond(respond_to, result)
+        }
+        Command::RemoveLabel {
+            project,
+            user,
+            ticket,
+            label,
+            respond_to,
+        } => {
+            let result = with_store(
+                registry,
+                &project,
+                user.as_deref(),
+                Access::Write,
+                |store| {
+                    let id = resolve_ticket(store, &ticket)?;
+                    Ok(store.remove_label(id, label)?)
+                },
+            );
+            respond(respond_to, result)
+        }
+        Command::ReferencedBlobs { respond_to } => {
+            respond(respond_to, Ok(registry.referenced_blobs()))
+        }
+        Command::ReportSources {
+            project,
+            user,
+            respond_to,
+        } => {
+            let stores = match project {
+                Some(project) => registry.resolve(&project).and_then(|store| {
+                    read_lock(&store).authorize(user.as_deref(), Access::Read)?;
+                    Ok(vec![store])
+                }),
+                None => Ok(registry.readable_stores(user.as_deref())),
+            };
+            let result = stores
+                .map(|stores| ReportSources {
+                    stores,
+                    now: registry.now(),
+                })
+                .map_err(StoreError::from);
+            respond(respond_to, result)
+        }
+        Command::DependencyGraph {
+            project,
+            user,
+            ticket,
+            respond_to,
+        } => {
+            let result = with_store(registry, &project, user.as_deref(), Access::Read, |store| {
+                let id = resolve_ticket(store, &ticket)?;
+                Ok(store.dependency_graph(id)?)
+            });
+            respond(respond_to, result)
+        }
+        Command::OpenWork {
+            project,
+            user,
+            respond_to,
+        } => {
+            let result = with_store(registry, &project, user.as_deref(), Access::Read, |store| {
+                Ok(store.open_work())
+            });
+            respond(respond_to, result)
+        }
+        Command::LocateProject {
+            project,
+            respond_to,
+        } => {
+            let result = registry
+                .lookup(&project)
+                .map(|lookup| match lookup {
+                    ProjectLookup::Found(_) => project,
+                    ProjectLookup::Renamed(current) => current,
+                })
+                .map_err(StoreError::from);
+            respond(respond_to, result)
+        }
+        Command::Query {
+            expr,
+            user,
+            respond_to,
+        } => respond(respond_to, Ok(registry.query(&expr, user.as_deref()))),
+        Command::Search {
+            text,
+            project,
+            user,
+            limit,
+            respond_to,
+        } => {
+            let result = match project {
+                Some(project) => (|| {
+                    let store = registry.resolve(&project)?;
+                    let store = read_lock(&store);
+                    store.authorize(user.as_deref(), Access::Read)?;
+                    let mut hits = store.search(&text);
+                    hits.truncate(limit);
+                    Ok(hits)
+                })(),
+                None => Ok(registry.search(&text, user.as_deref(), limit)),
+            };
+            respond(respond_to, result)
+        }
+        Command::Authorize {
+            project,
+            user,
+            access,
+            respond_to,
+        } => {
+            let result = registry
+                .resolve(&project)
+                .and_then(|store| read_lock(&store).authorize(user.as_deref(), access))
+                .map_err(StoreError::from);
+            respond(respond_to, result)
+        }
+        Command::ListProjects { user, respond_to } => {
+            respond(respond_to, Ok(registry.projects(user.as_deref())))
+        }
+        Command::GetProject {
+            project,
+            user,
+            respond_to,
+        } => {
+            let result = with_store(registry, &project, user.as_deref(), Access::Read, |store| {
+                Ok(store.project().clone())
+            });
+            respond(respond_to, result)
+        }
+        Command::Checkpoint { respond_to } => {
+            let result = registry.checkpoint().map_err(|e| {
+                tracing::error!(error = %e, "チケットストアを書き出せません。");
+                StoreError::Store(TicketStoreError::Persistence(Arc::new(e)))
+            });
+            respond(respond_to, result)
+        }
+        Command::Reencrypt { respond_to } => {
+            let result = registry.reencrypt_next().map_err(|e| {
+                tracing::error!(error = %e, "現在の鍵で暗号化し直せません。");
+                StoreError::Store(TicketStoreError::Persistence(Arc::new(e)))
+            });
+            respond(respond_to, result)
+        }
+        Command::Backup { respond_to } => respond(respond_to, Ok(registry.backup())),
+        command => unreachable!(
+            "プロジェクトの一覧を変更するコマンドは、プロジェクトの一覧の書き込みロックを保持して処理する: {command:?}"
+        ),
+    }
+}
+
+/// 複製した変更操作を、対応するコマンドとして処理する。
+fn replay(registry: &mut ProjectRegistry, operation: Operation) -> StoreResult<()> {
+    match operation {
+        Operation::Restore { projects } => {
+            registry.restore(projects);
+            Ok(())
+        }
+        Operation::CreateTicket {
+            project,
+            user,
+            draft,
+        } => replay_command(registry, |respond_to| Command::CreateTicket {
+            project,
+            user,
+            draft,
+            respond_to,
+        }),
+        Operation::UpdateTicket {
+            project,
+            user,
+            ticket,
+            patch,
+        } => replay_command(registry, |respond_to| Command::UpdateTicket {
+            project,
+            user,
+            ticket,
+            patch,
+            respond_to,
+        }),
+        Operation::MoveTicket {
+            project,
+            user,
+            ticket,
+            to,
+        } => replay_command(registry, |respond_to| Command::MoveTicket {
+            project,
+            user,
+            ticket,
+            to,
+            respond_to,
+        }),
+        Operation::SetParent {
+            project,
+            user,
+            ticket,
+            parent,
+        } => replay_command(registry, |respond_to| Command::SetParent {
+            project,
+            user,
+            ticket,
+            parent,
+            respond_to,
+        }),
+        Operation::AddBlocker {
+            project,
+            user,
+            ticket,
+            blocker,
+        } => replay_command(registry, |respond_to| Command::AddBlocker {
+            project,
+            user,
+            ticket,
+            blocker,
+            respond_to,
+        }),
+        Operation::RemoveBlocker {
+            project,
+            user,
+            ticket,
+            blocker,
+        } => replay_command(registry, |respond_to| Command::RemoveBlocker {
+            project,
+            user,
+            ticket,
+            blocker,
+            respond_to,
+        }),
+        Operation::AddAttachment {
+            project,
+            user,
+            ticket,
+            draft,
+        } => replay_command(registry, |respond_to| Command::AddAttachment {
+            project,
+            user,
+            ticket,
+            draft,
+            respond_to,
+        }),
+        Operation::RemoveAttachment {
+            project,
+            user,
+            ticket,
+            attachment,
+        } => replay_command(registry, |respond_to| Command::RemoveAttachment {
+            project,
+            user,
+            ticket,
+            attachment,
+            respond_to,
+        }),
+        Operation::AddLabel {
+            project,
+            user,
+            ticket,
+            label,
+        } => replay_command(registry, |respond_to| Command::AddLabel {
+            project,
+            user,
+            ticket,
+            label,
+            respond_to,
+        }),
+        Operation::RemoveLabel {
+            project,
+            user,
+            ticket,
+            label,
+        } => replay_command(registry, |respond_to| Command::RemoveLabel {
+            project,
+            user,
+            ticket,
+            label,
+            respond_to,
+        }),
+        Operation::CreateProject { draft } => replay_command(registry, |respond_to| {
+            Command::CreateProject { draft, respond_to }
+        }),
+        Operation::UpdateProject {
+            project,
+            user,
+            patch,
+        } => replay_command(registry, |respond_to| Command::UpdateProject {
+            project,
+            user,
+            patch,
+            respond_to,
+        }),
+        Operation::DeleteProject { project, user } => {
+            replay_command(registry, |respond_to| Command::DeleteProject {
+                project,
+                user,
+                respond_to,
+            })
+        }
+    }
+}
+
+/// コマンドを処理して、結果を破棄する。
+fn replay_command<T>(
+    registry: &mut ProjectRegistry,
+    command: impl FnOnce(Responder<T>) -> Command,
+) -> StoreResult<()> {
+    let (respond_to, mut response) = oneshot::channel();
+    handle(registry, command(respond_to));
+    response
+        .try_recv()
+        .map_err(|_| StoreError::Stopped)?
+        .map(|_| ())
+}
+
+/// プロジェクトキーで指定されたプロジェクトのチケットストアを取得する。
+///
+/// 変更前のプロジェクトキーで指定された場合は、[`StoreError::ProjectRenamed`]を返す。
+fn project_store(registry: &ProjectRegistry, project: &ProjectKey) -> StoreResult<ProjectStore> {
+    match registry.lookup(project)? {
+        ProjectLookup::Found(store) => Ok(store),
+        ProjectLookup::Renamed(current) => Err(StoreError::ProjectRenamed(current)),
+    }
+}
+
+/// チケットの指定をチケットIDに解決する。
+///
+/// チケットキーが変わっている場合は、[`StoreError::TicketMoved`]を返す。
+fn resolve_ticket(store: &TicketStore, ticket: &TicketRef) -> StoreResult<TicketId> {
+    match store.resolve(ticket)? {
+        TicketLookup::Found(id) => Ok(id),
+        TicketLookup::Moved(key) => Err(StoreError::TicketMoved(key)),
+    }
+}
+
+/// 利用者のアクセスを確認してから、プロジェクトのチケットストアの読み込みロックを保持して操作を実行する。
+///
+/// チケットの登録と更新も、チケットストアの読み込みロックで実行できる。
+fn with_store<T>(
+    registry: &ProjectRegistry,
+    project: &ProjectKey,
+    user: Option<&str>,
+    access: Access,
+    operation: impl FnOnce(&TicketStore) -> StoreResult<T>,
+) -> StoreResult<T> {
+    let store = project_store(registry, project)?;
+    let store = read_lock(&store);
+    store.authorize(user, access)?;
+    operation(&store)
+}
+
+/// 利用者の書き込みを確認してから、プロジェクトのチケットストアの書き込みロックを保持して操作を実行する。
+fn with_store_mut<T>(
+    registry: &ProjectRegistry,
+    project: &ProjectKey,
+    user: Option<&str>,
+    operation: impl FnOnce(&mut TicketStore) -> StoreResult<T>,
+) -> StoreResult<T> {
+    let store = project_store(registry, project)?;
+    let mut store = write_lock(&store);
+    store.authorize(user, Access::Write)?;
+    operation(&mut store)
+}
+
+/// チケットストアのアクターのエラー
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum StoreError {
+    #[error(transparent)]
+    Store(#[from] TicketStoreError),
+    #[error("プロジェクトキーは`{0}`に変更されました。")]
+    ProjectRenamed(ProjectKey),
+    #[error("チケットキーは`{0}`に変更されました。")]
+    TicketMoved(TicketKey),
+    #[error("チケットストアが混雑しています。しばらくしてから再試行してください。")]
+    Overloaded,
+    #[error("チケットストアが停止しています。")]
+    Stopped,
+}
+
+/// チケットストアのアクターの結果
+pub type StoreResult<T> = Result<T, StoreError>;
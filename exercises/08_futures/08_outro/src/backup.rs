@@ -0,0 +1,310 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use chrono::{DateTime, Utc};
+use serde::Serializer;
+use serde_json::Value;
+
+use crate::crypto::DecryptError;
+use crate::domain::{self, EventError, RecordedEvent, StoredEvent};
+use crate::models::{ProjectKey, Ticket, TicketId};
+use crate::persistence::Snapshot;
+
+/// 現在のアーカイブの形式バージョン
+///
+/// * `1` - プロジェクトごとのスナップショット（複製の`Restore`と同じ内容）
+/// * `2` - プロジェクトごとのスナップショットと、ドメインイベントの履歴
+pub const ARCHIVE_FORMAT_VERSION: u32 = 2;
+
+/// 形式バージョンごとの、1つ新しい形式バージョンへの変換
+///
+/// `MIGRATIONS[n - 1]`は、形式バージョン`n`のアーカイブを形式バージョン`n + 1`に変換する。
+/// アーカイブの形式を変更する場合は、[`ARCHIVE_FORMAT_VERSION`]を上げて、ここに変換を追加する。
+const MIGRATIONS: [fn(Value) -> serde_json::Result<Value>; ARCHIVE_FORMAT_VERSION as usize - 1] =
+    [import_snapshot_tickets];
+
+/// すべてのプロジェクトのバックアップ
+///
+/// アーカイブに書き出す内容であり、アーカイブから読み込んだ場合は検証済みである。
+/// 添付ファイルの内容、Webhookの購読および保存したクエリは含まない。
+#[derive(Debug, Clone)]
+pub struct Backup {
+    /// バックアップを取得した日時
+    pub created_at: DateTime<Utc>,
+    /// プロジェクトキー順のプロジェクト
+    pub projects: Vec<ProjectBackup>,
+}
+
+/// 1つのプロジェクトのバックアップ
+#[derive(Debug, Clone)]
+pub struct ProjectBackup {
+    /// チケットストアのスナップショット
+    ///
+    /// プロジェクトを持ち、ドメインイベントの履歴をすべて適用済みである。
+    pub snapshot: Snapshot,
+    /// 記録された順番のすべてのドメインイベント
+    pub history: Vec<RecordedEvent>,
+}
+
+/// 書き出すアーカイブ
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveRef<'a> {
+    format: u32,
+    created_at: DateTime<Utc>,
+    projects: Vec<ProjectArchiveRef<'a>>,
+}
+
+/// 書き出すアーカイブのプロジェクト
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProjectArchiveRef<'a> {
+    snapshot: &'a Snapshot,
+    /// ドメインイベントは、1つずつ永続化する形式に変換しながら書き出す。
+    #[serde(serialize_with = "serialize_events")]
+    events: &'a [RecordedEvent],
+}
+
+/// ドメインイベントを、現在のスキーマバージョンで永続化する形式で書き出す。
+fn serialize_events<S: Serializer>(
+    events: &&[RecordedEvent],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(events.iter().map(StoredEvent::new))
+}
+
+/// 読み込んだ、現在の形式バージョンのアーカイブ
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Archive {
+    format: u32,
+    created_at: DateTime<Utc>,
+    projects: Vec<ProjectArchive>,
+}
+
+/// 読み込んだアーカイブのプロジェクト
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ProjectArchive {
+    snapshot: Snapshot,
+    events: Vec<StoredEvent>,
+}
+
+impl Backup {
+    /// 現在の形式バージョンのアーカイブとして、JSONで書き出す。
+    ///
+    /// # 引数
+    ///
+    /// * `writer` - 書き出し先
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn write(&self, writer: impl Write) -> io::Result<()> {
+        let archive = ArchiveRef {
+            format: ARCHIVE_FORMAT_VERSION,
+            created_at: self.created_at,
+            projects: self
+                .projects
+                .iter()
+                .map(|project| ProjectArchiveRef {
+                    snapshot: &project.snapshot,
+                    events: &project.history,
+                })
+                .collect(),
+        };
+
+        serde_json::to_writer(writer, &archive).map_err(io::Error::from)
+    }
+
+    /// アーカイブを読み込み、現在の形式バージョンに変換して検証する。
+    ///
+    /// # 引数
+    ///
+    /// * `content` - JSONのアーカイブ
+    ///
+    /// # 戻り値
+    ///
+    /// 検証したバックアップ
+    pub fn read(content: &[u8]) -> BackupResult<Self> {
+        let archive: Value = serde_json::from_slice(content).map_err(BackupError::Malformed)?;
+        let format = archive
+            .get("format")
+            .and_then(Value::as_u64)
+            .ok_or(BackupError::MissingFormat)?;
+        let format = u32::try_from(format)
+            .ok()
+            .filter(|format| (1..=ARCHIVE_FORMAT_VERSION).contains(format))
+            .ok_or(BackupError::UnsupportedFormat(format))?;
+        let archive = MIGRATIONS[format as usize - 1..]
+            .iter()
+            .try_fold(archive, |archive, migrate| migrate(archive))
+            .map_err(BackupError::Malformed)?;
+        let archive: Archive = serde_json::from_value(archive).map_err(BackupError::Malformed)?;
+        debug_assert_eq!(archive.format, ARCHIVE_FORMAT_VERSION);
+
+        let mut used_keys = BTreeSet::new();
+        let mut projects = Vec::with_capacity(archive.projects.len());
+        for (index, project) in archive.projects.into_iter().enumerate() {
+            let project = validate(index, project)?;
+            let keys = project
+                .snapshot
+                .project
+                .iter()
+                .flat_map(|p| std::iter::once(&p.key).chain(&p.previous_keys));
+            for key in keys {
+                if !used_keys.insert(key.clone()) {
+                    return Err(BackupError::DuplicateKey(key.clone()));
+                }
+            }
+            projects.push(project);
+        }
+
+        Ok(Self {
+            created_at: archive.created_at,
+            projects,
+        })
+    }
+
+    /// 各プロジェクトのスナップショットを返す。
+    pub fn snapshots(&self) -> Vec<Snapshot> {
+        self.projects
+            .iter()
+            .map(|project| project.snapshot.clone())
+            .collect()
+    }
+
+    /// すべてのプロジェクトのチケットの数を返す。
+    pub fn tickets(&self) -> usize {
+        self.projects
+            .iter()
+            .map(|project| project.snapshot.tickets.len())
+            .sum()
+    }
+}
+
+/// アーカイブのプロジェクトのスナップショットが、ドメインイベントの履歴と一致するか検証する。
+///
+/// ドメインイベントの履歴を最初から再生して、スナップショットのチケットのIDとバージョンが一致すること、
+/// 次に割り当てるチケットIDが使用済みのチケットIDより大きいことを確かめる。
+fn validate(index: usize, archive: ProjectArchive) -> BackupResult<ProjectBackup> {
+    let ProjectArchive { snapshot, events } = archive;
+    let Some(project) = &snapshot.project else {
+        return Err(BackupError::MissingProject(index));
+    };
+    let project = project.key.clone();
+    let inconsistent = |reason: &'static str| BackupError::Inconsistent {
+        project: project.clone(),
+        reason,
+    };
+    let history = events
+        .into_iter()
+        .map(StoredEvent::upcast)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|source| BackupError::Events {
+            project: project.clone(),
+            source,
+        })?;
+    if snapshot.events != history.len() as u64 {
+        return Err(inconsistent(
+            "スナップショットに適用済みのドメインイベントの数が、履歴と一致しません。",
+        ));
+    }
+
+    let mut tickets = BTreeMap::<TicketId, Ticket>::new();
+    let mut recorded = BTreeSet::new();
+    for record in &history {
+        let ticket = domain::apply(tickets.remove(&record.ticket), record).map_err(|source| {
+            BackupError::Events {
+                project: project.clone(),
+                source,
+            }
+        })?;
+        recorded.insert(record.ticket);
+        if let Some(ticket) = ticket {
+            tickets.insert(record.ticket, ticket);
+        }
+    }
+    let replayed = tickets.values().map(|ticket| (ticket.id, ticket.version));
+    let saved = snapshot
+        .tickets
+        .iter()
+        .map(|ticket| (ticket.id, ticket.version));
+    if !replayed.eq(saved) {
+        return Err(inconsistent(
+            "スナップショットのチケットが、ドメインイベントの履歴と一致しません。",
+        ));
+    }
+    let mut moved = BTreeSet::new();
+    for moved_ticket in &snapshot.moved {
+        if tickets.contains_key(&moved_ticket.id) || !moved.insert(moved_ticket.id) {
+            return Err(inconsistent("移動したチケットが重複しています。"));
+        }
+    }
+    let used = recorded.iter().chain(&moved).map(|id| id.0).max();
+    if used.is_some_and(|id| snapshot.next_id <= id) || snapshot.next_id == 0 {
+        return Err(inconsistent(
+            "次に割り当てるチケットIDが、使用済みのチケットID以下です。",
+        ));
+    }
+
+    Ok(ProjectBackup { snapshot, history })
+}
+
+/// 形式バージョン1のスナップショットのチケットを取り込むドメインイベントを、履歴として加える。
+///
+/// 形式バージョン1はドメインイベントの履歴を持たないため、
+/// イベントソーシングに移行する前のスナップショットと同じように、チケットの状態から履歴を始める。
+fn import_snapshot_tickets(mut archive: Value) -> serde_json::Result<Value> {
+    let projects = match archive.get_mut("projects").map(Value::take) {
+        Some(Value::Array(projects)) => projects,
+        _ => vec![],
+    };
+    let projects = projects
+        .into_iter()
+        .map(|snapshot| {
+            let mut snapshot: Snapshot = serde_json::from_value(snapshot)?;
+            let events: Vec<_> = snapshot
+                .tickets
+                .iter()
+                .map(StoredEvent::from_ticket_state)
+                .collect();
+            snapshot.events = events.len() as u64;
+            Ok(serde_json::json!({"snapshot": snapshot, "events": events}))
+        })
+        .collect::<serde_json::Result<Vec<_>>>()?;
+    archive["projects"] = Value::Array(projects);
+    archive["format"] = 2.into();
+
+    Ok(archive)
+}
+
+/// バックアップエラー
+#[derive(Debug, thiserror::Error)]
+pub enum BackupError {
+    #[error("アーカイブを復号できません: {0}")]
+    Decrypt(#[source] DecryptError),
+    #[error("アーカイブの形式が誤っています: {0}")]
+    Malformed(#[source] serde_json::Error),
+    #[error("アーカイブに形式バージョンがありません。")]
+    MissingFormat,
+    #[error("アーカイブの形式バージョン{0}には対応していません。")]
+    UnsupportedFormat(u64),
+    #[error("アーカイブの{}番目のスナップショットにプロジェクトがありません。", .0 + 1)]
+    MissingProject(usize),
+    #[error("プロジェクトキー`{0}`が、アーカイブの複数のプロジェクトで使用されています。")]
+    DuplicateKey(ProjectKey),
+    #[error("プロジェクト`{project}`のドメインイベントを復元できません: {source}")]
+    Events {
+        project: ProjectKey,
+        source: EventError,
+    },
+    #[error("プロジェクト`{project}`: {reason}")]
+    Inconsistent {
+        project: ProjectKey,
+        reason: &'static str,
+    },
+}
+
+/// バックアップ結果
+pub type BackupResult<T> = Result<T, BackupError>;
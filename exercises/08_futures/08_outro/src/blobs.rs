@@ -0,0 +1,522 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use uuid::Uuid;
+
+use crate::config::AttachmentsConfig;
+use crate::crypto::{self, Encryption, SEALED_OVERHEAD};
+use crate::models::BlobDigest;
+use crate::persistence::PersistenceResult;
+use crate::sync::lock;
+
+/// 添付ファイルの内容を保存するディレクトリ名
+const BLOBS_DIR_NAME: &str = "blobs";
+
+/// 書き込み中の内容を置くディレクトリ名
+///
+/// 書き込みが完了した内容だけを、ダイジェストのパスに移動する。
+const PARTIAL_DIR_NAME: &str = "partial";
+
+/// ファイル名を指定できなかった添付ファイルの名前
+const DEFAULT_FILE_NAME: &str = "attachment";
+
+/// 添付ファイルの内容のストア
+///
+/// 内容はSHA-256ダイジェストをキーとして保存するため、同じ内容の添付ファイルは1つの内容を共有する。
+/// データディレクトリを持つ場合は`blobs/<ダイジェストの先頭2文字>/<ダイジェスト>`に保存し、
+/// 持たない場合はメモリに保持する。
+///
+/// 暗号化が有効な場合は、ダイジェストを関連データとして内容を暗号化して保存する。
+///
+/// 内容を保存してから添付ファイルをチケットに追加するまでの間に、同じ内容が削除されないように、
+/// 保存から追加までは[`BlobStore::share`]のロックを、参照されていない内容の削除は[`BlobStore::exclusive`]のロックを保持する。
+#[derive(Debug)]
+pub struct BlobStore {
+    /// 内容を保存するディレクトリ、メモリに保持する場合は`None`
+    dir: Option<PathBuf>,
+    /// 内容を保存するファイルの暗号化
+    encryption: Encryption,
+    memory: Mutex<BTreeMap<BlobDigest, Arc<[u8]>>>,
+    collection: RwLock<()>,
+    max_bytes: u64,
+}
+
+/// 保存されている添付ファイルの内容
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredBlob {
+    pub digest: BlobDigest,
+    pub size: u64,
+}
+
+impl BlobStore {
+    /// 内容をメモリに保持するストアを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `config` - 添付ファイル設定
+    ///
+    /// # 戻り値
+    ///
+    /// ストア
+    pub fn in_memory(config: &AttachmentsConfig) -> Arc<Self> {
+        Arc::new(Self {
+            dir: None,
+            encryption: Encryption::default(),
+            memory: Mutex::default(),
+            collection: RwLock::default(),
+            max_bytes: config.max_bytes,
+        })
+    }
+
+    /// データディレクトリに内容を保存するストアを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - データディレクトリ
+    /// * `config` - 添付ファイル設定
+    ///
+    /// # 戻り値
+    ///
+    /// ストア
+    pub fn open(data_dir: &Path, config: &AttachmentsConfig) -> PersistenceResult<Arc<Self>> {
+        Self::open_encrypted(data_dir, config, &Encryption::default())
+    }
+
+    /// 暗号化を指定して、データディレクトリに内容を保存するストアを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - データディレクトリ
+    /// * `config` - 添付ファイル設定
+    /// * `encryption` - 内容を保存するファイルの暗号化
+    ///
+    /// # 戻り値
+    ///
+    /// ストア
+    pub fn open_encrypted(
+        data_dir: &Path,
+        config: &AttachmentsConfig,
+        encryption: &Encryption,
+    ) -> PersistenceResult<Arc<Self>> {
+        let dir = data_dir.join(BLOBS_DIR_NAME);
+        let partial = dir.join(PARTIAL_DIR_NAME);
+        // 前回の停止で書き込みが完了しなかった内容は、どの添付ファイルからも参照されていない。
+        if partial.exists() {
+            fs::remove_dir_all(&partial)?;
+        }
+        fs::create_dir_all(&partial)?;
+
+        Ok(Arc::new(Self {
+            dir: Some(dir),
+            encryption: encryption.clone(),
+            memory: Mutex::default(),
+            collection: RwLock::default(),
+            max_bytes: config.max_bytes,
+        }))
+    }
+
+    /// 添付ファイルの最大バイト数を返す。
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// 内容を保存してから添付ファイルを追加するまでの間、参照されていない内容の削除を待たせる。
+    pub async fn share(&self) -> RwLockReadGuard<'_, ()> {
+        self.collection.read().await
+    }
+
+    /// 参照されていない内容を削除する間、内容の保存と添付ファイルの追加を待たせる。
+    pub async fn exclusive(&self) -> RwLockWriteGuard<'_, ()> {
+        self.collection.write().await
+    }
+
+    /// 内容のパスを返す。
+    fn path(dir: &Path, digest: &BlobDigest) -> PathBuf {
+        dir.join(&digest.as_str()[..2]).join(digest.as_str())
+    }
+
+    /// 内容を保存する。
+    ///
+    /// 同じ内容がすでに保存されている場合は、保存し直さない。
+    ///
+    /// # 引数
+    ///
+    /// * `content` - 添付ファイルの内容
+    ///
+    /// # 戻り値
+    ///
+    /// 内容のダイジェスト
+    pub fn put(&self, content: &[u8]) -> io::Result<BlobDigest> {
+        let digest = BlobDigest::of(content);
+        let Some(dir) = &self.dir else {
+            lock(&self.memory)
+                .entry(digest.clone())
+                .or_insert_with(|| content.into());
+            return Ok(digest);
+        };
+        let path = Self::path(dir, &digest);
+        if path.exists() {
+            tracing::debug!(%digest, "同じ内容がすでに保存されています。");
+            return Ok(digest);
+        }
+        self.write(dir, &digest, content)?;
+        tracing::info!(%digest, size = content.len(), "添付ファイルの内容を保存しました。");
+
+        Ok(digest)
+    }
+
+    /// 暗号化が有効な場合は暗号化して、内容を書き込みが完了してからダイジェストのパスに移動する。
+    fn write(&self, dir: &Path, digest: &BlobDigest, content: &[u8]) -> io::Result<()> {
+        let path = Self::path(dir, digest);
+        let partial = dir.join(PARTIAL_DIR_NAME).join(Uuid::new_v4().to_string());
+        let mut file = File::create(&partial)?;
+        file.write_all(&self.encryption.seal(digest.as_str(), content))?;
+        file.sync_all()?;
+        fs::create_dir_all(path.parent().expect("内容のパスは親ディレクトリを持つ"))?;
+        fs::rename(&partial, &path)
+    }
+
+    /// 内容を保存したファイルのバイト数から、内容のバイト数を求める。
+    fn content_size(&self, path: &Path, len: u64) -> io::Result<u64> {
+        if !self.encryption.is_enabled() {
+            return Ok(len);
+        }
+        let mut header = vec![];
+        File::open(path)?.take(4).read_to_end(&mut header)?;
+        if crypto::is_sealed(&header) {
+            return Ok(len.saturating_sub(SEALED_OVERHEAD));
+        }
+
+        Ok(len)
+    }
+
+    /// 暗号化されている内容を復号する。
+    fn decrypt(&self, digest: &BlobDigest, content: Vec<u8>) -> io::Result<Vec<u8>> {
+        match self.encryption.open(digest.as_str(), &content) {
+            Ok((plaintext, _)) => Ok(plaintext.into_owned()),
+            Err(e) => {
+                tracing::error!(%digest, error = %e, "添付ファイルの内容を復号できません。");
+                Err(io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+        }
+    }
+
+    /// 内容のバイト数を返す。
+    ///
+    /// # 戻り値
+    ///
+    /// 内容のバイト数、保存されていない場合は`None`
+    pub fn size(&self, digest: &BlobDigest) -> io::Result<Option<u64>> {
+        let Some(dir) = &self.dir else {
+            return Ok(lock(&self.memory)
+                .get(digest)
+                .map(|content| content.len() as u64));
+        };
+        let path = Self::path(dir, digest);
+        match fs::metadata(&path) {
+            Ok(metadata) => self.content_size(&path, metadata.len()).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 内容の指定した範囲を読み込む。
+    ///
+    /// # 引数
+    ///
+    /// * `digest` - 内容のダイジェスト
+    /// * `range` - 読み込むバイトの範囲
+    ///
+    /// # 戻り値
+    ///
+    /// 読み込んだバイト列
+    pub fn read(&self, digest: &BlobDigest, range: Range<u64>) -> io::Result<Vec<u8>> {
+        let Some(dir) = &self.dir else {
+            let memory = lock(&self.memory);
+            let content = memory
+                .get(digest)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+            let end = (range.end as usize).min(content.len());
+            let start = (range.start as usize).min(end);
+            return Ok(content[start..end].to_vec());
+        };
+        // 暗号化した内容は全体を認証してから復号するため、範囲だけを読み込めない。
+        if self.encryption.is_enabled() {
+            let content = self.decrypt(digest, fs::read(Self::path(dir, digest))?)?;
+            let end = (range.end as usize).min(content.len());
+            let start = (range.start as usize).min(end);
+            return Ok(content[start..end].to_vec());
+        }
+        let mut file = File::open(Self::path(dir, digest))?;
+        file.seek(SeekFrom::Start(range.start))?;
+        let mut content = vec![];
+        file.take(range.end.saturating_sub(range.start))
+            .read_to_end(&mut content)?;
+
+        Ok(content)
+    }
+
+    /// 内容を削除する。
+    ///
+    /// # 戻り値
+    ///
+    /// 内容を削除した場合は`true`、保存されていなかった場合は`false`
+    pub fn delete(&self, digest: &BlobDigest) -> io::Result<bool> {
+        let Some(dir) = &self.dir else {
+            return Ok(lock(&self.memory).remove(digest).is_some());
+        };
+        match fs::remove_file(Self::path(dir, digest)) {
+            Ok(()) => {
+                tracing::info!(%digest, "添付ファイルの内容を削除しました。");
+                Ok(true)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// 保存されているすべての内容を、ダイジェスト順に返す。
+    pub fn list(&self) -> io::Result<Vec<StoredBlob>> {
+        let Some(dir) = &self.dir else {
+            return Ok(lock(&self.memory)
+                .iter()
+                .map(|(digest, content)| StoredBlob {
+                    digest: digest.clone(),
+                    size: content.len() as u64,
+                })
+                .collect());
+        };
+        let mut blobs = vec![];
+        for prefix in fs::read_dir(dir)? {
+            let prefix = prefix?;
+            if !prefix.file_type()?.is_dir() || prefix.file_name() == PARTIAL_DIR_NAME {
+                continue;
+            }
+            for entry in fs::read_dir(prefix.path())? {
+                let entry = entry?;
+                let Some(digest) = entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| BlobDigest::try_from(name).ok())
+                else {
+                    continue;
+                };
+                blobs.push(StoredBlob {
+                    digest,
+                    size: self.content_size(&entry.path(), entry.metadata()?.len())?,
+                });
+            }
+        }
+        blobs.sort_by(|a, b| a.digest.cmp(&b.digest));
+
+        Ok(blobs)
+    }
+
+    /// 現在の鍵で暗号化されていない内容を、現在の鍵で暗号化し直す。
+    ///
+    /// 暗号化し直している内容が削除されないように、[`BlobStore::share`]のロックを保持したまま呼び出す。
+    /// 復号できない内容はログに記録して、暗号化し直さずに残す。
+    ///
+    /// # 戻り値
+    ///
+    /// 暗号化し直した内容の数
+    pub fn reencrypt(&self) -> io::Result<usize> {
+        let Some(dir) = &self.dir else {
+            return Ok(0);
+        };
+        if !self.encryption.is_enabled() {
+            return Ok(0);
+        }
+        let mut reencrypted = 0;
+        for blob in self.list()? {
+            let content = match fs::read(Self::path(dir, &blob.digest)) {
+                Ok(content) => content,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            let (content, sealing) = match self.encryption.open(blob.digest.as_str(), &content) {
+                Ok(opened) => opened,
+                Err(e) => {
+                    tracing::error!(digest = %blob.digest, error = %e, "添付ファイルの内容を復号できません。");
+                    continue;
+                }
+            };
+            if self.encryption.is_stale(sealing) {
+                self.write(dir, &blob.digest, &content)?;
+                reencrypted += 1;
+            }
+        }
+
+        Ok(reencrypted)
+    }
+
+    /// どの添付ファイルからも参照されていない内容を返す。
+    ///
+    /// [`BlobStore::exclusive`]のロックを保持したまま、参照数を取得してから呼び出す。
+    ///
+    /// # 引数
+    ///
+    /// * `referenced` - 添付ファイルが参照している内容ごとの、参照している添付ファイルの数
+    ///
+    /// # 戻り値
+    ///
+    /// 参照されていない内容
+    pub fn orphans(&self, referenced: &BTreeMap<BlobDigest, usize>) -> io::Result<Vec<StoredBlob>> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(|blob| !referenced.contains_key(&blob.digest))
+            .collect())
+    }
+}
+
+/// 添付ファイルの内容から、MIMEタイプを判定する。
+///
+/// 利用者が指定したMIMEタイプは信頼せず、先頭のバイト列で判定する。
+/// 判定できないUTF-8のテキストは`text/plain`、それ以外は`application/octet-stream`とする。
+///
+/// # 引数
+///
+/// * `content` - 添付ファイルの内容
+///
+/// # 戻り値
+///
+/// MIMEタイプ
+pub fn sniff_content_type(content: &[u8]) -> &'static str {
+    const SIGNATURES: [(&[u8], &str); 8] = [
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"\x00\x00\x01\x00", "image/x-icon"),
+    ];
+    if let Some((_, content_type)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| content.starts_with(signature))
+    {
+        return content_type;
+    }
+    if content.len() >= 12 && &content[..4] == b"RIFF" && &content[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+    match std::str::from_utf8(content) {
+        Ok(text) if !text.contains('\0') => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// 利用者が指定したファイル名から、ディレクトリと制御文字を取り除く。
+///
+/// # 引数
+///
+/// * `file_name` - 利用者が指定したファイル名
+///
+/// # 戻り値
+///
+/// 取り除いた後のファイル名、空になった場合は`attachment`
+pub fn sanitize_file_name(file_name: &str) -> String {
+    let base = file_name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let sanitized: String = base.chars().filter(|c| !c.is_control()).collect();
+    match sanitized.trim() {
+        "" | "." | ".." => DEFAULT_FILE_NAME.to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// 添付ファイルをダウンロードさせる`Content-Disposition`ヘッダの値を返す。
+///
+/// ASCII以外の文字を含むファイル名は、RFC 5987の形式でパーセントエンコードした`filename*`で伝える。
+///
+/// # 引数
+///
+/// * `file_name` - サニタイズ済みのファイル名
+///
+/// # 戻り値
+///
+/// `Content-Disposition`ヘッダの値
+pub fn content_disposition(file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+    let mut encoded = String::new();
+    for byte in file_name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+/// `Range`ヘッダを解析して、読み込むバイトの範囲を返す。
+///
+/// `bytes=0-99`、`bytes=100-`、`bytes=-100`のような、1つの範囲の指定だけに対応する。
+///
+/// # 引数
+///
+/// * `header` - `Range`ヘッダの値
+/// * `size` - 内容のバイト数
+///
+/// # 戻り値
+///
+/// 読み込むバイトの範囲、範囲が内容に含まれない場合は[`RangeNotSatisfiable`]
+pub fn parse_range(header: &str, size: u64) -> Result<Range<u64>, RangeNotSatisfiable> {
+    let spec = header
+        .trim()
+        .strip_prefix("bytes=")
+        .ok_or(RangeNotSatisfiable)?;
+    if spec.contains(',') {
+        return Err(RangeNotSatisfiable);
+    }
+    let (start, end) = spec.split_once('-').ok_or(RangeNotSatisfiable)?;
+    let parse = |s: &str| s.trim().parse::<u64>().map_err(|_| RangeNotSatisfiable);
+    let range = match (start.trim(), end.trim()) {
+        ("", "") => return Err(RangeNotSatisfiable),
+        ("", suffix) => size.saturating_sub(parse(suffix)?)..size,
+        (start, "") => parse(start)?..size,
+        (start, end) => parse(start)?..parse(end)?.saturating_add(1).min(size),
+    };
+    if range.start >= range.end {
+        return Err(RangeNotSatisfiable);
+    }
+
+    Ok(range)
+}
+
+/// `Range`ヘッダの範囲が、内容に含まれない
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("指定された範囲は、添付ファイルの内容に含まれません。")]
+pub struct RangeNotSatisfiable;
+
+/// 添付ファイルエラー
+#[derive(Debug, thiserror::Error)]
+pub enum AttachmentError {
+    #[error("添付ファイルは{0}バイト以下です。")]
+    TooLarge(u64),
+    #[error("`file`フィールドに添付ファイルを指定してください。")]
+    MissingFile,
+    #[error("マルチパートのリクエストボディを読み込めません: {0}")]
+    Multipart(#[from] axum::extract::multipart::MultipartError),
+    #[error("添付ファイルの内容が見つかりません。")]
+    MissingBlob,
+    #[error(transparent)]
+    Range(#[from] RangeNotSatisfiable),
+    #[error("添付ファイルの内容を読み書きできません: {0}")]
+    Io(#[from] io::Error),
+}
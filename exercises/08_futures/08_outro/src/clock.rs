@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+
+/// 現在日時を提供する時計
+///
+/// チケットストアは、チケットの作成日時や更新日時、期限の判定にこの時計を使用する。
+/// テストでは[`ManualClock`]を注入すると、日時に依存する振る舞いを決定的に検証できる。
+pub trait Clock: Debug + Send + Sync {
+    /// 現在日時を返す。
+    fn now(&self) -> DateTime<Utc>;
+
+    /// 現在日時の日付（UTC）を返す。
+    fn today(&self) -> NaiveDate {
+        self.now().date_naive()
+    }
+}
+
+/// システム時計
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// 手動で進める時計
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    /// 指定した日時を指す時計を構築する。
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    /// 時計が指す日時を設定する。
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// 時計を指定した時間だけ進める。
+    pub fn advance(&self, delta: TimeDelta) {
+        *self.now.lock().unwrap() += delta;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// 変更操作の間だけ日時を固定する時計
+///
+/// 複製では、リーダーとフォロワーが同じ日時で変更操作を適用できるように、
+/// 変更操作を処理する間は記録した日時に固定する。固定していない間は、元の時計の日時を返す。
+/// 異なるプロジェクトの変更操作は異なるスレッドで並行して処理するため、日時はスレッドごとに固定する。
+#[derive(Debug)]
+pub struct PinnedClock {
+    clock: Arc<dyn Clock>,
+    pinned: Mutex<HashMap<ThreadId, DateTime<Utc>>>,
+}
+
+impl PinnedClock {
+    /// 元の時計の日時を返す時計を構築する。
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            pinned: Mutex::default(),
+        }
+    }
+
+    /// 呼び出したスレッドから見た時計を、指定した日時に固定する。
+    pub fn pin(&self, at: DateTime<Utc>) {
+        self.pinned
+            .lock()
+            .unwrap()
+            .insert(thread::current().id(), at);
+    }
+
+    /// 呼び出したスレッドから見た時計の固定を解除する。
+    pub fn unpin(&self) {
+        self.pinned.lock().unwrap().remove(&thread::current().id());
+    }
+}
+
+impl Clock for PinnedClock {
+    fn now(&self) -> DateTime<Utc> {
+        let pinned = self
+            .pinned
+            .lock()
+            .unwrap()
+            .get(&thread::current().id())
+            .copied();
+        pinned.unwrap_or_else(|| self.clock.now())
+    }
+}
@@ -0,0 +1,795 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::clock::{Clock, ManualClock, PinnedClock};
+use crate::dto::{TicketDraft, TicketPatch};
+use crate::models::{Project, Ticket, TicketId};
+use crate::persistence::Snapshot;
+use crate::raft::{
+    Entry, Envelope, HardState, Message, NodeId, Payload, Raft, RaftConfig, RaftError, Role,
+};
+use crate::store::{TicketStore, TicketStoreError};
+
+/// クラスタを構築できるノード数
+pub const CLUSTER_SIZES: [usize; 2] = [3, 5];
+
+/// シミュレーターが1ティックで進める時計の時間（ミリ秒）
+const TICK_MILLIS: i64 = 10;
+
+/// シミュレーターが操作の完了を待つ最大のティック数
+const MAX_WAIT_TICKS: usize = 1000;
+
+/// Raftのログで複製する、チケットストアの変更コマンド
+///
+/// コマンドを提案したリーダーの日時を持ち、各ノードはその日時でチケットストアに適用する。
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all_fields = "camelCase")]
+pub enum TicketCommand {
+    AddTicket {
+        draft: TicketDraft,
+        occurred_at: DateTime<Utc>,
+    },
+    UpdateTicket {
+        id: TicketId,
+        patch: TicketPatch,
+        occurred_at: DateTime<Utc>,
+    },
+}
+
+/// クラスタのノード間のメッセージ
+///
+/// Raftのスナップショットには、チケットストアのスナップショットを持つ。
+pub type ClusterEnvelope = Envelope<TicketCommand, Snapshot>;
+
+/// クラスタのノードが停止しても失ってはならない状態
+pub type ClusterHardState = HardState<TicketCommand, Snapshot>;
+
+/// ノードに提案したコマンド
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Proposal {
+    /// コマンドを追加したログの位置
+    pub index: u64,
+    /// コマンドを追加したリーダーの項
+    pub term: u64,
+}
+
+/// クラスタの1つのノード
+///
+/// Raftのログでコミットした変更コマンドを、ログの順番にノードのチケットストアに適用する。
+/// 同じログを適用したノードのチケットストアは、チケットIDや日時を含めて同じ状態になる。
+///
+/// [`RaftConfig::snapshot_entries`]のエントリを適用するたびに、チケットストアのスナップショットでログを切り詰める。
+/// スナップショットはドメインイベントの履歴を含まないため、スナップショットから復元したノードの履歴は、
+/// スナップショットより後の変更だけになる。
+#[derive(Debug)]
+pub struct ClusterNode {
+    raft: Raft<TicketCommand, Snapshot>,
+    store: TicketStore,
+    /// 変更コマンドを提案した日時と、適用するときに固定する日時の時計
+    clock: Arc<PinnedClock>,
+    /// このノードに提案した、まだ適用していない変更コマンドの項
+    proposals: BTreeMap<u64, u64>,
+    /// このノードに提案した変更コマンドの結果
+    outcomes: BTreeMap<u64, ClusterResult<TicketId>>,
+    /// このノードに提案した、まだコミットしていないメンバーの変更の項
+    changes: BTreeMap<u64, u64>,
+    /// このノードに提案したメンバーの変更の結果
+    change_outcomes: BTreeMap<u64, ClusterResult<()>>,
+    /// 確認が終わった読み込みの、読み込める位置
+    reads: BTreeMap<u64, Result<u64, RaftError>>,
+    /// スナップショットでログを切り詰めるまでに適用するエントリの数
+    snapshot_entries: u64,
+}
+
+impl ClusterNode {
+    /// ノードを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - ノードID
+    /// * `members` - 最初のメンバーの一覧、既存のクラスタに追加する場合は空
+    /// * `config` - Raftの設定
+    /// * `seed` - 選挙タイムアウトを選ぶ乱数の種
+    /// * `clock` - 変更コマンドを提案した日時に使用する時計
+    ///
+    /// # 戻り値
+    ///
+    /// 空のチケットストアを持つノード
+    pub fn new(
+        id: NodeId,
+        members: impl IntoIterator<Item = NodeId>,
+        config: RaftConfig,
+        seed: u64,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self::with_raft(Raft::new(id, members, config, seed), clock)
+    }
+
+    /// 停止する前のRaftの状態からノードを再構築する。
+    ///
+    /// チケットストアはRaftのスナップショットから構築し直し、その後のコミットしたエントリを再び適用して復元する。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - ノードID
+    /// * `hard_state` - 停止する前の[`ClusterNode::hard_state`]
+    /// * `config` - Raftの設定
+    /// * `seed` - 選挙タイムアウトを選ぶ乱数の種
+    /// * `clock` - 変更コマンドを提案した日時に使用する時計
+    ///
+    /// # 戻り値
+    ///
+    /// ノード
+    pub fn recover(
+        id: NodeId,
+        hard_state: ClusterHardState,
+        config: RaftConfig,
+        seed: u64,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self::with_raft(Raft::recover(id, hard_state, config, seed), clock)
+    }
+
+    fn with_raft(raft: Raft<TicketCommand, Snapshot>, clock: Arc<dyn Clock>) -> Self {
+        let clock = Arc::new(PinnedClock::new(clock));
+        let store = TicketStore::from_snapshot(Project::default(), raft.snapshot().data.clone())
+            .with_clock(clock.clone());
+        let snapshot_entries = raft.config().snapshot_entries;
+
+        Self {
+            raft,
+            store,
+            clock,
+            proposals: BTreeMap::new(),
+            outcomes: BTreeMap::new(),
+            changes: BTreeMap::new(),
+            change_outcomes: BTreeMap::new(),
+            reads: BTreeMap::new(),
+            snapshot_entries,
+        }
+    }
+
+    /// ノードIDを返す。
+    pub fn id(&self) -> NodeId {
+        self.raft.id()
+    }
+
+    /// Raftのノードを返す。
+    pub fn raft(&self) -> &Raft<TicketCommand, Snapshot> {
+        &self.raft
+    }
+
+    /// チケットストアを返す。
+    ///
+    /// チケットストアから直接読み込んだ結果は、他のノードがすでにコミットした変更を反映していないことがある。
+    /// 最新の状態を読み込む場合は、[`ClusterNode::read_index`]を使用する。
+    pub fn store(&self) -> &TicketStore {
+        &self.store
+    }
+
+    /// 停止しても失ってはならないRaftの状態を返す。
+    pub fn hard_state(&self) -> ClusterHardState {
+        self.raft.hard_state()
+    }
+
+    /// まだ永続化していないRaftのエントリを返す。
+    ///
+    /// # 戻り値
+    ///
+    /// 最初のエントリの位置と、永続化していないエントリ
+    pub fn unstable_entries(&self) -> (u64, &[Entry<TicketCommand>]) {
+        self.raft.unstable_entries()
+    }
+
+    /// 指定した位置までのRaftのエントリを永続化したことを知らせる。
+    pub fn stabilize(&mut self, index: u64) {
+        self.raft.stabilize(index);
+    }
+
+    /// 時間を1ティック進める。
+    pub fn tick(&mut self) {
+        self.raft.tick();
+        self.apply();
+    }
+
+    /// 受信したメッセージを処理する。
+    pub fn step(&mut self, from: NodeId, message: Message<TicketCommand, Snapshot>) {
+        self.raft.step(from, message);
+        self.apply();
+    }
+
+    /// 送信するメッセージを取り出す。
+    pub fn take_messages(&mut self) -> Vec<ClusterEnvelope> {
+        self.raft.take_messages()
+    }
+
+    /// チケットの登録を提案する。
+    ///
+    /// # 引数
+    ///
+    /// * `draft` - 登録するチケットのドラフト
+    ///
+    /// # 戻り値
+    ///
+    /// 提案したコマンド、このノードがリーダーでない場合は[`RaftError::NotLeader`]
+    pub fn propose_add(&mut self, draft: TicketDraft) -> ClusterResult<Proposal> {
+        let occurred_at = self.clock.now();
+        self.propose(TicketCommand::AddTicket { draft, occurred_at })
+    }
+
+    /// チケットの更新を提案する。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - 更新するチケットのチケットID
+    /// * `patch` - チケットのパッチ
+    ///
+    /// # 戻り値
+    ///
+    /// 提案したコマンド、このノードがリーダーでない場合は[`RaftError::NotLeader`]
+    pub fn propose_update(&mut self, id: TicketId, patch: TicketPatch) -> ClusterResult<Proposal> {
+        let occurred_at = self.clock.now();
+        self.propose(TicketCommand::UpdateTicket {
+            id,
+            patch,
+            occurred_at,
+        })
+    }
+
+    fn propose(&mut self, command: TicketCommand) -> ClusterResult<Proposal> {
+        let index = self.raft.propose(command)?;
+        let term = self.raft.term();
+        self.proposals.insert(index, term);
+        self.apply();
+
+        Ok(Proposal { index, term })
+    }
+
+    /// 提案したコマンドを適用した結果を取り出す。
+    ///
+    /// # 引数
+    ///
+    /// * `proposal` - このノードに提案したコマンド
+    ///
+    /// # 戻り値
+    ///
+    /// 登録または更新したチケットのチケットID、まだ適用していない場合は`None`
+    pub fn take_outcome(&mut self, proposal: Proposal) -> Option<ClusterResult<TicketId>> {
+        self.outcomes.remove(&proposal.index)
+    }
+
+    /// メンバーを1ノード追加または削除する。
+    ///
+    /// # 引数
+    ///
+    /// * `members` - 変更後のメンバーの一覧
+    ///
+    /// # 戻り値
+    ///
+    /// 提案した変更、このノードがリーダーでない場合は[`RaftError::NotLeader`]
+    pub fn change_membership(&mut self, members: BTreeSet<NodeId>) -> ClusterResult<Proposal> {
+        let index = self.raft.change_membership(members)?;
+        let term = self.raft.term();
+        self.changes.insert(index, term);
+        self.apply();
+
+        Ok(Proposal { index, term })
+    }
+
+    /// 提案したメンバーの変更をコミットした結果を取り出す。
+    ///
+    /// # 引数
+    ///
+    /// * `proposal` - このノードに提案したメンバーの変更
+    ///
+    /// # 戻り値
+    ///
+    /// `()`、まだコミットしていない場合は`None`
+    pub fn take_change_outcome(&mut self, proposal: Proposal) -> Option<ClusterResult<()>> {
+        self.change_outcomes.remove(&proposal.index)
+    }
+
+    /// 線形化可能な読み込みを要求する。
+    ///
+    /// # 戻り値
+    ///
+    /// 読み込みID、このノードがリーダーでない場合は[`RaftError::NotLeader`]
+    pub fn read_index(&mut self) -> ClusterResult<u64> {
+        let id = self.raft.read_index()?;
+        self.apply();
+
+        Ok(id)
+    }
+
+    /// 確認が終わった読み込みで、チケットを取得する。
+    ///
+    /// # 引数
+    ///
+    /// * `read` - [`ClusterNode::read_index`]が返した読み込みID
+    /// * `id` - チケットID
+    ///
+    /// # 戻り値
+    ///
+    /// チケット、読み込みの確認が終わっていないか、読み込める位置まで適用していない場合は`None`
+    pub fn read_ticket(&mut self, read: u64, id: TicketId) -> Option<ClusterResult<Ticket>> {
+        let index = match self.reads.get(&read)? {
+            Ok(index) => *index,
+            Err(e) => {
+                let e = e.clone();
+                self.reads.remove(&read);
+                return Some(Err(e.into()));
+            }
+        };
+        if self.raft.commit_index() < index {
+            return None;
+        }
+        self.reads.remove(&read);
+
+        Some(self.store.get(id).map_err(ClusterError::from))
+    }
+
+    /// コミットしたエントリをチケットストアに適用し、確認が終わった読み込みを記録する。
+    ///
+    /// リーダーからスナップショットを受け取った場合は、先にチケットストアをスナップショットの状態に置き換える。
+    /// 適用したエントリが[`RaftConfig::snapshot_entries`]に達した場合は、ログを切り詰める。
+    fn apply(&mut self) {
+        if let Some(snapshot) = self.raft.take_snapshot() {
+            self.store = TicketStore::from_snapshot(Project::default(), snapshot.data)
+                .with_clock(self.clock.clone());
+            // スナップショットに含まれるコマンドを適用した結果は、このノードではわからない。
+            let compacted: Vec<u64> = self
+                .proposals
+                .range(..=snapshot.index)
+                .map(|(index, _)| *index)
+                .collect();
+            for index in compacted {
+                self.proposals.remove(&index);
+                self.outcomes.insert(index, Err(ClusterError::Compacted));
+            }
+            let compacted: Vec<u64> = self
+                .changes
+                .range(..=snapshot.index)
+                .map(|(index, _)| *index)
+                .collect();
+            for index in compacted {
+                self.changes.remove(&index);
+                self.change_outcomes
+                    .insert(index, Err(ClusterError::Compacted));
+            }
+        }
+        for (index, entry) in self.raft.take_committed() {
+            if let Some(term) = self.changes.remove(&index) {
+                let outcome = match &entry.payload {
+                    Payload::Membership(_) if term == entry.term => Ok(()),
+                    _ => Err(ClusterError::Superseded),
+                };
+                self.change_outcomes.insert(index, outcome);
+            }
+            let result = match entry.payload {
+                Payload::Command(command) => Some(self.execute(command)),
+                Payload::Noop | Payload::Membership(_) => None,
+            };
+            let Some(term) = self.proposals.remove(&index) else {
+                continue;
+            };
+            // 他のリーダーのエントリで置き換えられたかどうかは、その位置をコミットするまでわからない。
+            let outcome = match result {
+                Some(result) if term == entry.term => result,
+                _ => Err(ClusterError::Superseded),
+            };
+            self.outcomes.insert(index, outcome);
+        }
+        for read in self.raft.take_reads() {
+            self.reads.insert(read.id, read.result);
+        }
+        let applied = self.raft.commit_index();
+        if applied - self.raft.snapshot().index >= self.snapshot_entries {
+            self.raft.compact(applied, self.store.snapshot());
+        }
+    }
+
+    /// 変更コマンドを、提案した日時でチケットストアに適用する。
+    fn execute(&mut self, command: TicketCommand) -> ClusterResult<TicketId> {
+        let result = match command {
+            TicketCommand::AddTicket { draft, occurred_at } => {
+                self.clock.pin(occurred_at);
+                self.store.add_ticket(draft)
+            }
+            TicketCommand::UpdateTicket {
+                id,
+                patch,
+                occurred_at,
+            } => {
+                self.clock.pin(occurred_at);
+                self.store.update_ticket(id, patch).map(|()| id)
+            }
+        };
+        self.clock.unpin();
+
+        Ok(result?)
+    }
+}
+
+/// シミュレーターのノードの状態
+#[derive(Debug)]
+struct SimulatedNode {
+    /// 動作しているノード、停止している場合は`None`
+    node: Option<ClusterNode>,
+    /// 停止したときのRaftの状態
+    hard_state: ClusterHardState,
+}
+
+/// クラスタを1つのスレッドで決定的に動かすネットワークシミュレーター
+///
+/// 1ティックごとに、前のティックで送信されたメッセージを送信元のノードID順に配送してから、
+/// すべてのノードの時間を進める。ノード間のメッセージは、ネットワークの分断と停止したノードへの送信を除いて失われない。
+/// 同じ種から構築して同じ操作を行えば、常に同じ結果になる。
+#[derive(Debug)]
+pub struct Simulator {
+    nodes: BTreeMap<NodeId, SimulatedNode>,
+    in_flight: Vec<ClusterEnvelope>,
+    /// 分断されたネットワークでのノードのグループ、分断されていない場合は空
+    groups: BTreeMap<NodeId, usize>,
+    config: RaftConfig,
+    seed: u64,
+    clock: Arc<ManualClock>,
+}
+
+impl Simulator {
+    /// ノードIDが1から始まるクラスタを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `size` - ノード数（3または5）
+    /// * `seed` - 選挙タイムアウトを選ぶ乱数の種
+    ///
+    /// # 戻り値
+    ///
+    /// リーダーが選ばれていないクラスタ
+    pub fn new(size: usize, seed: u64) -> ClusterResult<Self> {
+        Self::with_config(size, seed, RaftConfig::default())
+    }
+
+    /// Raftの設定を指定して、ノードIDが1から始まるクラスタを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `size` - ノード数（3または5）
+    /// * `seed` - 選挙タイムアウトを選ぶ乱数の種
+    /// * `config` - Raftの設定
+    ///
+    /// # 戻り値
+    ///
+    /// リーダーが選ばれていないクラスタ
+    pub fn with_config(size: usize, seed: u64, config: RaftConfig) -> ClusterResult<Self> {
+        if !CLUSTER_SIZES.contains(&size) {
+            return Err(ClusterError::InvalidSize(size));
+        }
+        let epoch = DateTime::from_timestamp(1_700_000_000, 0).expect("有効な日時");
+        let clock = Arc::new(ManualClock::new(epoch));
+        let members: Vec<NodeId> = (1..=size as NodeId).collect();
+        let nodes = members
+            .iter()
+            .map(|&id| {
+                let node = ClusterNode::new(id, members.clone(), config, seed, clock.clone());
+                let hard_state = node.hard_state();
+                let node = SimulatedNode {
+                    node: Some(node),
+                    hard_state,
+                };
+                (id, node)
+            })
+            .collect();
+
+        Ok(Self {
+            nodes,
+            in_flight: vec![],
+            groups: BTreeMap::new(),
+            config,
+            seed,
+            clock,
+        })
+    }
+
+    /// 動作しているノードを返す。
+    pub fn node(&self, id: NodeId) -> Option<&ClusterNode> {
+        self.nodes.get(&id)?.node.as_ref()
+    }
+
+    /// 動作しているノードを返す。
+    pub fn node_mut(&mut self, id: NodeId) -> Option<&mut ClusterNode> {
+        self.nodes.get_mut(&id)?.node.as_mut()
+    }
+
+    /// 動作しているノードのノードIDを返す。
+    pub fn live_nodes(&self) -> Vec<NodeId> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| node.node.is_some())
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// 動作しているノードのうち、最も新しい項のリーダーを返す。
+    pub fn leader(&self) -> Option<NodeId> {
+        self.nodes
+            .values()
+            .filter_map(|node| node.node.as_ref())
+            .filter(|node| node.raft().role() == Role::Leader)
+            .max_by_key(|node| node.raft().term())
+            .map(ClusterNode::id)
+    }
+
+    /// 時間を1ティック進める。
+    pub fn tick(&mut self) {
+        self.clock.advance(TimeDelta::milliseconds(TICK_MILLIS));
+        for envelope in std::mem::take(&mut self.in_flight) {
+            if !self.connected(envelope.from, envelope.to) {
+                continue;
+            }
+            if let Some(node) = self.node_mut(envelope.to) {
+                node.step(envelope.from, envelope.message);
+            }
+        }
+        for node in self.nodes.values_mut() {
+            if let Some(node) = node.node.as_mut() {
+                node.tick();
+            }
+        }
+        for node in self.nodes.values_mut() {
+            if let Some(node) = node.node.as_mut() {
+                self.in_flight.extend(node.take_messages());
+            }
+        }
+    }
+
+    /// 時間を指定したティック数だけ進める。
+    pub fn run(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.tick();
+        }
+    }
+
+    /// 条件を満たすまで時間を進める。
+    ///
+    /// # 戻り値
+    ///
+    /// 条件を満たした場合は`true`、[`MAX_WAIT_TICKS`]ティック以内に満たさなかった場合は`false`
+    pub fn run_until(&mut self, mut condition: impl FnMut(&Self) -> bool) -> bool {
+        for _ in 0..MAX_WAIT_TICKS {
+            if condition(self) {
+                return true;
+            }
+            self.tick();
+        }
+
+        condition(self)
+    }
+
+    /// リーダーが選ばれるまで時間を進める。
+    pub fn elect_leader(&mut self) -> ClusterResult<NodeId> {
+        self.run_until(|sim| sim.leader().is_some());
+        self.leader().ok_or(ClusterError::Timeout)
+    }
+
+    /// ネットワークを分断する。
+    ///
+    /// 同じグループのノード同士だけが通信でき、どのグループにも含まれないノードは他のすべてのノードと通信できない。
+    ///
+    /// # 引数
+    ///
+    /// * `groups` - ノードのグループ
+    pub fn partition(&mut self, groups: &[&[NodeId]]) {
+        self.groups = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(group, ids)| ids.iter().map(move |id| (*id, group)))
+            .collect();
+        tracing::debug!(?groups, "ネットワークを分断しました。");
+    }
+
+    /// ネットワークの分断を解消する。
+    pub fn heal(&mut self) {
+        self.groups.clear();
+    }
+
+    /// ノードを停止する。
+    ///
+    /// Raftの状態は残し、チケットストアとメモリ上の状態は失う。
+    pub fn crash(&mut self, id: NodeId) {
+        if let Some(node) = self.nodes.get_mut(&id) {
+            if let Some(crashed) = node.node.take() {
+                node.hard_state = crashed.hard_state();
+            }
+        }
+    }
+
+    /// 停止したノードを、停止する前のRaftの状態から再起動する。
+    pub fn restart(&mut self, id: NodeId) {
+        let (config, seed, clock) = (self.config, self.seed, self.clock.clone());
+        if let Some(node) = self.nodes.get_mut(&id) {
+            if node.node.is_none() {
+                let hard_state = node.hard_state.clone();
+                node.node = Some(ClusterNode::recover(id, hard_state, config, seed, clock));
+            }
+        }
+    }
+
+    /// ノードをクラスタに追加する。
+    ///
+    /// 新しいノードを起動してから、リーダーにメンバーの追加を提案し、コミットするまで待つ。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - 追加するノードのノードID
+    pub fn add_node(&mut self, id: NodeId) -> ClusterResult<()> {
+        let node = ClusterNode::new(id, [], self.config, self.seed, self.clock.clone());
+        let hard_state = node.hard_state();
+        self.nodes.insert(
+            id,
+            SimulatedNode {
+                node: Some(node),
+                hard_state,
+            },
+        );
+        self.change_membership(|members| {
+            members.insert(id);
+        })
+    }
+
+    /// ノードをクラスタから削除する。
+    ///
+    /// リーダーにメンバーの削除を提案してコミットするまで待ってから、ノードを停止する。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - 削除するノードのノードID
+    pub fn remove_node(&mut self, id: NodeId) -> ClusterResult<()> {
+        self.change_membership(|members| {
+            members.remove(&id);
+        })?;
+        self.crash(id);
+        self.nodes.remove(&id);
+
+        Ok(())
+    }
+
+    /// リーダーにメンバーの変更を提案して、コミットするまで待つ。
+    fn change_membership(
+        &mut self,
+        change: impl FnOnce(&mut BTreeSet<NodeId>),
+    ) -> ClusterResult<()> {
+        let leader = self.elect_leader()?;
+        // 新しいリーダーは、自身の項のエントリをコミットするまでメンバーを変更できない。
+        self.run_until(|sim| {
+            sim.node(leader)
+                .is_some_and(|node| node.raft().commit_index() == node.raft().last_index())
+        });
+        let node = self.node_mut(leader).ok_or(ClusterError::Timeout)?;
+        let mut members = node.raft().members().clone();
+        change(&mut members);
+        let index = node.change_membership(members)?.index;
+        let committed = self.run_until(|sim| {
+            sim.nodes
+                .values()
+                .filter_map(|node| node.node.as_ref())
+                .any(|node| node.raft().commit_index() >= index)
+        });
+        if committed {
+            Ok(())
+        } else {
+            Err(ClusterError::Timeout)
+        }
+    }
+
+    /// ノードにチケットの登録を提案して、適用されるまで待つ。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - 提案するノードのノードID
+    /// * `draft` - 登録するチケットのドラフト
+    ///
+    /// # 戻り値
+    ///
+    /// 登録したチケットのチケットID
+    pub fn add_ticket(&mut self, id: NodeId, draft: TicketDraft) -> ClusterResult<TicketId> {
+        let proposal = self.live(id)?.propose_add(draft)?;
+        self.wait_outcome(id, proposal)
+    }
+
+    /// ノードにチケットの更新を提案して、適用されるまで待つ。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - 提案するノードのノードID
+    /// * `ticket` - 更新するチケットのチケットID
+    /// * `patch` - チケットのパッチ
+    ///
+    /// # 戻り値
+    ///
+    /// 更新したチケットのチケットID
+    pub fn update_ticket(
+        &mut self,
+        id: NodeId,
+        ticket: TicketId,
+        patch: TicketPatch,
+    ) -> ClusterResult<TicketId> {
+        let proposal = self.live(id)?.propose_update(ticket, patch)?;
+        self.wait_outcome(id, proposal)
+    }
+
+    /// ノードで、チケットを線形化可能に読み込む。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - 読み込むノードのノードID
+    /// * `ticket` - チケットID
+    ///
+    /// # 戻り値
+    ///
+    /// チケット
+    pub fn read_ticket(&mut self, id: NodeId, ticket: TicketId) -> ClusterResult<Ticket> {
+        let read = self.live(id)?.read_index()?;
+        for _ in 0..MAX_WAIT_TICKS {
+            if let Some(result) = self.live(id)?.read_ticket(read, ticket) {
+                return result;
+            }
+            self.tick();
+        }
+
+        Err(ClusterError::Timeout)
+    }
+
+    /// 提案したコマンドが適用されるまで時間を進める。
+    fn wait_outcome(&mut self, id: NodeId, proposal: Proposal) -> ClusterResult<TicketId> {
+        for _ in 0..MAX_WAIT_TICKS {
+            let node = self.live(id)?;
+            if let Some(outcome) = node.take_outcome(proposal) {
+                return outcome;
+            }
+            // リーダーを辞めたノードも、新しいリーダーからその位置のエントリを受け取って結果を知る。
+            self.tick();
+        }
+
+        Err(ClusterError::Timeout)
+    }
+
+    fn live(&mut self, id: NodeId) -> ClusterResult<&mut ClusterNode> {
+        self.node_mut(id).ok_or(ClusterError::NodeDown(id))
+    }
+
+    /// 2つのノードが通信できるか確認する。
+    fn connected(&self, a: NodeId, b: NodeId) -> bool {
+        self.groups.is_empty()
+            || matches!(
+                (self.groups.get(&a), self.groups.get(&b)),
+                (Some(x), Some(y)) if x == y
+            )
+    }
+}
+
+/// クラスタのエラー
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ClusterError {
+    #[error(transparent)]
+    Raft(#[from] RaftError),
+    #[error(transparent)]
+    Store(#[from] TicketStoreError),
+    #[error("コマンドは、他のリーダーのエントリに置き換えられました。")]
+    Superseded,
+    #[error("コマンドを適用した結果は、リーダーのスナップショットに含まれるためわかりません。")]
+    Compacted,
+    #[error("クラスタのノード数は3または5です: {0}")]
+    InvalidSize(usize),
+    #[error("ノード{0}は停止しています。")]
+    NodeDown(NodeId),
+    #[error("ノード{0}から接続されていないため、アドレスがわかりません。")]
+    UnknownPeer(NodeId),
+    #[error("クラスタが時間内に応答しませんでした。")]
+    Timeout,
+}
+
+/// クラスタの結果
+pub type ClusterResult<T> = Result<T, ClusterError>;
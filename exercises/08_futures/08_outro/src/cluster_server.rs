@@ -0,0 +1,867 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode, Uri};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::{delete, get, post};
+use axum::{middleware, Json, Router};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
+use uuid::Uuid;
+
+use crate::clock::Clock;
+use crate::cluster::{ClusterEnvelope, ClusterError, ClusterNode, ClusterResult, Proposal};
+use crate::config::{ClusterAdvertise, ClusterConfig, ClusterPeer, Config, StorageBackend};
+use crate::dto::{TicketDraft, TicketPatch};
+use crate::middleware::{require_admin, AdminToken};
+use crate::models::{ProjectKey, Ticket, TicketId, TicketKey, TicketRef};
+use crate::persistence::{self, PersistenceResult, RaftStorage};
+use crate::raft::{Envelope, NodeId, RaftConfig, RaftError, Role};
+use crate::replication::{self, INITIAL_BACKOFF, MAX_BACKOFF};
+use crate::store::TicketStoreError;
+use crate::sync::{lock, read_lock, write_lock};
+
+/// 他のノードから受信した、処理を待つメッセージの最大数
+const INBOUND_CAPACITY: usize = 1024;
+
+/// 1つのノードへの、送信を待つメッセージの最大数
+///
+/// 超えたメッセージは破棄する。失われたメッセージは、Raftがハートビートや再送で補う。
+const OUTBOUND_CAPACITY: usize = 1024;
+
+/// ノードの処理を待つ、HTTPのリクエストの最大数
+const REQUEST_CAPACITY: usize = 1024;
+
+/// ノード間の通信を認証するトークン
+///
+/// 接続するノードに送信するため平文で保持するが、表示するときは伏せる。
+#[derive(Clone, PartialEq, Eq)]
+pub struct ClusterToken(String);
+
+/// ノード間の通信のトークンエラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("ノード間の通信のトークンは、空白を含まない1文字以上の表示可能なASCII文字列です。")]
+pub struct ClusterTokenError;
+
+impl ClusterToken {
+    /// 提示されたトークンが、このトークンと一致するか確認する。
+    ///
+    /// 比較にかかる時間からトークンを推測されないように、ダイジェストを比較する。
+    fn matches(&self, presented: &str) -> bool {
+        Sha256::digest(presented.as_bytes()) == Sha256::digest(self.0.as_bytes())
+    }
+}
+
+/// 文字列からノード間の通信のトークンを構築する。
+///
+/// トークンファイルの末尾の改行などを許容するため、前後の空白は無視する。
+///
+/// # 引数
+///
+/// * `s` - トークン
+///
+/// # 戻り値
+///
+/// ノード間の通信のトークン
+fn cluster_token_from_str(s: &str) -> Result<ClusterToken, ClusterTokenError> {
+    let token = s.trim();
+    if token.is_empty() || !token.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(ClusterTokenError);
+    }
+
+    Ok(ClusterToken(token.into()))
+}
+
+impl TryFrom<&str> for ClusterToken {
+    type Error = ClusterTokenError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        cluster_token_from_str(value)
+    }
+}
+
+impl std::fmt::Debug for ClusterToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ClusterToken(..)")
+    }
+}
+
+/// ノードが他のノードに接続したときに、最初に送信する自己紹介
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PeerHello {
+    /// 送信元のノードID
+    node_id: NodeId,
+    /// ノード間の通信のトークン
+    token: String,
+    /// 送信元のノードのアドレス、知らせない場合は`None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    advertise: Option<ClusterAdvertise>,
+}
+
+/// ノードに依頼する、HTTPのリクエストの処理
+enum Request {
+    Add {
+        draft: TicketDraft,
+        reply: oneshot::Sender<ClusterResult<TicketId>>,
+    },
+    Update {
+        id: TicketId,
+        patch: TicketPatch,
+        reply: oneshot::Sender<ClusterResult<TicketId>>,
+    },
+    Read {
+        id: TicketId,
+        reply: oneshot::Sender<ClusterResult<Ticket>>,
+    },
+    AddMember {
+        id: NodeId,
+        reply: oneshot::Sender<ClusterResult<BTreeSet<NodeId>>>,
+    },
+    RemoveMember {
+        id: NodeId,
+        reply: oneshot::Sender<ClusterResult<BTreeSet<NodeId>>>,
+    },
+}
+
+/// ノードの状態
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterStatus {
+    pub node_id: NodeId,
+    pub role: Role,
+    pub term: u64,
+    /// 現在の項のリーダー、わからない場合は`None`
+    pub leader: Option<NodeId>,
+    pub commit_index: u64,
+    pub members: BTreeSet<NodeId>,
+}
+
+impl ClusterStatus {
+    fn of(node: &ClusterNode) -> Self {
+        let raft = node.raft();
+        Self {
+            node_id: raft.id(),
+            role: raft.role(),
+            term: raft.term(),
+            leader: raft.leader(),
+            commit_index: raft.commit_index(),
+            members: raft.members().clone(),
+        }
+    }
+}
+
+/// 実行しているクラスタのノードのハンドル
+///
+/// チケットの登録と更新はRaftのログでコミットしてから、読み込みはリーダーであることを過半数のノードに確認してから応答する。
+/// このノードがリーダーでない場合は、[`RaftError::NotLeader`]を返す。
+#[derive(Debug, Clone)]
+pub struct ClusterHandle {
+    requests: mpsc::Sender<Request>,
+    status: watch::Receiver<ClusterStatus>,
+    /// 他のノードのURL
+    ///
+    /// 設定した他のノードに加えて、接続してきたノードが知らせたURLを登録する。
+    urls: Arc<RwLock<BTreeMap<NodeId, String>>>,
+}
+
+impl ClusterHandle {
+    /// ノードの状態を返す。
+    pub fn status(&self) -> ClusterStatus {
+        self.status.borrow().clone()
+    }
+
+    /// 他のノードのURLを返す。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - ノードID
+    ///
+    /// # 戻り値
+    ///
+    /// ノードのURL、他のノードでない場合は`None`
+    pub fn url(&self, id: NodeId) -> Option<String> {
+        read_lock(&self.urls).get(&id).cloned()
+    }
+
+    /// チケットを登録する。
+    ///
+    /// # 引数
+    ///
+    /// * `draft` - 登録するチケットのドラフト
+    ///
+    /// # 戻り値
+    ///
+    /// 登録したチケットのチケットID
+    pub async fn add_ticket(&self, draft: TicketDraft) -> ClusterResult<TicketId> {
+        self.request(|reply| Request::Add { draft, reply }).await
+    }
+
+    /// チケットを更新する。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - 更新するチケットのチケットID
+    /// * `patch` - チケットのパッチ
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub async fn update_ticket(&self, id: TicketId, patch: TicketPatch) -> ClusterResult<()> {
+        self.request(|reply| Request::Update { id, patch, reply })
+            .await
+            .map(|_| ())
+    }
+
+    /// 線形化可能な読み込みでチケットを取得する。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - チケットID
+    ///
+    /// # 戻り値
+    ///
+    /// チケット
+    pub async fn read_ticket(&self, id: TicketId) -> ClusterResult<Ticket> {
+        self.request(|reply| Request::Read { id, reply }).await
+    }
+
+    /// ノードをメンバーに追加し、変更をコミットするまで待つ。
+    ///
+    /// 追加するノードは、先に既存のクラスタに追加するノードとして起動し、このノードに接続してアドレスを知らせておく。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - 追加するノードのノードID
+    ///
+    /// # 戻り値
+    ///
+    /// 変更後のメンバーの一覧
+    pub async fn add_member(&self, id: NodeId) -> ClusterResult<BTreeSet<NodeId>> {
+        self.request(|reply| Request::AddMember { id, reply }).await
+    }
+
+    /// ノードをメンバーから削除し、変更をコミットするまで待つ。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - 削除するノードのノードID
+    ///
+    /// # 戻り値
+    ///
+    /// 変更後のメンバーの一覧
+    pub async fn remove_member(&self, id: NodeId) -> ClusterResult<BTreeSet<NodeId>> {
+        self.request(|reply| Request::RemoveMember { id, reply })
+            .await
+    }
+
+    async fn request<T>(
+        &self,
+        request: impl FnOnce(oneshot::Sender<ClusterResult<T>>) -> Request,
+    ) -> ClusterResult<T> {
+        let stopped = || ClusterError::NodeDown(self.status.borrow().node_id);
+        let (reply, outcome) = oneshot::channel();
+        self.requests
+            .send(request(reply))
+            .await
+            .map_err(|_| stopped())?;
+
+        outcome.await.map_err(|_| stopped())?
+    }
+}
+
+/// 他のノードと通信して、クラスタのノードを実行する。
+pub struct ClusterRuntime {
+    node: ClusterNode,
+    listener: TcpListener,
+    peers: Vec<ClusterPeer>,
+    token: ClusterToken,
+    advertise: Option<ClusterAdvertise>,
+    urls: Arc<RwLock<BTreeMap<NodeId, String>>>,
+    tick: Duration,
+    /// Raftの状態を永続化するストレージ、永続化しない場合は`None`
+    storage: Option<Arc<Mutex<RaftStorage>>>,
+    requests: mpsc::Receiver<Request>,
+    status: watch::Sender<ClusterStatus>,
+}
+
+impl ClusterRuntime {
+    /// 設定に従ってノードを構築する。
+    ///
+    /// `file`ストレージバックエンドの場合は、データディレクトリに永続化したRaftの状態からノードを復元する。
+    /// 既存のクラスタに追加するノードは、メンバーを持たずに起動する。
+    ///
+    /// # 引数
+    ///
+    /// * `config` - クラスタモードの検証済みの設定
+    /// * `listener` - ノード間の通信を待ち受けるTCPリスナー
+    /// * `clock` - 変更コマンドを提案した日時に使用する時計
+    ///
+    /// # 戻り値
+    ///
+    /// ノードのハンドルと、[`ClusterRuntime::run`]で実行するノード
+    pub fn open(
+        config: &Config,
+        listener: TcpListener,
+        clock: Arc<dyn Clock>,
+    ) -> PersistenceResult<(ClusterHandle, Self)> {
+        let ClusterConfig {
+            node_id: Some(id),
+            peers,
+            token: Some(token),
+            ..
+        } = &config.cluster
+        else {
+            panic!("クラスタモードの設定にはノードIDとトークンがある");
+        };
+        let (storage, hard_state) = match (config.storage.backend, &config.storage.data_dir) {
+            (StorageBackend::File, Some(data_dir)) => {
+                let encryption = persistence::data_dir_encryption(data_dir, &config.encryption)?;
+                let (storage, hard_state) = RaftStorage::open(data_dir, &encryption)?;
+                (Some(Arc::new(Mutex::new(storage))), hard_state)
+            }
+            _ => (None, None),
+        };
+        let seed = Uuid::new_v4().as_u64_pair().0;
+        let raft = RaftConfig {
+            snapshot_entries: config.cluster.snapshot_entries,
+            ..RaftConfig::default()
+        };
+        let node = match hard_state {
+            Some(hard_state) => ClusterNode::recover(*id, hard_state, raft, seed, clock),
+            None if config.cluster.join => ClusterNode::new(*id, [], raft, seed, clock),
+            None => {
+                let members = peers.iter().map(|peer| peer.id).chain([*id]);
+                ClusterNode::new(*id, members, raft, seed, clock)
+            }
+        };
+        let (requests_tx, requests) = mpsc::channel(REQUEST_CAPACITY);
+        let (status_tx, status) = watch::channel(ClusterStatus::of(&node));
+        let urls = peers
+            .iter()
+            .map(|peer| (peer.id, peer.url.trim_end_matches('/').to_string()))
+            .collect();
+        let urls = Arc::new(RwLock::new(urls));
+        let handle = ClusterHandle {
+            requests: requests_tx,
+            status,
+            urls: Arc::clone(&urls),
+        };
+
+        Ok((
+            handle,
+            Self {
+                node,
+                listener,
+                peers: peers.clone(),
+                token: token.clone(),
+                advertise: config.cluster.advertise.clone(),
+                urls,
+                tick: config.cluster.tick(),
+                storage,
+                requests,
+                status: status_tx,
+            },
+        ))
+    }
+
+    /// ノード間の通信を待ち受けるソケットアドレスを返す。
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// すべてのハンドルが破棄されるか、Raftの状態を永続化できなくなるまでノードを実行する。
+    ///
+    /// Raftの状態は、変わったときにメッセージを送信する前に永続化する。
+    /// ログは、永続化していないエントリだけを追記する。
+    ///
+    /// 接続してきたノードがアドレスを知らせた場合は、そのノードにもメッセージを送信する。
+    pub async fn run(self) {
+        let Self {
+            mut node,
+            listener,
+            peers,
+            token,
+            advertise,
+            urls,
+            tick,
+            storage,
+            mut requests,
+            status,
+        } = self;
+        let mut tasks = JoinSet::new();
+        let (inbound_tx, mut inbound) = mpsc::channel(INBOUND_CAPACITY);
+        let (learned_tx, mut learned) = mpsc::channel(INBOUND_CAPACITY);
+        tasks.spawn(receive_messages(
+            listener,
+            token.clone(),
+            inbound_tx,
+            learned_tx,
+        ));
+        let hello = PeerHello {
+            node_id: node.id(),
+            token: token.0,
+            advertise,
+        };
+        let hello = Arc::new(hello);
+        let mut connect = |peer: &ClusterPeer| {
+            let (outbox, messages) = mpsc::channel(OUTBOUND_CAPACITY);
+            tasks.spawn(send_messages(peer.addr, Arc::clone(&hello), messages));
+            outbox
+        };
+        let mut outboxes: BTreeMap<_, _> = peers
+            .iter()
+            .map(|peer| (peer.id, (peer.addr, connect(peer))))
+            .collect();
+        let mut ticks = tokio::time::interval(tick);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut proposals: Vec<(Proposal, oneshot::Sender<ClusterResult<TicketId>>)> = vec![];
+        let mut reads: Vec<(u64, TicketId, oneshot::Sender<ClusterResult<Ticket>>)> = vec![];
+        let mut changes: Vec<(Proposal, oneshot::Sender<ClusterResult<BTreeSet<NodeId>>>)> = vec![];
+
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => node.tick(),
+                Some(envelope) = inbound.recv() => {
+                    let Envelope { from, to, message } = envelope;
+                    if to == node.id() {
+                        node.step(from, message);
+                    }
+                }
+                Some(peer) = learned.recv() => {
+                    // 知らせたアドレスが変わらない限り、同じノードへの接続を作り直さない。
+                    if peer.id != node.id()
+                        && outboxes.get(&peer.id).map(|(addr, _)| *addr) != Some(peer.addr)
+                    {
+                        tracing::info!(node = peer.id, addr = %peer.addr, "ノードのアドレスを登録しました。");
+                        outboxes.insert(peer.id, (peer.addr, connect(&peer)));
+                        write_lock(&urls)
+                            .insert(peer.id, peer.url.trim_end_matches('/').to_string());
+                    }
+                }
+                request = requests.recv() => match request {
+                    Some(Request::Add { draft, reply }) => match node.propose_add(draft) {
+                        Ok(proposal) => proposals.push((proposal, reply)),
+                        Err(e) => {
+                            let _ = reply.send(Err(e));
+                        }
+                    },
+                    Some(Request::Update { id, patch, reply }) => {
+                        match node.propose_update(id, patch) {
+                            Ok(proposal) => proposals.push((proposal, reply)),
+                            Err(e) => {
+                                let _ = reply.send(Err(e));
+                            }
+                        }
+                    }
+                    Some(Request::Read { id, reply }) => match node.read_index() {
+                        Ok(read) => reads.push((read, id, reply)),
+                        Err(e) => {
+                            let _ = reply.send(Err(e));
+                        }
+                    },
+                    Some(Request::AddMember { id, reply }) => {
+                        let mut members = node.raft().members().clone();
+                        members.insert(id);
+                        // アドレスがわからないノードを追加すると、ログを複製できずに過半数を失うおそれがある。
+                        let changed = if node.raft().role() == Role::Leader
+                            && id != node.id()
+                            && !outboxes.contains_key(&id)
+                        {
+                            Err(ClusterError::UnknownPeer(id))
+                        } else {
+                            node.change_membership(members)
+                        };
+                        match changed {
+                            Ok(change) => changes.push((change, reply)),
+                            Err(e) => {
+                                let _ = reply.send(Err(e));
+                            }
+                        }
+                    }
+                    Some(Request::RemoveMember { id, reply }) => {
+                        let mut members = node.raft().members().clone();
+                        members.remove(&id);
+                        match node.change_membership(members) {
+                            Ok(change) => changes.push((change, reply)),
+                            Err(e) => {
+                                let _ = reply.send(Err(e));
+                            }
+                        }
+                    }
+                    None => break,
+                },
+            }
+
+            if let Some(storage) = &storage {
+                let changes = lock(storage).changes(node.raft());
+                if let Some(changes) = changes {
+                    let storage = Arc::clone(storage);
+                    let written = tokio::task::spawn_blocking(move || {
+                        lock(&storage)
+                            .write(&changes)
+                            .map(|()| changes.last_index())
+                    })
+                    .await
+                    .unwrap_or_else(|e| Err(io::Error::other(e).into()));
+                    // 永続化していない投票やエントリを他のノードに伝えると、再起動後に矛盾した応答をするおそれがある。
+                    match written {
+                        Ok(index) => node.stabilize(index),
+                        Err(e) => {
+                            tracing::error!(error = %e, "Raftの状態を永続化できないため、ノードを停止します。");
+                            break;
+                        }
+                    }
+                }
+            }
+            for envelope in node.take_messages() {
+                if let Some((_, outbox)) = outboxes.get(&envelope.to) {
+                    let _ = outbox.try_send(envelope);
+                }
+            }
+            proposals = std::mem::take(&mut proposals)
+                .into_iter()
+                .filter_map(|(proposal, reply)| match node.take_outcome(proposal) {
+                    Some(outcome) => {
+                        let _ = reply.send(outcome);
+                        None
+                    }
+                    None => Some((proposal, reply)),
+                })
+                .collect();
+            reads = std::mem::take(&mut reads)
+                .into_iter()
+                .filter_map(|(read, id, reply)| match node.read_ticket(read, id) {
+                    Some(ticket) => {
+                        let _ = reply.send(ticket);
+                        None
+                    }
+                    None => Some((read, id, reply)),
+                })
+                .collect();
+            changes = std::mem::take(&mut changes)
+                .into_iter()
+                .filter_map(|(change, reply)| match node.take_change_outcome(change) {
+                    Some(outcome) => {
+                        let members = node.raft().members().clone();
+                        let _ = reply.send(outcome.map(|()| members));
+                        None
+                    }
+                    None => Some((change, reply)),
+                })
+                .collect();
+            status.send_if_modified(|status| {
+                let current = ClusterStatus::of(&node);
+                let changed = *status != current;
+                *status = current;
+                changed
+            });
+        }
+    }
+}
+
+/// 他のノードからの接続を受け付け、受信したメッセージをノードに渡す。
+///
+/// 接続ごとに、最初に受信した自己紹介のトークンを確認する。トークンが一致しない場合や、
+/// 自己紹介と異なるノードを送信元とするメッセージを受信した場合は、接続を閉じる。
+/// 自己紹介でアドレスを知らせたノードは、`learned`で通知する。
+async fn receive_messages(
+    listener: TcpListener,
+    token: ClusterToken,
+    inbound: mpsc::Sender<ClusterEnvelope>,
+    learned: mpsc::Sender<ClusterPeer>,
+) {
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    let inbound = inbound.clone();
+                    let learned = learned.clone();
+                    let token = token.clone();
+                    connections.spawn(async move {
+                        let (reader, _) = stream.into_split();
+                        let mut lines = BufReader::new(reader).lines();
+                        let hello: PeerHello = match replication::read_message(&mut lines).await {
+                            Ok(Some(hello)) => hello,
+                            Ok(None) => return,
+                            Err(e) => {
+                                tracing::warn!(%peer, error = %e, "ノードの自己紹介を受信できません。");
+                                return;
+                            }
+                        };
+                        if !token.matches(&hello.token) {
+                            tracing::warn!(%peer, node = hello.node_id, "ノードの接続を認証できません。");
+                            return;
+                        }
+                        if let Some(ClusterAdvertise { addr, url }) = hello.advertise {
+                            let advertised = ClusterPeer {
+                                id: hello.node_id,
+                                addr,
+                                url,
+                            };
+                            if learned.send(advertised).await.is_err() {
+                                return;
+                            }
+                        }
+                        loop {
+                            match replication::read_message::<ClusterEnvelope>(&mut lines).await {
+                                Ok(Some(envelope)) => {
+                                    if envelope.from != hello.node_id {
+                                        tracing::warn!(
+                                            %peer,
+                                            node = hello.node_id,
+                                            from = envelope.from,
+                                            "他のノードを送信元とするメッセージを受信したため、接続を閉じます。"
+                                        );
+                                        return;
+                                    }
+                                    if inbound.send(envelope).await.is_err() {
+                                        return;
+                                    }
+                                }
+                                Ok(None) => return,
+                                Err(e) => {
+                                    tracing::debug!(%peer, error = %e, "ノードからのメッセージを受信できません。");
+                                    return;
+                                }
+                            }
+                        }
+                    });
+                }
+                Err(e) => tracing::warn!(error = %e, "ノードの接続を受け付けられません。"),
+            },
+            Some(_) = connections.join_next() => {}
+        }
+    }
+}
+
+/// 他のノードに接続してメッセージを送信し、切断された場合は待ち時間を延ばしながら再接続する。
+///
+/// 接続するたびに、最初に自己紹介を送信する。
+/// 接続していない間に送信を待つメッセージは、再接続した後に送信する。
+async fn send_messages(
+    addr: SocketAddr,
+    hello: Arc<PeerHello>,
+    mut messages: mpsc::Receiver<ClusterEnvelope>,
+) {
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        let connected = match TcpStream::connect(addr).await {
+            Ok(mut stream) => replication::write_message(&mut stream, &*hello)
+                .await
+                .map(|()| stream)
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let mut stream = match connected {
+            Ok(stream) => {
+                backoff = INITIAL_BACKOFF;
+                stream
+            }
+            Err(e) => {
+                tracing::debug!(%addr, error = %e, "ノードに接続できません。");
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        };
+        while let Some(envelope) = messages.recv().await {
+            if let Err(e) = replication::write_message(&mut stream, &envelope).await {
+                tracing::debug!(%addr, error = %e, "ノードにメッセージを送信できません。");
+                break;
+            }
+        }
+        if messages.is_closed() {
+            return;
+        }
+    }
+}
+
+/// クラスタモードのルーターを構築する。
+///
+/// 既定のプロジェクトのチケットの登録、取得、更新を、Raftのログで複製するチケットストアで処理する。
+/// メンバーの変更は、管理APIのトークンで認証する。
+/// リーダーでないノードは、これらのリクエストをリーダーに`307 Temporary Redirect`でリダイレクトする。
+///
+/// # 引数
+///
+/// * `cluster` - クラスタのノードのハンドル
+/// * `admin` - 管理APIのトークン、管理APIを無効にする場合は`None`
+///
+/// # 戻り値
+///
+/// ルーター
+pub fn router(cluster: ClusterHandle, admin: Option<AdminToken>) -> Router {
+    let members = Router::new()
+        .route("/cluster/members", post(add_member))
+        .route("/cluster/members/:node_id", delete(remove_member))
+        .route_layer(middleware::from_fn_with_state(admin, require_admin));
+
+    Router::new()
+        .route("/cluster", get(retrieve_status))
+        .route("/projects/:project_key/tickets", post(register_ticket))
+        .route(
+            "/projects/:project_key/tickets/:ticket_ref",
+            get(retrieve_ticket).patch(update_ticket),
+        )
+        .merge(members)
+        .with_state(cluster)
+}
+
+type ClusterHandlerResult = Result<Response, Response>;
+
+/// ノードの状態を取得する。
+async fn retrieve_status(State(cluster): State<ClusterHandle>) -> Json<ClusterStatus> {
+    Json(cluster.status())
+}
+
+/// メンバーに追加するノード
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MemberDraft {
+    id: NodeId,
+}
+
+/// ノードをメンバーに追加する。
+async fn add_member(
+    State(cluster): State<ClusterHandle>,
+    uri: Uri,
+    Json(draft): Json<MemberDraft>,
+) -> ClusterHandlerResult {
+    let members = cluster
+        .add_member(draft.id)
+        .await
+        .map_err(rejected(&cluster, &uri))?;
+
+    Ok(Json(json!({"members": members})).into_response())
+}
+
+/// ノードをメンバーから削除する。
+async fn remove_member(
+    State(cluster): State<ClusterHandle>,
+    uri: Uri,
+    Path((node_id,)): Path<(NodeId,)>,
+) -> ClusterHandlerResult {
+    let members = cluster
+        .remove_member(node_id)
+        .await
+        .map_err(rejected(&cluster, &uri))?;
+
+    Ok(Json(json!({"members": members})).into_response())
+}
+
+/// 既定のプロジェクトにチケットを登録する。
+async fn register_ticket(
+    State(cluster): State<ClusterHandle>,
+    uri: Uri,
+    Path((project_key,)): Path<(ProjectKey,)>,
+    Json(draft): Json<TicketDraft>,
+) -> ClusterHandlerResult {
+    if project_key != ProjectKey::default() {
+        return Err(TicketStoreError::ProjectNotFound.into_response());
+    }
+    let id = cluster
+        .add_ticket(draft)
+        .await
+        .map_err(rejected(&cluster, &uri))?;
+    let key = TicketKey::new(project_key, id);
+
+    Ok(Json(json!({"id": id, "key": key})).into_response())
+}
+
+/// 既定のプロジェクトのチケットを、線形化可能な読み込みで取得する。
+async fn retrieve_ticket(
+    State(cluster): State<ClusterHandle>,
+    uri: Uri,
+    Path((project_key, ticket_ref)): Path<(ProjectKey, TicketRef)>,
+) -> ClusterHandlerResult {
+    let id = ticket_id(&project_key, ticket_ref).map_err(IntoResponse::into_response)?;
+    let ticket = cluster
+        .read_ticket(id)
+        .await
+        .map_err(rejected(&cluster, &uri))?;
+
+    Ok(ticket.into_response())
+}
+
+/// 既定のプロジェクトのチケットを更新する。
+async fn update_ticket(
+    State(cluster): State<ClusterHandle>,
+    uri: Uri,
+    Path((project_key, ticket_ref)): Path<(ProjectKey, TicketRef)>,
+    Json(patch): Json<TicketPatch>,
+) -> ClusterHandlerResult {
+    let id = ticket_id(&project_key, ticket_ref).map_err(IntoResponse::into_response)?;
+    cluster
+        .update_ticket(id, patch)
+        .await
+        .map_err(rejected(&cluster, &uri))?;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+/// 既定のプロジェクトのチケットの指定から、チケットIDを返す。
+fn ticket_id(
+    project_key: &ProjectKey,
+    ticket_ref: TicketRef,
+) -> Result<TicketId, TicketStoreError> {
+    if *project_key != ProjectKey::default() {
+        return Err(TicketStoreError::ProjectNotFound);
+    }
+    match ticket_ref {
+        TicketRef::Id(id) => Ok(id),
+        TicketRef::Key(key) if key.project == *project_key => Ok(key.id()),
+        TicketRef::Key(_) => Err(TicketStoreError::NotFound),
+    }
+}
+
+/// クラスタのエラーをレスポンスに変換する。
+///
+/// このノードがリーダーでない場合は、リーダーがわかればリーダーにメソッドとボディを維持してリダイレクトし、
+/// わからなければ選挙が終わるまで再試行するように`503 Service Unavailable`を返す。
+/// 受け付けられないメンバーの変更は、`409 Conflict`を返す。
+fn rejected<'a>(
+    cluster: &'a ClusterHandle,
+    uri: &'a Uri,
+) -> impl FnOnce(ClusterError) -> Response + 'a {
+    move |error| {
+        let code = match error {
+            ClusterError::Store(e) => return e.into_response(),
+            ClusterError::Raft(RaftError::NotLeader { leader }) => {
+                if let Some(url) = leader.and_then(|leader| cluster.url(leader)) {
+                    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+                    return Redirect::temporary(&format!("{url}{path}")).into_response();
+                }
+                "noLeader"
+            }
+            ClusterError::Raft(RaftError::InvalidMembershipChange) => {
+                let body =
+                    Json(json!({"error": format!("{error}"), "code": "invalidMembershipChange"}));
+                return (StatusCode::CONFLICT, body).into_response();
+            }
+            ClusterError::UnknownPeer(_) => {
+                let body = Json(json!({"error": format!("{error}"), "code": "unknownPeer"}));
+                return (StatusCode::CONFLICT, body).into_response();
+            }
+            _ => "unavailable",
+        };
+        let body = Json(json!({"error": format!("{error}"), "code": code}));
+
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, "1")],
+            body,
+        )
+            .into_response()
+    }
+}
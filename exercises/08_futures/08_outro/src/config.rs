@@ -0,0 +1,1151 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::cluster::CLUSTER_SIZES;
+use crate::cluster_server::ClusterToken;
+use crate::crypto::EncryptionKey;
+use crate::middleware::AdminToken;
+use crate::raft::NodeId;
+use crate::telemetry::LogFormat;
+
+/// コマンドライン引数
+///
+/// 各設定は、設定ファイル、環境変数、コマンドライン引数の順に優先される。
+/// 環境変数とコマンドライン引数の優先順位は`clap`が解決する。
+#[derive(Debug, Default, clap::Parser)]
+#[command(
+    name = "ticket-store",
+    version,
+    about = "チケット管理システムREST APIサーバー"
+)]
+pub struct Args {
+    /// TOML形式の設定ファイルのパス
+    #[arg(short, long, env = "TICKET_STORE_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// 設定を検証して有効な設定を表示し、サーバーを起動せずに終了する
+    #[arg(long)]
+    pub check_config: bool,
+    /// 待ち受けるIPアドレス
+    #[arg(long, env = "TICKET_STORE_HOST")]
+    pub host: Option<IpAddr>,
+    /// 待ち受けるポート番号
+    #[arg(long, env = "TICKET_STORE_PORT")]
+    pub port: Option<u16>,
+    /// 停止時に処理中のリクエストの完了を待つ秒数
+    #[arg(long, env = "TICKET_STORE_SHUTDOWN_TIMEOUT_SECS")]
+    pub shutdown_timeout_secs: Option<u64>,
+    /// ストレージバックエンド（`memory`または`file`）
+    #[arg(long, env = "TICKET_STORE_STORAGE_BACKEND", value_parser = |s: &str| StorageBackend::try_from(s))]
+    pub storage_backend: Option<StorageBackend>,
+    /// データディレクトリ
+    #[arg(long, env = "TICKET_STORE_DATA_DIR")]
+    pub data_dir: Option<PathBuf>,
+    /// ログの出力形式（`pretty`または`json`）
+    #[arg(long, env = "TICKET_STORE_LOG_FORMAT", value_parser = |s: &str| LogFormat::try_from(s))]
+    pub log_format: Option<LogFormat>,
+    /// リクエストボディの最大バイト数
+    #[arg(long, env = "TICKET_STORE_MAX_BODY_BYTES")]
+    pub max_body_bytes: Option<usize>,
+    /// 復元するアーカイブの最大バイト数
+    #[arg(long, env = "TICKET_STORE_MAX_RESTORE_BYTES")]
+    pub max_restore_bytes: Option<usize>,
+    /// リクエストのタイムアウト秒数
+    #[arg(long, env = "TICKET_STORE_REQUEST_TIMEOUT_SECS")]
+    pub request_timeout_secs: Option<u64>,
+    /// チケットストアのアクターごとの、処理を待つリクエストのキューの容量
+    #[arg(long, env = "TICKET_STORE_STORE_QUEUE_CAPACITY")]
+    pub store_queue_capacity: Option<usize>,
+    /// レディネスプローブでチケットストアの応答を待つミリ秒数
+    #[arg(long, env = "TICKET_STORE_HEALTH_LOCK_TIMEOUT_MS")]
+    pub health_lock_timeout_ms: Option<u64>,
+    /// レディネスプローブで必要とするデータディレクトリの空き容量（バイト）
+    #[arg(long, env = "TICKET_STORE_HEALTH_MIN_FREE_DISK_BYTES")]
+    pub health_min_free_disk_bytes: Option<u64>,
+    /// Webhookの配信を試行する最大回数
+    #[arg(long, env = "TICKET_STORE_WEBHOOK_MAX_ATTEMPTS")]
+    pub webhook_max_attempts: Option<u32>,
+    /// Webhookの配信に失敗してから、最初に再試行するまでのミリ秒数
+    #[arg(long, env = "TICKET_STORE_WEBHOOK_INITIAL_BACKOFF_MS")]
+    pub webhook_initial_backoff_ms: Option<u64>,
+    /// Webhookの1回の配信のタイムアウト秒数
+    #[arg(long, env = "TICKET_STORE_WEBHOOK_TIMEOUT_SECS")]
+    pub webhook_timeout_secs: Option<u64>,
+    /// Webhookのデッドレターに保持する配信の最大数
+    #[arg(long, env = "TICKET_STORE_WEBHOOK_MAX_DEAD_LETTERS")]
+    pub webhook_max_dead_letters: Option<usize>,
+    /// Webhookの配信を同時に試行する最大数
+    #[arg(long, env = "TICKET_STORE_WEBHOOK_MAX_CONCURRENT_DELIVERIES")]
+    pub webhook_max_concurrent_deliveries: Option<usize>,
+    /// 添付ファイルの最大バイト数
+    #[arg(long, env = "TICKET_STORE_ATTACHMENT_MAX_BYTES")]
+    pub attachment_max_bytes: Option<u64>,
+    /// 複製での役割（`standalone`、`leader`または`follower`）
+    #[arg(long, env = "TICKET_STORE_REPLICATION_ROLE", value_parser = |s: &str| ReplicationRole::try_from(s))]
+    pub replication_role: Option<ReplicationRole>,
+    /// リーダーが複製を待ち受けるソケットアドレス
+    #[arg(long, env = "TICKET_STORE_REPLICATION_LISTEN")]
+    pub replication_listen: Option<SocketAddr>,
+    /// フォロワーが接続する、リーダーが複製を待ち受けるソケットアドレス
+    #[arg(long, env = "TICKET_STORE_REPLICATION_LEADER_ADDR")]
+    pub replication_leader_addr: Option<SocketAddr>,
+    /// フォロワーが書き込みをリダイレクトする、リーダーのURL
+    #[arg(long, env = "TICKET_STORE_REPLICATION_LEADER_URL")]
+    pub replication_leader_url: Option<String>,
+    /// クラスタでのこのノードのノードID（指定した場合はクラスタモードで起動する）
+    #[arg(long, env = "TICKET_STORE_CLUSTER_NODE_ID")]
+    pub cluster_node_id: Option<NodeId>,
+    /// ノード間の通信を待ち受けるソケットアドレス
+    #[arg(long, env = "TICKET_STORE_CLUSTER_LISTEN")]
+    pub cluster_listen: Option<SocketAddr>,
+    /// 他のノード（`ノードID@ノード間の通信のソケットアドレス@URL`をカンマ区切りで指定する）
+    #[arg(long, env = "TICKET_STORE_CLUSTER_PEERS", value_delimiter = ',', value_parser = |s: &str| ClusterPeer::try_from(s))]
+    pub cluster_peers: Vec<ClusterPeer>,
+    /// 他のノードに知らせる、このノードのアドレス（`ノード間の通信のソケットアドレス@URL`で指定する）
+    #[arg(long, env = "TICKET_STORE_CLUSTER_ADVERTISE", value_parser = |s: &str| ClusterAdvertise::try_from(s))]
+    pub cluster_advertise: Option<ClusterAdvertise>,
+    /// 既存のクラスタに追加するノードとして起動する（他のノードには既存のクラスタのメンバーを指定する）
+    #[arg(long, env = "TICKET_STORE_CLUSTER_JOIN")]
+    pub cluster_join: bool,
+    /// Raftの1ティックのミリ秒数
+    #[arg(long, env = "TICKET_STORE_CLUSTER_TICK_MS")]
+    pub cluster_tick_ms: Option<u64>,
+    /// Raftのログをスナップショットで切り詰めるまでに適用するエントリの数
+    #[arg(long, env = "TICKET_STORE_CLUSTER_SNAPSHOT_ENTRIES")]
+    pub cluster_snapshot_entries: Option<u64>,
+    /// ノード間の通信を認証するトークン（トークンファイルより優先する）
+    #[arg(long, env = "TICKET_STORE_CLUSTER_TOKEN", hide_env_values = true, value_parser = |s: &str| ClusterToken::try_from(s))]
+    pub cluster_token: Option<ClusterToken>,
+    /// ノード間の通信を認証するトークンを読み込むファイル
+    #[arg(long, env = "TICKET_STORE_CLUSTER_TOKEN_FILE")]
+    pub cluster_token_file: Option<PathBuf>,
+    /// 暗号化の鍵（32バイトを表現する16進数64文字、鍵ファイルより優先する）
+    #[arg(long, env = "TICKET_STORE_ENCRYPTION_KEY", hide_env_values = true, value_parser = |s: &str| EncryptionKey::try_from(s))]
+    pub encryption_key: Option<EncryptionKey>,
+    /// 暗号化の鍵を読み込むファイル
+    #[arg(long, env = "TICKET_STORE_ENCRYPTION_KEY_FILE")]
+    pub encryption_key_file: Option<PathBuf>,
+    /// 鍵のローテーション中に、復号にだけ使用する以前の鍵を読み込むファイル（カンマ区切りで複数指定できる）
+    #[arg(
+        long,
+        env = "TICKET_STORE_PREVIOUS_ENCRYPTION_KEY_FILES",
+        value_delimiter = ','
+    )]
+    pub previous_encryption_key_files: Vec<PathBuf>,
+    /// 管理APIのトークン（トークンファイルより優先する）
+    #[arg(long, env = "TICKET_STORE_ADMIN_TOKEN", hide_env_values = true, value_parser = |s: &str| AdminToken::try_from(s))]
+    pub admin_token: Option<AdminToken>,
+    /// 管理APIのトークンを読み込むファイル
+    #[arg(long, env = "TICKET_STORE_ADMIN_TOKEN_FILE")]
+    pub admin_token_file: Option<PathBuf>,
+    /// サブコマンド（省略した場合はサーバーを起動する）
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// サブコマンド
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::Subcommand)]
+pub enum Command {
+    /// データディレクトリを現在の形式バージョンに移行して終了する（サーバーを停止してから実行する）
+    Migrate {
+        /// 移行できることを確かめて結果を表示し、データディレクトリを変更せずに終了する
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// ストレージバックエンド
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// メモリ上にのみチケットを保持する。
+    #[default]
+    Memory,
+    /// データディレクトリのファイルにチケットを永続化する。
+    File,
+}
+
+/// ストレージバックエンドエラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error(r#"ストレージバックエンドは、`"memory"`または`"file"`のいずれかです。"#)]
+pub struct StorageBackendError;
+
+/// 文字列からストレージバックエンドを構築する。
+///
+/// # 引数
+///
+/// * `s` - ストレージバックエンドを表現する文字列
+///
+/// # 戻り値
+///
+/// ストレージバックエンド
+fn storage_backend_from_str(s: &str) -> Result<StorageBackend, StorageBackendError> {
+    match s.trim().to_lowercase().as_str() {
+        "memory" => Ok(StorageBackend::Memory),
+        "file" => Ok(StorageBackend::File),
+        _ => Err(StorageBackendError),
+    }
+}
+
+impl TryFrom<String> for StorageBackend {
+    type Error = StorageBackendError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        storage_backend_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for StorageBackend {
+    type Error = StorageBackendError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        storage_backend_from_str(value)
+    }
+}
+
+/// 複製での役割
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplicationRole {
+    /// 複製しない。
+    #[default]
+    Standalone,
+    /// 変更操作をフォロワーに複製する。
+    Leader,
+    /// リーダーから変更操作を複製して、読み込みだけを処理する。
+    Follower,
+}
+
+/// 複製での役割エラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error(r#"複製での役割は、`"standalone"`、`"leader"`または`"follower"`のいずれかです。"#)]
+pub struct ReplicationRoleError;
+
+/// 文字列から複製での役割を構築する。
+///
+/// # 引数
+///
+/// * `s` - 複製での役割を表現する文字列
+///
+/// # 戻り値
+///
+/// 複製での役割
+fn replication_role_from_str(s: &str) -> Result<ReplicationRole, ReplicationRoleError> {
+    match s.trim().to_lowercase().as_str() {
+        "standalone" => Ok(ReplicationRole::Standalone),
+        "leader" => Ok(ReplicationRole::Leader),
+        "follower" => Ok(ReplicationRole::Follower),
+        _ => Err(ReplicationRoleError),
+    }
+}
+
+impl TryFrom<String> for ReplicationRole {
+    type Error = ReplicationRoleError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        replication_role_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for ReplicationRole {
+    type Error = ReplicationRoleError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        replication_role_from_str(value)
+    }
+}
+
+/// クラスタの他のノード
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClusterPeer {
+    /// ノードID
+    pub id: NodeId,
+    /// ノード間の通信を待ち受けるソケットアドレス
+    pub addr: SocketAddr,
+    /// リーダーでないノードが書き込みをリダイレクトする、ノードのURL
+    pub url: String,
+}
+
+/// クラスタのノードエラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("ノードは、`2@10.0.0.2:7000@http://10.0.0.2:3000`のように、ノードID、ノード間の通信のソケットアドレス、URLを`@`でつないだ文字列です。")]
+pub struct ClusterPeerError;
+
+/// 文字列からクラスタの他のノードを構築する。
+///
+/// # 引数
+///
+/// * `s` - ノードID、ノード間の通信のソケットアドレス、URLを`@`でつないだ文字列
+///
+/// # 戻り値
+///
+/// クラスタの他のノード
+fn cluster_peer_from_str(s: &str) -> Result<ClusterPeer, ClusterPeerError> {
+    let mut parts = s.trim().splitn(3, '@');
+    let (Some(id), Some(addr), Some(url)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(ClusterPeerError);
+    };
+    if url.is_empty() {
+        return Err(ClusterPeerError);
+    }
+
+    Ok(ClusterPeer {
+        id: id.parse().map_err(|_| ClusterPeerError)?,
+        addr: addr.parse().map_err(|_| ClusterPeerError)?,
+        url: url.into(),
+    })
+}
+
+impl TryFrom<&str> for ClusterPeer {
+    type Error = ClusterPeerError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        cluster_peer_from_str(value)
+    }
+}
+
+/// 他のノードに知らせる、このノードのアドレス
+///
+/// ノード間の通信の接続ごとに他のノードに送信し、受信したノードはこのノードにメッセージを送信できるようになる。
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClusterAdvertise {
+    /// ノード間の通信を待ち受けるソケットアドレス
+    pub addr: SocketAddr,
+    /// リーダーでないノードが書き込みをリダイレクトする、ノードのURL
+    pub url: String,
+}
+
+/// このノードのアドレスのエラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("このノードのアドレスは、`10.0.0.4:7000@http://10.0.0.4:3000`のように、ノード間の通信のソケットアドレスとURLを`@`でつないだ文字列です。")]
+pub struct ClusterAdvertiseError;
+
+/// 文字列から他のノードに知らせる、このノードのアドレスを構築する。
+///
+/// # 引数
+///
+/// * `s` - ノード間の通信のソケットアドレスとURLを`@`でつないだ文字列
+///
+/// # 戻り値
+///
+/// このノードのアドレス
+fn cluster_advertise_from_str(s: &str) -> Result<ClusterAdvertise, ClusterAdvertiseError> {
+    let Some((addr, url)) = s.trim().split_once('@') else {
+        return Err(ClusterAdvertiseError);
+    };
+    if url.is_empty() {
+        return Err(ClusterAdvertiseError);
+    }
+
+    Ok(ClusterAdvertise {
+        addr: addr.parse().map_err(|_| ClusterAdvertiseError)?,
+        url: url.into(),
+    })
+}
+
+impl TryFrom<&str> for ClusterAdvertise {
+    type Error = ClusterAdvertiseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        cluster_advertise_from_str(value)
+    }
+}
+
+/// サーバー設定
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ServerConfig {
+    /// 待ち受けるIPアドレス
+    pub host: IpAddr,
+    /// 待ち受けるポート番号（`0`の場合はOSが空いているポートを割り当てる）
+    pub port: u16,
+    /// 停止時に処理中のリクエストの完了を待つ秒数
+    pub shutdown_timeout_secs: u64,
+}
+
+impl ServerConfig {
+    /// 待ち受けるソケットアドレスを返す。
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.host, self.port)
+    }
+
+    /// 停止時に処理中のリクエストの完了を待つ時間を返す。
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+}
+
+/// ストレージ設定
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct StorageConfig {
+    /// ストレージバックエンド
+    pub backend: StorageBackend,
+    /// データディレクトリ（`file`バックエンドの場合は必須）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<PathBuf>,
+}
+
+/// ログ設定
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct LogConfig {
+    /// ログの出力形式
+    pub format: LogFormat,
+}
+
+/// リクエスト制限設定
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct LimitsConfig {
+    /// リクエストボディの最大バイト数
+    pub max_body_bytes: usize,
+    /// 復元するアーカイブの最大バイト数（超えたアーカイブには413を返す）
+    pub max_restore_bytes: usize,
+    /// リクエストのタイムアウト秒数
+    pub request_timeout_secs: u64,
+    /// チケットストアのアクターごとの、処理を待つリクエストのキューの容量（超えたリクエストには503を返す）
+    ///
+    /// チケットを変更するリクエストはプロジェクトごとのキューで待つ。チケットを変更しないリクエストはキューで待たない。
+    pub store_queue_capacity: usize,
+}
+
+impl LimitsConfig {
+    /// リクエストのタイムアウトを返す。
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+}
+
+/// ヘルスチェック設定
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct HealthConfig {
+    /// レディネスプローブでチケットストアの応答を待つミリ秒数
+    pub lock_timeout_ms: u64,
+    /// レディネスプローブで必要とするデータディレクトリの空き容量（バイト）
+    pub min_free_disk_bytes: u64,
+}
+
+impl HealthConfig {
+    /// レディネスプローブでチケットストアの応答を待つ時間を返す。
+    pub fn lock_timeout(&self) -> Duration {
+        Duration::from_millis(self.lock_timeout_ms)
+    }
+}
+
+/// Webhook設定
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct WebhooksConfig {
+    /// 配信を試行する最大回数（超えた配信はデッドレターに移す）
+    pub max_attempts: u32,
+    /// 配信に失敗してから、最初に再試行するまでのミリ秒数（再試行するたびに2倍にする）
+    pub initial_backoff_ms: u64,
+    /// 1回の配信のタイムアウト秒数
+    pub timeout_secs: u64,
+    /// デッドレターに保持する配信の最大数（超えた場合は古い配信から破棄する）
+    pub max_dead_letters: usize,
+    /// 配信を同時に試行する最大数（超えた配信は、試行中の配信が終わるまで開始を待つ）
+    pub max_concurrent_deliveries: usize,
+}
+
+impl WebhooksConfig {
+    /// 指定した回数の配信に失敗した後、次に再試行するまでの時間を返す。
+    pub fn backoff(&self, failed_attempts: u32) -> Duration {
+        let factor = 1u64 << failed_attempts.saturating_sub(1).min(16);
+        Duration::from_millis(self.initial_backoff_ms.saturating_mul(factor))
+    }
+
+    /// 1回の配信のタイムアウトを返す。
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+}
+
+/// 添付ファイル設定
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct AttachmentsConfig {
+    /// 添付ファイルの最大バイト数（超えた添付ファイルには413を返す）
+    pub max_bytes: u64,
+}
+
+/// 複製設定
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ReplicationConfig {
+    /// 複製での役割
+    pub role: ReplicationRole,
+    /// リーダーが複製を待ち受けるソケットアドレス（`leader`の場合は必須）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen: Option<SocketAddr>,
+    /// フォロワーが接続する、リーダーが複製を待ち受けるソケットアドレス（`follower`の場合は必須）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leader_addr: Option<SocketAddr>,
+    /// フォロワーが書き込みをリダイレクトする、リーダーのURL（`follower`の場合は必須）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leader_url: Option<String>,
+}
+
+/// クラスタ設定
+///
+/// ノードIDを指定した場合は、チケットの登録と更新をRaftのログで他のノードに複製するクラスタモードで起動する。
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ClusterConfig {
+    /// このノードのノードID（`None`の場合はクラスタモードで起動しない）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<NodeId>,
+    /// ノード間の通信を待ち受けるソケットアドレス（クラスタモードの場合は必須）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen: Option<SocketAddr>,
+    /// 他のノード（このノードと合わせて3または5ノード、既存のクラスタに追加する場合は既存のクラスタのメンバー）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub peers: Vec<ClusterPeer>,
+    /// 他のノードに知らせる、このノードのアドレス（既存のクラスタに追加する場合は必須）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub advertise: Option<ClusterAdvertise>,
+    /// 既存のクラスタに追加するノードとして、メンバーを持たずに起動するか
+    ///
+    /// リーダーが`POST /cluster/members`でメンバーに追加するまで、選挙を始めない。
+    pub join: bool,
+    /// Raftの1ティックのミリ秒数（選挙タイムアウトは10ティック以上、ハートビートは3ティックごと）
+    pub tick_ms: u64,
+    /// Raftのログをチケットストアのスナップショットで切り詰めるまでに適用するエントリの数
+    pub snapshot_entries: u64,
+    /// ノード間の通信を認証するトークン（クラスタモードの場合は必須、書き出さない）
+    #[serde(skip)]
+    pub token: Option<ClusterToken>,
+}
+
+impl ClusterConfig {
+    /// Raftの1ティックの時間を返す。
+    pub fn tick(&self) -> Duration {
+        Duration::from_millis(self.tick_ms)
+    }
+}
+
+/// 暗号化設定
+///
+/// 鍵を表示しないように、鍵IDだけを書き出す。
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct EncryptionConfig {
+    /// 永続化するデータを暗号化する現在の鍵（`None`の場合は暗号化しない）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<EncryptionKey>,
+    /// 鍵のローテーション中に、復号にだけ使用する以前の鍵
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub previous_keys: Vec<EncryptionKey>,
+}
+
+/// 管理API設定
+///
+/// トークンを表示しないように、トークンのダイジェストの先頭だけを書き出す。
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct AdminConfig {
+    /// `/admin`以下のAPIを認証するトークン（`None`の場合は管理APIを無効にする）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<AdminToken>,
+}
+
+/// 設定
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub log: LogConfig,
+    pub limits: LimitsConfig,
+    pub health: HealthConfig,
+    pub webhooks: WebhooksConfig,
+    pub attachments: AttachmentsConfig,
+    pub replication: ReplicationConfig,
+    pub cluster: ClusterConfig,
+    pub encryption: EncryptionConfig,
+    pub admin: AdminConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server: ServerConfig {
+                host: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                port: 3000,
+                shutdown_timeout_secs: 30,
+            },
+            storage: StorageConfig {
+                backend: StorageBackend::Memory,
+                data_dir: None,
+            },
+            log: LogConfig {
+                format: LogFormat::Pretty,
+            },
+            limits: LimitsConfig {
+                max_body_bytes: 2 * 1024 * 1024,
+                max_restore_bytes: 256 * 1024 * 1024,
+                request_timeout_secs: 30,
+                store_queue_capacity: 1024,
+            },
+            health: HealthConfig {
+                lock_timeout_ms: 500,
+                min_free_disk_bytes: 64 * 1024 * 1024,
+            },
+            webhooks: WebhooksConfig {
+                max_attempts: 5,
+                initial_backoff_ms: 1000,
+                timeout_secs: 10,
+                max_dead_letters: 1000,
+                max_concurrent_deliveries: 64,
+            },
+            attachments: AttachmentsConfig {
+                max_bytes: 10 * 1024 * 1024,
+            },
+            replication: ReplicationConfig {
+                role: ReplicationRole::Standalone,
+                listen: None,
+                leader_addr: None,
+                leader_url: None,
+            },
+            cluster: ClusterConfig {
+                node_id: None,
+                listen: None,
+                peers: vec![],
+                advertise: None,
+                join: false,
+                tick_ms: 50,
+                snapshot_entries: 1024,
+                token: None,
+            },
+            encryption: EncryptionConfig::default(),
+            admin: AdminConfig::default(),
+        }
+    }
+}
+
+/// 設定ファイルの内容
+///
+/// 設定ファイルでは、すべての項目を省略できる。
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    server: FileServerConfig,
+    storage: FileStorageConfig,
+    log: FileLogConfig,
+    limits: FileLimitsConfig,
+    health: FileHealthConfig,
+    webhooks: FileWebhooksConfig,
+    attachments: FileAttachmentsConfig,
+    replication: FileReplicationConfig,
+    cluster: FileClusterConfig,
+    encryption: FileEncryptionConfig,
+    admin: FileAdminConfig,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileServerConfig {
+    host: Option<IpAddr>,
+    port: Option<u16>,
+    shutdown_timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileStorageConfig {
+    backend: Option<StorageBackend>,
+    data_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileLogConfig {
+    format: Option<LogFormat>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileLimitsConfig {
+    max_body_bytes: Option<usize>,
+    max_restore_bytes: Option<usize>,
+    request_timeout_secs: Option<u64>,
+    store_queue_capacity: Option<usize>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileHealthConfig {
+    lock_timeout_ms: Option<u64>,
+    min_free_disk_bytes: Option<u64>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileWebhooksConfig {
+    max_attempts: Option<u32>,
+    initial_backoff_ms: Option<u64>,
+    timeout_secs: Option<u64>,
+    max_dead_letters: Option<usize>,
+    max_concurrent_deliveries: Option<usize>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileAttachmentsConfig {
+    max_bytes: Option<u64>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileReplicationConfig {
+    role: Option<ReplicationRole>,
+    listen: Option<SocketAddr>,
+    leader_addr: Option<SocketAddr>,
+    leader_url: Option<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileClusterConfig {
+    node_id: Option<NodeId>,
+    listen: Option<SocketAddr>,
+    peers: Option<Vec<ClusterPeer>>,
+    advertise: Option<ClusterAdvertise>,
+    join: Option<bool>,
+    tick_ms: Option<u64>,
+    snapshot_entries: Option<u64>,
+    token_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileEncryptionConfig {
+    key_file: Option<PathBuf>,
+    previous_key_files: Option<Vec<PathBuf>>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileAdminConfig {
+    token_file: Option<PathBuf>,
+}
+
+impl FileConfig {
+    /// 設定ファイルを読み込む。
+    fn read(path: &Path) -> ConfigResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.into(),
+            source,
+        })?;
+
+        toml::from_str(&content).map_err(|source| ConfigError::Parse {
+            path: path.into(),
+            source,
+        })
+    }
+}
+
+/// 鍵ファイルから暗号化の鍵を読み込む。
+fn read_encryption_key(path: &Path) -> ConfigResult<EncryptionKey> {
+    let content =
+        std::fs::read_to_string(path).map_err(|source| ConfigError::ReadEncryptionKey {
+            path: path.into(),
+            source,
+        })?;
+
+    EncryptionKey::try_from(content.as_str())
+        .map_err(|_| ConfigError::InvalidEncryptionKey { path: path.into() })
+}
+
+/// トークンファイルから管理APIのトークンを読み込む。
+fn read_admin_token(path: &Path) -> ConfigResult<AdminToken> {
+    let content = std::fs::read_to_string(path).map_err(|source| ConfigError::ReadAdminToken {
+        path: path.into(),
+        source,
+    })?;
+
+    AdminToken::try_from(content.as_str())
+        .map_err(|_| ConfigError::InvalidAdminToken { path: path.into() })
+}
+
+/// トークンファイルからノード間の通信を認証するトークンを読み込む。
+fn read_cluster_token(path: &Path) -> ConfigResult<ClusterToken> {
+    let content =
+        std::fs::read_to_string(path).map_err(|source| ConfigError::ReadClusterToken {
+            path: path.into(),
+            source,
+        })?;
+
+    ClusterToken::try_from(content.as_str())
+        .map_err(|_| ConfigError::InvalidClusterToken { path: path.into() })
+}
+
+impl Config {
+    /// 設定ファイル、環境変数、コマンドライン引数から設定を構築して検証する。
+    ///
+    /// # 引数
+    ///
+    /// * `args` - 環境変数を反映したコマンドライン引数
+    ///
+    /// # 戻り値
+    ///
+    /// 設定
+    pub fn load(args: &Args) -> ConfigResult<Self> {
+        let file = match &args.config {
+            Some(path) => FileConfig::read(path)?,
+            None => FileConfig::default(),
+        };
+        let default = Self::default();
+        let config = Self {
+            server: ServerConfig {
+                host: args
+                    .host
+                    .or(file.server.host)
+                    .unwrap_or(default.server.host),
+                port: args
+                    .port
+                    .or(file.server.port)
+                    .unwrap_or(default.server.port),
+                shutdown_timeout_secs: args
+                    .shutdown_timeout_secs
+                    .or(file.server.shutdown_timeout_secs)
+                    .unwrap_or(default.server.shutdown_timeout_secs),
+            },
+            storage: StorageConfig {
+                backend: args
+                    .storage_backend
+                    .or(file.storage.backend)
+                    .unwrap_or(default.storage.backend),
+                data_dir: args.data_dir.clone().or(file.storage.data_dir),
+            },
+            log: LogConfig {
+                format: args
+                    .log_format
+                    .or(file.log.format)
+                    .unwrap_or(default.log.format),
+            },
+            limits: LimitsConfig {
+                max_body_bytes: args
+                    .max_body_bytes
+                    .or(file.limits.max_body_bytes)
+                    .unwrap_or(default.limits.max_body_bytes),
+                max_restore_bytes: args
+                    .max_restore_bytes
+                    .or(file.limits.max_restore_bytes)
+                    .unwrap_or(default.limits.max_restore_bytes),
+                request_timeout_secs: args
+                    .request_timeout_secs
+                    .or(file.limits.request_timeout_secs)
+                    .unwrap_or(default.limits.request_timeout_secs),
+                store_queue_capacity: args
+                    .store_queue_capacity
+                    .or(file.limits.store_queue_capacity)
+                    .unwrap_or(default.limits.store_queue_capacity),
+            },
+            health: HealthConfig {
+                lock_timeout_ms: args
+                    .health_lock_timeout_ms
+                    .or(file.health.lock_timeout_ms)
+                    .unwrap_or(default.health.lock_timeout_ms),
+                min_free_disk_bytes: args
+                    .health_min_free_disk_bytes
+                    .or(file.health.min_free_disk_bytes)
+                    .unwrap_or(default.health.min_free_disk_bytes),
+            },
+            webhooks: WebhooksConfig {
+                max_attempts: args
+                    .webhook_max_attempts
+                    .or(file.webhooks.max_attempts)
+                    .unwrap_or(default.webhooks.max_attempts),
+                initial_backoff_ms: args
+                    .webhook_initial_backoff_ms
+                    .or(file.webhooks.initial_backoff_ms)
+                    .unwrap_or(default.webhooks.initial_backoff_ms),
+                timeout_secs: args
+                    .webhook_timeout_secs
+                    .or(file.webhooks.timeout_secs)
+                    .unwrap_or(default.webhooks.timeout_secs),
+                max_dead_letters: args
+                    .webhook_max_dead_letters
+                    .or(file.webhooks.max_dead_letters)
+                    .unwrap_or(default.webhooks.max_dead_letters),
+                max_concurrent_deliveries: args
+                    .webhook_max_concurrent_deliveries
+                    .or(file.webhooks.max_concurrent_deliveries)
+                    .unwrap_or(default.webhooks.max_concurrent_deliveries),
+            },
+            attachments: AttachmentsConfig {
+                max_bytes: args
+                    .attachment_max_bytes
+                    .or(file.attachments.max_bytes)
+                    .unwrap_or(default.attachments.max_bytes),
+            },
+            replication: ReplicationConfig {
+                role: args
+                    .replication_role
+                    .or(file.replication.role)
+                    .unwrap_or(default.replication.role),
+                listen: args.replication_listen.or(file.replication.listen),
+                leader_addr: args
+                    .replication_leader_addr
+                    .or(file.replication.leader_addr),
+                leader_url: args
+                    .replication_leader_url
+                    .clone()
+                    .or(file.replication.leader_url),
+            },
+            cluster: ClusterConfig {
+                node_id: args.cluster_node_id.or(file.cluster.node_id),
+                listen: args.cluster_listen.or(file.cluster.listen),
+                peers: match &args.cluster_peers[..] {
+                    [] => file.cluster.peers.unwrap_or_default(),
+                    peers => peers.to_vec(),
+                },
+                advertise: args.cluster_advertise.clone().or(file.cluster.advertise),
+                join: args.cluster_join || file.cluster.join.unwrap_or(default.cluster.join),
+                tick_ms: args
+                    .cluster_tick_ms
+                    .or(file.cluster.tick_ms)
+                    .unwrap_or(default.cluster.tick_ms),
+                snapshot_entries: args
+                    .cluster_snapshot_entries
+                    .or(file.cluster.snapshot_entries)
+                    .unwrap_or(default.cluster.snapshot_entries),
+                token: match (&args.cluster_token, &args.cluster_token_file) {
+                    (Some(token), _) => Some(token.clone()),
+                    (None, Some(path)) => Some(read_cluster_token(path)?),
+                    (None, None) => file
+                        .cluster
+                        .token_file
+                        .as_deref()
+                        .map(read_cluster_token)
+                        .transpose()?,
+                },
+            },
+            encryption: EncryptionConfig {
+                key: match (&args.encryption_key, &args.encryption_key_file) {
+                    (Some(key), _) => Some(key.clone()),
+                    (None, Some(path)) => Some(read_encryption_key(path)?),
+                    (None, None) => file
+                        .encryption
+                        .key_file
+                        .as_deref()
+                        .map(read_encryption_key)
+                        .transpose()?,
+                },
+                previous_keys: match &args.previous_encryption_key_files[..] {
+                    [] => file.encryption.previous_key_files.unwrap_or_default(),
+                    paths => paths.to_vec(),
+                }
+                .iter()
+                .map(|path| read_encryption_key(path))
+                .collect::<ConfigResult<_>>()?,
+            },
+            admin: AdminConfig {
+                token: match (&args.admin_token, &args.admin_token_file) {
+                    (Some(token), _) => Some(token.clone()),
+                    (None, Some(path)) => Some(read_admin_token(path)?),
+                    (None, None) => file
+                        .admin
+                        .token_file
+                        .as_deref()
+                        .map(read_admin_token)
+                        .transpose()?,
+                },
+            },
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// 設定を検証する。
+    pub fn validate(&self) -> ConfigResult<()> {
+        if self.storage.backend == StorageBackend::File && self.storage.data_dir.is_none() {
+            return Err(ConfigError::DataDirRequired);
+        }
+        if self.limits.max_body_bytes == 0 {
+            return Err(ConfigError::ZeroMaxBodyBytes);
+        }
+        if self.limits.max_restore_bytes == 0 {
+            return Err(ConfigError::ZeroMaxRestoreBytes);
+        }
+        if self.limits.request_timeout_secs == 0 {
+            return Err(ConfigError::ZeroRequestTimeout);
+        }
+        if self.limits.store_queue_capacity == 0 {
+            return Err(ConfigError::ZeroStoreQueueCapacity);
+        }
+        if self.health.lock_timeout_ms == 0 {
+            return Err(ConfigError::ZeroHealthLockTimeout);
+        }
+        if self.webhooks.max_attempts == 0 {
+            return Err(ConfigError::ZeroWebhookMaxAttempts);
+        }
+        if self.webhooks.timeout_secs == 0 {
+            return Err(ConfigError::ZeroWebhookTimeout);
+        }
+        if self.webhooks.max_dead_letters == 0 {
+            return Err(ConfigError::ZeroWebhookMaxDeadLetters);
+        }
+        if self.webhooks.max_concurrent_deliveries == 0 {
+            return Err(ConfigError::ZeroWebhookMaxConcurrentDeliveries);
+        }
+        if self.attachments.max_bytes == 0 {
+            return Err(ConfigError::ZeroAttachmentMaxBytes);
+        }
+        if self.encryption.key.is_none() && !self.encryption.previous_keys.is_empty() {
+            return Err(ConfigError::EncryptionKeyRequired);
+        }
+        match self.replication.role {
+            ReplicationRole::Standalone => {}
+            ReplicationRole::Leader => {
+                if self.replication.listen.is_none() {
+                    return Err(ConfigError::ReplicationListenRequired);
+                }
+            }
+            ReplicationRole::Follower => {
+                if self.replication.leader_addr.is_none() || self.replication.leader_url.is_none() {
+                    return Err(ConfigError::LeaderRequired);
+                }
+                // フォロワーのチケットはリーダーから複製するため、永続化すると再起動後にリーダーと食い違う。
+                if self.storage.backend != StorageBackend::Memory {
+                    return Err(ConfigError::FollowerStorage);
+                }
+            }
+        }
+        if let Some(node_id) = self.cluster.node_id {
+            self.validate_cluster(node_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// クラスタ設定を検証する。
+    fn validate_cluster(&self, node_id: NodeId) -> ConfigResult<()> {
+        if self.replication.role != ReplicationRole::Standalone {
+            return Err(ConfigError::ClusterReplication);
+        }
+        if self.cluster.listen.is_none() {
+            return Err(ConfigError::ClusterListenRequired);
+        }
+        if self.cluster.tick_ms == 0 {
+            return Err(ConfigError::ZeroClusterTick);
+        }
+        if self.cluster.snapshot_entries == 0 {
+            return Err(ConfigError::ZeroClusterSnapshotEntries);
+        }
+        if self.cluster.token.is_none() {
+            return Err(ConfigError::ClusterTokenRequired);
+        }
+        let mut ids = std::collections::BTreeSet::from([node_id]);
+        for peer in &self.cluster.peers {
+            if !ids.insert(peer.id) {
+                return Err(ConfigError::DuplicateClusterNode(peer.id));
+            }
+        }
+        if self.cluster.join {
+            // 追加するノードは、既存のクラスタのメンバーに接続してこのノードのアドレスを知らせる。
+            if self.cluster.advertise.is_none() {
+                return Err(ConfigError::ClusterAdvertiseRequired);
+            }
+            if self.cluster.peers.is_empty() {
+                return Err(ConfigError::ClusterJoinPeersRequired);
+            }
+        } else if !CLUSTER_SIZES.contains(&ids.len()) {
+            return Err(ConfigError::ClusterSize(ids.len()));
+        }
+
+        Ok(())
+    }
+
+    /// 設定をTOML形式の文字列で返す。
+    ///
+    /// 暗号化の鍵は、鍵そのものではなく鍵IDだけを書き出す。
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("設定は常にTOMLに変換できる")
+    }
+}
+
+/// 設定エラー
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("設定ファイル`{}`を読み込めません: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("設定ファイル`{}`の形式が誤っています: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("`file`ストレージバックエンドには、データディレクトリが必要です。")]
+    DataDirRequired,
+    #[error("リクエストボディの最大バイト数は1以上です。")]
+    ZeroMaxBodyBytes,
+    #[error("復元するアーカイブの最大バイト数は1以上です。")]
+    ZeroMaxRestoreBytes,
+    #[error("リクエストのタイムアウト秒数は1以上です。")]
+    ZeroRequestTimeout,
+    #[error("チケットストアのキューの容量は1以上です。")]
+    ZeroStoreQueueCapacity,
+    #[error("レディネスプローブのチケットストアの応答待ちミリ秒数は1以上です。")]
+    ZeroHealthLockTimeout,
+    #[error("Webhookの配信を試行する最大回数は1以上です。")]
+    ZeroWebhookMaxAttempts,
+    #[error("Webhookの配信のタイムアウト秒数は1以上です。")]
+    ZeroWebhookTimeout,
+    #[error("Webhookのデッドレターに保持する配信の最大数は1以上です。")]
+    ZeroWebhookMaxDeadLetters,
+    #[error("Webhookの配信を同時に試行する最大数は1以上です。")]
+    ZeroWebhookMaxConcurrentDeliveries,
+    #[error("添付ファイルの最大バイト数は1以上です。")]
+    ZeroAttachmentMaxBytes,
+    #[error("`leader`には、複製を待ち受けるソケットアドレスが必要です。")]
+    ReplicationListenRequired,
+    #[error(
+        "`follower`には、リーダーが複製を待ち受けるソケットアドレスとリーダーのURLが必要です。"
+    )]
+    LeaderRequired,
+    #[error("`follower`のストレージバックエンドは、`memory`だけです。")]
+    FollowerStorage,
+    #[error("クラスタモードでは、複製での役割は`standalone`だけです。")]
+    ClusterReplication,
+    #[error("クラスタモードには、ノード間の通信を待ち受けるソケットアドレスが必要です。")]
+    ClusterListenRequired,
+    #[error("Raftの1ティックのミリ秒数は1以上です。")]
+    ZeroClusterTick,
+    #[error("Raftのログを切り詰めるまでに適用するエントリの数は1以上です。")]
+    ZeroClusterSnapshotEntries,
+    #[error("クラスタモードには、ノード間の通信を認証するトークンが必要です。")]
+    ClusterTokenRequired,
+    #[error("クラスタのノードID`{0}`が重複しています。")]
+    DuplicateClusterNode(NodeId),
+    #[error("クラスタのノード数は、このノードを含めて3または5です: {0}")]
+    ClusterSize(usize),
+    #[error(
+        "既存のクラスタに追加するノードには、他のノードに知らせるこのノードのアドレスが必要です。"
+    )]
+    ClusterAdvertiseRequired,
+    #[error("既存のクラスタに追加するノードには、既存のクラスタのメンバーが必要です。")]
+    ClusterJoinPeersRequired,
+    #[error("鍵ファイル`{}`を読み込めません: {source}", path.display())]
+    ReadEncryptionKey {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("鍵ファイル`{}`の鍵は、32バイトを表現する16進数64文字です。", path.display())]
+    InvalidEncryptionKey { path: PathBuf },
+    #[error("以前の暗号化の鍵を使用するには、現在の暗号化の鍵が必要です。")]
+    EncryptionKeyRequired,
+    #[error("トークンファイル`{}`を読み込めません: {source}", path.display())]
+    ReadAdminToken {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("トークンファイル`{}`のトークンは、空白を含まない1文字以上の表示可能なASCII文字列です。", path.display())]
+    InvalidAdminToken { path: PathBuf },
+    #[error("トークンファイル`{}`を読み込めません: {source}", path.display())]
+    ReadClusterToken {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("トークンファイル`{}`のトークンは、空白を含まない1文字以上の表示可能なASCII文字列です。", path.display())]
+    InvalidClusterToken { path: PathBuf },
+}
+
+/// 設定結果
+pub type ConfigResult<T> = Result<T, ConfigError>;
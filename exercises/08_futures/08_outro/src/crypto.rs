@@ -0,0 +1,560 @@
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use sha2::{Digest, Sha256};
+
+use crate::config::EncryptionConfig;
+
+/// 暗号化したファイルとレコードの先頭に置く識別子
+const SEALED_MAGIC: &[u8; 4] = b"TSE1";
+
+/// 暗号化したアーカイブの先頭に置く識別子
+const ARCHIVE_MAGIC: &[u8; 4] = b"TSA1";
+
+/// 鍵IDのバイト数
+const KEY_ID_LEN: usize = 8;
+
+/// 暗号化したファイルとレコードのナンスのバイト数
+const NONCE_LEN: usize = 24;
+
+/// 暗号化したアーカイブのナンスのバイト数
+///
+/// STREAMは、ナンスの末尾5バイトをチャンクの番号と最後のチャンクであることの印に使用する。
+const ARCHIVE_NONCE_LEN: usize = NONCE_LEN - 5;
+
+/// 認証タグのバイト数
+const TAG_LEN: usize = 16;
+
+/// 暗号化したファイルとレコードのヘッダのバイト数
+const SEALED_HEADER_LEN: usize = SEALED_MAGIC.len() + KEY_ID_LEN + NONCE_LEN;
+
+/// 暗号化したアーカイブのヘッダのバイト数
+const ARCHIVE_HEADER_LEN: usize = ARCHIVE_MAGIC.len() + KEY_ID_LEN + ARCHIVE_NONCE_LEN;
+
+/// 暗号化したアーカイブの、1つのチャンクに含める平文のバイト数
+const ARCHIVE_CHUNK_BYTES: usize = 64 * 1024;
+
+/// 暗号化したアーカイブの関連データ
+const ARCHIVE_CONTEXT: &[u8] = b"backup";
+
+/// 暗号化によって増えるバイト数
+pub const SEALED_OVERHEAD: u64 = (SEALED_HEADER_LEN + TAG_LEN) as u64;
+
+/// 暗号化の鍵ID
+///
+/// 鍵のSHA-256ハッシュ値の先頭8バイトであり、暗号化したファイルに記録して、復号に使用する鍵を選ぶ。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyId([u8; KEY_ID_LEN]);
+
+impl std::fmt::Display for KeyId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+/// 暗号化の鍵
+///
+/// 鍵の内容は表示せず、鍵IDだけを表示する。
+#[derive(Clone)]
+pub struct EncryptionKey {
+    id: KeyId,
+    cipher: XChaCha20Poly1305,
+}
+
+/// 暗号化の鍵エラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("暗号化の鍵は、32バイトを表現する16進数64文字です。")]
+pub struct EncryptionKeyError;
+
+impl EncryptionKey {
+    /// 鍵IDを返す。
+    pub fn id(&self) -> KeyId {
+        self.id
+    }
+}
+
+/// 文字列から暗号化の鍵を構築する。
+///
+/// 鍵ファイルの末尾の改行などを許容するため、前後の空白は無視する。
+///
+/// # 引数
+///
+/// * `s` - 32バイトの鍵を表現する16進数64文字
+///
+/// # 戻り値
+///
+/// 暗号化の鍵
+fn encryption_key_from_str(s: &str) -> Result<EncryptionKey, EncryptionKeyError> {
+    let mut key = [0; 32];
+    hex::decode_to_slice(s.trim(), &mut key).map_err(|_| EncryptionKeyError)?;
+    let digest = Sha256::digest(key);
+    let mut id = [0; KEY_ID_LEN];
+    id.copy_from_slice(&digest[..KEY_ID_LEN]);
+
+    Ok(EncryptionKey {
+        id: KeyId(id),
+        cipher: XChaCha20Poly1305::new(&key.into()),
+    })
+}
+
+impl TryFrom<String> for EncryptionKey {
+    type Error = EncryptionKeyError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        encryption_key_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for EncryptionKey {
+    type Error = EncryptionKeyError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        encryption_key_from_str(value)
+    }
+}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptionKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for EncryptionKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl serde::Serialize for EncryptionKey {
+    /// 有効な設定を表示するときに鍵を漏らさないように、鍵IDだけを書き出す。
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("id:{}", self.id))
+    }
+}
+
+/// 暗号化されたデータの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sealing {
+    /// 暗号化されていない。
+    Plain,
+    /// 以前の鍵で暗号化されている。
+    Previous,
+    /// 現在の鍵で暗号化されている。
+    Current,
+}
+
+/// 復号エラー
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum DecryptError {
+    #[error("暗号化されていますが、暗号化の鍵が設定されていません。")]
+    KeyRequired,
+    #[error("鍵ID`{0}`の鍵が、現在の鍵にも以前の鍵にもありません。")]
+    UnknownKey(KeyId),
+    #[error("認証に失敗しました。改ざんされているか、壊れています。")]
+    Tampered,
+    #[error("暗号化されたレコードが途中で途切れています。")]
+    Truncated,
+    #[error("暗号化が必須ですが、暗号化されていません。")]
+    Unsealed,
+}
+
+/// 復号結果
+pub type DecryptResult<T> = Result<T, DecryptError>;
+
+/// 永続化するデータの暗号化
+///
+/// 鍵が設定されていない場合は暗号化せず、暗号化されたデータを復号できない。
+/// 鍵が設定されている場合は、現在の鍵でXChaCha20-Poly1305によって暗号化し、現在の鍵と以前の鍵で復号する。
+/// 暗号化を有効にする前のデータを読み込めるように、暗号化されていないデータはそのまま読み込む。
+/// ただし、[`Encryption::require_sealed`]で暗号化を必須にした場合は、暗号化されていないデータを読み込まない。
+///
+/// 暗号化したデータには、ファイル名などの関連データを認証させるため、別のファイルと入れ替えても復号できない。
+#[derive(Debug, Clone, Default)]
+pub struct Encryption {
+    keys: Option<Arc<Keys>>,
+    /// 暗号化されていないデータを読み込まない場合は`true`
+    required: bool,
+}
+
+#[derive(Debug)]
+struct Keys {
+    current: EncryptionKey,
+    previous: Vec<EncryptionKey>,
+}
+
+impl Encryption {
+    /// 設定に従って暗号化を構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `config` - 暗号化設定
+    ///
+    /// # 戻り値
+    ///
+    /// 暗号化
+    pub fn new(config: &EncryptionConfig) -> Self {
+        let keys = config.key.as_ref().map(|current| {
+            Arc::new(Keys {
+                current: current.clone(),
+                previous: config.previous_keys.clone(),
+            })
+        });
+
+        Self {
+            keys,
+            required: false,
+        }
+    }
+
+    /// 暗号化されていないデータを読み込まないようにする。
+    ///
+    /// すべてのデータを暗号化し終えた後は、暗号化されていないデータは差し込まれたものであるため、
+    /// 復号するときに[`DecryptError::Unsealed`]を返す。
+    pub fn require_sealed(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// 暗号化が有効か確認する。
+    pub fn is_enabled(&self) -> bool {
+        self.keys.is_some()
+    }
+
+    /// 現在の鍵で暗号化し直す必要があるか確認する。
+    ///
+    /// # 引数
+    ///
+    /// * `sealing` - 読み込んだデータの状態
+    ///
+    /// # 戻り値
+    ///
+    /// 暗号化が有効で、データが現在の鍵で暗号化されていない場合は`true`
+    pub fn is_stale(&self, sealing: Sealing) -> bool {
+        self.is_enabled() && sealing != Sealing::Current
+    }
+
+    /// 鍵IDの鍵と、その鍵の状態を返す。
+    fn key(&self, id: KeyId) -> DecryptResult<(&EncryptionKey, Sealing)> {
+        let keys = self.keys.as_ref().ok_or(DecryptError::KeyRequired)?;
+        if keys.current.id == id {
+            return Ok((&keys.current, Sealing::Current));
+        }
+        keys.previous
+            .iter()
+            .find(|key| key.id == id)
+            .map(|key| (key, Sealing::Previous))
+            .ok_or(DecryptError::UnknownKey(id))
+    }
+
+    /// 暗号化が有効な場合は、内容を現在の鍵で暗号化する。
+    ///
+    /// # 引数
+    ///
+    /// * `context` - 暗号化した内容と一緒に認証する関連データ（ファイル名など）
+    /// * `plaintext` - 内容
+    ///
+    /// # 戻り値
+    ///
+    /// 暗号化した内容、暗号化が無効な場合は内容そのもの
+    pub fn seal<'a>(&self, context: &str, plaintext: &'a [u8]) -> Cow<'a, [u8]> {
+        let Some(keys) = &self.keys else {
+            return Cow::Borrowed(plaintext);
+        };
+        let key = &keys.current;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = key
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: context.as_bytes(),
+                },
+            )
+            .expect("XChaCha20-Poly1305の暗号化は、平文の長さが上限を超えない限り失敗しない");
+
+        let mut sealed = Vec::with_capacity(SEALED_HEADER_LEN + ciphertext.len());
+        sealed.extend_from_slice(SEALED_MAGIC);
+        sealed.extend_from_slice(&key.id.0);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Cow::Owned(sealed)
+    }
+
+    /// 暗号化されている場合は復号する。
+    ///
+    /// # 引数
+    ///
+    /// * `context` - 暗号化したときの関連データ
+    /// * `data` - 読み込んだデータ
+    ///
+    /// # 戻り値
+    ///
+    /// 内容と、読み込んだデータの状態
+    pub fn open<'a>(
+        &self,
+        context: &str,
+        data: &'a [u8],
+    ) -> DecryptResult<(Cow<'a, [u8]>, Sealing)> {
+        if !is_sealed(data) {
+            return self.plain(data);
+        }
+        if data.len() < SEALED_HEADER_LEN + TAG_LEN {
+            return Err(DecryptError::Tampered);
+        }
+        let (header, ciphertext) = data.split_at(SEALED_HEADER_LEN);
+        let (key, sealing) = self.key(key_id(&header[SEALED_MAGIC.len()..]))?;
+        let nonce = XNonce::from_slice(&header[SEALED_MAGIC.len() + KEY_ID_LEN..]);
+        let plaintext = key
+            .cipher
+            .decrypt(
+                nonce,
+                Payload {
+                    msg: ciphertext,
+                    aad: context.as_bytes(),
+                },
+            )
+            .map_err(|_| DecryptError::Tampered)?;
+
+        Ok((Cow::Owned(plaintext), sealing))
+    }
+
+    /// 暗号化が有効な場合は、JSONの1行のレコードを暗号化してBase64で表現する。
+    ///
+    /// # 引数
+    ///
+    /// * `context` - 暗号化した内容と一緒に認証する関連データ（ファイル名と行番号など）
+    /// * `json` - 改行を含まないJSONのレコード
+    ///
+    /// # 戻り値
+    ///
+    /// 改行を含まない1行のレコード
+    pub fn seal_line<'a>(&self, context: &str, json: &'a [u8]) -> Cow<'a, [u8]> {
+        match self.seal(context, json) {
+            Cow::Borrowed(json) => Cow::Borrowed(json),
+            Cow::Owned(sealed) => Cow::Owned(BASE64.encode(sealed).into_bytes()),
+        }
+    }
+
+    /// [`Encryption::seal_line`]で書き出した1行のレコードを復号する。
+    ///
+    /// JSONのレコードは`{`で始まるため、それ以外で始まるレコードを暗号化されたレコードとして扱う。
+    /// 暗号化されたレコードがBase64として途切れているか、暗号化の形式より短い場合は、
+    /// 書き込みが途中で途切れたものとして[`DecryptError::Truncated`]を返す。
+    ///
+    /// # 引数
+    ///
+    /// * `context` - 暗号化したときの関連データ
+    /// * `line` - 改行を除いた1行のレコード
+    ///
+    /// # 戻り値
+    ///
+    /// JSONのレコードと、読み込んだレコードの状態
+    pub fn open_line<'a>(
+        &self,
+        context: &str,
+        line: &'a str,
+    ) -> DecryptResult<(Cow<'a, [u8]>, Sealing)> {
+        if line.is_empty() || line.starts_with('{') {
+            return self.plain(line.as_bytes());
+        }
+        let sealed = BASE64.decode(line).map_err(|_| DecryptError::Truncated)?;
+        if !is_sealed(&sealed) && !SEALED_MAGIC.starts_with(&sealed) {
+            return Err(DecryptError::Tampered);
+        }
+        if sealed.len() < SEALED_HEADER_LEN + TAG_LEN {
+            return Err(DecryptError::Truncated);
+        }
+        let (json, sealing) = self.open(context, &sealed)?;
+
+        Ok((Cow::Owned(json.into_owned()), sealing))
+    }
+
+    /// 暗号化されていないデータを、暗号化が必須でない場合だけそのまま返す。
+    fn plain<'a>(&self, data: &'a [u8]) -> DecryptResult<(Cow<'a, [u8]>, Sealing)> {
+        if self.required {
+            return Err(DecryptError::Unsealed);
+        }
+
+        Ok((Cow::Borrowed(data), Sealing::Plain))
+    }
+
+    /// 暗号化が有効な場合は、書き込んだ内容をアーカイブとして暗号化するライターを返す。
+    ///
+    /// アーカイブは、書き込みながら一定のバイト数ごとにSTREAMで暗号化するため、内容全体をメモリに保持しない。
+    /// 書き込み終えたら、[`ArchiveWriter::finish`]を呼び出さなければならない。
+    ///
+    /// # 引数
+    ///
+    /// * `writer` - 書き出し先
+    ///
+    /// # 戻り値
+    ///
+    /// アーカイブのライター
+    pub fn archive_writer<W: Write>(&self, writer: W) -> ArchiveWriter<W> {
+        let encryptor = self.keys.as_ref().map(|keys| {
+            let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+            let nonce = &nonce[..ARCHIVE_NONCE_LEN];
+            let mut header = Vec::with_capacity(ARCHIVE_HEADER_LEN);
+            header.extend_from_slice(ARCHIVE_MAGIC);
+            header.extend_from_slice(&keys.current.id.0);
+            header.extend_from_slice(nonce);
+            let encryptor = EncryptorBE32::from_aead(keys.current.cipher.clone(), nonce.into());
+            (encryptor, header)
+        });
+        let (encryptor, pending) = match encryptor {
+            Some((encryptor, header)) => (Some(encryptor), header),
+            None => (None, vec![]),
+        };
+
+        ArchiveWriter {
+            writer,
+            encryptor,
+            pending,
+            buffer: vec![],
+        }
+    }
+
+    /// 暗号化されたアーカイブの場合は復号する。
+    ///
+    /// # 引数
+    ///
+    /// * `data` - アーカイブ
+    ///
+    /// # 戻り値
+    ///
+    /// 復号したアーカイブ、暗号化されていない場合はアーカイブそのもの
+    pub fn open_archive<'a>(&self, data: &'a [u8]) -> DecryptResult<Cow<'a, [u8]>> {
+        if !data.starts_with(ARCHIVE_MAGIC) {
+            return Ok(Cow::Borrowed(data));
+        }
+        if data.len() < ARCHIVE_HEADER_LEN {
+            return Err(DecryptError::Tampered);
+        }
+        let (header, mut rest) = data.split_at(ARCHIVE_HEADER_LEN);
+        let (key, _) = self.key(key_id(&header[ARCHIVE_MAGIC.len()..]))?;
+        let nonce = &header[ARCHIVE_MAGIC.len() + KEY_ID_LEN..];
+        let mut decryptor = DecryptorBE32::from_aead(key.cipher.clone(), nonce.into());
+
+        let mut plaintext = Vec::with_capacity(rest.len());
+        loop {
+            let Some((len, chunk)) = rest.split_first_chunk::<4>() else {
+                return Err(DecryptError::Tampered);
+            };
+            let len = u32::from_be_bytes(*len) as usize;
+            if chunk.len() < len {
+                return Err(DecryptError::Tampered);
+            }
+            let (chunk, remaining) = chunk.split_at(len);
+            let payload = Payload {
+                msg: chunk,
+                aad: ARCHIVE_CONTEXT,
+            };
+            // 最後のチャンクは別のナンスで暗号化するため、途中で切り詰められたアーカイブは復号できない。
+            if remaining.is_empty() {
+                let chunk = decryptor
+                    .decrypt_last(payload)
+                    .map_err(|_| DecryptError::Tampered)?;
+                plaintext.extend_from_slice(&chunk);
+                return Ok(Cow::Owned(plaintext));
+            }
+            let chunk = decryptor
+                .decrypt_next(payload)
+                .map_err(|_| DecryptError::Tampered)?;
+            plaintext.extend_from_slice(&chunk);
+            rest = remaining;
+        }
+    }
+}
+
+/// データが、[`Encryption::seal`]で暗号化されているか確認する。
+///
+/// 先頭の4バイトだけで判定できる。
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(SEALED_MAGIC)
+}
+
+/// 鍵IDの位置から始まるバイト列から、鍵IDを読み込む。
+fn key_id(bytes: &[u8]) -> KeyId {
+    let mut id = [0; KEY_ID_LEN];
+    id.copy_from_slice(&bytes[..KEY_ID_LEN]);
+    KeyId(id)
+}
+
+/// 書き込んだ内容をアーカイブとして暗号化するライター
+///
+/// 暗号化が無効な場合は、書き込んだ内容をそのまま書き出す。
+/// 暗号化したアーカイブは、ヘッダの後に、4バイトのビッグエンディアンの長さと暗号化したチャンクを繰り返す。
+pub struct ArchiveWriter<W> {
+    writer: W,
+    encryptor: Option<EncryptorBE32<XChaCha20Poly1305>>,
+    /// まだ書き出していないヘッダ
+    pending: Vec<u8>,
+    /// まだ暗号化していない内容
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    /// 暗号化したチャンクを書き出す。
+    fn write_chunk(&mut self, chunk: &[u8]) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            self.writer.write_all(&self.pending)?;
+            self.pending.clear();
+        }
+        let len = u32::try_from(chunk.len()).map_err(io::Error::other)?;
+        self.writer.write_all(&len.to_be_bytes())?;
+        self.writer.write_all(chunk)
+    }
+
+    /// 残りの内容を最後のチャンクとして暗号化して書き出し、書き出し先を返す。
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(encryptor) = self.encryptor.take() {
+            let buffer = std::mem::take(&mut self.buffer);
+            let chunk = encryptor
+                .encrypt_last(Payload {
+                    msg: &buffer,
+                    aad: ARCHIVE_CONTEXT,
+                })
+                .map_err(|_| io::Error::other("アーカイブを暗号化できません。"))?;
+            self.write_chunk(&chunk)?;
+        }
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for ArchiveWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(encryptor) = &mut self.encryptor else {
+            return self.writer.write(buf);
+        };
+        // 最後のチャンクは`finish`で暗号化するため、次の内容が書き込まれてからチャンクを暗号化する。
+        if self.buffer.len() == ARCHIVE_CHUNK_BYTES {
+            let chunk = encryptor
+                .encrypt_next(Payload {
+                    msg: &self.buffer,
+                    aad: ARCHIVE_CONTEXT,
+                })
+                .map_err(|_| io::Error::other("アーカイブを暗号化できません。"))?;
+            self.buffer.clear();
+            self.write_chunk(&chunk)?;
+        }
+        let len = buf.len().min(ARCHIVE_CHUNK_BYTES - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
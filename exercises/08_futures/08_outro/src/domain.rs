@@ -0,0 +1,682 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::{json, Value};
+
+use crate::models::{
+    Attachment, AttachmentId, BlobDigest, Label, Priority, Ticket, TicketDescription, TicketId,
+    TicketKey, TicketStatus, TicketTitle,
+};
+use crate::reports::StatusTimeline;
+use crate::search::SearchIndex;
+use crate::shards::Shard;
+
+/// 現在のドメインイベントのスキーマバージョン
+///
+/// * `1` - イベントソーシングに移行する前のジャーナルが記録していた、変更後のチケットの状態
+/// * `2` - [`DomainEvent`]
+pub const EVENT_SCHEMA_VERSION: u32 = 2;
+
+/// スキーマバージョンごとの、1つ新しいスキーマバージョンへの変換
+///
+/// `UPCASTERS[n - 1]`は、スキーマバージョン`n`のイベントをスキーマバージョン`n + 1`に変換する。
+/// ドメインイベントの形式を変更する場合は、[`EVENT_SCHEMA_VERSION`]を上げて、ここに変換を追加する。
+const UPCASTERS: [fn(Value) -> Value; EVENT_SCHEMA_VERSION as usize - 1] = [import_ticket_state];
+
+/// チケットのドメインイベント
+///
+/// チケットの状態は直接変更せず、チケットのドメインイベントを記録された順に適用して構築する。
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all_fields = "camelCase")]
+pub enum DomainEvent {
+    /// チケットが登録された。
+    TicketCreated {
+        key: TicketKey,
+        title: TicketTitle,
+        description: TicketDescription,
+        priority: Priority,
+        due_date: Option<NaiveDate>,
+    },
+    /// チケットを、イベントの履歴を持たない状態のまま取り込んだ。
+    ///
+    /// 他のプロジェクトから移動したチケットと、イベントソーシングに移行する前に記録されたチケットを表現する。
+    TicketImported { ticket: Box<Ticket> },
+    /// タイトルが変更された。
+    TitleChanged { title: TicketTitle },
+    /// 説明が変更された。
+    DescriptionChanged { description: TicketDescription },
+    /// ステータスが変更された。
+    StatusChanged {
+        from: TicketStatus,
+        to: TicketStatus,
+    },
+    /// 優先度が変更された。
+    PriorityChanged { priority: Priority },
+    /// 期限が変更された。
+    DueDateChanged { due_date: Option<NaiveDate> },
+    /// 親チケットが変更された。
+    ParentChanged { parent: Option<TicketId> },
+    /// ブロッカーが追加された。
+    BlockerAdded { blocker: TicketId },
+    /// ブロッカーが取り除かれた。
+    BlockerRemoved { blocker: TicketId },
+    /// 添付ファイルが追加された。
+    AttachmentAdded { attachment: Attachment },
+    /// 添付ファイルが削除された。
+    AttachmentRemoved { attachment: AttachmentId },
+    /// ラベルが追加された。
+    LabelAdded { label: Label },
+    /// ラベルが取り除かれた。
+    LabelRemoved { label: Label },
+    /// どのフィールドも変更しないパッチが適用された。
+    ///
+    /// バージョンと更新日時だけを進める。
+    Touched,
+    /// チケットが他のプロジェクトに移動した。
+    MovedOut { moved_to: TicketKey },
+}
+
+impl DomainEvent {
+    /// チケットのタイトルまたは説明を変更するイベントか確認する。
+    fn changes_text(&self) -> bool {
+        matches!(
+            self,
+            Self::TicketCreated { .. }
+                | Self::TicketImported { .. }
+                | Self::TitleChanged { .. }
+                | Self::DescriptionChanged { .. }
+                | Self::MovedOut { .. }
+        )
+    }
+
+    /// チケットの添付ファイルを変更するイベントか確認する。
+    fn changes_attachments(&self) -> bool {
+        matches!(
+            self,
+            Self::TicketImported { .. }
+                | Self::AttachmentAdded { .. }
+                | Self::AttachmentRemoved { .. }
+                | Self::MovedOut { .. }
+        )
+    }
+
+    /// チケットの親チケットを変更するイベントか確認する。
+    fn changes_parent(&self) -> bool {
+        matches!(
+            self,
+            Self::TicketImported { .. } | Self::ParentChanged { .. } | Self::MovedOut { .. }
+        )
+    }
+
+    /// チケットのブロッカーを変更するイベントか確認する。
+    fn changes_blockers(&self) -> bool {
+        matches!(
+            self,
+            Self::TicketImported { .. }
+                | Self::BlockerAdded { .. }
+                | Self::BlockerRemoved { .. }
+                | Self::MovedOut { .. }
+        )
+    }
+
+    /// チケットのステータスを変更するイベントか確認する。
+    fn changes_status(&self) -> bool {
+        matches!(
+            self,
+            Self::TicketCreated { .. }
+                | Self::TicketImported { .. }
+                | Self::StatusChanged { .. }
+                | Self::MovedOut { .. }
+        )
+    }
+}
+
+/// 記録されたドメインイベント
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedEvent {
+    pub ticket: TicketId,
+    /// イベントを適用した後のチケットのバージョン
+    ///
+    /// 1つの操作で記録したイベントは、同じバージョンを持つ。
+    pub version: u64,
+    pub occurred_at: DateTime<Utc>,
+    pub event: DomainEvent,
+}
+
+/// 永続化されたドメインイベント
+///
+/// イベントは記録したときのスキーマバージョンのまま永続化し、読み込むときに現在のスキーマバージョンに変換する。
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredEvent {
+    pub schema: u32,
+    pub ticket: TicketId,
+    pub version: u64,
+    pub occurred_at: DateTime<Utc>,
+    pub event: Value,
+}
+
+impl StoredEvent {
+    /// ドメインイベントを、現在のスキーマバージョンで永続化する形式に変換する。
+    pub fn new(record: &RecordedEvent) -> Self {
+        Self {
+            schema: EVENT_SCHEMA_VERSION,
+            ticket: record.ticket,
+            version: record.version,
+            occurred_at: record.occurred_at,
+            event: serde_json::to_value(&record.event)
+                .expect("ドメインイベントは常にJSONに変換できる"),
+        }
+    }
+
+    /// イベントソーシングに移行する前に記録された、変更後のチケットの状態をスキーマバージョン1のイベントとして扱う。
+    pub fn from_ticket_state(ticket: &Ticket) -> Self {
+        Self {
+            schema: 1,
+            ticket: ticket.id,
+            version: ticket.version,
+            occurred_at: ticket.updated_at,
+            event: serde_json::to_value(ticket).expect("チケットは常にJSONに変換できる"),
+        }
+    }
+
+    /// 現在のスキーマバージョンに変換して、ドメインイベントを復元する。
+    ///
+    /// # 戻り値
+    ///
+    /// 記録されたドメインイベント
+    pub fn upcast(self) -> EventResult<RecordedEvent> {
+        if self.schema == 0 || self.schema > EVENT_SCHEMA_VERSION {
+            return Err(EventError::UnsupportedSchema(self.schema));
+        }
+        let event = UPCASTERS[self.schema as usize - 1..]
+            .iter()
+            .fold(self.event, |event, upcast| upcast(event));
+
+        Ok(RecordedEvent {
+            ticket: self.ticket,
+            version: self.version,
+            occurred_at: self.occurred_at,
+            event: serde_json::from_value(event).map_err(EventError::Invalid)?,
+        })
+    }
+}
+
+/// スキーマバージョン1のチケットの状態を、そのチケットを取り込むイベントに変換する。
+fn import_ticket_state(ticket: Value) -> Value {
+    json!({"type": "TicketImported", "ticket": ticket})
+}
+
+/// チケットにドメインイベントを適用する。
+///
+/// # 引数
+///
+/// * `ticket` - 適用する前のチケット、登録される前または他のプロジェクトに移動した後は`None`
+/// * `record` - 記録されたドメインイベント
+///
+/// # 戻り値
+///
+/// 適用した後のチケット、他のプロジェクトに移動した場合は`None`
+pub fn apply(ticket: Option<Ticket>, record: &RecordedEvent) -> EventResult<Option<Ticket>> {
+    let inconsistent = || EventError::Inconsistent {
+        ticket: record.ticket,
+        version: record.version,
+    };
+    let mut ticket = match (&record.event, ticket) {
+        (
+            DomainEvent::TicketCreated {
+                key,
+                title,
+                description,
+                priority,
+                due_date,
+            },
+            None,
+        ) => {
+            let mut ticket = Ticket::new(
+                record.ticket,
+                key.clone(),
+                title.clone(),
+                description.clone(),
+                *priority,
+                *due_date,
+                record.occurred_at,
+            );
+            ticket.version = record.version;
+            return Ok(Some(ticket));
+        }
+        // 取り込んだチケットは、それまでの状態を置き換える。
+        (DomainEvent::TicketImported { ticket }, _) => (**ticket).clone(),
+        (DomainEvent::MovedOut { .. }, Some(_)) => return Ok(None),
+        (DomainEvent::TicketCreated { .. }, Some(_)) | (_, None) => return Err(inconsistent()),
+        (_, Some(ticket)) => ticket,
+    };
+    match &record.event {
+        DomainEvent::TitleChanged { title } => ticket.title = title.clone(),
+        DomainEvent::DescriptionChanged { description } => {
+            ticket.description = description.clone();
+        }
+        DomainEvent::StatusChanged { to, .. } => ticket.status = *to,
+        DomainEvent::PriorityChanged { priority } => ticket.priority = *priority,
+        DomainEvent::DueDateChanged { due_date } => ticket.due_date = *due_date,
+        DomainEvent::ParentChanged { parent } => ticket.parent = *parent,
+        DomainEvent::BlockerAdded { blocker } => {
+            ticket.blocked_by.insert(*blocker);
+        }
+        DomainEvent::BlockerRemoved { blocker } => {
+            ticket.blocked_by.remove(blocker);
+        }
+        DomainEvent::AttachmentAdded { attachment } => ticket.attachments.push(attachment.clone()),
+        DomainEvent::AttachmentRemoved { attachment } => {
+            ticket.attachments.retain(|a| a.id != *attachment);
+        }
+        DomainEvent::LabelAdded { label } => {
+            ticket.labels.insert(label.clone());
+        }
+        DomainEvent::LabelRemoved { label } => {
+            ticket.labels.remove(label);
+        }
+        DomainEvent::TicketCreated { .. }
+        | DomainEvent::TicketImported { .. }
+        | DomainEvent::Touched
+        | DomainEvent::MovedOut { .. } => {}
+    }
+    ticket.id = record.ticket;
+    ticket.updated_at = record.occurred_at;
+    ticket.version = record.version;
+
+    Ok(Some(ticket))
+}
+
+/// 1つのチケットのドメインイベントを最初から適用して、チケットを構築する。
+///
+/// # 引数
+///
+/// * `events` - 記録された順番の、チケットのドメインイベント
+///
+/// # 戻り値
+///
+/// チケット、登録されていないか他のプロジェクトに移動した場合は`None`
+pub fn replay<'a>(
+    events: impl IntoIterator<Item = &'a RecordedEvent>,
+) -> EventResult<Option<Ticket>> {
+    events.into_iter().try_fold(None, apply)
+}
+
+/// ドメインイベントから構築する、チケットの読み込み用のビュー
+///
+/// 射影は、ドメインイベントを記録された順番に最初から適用すれば、いつでも構築し直せる。
+pub trait Projection {
+    /// 射影を空にする。
+    fn clear(&mut self);
+
+    /// 射影がドメインイベントによって変わるか確認する。
+    ///
+    /// 変わらないイベントでは、射影のロックを取得しない。
+    fn observes(_event: &DomainEvent) -> bool
+    where
+        Self: Sized,
+    {
+        true
+    }
+
+    /// ドメインイベントを射影に反映する。
+    ///
+    /// # 引数
+    ///
+    /// * `record` - 記録されたドメインイベント
+    /// * `ticket` - イベントを適用した後のチケット、他のプロジェクトに移動した場合は`None`
+    fn project(&mut self, record: &RecordedEvent, ticket: Option<&Ticket>);
+}
+
+/// チケットIDごとの現在のチケット
+impl Projection for Shard {
+    fn clear(&mut self) {
+        BTreeMap::clear(self);
+    }
+
+    fn project(&mut self, record: &RecordedEvent, ticket: Option<&Ticket>) {
+        match ticket {
+            Some(ticket) => self.insert(record.ticket, ticket.clone()),
+            None => self.remove(&record.ticket),
+        };
+    }
+}
+
+impl Projection for SearchIndex {
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn observes(event: &DomainEvent) -> bool {
+        event.changes_text()
+    }
+
+    fn project(&mut self, record: &RecordedEvent, ticket: Option<&Ticket>) {
+        match ticket {
+            Some(ticket) if record.event.changes_text() => self.insert(ticket),
+            Some(_) => {}
+            None => self.remove(record.ticket),
+        }
+    }
+}
+
+/// チケットのステータスごとのチケットIDの索引
+#[derive(Debug, Clone, Default)]
+pub struct StatusIndex {
+    tickets: BTreeMap<TicketStatus, BTreeSet<TicketId>>,
+    statuses: BTreeMap<TicketId, TicketStatus>,
+}
+
+impl StatusIndex {
+    /// チケットを索引する。
+    ///
+    /// すでに索引したチケットの場合は、以前のステータスから取り除いてから索引し直す。
+    pub fn insert(&mut self, ticket: &Ticket) {
+        self.remove(ticket.id);
+        self.tickets
+            .entry(ticket.status)
+            .or_default()
+            .insert(ticket.id);
+        self.statuses.insert(ticket.id, ticket.status);
+    }
+
+    /// チケットを索引から取り除く。
+    pub fn remove(&mut self, id: TicketId) {
+        let Some(status) = self.statuses.remove(&id) else {
+            return;
+        };
+        if let Some(ids) = self.tickets.get_mut(&status) {
+            ids.remove(&id);
+        }
+    }
+
+    /// ステータスのチケットのチケットIDを、チケットID順に返す。
+    pub fn ids(&self, status: TicketStatus) -> Vec<TicketId> {
+        self.tickets
+            .get(&status)
+            .map(|ids| ids.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// チケットのステータスを返す。
+    pub fn status(&self, id: TicketId) -> Option<TicketStatus> {
+        self.statuses.get(&id).copied()
+    }
+}
+
+impl Projection for StatusIndex {
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn observes(event: &DomainEvent) -> bool {
+        event.changes_status()
+    }
+
+    fn project(&mut self, record: &RecordedEvent, ticket: Option<&Ticket>) {
+        match ticket {
+            Some(ticket) if record.event.changes_status() => self.insert(ticket),
+            Some(_) => {}
+            None => self.remove(record.ticket),
+        }
+    }
+}
+
+/// 親チケットごとのサブタスクのチケットIDの索引
+#[derive(Debug, Clone, Default)]
+pub struct SubtaskIndex {
+    subtasks: BTreeMap<TicketId, BTreeSet<TicketId>>,
+    parents: BTreeMap<TicketId, TicketId>,
+}
+
+impl SubtaskIndex {
+    /// チケットを親チケットのサブタスクとして索引する。
+    ///
+    /// すでに索引したチケットの場合は、以前の親チケットから取り除いてから索引し直す。
+    pub fn insert(&mut self, ticket: &Ticket) {
+        self.remove(ticket.id);
+        if let Some(parent) = ticket.parent {
+            self.subtasks.entry(parent).or_default().insert(ticket.id);
+            self.parents.insert(ticket.id, parent);
+        }
+    }
+
+    /// チケットを索引から取り除く。
+    pub fn remove(&mut self, id: TicketId) {
+        let Some(parent) = self.parents.remove(&id) else {
+            return;
+        };
+        if let Some(ids) = self.subtasks.get_mut(&parent) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.subtasks.remove(&parent);
+            }
+        }
+    }
+
+    /// 親チケットのサブタスクのチケットIDを、チケットID順に返す。
+    pub fn ids(&self, parent: TicketId) -> Vec<TicketId> {
+        self.subtasks
+            .get(&parent)
+            .map(|ids| ids.iter().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Projection for SubtaskIndex {
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn observes(event: &DomainEvent) -> bool {
+        event.changes_parent()
+    }
+
+    fn project(&mut self, record: &RecordedEvent, ticket: Option<&Ticket>) {
+        match ticket {
+            Some(ticket) if record.event.changes_parent() => self.insert(ticket),
+            Some(_) => {}
+            None => self.remove(record.ticket),
+        }
+    }
+}
+
+/// ブロッカーごとのブロックされているチケットのチケットIDの索引
+#[derive(Debug, Clone, Default)]
+pub struct BlockerIndex {
+    blocking: BTreeMap<TicketId, BTreeSet<TicketId>>,
+    blockers: BTreeMap<TicketId, BTreeSet<TicketId>>,
+}
+
+impl BlockerIndex {
+    /// チケットを、ブロッカーごとにブロックされているチケットとして索引する。
+    ///
+    /// すでに索引したチケットの場合は、以前のブロッカーから取り除いてから索引し直す。
+    pub fn insert(&mut self, ticket: &Ticket) {
+        self.remove(ticket.id);
+        for &blocker in &ticket.blocked_by {
+            self.blocking.entry(blocker).or_default().insert(ticket.id);
+        }
+        if !ticket.blocked_by.is_empty() {
+            self.blockers.insert(ticket.id, ticket.blocked_by.clone());
+        }
+    }
+
+    /// チケットを索引から取り除く。
+    pub fn remove(&mut self, id: TicketId) {
+        for blocker in self.blockers.remove(&id).unwrap_or_default() {
+            if let Some(ids) = self.blocking.get_mut(&blocker) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.blocking.remove(&blocker);
+                }
+            }
+        }
+    }
+
+    /// ブロッカーがブロックしているチケットのチケットIDを、チケットID順に返す。
+    pub fn ids(&self, blocker: TicketId) -> Vec<TicketId> {
+        self.blocking
+            .get(&blocker)
+            .map(|ids| ids.iter().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Projection for BlockerIndex {
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn observes(event: &DomainEvent) -> bool {
+        event.changes_blockers()
+    }
+
+    fn project(&mut self, record: &RecordedEvent, ticket: Option<&Ticket>) {
+        match ticket {
+            Some(ticket) if record.event.changes_blockers() => self.insert(ticket),
+            Some(_) => {}
+            None => self.remove(record.ticket),
+        }
+    }
+}
+
+impl Projection for StatusTimeline {
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn observes(event: &DomainEvent) -> bool {
+        event.changes_status()
+    }
+
+    fn project(&mut self, record: &RecordedEvent, ticket: Option<&Ticket>) {
+        match (&record.event, ticket) {
+            (_, None) => self.untrack(record.ticket),
+            (
+                DomainEvent::TicketCreated { .. } | DomainEvent::TicketImported { .. },
+                Some(ticket),
+            ) => self.track(record.ticket, ticket.status),
+            (DomainEvent::StatusChanged { to, .. }, Some(_)) => {
+                self.transition(record.ticket, *to, record.occurred_at)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 添付ファイルの内容ごとの、内容を参照している添付ファイルの数
+///
+/// 参照している添付ファイルがなくなった内容は、削除できる。
+#[derive(Debug, Clone, Default)]
+pub struct BlobReferences {
+    counts: BTreeMap<BlobDigest, usize>,
+    tickets: BTreeMap<TicketId, Vec<BlobDigest>>,
+}
+
+impl BlobReferences {
+    /// チケットの添付ファイルの参照を数える。
+    ///
+    /// すでに数えたチケットの場合は、以前の添付ファイルの参照を取り除いてから数え直す。
+    pub fn insert(&mut self, ticket: &Ticket) {
+        self.remove(ticket.id);
+        let digests: Vec<_> = ticket
+            .attachments
+            .iter()
+            .map(|attachment| attachment.digest.clone())
+            .collect();
+        for digest in &digests {
+            *self.counts.entry(digest.clone()).or_default() += 1;
+        }
+        if !digests.is_empty() {
+            self.tickets.insert(ticket.id, digests);
+        }
+    }
+
+    /// チケットの添付ファイルの参照を取り除く。
+    pub fn remove(&mut self, id: TicketId) {
+        for digest in self.tickets.remove(&id).unwrap_or_default() {
+            if let Some(count) = self.counts.get_mut(&digest) {
+                *count -= 1;
+                if *count == 0 {
+                    self.counts.remove(&digest);
+                }
+            }
+        }
+    }
+
+    /// 内容を参照している添付ファイルの数を返す。
+    pub fn count(&self, digest: &BlobDigest) -> usize {
+        self.counts.get(digest).copied().unwrap_or_default()
+    }
+
+    /// 参照されている内容ごとの、参照している添付ファイルの数を返す。
+    pub fn counts(&self) -> &BTreeMap<BlobDigest, usize> {
+        &self.counts
+    }
+}
+
+impl Projection for BlobReferences {
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn observes(event: &DomainEvent) -> bool {
+        event.changes_attachments()
+    }
+
+    fn project(&mut self, record: &RecordedEvent, ticket: Option<&Ticket>) {
+        match ticket {
+            Some(ticket) if record.event.changes_attachments() => self.insert(ticket),
+            Some(_) => {}
+            None => self.remove(record.ticket),
+        }
+    }
+}
+
+/// ドメインイベントを最初から適用して、射影を構築し直す。
+///
+/// # 引数
+///
+/// * `events` - 記録された順番の、すべてのチケットのドメインイベント
+/// * `projections` - 構築し直す射影
+///
+/// # 戻り値
+///
+/// `()`
+pub fn rebuild(
+    events: &[RecordedEvent],
+    projections: &mut [&mut dyn Projection],
+) -> EventResult<()> {
+    for projection in projections.iter_mut() {
+        projection.clear();
+    }
+    let mut tickets = Shard::new();
+    for record in events {
+        let ticket = apply(tickets.remove(&record.ticket), record)?;
+        for projection in projections.iter_mut() {
+            projection.project(record, ticket.as_ref());
+        }
+        if let Some(ticket) = ticket {
+            tickets.insert(record.ticket, ticket);
+        }
+    }
+
+    Ok(())
+}
+
+/// ドメインイベントエラー
+#[derive(Debug, thiserror::Error)]
+pub enum EventError {
+    #[error("ドメインイベントのスキーマバージョン{0}には対応していません。")]
+    UnsupportedSchema(u32),
+    #[error("ドメインイベントを復元できません: {0}")]
+    Invalid(#[source] serde_json::Error),
+    #[error("チケット{}のバージョン{version}のドメインイベントを、チケットに適用できません。", .ticket.0)]
+    Inconsistent { ticket: TicketId, version: u64 },
+}
+
+/// ドメインイベント結果
+pub type EventResult<T> = Result<T, EventError>;
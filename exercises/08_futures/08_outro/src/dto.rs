@@ -1,17 +1,339 @@
-use crate::models::{TicketDescription, TicketStatus, TicketTitle};
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::events::TicketEventKind;
+use crate::models::{
+    AttachmentId, BlobDigest, DescriptionFormat, Permissions, Priority, ProjectKey, ProjectName,
+    Ticket, TicketDescription, TicketId, TicketStatus, TicketTitle, Workflow,
+};
+use crate::search::SearchText;
+use crate::webhooks::{WebhookSecret, WebhookUrl};
+
+/// プロジェクトドラフト
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectDraft {
+    pub key: ProjectKey,
+    pub name: ProjectName,
+    #[serde(default)]
+    pub workflow: Workflow,
+    #[serde(default)]
+    pub permissions: Permissions,
+    #[serde(default)]
+    pub description_format: DescriptionFormat,
+}
+
+/// プロジェクトのパッチ
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectPatch {
+    /// 変更した場合、変更前のプロジェクトキーを含むチケットキーは変更後のチケットキーに解決される。
+    pub key: Option<ProjectKey>,
+    pub name: Option<ProjectName>,
+    pub workflow: Option<Workflow>,
+    pub permissions: Option<Permissions>,
+    pub description_format: Option<DescriptionFormat>,
+}
+
+/// チケットの移動先
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TicketMove {
+    /// 移動先のプロジェクトのプロジェクトキー
+    pub project: ProjectKey,
+}
+
+/// チケットに追加する添付ファイル
+///
+/// 内容を保存してから、チケットストアに追加する。
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentDraft {
+    pub id: AttachmentId,
+    pub file_name: String,
+    pub content_type: String,
+    pub size: u64,
+    pub digest: BlobDigest,
+}
+
+/// チケットの親チケット
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TicketParent {
+    /// 親チケットのチケットID（`null`の場合は親チケットを解除）
+    pub parent: Option<TicketId>,
+}
 
 /// チケットドラフト
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TicketDraft {
     pub title: TicketTitle,
     pub description: TicketDescription,
+    /// 省略した場合は`Medium`
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default)]
+    pub due_date: Option<NaiveDate>,
+}
+
+/// チケットの取得方法
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TicketView {
+    /// 指定した場合は、説明を変換した結果を含める。
+    #[serde(default)]
+    pub render: Option<DescriptionRendering>,
+}
+
+/// チケットの説明の変換先
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DescriptionRendering {
+    /// サニタイズしたHTML
+    Html,
+}
+
+/// 説明をHTMLに変換したチケット
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedTicket {
+    #[serde(flatten)]
+    pub ticket: Ticket,
+    pub description_html: String,
 }
 
 /// チケットのパッチ
-#[derive(Debug, Clone, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TicketPatch {
     pub title: Option<TicketTitle>,
     pub description: Option<TicketDescription>,
     pub status: Option<TicketStatus>,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    /// 省略した場合は変更せず、`null`を指定した場合は期限を削除する。
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_present"
+    )]
+    pub due_date: Option<Option<NaiveDate>>,
     pub version: u64,
 }
+
+/// 存在するフィールドの値を`Some`で包んでデシリアライズする。
+///
+/// `#[serde(default)]`と組み合わせると、フィールドの省略（`None`）と`null`（`Some(None)`）を区別できる。
+fn deserialize_present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// 期限による絞り込み
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DueFilter {
+    /// 完了しておらず、期限を過ぎている。
+    Overdue,
+    /// 期限が今日である。
+    Today,
+    /// 期限が今週（月曜日から日曜日）である。
+    ThisWeek,
+    /// 期限が設定されていない。
+    None,
+}
+
+/// チケットの並び替えに使用する項目
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Id,
+    Priority,
+    DueDate,
+    CreatedAt,
+    UpdatedAt,
+}
+
+/// チケットの並び順
+///
+/// 文字列では`priority`のように項目名を指定し、先頭に`-`を付けると降順になる。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TicketSort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+/// チケットの並び順エラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error(
+    r#"チケットの並び順は、`"id"`、`"priority"`、`"dueDate"`、`"createdAt"`または`"updatedAt"`のいずれかで、降順の場合は先頭に`-`を付けます。"#
+)]
+pub struct TicketSortError;
+
+/// 文字列からチケットの並び順を構築する。
+///
+/// # 引数
+///
+/// * `s` - チケットの並び順を表現する文字列
+///
+/// # 戻り値
+///
+/// チケットの並び順
+fn ticket_sort_from_str(s: &str) -> Result<TicketSort, TicketSortError> {
+    let s = s.trim();
+    let (descending, key) = match s.strip_prefix('-') {
+        Some(key) => (true, key),
+        None => (false, s),
+    };
+    let key = match key {
+        "id" => SortKey::Id,
+        "priority" => SortKey::Priority,
+        "dueDate" => SortKey::DueDate,
+        "createdAt" => SortKey::CreatedAt,
+        "updatedAt" => SortKey::UpdatedAt,
+        _ => return Err(TicketSortError),
+    };
+
+    Ok(TicketSort { key, descending })
+}
+
+impl TryFrom<String> for TicketSort {
+    type Error = TicketSortError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        ticket_sort_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for TicketSort {
+    type Error = TicketSortError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        ticket_sort_from_str(value)
+    }
+}
+
+impl From<TicketSort> for String {
+    fn from(value: TicketSort) -> Self {
+        let key = match value.key {
+            SortKey::Id => "id",
+            SortKey::Priority => "priority",
+            SortKey::DueDate => "dueDate",
+            SortKey::CreatedAt => "createdAt",
+            SortKey::UpdatedAt => "updatedAt",
+        };
+        match value.descending {
+            true => format!("-{key}"),
+            false => key.into(),
+        }
+    }
+}
+
+/// レポートの対象と集計期間
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ReportQuery {
+    /// 集計するプロジェクト（省略時は読み込みを許可されたすべてのプロジェクト）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<ProjectKey>,
+    /// 集計期間の開始日（省略時は終了日の29日前）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<NaiveDate>,
+    /// 集計期間の終了日（省略時は今日）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<NaiveDate>,
+    /// スループットを集計する期間の単位
+    #[serde(default)]
+    pub bucket: ThroughputBucket,
+    #[serde(default)]
+    pub format: ReportFormat,
+}
+
+/// スループットを集計する期間の単位
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ThroughputBucket {
+    #[default]
+    Day,
+    /// 月曜日から日曜日
+    Week,
+}
+
+/// レポートの形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+/// チケット一覧の絞り込みと並び順
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TicketQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<TicketStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due: Option<DueFilter>,
+    /// この日時より前に更新されたチケットに絞り込む。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub sort: TicketSort,
+}
+
+/// チケットの全文検索の条件
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SearchQuery {
+    pub q: SearchText,
+    /// 検索するプロジェクトのプロジェクトキー（省略時は読み込みを許可されたすべてのプロジェクト）
+    pub project: Option<ProjectKey>,
+    /// 検索結果の最大件数（省略時は20件、100件を超える場合は100件）
+    pub limit: Option<usize>,
+}
+
+/// 変更フィードの絞り込み
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct EventFilter {
+    /// 配信するプロジェクトのプロジェクトキー（省略時は読み込みを許可されたすべてのプロジェクト）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<ProjectKey>,
+}
+
+/// クエリによるチケットの絞り込み
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TicketExpression {
+    /// クエリ（例: `status:InProgress AND (title~"login" OR priority>=High)`）
+    #[serde(default)]
+    pub q: String,
+}
+
+/// 保存するクエリ
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedQueryDraft {
+    pub query: String,
+}
+
+/// Webhookドラフト
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDraft {
+    pub url: WebhookUrl,
+    /// 配信するチケットイベントの種類（省略時はすべての種類）
+    #[serde(default)]
+    pub events: BTreeSet<TicketEventKind>,
+    pub secret: WebhookSecret,
+}
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::{broadcast, mpsc, watch};
+
+use crate::models::{ProjectKey, Ticket};
+
+/// チケットイベントの種類
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub enum TicketEventKind {
+    /// チケットが登録された。
+    #[serde(rename = "ticket.created")]
+    Created,
+    /// チケットの内容または関連が更新された。
+    #[serde(rename = "ticket.updated")]
+    Updated,
+    /// チケットが他のプロジェクトから移動してきた。
+    #[serde(rename = "ticket.moved")]
+    Moved,
+}
+
+impl TicketEventKind {
+    /// イベントの種類を表現する文字列を返す。
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "ticket.created",
+            Self::Updated => "ticket.updated",
+            Self::Moved => "ticket.moved",
+        }
+    }
+}
+
+/// チケットイベント
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TicketEvent {
+    pub kind: TicketEventKind,
+    pub project: ProjectKey,
+    /// 変更後のチケット
+    pub ticket: Ticket,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// チケットイベントの送信側
+///
+/// 送信は受信側の処理を待たないため、チケットストアの操作の応答時間はイベントの受信側に依存しない。
+pub type EventSender = mpsc::UnboundedSender<TicketEvent>;
+
+/// チケットイベントの受信側
+pub type EventReceiver = mpsc::UnboundedReceiver<TicketEvent>;
+
+/// チケットイベントのチャネルを作成する。
+pub fn channel() -> (EventSender, EventReceiver) {
+    mpsc::unbounded_channel()
+}
+
+/// 購読者ごとに保持する、未受信のチケットイベントの最大数
+const FEED_CAPACITY: usize = 256;
+
+/// チケットの変更フィード
+///
+/// チケットストアが送信したチケットイベントを、`GET /events`を購読しているすべてのクライアントに配信する。
+#[derive(Debug, Clone)]
+pub struct ChangeFeed {
+    sender: broadcast::Sender<TicketEvent>,
+    /// サーバーの停止時に、購読中のクライアントへの配信を終了させるための通知
+    closed: Arc<watch::Sender<bool>>,
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(FEED_CAPACITY);
+        let (closed, _) = watch::channel(false);
+        Self {
+            sender,
+            closed: Arc::new(closed),
+        }
+    }
+}
+
+impl ChangeFeed {
+    /// 変更フィードを購読する。
+    ///
+    /// 購読を開始した後に送信されたチケットイベントだけを受信する。
+    pub fn subscribe(&self) -> FeedReceiver {
+        FeedReceiver {
+            events: self.sender.subscribe(),
+            closed: self.closed.subscribe(),
+        }
+    }
+
+    /// 変更フィードを閉じて、すべての購読者の受信を終了させる。
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+    }
+
+    /// チケットイベントを変更フィードに配信してから、指定した送信先に転送するタスクを開始する。
+    ///
+    /// # 引数
+    ///
+    /// * `downstream` - 変更フィードに配信した後のチケットイベントの送信先、転送しない場合は`None`
+    ///
+    /// # 戻り値
+    ///
+    /// チケットストアに設定するチケットイベントの送信側
+    pub fn start(&self, downstream: Option<EventSender>) -> EventSender {
+        let (sender, mut receiver) = channel();
+        let feed = self.sender.clone();
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                // 購読者がいない場合の送信エラーは無視する。
+                let _ = feed.send(event.clone());
+                if let Some(downstream) = &downstream {
+                    let _ = downstream.send(event);
+                }
+            }
+        });
+
+        sender
+    }
+}
+
+/// 変更フィードの受信側
+#[derive(Debug)]
+pub struct FeedReceiver {
+    events: broadcast::Receiver<TicketEvent>,
+    closed: watch::Receiver<bool>,
+}
+
+impl FeedReceiver {
+    /// 次のチケットイベントを受信する。
+    ///
+    /// # 戻り値
+    ///
+    /// チケットイベント、受信が遅れてチケットイベントが欠落した場合は欠落した数を含む`Err`、
+    /// 変更フィードが閉じられた場合は`None`
+    pub async fn recv(&mut self) -> Option<Result<TicketEvent, u64>> {
+        tokio::select! {
+            biased;
+            _ = self.closed.wait_for(|closed| *closed) => None,
+            received = self.events.recv() => match received {
+                Ok(event) => Some(Ok(event)),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => Some(Err(skipped)),
+                Err(broadcast::error::RecvError::Closed) => None,
+            },
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::models::{Ticket, TicketId, TicketKey, TicketStatus, TicketTitle};
+
+/// チケットの関連の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+pub enum LinkKind {
+    /// `to`は`from`のサブタスクである。
+    Subtask,
+    /// `from`は`to`をブロックしている。
+    Blocks,
+}
+
+/// チケットの関連
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphEdge {
+    pub from: TicketId,
+    pub to: TicketId,
+    pub kind: LinkKind,
+}
+
+/// 依存関係グラフのチケット
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphNode {
+    pub id: TicketId,
+    pub key: TicketKey,
+    pub title: TicketTitle,
+    pub status: TicketStatus,
+}
+
+/// チケットの依存関係グラフ
+///
+/// 起点のチケットから、サブタスクとブロックの関連を向きを問わずにたどれるチケットを含む。
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyGraph {
+    pub root: TicketId,
+    /// チケットID順のチケット
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// チケットの関連をすべて返す。
+fn edges(tickets: &BTreeMap<TicketId, Ticket>) -> impl Iterator<Item = GraphEdge> + '_ {
+    tickets.values().flat_map(|ticket| {
+        let subtask = ticket.parent.map(|parent| GraphEdge {
+            from: parent,
+            to: ticket.id,
+            kind: LinkKind::Subtask,
+        });
+        let blocks = ticket.blocked_by.iter().map(|&blocker| GraphEdge {
+            from: blocker,
+            to: ticket.id,
+            kind: LinkKind::Blocks,
+        });
+        subtask.into_iter().chain(blocks)
+    })
+}
+
+/// チケットごとに、そのチケットより先に完了しなければならないチケット（サブタスクとブロッカー）を返す。
+fn prerequisites(tickets: &BTreeMap<TicketId, Ticket>) -> BTreeMap<TicketId, Vec<TicketId>> {
+    let mut prerequisites: BTreeMap<TicketId, Vec<TicketId>> = BTreeMap::new();
+    for edge in edges(tickets) {
+        let (before, after) = match edge.kind {
+            LinkKind::Subtask => (edge.to, edge.from),
+            LinkKind::Blocks => (edge.from, edge.to),
+        };
+        prerequisites.entry(after).or_default().push(before);
+    }
+    prerequisites
+}
+
+/// `before`を`after`より先に完了しなければならないとする関連を追加したときに、依存関係が循環するか確認する。
+///
+/// # 引数
+///
+/// * `tickets` - チケット
+/// * `before` - 先に完了しなければならないチケットのチケットID
+/// * `after` - 後に完了するチケットのチケットID
+///
+/// # 戻り値
+///
+/// 依存関係が循環する場合は`true`
+pub fn would_cycle(
+    tickets: &BTreeMap<TicketId, Ticket>,
+    before: TicketId,
+    after: TicketId,
+) -> bool {
+    let prerequisites = prerequisites(tickets);
+    let mut visited = BTreeSet::new();
+    let mut stack = vec![before];
+    while let Some(id) = stack.pop() {
+        if id == after {
+            return true;
+        }
+        if visited.insert(id) {
+            stack.extend(prerequisites.get(&id).into_iter().flatten().copied());
+        }
+    }
+    false
+}
+
+/// チケットに関連するチケットがあるか確認する。
+pub fn is_linked(tickets: &BTreeMap<TicketId, Ticket>, id: TicketId) -> bool {
+    edges(tickets).any(|edge| edge.from == id || edge.to == id)
+}
+
+/// チケットの依存関係グラフを構築する。
+///
+/// # 引数
+///
+/// * `tickets` - チケット
+/// * `root` - 起点のチケットのチケットID
+///
+/// # 戻り値
+///
+/// 依存関係グラフ
+pub fn dependency_graph(tickets: &BTreeMap<TicketId, Ticket>, root: TicketId) -> DependencyGraph {
+    let all: Vec<_> = edges(tickets).collect();
+    let mut neighbors: BTreeMap<TicketId, Vec<TicketId>> = BTreeMap::new();
+    for edge in &all {
+        neighbors.entry(edge.from).or_default().push(edge.to);
+        neighbors.entry(edge.to).or_default().push(edge.from);
+    }
+
+    let mut reached = BTreeSet::from([root]);
+    let mut queue = VecDeque::from([root]);
+    while let Some(id) = queue.pop_front() {
+        for &next in neighbors.get(&id).into_iter().flatten() {
+            if reached.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let nodes = reached
+        .iter()
+        .filter_map(|id| tickets.get(id))
+        .map(|ticket| GraphNode {
+            id: ticket.id,
+            key: ticket.key.clone(),
+            title: ticket.title.clone(),
+            status: ticket.status,
+        })
+        .collect();
+    let mut edges: Vec<_> = all
+        .into_iter()
+        .filter(|edge| reached.contains(&edge.from))
+        .collect();
+    edges.sort();
+
+    DependencyGraph { root, nodes, edges }
+}
+
+/// 完了していないチケットを、先に完了しなければならないチケットが前に来るように並べる。
+///
+/// 同時に着手できるチケットは、チケットID順に並べる。
+///
+/// # 引数
+///
+/// * `tickets` - チケット
+///
+/// # 戻り値
+///
+/// 完了していないチケットのチケットID
+pub fn open_work_order(tickets: &BTreeMap<TicketId, Ticket>) -> Vec<TicketId> {
+    let open = |id: &TicketId| {
+        tickets
+            .get(id)
+            .is_some_and(|t| t.status != TicketStatus::Done)
+    };
+    let mut remaining: BTreeMap<TicketId, usize> = tickets
+        .keys()
+        .filter(|id| open(id))
+        .map(|&id| (id, 0))
+        .collect();
+    let mut dependents: BTreeMap<TicketId, Vec<TicketId>> = BTreeMap::new();
+    for (after, befores) in prerequisites(tickets) {
+        if !open(&after) {
+            continue;
+        }
+        for before in befores.into_iter().filter(open) {
+            *remaining.entry(after).or_default() += 1;
+            dependents.entry(before).or_default().push(after);
+        }
+    }
+
+    let mut ready: BTreeSet<_> = remaining
+        .iter()
+        .filter(|(_, &count)| count == 0)
+        .map(|(&id, _)| id)
+        .collect();
+    let mut order = Vec::with_capacity(remaining.len());
+    while let Some(id) = ready.pop_first() {
+        order.push(id);
+        for after in dependents.remove(&id).into_iter().flatten() {
+            let count = remaining.get_mut(&after).expect("open ticket");
+            *count -= 1;
+            if *count == 0 {
+                ready.insert(after);
+            }
+        }
+    }
+
+    order
+}
@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+
+use crate::actor::StoreHandle;
+use crate::config::{Config, HealthConfig, StorageBackend};
+
+/// ストレージの復元状態
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageState {
+    /// 永続化されたチケットを復元している。
+    Replaying,
+    /// 復元が完了して、リクエストを処理できる。
+    Ready,
+    /// 復元に失敗した。
+    Failed(String),
+}
+
+/// サーバーの稼働状態
+#[derive(Debug)]
+pub struct Health {
+    started_at: Instant,
+    storage: Mutex<StorageState>,
+    data_dir: Option<PathBuf>,
+    config: HealthConfig,
+}
+
+impl Health {
+    /// 設定に従って稼働状態を構築する。
+    ///
+    /// `file`ストレージバックエンドの場合は、ストレージの復元が完了するまで準備中として扱う。
+    pub fn new(config: &Config) -> Self {
+        let (storage, data_dir) = match config.storage.backend {
+            StorageBackend::Memory => (StorageState::Ready, None),
+            StorageBackend::File => (StorageState::Replaying, config.storage.data_dir.clone()),
+        };
+
+        Self {
+            started_at: Instant::now(),
+            storage: Mutex::new(storage),
+            data_dir,
+            config: config.health.clone(),
+        }
+    }
+
+    /// ストレージの復元状態を返す。
+    pub fn storage_state(&self) -> StorageState {
+        self.storage
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// ストレージの復元状態を設定する。
+    pub fn set_storage_state(&self, state: StorageState) {
+        *self
+            .storage
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = state;
+    }
+
+    /// ストレージの復元が完了しているか確認する。
+    pub fn is_storage_ready(&self) -> bool {
+        self.storage_state() == StorageState::Ready
+    }
+
+    /// ライブネスを確認する。
+    ///
+    /// プロセスがリクエストに応答できれば、常に正常と判定する。
+    pub fn liveness(&self) -> HealthReport {
+        let mut checks = BTreeMap::new();
+        checks.insert("process", Check::run(|| Ok(None)));
+
+        HealthReport::new(checks, self.started_at)
+    }
+
+    /// レディネスを確認する。
+    ///
+    /// # 引数
+    ///
+    /// * `store` - チケットストア
+    pub async fn readiness(&self, store: &StoreHandle) -> HealthReport {
+        let mut checks = BTreeMap::new();
+        checks.insert("storage", Check::run(|| self.check_storage()));
+        checks.insert("storeLock", self.check_store(store).await);
+        checks.insert("disk", Check::run(|| self.check_disk()));
+
+        HealthReport::new(checks, self.started_at)
+    }
+
+    /// ストレージの復元が完了しているか確認する。
+    fn check_storage(&self) -> CheckOutcome {
+        match self.storage_state() {
+            StorageState::Ready => Ok(None),
+            StorageState::Replaying => Err("永続化されたチケットを復元しています。".into()),
+            StorageState::Failed(e) => Err(format!("チケットの復元に失敗しました: {e}")),
+        }
+    }
+
+    /// チケットストアのアクターが時間内に応答するか確認する。
+    async fn check_store(&self, store: &StoreHandle) -> Check {
+        let started_at = Instant::now();
+        let timeout = self.config.lock_timeout();
+        let outcome = match tokio::time::timeout(timeout, store.ping()).await {
+            Ok(Ok(())) => Ok(None),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!(
+                "{}ミリ秒以内にチケットストアが応答しませんでした。",
+                timeout.as_millis()
+            )),
+        };
+
+        Check::from_outcome(outcome, started_at)
+    }
+
+    /// データディレクトリの空き容量が十分か確認する。
+    fn check_disk(&self) -> CheckOutcome {
+        let Some(data_dir) = &self.data_dir else {
+            return Ok(Some("データディレクトリを使用していません。".into()));
+        };
+        let available =
+            available_space(data_dir).map_err(|e| format!("空き容量を取得できません: {e}"))?;
+        let Some(available) = available else {
+            return Ok(Some(
+                "このプラットフォームでは空き容量を確認できません。".into(),
+            ));
+        };
+        if available < self.config.min_free_disk_bytes {
+            return Err(format!(
+                "空き容量が{available}バイトで、必要な{}バイトに足りません。",
+                self.config.min_free_disk_bytes
+            ));
+        }
+
+        Ok(Some(format!("空き容量は{available}バイトです。")))
+    }
+}
+
+/// パスを含むファイルシステムで利用できる空き容量を返す。
+#[cfg(unix)]
+fn available_space(path: &Path) -> std::io::Result<Option<u64>> {
+    let stat = rustix::fs::statvfs(path)?;
+
+    Ok(Some(stat.f_bavail.saturating_mul(stat.f_frsize)))
+}
+
+/// パスを含むファイルシステムで利用できる空き容量を返す。
+#[cfg(not(unix))]
+fn available_space(_path: &Path) -> std::io::Result<Option<u64>> {
+    Ok(None)
+}
+
+/// チェックの結果
+///
+/// 成功した場合は補足情報、失敗した場合は失敗の理由を持つ。
+type CheckOutcome = Result<Option<String>, String>;
+
+/// チェックの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Fail,
+}
+
+/// 個々のチェックの結果
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Check {
+    pub status: CheckStatus,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Check {
+    /// チェックを実行して、その所要時間とともに結果を返す。
+    fn run(f: impl FnOnce() -> CheckOutcome) -> Self {
+        let started_at = Instant::now();
+        Self::from_outcome(f(), started_at)
+    }
+
+    fn from_outcome(outcome: CheckOutcome, started_at: Instant) -> Self {
+        let latency_ms = started_at.elapsed().as_secs_f64() * 1000.0;
+        match outcome {
+            Ok(detail) => Self {
+                status: CheckStatus::Ok,
+                latency_ms,
+                detail,
+            },
+            Err(detail) => Self {
+                status: CheckStatus::Fail,
+                latency_ms,
+                detail: Some(detail),
+            },
+        }
+    }
+}
+
+/// ビルド情報
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub profile: &'static str,
+    /// ビルド時に`TICKET_STORE_GIT_COMMIT`環境変数で指定されたコミット
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_commit: Option<&'static str>,
+}
+
+impl BuildInfo {
+    /// 実行中のバイナリのビルド情報を返す。
+    pub fn current() -> Self {
+        Self {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            profile: if cfg!(debug_assertions) {
+                "debug"
+            } else {
+                "release"
+            },
+            git_commit: option_env!("TICKET_STORE_GIT_COMMIT"),
+        }
+    }
+}
+
+/// ヘルスチェックの結果
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    pub status: CheckStatus,
+    pub checks: BTreeMap<&'static str, Check>,
+    pub build: BuildInfo,
+    pub uptime_secs: u64,
+}
+
+impl HealthReport {
+    fn new(checks: BTreeMap<&'static str, Check>, started_at: Instant) -> Self {
+        let status = if checks.values().all(|c| c.status == CheckStatus::Ok) {
+            CheckStatus::Ok
+        } else {
+            CheckStatus::Fail
+        };
+
+        Self {
+            status,
+            checks,
+            build: BuildInfo::current(),
+            uptime_secs: started_at.elapsed().as_secs(),
+        }
+    }
+}
+
+impl IntoResponse for HealthReport {
+    fn into_response(self) -> Response {
+        let status_code = match self.status {
+            CheckStatus::Ok => StatusCode::OK,
+            CheckStatus::Fail => StatusCode::SERVICE_UNAVAILABLE,
+        };
+
+        (status_code, Json(self)).into_response()
+    }
+}
+
+/// ライブネスプローブ
+pub async fn healthz(State(health): State<Arc<Health>>) -> HealthReport {
+    health.liveness()
+}
+
+/// レディネスプローブ
+pub async fn readyz(
+    State(health): State<Arc<Health>>,
+    State(store): State<StoreHandle>,
+) -> HealthReport {
+    health.readiness(&store).await
+}
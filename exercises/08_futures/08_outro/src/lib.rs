@@ -19,7 +19,34 @@
 // このシステムを構築するために、任意で必要な依存関係を見つけるために、Rustのパッケージレジストリである
 // crate.ioを使用してください。
 
+pub mod actor;
+pub mod backup;
+pub mod blobs;
+pub mod clock;
+pub mod cluster;
+pub mod cluster_server;
+pub mod config;
+pub mod crypto;
+pub mod domain;
 pub mod dto;
+pub mod events;
+pub mod graph;
+pub mod health;
+pub mod markdown;
+pub mod middleware;
+pub mod migrate;
 pub mod models;
+pub mod persistence;
+pub mod query;
+pub mod raft;
+pub mod registry;
+pub mod replication;
+pub mod reports;
+pub mod saved_queries;
+pub mod search;
 pub mod server;
+pub mod shards;
 pub mod store;
+pub mod sync;
+pub mod telemetry;
+pub mod webhooks;
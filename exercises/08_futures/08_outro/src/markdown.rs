@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+
+use pulldown_cmark::{CowStr, Event, LinkType, Parser, Tag, TagEnd, TextMergeStream};
+
+use crate::models::{DescriptionFormat, ProjectKey, TicketDescription};
+
+/// 説明のリンクに許可するURLスキーム
+///
+/// 相対URLは、スキームにかかわらず許可する。
+const URL_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+/// 説明のリンクに付与する`rel`属性
+const LINK_REL: &str = "noopener noreferrer nofollow";
+
+/// チケットの説明を、サニタイズしたHTMLに変換する。
+///
+/// `Markdown`の説明はCommonMarkとして解釈し、`Plain`の説明は空行で段落を分けて、改行を`<br>`にする。
+/// どちらの書式でも、本文の`#123`は同じプロジェクトのチケットへのリンクにする。
+/// 変換したHTMLからは、スクリプトや`javascript:`のような安全でないURLを取り除く。
+///
+/// # 引数
+///
+/// * `description` - チケットの説明
+/// * `format` - 説明の書式
+/// * `project` - チケットのプロジェクトキー
+///
+/// # 戻り値
+///
+/// サニタイズしたHTML
+pub fn render_html(
+    description: &TicketDescription,
+    format: DescriptionFormat,
+    project: &ProjectKey,
+) -> String {
+    let events: Vec<_> = match format {
+        DescriptionFormat::Plain => plain_events(&description.0),
+        DescriptionFormat::Markdown => TextMergeStream::new(Parser::new(&description.0)).collect(),
+    };
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(
+        &mut html,
+        link_ticket_references(events, project).into_iter(),
+    );
+
+    ammonia::Builder::default()
+        .url_schemes(HashSet::from(URL_SCHEMES))
+        .link_rel(Some(LINK_REL))
+        .clean(&html)
+        .to_string()
+}
+
+/// プレーンテキストの説明を、段落と改行のイベントに変換する。
+fn plain_events(text: &str) -> Vec<Event<'_>> {
+    let mut events = vec![];
+    let mut in_paragraph = false;
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() {
+            if in_paragraph {
+                events.push(Event::End(TagEnd::Paragraph));
+                in_paragraph = false;
+            }
+            continue;
+        }
+        if in_paragraph {
+            events.push(Event::HardBreak);
+        } else {
+            events.push(Event::Start(Tag::Paragraph));
+            in_paragraph = true;
+        }
+        events.push(Event::Text(line.into()));
+    }
+    if in_paragraph {
+        events.push(Event::End(TagEnd::Paragraph));
+    }
+
+    events
+}
+
+/// テキストの`#123`を、チケットへのリンクに置き換える。
+///
+/// リンク、画像の代替テキストおよびコードブロックの中のテキストは置き換えない。
+fn link_ticket_references<'a>(events: Vec<Event<'a>>, project: &ProjectKey) -> Vec<Event<'a>> {
+    let mut linked = Vec::with_capacity(events.len());
+    let mut literal_depth = 0usize;
+    for event in events {
+        match &event {
+            Event::Start(Tag::Link { .. } | Tag::Image { .. } | Tag::CodeBlock(_)) => {
+                literal_depth += 1;
+            }
+            Event::End(TagEnd::Link | TagEnd::Image | TagEnd::CodeBlock) => {
+                literal_depth = literal_depth.saturating_sub(1);
+            }
+            Event::Text(text) if literal_depth == 0 => {
+                push_linked_text(&mut linked, text, project);
+                continue;
+            }
+            _ => {}
+        }
+        linked.push(event);
+    }
+
+    linked
+}
+
+/// テキストを、`#123`をリンクにしたイベントとして追加する。
+fn push_linked_text<'a>(events: &mut Vec<Event<'a>>, text: &str, project: &ProjectKey) {
+    let mut rest = 0;
+    for (start, end, number) in ticket_references(text) {
+        if rest < start {
+            events.push(Event::Text(CowStr::from(text[rest..start].to_string())));
+        }
+        events.push(Event::Start(Tag::Link {
+            link_type: LinkType::Inline,
+            dest_url: format!("/projects/{project}/tickets/{number}").into(),
+            title: "".into(),
+            id: "".into(),
+        }));
+        events.push(Event::Text(CowStr::from(text[start..end].to_string())));
+        events.push(Event::End(TagEnd::Link));
+        rest = end;
+    }
+    if rest < text.len() {
+        events.push(Event::Text(CowStr::from(text[rest..].to_string())));
+    }
+}
+
+/// テキストから`#123`形式のチケットの参照を探す。
+///
+/// `issue#123`や`#123a`のように、ASCIIの英数字に続く、またはASCIIの英数字が続く参照は無視する。
+///
+/// # 戻り値
+///
+/// 参照の開始位置、終了位置およびチケット番号
+fn ticket_references(text: &str) -> Vec<(usize, usize, u64)> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut references = vec![];
+    for (start, _) in text.match_indices('#') {
+        if text[..start].chars().next_back().is_some_and(is_word) {
+            continue;
+        }
+        let digits = text[start + 1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(text.len(), |n| start + 1 + n);
+        if text[digits..].chars().next().is_some_and(is_word) {
+            continue;
+        }
+        match text[start + 1..digits].parse::<u64>() {
+            Ok(number) if number > 0 => references.push((start, digits, number)),
+            _ => {}
+        }
+    }
+
+    references
+}
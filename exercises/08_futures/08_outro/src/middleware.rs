@@ -0,0 +1,285 @@
+use std::any::Any;
+use std::convert::Infallible;
+use std::panic::AssertUnwindSafe;
+use std::time::{Duration, Instant};
+
+use axum::async_trait;
+use axum::extract::{FromRequestParts, Request, State};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderName, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use futures_util::FutureExt;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::Instrument;
+
+/// リクエストIDを伝搬するHTTPヘッダ
+pub static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// リクエストした利用者を示すHTTPヘッダ
+pub static X_USER: HeaderName = HeaderName::from_static("x-user");
+
+/// 受け付けるリクエストIDの最大文字数
+const REQUEST_ID_MAX_CHARS: usize = 128;
+
+/// リクエストID
+///
+/// リクエストのエクステンションに格納されるため、ハンドラは`Extension<RequestId>`で取得できる。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// リクエストヘッダからリクエストIDを取得して、存在しない場合は新しく生成する。
+    ///
+    /// 空、長すぎる、または表示可能なASCII文字以外を含むリクエストIDは、ログを汚さないように無視する。
+    ///
+    /// # 引数
+    ///
+    /// * `request` - リクエスト
+    ///
+    /// # 戻り値
+    ///
+    /// リクエストID
+    fn from_request(request: &Request) -> Self {
+        request
+            .headers()
+            .get(&X_REQUEST_ID)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|id| {
+                !id.is_empty()
+                    && id.len() <= REQUEST_ID_MAX_CHARS
+                    && id.chars().all(|c| c.is_ascii_graphic())
+            })
+            .map(|id| Self(id.into()))
+            .unwrap_or_else(|| Self(uuid::Uuid::new_v4().to_string()))
+    }
+}
+
+/// リクエストした利用者
+///
+/// `X-User`ヘッダで指定された利用者で、ヘッダがない場合は`None`になる。
+/// 利用者の認証は、このサーバーの前段にあるリバースプロキシなどで行うことを想定している。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CurrentUser(pub Option<String>);
+
+impl CurrentUser {
+    /// 利用者名を返す。
+    pub fn name(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CurrentUser
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user = parts
+            .headers
+            .get(&X_USER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|user| !user.is_empty())
+            .map(String::from);
+
+        Ok(Self(user))
+    }
+}
+
+/// 管理APIのトークン
+///
+/// トークンそのものは保持せず、SHA-256のダイジェストだけを保持して比較する。
+/// トークンを表示しないように、ダイジェストの先頭だけを表示する。
+#[derive(Clone, PartialEq, Eq)]
+pub struct AdminToken([u8; 32]);
+
+/// 管理APIのトークンエラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("管理APIのトークンは、空白を含まない1文字以上の表示可能なASCII文字列です。")]
+pub struct AdminTokenError;
+
+impl AdminToken {
+    /// 提示されたトークンが、このトークンと一致するか確認する。
+    fn matches(&self, presented: &str) -> bool {
+        Sha256::digest(presented.as_bytes())[..] == self.0
+    }
+}
+
+/// 文字列から管理APIのトークンを構築する。
+///
+/// トークンファイルの末尾の改行などを許容するため、前後の空白は無視する。
+///
+/// # 引数
+///
+/// * `s` - トークン
+///
+/// # 戻り値
+///
+/// 管理APIのトークン
+fn admin_token_from_str(s: &str) -> Result<AdminToken, AdminTokenError> {
+    let token = s.trim();
+    if token.is_empty() || !token.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(AdminTokenError);
+    }
+
+    Ok(AdminToken(Sha256::digest(token.as_bytes()).into()))
+}
+
+impl TryFrom<String> for AdminToken {
+    type Error = AdminTokenError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        admin_token_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for AdminToken {
+    type Error = AdminTokenError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        admin_token_from_str(value)
+    }
+}
+
+impl std::fmt::Debug for AdminToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AdminToken")
+            .field(&hex::encode(&self.0[..4]))
+            .finish()
+    }
+}
+
+impl serde::Serialize for AdminToken {
+    /// 有効な設定を表示するときにトークンを漏らさないように、ダイジェストの先頭だけを書き出す。
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("sha256:{}", hex::encode(&self.0[..4])))
+    }
+}
+
+/// 管理APIのリクエストを、`Authorization: Bearer`ヘッダのトークンで認証するミドルウェア
+///
+/// トークンを設定していない場合は、管理APIを無効にして`403 Forbidden`を返す。
+/// トークンがないか一致しない場合は、`401 Unauthorized`を返す。
+pub async fn require_admin(
+    State(token): State<Option<AdminToken>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(token) = token else {
+        let body = Json(json!({
+            "error": "管理APIのトークンが設定されていないため、管理APIは無効です。",
+            "code": "adminDisabled",
+        }));
+        return (StatusCode::FORBIDDEN, body).into_response();
+    };
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !presented.is_some_and(|presented| token.matches(presented.trim())) {
+        tracing::warn!("管理APIのリクエストを認証できません。");
+        let body = Json(json!({
+            "error": "管理APIのトークンが誤っています。",
+            "code": "unauthorized",
+        }));
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            body,
+        )
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+/// リクエストごとにリクエストIDを付与して、トレーシングのスパンでリクエストの処理を包むミドルウェア
+///
+/// ハンドラがパニックした場合は、パニックを捕捉してリクエストIDを含む`500 Internal Server Error`を返す。
+/// レスポンスには常に`X-Request-Id`ヘッダを付与する。
+pub async fn request_context(mut request: Request, next: Next) -> Response {
+    let request_id = RequestId::from_request(&request);
+    request.extensions_mut().insert(request_id.clone());
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id.0,
+        method = %request.method(),
+        uri = %request.uri(),
+    );
+
+    async move {
+        let started_at = Instant::now();
+        let mut response = match AssertUnwindSafe(next.run(request)).catch_unwind().await {
+            Ok(response) => response,
+            Err(panic) => {
+                tracing::error!(
+                    panic = panic_message(&panic),
+                    "ハンドラがパニックしました。"
+                );
+                internal_server_error(&request_id)
+            }
+        };
+        tracing::info!(
+            status = response.status().as_u16(),
+            latency_ms = started_at.elapsed().as_millis() as u64,
+            "リクエストを処理しました。"
+        );
+        if let Ok(value) = HeaderValue::from_str(&request_id.0) {
+            response.headers_mut().insert(X_REQUEST_ID.clone(), value);
+        }
+
+        response
+    }
+    .instrument(span)
+    .await
+}
+
+/// パニックのペイロードからメッセージを取り出す。
+fn panic_message(panic: &Box<dyn Any + Send>) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "不明なパニック"
+    }
+}
+
+/// リクエストIDを含む`500 Internal Server Error`のレスポンスを構築する。
+fn internal_server_error(request_id: &RequestId) -> Response {
+    let body = Json(json!({
+        "error": "サーバー内部でエラーが発生しました。",
+        "requestId": request_id.0,
+    }));
+
+    (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
+}
+
+/// 指定した時間内に処理が完了しないリクエストに、`503 Service Unavailable`を返すミドルウェア
+///
+/// `408 Request Timeout`はクライアントがリクエストを送信し終えなかったことを表し、
+/// クライアントやプロキシが間隔を空けずに再送することがあるため、サーバー側の処理の遅れには使わない。
+pub async fn request_timeout(
+    State(timeout): State<Duration>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match tokio::time::timeout(timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            tracing::warn!(
+                timeout_secs = timeout.as_secs(),
+                "リクエストがタイムアウトしました。"
+            );
+            let body = Json(json!({"error": "リクエストがタイムアウトしました。"}));
+            (StatusCode::SERVICE_UNAVAILABLE, body).into_response()
+        }
+    }
+}
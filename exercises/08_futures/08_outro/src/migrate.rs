@@ -0,0 +1,755 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value};
+
+use crate::models::{Project, DEFAULT_PROJECT_KEY};
+use crate::persistence::{
+    write_json, PersistenceError, PersistenceResult, EVENT_LOG_FILE_NAME, JOURNAL_FILE_NAME,
+    SNAPSHOT_FILE_NAME,
+};
+use crate::registry::PROJECTS_DIR_NAME;
+use crate::store::TicketStore;
+
+/// 現在のデータディレクトリの形式バージョン
+///
+/// * `1` - データディレクトリ直下のスナップショットとジャーナルに、チケットだけを記録する。
+/// * `2` - チケットに優先度、期限、作成日時、更新日時を加える。
+/// * `3` - スナップショットとジャーナルにプロジェクトを記録し、チケットにチケットキーを加える。
+/// * `4` - `projects`ディレクトリに、プロジェクトごとのスナップショットとジャーナルを記録する。
+/// * `5` - プロジェクトごとのイベントログに、チケットのドメインイベントを記録する。
+/// * `6` - データディレクトリに形式バージョンを記録する。
+pub const DATA_FORMAT_VERSION: u32 = 6;
+
+/// 形式バージョンごとの、1つ新しい形式バージョンへの変換
+///
+/// `MIGRATIONS[n - 1]`は、形式バージョン`n`のデータディレクトリを形式バージョン`n + 1`に変換する。
+/// データディレクトリの形式を変更する場合は、[`DATA_FORMAT_VERSION`]を上げて、ここに変換を追加する。
+const MIGRATIONS: [Migration; DATA_FORMAT_VERSION as usize - 1] = [
+    add_ticket_fields,
+    add_projects,
+    split_projects,
+    import_events,
+    record_format,
+];
+
+/// 一時ディレクトリに複製したデータディレクトリを、移行する日時を使って1つ新しい形式バージョンに変換する関数
+type Migration = fn(&Path, DateTime<Utc>) -> PersistenceResult<()>;
+
+/// データディレクトリの形式バージョンを記録するファイル名
+const FORMAT_FILE_NAME: &str = "format.json";
+
+/// 移行したデータディレクトリを書き出す一時ディレクトリ名
+const MIGRATING_DIR_NAME: &str = "migrating";
+
+/// 移行前の項目を退避するディレクトリ名の接頭辞（移行前の形式バージョンを続ける）
+const BACKUP_DIR_PREFIX: &str = "pre-migration-v";
+
+/// 形式バージョンで形式を管理する、データディレクトリの項目
+///
+/// Webhookの購読、保存したクエリおよび添付ファイルの内容は、それぞれの形式を変えていないため含まない。
+const VERSIONED_ENTRIES: [&str; 3] = [SNAPSHOT_FILE_NAME, JOURNAL_FILE_NAME, PROJECTS_DIR_NAME];
+
+/// 形式バージョンを記録するファイルの内容
+#[derive(serde::Serialize, serde::Deserialize)]
+struct FormatHeader {
+    format: u32,
+}
+
+/// 形式バージョン3のプロジェクト
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProjectV3 {
+    key: String,
+    name: String,
+    #[serde(default)]
+    previous_keys: Vec<String>,
+    next_number: u64,
+}
+
+/// 移行の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationReport {
+    /// データディレクトリが空のため、現在の形式バージョンとして初期化した。
+    Initialized,
+    /// データディレクトリは既に現在の形式バージョンである。
+    UpToDate,
+    /// データディレクトリを現在の形式バージョンに移行した。
+    Migrated {
+        /// 移行前の形式バージョン
+        from: u32,
+        /// 移行したプロジェクトの数
+        projects: usize,
+        /// 移行したチケットの数
+        tickets: usize,
+        /// 移行前の項目を退避したディレクトリ、ドライランの場合は`None`
+        backup_dir: Option<PathBuf>,
+    },
+}
+
+/// データディレクトリの形式バージョンを判定する。
+///
+/// 形式バージョンを記録していない以前のデータディレクトリは、ファイルの構成と内容から判定する。
+///
+/// # 引数
+///
+/// * `data_dir` - データディレクトリ
+///
+/// # 戻り値
+///
+/// 形式バージョン、空のデータディレクトリの場合は`None`
+pub fn detect(data_dir: &Path) -> PersistenceResult<Option<u32>> {
+    if data_dir.join(MIGRATING_DIR_NAME).exists() {
+        return Err(PersistenceError::MigrationInterrupted);
+    }
+    if let Some(header) = read_json::<FormatHeader>(&data_dir.join(FORMAT_FILE_NAME))? {
+        if header.format == 0 || header.format > DATA_FORMAT_VERSION {
+            return Err(PersistenceError::UnsupportedFormat(header.format));
+        }
+        return Ok(Some(header.format));
+    }
+
+    let project_dirs = project_dirs(data_dir)?;
+    if !project_dirs.is_empty() {
+        let events = project_dirs
+            .iter()
+            .any(|dir| dir.join(EVENT_LOG_FILE_NAME).exists());
+        return Ok(Some(if events { 5 } else { 4 }));
+    }
+    let snapshot_path = data_dir.join(SNAPSHOT_FILE_NAME);
+    let journal_path = data_dir.join(JOURNAL_FILE_NAME);
+    if !snapshot_path.exists() && !journal_path.exists() {
+        return Ok(None);
+    }
+    let snapshot = read_json::<Value>(&snapshot_path)?.unwrap_or_default();
+    let journal = read_lines(&journal_path)?;
+    let records: Vec<_> = array(&snapshot, "tickets").chain(&journal).collect();
+    // 形式バージョン3のジャーナルのチケットとプロジェクトは、いずれもキーを持つ。
+    let version =
+        if snapshot.get("projects").is_some() || records.iter().any(|r| r.get("key").is_some()) {
+            3
+        } else if records.iter().any(|r| r.get("priority").is_some()) {
+            2
+        } else {
+            1
+        };
+
+    Ok(Some(version))
+}
+
+/// データディレクトリが現在の形式バージョンであることを確かめる。
+///
+/// 空のデータディレクトリには、現在の形式バージョンを記録する。
+///
+/// # 引数
+///
+/// * `data_dir` - データディレクトリ
+///
+/// # 戻り値
+///
+/// `()`
+pub fn ensure_current(data_dir: &Path) -> PersistenceResult<()> {
+    match detect(data_dir)? {
+        None => {
+            fs::create_dir_all(data_dir)?;
+            write_header(data_dir)
+        }
+        Some(DATA_FORMAT_VERSION) => Ok(()),
+        Some(found) => Err(PersistenceError::OutdatedFormat {
+            found,
+            current: DATA_FORMAT_VERSION,
+        }),
+    }
+}
+
+/// データディレクトリを現在の形式バージョンに移行する。
+///
+/// 移行したデータディレクトリを`migrating`ディレクトリに書き出し、すべてのプロジェクトを開けることを確かめてから、
+/// 移行前の項目を`pre-migration-v<移行前の形式バージョン>`ディレクトリに退避して入れ替える。
+/// 形式バージョンを最後に記録するため、入れ替えの途中で停止した場合は、次の移行で移行前の状態に戻してやり直す。
+/// サーバーを停止してから実行する。
+///
+/// # 引数
+///
+/// * `data_dir` - データディレクトリ
+/// * `now` - 移行する日時（記録されていないチケットの作成日時と更新日時に使用する）
+/// * `dry_run` - `true`の場合は、移行できることを確かめるだけで、データディレクトリを変更しない
+///
+/// # 戻り値
+///
+/// 移行の結果
+pub fn migrate(
+    data_dir: &Path,
+    now: DateTime<Utc>,
+    dry_run: bool,
+) -> PersistenceResult<MigrationReport> {
+    if !dry_run {
+        recover(data_dir)?;
+    }
+    let from = match detect(data_dir)? {
+        None => {
+            if !dry_run {
+                fs::create_dir_all(data_dir)?;
+                write_header(data_dir)?;
+            }
+            return Ok(MigrationReport::Initialized);
+        }
+        Some(DATA_FORMAT_VERSION) => return Ok(MigrationReport::UpToDate),
+        Some(from) => from,
+    };
+    let backup_name = format!("{BACKUP_DIR_PREFIX}{from}");
+    if data_dir.join(&backup_name).exists() {
+        return Err(PersistenceError::Unmigratable(format!(
+            "移行前の項目を退避するディレクトリ`{backup_name}`が既に存在します。"
+        )));
+    }
+
+    let staging = data_dir.join(MIGRATING_DIR_NAME);
+    let (projects, tickets) = match stage(data_dir, &staging, from, now) {
+        Ok(counts) => counts,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(e);
+        }
+    };
+    if dry_run {
+        fs::remove_dir_all(&staging)?;
+        return Ok(MigrationReport::Migrated {
+            from,
+            projects,
+            tickets,
+            backup_dir: None,
+        });
+    }
+    install(data_dir, &staging, &backup_name)?;
+    tracing::info!(
+        data_dir = %data_dir.display(),
+        from,
+        to = DATA_FORMAT_VERSION,
+        projects,
+        tickets,
+        "データディレクトリを移行しました。"
+    );
+
+    Ok(MigrationReport::Migrated {
+        from,
+        projects,
+        tickets,
+        backup_dir: Some(data_dir.join(backup_name)),
+    })
+}
+
+/// 移行前の項目を一時ディレクトリに複製して、現在の形式バージョンに変換する。
+///
+/// # 戻り値
+///
+/// 移行したプロジェクトの数と、チケットの数
+fn stage(
+    data_dir: &Path,
+    staging: &Path,
+    from: u32,
+    now: DateTime<Utc>,
+) -> PersistenceResult<(usize, usize)> {
+    fs::create_dir(staging)?;
+    for name in VERSIONED_ENTRIES {
+        copy_entry(&data_dir.join(name), &staging.join(name))?;
+    }
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(from as usize - 1) {
+        migration(staging, now)?;
+        tracing::info!(
+            from = index + 1,
+            to = index + 2,
+            "データディレクトリの形式バージョンを変換しました。"
+        );
+    }
+    fs::create_dir_all(staging.join(PROJECTS_DIR_NAME))?;
+    write_header(staging)?;
+
+    // 移行したすべてのプロジェクトを、サーバーと同じように開けることを確かめる。
+    let project_dirs = project_dirs(staging)?;
+    let mut tickets = 0;
+    for project_dir in &project_dirs {
+        tickets += TicketStore::open(project_dir)?.len();
+    }
+
+    Ok((project_dirs.len(), tickets))
+}
+
+/// 移行前の項目を退避して、移行した項目と入れ替える。
+///
+/// 移行前の項目は一時ディレクトリの中に退避し、形式バージョンを記録してから、データディレクトリに移す。
+fn install(data_dir: &Path, staging: &Path, backup_name: &str) -> PersistenceResult<()> {
+    let replaced = staging.join(backup_name);
+    fs::create_dir(&replaced)?;
+    for name in VERSIONED_ENTRIES {
+        let path = data_dir.join(name);
+        if path.exists() {
+            fs::rename(&path, replaced.join(name))?;
+        }
+    }
+    fs::rename(
+        staging.join(PROJECTS_DIR_NAME),
+        data_dir.join(PROJECTS_DIR_NAME),
+    )?;
+    // 形式バージョンを記録した時点で、移行が完了する。
+    fs::rename(
+        staging.join(FORMAT_FILE_NAME),
+        data_dir.join(FORMAT_FILE_NAME),
+    )?;
+    File::open(data_dir)?.sync_all()?;
+    fs::rename(&replaced, data_dir.join(backup_name))?;
+    fs::remove_dir_all(staging)?;
+
+    Ok(())
+}
+
+/// 中断した移行の一時ディレクトリを片付ける。
+///
+/// 形式バージョンを記録する前に中断した場合は、退避した移行前の項目を元に戻す。
+fn recover(data_dir: &Path) -> PersistenceResult<()> {
+    let staging = data_dir.join(MIGRATING_DIR_NAME);
+    if !staging.exists() {
+        return Ok(());
+    }
+    let mut replaced = None;
+    for entry in fs::read_dir(&staging)? {
+        let entry = entry?;
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(BACKUP_DIR_PREFIX)
+        {
+            replaced = Some(entry);
+        }
+    }
+
+    if let Some(replaced) = replaced {
+        if staging.join(FORMAT_FILE_NAME).exists() {
+            // 移行した`projects`ディレクトリを入れ替えた後であれば、移行前の項目に戻す前に取り除く。
+            let projects_dir = data_dir.join(PROJECTS_DIR_NAME);
+            if !staging.join(PROJECTS_DIR_NAME).exists() && projects_dir.exists() {
+                fs::remove_dir_all(&projects_dir)?;
+            }
+            for entry in fs::read_dir(replaced.path())? {
+                let entry = entry?;
+                fs::rename(entry.path(), data_dir.join(entry.file_name()))?;
+            }
+            tracing::warn!(
+                data_dir = %data_dir.display(),
+                "中断した移行を取り消し、移行前の状態に戻しました。"
+            );
+        } else {
+            fs::rename(replaced.path(), data_dir.join(replaced.file_name()))?;
+        }
+    }
+    fs::remove_dir_all(&staging)?;
+
+    Ok(())
+}
+
+/// 形式バージョン1のチケットに、優先度、期限、作成日時、更新日時を加える。
+///
+/// 作成日時と更新日時は記録されていないため、移行した日時とする。
+fn add_ticket_fields(dir: &Path, now: DateTime<Utc>) -> PersistenceResult<()> {
+    let now = json!(now);
+    update_tickets(dir, |ticket| {
+        ticket.entry("priority").or_insert(json!("Medium"));
+        ticket.entry("dueDate").or_insert(Value::Null);
+        ticket.entry("createdAt").or_insert(now.clone());
+        ticket.entry("updatedAt").or_insert(now.clone());
+        Ok(())
+    })
+}
+
+/// 形式バージョン2のチケットにチケットキーを加え、すべてのチケットを既定のプロジェクトに含める。
+///
+/// 形式バージョン2までのチケットIDは0から割り当てるため、チケットキーの番号はチケットIDに1を加えた値とする。
+fn add_projects(dir: &Path, _now: DateTime<Utc>) -> PersistenceResult<()> {
+    let mut next_number = 1;
+    update_tickets(dir, |ticket| {
+        let number = ticket_id(ticket)? + 1;
+        ticket.insert(
+            "key".into(),
+            json!(format!("{DEFAULT_PROJECT_KEY}-{number}")),
+        );
+        next_number = next_number.max(number + 1);
+        Ok(())
+    })?;
+
+    let path = dir.join(SNAPSHOT_FILE_NAME);
+    if let Some(mut snapshot) = read_json::<Value>(&path)? {
+        let next_id = snapshot["nextId"].as_u64().unwrap_or_default();
+        snapshot["projects"] = json!([{
+            "key": DEFAULT_PROJECT_KEY,
+            "name": Project::default().name.0,
+            "previousKeys": [],
+            "nextNumber": next_number.max(next_id + 1),
+        }]);
+        write_json(&path, &snapshot)?;
+    }
+
+    Ok(())
+}
+
+/// 形式バージョン3のデータディレクトリ直下のプロジェクトとチケットを、プロジェクトごとのディレクトリに分ける。
+///
+/// 形式バージョン4からチケットIDはプロジェクトごとに0から割り当てるため、チケットキーの番号から1を引いた値をチケットIDとする。
+/// プロジェクトキーを変更する前に記録されたチケットのチケットキーは、変更後のプロジェクトキーに読み替える。
+fn split_projects(dir: &Path, _now: DateTime<Utc>) -> PersistenceResult<()> {
+    let snapshot_path = dir.join(SNAPSHOT_FILE_NAME);
+    let journal_path = dir.join(JOURNAL_FILE_NAME);
+    let snapshot = read_json::<Value>(&snapshot_path)?.unwrap_or_default();
+    let journal = read_lines(&journal_path)?;
+
+    // 形式バージョン3は、既定のプロジェクトを常に持つ。
+    let mut projects = BTreeMap::from([(
+        DEFAULT_PROJECT_KEY.to_string(),
+        ProjectV3 {
+            key: DEFAULT_PROJECT_KEY.into(),
+            name: Project::default().name.0,
+            previous_keys: vec![],
+            next_number: 1,
+        },
+    )]);
+    let mut tickets = BTreeMap::new();
+    let records = array(&snapshot, "projects")
+        .chain(array(&snapshot, "tickets"))
+        .chain(&journal);
+    for record in records {
+        if record.get("name").is_some() {
+            let project: ProjectV3 = serde_json::from_value(record.clone())
+                .map_err(|source| corrupted(&journal_path, source))?;
+            for previous_key in &project.previous_keys {
+                projects.remove(previous_key);
+            }
+            projects.insert(project.key.clone(), project);
+        } else {
+            let ticket = object(record)?;
+            tickets.insert(ticket_id(&ticket)?, ticket);
+        }
+    }
+
+    let aliases: BTreeMap<_, _> = projects
+        .values()
+        .flat_map(|p| p.previous_keys.iter().map(|previous| (previous, &p.key)))
+        .collect();
+    let mut project_tickets = BTreeMap::<&String, BTreeMap<u64, Map<String, Value>>>::new();
+    for mut ticket in tickets.into_values() {
+        let ticket_key = ticket["key"].as_str().unwrap_or_default().to_string();
+        let (project, number) = ticket_key
+            .rsplit_once('-')
+            .and_then(|(project, number)| Some((project, number.parse::<u64>().ok()?)))
+            .ok_or_else(|| {
+                PersistenceError::Unmigratable(format!(
+                    "チケットキー`{ticket_key}`が誤っています。"
+                ))
+            })?;
+        let project = project.to_string();
+        let current = aliases.get(&project).copied().unwrap_or(&project);
+        let Some(project) = projects.get(current).map(|p| &p.key) else {
+            return Err(PersistenceError::Unmigratable(format!(
+                "チケット`{ticket_key}`のプロジェクトが見つかりません。"
+            )));
+        };
+        ticket.insert("id".into(), json!(number.saturating_sub(1)));
+        ticket.insert("key".into(), json!(format!("{project}-{number}")));
+        ticket.insert("previousKeys".into(), json!([]));
+        if project_tickets
+            .entry(project)
+            .or_default()
+            .insert(number, ticket)
+            .is_some()
+        {
+            return Err(PersistenceError::Unmigratable(format!(
+                "チケットキー`{project}-{number}`が重複しています。"
+            )));
+        }
+    }
+
+    for project in projects.values() {
+        let tickets = project_tickets.remove(&project.key).unwrap_or_default();
+        let next_id = tickets
+            .keys()
+            .last()
+            .copied()
+            .unwrap_or_default()
+            .max(project.next_number.saturating_sub(1));
+        let project_dir = dir.join(PROJECTS_DIR_NAME).join(&project.key);
+        fs::create_dir_all(&project_dir)?;
+        let snapshot = json!({
+            "project": {
+                "key": project.key,
+                "name": project.name,
+                "previousKeys": project.previous_keys,
+            },
+            "nextId": next_id,
+            "tickets": tickets.into_values().collect::<Vec<_>>(),
+            "moved": [],
+        });
+        write_json(&project_dir.join(SNAPSHOT_FILE_NAME), &snapshot)?;
+    }
+    remove_file_if_exists(&snapshot_path)?;
+    remove_file_if_exists(&journal_path)
+}
+
+/// 形式バージョン4の各プロジェクトのチケットを取り込むドメインイベントを、イベントログに記録する。
+///
+/// イベントソーシングに移行する前に記録されたチケットと同じように、
+/// チケットの状態をスキーマバージョン1のドメインイベントとして記録する。
+fn import_events(dir: &Path, _now: DateTime<Utc>) -> PersistenceResult<()> {
+    for project_dir in project_dirs(dir)? {
+        let snapshot_path = project_dir.join(SNAPSHOT_FILE_NAME);
+        let journal_path = project_dir.join(JOURNAL_FILE_NAME);
+        let snapshot = read_json::<Value>(&snapshot_path)?.unwrap_or_default();
+        let mut project = snapshot.get("project").filter(|p| !p.is_null()).cloned();
+        let mut next_id = snapshot["nextId"].as_u64().unwrap_or_default();
+        let mut tickets = BTreeMap::new();
+        for ticket in array(&snapshot, "tickets") {
+            let ticket = object(ticket)?;
+            tickets.insert(ticket_id(&ticket)?, ticket);
+        }
+        let mut moved = BTreeMap::new();
+        for moved_ticket in array(&snapshot, "moved") {
+            let moved_ticket = object(moved_ticket)?;
+            moved.insert(ticket_id(&moved_ticket)?, moved_ticket);
+        }
+        for record in read_lines(&journal_path)? {
+            let record = object(&record)?;
+            if record.contains_key("movedTo") {
+                let id = ticket_id(&record)?;
+                tickets.remove(&id);
+                moved.insert(id, record);
+            } else if record.contains_key("name") {
+                project = Some(Value::Object(record));
+            } else {
+                let id = ticket_id(&record)?;
+                next_id = next_id.max(id + 1);
+                tickets.insert(id, record);
+            }
+        }
+        let Some(project) = project else {
+            return Err(PersistenceError::MissingProject(project_dir));
+        };
+
+        let events: Vec<_> = tickets
+            .iter()
+            .map(|(id, ticket)| {
+                json!({
+                    "schema": 1,
+                    "ticket": id,
+                    "version": ticket["version"],
+                    "occurredAt": ticket["updatedAt"],
+                    "event": ticket,
+                })
+            })
+            .collect();
+        write_lines(&project_dir.join(EVENT_LOG_FILE_NAME), &events)?;
+        let snapshot = json!({
+            "project": project,
+            "nextId": next_id,
+            "events": events.len(),
+            "tickets": tickets.into_values().collect::<Vec<_>>(),
+            "moved": moved.into_values().collect::<Vec<_>>(),
+        });
+        write_json(&snapshot_path, &snapshot)?;
+        write_lines(&journal_path, &[])?;
+    }
+
+    Ok(())
+}
+
+/// 形式バージョン5から形式バージョン6への変換
+///
+/// 形式バージョン6はファイルの形式を変えず、形式バージョンを記録するファイルを加える。
+/// 形式バージョンを記録するファイルは、すべての変換の後に書き出す。
+fn record_format(_dir: &Path, _now: DateTime<Utc>) -> PersistenceResult<()> {
+    Ok(())
+}
+
+/// データディレクトリ直下のスナップショットとジャーナルのチケットを変換する。
+///
+/// 形式バージョン3より前のジャーナルは、チケットだけを記録する。
+fn update_tickets(
+    dir: &Path,
+    mut update: impl FnMut(&mut Map<String, Value>) -> PersistenceResult<()>,
+) -> PersistenceResult<()> {
+    let snapshot_path = dir.join(SNAPSHOT_FILE_NAME);
+    if let Some(mut snapshot) = read_json::<Value>(&snapshot_path)? {
+        if let Some(tickets) = snapshot.get_mut("tickets").and_then(Value::as_array_mut) {
+            for ticket in tickets {
+                let Value::Object(ticket) = ticket else {
+                    return Err(PersistenceError::Unmigratable(format!(
+                        "チケットの形式が誤っています: {ticket}"
+                    )));
+                };
+                update(ticket)?;
+            }
+        }
+        write_json(&snapshot_path, &snapshot)?;
+    }
+
+    let journal_path = dir.join(JOURNAL_FILE_NAME);
+    if journal_path.exists() {
+        let mut records = vec![];
+        for record in read_lines(&journal_path)? {
+            let mut ticket = object(&record)?;
+            update(&mut ticket)?;
+            records.push(Value::Object(ticket));
+        }
+        write_lines(&journal_path, &records)?;
+    }
+
+    Ok(())
+}
+
+/// データディレクトリに、現在の形式バージョンを記録する。
+fn write_header(data_dir: &Path) -> PersistenceResult<()> {
+    let header = FormatHeader {
+        format: DATA_FORMAT_VERSION,
+    };
+    write_json(&data_dir.join(FORMAT_FILE_NAME), &header)
+}
+
+/// `projects`ディレクトリにある、パス順のプロジェクトのディレクトリを返す。
+fn project_dirs(data_dir: &Path) -> PersistenceResult<Vec<PathBuf>> {
+    let entries = match fs::read_dir(data_dir.join(PROJECTS_DIR_NAME)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut project_dirs = vec![];
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            project_dirs.push(entry.path());
+        }
+    }
+    project_dirs.sort();
+
+    Ok(project_dirs)
+}
+
+/// ファイルまたはディレクトリを、中身ごと複製する。存在しない場合は何もしない。
+fn copy_entry(from: &Path, to: &Path) -> PersistenceResult<()> {
+    let metadata = match fs::metadata(from) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    if metadata.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_entry(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, to)?;
+    }
+
+    Ok(())
+}
+
+/// JSONファイルを読み込む。
+///
+/// # 戻り値
+///
+/// ファイルの内容、ファイルが存在しない場合は`None`
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> PersistenceResult<Option<T>> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|source| corrupted(path, source))
+}
+
+/// 1行1レコードのJSONファイルを読み込む。
+///
+/// ジャーナルを開くときと同じように、書き込みが途中で途切れた最終行は切り捨てる。
+fn read_lines(path: &Path) -> PersistenceResult<Vec<Value>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut records = vec![];
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        match serde_json::from_str(line) {
+            Ok(record) => records.push(record),
+            Err(_) if !line.ends_with('\n') => break,
+            Err(source) => {
+                return Err(PersistenceError::Corrupted {
+                    line: index + 1,
+                    source,
+                })
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+/// レコードを1行1レコードのJSONファイルに書き出す。
+fn write_lines(path: &Path, records: &[Value]) -> PersistenceResult<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for record in records {
+        serde_json::to_writer(&mut writer, record).map_err(io::Error::from)?;
+        writer.write_all(b"\n")?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+
+    Ok(())
+}
+
+fn remove_file_if_exists(path: &Path) -> PersistenceResult<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// JSONの値の、配列であるフィールドの要素を返す。
+fn array<'a>(value: &'a Value, field: &str) -> impl Iterator<Item = &'a Value> {
+    value
+        .get(field)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+/// レコードをJSONのオブジェクトとして複製する。
+fn object(record: &Value) -> PersistenceResult<Map<String, Value>> {
+    match record {
+        Value::Object(record) => Ok(record.clone()),
+        _ => Err(PersistenceError::Unmigratable(format!(
+            "レコードの形式が誤っています: {record}"
+        ))),
+    }
+}
+
+/// レコードのチケットIDを返す。
+fn ticket_id(record: &Map<String, Value>) -> PersistenceResult<u64> {
+    record.get("id").and_then(Value::as_u64).ok_or_else(|| {
+        PersistenceError::Unmigratable(format!(
+            "レコードにチケットIDがありません: {}",
+            Value::Object(record.clone())
+        ))
+    })
+}
+
+fn corrupted(path: &Path, source: serde_json::Error) -> PersistenceError {
+    PersistenceError::CorruptedFile {
+        path: path.into(),
+        source,
+    }
+}
@@ -1,4 +1,12 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
 /// チケットID
+///
+/// チケットIDはプロジェクトごとに0から割り当てる。
 #[derive(
     Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
 )]
@@ -62,7 +70,21 @@ impl TryFrom<&str> for TicketTitle {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct TicketDescription(pub String);
 
+/// チケットの説明の書式
+///
+/// プロジェクトごとに設定し、説明はどちらの書式でも入力された原文のまま保存する。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum DescriptionFormat {
+    /// プレーンテキスト
+    #[default]
+    Plain,
+    /// CommonMark
+    Markdown,
+}
+
 /// チケットの説明の最大文字数
+///
+/// 書式が`Markdown`の場合も、HTMLに変換する前の原文に適用する。
 pub const TICKET_DESCRIPTION_MAX_CHARS: usize = 500;
 
 /// チケット説明エラー
@@ -112,7 +134,9 @@ impl TryFrom<&str> for TicketDescription {
 }
 
 /// チケットステータス
-#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
 pub enum TicketStatus {
     /// 未着手
     ToDo,
@@ -161,25 +185,729 @@ impl TryFrom<&str> for TicketStatus {
     }
 }
 
+/// チケットの優先度
+///
+/// 優先度は`Low`、`Medium`、`High`、`Urgent`の順に高くなる。
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(try_from = "String")]
+pub enum Priority {
+    /// 低
+    Low,
+    /// 中
+    #[default]
+    Medium,
+    /// 高
+    High,
+    /// 緊急
+    Urgent,
+}
+
+/// チケットの優先度エラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error(r#"チケットの優先度は、`"Low"`、`"Medium"`、`"High"`または`"Urgent"`のいずれかです。"#)]
+pub struct PriorityError;
+
+/// 文字列からチケットの優先度を構築する。
+///
+/// # 引数
+///
+/// * `s` - チケットの優先度を表現する文字列
+///
+/// # 戻り値
+///
+/// チケットの優先度
+fn priority_from_str(s: &str) -> Result<Priority, PriorityError> {
+    match s.trim().to_lowercase().as_str() {
+        "low" => Ok(Priority::Low),
+        "medium" => Ok(Priority::Medium),
+        "high" => Ok(Priority::High),
+        "urgent" => Ok(Priority::Urgent),
+        _ => Err(PriorityError),
+    }
+}
+
+impl TryFrom<String> for Priority {
+    type Error = PriorityError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        priority_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for Priority {
+    type Error = PriorityError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        priority_from_str(value)
+    }
+}
+
+/// プロジェクトキー
+///
+/// プロジェクトキーは英大文字で始まる2文字以上10文字以下の英大文字と数字で、チケットキーの接頭辞になる。
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(try_from = "String")]
+pub struct ProjectKey(String);
+
+/// プロジェクトキーの最小文字数
+const PROJECT_KEY_MIN_CHARS: usize = 2;
+
+/// プロジェクトキーの最大文字数
+const PROJECT_KEY_MAX_CHARS: usize = 10;
+
+/// 既定のプロジェクトのキー
+pub const DEFAULT_PROJECT_KEY: &str = "TICKET";
+
+/// プロジェクトキーエラー
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum ProjectKeyError {
+    #[error("プロジェクトキーは2文字以上10文字以下です。")]
+    Length,
+    #[error("プロジェクトキーは英字で始まり、英字と数字のみを含みます。")]
+    InvalidChar,
+}
+
+/// 文字列からプロジェクトキーを構築する。
+///
+/// 英小文字は英大文字に変換する。
+///
+/// # 引数
+///
+/// * `s` - プロジェクトキーを表現する文字列
+///
+/// # 戻り値
+///
+/// プロジェクトキー
+fn project_key_from_str(s: &str) -> Result<ProjectKey, ProjectKeyError> {
+    let s = s.trim().to_ascii_uppercase();
+    if !(PROJECT_KEY_MIN_CHARS..=PROJECT_KEY_MAX_CHARS).contains(&s.len()) {
+        return Err(ProjectKeyError::Length);
+    }
+    if !s.starts_with(|c: char| c.is_ascii_uppercase())
+        || !s
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+    {
+        return Err(ProjectKeyError::InvalidChar);
+    }
+
+    Ok(ProjectKey(s))
+}
+
+impl ProjectKey {
+    /// プロジェクトキーを文字列として返す。
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for ProjectKey {
+    fn default() -> Self {
+        Self(DEFAULT_PROJECT_KEY.into())
+    }
+}
+
+impl std::fmt::Display for ProjectKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<String> for ProjectKey {
+    type Error = ProjectKeyError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        project_key_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for ProjectKey {
+    type Error = ProjectKeyError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        project_key_from_str(value)
+    }
+}
+
+/// プロジェクト名
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct ProjectName(pub String);
+
+/// プロジェクト名の最大文字数
+const PROJECT_NAME_MAX_CHARS: usize = 50;
+
+/// プロジェクト名エラー
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum ProjectNameError {
+    #[error("プロジェクト名は空にできません。")]
+    Empty,
+    #[error("プロジェクト名は50文字以下です。")]
+    TooLong,
+}
+
+/// 文字列からプロジェクト名を構築する。
+///
+/// # 引数
+///
+/// * `s` - プロジェクト名を表現する文字列
+///
+/// # 戻り値
+///
+/// プロジェクト名
+fn project_name_from_str(s: &str) -> Result<ProjectName, ProjectNameError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(ProjectNameError::Empty);
+    }
+    if PROJECT_NAME_MAX_CHARS < s.chars().count() {
+        return Err(ProjectNameError::TooLong);
+    }
+
+    Ok(ProjectName(s.into()))
+}
+
+impl TryFrom<String> for ProjectName {
+    type Error = ProjectNameError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        project_name_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for ProjectName {
+    type Error = ProjectNameError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        project_name_from_str(value)
+    }
+}
+
+/// ステータスの遷移
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct StatusTransition {
+    pub from: TicketStatus,
+    pub to: TicketStatus,
+}
+
+/// ワークフロー
+///
+/// チケットのステータスを変更できる遷移を定める。遷移を定めていない場合は、すべての遷移を許可する。
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Workflow {
+    #[serde(default)]
+    pub transitions: Vec<StatusTransition>,
+}
+
+impl Workflow {
+    /// ステータスを遷移できるか確認する。
+    ///
+    /// 同じステータスへの遷移は、常に許可する。
+    pub fn allows(&self, from: TicketStatus, to: TicketStatus) -> bool {
+        from == to
+            || self.transitions.is_empty()
+            || self.transitions.contains(&StatusTransition { from, to })
+    }
+}
+
+/// プロジェクトの権限
+///
+/// 利用者の一覧が空の場合は、利用者を識別できないリクエストを含むすべてのリクエストにその操作を許可する。
+/// 書き込みを許可された利用者には、読み込みも許可する。
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Permissions {
+    /// 読み込みを許可する利用者
+    #[serde(default)]
+    pub readers: Vec<String>,
+    /// 書き込みを許可する利用者
+    #[serde(default)]
+    pub writers: Vec<String>,
+}
+
+impl Permissions {
+    /// 利用者に読み込みを許可するか確認する。
+    pub fn can_read(&self, user: Option<&str>) -> bool {
+        self.readers.is_empty()
+            || Self::contains(&self.readers, user)
+            || Self::contains(&self.writers, user)
+    }
+
+    /// 利用者に書き込みを許可するか確認する。
+    pub fn can_write(&self, user: Option<&str>) -> bool {
+        self.writers.is_empty() || Self::contains(&self.writers, user)
+    }
+
+    fn contains(users: &[String], user: Option<&str>) -> bool {
+        user.is_some_and(|user| users.iter().any(|u| u == user))
+    }
+}
+
+/// プロジェクト
+///
+/// プロジェクトキーを変更した場合、変更前のキーを`previous_keys`に残し、変更前のチケットキーを解決できるようにする。
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Project {
+    pub key: ProjectKey,
+    pub name: ProjectName,
+    /// 変更前のプロジェクトキー（古い順）
+    #[serde(default)]
+    pub previous_keys: Vec<ProjectKey>,
+    #[serde(default)]
+    pub workflow: Workflow,
+    #[serde(default)]
+    pub permissions: Permissions,
+    /// チケットの説明の書式
+    #[serde(default)]
+    pub description_format: DescriptionFormat,
+}
+
+impl Project {
+    pub fn new(key: ProjectKey, name: ProjectName) -> Self {
+        Self {
+            key,
+            name,
+            previous_keys: vec![],
+            workflow: Workflow::default(),
+            permissions: Permissions::default(),
+            description_format: DescriptionFormat::default(),
+        }
+    }
+
+    /// プロジェクトキーが、このプロジェクトの現在または変更前のプロジェクトキーか確認する。
+    pub fn is_known_as(&self, key: &ProjectKey) -> bool {
+        &self.key == key || self.previous_keys.contains(key)
+    }
+}
+
+impl Default for Project {
+    /// 既定のプロジェクトを返す。
+    fn default() -> Self {
+        Self::new(
+            ProjectKey::default(),
+            ProjectName("既定のプロジェクト".into()),
+        )
+    }
+}
+
+/// チケットキー
+///
+/// チケットキーは`WEB-42`のように、プロジェクトキーと1から始まる番号をハイフンでつないだ文字列で表現する。
+/// 番号は、プロジェクト内のチケットIDに1を加えた値である。
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct TicketKey {
+    pub project: ProjectKey,
+    pub number: u64,
+}
+
+/// チケットキーエラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error(
+    "チケットキーは、`WEB-42`のようにプロジェクトキーと1以上の番号をハイフンでつないだ文字列です。"
+)]
+pub struct TicketKeyError;
+
+/// 文字列からチケットキーを構築する。
+///
+/// # 引数
+///
+/// * `s` - チケットキーを表現する文字列
+///
+/// # 戻り値
+///
+/// チケットキー
+fn ticket_key_from_str(s: &str) -> Result<TicketKey, TicketKeyError> {
+    let (project, number) = s.trim().rsplit_once('-').ok_or(TicketKeyError)?;
+    let project = ProjectKey::try_from(project).map_err(|_| TicketKeyError)?;
+    if number.starts_with('0') || !number.chars().all(|c| c.is_ascii_digit()) {
+        return Err(TicketKeyError);
+    }
+    let number = number.parse().map_err(|_| TicketKeyError)?;
+
+    Ok(TicketKey { project, number })
+}
+
+impl TicketKey {
+    /// プロジェクト内のチケットIDから、チケットキーを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `project` - プロジェクトキー
+    /// * `id` - プロジェクト内のチケットID
+    ///
+    /// # 戻り値
+    ///
+    /// チケットキー
+    pub fn new(project: ProjectKey, id: TicketId) -> Self {
+        Self {
+            project,
+            number: id.0 + 1,
+        }
+    }
+
+    /// チケットキーの番号に対応する、プロジェクト内のチケットIDを返す。
+    pub fn id(&self) -> TicketId {
+        TicketId(self.number.saturating_sub(1))
+    }
+}
+
+impl std::fmt::Display for TicketKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.project, self.number)
+    }
+}
+
+impl From<TicketKey> for String {
+    fn from(value: TicketKey) -> Self {
+        value.to_string()
+    }
+}
+
+impl TryFrom<String> for TicketKey {
+    type Error = TicketKeyError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        ticket_key_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for TicketKey {
+    type Error = TicketKeyError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        ticket_key_from_str(value)
+    }
+}
+
+/// チケットの指定
+///
+/// 数値のみの文字列はチケットID、それ以外はチケットキーとして扱う。
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum TicketRef {
+    Id(TicketId),
+    Key(TicketKey),
+}
+
+/// チケットの指定エラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("チケットは、数値のチケットIDまたは`WEB-42`のようなチケットキーで指定します。")]
+pub struct TicketRefError;
+
+/// 文字列からチケットの指定を構築する。
+///
+/// # 引数
+///
+/// * `s` - チケットIDまたはチケットキーを表現する文字列
+///
+/// # 戻り値
+///
+/// チケットの指定
+fn ticket_ref_from_str(s: &str) -> Result<TicketRef, TicketRefError> {
+    let s = s.trim();
+    if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) {
+        return s
+            .parse()
+            .map(|id| TicketRef::Id(TicketId(id)))
+            .map_err(|_| TicketRefError);
+    }
+
+    TicketKey::try_from(s)
+        .map(TicketRef::Key)
+        .map_err(|_| TicketRefError)
+}
+
+impl TryFrom<String> for TicketRef {
+    type Error = TicketRefError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        ticket_ref_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for TicketRef {
+    type Error = TicketRefError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        ticket_ref_from_str(value)
+    }
+}
+
+impl From<TicketRef> for String {
+    fn from(value: TicketRef) -> Self {
+        value.to_string()
+    }
+}
+
+impl std::fmt::Display for TicketRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Id(id) => write!(f, "{}", id.0),
+            Self::Key(key) => key.fmt(f),
+        }
+    }
+}
+
+/// ラベル
+///
+/// ラベルは1文字以上30文字以下の英小文字、数字、`-`、`_`および`.`で、英大文字は英小文字に変換する。
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(try_from = "String")]
+pub struct Label(String);
+
+/// ラベルの最大文字数
+const LABEL_MAX_CHARS: usize = 30;
+
+/// ラベルエラー
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum LabelError {
+    #[error("ラベルは1文字以上30文字以下です。")]
+    Length,
+    #[error("ラベルは英数字、`-`、`_`および`.`のみを含みます。")]
+    InvalidChar,
+}
+
+/// 文字列からラベルを構築する。
+///
+/// # 引数
+///
+/// * `s` - ラベルを表現する文字列
+///
+/// # 戻り値
+///
+/// ラベル
+fn label_from_str(s: &str) -> Result<Label, LabelError> {
+    let s = s.trim().to_ascii_lowercase();
+    if s.is_empty() || LABEL_MAX_CHARS < s.len() {
+        return Err(LabelError::Length);
+    }
+    if !s
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(LabelError::InvalidChar);
+    }
+
+    Ok(Label(s))
+}
+
+impl Label {
+    /// ラベルを文字列として返す。
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Label {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<String> for Label {
+    type Error = LabelError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        label_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for Label {
+    type Error = LabelError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        label_from_str(value)
+    }
+}
+
 /// チケット
-#[derive(Debug, Clone, serde::Serialize)]
+///
+/// 作成日時と更新日時はチケットストアが設定する。
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Ticket {
     pub id: TicketId,
+    pub key: TicketKey,
+    /// 他のプロジェクトから移動する前のチケットキー（古い順）
+    #[serde(default)]
+    pub previous_keys: Vec<TicketKey>,
     pub title: TicketTitle,
     pub description: TicketDescription,
     pub status: TicketStatus,
+    pub priority: Priority,
+    pub due_date: Option<NaiveDate>,
+    /// 親チケットのチケットID（このチケットは親チケットのサブタスク）
+    #[serde(default)]
+    pub parent: Option<TicketId>,
+    /// このチケットをブロックしているチケットのチケットID
+    #[serde(default)]
+    pub blocked_by: BTreeSet<TicketId>,
+    /// 添付ファイル（追加した順）
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// ラベル
+    #[serde(default)]
+    pub labels: BTreeSet<Label>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
     pub version: u64,
 }
 
 impl Ticket {
-    pub fn new(id: TicketId, title: TicketTitle, description: TicketDescription) -> Self {
+    pub fn new(
+        id: TicketId,
+        key: TicketKey,
+        title: TicketTitle,
+        description: TicketDescription,
+        priority: Priority,
+        due_date: Option<NaiveDate>,
+        now: DateTime<Utc>,
+    ) -> Self {
         Self {
             id,
+            key,
+            previous_keys: vec![],
             title,
             description,
             status: TicketStatus::ToDo,
+            priority,
+            due_date,
+            parent: None,
+            blocked_by: BTreeSet::new(),
+            attachments: vec![],
+            labels: BTreeSet::new(),
+            created_at: now,
+            updated_at: now,
             version: 0,
         }
     }
+
+    /// 指定した日付の時点で、チケットの期限が過ぎているか確認する。
+    ///
+    /// 完了したチケットは、期限を過ぎていても期限切れとして扱わない。
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        self.status != TicketStatus::Done && self.due_date.is_some_and(|due| due < today)
+    }
+}
+
+/// 添付ファイルID
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub struct AttachmentId(pub Uuid);
+
+/// 添付ファイルの内容のSHA-256ダイジェスト
+///
+/// 小文字の16進数64文字で表現する。同じ内容の添付ファイルは、同じダイジェストの内容を共有する。
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct BlobDigest(String);
+
+/// 添付ファイルの内容のダイジェストエラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("ダイジェストは、SHA-256ハッシュ値を表現する小文字の16進数64文字です。")]
+pub struct BlobDigestError;
+
+impl BlobDigest {
+    /// 内容のダイジェストを計算する。
+    ///
+    /// # 引数
+    ///
+    /// * `content` - 添付ファイルの内容
+    ///
+    /// # 戻り値
+    ///
+    /// ダイジェスト
+    pub fn of(content: &[u8]) -> Self {
+        Self(hex::encode(Sha256::digest(content)))
+    }
+
+    /// ダイジェストを表現する文字列を返す。
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for BlobDigest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<BlobDigest> for String {
+    fn from(value: BlobDigest) -> Self {
+        value.0
+    }
+}
+
+impl TryFrom<String> for BlobDigest {
+    type Error = BlobDigestError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value.len() != 64
+            || !value
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+        {
+            return Err(BlobDigestError);
+        }
+
+        Ok(Self(value))
+    }
+}
+
+impl TryFrom<&str> for BlobDigest {
+    type Error = BlobDigestError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::try_from(value.to_string())
+    }
+}
+
+/// チケットの添付ファイルのメタデータ
+///
+/// 内容はチケットストアの外に、ダイジェストをキーとして保存する。
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub id: AttachmentId,
+    pub file_name: String,
+    /// 内容から判定したMIMEタイプ
+    pub content_type: String,
+    /// 内容のバイト数
+    pub size: u64,
+    pub digest: BlobDigest,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+/// 他のプロジェクトに移動したチケット
+///
+/// 移動元のプロジェクトは、移動したチケットのチケットIDを再利用せず、移動先のチケットキーに解決する。
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MovedTicket {
+    pub id: TicketId,
+    pub moved_to: TicketKey,
 }
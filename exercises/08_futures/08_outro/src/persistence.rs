@@ -0,0 +1,1033 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::EncryptionConfig;
+use crate::crypto::{DecryptError, Encryption, Sealing};
+use crate::domain::{self, EventError, RecordedEvent, StoredEvent};
+use crate::models::{MovedTicket, Project, Ticket};
+use crate::raft::{self, Entry, HardState, NodeId, Raft};
+
+/// ジャーナルファイル名
+pub(crate) const JOURNAL_FILE_NAME: &str = "journal.jsonl";
+
+/// イベントログファイル名
+pub(crate) const EVENT_LOG_FILE_NAME: &str = "events.jsonl";
+
+/// スナップショットファイル名
+pub(crate) const SNAPSHOT_FILE_NAME: &str = "snapshot.json";
+
+/// Raftの項と投票を永続化するファイル名
+const RAFT_VOTE_FILE_NAME: &str = "raft.json";
+
+/// Raftのスナップショットを永続化するファイル名
+const RAFT_SNAPSHOT_FILE_NAME: &str = "raft-snapshot.json";
+
+/// Raftのログを永続化するファイル名
+const RAFT_LOG_FILE_NAME: &str = "raft-log.jsonl";
+
+/// データディレクトリの暗号化が必須であることを記録するファイル名
+const ENCRYPTION_FILE_NAME: &str = "encryption.json";
+
+/// データディレクトリの暗号化が必須であることを記録するファイルの内容
+#[derive(serde::Serialize, serde::Deserialize)]
+struct EncryptionHeader {
+    required: bool,
+}
+
+/// データディレクトリに1つのプロジェクトのチケットを永続化するファイルストレージ
+///
+/// チケットのドメインイベントはイベントログに追記し、プロジェクトの変更とチケットの移動はジャーナルに追記する。
+/// チェックポイントでチケットストア全体をスナップショットに書き出してジャーナルを空にするが、
+/// イベントログはチケットの履歴として空にしない。
+/// 起動時は、スナップショットを読み込んだ後に、スナップショットより後のドメインイベントとジャーナルを再生する。
+///
+/// イベントソーシングに移行する前のジャーナルに記録されたチケットと、イベントの履歴を持たないスナップショットの
+/// チケットは、開くときにドメインイベントに変換してイベントログに移行する。
+///
+/// 暗号化が有効な場合は、スナップショットとジャーナルおよびイベントログの各レコードを暗号化する。
+#[derive(Debug)]
+pub struct FileStorage {
+    data_dir: PathBuf,
+    journal: Journal,
+    events: Journal,
+    encryption: Encryption,
+    /// 現在の鍵で暗号化されていないスナップショットを読み込んだ場合は`true`
+    stale_snapshot: bool,
+}
+
+impl FileStorage {
+    /// データディレクトリのファイルストレージを開き、永続化されているチケットを復元する。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - データディレクトリ
+    ///
+    /// # 戻り値
+    ///
+    /// ファイルストレージと、スナップショットとジャーナルから復元したチケットストアの状態、
+    /// 記録された順番のすべてのドメインイベント
+    pub fn open(data_dir: &Path) -> PersistenceResult<(Self, Snapshot, Vec<RecordedEvent>)> {
+        Self::open_encrypted(data_dir, &Encryption::default())
+    }
+
+    /// 暗号化を指定して、データディレクトリのファイルストレージを開き、永続化されているチケットを復元する。
+    ///
+    /// 暗号化されたファイルは現在の鍵または以前の鍵で復号し、認証に失敗した場合は[`PersistenceError::Tampered`]を返す。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - データディレクトリ
+    /// * `encryption` - 暗号化
+    ///
+    /// # 戻り値
+    ///
+    /// ファイルストレージと、スナップショットとジャーナルから復元したチケットストアの状態、
+    /// 記録された順番のすべてのドメインイベント
+    pub fn open_encrypted(
+        data_dir: &Path,
+        encryption: &Encryption,
+    ) -> PersistenceResult<(Self, Snapshot, Vec<RecordedEvent>)> {
+        fs::create_dir_all(data_dir)?;
+        let (mut snapshot, stale_snapshot) = match Snapshot::read(data_dir, encryption)? {
+            Some((snapshot, sealing)) => (snapshot, encryption.is_stale(sealing)),
+            None => (Snapshot::default(), false),
+        };
+        let (journal, records) =
+            Journal::open::<JournalRecord>(&data_dir.join(JOURNAL_FILE_NAME), encryption)?;
+        let (events, stored) =
+            Journal::open::<StoredEvent>(&data_dir.join(EVENT_LOG_FILE_NAME), encryption)?;
+        let mut history = stored
+            .into_iter()
+            .map(StoredEvent::upcast)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut storage = Self {
+            data_dir: data_dir.into(),
+            journal,
+            events,
+            encryption: encryption.clone(),
+            stale_snapshot,
+        };
+
+        let mut tickets: BTreeMap<_, _> = snapshot.tickets.into_iter().map(|t| (t.id, t)).collect();
+        let mut moved: BTreeMap<_, _> = snapshot.moved.into_iter().map(|m| (m.id, m)).collect();
+        let mut imported = vec![];
+        if snapshot.events == 0 && history.is_empty() {
+            imported.extend(tickets.values().map(StoredEvent::from_ticket_state));
+        }
+        let position = (snapshot.events as usize).min(history.len());
+        for record in &history[position..] {
+            let ticket = domain::apply(tickets.remove(&record.ticket), record)?;
+            snapshot.next_id = snapshot.next_id.max(record.ticket.0 + 1);
+            if let Some(ticket) = ticket {
+                tickets.insert(ticket.id, ticket);
+            }
+        }
+        for record in records {
+            match record {
+                JournalRecord::Ticket(ticket) => {
+                    imported.push(StoredEvent::from_ticket_state(&ticket));
+                    snapshot.next_id = snapshot.next_id.max(ticket.id.0 + 1);
+                    tickets.insert(ticket.id, ticket);
+                }
+                JournalRecord::Project(project) => snapshot.project = Some(project),
+                JournalRecord::Moved(moved_ticket) => {
+                    tickets.remove(&moved_ticket.id);
+                    moved.insert(moved_ticket.id, moved_ticket);
+                }
+            }
+        }
+        snapshot.tickets = tickets.into_values().collect();
+        snapshot.moved = moved.into_values().collect();
+
+        if !imported.is_empty() {
+            for stored in imported {
+                let record = stored.upcast()?;
+                storage.record_events(&EncodedEvents::new(std::slice::from_ref(&record))?)?;
+                history.push(record);
+            }
+            // 移行したチケットを再び移行しないように、ジャーナルを空にする。
+            snapshot.events = history.len() as u64;
+            storage.checkpoint(&snapshot)?;
+            tracing::info!(
+                data_dir = %data_dir.display(),
+                tickets = snapshot.tickets.len(),
+                "記録されたチケットをイベントログに移行しました。"
+            );
+        }
+        snapshot.events = history.len() as u64;
+
+        Ok((storage, snapshot, history))
+    }
+
+    /// バックアップしたプロジェクトのスナップショットとドメインイベントの履歴を、空のデータディレクトリに書き出す。
+    ///
+    /// 書き出したデータディレクトリは、[`FileStorage::open`]で開ける。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - 空または存在しないデータディレクトリ
+    /// * `snapshot` - ドメインイベントの履歴をすべて適用済みのスナップショット
+    /// * `history` - 記録された順番のすべてのドメインイベント
+    /// * `encryption` - 暗号化
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn import(
+        data_dir: &Path,
+        snapshot: &Snapshot,
+        history: &[RecordedEvent],
+        encryption: &Encryption,
+    ) -> PersistenceResult<()> {
+        fs::create_dir_all(data_dir)?;
+        let lines = history
+            .iter()
+            .map(|record| serde_json::to_vec(&StoredEvent::new(record)).map_err(io::Error::from));
+        let path = data_dir.join(EVENT_LOG_FILE_NAME);
+        write_lines(&path, &path, lines, encryption)?;
+
+        snapshot.write(data_dir, encryption)
+    }
+
+    /// JSONに変換したチケットのドメインイベントを、記録した順番にイベントログに記録する。
+    pub fn record_events(&mut self, events: &EncodedEvents) -> PersistenceResult<()> {
+        self.events.append_json(&events.0)
+    }
+
+    /// 追加または更新された後のプロジェクトをジャーナルに記録する。
+    pub fn record_project(&mut self, project: &Project) -> PersistenceResult<()> {
+        self.journal.append(project)
+    }
+
+    /// 他のプロジェクトに移動したチケットをジャーナルに記録する。
+    pub fn record_moved(&mut self, moved: &MovedTicket) -> PersistenceResult<()> {
+        self.journal.append(moved)
+    }
+
+    /// データディレクトリとその内容を削除する。
+    pub fn destroy(self) -> PersistenceResult<()> {
+        let Self {
+            data_dir,
+            journal,
+            events,
+            ..
+        } = self;
+        drop(journal);
+        drop(events);
+        fs::remove_dir_all(&data_dir)?;
+        tracing::info!(data_dir = %data_dir.display(), "データディレクトリを削除しました。");
+
+        Ok(())
+    }
+
+    /// チケットストア全体をスナップショットに書き出して、ジャーナルを空にする。
+    ///
+    /// # 引数
+    ///
+    /// * `snapshot` - チケットストアの状態
+    pub fn checkpoint(&mut self, snapshot: &Snapshot) -> PersistenceResult<()> {
+        snapshot.write(&self.data_dir, &self.encryption)?;
+        self.stale_snapshot = false;
+        self.journal.truncate()?;
+        tracing::info!(
+            data_dir = %self.data_dir.display(),
+            tickets = snapshot.tickets.len(),
+            "スナップショットを書き出しました。"
+        );
+
+        Ok(())
+    }
+
+    /// 現在の鍵で暗号化し直す必要があるファイルがあるか確認する。
+    pub fn is_stale(&self) -> bool {
+        self.stale_snapshot || self.journal.stale || self.events.stale
+    }
+
+    /// イベントログを現在の鍵で暗号化し直してから、チケットストア全体をスナップショットに書き出して、
+    /// ジャーナルを空にする。
+    ///
+    /// # 引数
+    ///
+    /// * `snapshot` - チケットストアの状態
+    pub fn reencrypt(&mut self, snapshot: &Snapshot) -> PersistenceResult<()> {
+        self.events.rewrite()?;
+        self.checkpoint(snapshot)?;
+        self.journal.stale = false;
+        tracing::info!(
+            data_dir = %self.data_dir.display(),
+            "現在の鍵で暗号化し直しました。"
+        );
+
+        Ok(())
+    }
+}
+
+/// イベントログに記録するために、JSONに変換したドメインイベント
+///
+/// ファイルストレージのロックを取得する前に変換して、ロックを保持する時間を追記だけにする。
+#[derive(Debug)]
+pub struct EncodedEvents(Vec<Vec<u8>>);
+
+impl EncodedEvents {
+    /// ドメインイベントを、記録した順番にJSONに変換する。
+    ///
+    /// # 引数
+    ///
+    /// * `records` - 記録した順番のドメインイベント
+    ///
+    /// # 戻り値
+    ///
+    /// JSONに変換したドメインイベント
+    pub fn new(records: &[RecordedEvent]) -> PersistenceResult<Self> {
+        records
+            .iter()
+            .map(|record| {
+                serde_json::to_vec(&StoredEvent::new(record))
+                    .map_err(|e| PersistenceError::Io(e.into()))
+            })
+            .collect::<PersistenceResult<_>>()
+            .map(Self)
+    }
+}
+
+/// チケットストアのスナップショット
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    /// プロジェクト
+    ///
+    /// プロジェクトを作成したときにジャーナルに記録するため、スナップショットとジャーナルのいずれにもない場合は
+    /// データディレクトリが壊れている。
+    pub project: Option<Project>,
+    /// 次に割り当てるチケットID
+    pub next_id: u64,
+    /// スナップショットのチケットに適用した、イベントログの先頭からのドメインイベントの数
+    #[serde(default)]
+    pub events: u64,
+    /// チケットID順のチケット
+    pub tickets: Vec<Ticket>,
+    /// チケットID順の、他のプロジェクトに移動したチケット
+    #[serde(default)]
+    pub moved: Vec<MovedTicket>,
+}
+
+impl Snapshot {
+    /// データディレクトリのスナップショットを読み込む。
+    ///
+    /// # 戻り値
+    ///
+    /// スナップショットと暗号化の状態、スナップショットが存在しない場合は`None`
+    fn read(
+        data_dir: &Path,
+        encryption: &Encryption,
+    ) -> PersistenceResult<Option<(Self, Sealing)>> {
+        let Some((content, sealing)) = read_file(&data_dir.join(SNAPSHOT_FILE_NAME), encryption)?
+        else {
+            return Ok(None);
+        };
+
+        serde_json::from_slice(&content)
+            .map(|snapshot| Some((snapshot, sealing)))
+            .map_err(PersistenceError::CorruptedSnapshot)
+    }
+
+    /// スナップショットをデータディレクトリに書き出す。
+    ///
+    /// [`write_encrypted_json`]で書き出すため、書き出しの途中で停止しても以前のスナップショットが残る。
+    fn write(&self, data_dir: &Path, encryption: &Encryption) -> PersistenceResult<()> {
+        write_encrypted_json(&data_dir.join(SNAPSHOT_FILE_NAME), self, encryption)
+    }
+}
+
+/// ファイル名を、暗号化した内容と一緒に認証する関連データとして返す。
+fn file_context(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// 復号エラーを、ファイルのパスを含む永続化エラーに変換する。
+fn decrypt_error(path: &Path) -> impl FnOnce(DecryptError) -> PersistenceError + '_ {
+    move |e| match e {
+        DecryptError::Tampered | DecryptError::Truncated | DecryptError::Unsealed => {
+            PersistenceError::Tampered(path.into())
+        }
+        source => PersistenceError::Undecryptable {
+            path: path.into(),
+            source,
+        },
+    }
+}
+
+/// 設定に従って、データディレクトリの暗号化を構築する。
+///
+/// [`require_encryption`]で暗号化を必須にしたデータディレクトリでは、暗号化されていないファイルとレコードを
+/// 改ざんされたものとして扱う。
+///
+/// # 引数
+///
+/// * `data_dir` - データディレクトリ
+/// * `config` - 暗号化設定
+///
+/// # 戻り値
+///
+/// 暗号化
+pub fn data_dir_encryption(
+    data_dir: &Path,
+    config: &EncryptionConfig,
+) -> PersistenceResult<Encryption> {
+    let encryption = Encryption::new(config);
+    let path = data_dir.join(ENCRYPTION_FILE_NAME);
+    let Some((content, _)) = read_file(&path, &Encryption::default())? else {
+        return Ok(encryption);
+    };
+    let header = serde_json::from_slice::<EncryptionHeader>(&content)
+        .map_err(|source| PersistenceError::CorruptedFile { path, source })?;
+    if !header.required {
+        return Ok(encryption);
+    }
+    if !encryption.is_enabled() {
+        return Err(PersistenceError::EncryptionRequired(data_dir.into()));
+    }
+
+    Ok(encryption.require_sealed())
+}
+
+/// データディレクトリのすべてのファイルを暗号化し終えたときに、以降は暗号化を必須にする。
+///
+/// 暗号化を必須にしたデータディレクトリは、暗号化の鍵を設定しなければ開けない。
+///
+/// # 引数
+///
+/// * `data_dir` - データディレクトリ
+///
+/// # 戻り値
+///
+/// `()`
+pub fn require_encryption(data_dir: &Path) -> PersistenceResult<()> {
+    write_json(
+        &data_dir.join(ENCRYPTION_FILE_NAME),
+        &EncryptionHeader { required: true },
+    )
+}
+
+/// ファイルを読み込み、暗号化されている場合は復号する。
+///
+/// # 引数
+///
+/// * `path` - 読み込むファイルのパス
+/// * `encryption` - 暗号化
+///
+/// # 戻り値
+///
+/// 内容と暗号化の状態、ファイルが存在しない場合は`None`
+pub(crate) fn read_file(
+    path: &Path,
+    encryption: &Encryption,
+) -> PersistenceResult<Option<(Vec<u8>, Sealing)>> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let (content, sealing) = encryption
+        .open(&file_context(path), &content)
+        .map_err(decrypt_error(path))?;
+
+    Ok(Some((content.into_owned(), sealing)))
+}
+
+/// 値をJSONファイルに書き出す。
+///
+/// # 引数
+///
+/// * `path` - 書き出すファイルのパス
+/// * `value` - 書き出す値
+///
+/// # 戻り値
+///
+/// `()`
+pub(crate) fn write_json(path: &Path, value: &impl serde::Serialize) -> PersistenceResult<()> {
+    write_encrypted_json(path, value, &Encryption::default())
+}
+
+/// 暗号化が有効な場合は暗号化して、値をJSONファイルに書き出す。
+///
+/// 一時ファイルに書き込んでから名前を変更するため、書き出しの途中で停止しても以前のファイルが残る。
+///
+/// # 引数
+///
+/// * `path` - 書き出すファイルのパス
+/// * `value` - 書き出す値
+/// * `encryption` - 暗号化
+///
+/// # 戻り値
+///
+/// `()`
+pub(crate) fn write_encrypted_json(
+    path: &Path,
+    value: &impl serde::Serialize,
+    encryption: &Encryption,
+) -> PersistenceResult<()> {
+    let content = serde_json::to_vec(value).map_err(io::Error::from)?;
+    let tmp_path = path.with_extension("json.tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(&encryption.seal(&file_context(path), &content))?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    sync_parent(path)
+}
+
+/// ファイルの名前の変更を永続化するため、親ディレクトリを同期する。
+fn sync_parent(path: &Path) -> PersistenceResult<()> {
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+/// 暗号化が有効な場合は各行を暗号化して、1行1レコードのファイルを書き出す。
+///
+/// # 引数
+///
+/// * `path` - 書き出すファイルのパス
+/// * `context_path` - 暗号化したレコードと一緒に認証するファイル名を持つパス
+/// * `lines` - 改行を含まないJSONのレコード
+/// * `encryption` - 暗号化
+///
+/// # 戻り値
+///
+/// `()`
+fn write_lines(
+    path: &Path,
+    context_path: &Path,
+    lines: impl IntoIterator<Item = io::Result<Vec<u8>>>,
+    encryption: &Encryption,
+) -> PersistenceResult<()> {
+    let name = file_context(context_path);
+    let mut writer = BufWriter::new(File::create(path)?);
+    for (index, line) in lines.into_iter().enumerate() {
+        writer.write_all(&encryption.seal_line(&line_context(&name, index), &line?))?;
+        writer.write_all(b"\n")?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+
+    Ok(())
+}
+
+/// ファイル名と行番号を、暗号化したレコードと一緒に認証する関連データとして返す。
+///
+/// 行番号を認証させるため、暗号化したレコードを並べ替えると復号できない。
+fn line_context(name: &str, index: usize) -> String {
+    format!("{name}:{index}")
+}
+
+/// 書き込みが途中で途切れた最終行を切り捨てることを記録する。
+fn warn_torn(index: usize) {
+    tracing::warn!(
+        line = index + 1,
+        "書き込みが途中で途切れたレコードを切り捨てます。"
+    );
+}
+
+/// ジャーナルのレコード
+///
+/// `Ticket`は、イベントソーシングに移行する前に記録された、追加または更新された後のチケットである。
+#[derive(Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum JournalRecord {
+    Ticket(Ticket),
+    Project(Project),
+    Moved(MovedTicket),
+}
+
+/// 変更を1行1レコードのJSONで追記するファイル
+///
+/// ジャーナルには、変更後のプロジェクトと他のプロジェクトに移動したチケットを記録する。
+/// イベントログには、チケットのドメインイベントを記録する。
+/// 先頭から再生すると、プロジェクトと各チケットの最新の状態を復元できる。
+#[derive(Debug)]
+struct Journal {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    encryption: Encryption,
+    /// 記録されているレコードの数
+    lines: usize,
+    /// 現在の鍵で暗号化されていないレコードがある場合は`true`
+    stale: bool,
+}
+
+impl Journal {
+    /// ジャーナルを開き、記録されているレコードを読み込む。
+    ///
+    /// ジャーナルが存在しない場合は作成する。
+    /// 書き込み途中で停止したために、改行で終わらない最終行がJSONとして途切れているか、
+    /// 暗号化されたレコードとして途切れている場合は、その行を切り捨てる。
+    /// 暗号化されたレコードの認証に失敗した場合は、最終行であっても、暗号化されたレコードの後に
+    /// 暗号化されていないレコードがある場合と同じく、改ざんされたものとして[`PersistenceError::Tampered`]を返す。
+    ///
+    /// # 引数
+    ///
+    /// * `path` - ジャーナルのパス
+    /// * `encryption` - 暗号化
+    ///
+    /// # 戻り値
+    ///
+    /// ジャーナルと、ジャーナルに記録された順番のレコード
+    fn open<R: serde::de::DeserializeOwned>(
+        path: &Path,
+        encryption: &Encryption,
+    ) -> PersistenceResult<(Self, Vec<R>)> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let name = file_context(path);
+        let mut records = vec![];
+        let mut valid_len = 0;
+        let mut sealed = false;
+        let mut stale = false;
+        for (index, line) in content.split_inclusive('\n').enumerate() {
+            let torn = !line.ends_with('\n');
+            let (json, sealing) = match encryption
+                .open_line(&line_context(&name, index), line.trim_end_matches('\n'))
+            {
+                Ok(opened) => opened,
+                Err(DecryptError::Truncated) if torn => {
+                    warn_torn(index);
+                    break;
+                }
+                Err(e) => return Err(decrypt_error(path)(e)),
+            };
+            // 暗号化を有効にした後は暗号化したレコードだけを追記するため、その後の平文は差し込まれたものである。
+            if sealing == Sealing::Plain && sealed {
+                return Err(PersistenceError::Tampered(path.into()));
+            }
+            sealed |= sealing != Sealing::Plain;
+            stale |= encryption.is_stale(sealing);
+            match serde_json::from_slice::<R>(&json) {
+                Ok(record) => records.push(record),
+                Err(_) if torn => {
+                    warn_torn(index);
+                    break;
+                }
+                Err(e) => {
+                    return Err(PersistenceError::Corrupted {
+                        line: index + 1,
+                        source: e,
+                    })
+                }
+            }
+            valid_len += line.len();
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.set_len(valid_len as u64)?;
+        tracing::info!(
+            path = %path.display(),
+            records = records.len(),
+            "ジャーナルを再生しました。"
+        );
+
+        Ok((
+            Self {
+                path: path.into(),
+                writer: BufWriter::new(file),
+                encryption: encryption.clone(),
+                lines: records.len(),
+                stale,
+            },
+            records,
+        ))
+    }
+
+    /// レコードをジャーナルに追記する。
+    ///
+    /// # 引数
+    ///
+    /// * `record` - ジャーナルに記録するレコード
+    fn append(&mut self, record: &impl serde::Serialize) -> PersistenceResult<()> {
+        let json = serde_json::to_vec(record).map_err(io::Error::from)?;
+        self.append_json(&[json])
+    }
+
+    /// JSONに変換したレコードを、順番にジャーナルに追記する。
+    ///
+    /// 暗号化はレコードの行番号を認証するため、追記する直前に行う。
+    ///
+    /// # 引数
+    ///
+    /// * `jsons` - ジャーナルに記録するレコードのJSON
+    fn append_json(&mut self, jsons: &[Vec<u8>]) -> PersistenceResult<()> {
+        let name = file_context(&self.path);
+        for json in jsons {
+            let context = line_context(&name, self.lines);
+            self.writer
+                .write_all(&self.encryption.seal_line(&context, json))?;
+            self.writer.write_all(b"\n")?;
+            self.lines += 1;
+        }
+        self.writer.flush()?;
+
+        Ok(())
+    }
+
+    /// ジャーナルを空にする。
+    fn truncate(&mut self) -> PersistenceResult<()> {
+        self.writer.flush()?;
+        let file = self.writer.get_ref();
+        file.set_len(0)?;
+        file.sync_all()?;
+        self.lines = 0;
+
+        Ok(())
+    }
+
+    /// すべてのレコードを現在の鍵で暗号化し直す。
+    ///
+    /// 一時ファイルに書き出してから名前を変更するため、書き出しの途中で停止しても以前のジャーナルが残る。
+    fn rewrite(&mut self) -> PersistenceResult<()> {
+        if !self.stale {
+            return Ok(());
+        }
+        self.writer.flush()?;
+        let content = fs::read_to_string(&self.path)?;
+        let name = file_context(&self.path);
+        let lines = content
+            .lines()
+            .enumerate()
+            .map(|(index, line)| {
+                self.encryption
+                    .open_line(&line_context(&name, index), line)
+                    .map(|(json, _)| json.into_owned())
+                    .map_err(decrypt_error(&self.path))
+            })
+            .collect::<PersistenceResult<Vec<_>>>()?;
+
+        self.replace(lines)
+    }
+
+    /// すべてのレコードを、現在の鍵で暗号化したレコードに置き換える。
+    ///
+    /// 一時ファイルに書き出してから名前を変更するため、書き出しの途中で停止しても以前のジャーナルが残る。
+    ///
+    /// # 引数
+    ///
+    /// * `jsons` - 置き換えた後のレコードのJSON
+    fn replace(&mut self, jsons: Vec<Vec<u8>>) -> PersistenceResult<()> {
+        self.writer.flush()?;
+        let lines = jsons.len();
+        let tmp_path = self.path.with_extension("jsonl.tmp");
+        write_lines(
+            &tmp_path,
+            &self.path,
+            jsons.into_iter().map(Ok),
+            &self.encryption,
+        )?;
+        fs::rename(&tmp_path, &self.path)?;
+        sync_parent(&self.path)?;
+        let file = OpenOptions::new().append(true).open(&self.path)?;
+        self.writer = BufWriter::new(file);
+        self.lines = lines;
+        self.stale = false;
+
+        Ok(())
+    }
+
+    /// 追記したレコードをディスクに同期する。
+    fn sync(&mut self) -> PersistenceResult<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()?;
+
+        Ok(())
+    }
+}
+
+/// Raftの項と投票
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RaftVote {
+    term: u64,
+    voted_for: Option<NodeId>,
+}
+
+/// Raftのログのレコード
+///
+/// 記録されている位置のレコードを再び記録した場合は、その位置以降のエントリを置き換える。
+#[derive(serde::Serialize, serde::Deserialize)]
+struct RaftLogRecord<E> {
+    index: u64,
+    entry: E,
+}
+
+/// 前回の書き出しから変わった、Raftの状態
+#[derive(Debug)]
+pub struct RaftChanges<C, S> {
+    /// 変わった項と投票
+    vote: Option<RaftVote>,
+    /// ログを切り詰めたか、リーダーから受け取ったスナップショット
+    snapshot: Option<raft::Snapshot<S>>,
+    /// `entries`の最初のエントリの位置
+    first_index: u64,
+    /// 永続化していないエントリ（スナップショットがある場合は、その後のすべてのエントリ）
+    entries: Vec<Entry<C>>,
+}
+
+impl<C, S> RaftChanges<C, S> {
+    /// 書き出した後のログの最後のエントリの位置を返す。
+    pub fn last_index(&self) -> u64 {
+        self.first_index + self.entries.len() as u64 - 1
+    }
+}
+
+/// データディレクトリにRaftの状態を永続化するストレージ
+///
+/// 項と投票、スナップショットはそれぞれJSONファイルに書き出し、エントリはログに追記する。
+/// ログは、スナップショットでログを切り詰めたときに、スナップショットより後のエントリだけで書き直す。
+/// 暗号化が有効な場合は、ファイルとログの各レコードを暗号化する。
+#[derive(Debug)]
+pub struct RaftStorage {
+    data_dir: PathBuf,
+    log: Journal,
+    encryption: Encryption,
+    /// 書き出した項と投票
+    vote: Option<RaftVote>,
+    /// 書き出したスナップショットの最後のエントリの位置
+    snapshot_index: u64,
+}
+
+impl RaftStorage {
+    /// データディレクトリのRaftのストレージを開き、永続化されている状態を復元する。
+    ///
+    /// 現在の鍵で暗号化されていないファイルやレコードは、現在の鍵で暗号化し直す。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - データディレクトリ
+    /// * `encryption` - 暗号化
+    ///
+    /// # 戻り値
+    ///
+    /// ストレージと、永続化されているRaftの状態（永続化していない場合は`None`）
+    pub fn open<C, S>(
+        data_dir: &Path,
+        encryption: &Encryption,
+    ) -> PersistenceResult<(Self, Option<HardState<C, S>>)>
+    where
+        C: serde::Serialize + serde::de::DeserializeOwned,
+        S: serde::Serialize + serde::de::DeserializeOwned + Default,
+    {
+        let vote: Option<RaftVote> = read_json(&data_dir.join(RAFT_VOTE_FILE_NAME), encryption)?;
+        let snapshot: Option<raft::Snapshot<S>> =
+            read_json(&data_dir.join(RAFT_SNAPSHOT_FILE_NAME), encryption)?;
+        let (mut log, records) = Journal::open::<RaftLogRecord<Entry<C>>>(
+            &data_dir.join(RAFT_LOG_FILE_NAME),
+            encryption,
+        )?;
+        log.rewrite()?;
+
+        let persisted = vote.is_some() || snapshot.is_some() || !records.is_empty();
+        let snapshot = snapshot.unwrap_or_default();
+        let mut entries = vec![];
+        // スナップショットを書き出した後、ログを書き直す前に停止した場合は、スナップショットに含まれるエントリが残る。
+        for record in records {
+            let Some(offset) = record.index.checked_sub(snapshot.index + 1) else {
+                continue;
+            };
+            if offset as usize > entries.len() {
+                return Err(PersistenceError::MissingRaftEntry(record.index));
+            }
+            entries.truncate(offset as usize);
+            entries.push(record.entry);
+        }
+        let storage = Self {
+            data_dir: data_dir.into(),
+            log,
+            encryption: encryption.clone(),
+            vote,
+            snapshot_index: snapshot.index,
+        };
+        let vote = vote.unwrap_or(RaftVote {
+            term: 0,
+            voted_for: None,
+        });
+        let hard_state = persisted.then_some(HardState {
+            term: vote.term,
+            voted_for: vote.voted_for,
+            snapshot,
+            log: entries,
+        });
+
+        Ok((storage, hard_state))
+    }
+
+    /// 前回の書き出しから変わった状態を返す。
+    ///
+    /// スナップショットが変わった場合だけ、スナップショットとその後のすべてのエントリをコピーする。
+    ///
+    /// # 引数
+    ///
+    /// * `raft` - Raftのノード
+    ///
+    /// # 戻り値
+    ///
+    /// 変わった状態、変わっていない場合は`None`
+    pub fn changes<C: Clone, S: Clone + Default>(
+        &self,
+        raft: &Raft<C, S>,
+    ) -> Option<RaftChanges<C, S>> {
+        let vote = RaftVote {
+            term: raft.term(),
+            voted_for: raft.voted_for(),
+        };
+        let snapshot = raft.snapshot();
+        let compacted = snapshot.index != self.snapshot_index;
+        let (first_index, entries) = if compacted {
+            (snapshot.index + 1, raft.entries())
+        } else {
+            raft.unstable_entries()
+        };
+        let changes = RaftChanges {
+            vote: (Some(vote) != self.vote).then_some(vote),
+            snapshot: compacted.then(|| snapshot.clone()),
+            first_index,
+            entries: entries.to_vec(),
+        };
+
+        (changes.vote.is_some() || changes.snapshot.is_some() || !changes.entries.is_empty())
+            .then_some(changes)
+    }
+
+    /// 変わった状態を書き出す。
+    ///
+    /// スナップショットは、ログを書き直す前に書き出す。
+    ///
+    /// # 引数
+    ///
+    /// * `changes` - [`RaftStorage::changes`]が返した状態
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn write<C: serde::Serialize, S: serde::Serialize>(
+        &mut self,
+        changes: &RaftChanges<C, S>,
+    ) -> PersistenceResult<()> {
+        let records = (changes.first_index..)
+            .zip(&changes.entries)
+            .map(|(index, entry)| serde_json::to_vec(&RaftLogRecord { index, entry }))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(io::Error::from)?;
+        match &changes.snapshot {
+            Some(snapshot) => {
+                write_encrypted_json(
+                    &self.data_dir.join(RAFT_SNAPSHOT_FILE_NAME),
+                    snapshot,
+                    &self.encryption,
+                )?;
+                self.snapshot_index = snapshot.index;
+                self.log.replace(records)?;
+            }
+            None if !records.is_empty() => {
+                self.log.append_json(&records)?;
+                self.log.sync()?;
+            }
+            None => {}
+        }
+        if let Some(vote) = changes.vote {
+            write_encrypted_json(
+                &self.data_dir.join(RAFT_VOTE_FILE_NAME),
+                &vote,
+                &self.encryption,
+            )?;
+            self.vote = Some(vote);
+        }
+
+        Ok(())
+    }
+}
+
+/// JSONファイルを読み込む。
+///
+/// 現在の鍵で暗号化されていない場合は、データディレクトリの暗号化を必須にした後も読み込めるように、
+/// 現在の鍵で暗号化し直す。
+///
+/// # 戻り値
+///
+/// ファイルの内容、ファイルが存在しない場合は`None`
+fn read_json<T>(path: &Path, encryption: &Encryption) -> PersistenceResult<Option<T>>
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    let Some((content, sealing)) = read_file(path, encryption)? else {
+        return Ok(None);
+    };
+    let value =
+        serde_json::from_slice(&content).map_err(|source| PersistenceError::CorruptedFile {
+            path: path.into(),
+            source,
+        })?;
+    if encryption.is_stale(sealing) {
+        write_encrypted_json(path, &value, encryption)?;
+    }
+
+    Ok(Some(value))
+}
+
+/// 永続化エラー
+#[derive(Debug, thiserror::Error)]
+pub enum PersistenceError {
+    #[error("データファイルの入出力に失敗しました: {0}")]
+    Io(#[from] io::Error),
+    #[error("ジャーナルの{line}行目が壊れています: {source}")]
+    Corrupted {
+        line: usize,
+        source: serde_json::Error,
+    },
+    #[error("スナップショットが壊れています: {0}")]
+    CorruptedSnapshot(#[source] serde_json::Error),
+    #[error("Webhookの購読ファイルが壊れています: {0}")]
+    CorruptedWebhooks(#[source] serde_json::Error),
+    #[error("Webhookのデッドレターのファイルが壊れています: {0}")]
+    CorruptedDeadLetters(#[source] serde_json::Error),
+    #[error("保存したクエリのファイルが壊れています: {0}")]
+    CorruptedSavedQueries(#[source] serde_json::Error),
+    #[error("{}にプロジェクトの情報がありません。", .0.display())]
+    MissingProject(PathBuf),
+    #[error("イベントログを再生できません: {0}")]
+    Events(#[from] EventError),
+    #[error("{}が壊れています: {source}", path.display())]
+    CorruptedFile {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("データディレクトリの形式バージョン{0}には対応していません。")]
+    UnsupportedFormat(u32),
+    #[error(
+        "データディレクトリの形式バージョン{found}は古いため、`ticket-store migrate`で形式バージョン{current}に移行してください。"
+    )]
+    OutdatedFormat { found: u32, current: u32 },
+    #[error("データディレクトリの移行が中断されています。`ticket-store migrate`で移行をやり直してください。")]
+    MigrationInterrupted,
+    #[error("データディレクトリを移行できません: {0}")]
+    Unmigratable(String),
+    #[error("{}の暗号化の認証に失敗しました。改ざんされているか、壊れています。", .0.display())]
+    Tampered(PathBuf),
+    #[error("{}を復号できません: {source}", path.display())]
+    Undecryptable { path: PathBuf, source: DecryptError },
+    #[error("{}は暗号化が必須ですが、暗号化の鍵が設定されていません。", .0.display())]
+    EncryptionRequired(PathBuf),
+    #[error("Raftのログに、エントリ{0}の前のエントリがありません。")]
+    MissingRaftEntry(u64),
+}
+
+/// 永続化結果
+pub type PersistenceResult<T> = Result<T, PersistenceError>;
@@ -0,0 +1,811 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+
+use chrono::NaiveDate;
+
+use crate::models::{Label, Priority, Project, ProjectKey, Ticket, TicketId, TicketStatus};
+use crate::search::{self, SearchField, SearchIndex, SearchText};
+
+/// クエリの最大文字数
+const QUERY_MAX_CHARS: usize = 1000;
+
+/// 括弧と`NOT`の入れ子の最大の深さ
+const QUERY_MAX_DEPTH: usize = 32;
+
+/// 日付の書式
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// クエリ中の範囲
+///
+/// 位置は0から数えた文字単位で、`end`の文字は含まない。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// 比較演算子
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    /// `:`または`=`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+}
+
+impl Comparison {
+    /// チケットの値と、クエリで指定された値を比較する。
+    fn holds<T: Ord>(self, actual: &T, expected: &T) -> bool {
+        match self {
+            Self::Eq => actual == expected,
+            Self::Ne => actual != expected,
+            Self::Lt => actual < expected,
+            Self::Le => actual <= expected,
+            Self::Gt => actual > expected,
+            Self::Ge => actual >= expected,
+        }
+    }
+
+    /// 等しいかどうかだけを比較するか確認する。
+    fn is_equality(self) -> bool {
+        matches!(self, Self::Eq | Self::Ne)
+    }
+}
+
+/// チケットに対する条件
+#[derive(Debug, Clone)]
+pub enum Predicate {
+    Id(Comparison, TicketId),
+    Version(Comparison, u64),
+    /// `Eq`または`Ne`
+    Status(Comparison, TicketStatus),
+    Priority(Comparison, Priority),
+    /// `Eq`または`Ne`で、変更前のプロジェクトキーにも一致する。
+    Project(Comparison, ProjectKey),
+    /// `Eq`または`Ne`で、`None`は親チケットがないことを表す。
+    Parent(Comparison, Option<TicketId>),
+    /// `None`は期限がないことを表し、`Eq`または`Ne`でのみ比較する。
+    Due(Comparison, Option<NaiveDate>),
+    /// 作成日（UTC）
+    Created(Comparison, NaiveDate),
+    /// 更新日（UTC）
+    Updated(Comparison, NaiveDate),
+    /// `Eq`はラベルがあること、`Ne`はラベルがないことを表す。
+    Label(Comparison, Label),
+    /// 検索対象に検索語がすべて出現する。
+    Text(SearchField, SearchText),
+}
+
+impl Predicate {
+    /// チケットが条件を満たすか確認する。
+    fn matches(&self, project: &Project, ticket: &Ticket) -> bool {
+        match self {
+            Self::Id(comparison, id) => comparison.holds(&ticket.id, id),
+            Self::Version(comparison, version) => comparison.holds(&ticket.version, version),
+            Self::Status(comparison, status) => {
+                (ticket.status == *status) == (*comparison == Comparison::Eq)
+            }
+            Self::Priority(comparison, priority) => comparison.holds(&ticket.priority, priority),
+            Self::Project(comparison, key) => {
+                project.is_known_as(key) == (*comparison == Comparison::Eq)
+            }
+            Self::Parent(comparison, parent) => comparison.holds(&ticket.parent, parent),
+            Self::Due(comparison, due) => match (ticket.due_date, due) {
+                (Some(_), Some(_)) => comparison.holds(&ticket.due_date, due),
+                _ => comparison.is_equality() && comparison.holds(&ticket.due_date, due),
+            },
+            Self::Created(comparison, date) => {
+                comparison.holds(&ticket.created_at.date_naive(), date)
+            }
+            Self::Updated(comparison, date) => {
+                comparison.holds(&ticket.updated_at.date_naive(), date)
+            }
+            Self::Label(comparison, label) => {
+                ticket.labels.contains(label) == (*comparison == Comparison::Eq)
+            }
+            Self::Text(field, search) => search::matches(ticket, search, *field),
+        }
+    }
+
+    /// 索引を使用して、条件を満たす可能性があるチケットを返す。
+    ///
+    /// 索引を使用できない条件の場合は`None`を返す。
+    fn candidates(
+        &self,
+        project: &Project,
+        tickets: &BTreeMap<TicketId, Ticket>,
+        index: &SearchIndex,
+    ) -> Option<BTreeSet<TicketId>> {
+        match self {
+            Self::Text(field, search) => Some(index.candidates(search, *field)),
+            Self::Id(comparison, id) => {
+                let range = match comparison {
+                    Comparison::Eq => (Bound::Included(*id), Bound::Included(*id)),
+                    Comparison::Lt => (Bound::Unbounded, Bound::Excluded(*id)),
+                    Comparison::Le => (Bound::Unbounded, Bound::Included(*id)),
+                    Comparison::Gt => (Bound::Excluded(*id), Bound::Unbounded),
+                    Comparison::Ge => (Bound::Included(*id), Bound::Unbounded),
+                    Comparison::Ne => return None,
+                };
+                Some(tickets.range(range).map(|(id, _)| *id).collect())
+            }
+            Self::Project(Comparison::Eq, key) if !project.is_known_as(key) => {
+                Some(BTreeSet::new())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// クエリの構文木
+#[derive(Debug, Clone)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Predicate(Predicate),
+}
+
+impl Expr {
+    /// チケットが条件を満たすか確認する。
+    ///
+    /// # 引数
+    ///
+    /// * `project` - チケットのプロジェクト
+    /// * `ticket` - チケット
+    ///
+    /// # 戻り値
+    ///
+    /// 条件を満たす場合は`true`
+    pub fn matches(&self, project: &Project, ticket: &Ticket) -> bool {
+        match self {
+            Self::And(left, right) => {
+                left.matches(project, ticket) && right.matches(project, ticket)
+            }
+            Self::Or(left, right) => {
+                left.matches(project, ticket) || right.matches(project, ticket)
+            }
+            Self::Not(expr) => !expr.matches(project, ticket),
+            Self::Predicate(predicate) => predicate.matches(project, ticket),
+        }
+    }
+
+    /// 索引を使用して、条件を満たす可能性があるチケットを返す。
+    ///
+    /// `AND`はいずれかの条件で索引を使用できればよく、`OR`は両方の条件で索引を使用できなければならない。
+    fn candidates(
+        &self,
+        project: &Project,
+        tickets: &BTreeMap<TicketId, Ticket>,
+        index: &SearchIndex,
+    ) -> Option<BTreeSet<TicketId>> {
+        match self {
+            Self::And(left, right) => match (
+                left.candidates(project, tickets, index),
+                right.candidates(project, tickets, index),
+            ) {
+                (Some(left), Some(right)) => Some(&left & &right),
+                (Some(candidates), None) | (None, Some(candidates)) => Some(candidates),
+                (None, None) => None,
+            },
+            Self::Or(left, right) => {
+                let left = left.candidates(project, tickets, index)?;
+                let right = right.candidates(project, tickets, index)?;
+                Some(&left | &right)
+            }
+            Self::Not(_) => None,
+            Self::Predicate(predicate) => predicate.candidates(project, tickets, index),
+        }
+    }
+}
+
+/// プロジェクトのチケットから、条件を満たすチケットをチケットID順に取得する。
+///
+/// 索引を使用できる条件があれば、索引で絞り込んだチケットだけを評価する。
+///
+/// # 引数
+///
+/// * `expr` - クエリの構文木
+/// * `project` - プロジェクト
+/// * `tickets` - プロジェクトのチケット
+/// * `index` - プロジェクトのチケットの全文検索の索引
+///
+/// # 戻り値
+///
+/// 条件を満たすチケットの参照
+pub fn evaluate<'a>(
+    expr: &Expr,
+    project: &Project,
+    tickets: &'a BTreeMap<TicketId, Ticket>,
+    index: &SearchIndex,
+) -> Vec<&'a Ticket> {
+    let matches = |ticket: &&Ticket| expr.matches(project, ticket);
+    match expr.candidates(project, tickets, index) {
+        Some(candidates) => candidates
+            .iter()
+            .filter_map(|id| tickets.get(id))
+            .filter(matches)
+            .collect(),
+        None => tickets.values().filter(matches).collect(),
+    }
+}
+
+/// クエリエラーの種類
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum QueryErrorKind {
+    #[error("クエリが空です。")]
+    Empty,
+    #[error("クエリは1000文字以下です。")]
+    TooLong,
+    #[error("文字列が`\"`で閉じられていません。")]
+    UnterminatedString,
+    #[error("`{0}`は使用できない文字です。")]
+    UnexpectedChar(char),
+    #[error("{expected}が必要ですが、`{found}`があります。")]
+    Unexpected {
+        expected: &'static str,
+        found: String,
+    },
+    #[error("{0}が必要ですが、クエリが終わっています。")]
+    UnexpectedEnd(&'static str),
+    #[error("括弧や`NOT`の入れ子が深すぎます。")]
+    TooDeep,
+    #[error(
+        "`{0}`は不明なフィールドです。使用できるフィールドは、`id`、`version`、`status`、`priority`、`project`、`parent`、`due`、`created`、`updated`、`label`、`title`、`description`、`text`です。"
+    )]
+    UnknownField(String),
+    #[error("`{field}`には演算子`{operator}`を使用できません。")]
+    UnsupportedOperator {
+        field: &'static str,
+        operator: &'static str,
+    },
+    #[error("`{field}`の値が不正です: {message}")]
+    InvalidValue {
+        field: &'static str,
+        message: String,
+    },
+}
+
+/// クエリエラー
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{}文字目: {kind}", .span.start + 1)]
+pub struct QueryError {
+    pub kind: QueryErrorKind,
+    /// エラーの原因となったクエリ中の範囲
+    pub span: Span,
+}
+
+/// クエリ結果
+pub type QueryResult<T> = Result<T, QueryError>;
+
+fn error<T>(kind: QueryErrorKind, start: usize, end: usize) -> QueryResult<T> {
+    Err(QueryError {
+        kind,
+        span: Span { start, end },
+    })
+}
+
+/// 演算子
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Colon,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Tilde,
+}
+
+impl Operator {
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Colon => ":",
+            Self::Equal => "=",
+            Self::NotEqual => "!=",
+            Self::Less => "<",
+            Self::LessEqual => "<=",
+            Self::Greater => ">",
+            Self::GreaterEqual => ">=",
+            Self::Tilde => "~",
+        }
+    }
+
+    /// 比較演算子に変換する。
+    fn comparison(self) -> Option<Comparison> {
+        match self {
+            Self::Colon | Self::Equal => Some(Comparison::Eq),
+            Self::NotEqual => Some(Comparison::Ne),
+            Self::Less => Some(Comparison::Lt),
+            Self::LessEqual => Some(Comparison::Le),
+            Self::Greater => Some(Comparison::Gt),
+            Self::GreaterEqual => Some(Comparison::Ge),
+            Self::Tilde => None,
+        }
+    }
+}
+
+/// 字句の種類
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Operator(Operator),
+    Word(String),
+    /// `"`で囲まれた文字列
+    Quoted(String),
+}
+
+/// 字句
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    span: Span,
+}
+
+impl Token {
+    /// エラーメッセージに表示する字句を返す。
+    fn describe(&self) -> String {
+        match &self.kind {
+            TokenKind::LParen => "(".into(),
+            TokenKind::RParen => ")".into(),
+            TokenKind::And => "AND".into(),
+            TokenKind::Or => "OR".into(),
+            TokenKind::Not => "NOT".into(),
+            TokenKind::Operator(operator) => operator.symbol().into(),
+            TokenKind::Word(word) => word.clone(),
+            TokenKind::Quoted(text) => format!("\"{text}\""),
+        }
+    }
+}
+
+/// 単語に含められない文字か確認する。
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | '"' | ':' | '~' | '=' | '!' | '<' | '>')
+}
+
+/// クエリを字句に分割する。
+fn lex(query: &str) -> QueryResult<Vec<Token>> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let c = chars[i];
+        i += 1;
+        let next = chars.get(i).copied();
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            ':' => TokenKind::Operator(Operator::Colon),
+            '~' => TokenKind::Operator(Operator::Tilde),
+            '=' => TokenKind::Operator(Operator::Equal),
+            '!' if next == Some('=') => {
+                i += 1;
+                TokenKind::Operator(Operator::NotEqual)
+            }
+            '<' if next == Some('=') => {
+                i += 1;
+                TokenKind::Operator(Operator::LessEqual)
+            }
+            '<' => TokenKind::Operator(Operator::Less),
+            '>' if next == Some('=') => {
+                i += 1;
+                TokenKind::Operator(Operator::GreaterEqual)
+            }
+            '>' => TokenKind::Operator(Operator::Greater),
+            '!' => return error(QueryErrorKind::UnexpectedChar(c), start, i),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.get(i) {
+                        None => {
+                            return error(QueryErrorKind::UnterminatedString, start, chars.len())
+                        }
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.get(i + 1), Some('"' | '\\')) => {
+                            text.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(&c) => {
+                            text.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                TokenKind::Quoted(text)
+            }
+            _ => {
+                while i < chars.len() && !is_delimiter(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                match word.as_str() {
+                    "AND" => TokenKind::And,
+                    "OR" => TokenKind::Or,
+                    "NOT" => TokenKind::Not,
+                    _ => TokenKind::Word(word),
+                }
+            }
+        };
+        tokens.push(Token {
+            kind,
+            span: Span { start, end: i },
+        });
+    }
+
+    Ok(tokens)
+}
+
+/// クエリのフィールド
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Id,
+    Version,
+    Status,
+    Priority,
+    Project,
+    Parent,
+    Due,
+    Created,
+    Updated,
+    Label,
+    Title,
+    Description,
+    Text,
+}
+
+impl Field {
+    /// フィールド名からフィールドを返す。
+    ///
+    /// フィールド名の英字の大文字と小文字は区別しない。
+    fn from_name(name: &str) -> Option<Self> {
+        let field = match name.to_ascii_lowercase().as_str() {
+            "id" => Self::Id,
+            "version" => Self::Version,
+            "status" => Self::Status,
+            "priority" => Self::Priority,
+            "project" => Self::Project,
+            "parent" => Self::Parent,
+            "due" | "duedate" => Self::Due,
+            "created" | "createdat" => Self::Created,
+            "updated" | "updatedat" => Self::Updated,
+            "label" | "labels" => Self::Label,
+            "title" => Self::Title,
+            "description" => Self::Description,
+            "text" => Self::Text,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Version => "version",
+            Self::Status => "status",
+            Self::Priority => "priority",
+            Self::Project => "project",
+            Self::Parent => "parent",
+            Self::Due => "due",
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Label => "label",
+            Self::Title => "title",
+            Self::Description => "description",
+            Self::Text => "text",
+        }
+    }
+
+    /// 演算子を使用できるか確認する。
+    fn accepts(self, operator: Operator) -> bool {
+        match self {
+            Self::Status | Self::Project | Self::Parent | Self::Label => matches!(
+                operator,
+                Operator::Colon | Operator::Equal | Operator::NotEqual
+            ),
+            Self::Title | Self::Description | Self::Text => {
+                matches!(operator, Operator::Colon | Operator::Tilde)
+            }
+            _ => operator != Operator::Tilde,
+        }
+    }
+}
+
+/// `none`を`None`として、値を解析する。
+fn optional<T, E>(value: &str, parse: impl Fn(&str) -> Result<T, E>) -> Result<Option<T>, E> {
+    match value.eq_ignore_ascii_case("none") {
+        true => Ok(None),
+        false => parse(value).map(Some),
+    }
+}
+
+fn parse_number(value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| "0以上の整数を指定してください。".to_string())
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, DATE_FORMAT)
+        .map_err(|_| "日付は`2024-07-16`の形式で指定してください。".to_string())
+}
+
+/// フィールドと演算子と値から条件を構築する。
+fn predicate(field: Field, operator: Operator, value: &str) -> Result<Predicate, String> {
+    let comparison = operator.comparison();
+    let predicate = match (field, comparison) {
+        (Field::Title | Field::Description | Field::Text, _) => {
+            let field = match field {
+                Field::Title => SearchField::Title,
+                Field::Description => SearchField::Description,
+                _ => SearchField::Any,
+            };
+            let search = SearchText::try_from(value).map_err(|e| e.to_string())?;
+            Predicate::Text(field, search)
+        }
+        (_, None) => unreachable!("`~`は検索対象のフィールドにのみ使用できる"),
+        (Field::Id, Some(comparison)) => Predicate::Id(comparison, TicketId(parse_number(value)?)),
+        (Field::Version, Some(comparison)) => Predicate::Version(comparison, parse_number(value)?),
+        (Field::Status, Some(comparison)) => Predicate::Status(
+            comparison,
+            TicketStatus::try_from(value).map_err(|e| e.to_string())?,
+        ),
+        (Field::Priority, Some(comparison)) => Predicate::Priority(
+            comparison,
+            Priority::try_from(value).map_err(|e| e.to_string())?,
+        ),
+        (Field::Project, Some(comparison)) => Predicate::Project(
+            comparison,
+            ProjectKey::try_from(value).map_err(|e| e.to_string())?,
+        ),
+        (Field::Parent, Some(comparison)) => Predicate::Parent(
+            comparison,
+            optional(value, |value| parse_number(value).map(TicketId))?,
+        ),
+        (Field::Due, Some(comparison)) => {
+            let due = optional(value, parse_date)?;
+            if due.is_none() && !comparison.is_equality() {
+                return Err("`none`は`:`、`=`または`!=`でのみ比較できます。".into());
+            }
+            Predicate::Due(comparison, due)
+        }
+        (Field::Created, Some(comparison)) => Predicate::Created(comparison, parse_date(value)?),
+        (Field::Updated, Some(comparison)) => Predicate::Updated(comparison, parse_date(value)?),
+        (Field::Label, Some(comparison)) => Predicate::Label(
+            comparison,
+            Label::try_from(value).map_err(|e| e.to_string())?,
+        ),
+    };
+
+    Ok(predicate)
+}
+
+/// 再帰下降構文解析器
+///
+/// ```text
+/// query      = or
+/// or         = and { "OR" and }
+/// and        = not { "AND" not }
+/// not        = "NOT" not | primary
+/// primary    = "(" or ")" | comparison
+/// comparison = field operator value
+/// operator   = ":" | "=" | "!=" | "<" | "<=" | ">" | ">=" | "~"
+/// value      = word | quoted
+/// ```
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// クエリの文字数
+    len: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self, expected: &'static str) -> QueryResult<Token> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => error(QueryErrorKind::UnexpectedEnd(expected), self.len, self.len),
+        }
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        let found = self.peek().is_some_and(|token| token.kind == *kind);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> QueryResult<Expr> {
+        let mut expr = self.and()?;
+        while self.eat(&TokenKind::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> QueryResult<Expr> {
+        let mut expr = self.not()?;
+        while self.eat(&TokenKind::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> QueryResult<Expr> {
+        let Some(token) = self.peek().cloned() else {
+            return self.primary();
+        };
+        if token.kind != TokenKind::Not {
+            return self.primary();
+        }
+        self.position += 1;
+        self.nested(token.span, |parser| Ok(Expr::Not(Box::new(parser.not()?))))
+    }
+
+    /// 入れ子の深さを確認して、入れ子の構文を解析する。
+    fn nested(
+        &mut self,
+        span: Span,
+        parse: impl FnOnce(&mut Self) -> QueryResult<Expr>,
+    ) -> QueryResult<Expr> {
+        if QUERY_MAX_DEPTH <= self.depth {
+            return error(QueryErrorKind::TooDeep, span.start, span.end);
+        }
+        self.depth += 1;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn primary(&mut self) -> QueryResult<Expr> {
+        const EXPECTED: &str = "条件または`(`";
+        let token = self.next(EXPECTED)?;
+        match token.kind {
+            TokenKind::LParen => self.nested(token.span, |parser| {
+                let expr = parser.or()?;
+                let close = parser.next("`)`")?;
+                if close.kind != TokenKind::RParen {
+                    return error(
+                        QueryErrorKind::Unexpected {
+                            expected: "`)`",
+                            found: close.describe(),
+                        },
+                        close.span.start,
+                        close.span.end,
+                    );
+                }
+                Ok(expr)
+            }),
+            TokenKind::Word(name) => self.comparison(&name, token.span),
+            _ => error(
+                QueryErrorKind::Unexpected {
+                    expected: EXPECTED,
+                    found: token.describe(),
+                },
+                token.span.start,
+                token.span.end,
+            ),
+        }
+    }
+
+    fn comparison(&mut self, name: &str, name_span: Span) -> QueryResult<Expr> {
+        let Some(field) = Field::from_name(name) else {
+            return error(
+                QueryErrorKind::UnknownField(name.into()),
+                name_span.start,
+                name_span.end,
+            );
+        };
+
+        let token = self.next("演算子")?;
+        let TokenKind::Operator(operator) = token.kind else {
+            return error(
+                QueryErrorKind::Unexpected {
+                    expected: "演算子",
+                    found: token.describe(),
+                },
+                token.span.start,
+                token.span.end,
+            );
+        };
+        if !field.accepts(operator) {
+            return error(
+                QueryErrorKind::UnsupportedOperator {
+                    field: field.name(),
+                    operator: operator.symbol(),
+                },
+                token.span.start,
+                token.span.end,
+            );
+        }
+
+        let token = self.next("値")?;
+        let (TokenKind::Word(value) | TokenKind::Quoted(value)) = &token.kind else {
+            return error(
+                QueryErrorKind::Unexpected {
+                    expected: "値",
+                    found: token.describe(),
+                },
+                token.span.start,
+                token.span.end,
+            );
+        };
+        match predicate(field, operator, value) {
+            Ok(predicate) => Ok(Expr::Predicate(predicate)),
+            Err(message) => error(
+                QueryErrorKind::InvalidValue {
+                    field: field.name(),
+                    message,
+                },
+                token.span.start,
+                token.span.end,
+            ),
+        }
+    }
+}
+
+/// クエリを解析して、構文木を構築する。
+///
+/// `AND`は`OR`より優先され、`NOT`、`AND`、`OR`は英大文字で記述する。
+/// 空白や演算子を含む値は`"`で囲み、`"`と`\`は`\`でエスケープする。
+///
+/// # 引数
+///
+/// * `query` - クエリ（例: `status:InProgress AND (title~"login" OR priority>=High)`）
+///
+/// # 戻り値
+///
+/// クエリの構文木
+pub fn parse(query: &str) -> QueryResult<Expr> {
+    let len = query.chars().count();
+    if QUERY_MAX_CHARS < len {
+        return error(QueryErrorKind::TooLong, QUERY_MAX_CHARS, len);
+    }
+    let tokens = lex(query)?;
+    if tokens.is_empty() {
+        return error(QueryErrorKind::Empty, 0, len);
+    }
+
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        len,
+        depth: 0,
+    };
+    let expr = parser.or()?;
+    if let Some(token) = parser.peek() {
+        return error(
+            QueryErrorKind::Unexpected {
+                expected: "`AND`または`OR`",
+                found: token.describe(),
+            },
+            token.span.start,
+            token.span.end,
+        );
+    }
+
+    Ok(expr)
+}
@@ -0,0 +1,1131 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// ノードID
+pub type NodeId = u64;
+
+/// リーダーが1つのメッセージで送信するエントリの最大数
+const MAX_ENTRIES_PER_MESSAGE: usize = 64;
+
+/// Raftの設定
+///
+/// 時間はすべてティック数で表し、[`Raft::tick`]を呼び出す間隔が1ティックになる。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RaftConfig {
+    /// 選挙タイムアウトの最小ティック数（実際のタイムアウトは、この値から2倍未満の間で無作為に選ぶ）
+    pub election_ticks: u32,
+    /// リーダーがハートビートを送信する間隔のティック数
+    pub heartbeat_ticks: u32,
+    /// 状態機械がスナップショットを作成して、ログを切り詰めるまでに適用するエントリの数
+    pub snapshot_entries: u64,
+}
+
+impl Default for RaftConfig {
+    fn default() -> Self {
+        Self {
+            election_ticks: 10,
+            heartbeat_ticks: 3,
+            snapshot_entries: 1024,
+        }
+    }
+}
+
+/// ノードの役割
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+/// ログのエントリの内容
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Payload<C> {
+    /// リーダーが自身の項のエントリをコミットするために追加する、何もしないエントリ
+    Noop,
+    /// 状態機械に適用するコマンド
+    Command(C),
+    /// 追加した時点から有効になるメンバーの一覧
+    Membership(BTreeSet<NodeId>),
+}
+
+/// ログのエントリ
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Entry<C> {
+    /// エントリを追加したリーダーの項
+    pub term: u64,
+    pub payload: Payload<C>,
+}
+
+/// ログの先頭のエントリを置き換える、状態機械のスナップショット
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot<S> {
+    /// スナップショットに含まれる最後のエントリの位置
+    pub index: u64,
+    /// スナップショットに含まれる最後のエントリの項
+    pub term: u64,
+    /// スナップショットに含まれる最後のメンバーの一覧
+    pub members: BTreeSet<NodeId>,
+    /// 最後のエントリまで適用した状態機械の状態
+    pub data: S,
+}
+
+/// ノード間のメッセージ
+///
+/// リーダーは`read`に読み込みの確認の通し番号を設定し、フォロワーは応答でそのまま返す。
+/// フォロワーは`InstallSnapshot`に、スナップショットの位置までログが一致した`AppendResult`で応答する。
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all_fields = "camelCase")]
+pub enum Message<C, S> {
+    RequestVote {
+        term: u64,
+        last_log_index: u64,
+        last_log_term: u64,
+    },
+    Vote {
+        term: u64,
+        granted: bool,
+    },
+    AppendEntries {
+        term: u64,
+        prev_log_index: u64,
+        prev_log_term: u64,
+        entries: Vec<Entry<C>>,
+        leader_commit: u64,
+        read: u64,
+    },
+    AppendResult {
+        term: u64,
+        success: bool,
+        /// 成功した場合はフォロワーのログがリーダーと一致する最後の位置、
+        /// 失敗した場合は次に試す位置の手がかり
+        match_index: u64,
+        read: u64,
+    },
+    InstallSnapshot {
+        term: u64,
+        snapshot: Snapshot<S>,
+    },
+}
+
+impl<C, S> Message<C, S> {
+    /// 送信元の項を返す。
+    pub fn term(&self) -> u64 {
+        match self {
+            Self::RequestVote { term, .. }
+            | Self::Vote { term, .. }
+            | Self::AppendEntries { term, .. }
+            | Self::AppendResult { term, .. }
+            | Self::InstallSnapshot { term, .. } => *term,
+        }
+    }
+}
+
+/// 送信元と送信先を持つメッセージ
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Envelope<C, S> {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub message: Message<C, S>,
+}
+
+/// ノードが停止しても失ってはならない状態
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HardState<C, S> {
+    pub term: u64,
+    pub voted_for: Option<NodeId>,
+    /// ログの先頭を置き換えたスナップショット
+    pub snapshot: Snapshot<S>,
+    /// スナップショットより後のエントリ
+    pub log: Vec<Entry<C>>,
+}
+
+/// 線形化可能な読み込みの確認結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadState {
+    /// [`Raft::read_index`]が返した読み込みID
+    pub id: u64,
+    /// 状態機械がこの位置まで適用すれば読み込める位置
+    pub result: Result<u64, RaftError>,
+}
+
+/// リーダーが把握している、フォロワーのログの複製状況
+#[derive(Debug, Clone, Copy)]
+struct Progress {
+    /// 次に送信するエントリの位置
+    next: u64,
+    /// リーダーのログと一致することがわかっている最後の位置
+    matched: u64,
+    /// フォロワーが応答した、読み込みの確認の最大の通し番号
+    read: u64,
+    /// 直近の確認期間にフォロワーが応答したか
+    active: bool,
+}
+
+/// Raftの合意アルゴリズムを実行する1つのノード
+///
+/// 入出力を持たない状態機械で、呼び出し側が[`Raft::tick`]で時間を進め、[`Raft::step`]で受信したメッセージを渡す。
+/// 送信するメッセージは[`Raft::take_messages`]、コミットしたエントリは[`Raft::take_committed`]で取り出す。
+/// 同じ入力を同じ順番で与えれば、常に同じ出力になる。
+///
+/// メンバーの変更は1ノードずつ行い、変更はログに追加した時点から有効になる。
+/// リーダーは選挙タイムアウトの間に過半数のメンバーから応答がない場合は、フォロワーに戻る。
+/// リーダーからの連絡が途絶えていないノードは投票要求に応じないため、メンバーから外れたノードが選挙を妨げることはない。
+///
+/// 状態機械は、適用したエントリまでのスナップショットを[`Raft::compact`]で渡して、ログを切り詰める。
+/// リーダーは、切り詰めたエントリを必要とするフォロワーにはスナップショットを送信する。
+#[derive(Debug)]
+pub struct Raft<C, S> {
+    id: NodeId,
+    config: RaftConfig,
+    term: u64,
+    voted_for: Option<NodeId>,
+    /// ログの先頭を置き換えたスナップショット
+    snapshot: Snapshot<S>,
+    /// スナップショットより後のエントリ
+    log: Vec<Entry<C>>,
+    /// 永続化したことを[`Raft::stabilize`]で知らされた、最後のエントリの位置
+    stable: u64,
+    /// リーダーから受け取り、まだ[`Raft::take_snapshot`]で取り出していないスナップショットがあるか
+    snapshot_pending: bool,
+    role: Role,
+    leader: Option<NodeId>,
+    /// コミットした最後のエントリの位置
+    commit: u64,
+    /// [`Raft::take_committed`]で取り出した最後のエントリの位置
+    applied: u64,
+    /// ログの最後のメンバーの一覧
+    members: BTreeSet<NodeId>,
+    /// 最後にリーダーから連絡があるか、選挙を始めてからのティック数
+    elapsed: u32,
+    election_timeout: u32,
+    /// リーダーが最後に過半数の応答を確認してからのティック数
+    quorum_elapsed: u32,
+    rng: u64,
+    votes: BTreeSet<NodeId>,
+    progress: BTreeMap<NodeId, Progress>,
+    /// 最後に割り当てた読み込みの確認の通し番号
+    read_seq: u64,
+    pending_reads: Vec<u64>,
+    reads: Vec<ReadState>,
+    outbox: Vec<Envelope<C, S>>,
+}
+
+impl<C: Clone, S: Clone + Default> Raft<C, S> {
+    /// ノードを構築する。
+    ///
+    /// クラスタを構築するときは、すべてのノードに同じメンバーの一覧を渡す。
+    /// メンバーの一覧はログの最初のエントリになり、最初からコミットされている。
+    /// 既存のクラスタに追加するノードには空のメンバーの一覧を渡し、リーダーからログを受け取るまで選挙を始めない。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - ノードID
+    /// * `members` - 最初のメンバーの一覧
+    /// * `config` - Raftの設定
+    /// * `seed` - 選挙タイムアウトを選ぶ乱数の種
+    ///
+    /// # 戻り値
+    ///
+    /// フォロワーのノード
+    pub fn new(
+        id: NodeId,
+        members: impl IntoIterator<Item = NodeId>,
+        config: RaftConfig,
+        seed: u64,
+    ) -> Self {
+        let members: BTreeSet<_> = members.into_iter().collect();
+        let log = if members.is_empty() {
+            vec![]
+        } else {
+            vec![Entry {
+                term: 0,
+                payload: Payload::Membership(members),
+            }]
+        };
+        let hard_state = HardState {
+            term: 0,
+            voted_for: None,
+            snapshot: Snapshot::default(),
+            log,
+        };
+
+        let mut raft = Self::recover(id, hard_state, config, seed);
+        // 最初のメンバーの一覧も永続化する。
+        raft.stable = 0;
+
+        raft
+    }
+
+    /// 停止する前の状態からノードを再構築する。
+    ///
+    /// 状態機械はスナップショットから復元し、スナップショットより後のエントリだけを取り出す。
+    /// コミットした位置は失われるため、リーダーから再び知らされるまでスナップショットより後のエントリを取り出せない。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - ノードID
+    /// * `hard_state` - 停止する前の[`Raft::hard_state`]
+    /// * `config` - Raftの設定
+    /// * `seed` - 選挙タイムアウトを選ぶ乱数の種
+    ///
+    /// # 戻り値
+    ///
+    /// フォロワーのノード
+    pub fn recover(id: NodeId, hard_state: HardState<C, S>, config: RaftConfig, seed: u64) -> Self {
+        // 項0のエントリは、すべてのノードが同じものを持つ最初のメンバーの一覧である。
+        let initial = hard_state
+            .log
+            .iter()
+            .take_while(|entry| entry.term == 0)
+            .count() as u64;
+        let snapshot_index = hard_state.snapshot.index;
+        let last_index = snapshot_index + hard_state.log.len() as u64;
+        let mut raft = Self {
+            id,
+            config,
+            term: hard_state.term,
+            voted_for: hard_state.voted_for,
+            snapshot: hard_state.snapshot,
+            log: hard_state.log,
+            stable: last_index,
+            snapshot_pending: false,
+            role: Role::Follower,
+            leader: None,
+            commit: snapshot_index + initial,
+            applied: snapshot_index,
+            members: BTreeSet::new(),
+            elapsed: 0,
+            election_timeout: config.election_ticks,
+            quorum_elapsed: 0,
+            rng: (seed ^ id.wrapping_mul(0x9E37_79B9_7F4A_7C15)) | 1,
+            votes: BTreeSet::new(),
+            progress: BTreeMap::new(),
+            read_seq: 0,
+            pending_reads: vec![],
+            reads: vec![],
+            outbox: vec![],
+        };
+        raft.refresh_members();
+        raft.reset_election_timer();
+
+        raft
+    }
+
+    /// ノードIDを返す。
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    /// Raftの設定を返す。
+    pub fn config(&self) -> RaftConfig {
+        self.config
+    }
+
+    /// 現在の役割を返す。
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// 現在の項を返す。
+    pub fn term(&self) -> u64 {
+        self.term
+    }
+
+    /// 現在の項のリーダーを返す。わからない場合は`None`
+    pub fn leader(&self) -> Option<NodeId> {
+        self.leader
+    }
+
+    /// コミットした最後のエントリの位置を返す。
+    pub fn commit_index(&self) -> u64 {
+        self.commit
+    }
+
+    /// ログの最後のエントリの位置を返す。
+    pub fn last_index(&self) -> u64 {
+        self.snapshot.index + self.log.len() as u64
+    }
+
+    /// ログの最後のエントリの項を返す。ログが空の場合は`0`
+    pub fn last_term(&self) -> u64 {
+        self.term_at(self.last_index())
+    }
+
+    /// 現在の項で投票したノードを返す。投票していない場合は`None`
+    pub fn voted_for(&self) -> Option<NodeId> {
+        self.voted_for
+    }
+
+    /// ログの最後のメンバーの一覧を返す。
+    pub fn members(&self) -> &BTreeSet<NodeId> {
+        &self.members
+    }
+
+    /// ログの先頭を置き換えたスナップショットを返す。
+    pub fn snapshot(&self) -> &Snapshot<S> {
+        &self.snapshot
+    }
+
+    /// スナップショットより後のエントリを返す。
+    pub fn entries(&self) -> &[Entry<C>] {
+        &self.log
+    }
+
+    /// 停止しても失ってはならない状態を返す。
+    ///
+    /// スナップショットとログ全体をコピーするため、変わったときに永続化する場合は、
+    /// [`Raft::unstable_entries`]で永続化していないエントリだけを取り出す。
+    pub fn hard_state(&self) -> HardState<C, S> {
+        HardState {
+            term: self.term,
+            voted_for: self.voted_for,
+            snapshot: self.snapshot.clone(),
+            log: self.log.clone(),
+        }
+    }
+
+    /// まだ永続化していないエントリを返す。
+    ///
+    /// 他のリーダーのエントリで置き換えられた位置からのエントリを含む。
+    ///
+    /// # 戻り値
+    ///
+    /// 最初のエントリの位置と、永続化していないエントリ
+    pub fn unstable_entries(&self) -> (u64, &[Entry<C>]) {
+        let stable = self.stable.max(self.snapshot.index);
+        (
+            stable + 1,
+            &self.log[(stable - self.snapshot.index) as usize..],
+        )
+    }
+
+    /// 指定した位置までのエントリを永続化したことを知らせる。
+    ///
+    /// # 引数
+    ///
+    /// * `index` - 永続化した最後のエントリの位置
+    pub fn stabilize(&mut self, index: u64) {
+        self.stable = index.min(self.last_index());
+    }
+
+    /// 時間を1ティック進める。
+    ///
+    /// リーダーはハートビートを送信し、それ以外のメンバーは選挙タイムアウトになると選挙を始める。
+    pub fn tick(&mut self) {
+        self.elapsed += 1;
+        if self.role == Role::Leader {
+            self.quorum_elapsed += 1;
+            if self.quorum_elapsed >= self.config.election_ticks {
+                self.quorum_elapsed = 0;
+                if !self.check_quorum() {
+                    tracing::info!(
+                        node = self.id,
+                        term = self.term,
+                        "過半数のメンバーから応答がないため、リーダーを辞めます。"
+                    );
+                    self.become_follower(self.term, None);
+                    return;
+                }
+            }
+            if self.elapsed >= self.config.heartbeat_ticks {
+                self.elapsed = 0;
+                self.broadcast_append();
+            }
+        } else if self.elapsed >= self.election_timeout && self.members.contains(&self.id) {
+            self.campaign();
+        }
+    }
+
+    /// 受信したメッセージを処理する。
+    ///
+    /// # 引数
+    ///
+    /// * `from` - 送信元のノードID
+    /// * `message` - メッセージ
+    pub fn step(&mut self, from: NodeId, message: Message<C, S>) {
+        let term = message.term();
+        if term > self.term {
+            if matches!(message, Message::RequestVote { .. }) && self.in_lease() {
+                return;
+            }
+            let leader = matches!(
+                message,
+                Message::AppendEntries { .. } | Message::InstallSnapshot { .. }
+            )
+            .then_some(from);
+            self.become_follower(term, leader);
+        }
+        if term < self.term {
+            let reply = match message {
+                Message::RequestVote { .. } => Message::Vote {
+                    term: self.term,
+                    granted: false,
+                },
+                Message::AppendEntries { .. } | Message::InstallSnapshot { .. } => {
+                    Message::AppendResult {
+                        term: self.term,
+                        success: false,
+                        match_index: 0,
+                        read: 0,
+                    }
+                }
+                _ => return,
+            };
+            self.send(from, reply);
+            return;
+        }
+
+        match message {
+            Message::RequestVote {
+                last_log_index,
+                last_log_term,
+                ..
+            } => {
+                let last_index = self.last_index();
+                let up_to_date =
+                    (last_log_term, last_log_index) >= (self.term_at(last_index), last_index);
+                let granted = up_to_date && self.voted_for.is_none_or(|voted| voted == from);
+                if granted {
+                    self.voted_for = Some(from);
+                    self.reset_election_timer();
+                }
+                self.send(
+                    from,
+                    Message::Vote {
+                        term: self.term,
+                        granted,
+                    },
+                );
+            }
+            Message::Vote { granted, .. } => {
+                if self.role == Role::Candidate && granted {
+                    self.votes.insert(from);
+                    let votes = self.votes.intersection(&self.members).count();
+                    if self.is_quorum(votes) {
+                        self.become_leader();
+                    }
+                }
+            }
+            Message::AppendEntries {
+                prev_log_index,
+                prev_log_term,
+                entries,
+                leader_commit,
+                read,
+                ..
+            } => self.handle_append(
+                from,
+                prev_log_index,
+                prev_log_term,
+                entries,
+                leader_commit,
+                read,
+            ),
+            Message::AppendResult {
+                success,
+                match_index,
+                read,
+                ..
+            } => self.handle_append_result(from, success, match_index, read),
+            Message::InstallSnapshot { snapshot, .. } => self.handle_snapshot(from, snapshot),
+        }
+    }
+
+    /// コマンドをログに追加して、フォロワーに複製する。
+    ///
+    /// # 引数
+    ///
+    /// * `command` - 状態機械に適用するコマンド
+    ///
+    /// # 戻り値
+    ///
+    /// コマンドを追加した位置
+    pub fn propose(&mut self, command: C) -> Result<u64, RaftError> {
+        self.ensure_leader()?;
+        let index = self.append(Payload::Command(command));
+        self.broadcast_append();
+
+        Ok(index)
+    }
+
+    /// メンバーを1ノード追加または削除する。
+    ///
+    /// 変更はログに追加した時点から有効になり、以前の変更がコミットされるまで次の変更はできない。
+    /// リーダー自身を削除した場合は、変更がコミットされた時点でリーダーを辞める。
+    ///
+    /// # 引数
+    ///
+    /// * `members` - 変更後のメンバーの一覧
+    ///
+    /// # 戻り値
+    ///
+    /// 変更を追加した位置
+    pub fn change_membership(&mut self, members: BTreeSet<NodeId>) -> Result<u64, RaftError> {
+        self.ensure_leader()?;
+        let pending = self.log[(self.commit - self.snapshot.index) as usize..]
+            .iter()
+            .any(|entry| matches!(entry.payload, Payload::Membership(_)));
+        // 自身の項のエントリをコミットする前に変更すると、前の項の変更と食い違うおそれがある。
+        if pending || self.term_at(self.commit) != self.term {
+            return Err(RaftError::MembershipChangePending);
+        }
+        if self.members.symmetric_difference(&members).count() != 1 {
+            return Err(RaftError::InvalidMembershipChange);
+        }
+        let index = self.append(Payload::Membership(members));
+        self.broadcast_append();
+
+        Ok(index)
+    }
+
+    /// 線形化可能な読み込みを要求する。
+    ///
+    /// リーダーは、過半数のメンバーから自身がまだリーダーであることを確認した時点のコミットした位置を、
+    /// [`Raft::take_reads`]で返す。状態機械がその位置まで適用してから読み込めば、
+    /// 要求する前にコミットしたすべての書き込みを観測できる。
+    ///
+    /// # 戻り値
+    ///
+    /// 読み込みID
+    pub fn read_index(&mut self) -> Result<u64, RaftError> {
+        self.ensure_leader()?;
+        self.read_seq += 1;
+        self.pending_reads.push(self.read_seq);
+        self.broadcast_append();
+        self.confirm_reads();
+
+        Ok(self.read_seq)
+    }
+
+    /// 送信するメッセージを取り出す。
+    pub fn take_messages(&mut self) -> Vec<Envelope<C, S>> {
+        std::mem::take(&mut self.outbox)
+    }
+
+    /// コミットしたが、まだ取り出していないエントリを位置とともに取り出す。
+    ///
+    /// リーダーから受け取ったスナップショットは、[`Raft::take_snapshot`]で先に取り出す。
+    pub fn take_committed(&mut self) -> Vec<(u64, Entry<C>)> {
+        let entries = (self.applied + 1..=self.commit)
+            .map(|index| (index, self.entry(index).clone()))
+            .collect();
+        self.applied = self.commit;
+
+        entries
+    }
+
+    /// リーダーから受け取った、状態機械に適用していないスナップショットを取り出す。
+    ///
+    /// 状態機械はスナップショットの状態に置き換えてから、[`Raft::take_committed`]でその後のエントリを適用する。
+    pub fn take_snapshot(&mut self) -> Option<Snapshot<S>> {
+        std::mem::take(&mut self.snapshot_pending).then(|| self.snapshot.clone())
+    }
+
+    /// 状態機械に適用したエントリまでをスナップショットに置き換えて、ログを切り詰める。
+    ///
+    /// # 引数
+    ///
+    /// * `index` - スナップショットに含まれる最後のエントリの位置（[`Raft::take_committed`]で取り出した位置まで）
+    /// * `data` - その位置まで適用した状態機械の状態
+    pub fn compact(&mut self, index: u64, data: S) {
+        if index <= self.snapshot.index || index > self.applied {
+            return;
+        }
+        let term = self.term_at(index);
+        let compacted: Vec<_> = self
+            .log
+            .drain(..(index - self.snapshot.index) as usize)
+            .collect();
+        let members = compacted
+            .into_iter()
+            .rev()
+            .find_map(|entry| match entry.payload {
+                Payload::Membership(members) => Some(members),
+                _ => None,
+            })
+            .unwrap_or_else(|| self.snapshot.members.clone());
+        self.snapshot = Snapshot {
+            index,
+            term,
+            members,
+            data,
+        };
+        self.stable = self.stable.max(index);
+        tracing::debug!(node = self.id, index, "ログを切り詰めました。");
+    }
+
+    /// 確認が終わった読み込みを取り出す。
+    pub fn take_reads(&mut self) -> Vec<ReadState> {
+        std::mem::take(&mut self.reads)
+    }
+
+    /// リーダーからエントリを受け取り、ログに追加する。
+    fn handle_append(
+        &mut self,
+        from: NodeId,
+        prev_log_index: u64,
+        prev_log_term: u64,
+        entries: Vec<Entry<C>>,
+        leader_commit: u64,
+        read: u64,
+    ) {
+        if self.role != Role::Follower {
+            self.become_follower(self.term, Some(from));
+        }
+        self.leader = Some(from);
+        self.reset_election_timer();
+
+        // スナップショットに含まれるエントリはコミットしているため、どのリーダーのログとも一致する。
+        let (prev_log_index, prev_log_term, entries) = if prev_log_index < self.snapshot.index {
+            let skipped = (self.snapshot.index - prev_log_index) as usize;
+            let entries = entries.into_iter().skip(skipped).collect();
+            (self.snapshot.index, self.snapshot.term, entries)
+        } else {
+            (prev_log_index, prev_log_term, entries)
+        };
+        if prev_log_index > self.last_index() || self.term_at(prev_log_index) != prev_log_term {
+            let hint = self.last_index().min(prev_log_index.saturating_sub(1));
+            self.send(
+                from,
+                Message::AppendResult {
+                    term: self.term,
+                    success: false,
+                    match_index: hint,
+                    read,
+                },
+            );
+            return;
+        }
+
+        let last_new = prev_log_index + entries.len() as u64;
+        for (index, entry) in (prev_log_index + 1..).zip(entries) {
+            if index <= self.last_index() {
+                if self.term_at(index) == entry.term {
+                    continue;
+                }
+                // コミットしたエントリは、どのリーダーのログにも含まれるため食い違わない。
+                debug_assert!(index > self.commit, "コミットしたエントリは削除できない");
+                self.log
+                    .truncate((index - self.snapshot.index) as usize - 1);
+                self.stable = self.stable.min(index - 1);
+            }
+            self.log.push(entry);
+        }
+        self.refresh_members();
+        if leader_commit > self.commit {
+            self.commit = leader_commit.min(last_new).max(self.commit);
+        }
+        self.send(
+            from,
+            Message::AppendResult {
+                term: self.term,
+                success: true,
+                match_index: last_new,
+                read,
+            },
+        );
+    }
+
+    /// リーダーからスナップショットを受け取り、ログの先頭を置き換える。
+    ///
+    /// スナップショットの最後のエントリがログにある場合は、その後のエントリを残す。
+    fn handle_snapshot(&mut self, from: NodeId, snapshot: Snapshot<S>) {
+        if self.role != Role::Follower {
+            self.become_follower(self.term, Some(from));
+        }
+        self.leader = Some(from);
+        self.reset_election_timer();
+
+        let index = snapshot.index;
+        if index > self.commit {
+            let retained = index < self.last_index() && self.term_at(index) == snapshot.term;
+            self.log = if retained {
+                self.log.split_off((index - self.snapshot.index) as usize)
+            } else {
+                vec![]
+            };
+            tracing::info!(
+                node = self.id,
+                index,
+                "リーダーのスナップショットを受け取りました。"
+            );
+            self.snapshot = snapshot;
+            self.stable = self.stable.clamp(index, self.last_index());
+            self.commit = index;
+            self.applied = index;
+            self.snapshot_pending = true;
+            self.refresh_members();
+        }
+        self.send(
+            from,
+            Message::AppendResult {
+                term: self.term,
+                success: true,
+                match_index: self.commit.max(index),
+                read: 0,
+            },
+        );
+    }
+
+    /// フォロワーの応答から複製状況を更新して、コミットした位置を進める。
+    fn handle_append_result(&mut self, from: NodeId, success: bool, match_index: u64, read: u64) {
+        if self.role != Role::Leader {
+            return;
+        }
+        let last_index = self.last_index();
+        let Some(progress) = self.progress.get_mut(&from) else {
+            return;
+        };
+        progress.active = true;
+        progress.read = progress.read.max(read);
+        if success {
+            progress.matched = progress.matched.max(match_index);
+            progress.next = progress.matched + 1;
+            let behind = progress.next <= last_index;
+            self.maybe_commit();
+            if behind && self.role == Role::Leader {
+                self.send_append(from);
+            }
+        } else {
+            progress.next = (progress.next - 1).min(match_index + 1).max(1);
+            self.send_append(from);
+        }
+        self.confirm_reads();
+    }
+
+    /// 選挙を始める。
+    fn campaign(&mut self) {
+        self.term += 1;
+        self.role = Role::Candidate;
+        self.leader = None;
+        self.voted_for = Some(self.id);
+        self.votes = BTreeSet::from([self.id]);
+        self.reset_election_timer();
+        tracing::debug!(node = self.id, term = self.term, "選挙を始めます。");
+        if self.is_quorum(1) {
+            self.become_leader();
+            return;
+        }
+
+        let last_log_index = self.last_index();
+        let last_log_term = self.term_at(last_log_index);
+        for peer in self.peers() {
+            self.send(
+                peer,
+                Message::RequestVote {
+                    term: self.term,
+                    last_log_index,
+                    last_log_term,
+                },
+            );
+        }
+    }
+
+    /// リーダーになり、自身の項のエントリを追加する。
+    fn become_leader(&mut self) {
+        tracing::info!(node = self.id, term = self.term, "リーダーになりました。");
+        self.role = Role::Leader;
+        self.leader = Some(self.id);
+        self.elapsed = 0;
+        self.quorum_elapsed = 0;
+        let next = self.last_index() + 1;
+        self.progress = self
+            .peers()
+            .into_iter()
+            .map(|peer| {
+                let progress = Progress {
+                    next,
+                    matched: 0,
+                    read: 0,
+                    active: true,
+                };
+                (peer, progress)
+            })
+            .collect();
+        self.append(Payload::Noop);
+        self.broadcast_append();
+    }
+
+    /// フォロワーになる。
+    ///
+    /// 項が進む場合は、その項ではまだ投票していない。
+    fn become_follower(&mut self, term: u64, leader: Option<NodeId>) {
+        if term > self.term {
+            self.term = term;
+            self.voted_for = None;
+        }
+        self.role = Role::Follower;
+        self.leader = leader;
+        self.progress.clear();
+        self.votes.clear();
+        for id in self.pending_reads.drain(..) {
+            self.reads.push(ReadState {
+                id,
+                result: Err(RaftError::LeadershipLost),
+            });
+        }
+        self.reset_election_timer();
+    }
+
+    /// リーダーのログにエントリを追加する。
+    fn append(&mut self, payload: Payload<C>) -> u64 {
+        let membership = matches!(payload, Payload::Membership(_));
+        self.log.push(Entry {
+            term: self.term,
+            payload,
+        });
+        let index = self.last_index();
+        if membership {
+            self.refresh_members();
+            let peers = self.peers();
+            self.progress.retain(|peer, _| peers.contains(peer));
+            for peer in peers {
+                self.progress.entry(peer).or_insert(Progress {
+                    next: index,
+                    matched: 0,
+                    read: 0,
+                    active: true,
+                });
+            }
+        }
+        self.maybe_commit();
+
+        index
+    }
+
+    /// すべてのフォロワーにエントリまたはハートビートを送信する。
+    fn broadcast_append(&mut self) {
+        let peers: Vec<_> = self.progress.keys().copied().collect();
+        for peer in peers {
+            self.send_append(peer);
+        }
+    }
+
+    /// フォロワーが次に必要とするエントリを送信する。
+    fn send_append(&mut self, peer: NodeId) {
+        let Some(progress) = self.progress.get(&peer) else {
+            return;
+        };
+        let prev_log_index = progress.next - 1;
+        if prev_log_index < self.snapshot.index {
+            let message = Message::InstallSnapshot {
+                term: self.term,
+                snapshot: self.snapshot.clone(),
+            };
+            // スナップショットが届かなかった場合は、次のエントリの送信が失敗して再び送信する。
+            if let Some(progress) = self.progress.get_mut(&peer) {
+                progress.next = self.snapshot.index + 1;
+            }
+            self.send(peer, message);
+            return;
+        }
+        let entries = self.log[(prev_log_index - self.snapshot.index) as usize..]
+            .iter()
+            .take(MAX_ENTRIES_PER_MESSAGE)
+            .cloned()
+            .collect();
+        let message = Message::AppendEntries {
+            term: self.term,
+            prev_log_index,
+            prev_log_term: self.term_at(prev_log_index),
+            entries,
+            leader_commit: self.commit,
+            read: self.read_seq,
+        };
+        self.send(peer, message);
+    }
+
+    /// 過半数のメンバーが複製した、自身の項の最後のエントリまでコミットする。
+    ///
+    /// 前の項のエントリは、自身の項のエントリをコミットしたときに、まとめてコミットされる。
+    fn maybe_commit(&mut self) {
+        let mut committed = false;
+        for index in (self.commit + 1..=self.last_index()).rev() {
+            if self.term_at(index) != self.term {
+                break;
+            }
+            let replicated = self
+                .members
+                .iter()
+                .filter(|member| {
+                    **member == self.id
+                        || self
+                            .progress
+                            .get(member)
+                            .is_some_and(|progress| progress.matched >= index)
+                })
+                .count();
+            if self.is_quorum(replicated) {
+                self.commit = index;
+                committed = true;
+                break;
+            }
+        }
+        if !committed {
+            return;
+        }
+
+        // ログにメンバーの変更がない場合は、スナップショットのメンバーの一覧がコミットした最後の変更である。
+        let membership = self
+            .log
+            .iter()
+            .rposition(|entry| matches!(entry.payload, Payload::Membership(_)));
+        let removed = membership
+            .is_none_or(|position| self.snapshot.index + (position as u64) < self.commit)
+            && !self.members.contains(&self.id);
+        if removed {
+            tracing::info!(
+                node = self.id,
+                "メンバーから外れたため、リーダーを辞めます。"
+            );
+            // 最後のコミットした位置を知らせてから辞める。
+            self.broadcast_append();
+            self.become_follower(self.term, None);
+            return;
+        }
+        self.broadcast_append();
+        self.confirm_reads();
+    }
+
+    /// 過半数のメンバーがリーダーであることを確認した読み込みを確定する。
+    fn confirm_reads(&mut self) {
+        // 自身の項のエントリをコミットするまでは、前のリーダーがコミットした位置を知らない。
+        if self.role != Role::Leader || self.term_at(self.commit) != self.term {
+            return;
+        }
+        let commit = self.commit;
+        let (confirmed, pending) = std::mem::take(&mut self.pending_reads)
+            .into_iter()
+            .partition(|id| {
+                let acknowledged = self
+                    .members
+                    .iter()
+                    .filter(|member| {
+                        **member == self.id
+                            || self
+                                .progress
+                                .get(member)
+                                .is_some_and(|progress| progress.read >= *id)
+                    })
+                    .count();
+                self.is_quorum(acknowledged)
+            });
+        let confirmed: Vec<u64> = confirmed;
+        self.pending_reads = pending;
+        self.reads.extend(confirmed.into_iter().map(|id| ReadState {
+            id,
+            result: Ok(commit),
+        }));
+    }
+
+    /// 直近の確認期間に過半数のメンバーが応答したか確認して、応答の記録を消去する。
+    fn check_quorum(&mut self) -> bool {
+        let active = self
+            .members
+            .iter()
+            .filter(|member| {
+                **member == self.id || self.progress.get(member).is_some_and(|p| p.active)
+            })
+            .count();
+        for progress in self.progress.values_mut() {
+            progress.active = false;
+        }
+
+        self.is_quorum(active)
+    }
+
+    /// リーダーから最近連絡があり、そのリーダーがまだ有効だとみなせるか確認する。
+    fn in_lease(&self) -> bool {
+        self.leader.is_some() && self.elapsed < self.config.election_ticks
+    }
+
+    fn ensure_leader(&self) -> Result<(), RaftError> {
+        if self.role == Role::Leader {
+            Ok(())
+        } else {
+            Err(RaftError::NotLeader {
+                leader: self.leader,
+            })
+        }
+    }
+
+    /// メンバーの過半数か確認する。
+    fn is_quorum(&self, count: usize) -> bool {
+        count * 2 > self.members.len()
+    }
+
+    /// 自身以外のメンバーを返す。
+    fn peers(&self) -> Vec<NodeId> {
+        self.members
+            .iter()
+            .copied()
+            .filter(|member| *member != self.id)
+            .collect()
+    }
+
+    /// 指定した位置のエントリの項を返す。
+    ///
+    /// スナップショットの最後のエントリの位置の場合はスナップショットの項、それより前かログにない場合は0
+    fn term_at(&self, index: u64) -> u64 {
+        match index.checked_sub(self.snapshot.index) {
+            Some(0) => self.snapshot.term,
+            Some(offset) => self
+                .log
+                .get(offset as usize - 1)
+                .map_or(0, |entry| entry.term),
+            None => 0,
+        }
+    }
+
+    /// スナップショットより後の、指定した位置のエントリを返す。
+    fn entry(&self, index: u64) -> &Entry<C> {
+        &self.log[(index - self.snapshot.index) as usize - 1]
+    }
+
+    /// ログの最後のメンバーの一覧を反映する。
+    fn refresh_members(&mut self) {
+        self.members = self
+            .log
+            .iter()
+            .rev()
+            .find_map(|entry| match &entry.payload {
+                Payload::Membership(members) => Some(members.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| self.snapshot.members.clone());
+    }
+
+    /// 選挙タイムアウトを選び直して、経過時間を消去する。
+    fn reset_election_timer(&mut self) {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        let ticks = self.config.election_ticks.max(1);
+        self.elapsed = 0;
+        self.election_timeout = ticks + (self.rng % u64::from(ticks)) as u32;
+    }
+
+    fn send(&mut self, to: NodeId, message: Message<C, S>) {
+        self.outbox.push(Envelope {
+            from: self.id,
+            to,
+            message,
+        });
+    }
+}
+
+/// Raftのエラー
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum RaftError {
+    #[error("このノードはリーダーではありません。")]
+    NotLeader { leader: Option<NodeId> },
+    #[error("コミットしていないメンバーの変更があります。")]
+    MembershipChangePending,
+    #[error("メンバーは1ノードずつ追加または削除します。")]
+    InvalidMembershipChange,
+    #[error("読み込みを確認する前に、リーダーではなくなりました。")]
+    LeadershipLost,
+}
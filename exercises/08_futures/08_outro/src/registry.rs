@@ -0,0 +1,721 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, RwLockWriteGuard};
+
+use chrono::{DateTime, Utc};
+
+use crate::backup::Backup;
+use crate::clock::{Clock, SystemClock};
+use crate::crypto::Encryption;
+use crate::dto::{ProjectDraft, ProjectPatch};
+use crate::events::EventSender;
+use crate::migrate;
+use crate::models::{BlobDigest, Project, ProjectKey, Ticket, TicketId, TicketKey};
+use crate::persistence::{FileStorage, PersistenceError, PersistenceResult, Snapshot};
+use crate::query::Expr;
+use crate::search::{SearchHit, SearchText};
+use crate::store::{Access, TicketStore, TicketStoreError, TicketStoreResult};
+use crate::sync::{read_lock, write_lock};
+
+/// プロジェクトのデータディレクトリを格納するディレクトリ名
+pub(crate) const PROJECTS_DIR_NAME: &str = "projects";
+
+/// 復元するバックアップを書き出す一時ディレクトリ名
+///
+/// データディレクトリと同じ構成で書き出し、開けることを確かめてから`projects`ディレクトリと入れ替える。
+const RESTORING_DIR_NAME: &str = "restoring";
+
+/// 復元で置き換える前の`projects`ディレクトリを、入れ替えの間だけ退避するディレクトリ名
+const REPLACED_DIR_NAME: &str = "projects.replaced";
+
+/// プロジェクトごとのチケットストア
+///
+/// プロジェクトごとにロックを持つため、あるプロジェクトへの書き込みは他のプロジェクトへの書き込みを妨げない。
+pub type ProjectStore = Arc<RwLock<TicketStore>>;
+
+/// プロジェクトキーでプロジェクトを検索した結果
+#[derive(Debug, Clone)]
+pub enum ProjectLookup {
+    /// プロジェクトが見つかった。
+    Found(ProjectStore),
+    /// 変更前のプロジェクトキーで指定されたため、変更後のプロジェクトキーで指定し直す必要がある。
+    Renamed(ProjectKey),
+}
+
+/// プロジェクトの一覧
+///
+/// ファイルストレージを使用する場合、各プロジェクトはデータディレクトリの`projects`ディレクトリに、
+/// 作成したときのプロジェクトキーを名前とするディレクトリを持つ。
+#[derive(Debug)]
+pub struct ProjectRegistry {
+    projects: BTreeMap<ProjectKey, ProjectStore>,
+    /// 変更前のプロジェクトキーから、現在のプロジェクトキーへの対応
+    aliases: BTreeMap<ProjectKey, ProjectKey>,
+    data_dir: Option<PathBuf>,
+    /// 各プロジェクトのファイルストレージの暗号化
+    encryption: Encryption,
+    clock: Arc<dyn Clock>,
+    /// 各プロジェクトのチケットストアに設定する、チケットイベントの送信先
+    events: Option<EventSender>,
+}
+
+impl Default for ProjectRegistry {
+    fn default() -> Self {
+        Self::in_memory(Arc::new(SystemClock))
+    }
+}
+
+impl ProjectRegistry {
+    /// 既定のプロジェクトだけを持ち、チケットを永続化しないプロジェクトの一覧を構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `clock` - チケットストアが使用する時計
+    ///
+    /// # 戻り値
+    ///
+    /// プロジェクトの一覧
+    pub fn in_memory(clock: Arc<dyn Clock>) -> Self {
+        let mut registry = Self {
+            projects: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            data_dir: None,
+            encryption: Encryption::default(),
+            clock,
+            events: None,
+        };
+        let store = TicketStore::new(Project::default()).with_clock(Arc::clone(&registry.clock));
+        registry.insert(store);
+
+        registry
+    }
+
+    /// データディレクトリから、各プロジェクトの永続化されたチケットを復元する。
+    ///
+    /// プロジェクトが1つもない場合は、既定のプロジェクトを作成する。
+    /// データディレクトリが以前の形式バージョンの場合は、`ticket-store migrate`で移行するまで開けない。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - データディレクトリ
+    /// * `clock` - チケットストアが使用する時計
+    ///
+    /// # 戻り値
+    ///
+    /// プロジェクトの一覧
+    pub fn open(data_dir: &Path, clock: Arc<dyn Clock>) -> PersistenceResult<Self> {
+        Self::open_encrypted(data_dir, clock, &Encryption::default())
+    }
+
+    /// 暗号化を指定して、データディレクトリから、各プロジェクトの永続化されたチケットを復元する。
+    ///
+    /// 暗号化されたファイルの認証に失敗した場合は、[`PersistenceError::Tampered`]を返す。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - データディレクトリ
+    /// * `clock` - チケットストアが使用する時計
+    /// * `encryption` - 暗号化
+    ///
+    /// # 戻り値
+    ///
+    /// プロジェクトの一覧
+    pub fn open_encrypted(
+        data_dir: &Path,
+        clock: Arc<dyn Clock>,
+        encryption: &Encryption,
+    ) -> PersistenceResult<Self> {
+        let projects_dir = data_dir.join(PROJECTS_DIR_NAME);
+        let replaced_dir = data_dir.join(REPLACED_DIR_NAME);
+        if replaced_dir.exists() {
+            if projects_dir.exists() {
+                fs::remove_dir_all(&replaced_dir)?;
+            } else {
+                // 復元で`projects`ディレクトリを入れ替える途中で停止したため、置き換える前のプロジェクトに戻す。
+                fs::rename(&replaced_dir, &projects_dir)?;
+                tracing::warn!(
+                    data_dir = %data_dir.display(),
+                    "復元を中断したため、復元する前のプロジェクトに戻しました。"
+                );
+            }
+        }
+        migrate::ensure_current(data_dir)?;
+        fs::create_dir_all(&projects_dir)?;
+        let mut registry = Self {
+            projects: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            data_dir: Some(data_dir.into()),
+            encryption: encryption.clone(),
+            clock,
+            events: None,
+        };
+
+        let mut project_dirs = vec![];
+        for entry in fs::read_dir(&projects_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                project_dirs.push(entry.path());
+            }
+        }
+        project_dirs.sort();
+        for project_dir in project_dirs {
+            let store = TicketStore::open_encrypted(&project_dir, encryption)?
+                .with_clock(Arc::clone(&registry.clock));
+            tracing::info!(
+                project_key = %store.project().key,
+                tickets = store.len(),
+                "プロジェクトを復元しました。"
+            );
+            registry.insert(store);
+        }
+        if registry.projects.is_empty() {
+            let store = registry.create_store(Project::default())?;
+            registry.insert(store);
+        }
+
+        Ok(registry)
+    }
+
+    /// すべてのプロジェクトのチケットストアに、チケットイベントの送信先を設定する。
+    ///
+    /// 後から作成したプロジェクトのチケットストアにも、同じ送信先を設定する。
+    ///
+    /// # 引数
+    ///
+    /// * `events` - チケットイベントの送信先
+    ///
+    /// # 戻り値
+    ///
+    /// 送信先を設定したプロジェクトの一覧
+    pub fn with_events(mut self, events: EventSender) -> Self {
+        for store in self.projects.values() {
+            write_lock(store).set_events(Some(events.clone()));
+        }
+        self.events = Some(events);
+        self
+    }
+
+    /// プロジェクトのチケットストアを作成する。
+    fn create_store(&self, project: Project) -> PersistenceResult<TicketStore> {
+        let store = match &self.data_dir {
+            Some(data_dir) => {
+                let project_dir = data_dir.join(PROJECTS_DIR_NAME).join(project.key.as_str());
+                TicketStore::create_encrypted(&project_dir, project, &self.encryption)?
+            }
+            None => TicketStore::new(project),
+        };
+
+        Ok(store.with_clock(Arc::clone(&self.clock)))
+    }
+
+    /// チケットストアと、そのプロジェクトの変更前のプロジェクトキーを登録する。
+    fn insert(&mut self, mut store: TicketStore) {
+        store.set_events(self.events.clone());
+        let project = store.project();
+        for previous_key in &project.previous_keys {
+            self.aliases
+                .insert(previous_key.clone(), project.key.clone());
+        }
+        self.aliases.remove(&project.key);
+        self.projects
+            .insert(project.key.clone(), Arc::new(RwLock::new(store)));
+    }
+
+    /// プロジェクトキーが、いずれかのプロジェクトの現在または変更前のプロジェクトキーとして使用されているか確認する。
+    fn is_key_used(&self, key: &ProjectKey) -> bool {
+        self.projects.contains_key(key) || self.aliases.contains_key(key)
+    }
+
+    /// プロジェクトキー順のプロジェクトを返す。
+    ///
+    /// # 引数
+    ///
+    /// * `user` - 利用者、識別できない場合は`None`
+    ///
+    /// # 戻り値
+    ///
+    /// 利用者が読み込みを許可されたプロジェクト
+    pub fn projects(&self, user: Option<&str>) -> Vec<Project> {
+        self.projects
+            .values()
+            .filter_map(|store| {
+                let store = read_lock(store);
+                store.authorize(user, Access::Read).ok()?;
+                Some(store.project().clone())
+            })
+            .collect()
+    }
+
+    /// 利用者が読み込みを許可されたすべてのプロジェクトから、チケットを関連度の高い順に検索する。
+    ///
+    /// 関連度が等しいチケットは、プロジェクトキーとチケットIDの順に並べる。
+    ///
+    /// # 引数
+    ///
+    /// * `text` - 検索文字列
+    /// * `user` - 利用者、識別できない場合は`None`
+    /// * `limit` - 検索結果の最大件数
+    ///
+    /// # 戻り値
+    ///
+    /// 検索結果のチケット
+    pub fn search(&self, text: &SearchText, user: Option<&str>, limit: usize) -> Vec<SearchHit> {
+        let mut hits: Vec<_> = self
+            .projects
+            .values()
+            .flat_map(|store| {
+                let store = read_lock(store);
+                match store.authorize(user, Access::Read) {
+                    Ok(()) => store.search(text),
+                    Err(_) => vec![],
+                }
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.project.cmp(&b.project))
+                .then(a.id.cmp(&b.id))
+        });
+        hits.truncate(limit);
+
+        hits
+    }
+
+    /// 利用者が読み込みを許可されたすべてのプロジェクトから、クエリの条件を満たすチケットを取得する。
+    ///
+    /// # 引数
+    ///
+    /// * `expr` - クエリの構文木
+    /// * `user` - 利用者、識別できない場合は`None`
+    ///
+    /// # 戻り値
+    ///
+    /// プロジェクトキーとチケットIDの順のチケット
+    pub fn query(&self, expr: &Expr, user: Option<&str>) -> Vec<Ticket> {
+        self.projects
+            .values()
+            .flat_map(|store| {
+                let store = read_lock(store);
+                match store.authorize(user, Access::Read) {
+                    Ok(()) => store.query(expr),
+                    Err(_) => vec![],
+                }
+            })
+            .collect()
+    }
+
+    /// 添付ファイルの内容を参照している、すべてのプロジェクトの添付ファイルの数を返す。
+    ///
+    /// # 引数
+    ///
+    /// * `digest` - 添付ファイルの内容のダイジェスト
+    ///
+    /// # 戻り値
+    ///
+    /// 参照している添付ファイルの数
+    pub fn blob_references(&self, digest: &BlobDigest) -> usize {
+        self.projects
+            .values()
+            .map(|store| read_lock(store).blob_references(digest))
+            .sum()
+    }
+
+    /// すべてのプロジェクトの添付ファイルが参照している内容ごとの、参照している添付ファイルの数を返す。
+    pub fn referenced_blobs(&self) -> BTreeMap<BlobDigest, usize> {
+        let mut counts = BTreeMap::new();
+        for store in self.projects.values() {
+            for (digest, count) in read_lock(store).referenced_blobs() {
+                *counts.entry(digest).or_default() += count;
+            }
+        }
+
+        counts
+    }
+
+    /// 利用者が読み込みを許可されたすべてのプロジェクトのチケットストアを返す。
+    pub fn readable_stores(&self, user: Option<&str>) -> Vec<ProjectStore> {
+        self.projects
+            .values()
+            .filter(|store| read_lock(store).authorize(user, Access::Read).is_ok())
+            .cloned()
+            .collect()
+    }
+
+    /// チケットストアの時計による現在の日時を返す。
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// プロジェクトキーでプロジェクトを検索する。
+    ///
+    /// # 引数
+    ///
+    /// * `key` - 現在または変更前のプロジェクトキー
+    ///
+    /// # 戻り値
+    ///
+    /// 検索した結果
+    pub fn lookup(&self, key: &ProjectKey) -> TicketStoreResult<ProjectLookup> {
+        if let Some(store) = self.projects.get(key) {
+            return Ok(ProjectLookup::Found(Arc::clone(store)));
+        }
+
+        self.aliases
+            .get(key)
+            .map(|current| ProjectLookup::Renamed(current.clone()))
+            .ok_or(TicketStoreError::ProjectNotFound)
+    }
+
+    /// 変更前のプロジェクトキーも解決して、プロジェクトのチケットストアを取得する。
+    ///
+    /// # 引数
+    ///
+    /// * `key` - 現在または変更前のプロジェクトキー
+    ///
+    /// # 戻り値
+    ///
+    /// プロジェクトのチケットストア
+    pub fn resolve(&self, key: &ProjectKey) -> TicketStoreResult<ProjectStore> {
+        let key = self.aliases.get(key).unwrap_or(key);
+        self.projects
+            .get(key)
+            .cloned()
+            .ok_or(TicketStoreError::ProjectNotFound)
+    }
+
+    /// プロジェクトを作成する。
+    ///
+    /// 変更前のプロジェクトキーを含むチケットキーを解決できなくなるため、
+    /// 他のプロジェクトの変更前のプロジェクトキーは使用できない。
+    ///
+    /// # 引数
+    ///
+    /// * `draft` - 作成するプロジェクトのドラフト
+    ///
+    /// # 戻り値
+    ///
+    /// 作成したプロジェクトのプロジェクトキー
+    pub fn create(&mut self, draft: ProjectDraft) -> TicketStoreResult<ProjectKey> {
+        if self.is_key_used(&draft.key) {
+            tracing::info!(project_key = %draft.key, "プロジェクトキーが使用されています。");
+            return Err(TicketStoreError::ProjectKeyConflict);
+        }
+        let mut project = Project::new(draft.key, draft.name);
+        project.workflow = draft.workflow;
+        project.permissions = draft.permissions;
+        project.description_format = draft.description_format;
+        let store = self.create_store(project).map_err(|e| {
+            tracing::error!(error = %e, "プロジェクトを作成できません。");
+            TicketStoreError::Persistence(Arc::new(e))
+        })?;
+        let key = store.project().key.clone();
+        self.insert(store);
+        tracing::info!(project_key = %key, "プロジェクトを作成しました。");
+
+        Ok(key)
+    }
+
+    /// プロジェクトを更新する。
+    ///
+    /// プロジェクトキーを変更した場合は、変更前のプロジェクトキーを変更後のプロジェクトキーに解決する。
+    ///
+    /// # 引数
+    ///
+    /// * `key` - 更新するプロジェクトのプロジェクトキー
+    /// * `patch` - プロジェクトのパッチ
+    ///
+    /// # 戻り値
+    ///
+    /// 更新後のプロジェクトキー
+    pub fn update(
+        &mut self,
+        key: &ProjectKey,
+        patch: ProjectPatch,
+    ) -> TicketStoreResult<ProjectKey> {
+        let store = self
+            .projects
+            .get(key)
+            .cloned()
+            .ok_or(TicketStoreError::ProjectNotFound)?;
+        let renamed = patch.key.clone().filter(|new_key| new_key != key);
+        if let Some(new_key) = &renamed {
+            // 自身の変更前のプロジェクトキーに戻すことはできる。
+            let used_by_other = self.projects.contains_key(new_key)
+                || self
+                    .aliases
+                    .get(new_key)
+                    .is_some_and(|current| current != key);
+            if used_by_other {
+                tracing::info!(project_key = %new_key, "プロジェクトキーが使用されています。");
+                return Err(TicketStoreError::ProjectKeyConflict);
+            }
+        }
+
+        write_lock(&store).update_project(patch)?;
+        if let Some(new_key) = renamed {
+            self.projects.remove(key);
+            for current in self.aliases.values_mut() {
+                if current == key {
+                    *current = new_key.clone();
+                }
+            }
+            self.aliases.insert(key.clone(), new_key.clone());
+            self.aliases.remove(&new_key);
+            self.projects.insert(new_key.clone(), store);
+            return Ok(new_key);
+        }
+
+        Ok(key.clone())
+    }
+
+    /// チケットのないプロジェクトを削除する。
+    ///
+    /// 削除したプロジェクトの現在と変更前のプロジェクトキーは、他のプロジェクトで使用できるようになる。
+    ///
+    /// # 引数
+    ///
+    /// * `key` - 削除するプロジェクトのプロジェクトキー
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn delete(&mut self, key: &ProjectKey) -> TicketStoreResult<()> {
+        let store = self
+            .projects
+            .get(key)
+            .cloned()
+            .ok_or(TicketStoreError::ProjectNotFound)?;
+        let mut store = write_lock(&store);
+        if !store.is_empty() {
+            return Err(TicketStoreError::ProjectNotEmpty);
+        }
+        store.destroy().map_err(|e| {
+            tracing::error!(project_key = %key, error = %e, "プロジェクトを削除できません。");
+            TicketStoreError::Persistence(Arc::new(e))
+        })?;
+        self.projects.remove(key);
+        self.aliases.retain(|_, current| current != key);
+        tracing::info!(project_key = %key, "プロジェクトを削除しました。");
+
+        Ok(())
+    }
+
+    /// すべてのプロジェクトのチケットストアのスナップショットを、プロジェクトキー順に返す。
+    pub fn snapshots(&self) -> Vec<Snapshot> {
+        self.projects
+            .values()
+            .map(|store| read_lock(store).snapshot())
+            .collect()
+    }
+
+    /// すべてのプロジェクトを、スナップショットから復元したプロジェクトに置き換える。
+    ///
+    /// 復元したプロジェクトのチケットストアはファイルストレージを持たず、
+    /// この一覧の時計とチケットイベントの送信先を使用する。
+    ///
+    /// # 引数
+    ///
+    /// * `snapshots` - [`ProjectRegistry::snapshots`]で取得したスナップショット
+    pub fn restore(&mut self, snapshots: Vec<Snapshot>) {
+        self.projects.clear();
+        self.aliases.clear();
+        for mut snapshot in snapshots {
+            let Some(project) = snapshot.project.take() else {
+                continue;
+            };
+            let store =
+                TicketStore::from_snapshot(project, snapshot).with_clock(Arc::clone(&self.clock));
+            self.insert(store);
+        }
+    }
+
+    /// すべてのプロジェクトのスナップショットとドメインイベントの履歴を、バックアップとして返す。
+    ///
+    /// プロジェクトごとにチケットストアの書き込みロックを保持して、スナップショットと履歴を一致させる。
+    /// 複数のプロジェクトを変更する操作は、プロジェクトの一覧の書き込みロックを必要とするため、
+    /// プロジェクトの一覧の読み込みロックを保持して呼び出せば、すべてのプロジェクトで一貫したバックアップになる。
+    pub fn backup(&self) -> Backup {
+        Backup {
+            created_at: self.clock.now(),
+            projects: self
+                .projects
+                .values()
+                .map(|store| write_lock(store).backup())
+                .collect(),
+        }
+    }
+
+    /// すべてのプロジェクトを、バックアップから復元したプロジェクトに置き換える。
+    ///
+    /// ファイルストレージを使用する場合は、バックアップを一時ディレクトリに書き出して開けることを確かめてから、
+    /// `projects`ディレクトリを入れ替える。
+    /// 書き出しや入れ替えに失敗した場合は、置き換える前のプロジェクトを残す。
+    ///
+    /// # 引数
+    ///
+    /// * `backup` - [`Backup::read`]で検証したバックアップ
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn restore_backup(&mut self, backup: Backup) -> PersistenceResult<()> {
+        let Some(data_dir) = self.data_dir.clone() else {
+            self.projects.clear();
+            self.aliases.clear();
+            for mut project in backup.projects {
+                let Some(key) = project.snapshot.project.take() else {
+                    continue;
+                };
+                let store =
+                    TicketStore::from_backup(key, project).with_clock(Arc::clone(&self.clock));
+                self.insert(store);
+            }
+            return Ok(());
+        };
+
+        let restoring_dir = data_dir.join(RESTORING_DIR_NAME);
+        if restoring_dir.exists() {
+            fs::remove_dir_all(&restoring_dir)?;
+        }
+        // 現在の形式バージョンで書き出すため、空の一時ディレクトリに形式バージョンを記録する。
+        migrate::ensure_current(&restoring_dir)?;
+        for project in &backup.projects {
+            let Some(key) = project.snapshot.project.as_ref().map(|p| p.key.as_str()) else {
+                continue;
+            };
+            let project_dir = restoring_dir.join(PROJECTS_DIR_NAME).join(key);
+            FileStorage::import(
+                &project_dir,
+                &project.snapshot,
+                &project.history,
+                &self.encryption,
+            )?;
+        }
+        drop(Self::open_encrypted(
+            &restoring_dir,
+            Arc::clone(&self.clock),
+            &self.encryption,
+        )?);
+
+        let projects_dir = data_dir.join(PROJECTS_DIR_NAME);
+        let replaced_dir = data_dir.join(REPLACED_DIR_NAME);
+        fs::rename(&projects_dir, &replaced_dir)?;
+        let restored = fs::rename(restoring_dir.join(PROJECTS_DIR_NAME), &projects_dir)
+            .map_err(PersistenceError::from)
+            .and_then(|()| {
+                Self::open_encrypted(&data_dir, Arc::clone(&self.clock), &self.encryption)
+            });
+        let restored = match restored {
+            Ok(restored) => restored,
+            Err(e) => {
+                if projects_dir.exists() {
+                    fs::remove_dir_all(&projects_dir)?;
+                }
+                fs::rename(&replaced_dir, &projects_dir)?;
+                return Err(e);
+            }
+        };
+        *self = match self.events.clone() {
+            Some(events) => restored.with_events(events),
+            None => restored,
+        };
+        for dir in [&replaced_dir, &restoring_dir] {
+            if let Err(e) = fs::remove_dir_all(dir) {
+                tracing::warn!(dir = %dir.display(), error = %e, "一時ディレクトリを削除できません。");
+            }
+        }
+        tracing::info!(
+            data_dir = %data_dir.display(),
+            projects = backup.projects.len(),
+            "バックアップから復元しました。"
+        );
+
+        Ok(())
+    }
+
+    /// すべてのプロジェクトのチケットストアをスナップショットに書き出す。
+    pub fn checkpoint(&self) -> PersistenceResult<()> {
+        for store in self.projects.values() {
+            write_lock(store).checkpoint()?;
+        }
+
+        Ok(())
+    }
+
+    /// 現在の鍵で暗号化し直す必要があるファイルを持つ、プロジェクトキー順で最初のプロジェクトを暗号化し直す。
+    ///
+    /// 書き直している間は他のリクエストを処理できないため、1回の呼び出しでは1つのプロジェクトだけを暗号化し直す。
+    ///
+    /// # 戻り値
+    ///
+    /// 暗号化し直したプロジェクトのプロジェクトキー、暗号化し直す必要があるプロジェクトがない場合は`None`
+    pub fn reencrypt_next(&self) -> PersistenceResult<Option<ProjectKey>> {
+        let Some((key, store)) = self
+            .projects
+            .iter()
+            .find(|(_, store)| read_lock(store).is_stale())
+        else {
+            return Ok(None);
+        };
+        write_lock(store).reencrypt()?;
+
+        Ok(Some(key.clone()))
+    }
+}
+
+/// チケットを他のプロジェクトに移動する。
+///
+/// 移動先のプロジェクトにチケットを追加してから、移動元のプロジェクトからチケットを取り除く。
+/// 2つのプロジェクトのロックは、デッドロックしないように常に同じ順番で取得する。
+///
+/// # 引数
+///
+/// * `from` - 移動元のプロジェクトのチケットストア
+/// * `id` - 移動するチケットの移動元のプロジェクトでのチケットID
+/// * `to` - 移動先のプロジェクトのチケットストア
+/// * `user` - 利用者、識別できない場合は`None`
+///
+/// # 戻り値
+///
+/// 移動後のチケットキー
+pub fn move_ticket(
+    from: &ProjectStore,
+    id: TicketId,
+    to: &ProjectStore,
+    user: Option<&str>,
+) -> TicketStoreResult<TicketKey> {
+    if Arc::ptr_eq(from, to) {
+        let from = read_lock(from);
+        from.authorize(user, Access::Write)?;
+        return Ok(from.get(id)?.key);
+    }
+
+    let (mut from, to) = lock_both(from, to);
+    from.authorize(user, Access::Write)?;
+    to.authorize(user, Access::Write)?;
+    from.ensure_unlinked(id)?;
+    let ticket = from.get(id)?;
+    let moved_to = to.adopt_ticket(ticket)?;
+    from.release_ticket(id, moved_to.clone())?;
+
+    Ok(moved_to)
+}
+
+/// 2つのチケットストアの書き込みロックを、アドレス順に取得する。
+fn lock_both<'a>(
+    a: &'a ProjectStore,
+    b: &'a ProjectStore,
+) -> (
+    RwLockWriteGuard<'a, TicketStore>,
+    RwLockWriteGuard<'a, TicketStore>,
+) {
+    if Arc::as_ptr(a) < Arc::as_ptr(b) {
+        let a = write_lock(a);
+        (a, write_lock(b))
+    } else {
+        let b = write_lock(b);
+        (write_lock(a), b)
+    }
+}
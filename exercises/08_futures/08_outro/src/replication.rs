@@ -0,0 +1,502 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::{Request, State};
+use axum::http::{HeaderName, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+use chrono::{DateTime, Utc};
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader, Lines};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+use crate::actor::{StoreError, StoreHandle};
+use crate::dto::{AttachmentDraft, ProjectDraft, ProjectPatch, TicketDraft, TicketPatch};
+use crate::models::{AttachmentId, Label, ProjectKey, TicketId, TicketRef};
+use crate::persistence::Snapshot;
+
+/// フォロワーが適用していない、リーダーの変更操作の数を示すHTTPヘッダ
+pub static X_REPLICATION_LAG: HeaderName = HeaderName::from_static("x-replication-lag");
+
+/// リーダーが1度に読み出して送信する、変更操作の最大数
+const BATCH_SIZE: usize = 256;
+
+/// リーダーに再接続するまでの最初の待ち時間
+pub(crate) const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// リーダーに再接続するまでの最大の待ち時間
+pub(crate) const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// 複製するチケットストアの変更操作
+///
+/// チケットストアのアクターのコマンドのうち、成功したときにプロジェクトの一覧を変更するものである。
+/// フォロワーは、リーダーが記録した順番に、リーダーが記録した日時で変更操作を適用する。
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all_fields = "camelCase")]
+pub enum Operation {
+    /// プロジェクトの一覧全体を、スナップショットから復元したプロジェクトに置き換える。
+    Restore {
+        projects: Vec<Snapshot>,
+    },
+    CreateTicket {
+        project: ProjectKey,
+        user: Option<String>,
+        draft: TicketDraft,
+    },
+    UpdateTicket {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        patch: TicketPatch,
+    },
+    MoveTicket {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        to: ProjectKey,
+    },
+    SetParent {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        parent: Option<TicketId>,
+    },
+    AddBlocker {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        blocker: TicketId,
+    },
+    RemoveBlocker {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        blocker: TicketId,
+    },
+    AddAttachment {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        draft: AttachmentDraft,
+    },
+    RemoveAttachment {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        attachment: AttachmentId,
+    },
+    AddLabel {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        label: Label,
+    },
+    RemoveLabel {
+        project: ProjectKey,
+        user: Option<String>,
+        ticket: TicketRef,
+        label: Label,
+    },
+    CreateProject {
+        draft: ProjectDraft,
+    },
+    UpdateProject {
+        project: ProjectKey,
+        user: Option<String>,
+        patch: ProjectPatch,
+    },
+    DeleteProject {
+        project: ProjectKey,
+        user: Option<String>,
+    },
+}
+
+/// 複製ログに記録した変更操作
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplicatedOperation {
+    /// 複製ログの先頭からの位置
+    pub offset: u64,
+    /// リーダーが変更操作を適用した日時
+    pub occurred_at: DateTime<Utc>,
+    pub operation: Operation,
+}
+
+/// リーダーの複製ログ
+///
+/// 複製ログはリーダーを起動してからの変更操作をメモリに保持する。
+/// 先頭には、起動したときのプロジェクトの一覧を復元する変更操作を記録するため、
+/// フォロワーは先頭から適用すればリーダーと同じプロジェクトの一覧を構築できる。
+/// リーダーを起動するたびに新しいエポックを割り当てて、フォロワーが以前のリーダーの位置から再開しないようにする。
+#[derive(Debug)]
+pub struct ReplicationLog {
+    epoch: Uuid,
+    entries: Mutex<Vec<ReplicatedOperation>>,
+    /// 次に記録する変更操作の位置
+    head: watch::Sender<u64>,
+}
+
+impl Default for ReplicationLog {
+    fn default() -> Self {
+        Self {
+            epoch: Uuid::new_v4(),
+            entries: Mutex::default(),
+            head: watch::Sender::new(0),
+        }
+    }
+}
+
+impl ReplicationLog {
+    /// 複製ログのエポックを返す。
+    pub fn epoch(&self) -> Uuid {
+        self.epoch
+    }
+
+    /// 次に記録する変更操作の位置を返す。
+    pub fn head(&self) -> u64 {
+        *self.head.borrow()
+    }
+
+    /// 変更操作を複製ログの末尾に記録する。
+    ///
+    /// # 引数
+    ///
+    /// * `occurred_at` - 変更操作を適用した日時
+    /// * `operation` - 変更操作
+    ///
+    /// # 戻り値
+    ///
+    /// 記録した位置
+    pub fn append(&self, occurred_at: DateTime<Utc>, operation: Operation) -> u64 {
+        let mut entries = self.entries.lock().unwrap();
+        let offset = entries.len() as u64;
+        entries.push(ReplicatedOperation {
+            offset,
+            occurred_at,
+            operation,
+        });
+        self.head.send_replace(offset + 1);
+
+        offset
+    }
+
+    /// 指定した位置から、記録した変更操作を読み出す。
+    ///
+    /// # 引数
+    ///
+    /// * `offset` - 読み出しを開始する位置
+    /// * `limit` - 読み出す変更操作の最大数
+    ///
+    /// # 戻り値
+    ///
+    /// 位置の順の変更操作
+    pub fn read(&self, offset: u64, limit: usize) -> Vec<ReplicatedOperation> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .skip(offset as usize)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+/// フォロワーがリーダーに接続したときに送信する購読要求
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Subscribe {
+    /// 前回接続したリーダーのエポック、接続したことがない場合は`None`
+    epoch: Option<Uuid>,
+    /// 次に適用する変更操作の位置
+    offset: u64,
+}
+
+/// リーダーが購読要求に応答する、送信を開始する位置
+///
+/// エポックが異なるか、位置が複製ログにない場合は、先頭から送信する。
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Hello {
+    epoch: Uuid,
+    offset: u64,
+}
+
+/// リーダーがフォロワーに送信する変更操作
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Delivery {
+    /// 送信したときの、リーダーが次に記録する変更操作の位置
+    head: u64,
+    entry: ReplicatedOperation,
+}
+
+/// フォロワーからの接続を受け付けて、複製ログの変更操作を送信する。
+///
+/// 接続ごとに、購読要求で指定された位置から送信を開始し、その後は記録されるたびに送信する。
+/// この関数が返すfutureを破棄すると、すべての接続を閉じる。
+///
+/// # 引数
+///
+/// * `listener` - 複製を待ち受けるTCPリスナー
+/// * `log` - 複製ログ
+pub async fn serve_leader(listener: TcpListener, log: Arc<ReplicationLog>) {
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    let log = Arc::clone(&log);
+                    connections.spawn(async move {
+                        tracing::info!(%peer, "フォロワーが接続しました。");
+                        match stream_operations(stream, &log).await {
+                            Ok(()) => tracing::info!(%peer, "フォロワーが切断しました。"),
+                            Err(e) => tracing::warn!(%peer, error = %e, "フォロワーに複製できません。"),
+                        }
+                    });
+                }
+                Err(e) => tracing::warn!(error = %e, "フォロワーの接続を受け付けられません。"),
+            },
+            Some(_) = connections.join_next() => {}
+        }
+    }
+}
+
+/// 購読要求を受け取り、フォロワーが切断するまで変更操作を送信する。
+async fn stream_operations(stream: TcpStream, log: &ReplicationLog) -> ReplicationResult<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let subscribe: Subscribe = read_message(&mut lines)
+        .await?
+        .ok_or(ReplicationError::Disconnected)?;
+    let mut offset = match subscribe.epoch {
+        Some(epoch) if epoch == log.epoch() && subscribe.offset <= log.head() => subscribe.offset,
+        _ => 0,
+    };
+    write_message(
+        &mut writer,
+        &Hello {
+            epoch: log.epoch(),
+            offset,
+        },
+    )
+    .await?;
+
+    let mut head = log.head.subscribe();
+    loop {
+        head.borrow_and_update();
+        let entries = log.read(offset, BATCH_SIZE);
+        if entries.is_empty() {
+            tokio::select! {
+                changed = head.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                }
+                // フォロワーは購読要求の後に何も送信しないため、読み込めた場合は切断である。
+                _ = lines.next_line() => return Ok(()),
+            }
+            continue;
+        }
+        let current = log.head();
+        for entry in entries {
+            offset = entry.offset + 1;
+            write_message(
+                &mut writer,
+                &Delivery {
+                    head: current,
+                    entry,
+                },
+            )
+            .await?;
+        }
+    }
+}
+
+/// フォロワーの複製の状態
+///
+/// フォロワーはリーダーに接続して変更操作を適用し、切断された場合は待ち時間を延ばしながら再接続する。
+/// 書き込みはリーダーで処理するため、フォロワーが受け付けたリクエストのうち読み込み以外は、リーダーにリダイレクトする。
+#[derive(Debug)]
+pub struct Follower {
+    /// 書き込みをリダイレクトするリーダーのURL
+    leader_url: String,
+    /// 接続しているリーダーのエポック
+    epoch: Mutex<Option<Uuid>>,
+    /// 次に適用する変更操作の位置
+    applied: AtomicU64,
+    /// 最後に受信したときの、リーダーが次に記録する変更操作の位置
+    head: AtomicU64,
+    /// 最後の接続でリーダーが購読要求に応答したか
+    connected: AtomicBool,
+}
+
+impl Follower {
+    /// 複製を開始していないフォロワーを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `leader_url` - 書き込みをリダイレクトするリーダーのURL
+    ///
+    /// # 戻り値
+    ///
+    /// フォロワー
+    pub fn new(leader_url: &str) -> Self {
+        Self {
+            leader_url: leader_url.trim_end_matches('/').into(),
+            epoch: Mutex::new(None),
+            applied: AtomicU64::new(0),
+            head: AtomicU64::new(0),
+            connected: AtomicBool::new(false),
+        }
+    }
+
+    /// 次に適用する変更操作の位置を返す。
+    pub fn applied(&self) -> u64 {
+        self.applied.load(Ordering::Acquire)
+    }
+
+    /// 最後に受信したリーダーの複製ログに対して、適用していない変更操作の数を返す。
+    pub fn lag(&self) -> u64 {
+        self.head
+            .load(Ordering::Acquire)
+            .saturating_sub(self.applied())
+    }
+
+    /// リーダーから受信した変更操作を、チケットストアに適用し続ける。
+    ///
+    /// # 引数
+    ///
+    /// * `leader` - リーダーが複製を待ち受けるソケットアドレス
+    /// * `store` - フォロワーのチケットストアのアクターのハンドル
+    pub async fn follow(self: Arc<Self>, leader: SocketAddr, store: StoreHandle) {
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            match self.replicate(leader, &store).await {
+                Ok(()) => tracing::warn!(%leader, "リーダーとの接続が切断されました。"),
+                Err(ReplicationError::Store(StoreError::Stopped)) => return,
+                Err(e) => tracing::warn!(%leader, error = %e, "リーダーから複製できません。"),
+            }
+            if self.connected.swap(false, Ordering::AcqRel) {
+                backoff = INITIAL_BACKOFF;
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// リーダーに接続して、切断されるまで変更操作を適用する。
+    async fn replicate(&self, leader: SocketAddr, store: &StoreHandle) -> ReplicationResult<()> {
+        let stream = TcpStream::connect(leader).await?;
+        let (reader, mut writer) = stream.into_split();
+        let epoch = *self.epoch.lock().unwrap();
+        let subscribe = Subscribe {
+            epoch,
+            offset: self.applied(),
+        };
+        write_message(&mut writer, &subscribe).await?;
+        let mut lines = BufReader::new(reader).lines();
+        let hello: Hello = read_message(&mut lines)
+            .await?
+            .ok_or(ReplicationError::Disconnected)?;
+        if epoch != Some(hello.epoch) {
+            tracing::info!(epoch = %hello.epoch, "リーダーの複製ログを先頭から適用します。");
+        }
+        *self.epoch.lock().unwrap() = Some(hello.epoch);
+        self.applied.store(hello.offset, Ordering::Release);
+        self.connected.store(true, Ordering::Release);
+        tracing::info!(%leader, offset = hello.offset, "リーダーに接続しました。");
+
+        while let Some(delivery) = read_message::<Delivery>(&mut lines).await? {
+            let offset = delivery.entry.offset;
+            self.head.store(delivery.head, Ordering::Release);
+            match store.apply(delivery.entry).await {
+                Ok(()) => {}
+                Err(StoreError::Stopped) => return Err(StoreError::Stopped.into()),
+                Err(e) => {
+                    tracing::error!(offset, error = %e, "複製した変更操作を適用できません。");
+                }
+            }
+            self.applied.store(offset + 1, Ordering::Release);
+        }
+
+        Ok(())
+    }
+}
+
+/// フォロワーで、読み込み以外のリクエストをリーダーにリダイレクトし、
+/// 読み込みのレスポンスに複製の遅れを示すヘッダを追加するミドルウェア
+///
+/// リダイレクトはメソッドとボディを維持する`307 Temporary Redirect`で返す。
+pub async fn follower_gate(
+    State(follower): State<Arc<Follower>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        let path = request
+            .uri()
+            .path_and_query()
+            .map(|p| p.as_str())
+            .unwrap_or("/");
+        tracing::debug!(method = %request.method(), path, "書き込みをリーダーにリダイレクトします。");
+        return Redirect::temporary(&format!("{}{path}", follower.leader_url)).into_response();
+    }
+
+    let lag = follower.lag();
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(X_REPLICATION_LAG.clone(), HeaderValue::from(lag));
+    response
+}
+
+/// メッセージをJSONの1行として書き込む。
+pub(crate) async fn write_message<W, T>(writer: &mut W, message: &T) -> ReplicationResult<()>
+where
+    W: AsyncWrite + Unpin,
+    T: serde::Serialize,
+{
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    writer.flush().await?;
+
+    Ok(())
+}
+
+/// JSONの1行をメッセージとして読み込む。
+///
+/// # 戻り値
+///
+/// メッセージ、接続が閉じられた場合は`None`
+pub(crate) async fn read_message<T>(
+    lines: &mut Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>,
+) -> ReplicationResult<Option<T>>
+where
+    T: serde::de::DeserializeOwned,
+{
+    match lines.next_line().await? {
+        Some(line) => Ok(Some(serde_json::from_str(&line)?)),
+        None => Ok(None),
+    }
+}
+
+/// 複製エラー
+#[derive(Debug, thiserror::Error)]
+pub enum ReplicationError {
+    #[error("複製の通信に失敗しました: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("複製のメッセージが誤っています: {0}")]
+    Protocol(#[from] serde_json::Error),
+    #[error("複製を開始する前に接続が閉じられました。")]
+    Disconnected,
+    #[error(transparent)]
+    Store(#[from] StoreError),
+}
+
+/// 複製結果
+pub type ReplicationResult<T> = Result<T, ReplicationError>;
@@ -0,0 +1,379 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, Utc};
+
+use crate::dto::ThroughputBucket;
+use crate::models::{TicketId, TicketStatus};
+
+/// 集計期間を指定しない場合の、今日までの日数
+pub const DEFAULT_REPORT_DAYS: u64 = 30;
+
+/// 集計期間の最大日数
+pub const MAX_REPORT_DAYS: u64 = 366;
+
+/// サイクルタイムのレポートに含めるパーセンタイル
+const CYCLE_TIME_PERCENTILES: [u8; 4] = [50, 75, 90, 95];
+
+/// レポートに含めるステータス
+const STATUSES: [TicketStatus; 3] = [
+    TicketStatus::ToDo,
+    TicketStatus::InProgress,
+    TicketStatus::Done,
+];
+
+/// チケットのステータスの遷移
+///
+/// ドメインイベントの`StatusChanged`から、各チケットの現在のステータスと、チケットが完了した日時を射影する。
+/// レポートは、チケットストアから複製したこの射影から、書き込みを妨げずに集計する。
+#[derive(Debug, Clone, Default)]
+pub struct StatusTimeline {
+    progress: BTreeMap<TicketId, Progress>,
+    /// 完了した順の、チケットの完了
+    completions: Vec<Completion>,
+}
+
+/// 完了していないチケットの進捗
+#[derive(Debug, Clone, Copy)]
+struct Progress {
+    status: TicketStatus,
+    /// 前に完了してから、最初に`InProgress`になった日時
+    started_at: Option<DateTime<Utc>>,
+}
+
+/// チケットの完了
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Completion {
+    pub ticket: TicketId,
+    /// 作業を開始した日時、`InProgress`を経ずに完了した場合は`None`
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: DateTime<Utc>,
+}
+
+impl StatusTimeline {
+    /// 登録または取り込んだチケットの追跡を始める。
+    ///
+    /// 取り込んだチケットは遷移の履歴を持たないため、作業を開始した日時は分からない。
+    pub fn track(&mut self, id: TicketId, status: TicketStatus) {
+        self.progress.insert(
+            id,
+            Progress {
+                status,
+                started_at: None,
+            },
+        );
+    }
+
+    /// チケットのステータスの遷移を記録する。
+    ///
+    /// `Done`に遷移した場合は、作業を開始した日時からの完了として記録する。
+    /// 完了したチケットを再び開いた場合は、次に`InProgress`になった日時から作業を開始したとみなす。
+    pub fn transition(&mut self, id: TicketId, to: TicketStatus, at: DateTime<Utc>) {
+        let progress = self.progress.entry(id).or_insert(Progress {
+            status: to,
+            started_at: None,
+        });
+        progress.status = to;
+        match to {
+            TicketStatus::InProgress if progress.started_at.is_none() => {
+                progress.started_at = Some(at);
+            }
+            TicketStatus::Done => self.completions.push(Completion {
+                ticket: id,
+                started_at: progress.started_at.take(),
+                completed_at: at,
+            }),
+            _ => {}
+        }
+    }
+
+    /// 他のプロジェクトに移動したチケットの追跡をやめる。
+    ///
+    /// 移動する前の完了は、このプロジェクトの完了として残す。
+    pub fn untrack(&mut self, id: TicketId) {
+        self.progress.remove(&id);
+    }
+
+    /// ステータスごとのチケットの数を返す。
+    pub fn counts(&self) -> BTreeMap<TicketStatus, usize> {
+        let mut counts = BTreeMap::new();
+        for progress in self.progress.values() {
+            *counts.entry(progress.status).or_default() += 1;
+        }
+        counts
+    }
+
+    /// 完了した順の、チケットの完了を返す。
+    pub fn completions(&self) -> &[Completion] {
+        &self.completions
+    }
+}
+
+/// レポートの集計期間
+///
+/// 開始日と終了日を含み、日付はUTCで区切る。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct ReportWindow {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl ReportWindow {
+    /// 集計期間を構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `from` - 開始日、`None`の場合は終了日の[`DEFAULT_REPORT_DAYS`]日前の翌日
+    /// * `to` - 終了日、`None`の場合は今日
+    /// * `today` - 今日
+    ///
+    /// # 戻り値
+    ///
+    /// 集計期間
+    pub fn new(
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        today: NaiveDate,
+    ) -> Result<Self, ReportError> {
+        let to = to.unwrap_or(today);
+        let from = from.unwrap_or_else(|| to - Days::new(DEFAULT_REPORT_DAYS - 1));
+        if to < from {
+            return Err(ReportError::InvalidWindow);
+        }
+        if MAX_REPORT_DAYS <= (to - from).num_days() as u64 {
+            return Err(ReportError::WindowTooLong);
+        }
+
+        Ok(Self { from, to })
+    }
+
+    /// 日時が集計期間に含まれるか確認する。
+    fn contains(&self, at: DateTime<Utc>) -> bool {
+        (self.from..=self.to).contains(&at.date_naive())
+    }
+}
+
+/// ステータスごとのチケットの数
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusSummary {
+    pub total: usize,
+    pub statuses: Vec<StatusCount>,
+}
+
+/// ステータスのチケットの数
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusCount {
+    pub status: TicketStatus,
+    pub count: usize,
+}
+
+/// `InProgress`から`Done`までの時間の分布
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycleTime {
+    #[serde(flatten)]
+    pub window: ReportWindow,
+    /// 集計期間に完了した、作業を開始した日時が分かるチケットの完了の数
+    pub count: usize,
+    /// 完了がない場合は空
+    pub percentiles: Vec<CycleTimePercentile>,
+}
+
+/// サイクルタイムのパーセンタイル
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycleTimePercentile {
+    pub percentile: u8,
+    pub seconds: i64,
+}
+
+/// 期間ごとの、完了したチケットの数
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Throughput {
+    #[serde(flatten)]
+    pub window: ReportWindow,
+    pub bucket: ThroughputBucket,
+    pub periods: Vec<ThroughputPeriod>,
+}
+
+/// 期間に完了したチケットの数
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThroughputPeriod {
+    /// 期間の開始日（週の場合は月曜日）
+    pub start: NaiveDate,
+    pub completed: usize,
+}
+
+/// ステータスごとのチケットの数を集計する。
+///
+/// # 引数
+///
+/// * `timelines` - 集計するプロジェクトのステータスの遷移
+///
+/// # 戻り値
+///
+/// ステータスごとのチケットの数
+pub fn summary(timelines: &[StatusTimeline]) -> StatusSummary {
+    let mut counts = BTreeMap::<TicketStatus, usize>::new();
+    for timeline in timelines {
+        for (status, count) in timeline.counts() {
+            *counts.entry(status).or_default() += count;
+        }
+    }
+    let statuses: Vec<_> = STATUSES
+        .into_iter()
+        .map(|status| StatusCount {
+            status,
+            count: counts.get(&status).copied().unwrap_or_default(),
+        })
+        .collect();
+
+    StatusSummary {
+        total: statuses.iter().map(|s| s.count).sum(),
+        statuses,
+    }
+}
+
+/// 集計期間に完了したチケットの、`InProgress`になってから`Done`になるまでの時間を集計する。
+///
+/// パーセンタイルは最近傍順位法で求める。
+///
+/// # 引数
+///
+/// * `timelines` - 集計するプロジェクトのステータスの遷移
+/// * `window` - 集計期間
+///
+/// # 戻り値
+///
+/// サイクルタイムの分布
+pub fn cycle_time(timelines: &[StatusTimeline], window: ReportWindow) -> CycleTime {
+    let mut durations: Vec<i64> = timelines
+        .iter()
+        .flat_map(StatusTimeline::completions)
+        .filter(|completion| window.contains(completion.completed_at))
+        .filter_map(|completion| {
+            let started_at = completion.started_at?;
+            Some((completion.completed_at - started_at).num_seconds())
+        })
+        .collect();
+    durations.sort_unstable();
+    let percentiles = match durations.len() {
+        0 => vec![],
+        n => CYCLE_TIME_PERCENTILES
+            .into_iter()
+            .map(|percentile| {
+                let rank = (usize::from(percentile) * n).div_ceil(100).max(1);
+                CycleTimePercentile {
+                    percentile,
+                    seconds: durations[rank - 1],
+                }
+            })
+            .collect(),
+    };
+
+    CycleTime {
+        window,
+        count: durations.len(),
+        percentiles,
+    }
+}
+
+/// 集計期間に完了したチケットの数を、日または週ごとに集計する。
+///
+/// 完了したチケットがない期間も、`0`として含める。
+///
+/// # 引数
+///
+/// * `timelines` - 集計するプロジェクトのステータスの遷移
+/// * `window` - 集計期間
+/// * `bucket` - 集計する期間の単位
+///
+/// # 戻り値
+///
+/// 期間ごとの、完了したチケットの数
+pub fn throughput(
+    timelines: &[StatusTimeline],
+    window: ReportWindow,
+    bucket: ThroughputBucket,
+) -> Throughput {
+    let start_of = |date: NaiveDate| match bucket {
+        ThroughputBucket::Day => date,
+        ThroughputBucket::Week => date - Days::new(date.weekday().num_days_from_monday().into()),
+    };
+    let step = match bucket {
+        ThroughputBucket::Day => Days::new(1),
+        ThroughputBucket::Week => Days::new(7),
+    };
+    let mut periods = BTreeMap::new();
+    let mut start = start_of(window.from);
+    while start <= window.to {
+        periods.insert(start, 0);
+        start = start + step;
+    }
+    for completion in timelines.iter().flat_map(StatusTimeline::completions) {
+        if window.contains(completion.completed_at) {
+            *periods
+                .entry(start_of(completion.completed_at.date_naive()))
+                .or_default() += 1;
+        }
+    }
+
+    Throughput {
+        window,
+        bucket,
+        periods: periods
+            .into_iter()
+            .map(|(start, completed)| ThroughputPeriod { start, completed })
+            .collect(),
+    }
+}
+
+/// CSVで出力できるレポート
+pub trait CsvReport {
+    /// ヘッダ行を含むCSVを返す。
+    fn to_csv(&self) -> String;
+}
+
+impl CsvReport for StatusSummary {
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("status,count\n");
+        for row in &self.statuses {
+            // ステータスのデバッグ表現は、JSONのステータスと同じ名前である。
+            let _ = writeln!(csv, "{:?},{}", row.status, row.count);
+        }
+        csv
+    }
+}
+
+impl CsvReport for CycleTime {
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("percentile,seconds\n");
+        for row in &self.percentiles {
+            let _ = writeln!(csv, "{},{}", row.percentile, row.seconds);
+        }
+        csv
+    }
+}
+
+impl CsvReport for Throughput {
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("start,completed\n");
+        for row in &self.periods {
+            let _ = writeln!(csv, "{},{}", row.start, row.completed);
+        }
+        csv
+    }
+}
+
+/// レポートエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ReportError {
+    #[error("集計期間の開始日は、終了日以前の日付です。")]
+    InvalidWindow,
+    #[error("集計期間は{MAX_REPORT_DAYS}日以内です。")]
+    WindowTooLong,
+}
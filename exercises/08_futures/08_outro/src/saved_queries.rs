@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Utc};
+
+use crate::clock::Clock;
+use crate::crypto::Encryption;
+use crate::dto::SavedQueryDraft;
+use crate::persistence::{self, PersistenceError, PersistenceResult};
+use crate::query::{self, QueryError};
+use crate::sync::{read_lock, write_lock};
+
+/// 保存したクエリを永続化するファイル名
+const SAVED_QUERIES_FILE_NAME: &str = "saved-queries.json";
+
+/// 保存したクエリの名前の最大文字数
+const SAVED_QUERY_NAME_MAX_CHARS: usize = 50;
+
+/// 保存したクエリの名前
+///
+/// URIのパスに含めるため、`/`と制御文字は使用できない。
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SavedQueryName(String);
+
+/// 保存したクエリの名前エラー
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum SavedQueryNameError {
+    #[error("クエリの名前は1文字以上50文字以下です。")]
+    Length,
+    #[error("クエリの名前には`/`と制御文字を使用できません。")]
+    InvalidChar,
+}
+
+/// 文字列から保存したクエリの名前を構築する。
+///
+/// # 引数
+///
+/// * `s` - 保存したクエリの名前を表現する文字列
+///
+/// # 戻り値
+///
+/// 保存したクエリの名前
+fn saved_query_name_from_str(s: &str) -> Result<SavedQueryName, SavedQueryNameError> {
+    let s = s.trim();
+    if !(1..=SAVED_QUERY_NAME_MAX_CHARS).contains(&s.chars().count()) {
+        return Err(SavedQueryNameError::Length);
+    }
+    if s.chars().any(|c| c == '/' || c.is_control()) {
+        return Err(SavedQueryNameError::InvalidChar);
+    }
+
+    Ok(SavedQueryName(s.into()))
+}
+
+impl TryFrom<String> for SavedQueryName {
+    type Error = SavedQueryNameError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        saved_query_name_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for SavedQueryName {
+    type Error = SavedQueryNameError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        saved_query_name_from_str(value)
+    }
+}
+
+impl From<SavedQueryName> for String {
+    fn from(value: SavedQueryName) -> Self {
+        value.0
+    }
+}
+
+impl fmt::Display for SavedQueryName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// 保存したクエリ
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedQuery {
+    pub name: SavedQueryName,
+    /// 保存したときに構文を検証したクエリ
+    pub query: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 利用者ごとの保存したクエリ
+///
+/// データディレクトリを持つ場合は、保存または削除するたびに`saved-queries.json`に書き出す。
+#[derive(Debug)]
+pub struct SavedQueries {
+    /// 利用者ごとの、名前順の保存したクエリ
+    queries: RwLock<BTreeMap<String, BTreeMap<SavedQueryName, SavedQuery>>>,
+    path: Option<PathBuf>,
+    /// 保存したクエリを永続化するファイルの暗号化
+    encryption: Encryption,
+    clock: Arc<dyn Clock>,
+}
+
+impl SavedQueries {
+    /// 永続化しない保存したクエリを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `clock` - 更新日時に使用する時計
+    ///
+    /// # 戻り値
+    ///
+    /// 保存したクエリ
+    pub fn in_memory(clock: Arc<dyn Clock>) -> Arc<Self> {
+        Arc::new(Self {
+            queries: RwLock::new(BTreeMap::new()),
+            path: None,
+            encryption: Encryption::default(),
+            clock,
+        })
+    }
+
+    /// データディレクトリから、保存したクエリを復元する。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - データディレクトリ
+    /// * `clock` - 更新日時に使用する時計
+    ///
+    /// # 戻り値
+    ///
+    /// 保存したクエリ
+    pub fn open(data_dir: &Path, clock: Arc<dyn Clock>) -> PersistenceResult<Arc<Self>> {
+        Self::open_encrypted(data_dir, clock, &Encryption::default())
+    }
+
+    /// 暗号化を指定して、データディレクトリから、保存したクエリを復元する。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - データディレクトリ
+    /// * `clock` - 更新日時に使用する時計
+    /// * `encryption` - 保存したクエリを永続化するファイルの暗号化
+    ///
+    /// # 戻り値
+    ///
+    /// 保存したクエリ
+    pub fn open_encrypted(
+        data_dir: &Path,
+        clock: Arc<dyn Clock>,
+        encryption: &Encryption,
+    ) -> PersistenceResult<Arc<Self>> {
+        std::fs::create_dir_all(data_dir)?;
+        let path = data_dir.join(SAVED_QUERIES_FILE_NAME);
+        let queries = match persistence::read_file(&path, encryption)? {
+            Some((content, _)) => {
+                serde_json::from_slice(&content).map_err(PersistenceError::CorruptedSavedQueries)?
+            }
+            None => BTreeMap::new(),
+        };
+
+        Ok(Arc::new(Self {
+            queries: RwLock::new(queries),
+            path: Some(path),
+            encryption: encryption.clone(),
+            clock,
+        }))
+    }
+
+    /// 保存したクエリをファイルに書き出す。
+    fn save_all(
+        &self,
+        queries: &BTreeMap<String, BTreeMap<SavedQueryName, SavedQuery>>,
+    ) -> SavedQueryResult<()> {
+        match &self.path {
+            Some(path) => persistence::write_encrypted_json(path, queries, &self.encryption)
+                .map_err(|e| SavedQueryError::Persistence(Arc::new(e))),
+            None => Ok(()),
+        }
+    }
+
+    /// 保存したクエリを永続化するファイルが現在の鍵で暗号化されていない場合は、暗号化し直す。
+    ///
+    /// # 戻り値
+    ///
+    /// 暗号化し直した場合は`true`
+    pub fn reencrypt(&self) -> PersistenceResult<bool> {
+        let Some(path) = &self.path else {
+            return Ok(false);
+        };
+        let queries = write_lock(&self.queries);
+        match persistence::read_file(path, &self.encryption)? {
+            Some((_, sealing)) if self.encryption.is_stale(sealing) => {
+                persistence::write_encrypted_json(path, &*queries, &self.encryption)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// 利用者の保存したクエリを、名前順に取得する。
+    ///
+    /// # 引数
+    ///
+    /// * `user` - 利用者、識別できない場合は`None`
+    ///
+    /// # 戻り値
+    ///
+    /// 保存したクエリ
+    pub fn list(&self, user: Option<&str>) -> SavedQueryResult<Vec<SavedQuery>> {
+        let user = user.ok_or(SavedQueryError::Anonymous)?;
+        Ok(read_lock(&self.queries)
+            .get(user)
+            .map(|queries| queries.values().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    /// 利用者の保存したクエリを取得する。
+    ///
+    /// # 引数
+    ///
+    /// * `user` - 利用者、識別できない場合は`None`
+    /// * `name` - クエリの名前
+    ///
+    /// # 戻り値
+    ///
+    /// 保存したクエリ
+    pub fn get(&self, user: Option<&str>, name: &SavedQueryName) -> SavedQueryResult<SavedQuery> {
+        let user = user.ok_or(SavedQueryError::Anonymous)?;
+        read_lock(&self.queries)
+            .get(user)
+            .and_then(|queries| queries.get(name))
+            .cloned()
+            .ok_or(SavedQueryError::NotFound)
+    }
+
+    /// クエリの構文を検証して、利用者のクエリとして保存する。
+    ///
+    /// 同じ名前のクエリがある場合は置き換える。
+    ///
+    /// # 引数
+    ///
+    /// * `user` - 利用者、識別できない場合は`None`
+    /// * `name` - クエリの名前
+    /// * `draft` - 保存するクエリ
+    ///
+    /// # 戻り値
+    ///
+    /// 保存したクエリ
+    pub fn save(
+        &self,
+        user: Option<&str>,
+        name: SavedQueryName,
+        draft: SavedQueryDraft,
+    ) -> SavedQueryResult<SavedQuery> {
+        let user = user.ok_or(SavedQueryError::Anonymous)?;
+        query::parse(&draft.query)?;
+        let saved = SavedQuery {
+            name: name.clone(),
+            query: draft.query,
+            updated_at: self.clock.now(),
+        };
+
+        let mut queries = write_lock(&self.queries);
+        let previous = queries
+            .entry(user.into())
+            .or_default()
+            .insert(name.clone(), saved.clone());
+        if let Err(e) = self.save_all(&queries) {
+            let user_queries = queries.get_mut(user).expect("保存したばかりの利用者");
+            match previous {
+                Some(previous) => user_queries.insert(name, previous),
+                None => user_queries.remove(&name),
+            };
+            return Err(e);
+        }
+        tracing::info!(user, name = %saved.name, "クエリを保存しました。");
+
+        Ok(saved)
+    }
+
+    /// 利用者の保存したクエリを削除する。
+    ///
+    /// # 引数
+    ///
+    /// * `user` - 利用者、識別できない場合は`None`
+    /// * `name` - クエリの名前
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn delete(&self, user: Option<&str>, name: &SavedQueryName) -> SavedQueryResult<()> {
+        let user = user.ok_or(SavedQueryError::Anonymous)?;
+        let mut queries = write_lock(&self.queries);
+        let removed = queries
+            .get_mut(user)
+            .and_then(|queries| queries.remove(name))
+            .ok_or(SavedQueryError::NotFound)?;
+        if let Err(e) = self.save_all(&queries) {
+            queries
+                .entry(user.into())
+                .or_default()
+                .insert(name.clone(), removed);
+            return Err(e);
+        }
+        tracing::info!(user, %name, "保存したクエリを削除しました。");
+
+        Ok(())
+    }
+}
+
+/// 保存したクエリエラー
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum SavedQueryError {
+    #[error("クエリを保存するには、`X-User`ヘッダで利用者を指定してください。")]
+    Anonymous,
+    #[error("保存したクエリが見つかりません。")]
+    NotFound,
+    #[error("クエリが不正です: {0}")]
+    Invalid(#[from] QueryError),
+    #[error("保存したクエリを永続化できません。")]
+    Persistence(#[source] Arc<PersistenceError>),
+}
+
+/// 保存したクエリ結果
+pub type SavedQueryResult<T> = Result<T, SavedQueryError>;
@@ -0,0 +1,582 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use crate::models::{ProjectKey, Ticket, TicketId, TicketKey, TicketStatus};
+
+/// 検索結果の既定の件数
+pub const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+/// 検索結果の最大件数
+pub const MAX_SEARCH_LIMIT: usize = 100;
+
+/// 検索文字列の最大文字数
+const SEARCH_TEXT_MAX_CHARS: usize = 200;
+
+/// スニペットに含める最大文字数
+const SNIPPET_MAX_CHARS: usize = 80;
+
+/// スニペットで、最初に一致した箇所より前に含める文字数
+const SNIPPET_LEADING_CHARS: usize = 20;
+
+/// タイトルに出現した語の重み
+const TITLE_WEIGHT: f64 = 2.0;
+
+/// BM25の語の出現回数の飽和を調整するパラメーター
+const BM25_K1: f64 = 1.2;
+
+/// BM25の文書の長さによる正規化を調整するパラメーター
+const BM25_B: f64 = 0.75;
+
+/// 検索文字列
+///
+/// 少なくとも1つの検索語を含む。
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct SearchText {
+    text: String,
+    terms: BTreeSet<String>,
+}
+
+/// 検索文字列エラー
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum SearchTextError {
+    #[error("検索語を指定してください。")]
+    Empty,
+    #[error("検索文字列は200文字以下です。")]
+    TooLong,
+}
+
+/// 文字列から検索文字列を構築する。
+///
+/// # 引数
+///
+/// * `s` - 検索文字列
+///
+/// # 戻り値
+///
+/// 検索文字列
+fn search_text_from_str(s: &str) -> Result<SearchText, SearchTextError> {
+    let s = s.trim();
+    if SEARCH_TEXT_MAX_CHARS < s.chars().count() {
+        return Err(SearchTextError::TooLong);
+    }
+    let terms = query_terms(s);
+    if terms.is_empty() {
+        return Err(SearchTextError::Empty);
+    }
+
+    Ok(SearchText {
+        text: s.into(),
+        terms,
+    })
+}
+
+impl TryFrom<String> for SearchText {
+    type Error = SearchTextError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        search_text_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for SearchText {
+    type Error = SearchTextError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        search_text_from_str(value)
+    }
+}
+
+impl SearchText {
+    /// 検索文字列を返す。
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// 検索語を返す。
+    pub fn terms(&self) -> &BTreeSet<String> {
+        &self.terms
+    }
+}
+
+/// 検索結果のチケット
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub project: ProjectKey,
+    pub id: TicketId,
+    pub key: TicketKey,
+    pub status: TicketStatus,
+    /// 関連度（大きいほど検索文字列に関連する）
+    pub score: f64,
+    /// 一致した箇所を`<mark>`で囲んだ、HTMLエスケープ済みのタイトル
+    pub title: String,
+    /// 一致した箇所を`<mark>`で囲んだ、HTMLエスケープ済みの説明の抜粋
+    pub snippet: String,
+}
+
+/// 文字の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    /// 空白で区切られる言語の文字や数字
+    Word,
+    /// ひらがな、カタカナ、漢字など、空白で区切られない言語の文字
+    Cjk,
+    /// 区切り文字
+    Separator,
+}
+
+/// 全角英数字を半角に、英字を小文字にする。
+///
+/// 1文字を1文字に変換するため、変換前の文字列の位置をそのまま使用できる。
+fn normalize(c: char) -> char {
+    let c = match c {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        '\u{3000}' => ' ',
+        _ => c,
+    };
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn classify(c: char) -> CharClass {
+    match c {
+        '\u{3005}'
+        | '\u{3040}'..='\u{30FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FF66}'..='\u{FF9F}' => CharClass::Cjk,
+        c if c.is_alphanumeric() => CharClass::Word,
+        _ => CharClass::Separator,
+    }
+}
+
+/// 文字列中の語
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    term: String,
+    /// 文字列中の語のバイト位置
+    span: Range<usize>,
+}
+
+/// 文字列を語に分割する。
+///
+/// 空白で区切られる言語の文字は、連続する文字や数字を1つの語にする。
+/// 空白で区切られない言語の文字は、連続する2文字（bi-gram）と1文字（uni-gram）を語にする。
+///
+/// # 引数
+///
+/// * `text` - 文字列
+///
+/// # 戻り値
+///
+/// 出現順の語
+fn tokenize(text: &str) -> Vec<Token> {
+    let chars: Vec<_> = text
+        .char_indices()
+        .map(|(i, c)| {
+            let c = normalize(c);
+            (i, c, classify(c))
+        })
+        .collect();
+    let end_of = |index: usize| {
+        chars
+            .get(index + 1)
+            .map_or(text.len(), |&(position, _, _)| position)
+    };
+
+    let mut tokens = vec![];
+    let mut start = 0;
+    while start < chars.len() {
+        let class = chars[start].2;
+        let mut end = start + 1;
+        while end < chars.len() && chars[end].2 == class {
+            end += 1;
+        }
+        match class {
+            CharClass::Word => tokens.push(Token {
+                term: chars[start..end].iter().map(|&(_, c, _)| c).collect(),
+                span: chars[start].0..end_of(end - 1),
+            }),
+            CharClass::Cjk => {
+                for i in start..end {
+                    tokens.push(Token {
+                        term: chars[i].1.to_string(),
+                        span: chars[i].0..end_of(i),
+                    });
+                    if i + 1 < end {
+                        tokens.push(Token {
+                            term: [chars[i].1, chars[i + 1].1].iter().collect(),
+                            span: chars[i].0..end_of(i + 1),
+                        });
+                    }
+                }
+            }
+            CharClass::Separator => {}
+        }
+        start = end;
+    }
+
+    tokens
+}
+
+/// 検索文字列を検索語に分割する。
+///
+/// 空白で区切られない言語の文字が2文字以上連続する場合は、bi-gramだけを検索語にする。
+/// そのため、2文字以上の語は、語のbi-gramがすべて出現するチケットに一致する。
+fn query_terms(text: &str) -> BTreeSet<String> {
+    let tokens = tokenize(text);
+    tokens
+        .iter()
+        .filter(|token| {
+            // uni-gramを含むbi-gramは、uni-gramと同じ位置から始まるか、同じ位置で終わる。
+            let mut chars = token.term.chars();
+            let single_cjk = matches!(
+                (chars.next().map(classify), chars.next()),
+                (Some(CharClass::Cjk), None)
+            );
+            !single_cjk
+                || !tokens.iter().any(|other| {
+                    other.term.chars().count() == 2
+                        && (other.span.start == token.span.start
+                            || other.span.end == token.span.end)
+                })
+        })
+        .map(|token| token.term.clone())
+        .collect()
+}
+
+/// 全文検索の対象
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    Title,
+    Description,
+    /// タイトルと説明
+    Any,
+}
+
+/// チケットの検索対象に、検索語がすべて出現するか確認する。
+///
+/// [`SearchIndex::candidates`]と同じ判定を、索引を使用せずに行う。
+///
+/// # 引数
+///
+/// * `ticket` - チケット
+/// * `search` - 検索文字列
+/// * `field` - 検索対象
+///
+/// # 戻り値
+///
+/// 検索語がすべて出現する場合は`true`
+pub fn matches(ticket: &Ticket, search: &SearchText, field: SearchField) -> bool {
+    let mut tokens = vec![];
+    if field != SearchField::Description {
+        tokens.extend(tokenize(&ticket.title.0));
+    }
+    if field != SearchField::Title {
+        tokens.extend(tokenize(&ticket.description.0));
+    }
+    let terms: BTreeSet<_> = tokens.into_iter().map(|token| token.term).collect();
+
+    search.terms().is_subset(&terms)
+}
+
+/// 索引したチケットの語の出現回数
+#[derive(Debug, Clone, Copy, Default)]
+struct Frequency {
+    title: u32,
+    description: u32,
+}
+
+impl Frequency {
+    /// タイトルの重みを加味した出現回数を返す。
+    fn weighted(&self) -> f64 {
+        TITLE_WEIGHT * self.title as f64 + self.description as f64
+    }
+}
+
+/// 索引したチケット
+#[derive(Debug, Clone, Default)]
+struct Document {
+    /// チケットに出現する語
+    terms: BTreeSet<String>,
+    /// タイトルの重みを加味した語の数
+    length: f64,
+}
+
+/// 転置索引に追加する前に数えた、チケットのタイトルと説明の語の出現回数
+///
+/// 語の分割は転置索引のロックを必要としないため、ロックを取得する前に数えておく。
+#[derive(Debug, Clone)]
+pub struct TicketTerms {
+    id: TicketId,
+    frequencies: BTreeMap<String, Frequency>,
+}
+
+impl TicketTerms {
+    /// チケットのタイトルと説明を語に分割して、語ごとの出現回数を数える。
+    pub fn new(ticket: &Ticket) -> Self {
+        let mut frequencies: BTreeMap<String, Frequency> = BTreeMap::new();
+        for token in tokenize(&ticket.title.0) {
+            frequencies.entry(token.term).or_default().title += 1;
+        }
+        for token in tokenize(&ticket.description.0) {
+            frequencies.entry(token.term).or_default().description += 1;
+        }
+
+        Self {
+            id: ticket.id,
+            frequencies,
+        }
+    }
+}
+
+/// チケットのタイトルと説明の転置索引
+///
+/// チケットを追加または更新するたびに、そのチケットの語だけを索引し直す。
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex {
+    /// 語ごとの、語が出現するチケットと出現回数
+    postings: BTreeMap<String, BTreeMap<TicketId, Frequency>>,
+    documents: BTreeMap<TicketId, Document>,
+    /// 索引したチケットの語の数の合計
+    total_length: f64,
+}
+
+impl SearchIndex {
+    /// チケットを索引する。
+    ///
+    /// すでに索引したチケットの場合は、以前の語を取り除いてから索引し直す。
+    ///
+    /// # 引数
+    ///
+    /// * `ticket` - チケット
+    pub fn insert(&mut self, ticket: &Ticket) {
+        self.insert_terms(TicketTerms::new(ticket));
+    }
+
+    /// 語の出現回数を数えたチケットを索引する。
+    ///
+    /// すでに索引したチケットの場合は、以前の語を取り除いてから索引し直す。
+    ///
+    /// # 引数
+    ///
+    /// * `terms` - チケットの語の出現回数
+    pub fn insert_terms(&mut self, terms: TicketTerms) {
+        let TicketTerms { id, frequencies } = terms;
+        self.remove(id);
+
+        let length = frequencies.values().map(Frequency::weighted).sum();
+        for (term, frequency) in &frequencies {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .insert(id, *frequency);
+        }
+        self.total_length += length;
+        self.documents.insert(
+            id,
+            Document {
+                terms: frequencies.into_keys().collect(),
+                length,
+            },
+        );
+    }
+
+    /// チケットを索引から取り除く。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - チケットID
+    pub fn remove(&mut self, id: TicketId) {
+        let Some(document) = self.documents.remove(&id) else {
+            return;
+        };
+        self.total_length -= document.length;
+        for term in document.terms {
+            if let Some(postings) = self.postings.get_mut(&term) {
+                postings.remove(&id);
+                if postings.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+
+    /// 検索対象に検索語がすべて出現するチケットを返す。
+    ///
+    /// # 引数
+    ///
+    /// * `search` - 検索文字列
+    /// * `field` - 検索対象
+    ///
+    /// # 戻り値
+    ///
+    /// チケットID
+    pub fn candidates(&self, search: &SearchText, field: SearchField) -> BTreeSet<TicketId> {
+        let mut candidates: Option<BTreeSet<TicketId>> = None;
+        for term in search.terms() {
+            let found = self
+                .postings
+                .get(term)
+                .into_iter()
+                .flatten()
+                .filter(|(_, frequency)| match field {
+                    SearchField::Title => frequency.title > 0,
+                    SearchField::Description => frequency.description > 0,
+                    SearchField::Any => true,
+                })
+                .map(|(id, _)| *id);
+            candidates = Some(match candidates {
+                None => found.collect(),
+                Some(candidates) => found.filter(|id| candidates.contains(id)).collect(),
+            });
+        }
+
+        candidates.unwrap_or_default()
+    }
+
+    /// すべての検索語を含むチケットを、関連度（BM25）の高い順に検索する。
+    ///
+    /// # 引数
+    ///
+    /// * `text` - 検索文字列
+    ///
+    /// # 戻り値
+    ///
+    /// チケットIDと関連度
+    pub fn search(&self, text: &SearchText) -> Vec<(TicketId, f64)> {
+        let count = self.documents.len() as f64;
+        let average_length = (self.total_length / count).max(1.0);
+        let mut scores: Option<BTreeMap<TicketId, f64>> = None;
+        for term in text.terms() {
+            let Some(postings) = self.postings.get(term) else {
+                return vec![];
+            };
+            let frequency = postings.len() as f64;
+            let idf = (1.0 + (count - frequency + 0.5) / (frequency + 0.5)).ln();
+            let term_scores = postings.iter().map(|(id, frequency)| {
+                let length = self.documents[id].length;
+                let tf = frequency.weighted();
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * length / average_length);
+                (*id, idf * tf * (BM25_K1 + 1.0) / (tf + norm))
+            });
+            scores = Some(match scores {
+                None => term_scores.collect(),
+                Some(mut scores) => {
+                    let term_scores: BTreeMap<_, _> = term_scores.collect();
+                    scores.retain(|id, _| term_scores.contains_key(id));
+                    for (id, score) in scores.iter_mut() {
+                        *score += term_scores[id];
+                    }
+                    scores
+                }
+            });
+        }
+
+        let mut hits: Vec<_> = scores.unwrap_or_default().into_iter().collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        hits
+    }
+}
+
+/// HTMLの特殊文字をエスケープして追加する。
+fn push_escaped(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// 文字列の、検索語に一致した箇所を`<mark>`で囲む。
+///
+/// 文字列が`max_chars`より長い場合は、最初に一致した箇所の周辺を抜粋し、省略した側に`…`を付ける。
+///
+/// # 引数
+///
+/// * `text` - 文字列
+/// * `search` - 検索文字列
+/// * `max_chars` - 抜粋する最大文字数
+///
+/// # 戻り値
+///
+/// HTMLエスケープ済みの文字列
+pub fn highlight(text: &str, search: &SearchText, max_chars: usize) -> String {
+    let mut spans: Vec<Range<usize>> = vec![];
+    for token in tokenize(text) {
+        if !search.terms().contains(&token.term) {
+            continue;
+        }
+        match spans.last_mut() {
+            Some(last) if token.span.start <= last.end => last.end = last.end.max(token.span.end),
+            _ => spans.push(token.span),
+        }
+    }
+
+    let boundaries: Vec<_> = text
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain([text.len()])
+        .collect();
+    let chars = boundaries.len() - 1;
+    let first = spans.first().map_or(0, |span| {
+        boundaries.partition_point(|&position| position < span.start)
+    });
+    let start = first
+        .saturating_sub(SNIPPET_LEADING_CHARS)
+        .min(chars.saturating_sub(max_chars));
+    let end = start.saturating_add(max_chars).min(chars);
+    let window = boundaries[start]..boundaries[end];
+
+    let mut out = String::new();
+    if start > 0 {
+        out.push('…');
+    }
+    let mut position = window.start;
+    for span in spans {
+        let span = span.start.max(window.start)..span.end.min(window.end);
+        if span.is_empty() {
+            continue;
+        }
+        push_escaped(&mut out, &text[position..span.start]);
+        out.push_str("<mark>");
+        push_escaped(&mut out, &text[span.clone()]);
+        out.push_str("</mark>");
+        position = span.end;
+    }
+    push_escaped(&mut out, &text[position..window.end]);
+    if end < chars {
+        out.push('…');
+    }
+
+    out
+}
+
+/// チケットの検索結果を構築する。
+///
+/// # 引数
+///
+/// * `ticket` - チケット
+/// * `search` - 検索文字列
+/// * `score` - 関連度
+///
+/// # 戻り値
+///
+/// 検索結果のチケット
+pub fn hit(ticket: &Ticket, search: &SearchText, score: f64) -> SearchHit {
+    SearchHit {
+        project: ticket.key.project.clone(),
+        id: ticket.id,
+        key: ticket.key.clone(),
+        status: ticket.status,
+        score,
+        title: highlight(&ticket.title.0, search, usize::MAX),
+        snippet: highlight(&ticket.description.0, search, SNIPPET_MAX_CHARS),
+    }
+}
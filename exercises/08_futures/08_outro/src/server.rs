@@ -1,79 +1,2044 @@
-use std::sync::{Arc, RwLock};
+use std::future::{Future, IntoFuture};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
+use axum::body::{Body, Bytes};
+use axum::extract::{DefaultBodyLimit, FromRef, Multipart, Path, Query, Request, State};
+use axum::handler::Handler;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::IntoResponse;
-use axum::response::Response;
-use axum::routing::{get, patch, post};
-use axum::{Json, Router};
+use axum::response::{Redirect, Response};
+use axum::routing::{get, post, put};
+use axum::{middleware, Json, Router};
 use serde_json::json;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
 
-use crate::dto::{TicketDraft, TicketPatch};
-use crate::models::{Ticket, TicketId};
-use crate::store::{TicketStore, TicketStoreError};
+use crate::actor::{Replica, StoreError, StoreHandle};
+use crate::backup::{Backup, BackupError};
+use crate::blobs::{self, AttachmentError, BlobStore};
+use crate::clock::{Clock, PinnedClock, SystemClock};
+use crate::cluster_server::{self, ClusterHandle, ClusterRuntime};
+use crate::config::{Config, LimitsConfig, ReplicationConfig, ReplicationRole, StorageBackend};
+use crate::crypto::Encryption;
+use crate::dto::{
+    AttachmentDraft, DescriptionRendering, EventFilter, ProjectDraft, ProjectPatch, RenderedTicket,
+    ReportFormat, ReportQuery, SavedQueryDraft, SearchQuery, TicketDraft, TicketExpression,
+    TicketMove, TicketParent, TicketPatch, TicketQuery, TicketView, WebhookDraft,
+};
+use crate::events::{ChangeFeed, EventSender, TicketEvent, TicketEventKind};
+use crate::health::{self, Health, StorageState};
+use crate::markdown;
+use crate::middleware::{request_context, request_timeout, require_admin, AdminToken, CurrentUser};
+use crate::models::{
+    AttachmentId, Label, ProjectKey, Ticket, TicketId, TicketKey, TicketRef, DEFAULT_PROJECT_KEY,
+};
+use crate::persistence::{
+    data_dir_encryption, require_encryption, PersistenceError, PersistenceResult,
+};
+use crate::query::{self, QueryError};
+use crate::registry::ProjectRegistry;
+use crate::replication::{self, follower_gate, Follower, ReplicationLog};
+use crate::reports::{self, CsvReport, ReportError, ReportWindow, StatusTimeline};
+use crate::saved_queries::{SavedQueries, SavedQueryError, SavedQueryName};
+use crate::search::{DEFAULT_SEARCH_LIMIT, MAX_SEARCH_LIMIT};
+use crate::store::{Access, TicketStoreError};
+use crate::sync::read_lock;
+use crate::webhooks::{DeliveryId, WebhookError, WebhookId, Webhooks};
+
+/// 添付ファイルのアップロードで、添付ファイルの最大バイト数に加えて受け付けるリクエストボディのバイト数
+///
+/// マルチパートの境界とパートのヘッダに使用する。
+const MULTIPART_OVERHEAD_BYTES: usize = 64 * 1024;
+
+/// バックアップのアーカイブを送信する単位のバイト数
+const BACKUP_CHUNK_BYTES: usize = 64 * 1024;
+
+/// 送信を待つ、バックアップのアーカイブの単位の最大数
+const BACKUP_CHANNEL_CAPACITY: usize = 4;
 
 /// アプリステート
-type SharedState = Arc<RwLock<TicketStore>>;
+#[derive(Clone)]
+pub struct AppState {
+    pub store: StoreHandle,
+    pub health: Arc<Health>,
+    pub webhooks: Arc<Webhooks>,
+    pub saved_queries: Arc<SavedQueries>,
+    pub feed: ChangeFeed,
+    pub blobs: Arc<BlobStore>,
+    /// 永続化するデータとバックアップのアーカイブの暗号化
+    pub encryption: Encryption,
+    /// 管理APIを認証するトークン（`None`の場合は管理APIを無効にする）
+    pub admin: Option<AdminToken>,
+}
 
-pub async fn run() {
-    let shared_state = SharedState::default();
-    let app = Router::new()
-        .route("/", get(|| async { "Hello, World!" }))
-        .route("/tickets", post(register_ticket))
-        .route("/tickets/:ticket_id", get(retrieve_ticket))
-        .route("/tickets/:ticket_id", patch(update_ticket))
-        .with_state(Arc::clone(&shared_state));
+impl FromRef<AppState> for StoreHandle {
+    fn from_ref(state: &AppState) -> Self {
+        state.store.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Health> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.health)
+    }
+}
+
+impl FromRef<AppState> for Arc<Webhooks> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.webhooks)
+    }
+}
+
+impl FromRef<AppState> for Arc<SavedQueries> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.saved_queries)
+    }
+}
+
+impl FromRef<AppState> for Arc<BlobStore> {
+    fn from_ref(state: &AppState) -> Self {
+        Arc::clone(&state.blobs)
+    }
+}
+
+impl FromRef<AppState> for Encryption {
+    fn from_ref(state: &AppState) -> Self {
+        state.encryption.clone()
+    }
+}
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+impl FromRef<AppState> for ChangeFeed {
+    fn from_ref(state: &AppState) -> Self {
+        state.feed.clone()
+    }
+}
+
+/// ルーターを構築する。
+///
+/// # 引数
+///
+/// * `state` - アプリステート
+/// * `limits` - リクエスト制限設定
+///
+/// # 戻り値
+///
+/// ルーター
+pub fn app(state: AppState, limits: &LimitsConfig) -> Router {
+    router(state, limits, None)
+}
+
+/// ルーターを構築する。
+///
+/// フォロワーの場合は、読み込み以外のリクエストをリーダーにリダイレクトする。
+fn router(state: AppState, limits: &LimitsConfig, follower: Option<Arc<Follower>>) -> Router {
+    // マルチパートの境界やヘッダの分だけ、添付ファイルの最大バイト数より大きいリクエストボディを受け付ける。
+    let upload_limit = usize::try_from(state.blobs.max_bytes())
+        .unwrap_or(usize::MAX)
+        .saturating_add(MULTIPART_OVERHEAD_BYTES);
+    // プロジェクトの権限によらず、すべてのプロジェクトや添付ファイルの内容を読み書きするため、管理APIのトークンで認証する。
+    // Webhookも、すべてのプロジェクトのチケットイベントを任意のURLに配信するため、管理APIとして扱う。
+    let admin = Router::new()
+        .route("/webhooks", get(list_webhooks).post(subscribe_webhook))
+        .route(
+            "/webhooks/:webhook_id",
+            get(retrieve_webhook).delete(unsubscribe_webhook),
+        )
+        .route("/webhook-deliveries/dead-letters", get(list_dead_letters))
+        .route(
+            "/webhook-deliveries/:delivery_id/replay",
+            post(replay_delivery),
+        )
+        .route(
+            "/admin/orphaned-blobs",
+            get(list_orphaned_blobs).delete(delete_orphaned_blobs),
+        )
+        .route("/admin/backup", post(backup_archive))
+        .route(
+            "/admin/restore",
+            post(restore_archive.layer(DefaultBodyLimit::max(limits.max_restore_bytes))),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.admin.clone(),
+            require_admin,
+        ));
+    let tickets = Router::new()
+        .route("/projects", get(list_projects).post(register_project))
+        .route(
+            "/projects/:project_key",
+            get(retrieve_project)
+                .patch(update_project)
+                .delete(delete_project),
+        )
+        .route(
+            "/projects/:project_key/tickets",
+            get(list_tickets).post(register_ticket),
+        )
+        .route(
+            "/projects/:project_key/tickets/:ticket_ref",
+            get(retrieve_ticket).patch(update_ticket),
+        )
+        .route(
+            "/projects/:project_key/tickets/:ticket_ref/move",
+            post(move_ticket),
+        )
+        .route(
+            "/projects/:project_key/tickets/:ticket_ref/parent",
+            put(set_parent),
+        )
+        .route(
+            "/projects/:project_key/tickets/:ticket_ref/blockers/:blocker_id",
+            put(add_blocker).delete(remove_blocker),
+        )
+        .route(
+            "/projects/:project_key/tickets/:ticket_ref/labels/:label",
+            put(add_label).delete(remove_label),
+        )
+        .route(
+            "/projects/:project_key/tickets/:ticket_ref/graph",
+            get(retrieve_graph),
+        )
+        .route(
+            "/projects/:project_key/tickets/:ticket_ref/attachments",
+            get(list_attachments)
+                .post(upload_attachment.layer(DefaultBodyLimit::max(upload_limit))),
+        )
+        .route(
+            "/projects/:project_key/tickets/:ticket_ref/attachments/:attachment_id",
+            get(download_attachment).delete(delete_attachment),
+        )
+        .route("/projects/:project_key/open-work", get(list_open_work))
+        .route(
+            "/tickets/:ticket_ref",
+            get(retrieve_default_ticket).patch(update_default_ticket),
+        )
+        .route("/tickets", get(query_tickets).post(register_default_ticket))
+        .route("/search", get(search_tickets))
+        .route("/reports/summary", get(report_summary))
+        .route("/reports/cycle-time", get(report_cycle_time))
+        .route("/reports/throughput", get(report_throughput))
+        .route("/events", get(stream_events))
+        .route("/saved-queries", get(list_saved_queries))
+        .route(
+            "/saved-queries/:name",
+            get(retrieve_saved_query)
+                .put(save_query)
+                .delete(delete_saved_query),
+        )
+        .route("/saved-queries/:name/tickets", get(run_saved_query))
+        .merge(admin)
+        .route_layer(middleware::from_fn_with_state(
+            Arc::clone(&state.health),
+            require_storage_ready,
+        ));
+
+    let router = Router::new()
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .merge(tickets)
+        .with_state(state)
+        .layer(DefaultBodyLimit::max(limits.max_body_bytes))
+        .layer(middleware::from_fn_with_state(
+            limits.request_timeout(),
+            request_timeout,
+        ));
+    let router = match follower {
+        Some(follower) => router.layer(middleware::from_fn_with_state(follower, follower_gate)),
+        None => router,
+    };
+
+    router.layer(middleware::from_fn(request_context))
+}
+
+/// 設定に従ってプロジェクトの一覧を構築する。
+///
+/// `file`ストレージバックエンドの場合は、データディレクトリに永続化されたプロジェクトとチケットを復元する。
+/// 暗号化されたファイルの認証に失敗した場合は、[`PersistenceError::Tampered`]を返す。
+///
+/// # 引数
+///
+/// * `config` - 設定
+/// * `clock` - チケットストアが使用する時計
+///
+/// # 戻り値
+///
+/// プロジェクトの一覧
+pub fn open_registry(config: &Config, clock: Arc<dyn Clock>) -> PersistenceResult<ProjectRegistry> {
+    match (config.storage.backend, &config.storage.data_dir) {
+        (StorageBackend::File, Some(data_dir)) => {
+            let encryption = data_dir_encryption(data_dir, &config.encryption)?;
+            ProjectRegistry::open_encrypted(data_dir, clock, &encryption)
+        }
+        _ => Ok(ProjectRegistry::in_memory(clock)),
+    }
+}
+
+/// 設定に従ってWebhookの配信を構築する。
+///
+/// `file`ストレージバックエンドの場合は、データディレクトリに永続化された購読を復元する。
+///
+/// # 引数
+///
+/// * `config` - 設定
+/// * `clock` - 購読や失敗の日時に使用する時計
+///
+/// # 戻り値
+///
+/// Webhookの配信
+pub fn open_webhooks(config: &Config, clock: Arc<dyn Clock>) -> PersistenceResult<Arc<Webhooks>> {
+    match (config.storage.backend, &config.storage.data_dir) {
+        (StorageBackend::File, Some(data_dir)) => Webhooks::open_encrypted(
+            data_dir,
+            &config.webhooks,
+            clock,
+            &data_dir_encryption(data_dir, &config.encryption)?,
+        ),
+        _ => Ok(Webhooks::in_memory(&config.webhooks, clock)),
+    }
+}
+
+/// 設定に従って、利用者ごとの保存したクエリを構築する。
+///
+/// `file`ストレージバックエンドの場合は、データディレクトリに永続化されたクエリを復元する。
+///
+/// # 引数
+///
+/// * `config` - 設定
+/// * `clock` - 更新日時に使用する時計
+///
+/// # 戻り値
+///
+/// 保存したクエリ
+pub fn open_saved_queries(
+    config: &Config,
+    clock: Arc<dyn Clock>,
+) -> PersistenceResult<Arc<SavedQueries>> {
+    match (config.storage.backend, &config.storage.data_dir) {
+        (StorageBackend::File, Some(data_dir)) => {
+            let encryption = data_dir_encryption(data_dir, &config.encryption)?;
+            SavedQueries::open_encrypted(data_dir, clock, &encryption)
+        }
+        _ => Ok(SavedQueries::in_memory(clock)),
+    }
+}
+
+/// 設定に従って添付ファイルの内容のストアを構築する。
+///
+/// `file`ストレージバックエンドの場合は、データディレクトリの`blobs`ディレクトリに内容を保存する。
+///
+/// # 引数
+///
+/// * `config` - 設定
+///
+/// # 戻り値
+///
+/// 添付ファイルの内容のストア
+pub fn open_blobs(config: &Config) -> PersistenceResult<Arc<BlobStore>> {
+    match (config.storage.backend, &config.storage.data_dir) {
+        (StorageBackend::File, Some(data_dir)) => BlobStore::open_encrypted(
+            data_dir,
+            &config.attachments,
+            &data_dir_encryption(data_dir, &config.encryption)?,
+        ),
+        _ => Ok(BlobStore::in_memory(&config.attachments)),
+    }
+}
+
+/// クラスタモードのルーターを構築する。
+///
+/// 既定のプロジェクトのチケットの登録、取得、更新と、管理APIのトークンで認証するメンバーの変更だけを、
+/// Raftのログで複製するチケットストアで処理する。
+fn cluster_router(state: AppState, limits: &LimitsConfig, cluster: ClusterHandle) -> Router {
+    let admin = state.admin.clone();
+    Router::new()
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .with_state(state)
+        .merge(cluster_server::router(cluster, admin))
+        .layer(DefaultBodyLimit::max(limits.max_body_bytes))
+        .layer(middleware::from_fn_with_state(
+            limits.request_timeout(),
+            request_timeout,
+        ))
+        .layer(middleware::from_fn(request_context))
+}
+
+/// 待ち受けを開始したサーバー
+///
+/// テストなどでは、ポート番号に`0`を指定して構築し、[`Server::local_addr`]で割り当てられたポートを確認してから、
+/// 任意の停止トリガーを渡して[`Server::serve`]を呼び出す。
+pub struct Server {
+    listener: TcpListener,
+    state: AppState,
+    app: Router,
+    config: Config,
+    clock: Arc<dyn Clock>,
+    /// 復元したプロジェクトの一覧に設定する、チケットイベントの送信先
+    events: EventSender,
+    replication: Replication,
+}
+
+/// サーバーの複製での役割と、その状態
+enum Replication {
+    Standalone,
+    Leader {
+        listener: TcpListener,
+        log: Arc<ReplicationLog>,
+    },
+    Follower {
+        leader: SocketAddr,
+        follower: Arc<Follower>,
+    },
+    /// チケットの登録と更新を、Raftのログで他のノードに複製する。
+    Cluster {
+        runtime: Box<ClusterRuntime>,
+    },
+}
+
+impl Server {
+    /// 設定に従って待ち受けを開始する。
+    ///
+    /// 永続化されたチケットの復元は、[`Server::serve`]を呼び出した後にバックグラウンドで実行する。
+    ///
+    /// # 引数
+    ///
+    /// * `config` - 検証済みの設定
+    ///
+    /// # 戻り値
+    ///
+    /// 待ち受けを開始したサーバー
+    pub async fn bind(config: &Config) -> ServerResult<Self> {
+        Self::bind_with_clock(config, Arc::new(SystemClock)).await
+    }
+
+    /// 設定に従って、指定した時計を使用するサーバーの待ち受けを開始する。
+    ///
+    /// # 引数
+    ///
+    /// * `config` - 検証済みの設定
+    /// * `clock` - チケットストアが使用する時計
+    ///
+    /// # 戻り値
+    ///
+    /// 待ち受けを開始したサーバー
+    pub async fn bind_with_clock(config: &Config, clock: Arc<dyn Clock>) -> ServerResult<Self> {
+        let webhooks = open_webhooks(config, Arc::clone(&clock))?;
+        let feed = ChangeFeed::default();
+        // フォロワーのチケットイベントはリーダーでも発生するため、Webhookはリーダーだけが配信する。
+        let events = match config.replication.role {
+            ReplicationRole::Follower => feed.start(None),
+            _ => feed.start(Some(webhooks.start())),
+        };
+        let saved_queries = open_saved_queries(config, Arc::clone(&clock))?;
+        let blobs = open_blobs(config)?;
+        let pinned = Arc::new(PinnedClock::new(clock));
+        let clock: Arc<dyn Clock> = pinned.clone();
+        let store = ProjectRegistry::in_memory(Arc::clone(&clock)).with_events(events.clone());
+        let capacity = config.limits.store_queue_capacity;
+        let (store, replication) = match &config.replication {
+            ReplicationConfig {
+                role: ReplicationRole::Leader,
+                listen: Some(addr),
+                ..
+            } => {
+                let listener =
+                    TcpListener::bind(addr)
+                        .await
+                        .map_err(|source| ServerError::Bind {
+                            addr: *addr,
+                            source,
+                        })?;
+                let log = Arc::new(ReplicationLog::default());
+                let replica = Replica::Leader {
+                    log: Arc::clone(&log),
+                    clock: pinned,
+                };
+                (
+                    StoreHandle::spawn_replica(store, capacity, replica),
+                    Replication::Leader { listener, log },
+                )
+            }
+            ReplicationConfig {
+                role: ReplicationRole::Follower,
+                leader_addr: Some(leader),
+                leader_url: Some(leader_url),
+                ..
+            } => {
+                let replica = Replica::Follower { clock: pinned };
+                (
+                    StoreHandle::spawn_replica(store, capacity, replica),
+                    Replication::Follower {
+                        leader: *leader,
+                        follower: Arc::new(Follower::new(leader_url)),
+                    },
+                )
+            }
+            _ => (StoreHandle::spawn(store, capacity), Replication::Standalone),
+        };
+        let state = AppState {
+            store,
+            health: Arc::new(Health::new(config)),
+            webhooks,
+            saved_queries,
+            feed,
+            blobs,
+            encryption: Encryption::new(&config.encryption),
+            admin: config.admin.token.clone(),
+        };
+        let (app, replication) = match config.cluster.listen {
+            Some(addr) if config.cluster.node_id.is_some() => {
+                let listener = TcpListener::bind(addr)
+                    .await
+                    .map_err(|source| ServerError::Bind { addr, source })?;
+                let (cluster, runtime) = ClusterRuntime::open(config, listener, clock.clone())?;
+                (
+                    cluster_router(state.clone(), &config.limits, cluster),
+                    Replication::Cluster {
+                        runtime: Box::new(runtime),
+                    },
+                )
+            }
+            _ => {
+                let follower = match &replication {
+                    Replication::Follower { follower, .. } => Some(Arc::clone(follower)),
+                    _ => None,
+                };
+                (router(state.clone(), &config.limits, follower), replication)
+            }
+        };
+        let addr = config.server.addr();
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|source| ServerError::Bind { addr, source })?;
+
+        Ok(Self {
+            listener,
+            state,
+            app,
+            config: config.clone(),
+            clock,
+            events,
+            replication,
+        })
+    }
+
+    /// リーダーの場合は、複製を待ち受けているソケットアドレスを返す。
+    pub fn replication_addr(&self) -> Option<SocketAddr> {
+        match &self.replication {
+            Replication::Leader { listener, .. } => listener.local_addr().ok(),
+            _ => None,
+        }
+    }
+
+    /// クラスタモードの場合は、ノード間の通信を待ち受けているソケットアドレスを返す。
+    pub fn cluster_addr(&self) -> Option<SocketAddr> {
+        match &self.replication {
+            Replication::Cluster { runtime } => runtime.local_addr().ok(),
+            _ => None,
+        }
+    }
+
+    /// 複製での役割に従って、フォロワーへの送信またはリーダーからの受信をバックグラウンドで開始する。
+    fn spawn_replication(&mut self) -> Option<JoinHandle<()>> {
+        match std::mem::replace(&mut self.replication, Replication::Standalone) {
+            Replication::Standalone => None,
+            Replication::Leader { listener, log } => {
+                Some(tokio::spawn(replication::serve_leader(listener, log)))
+            }
+            Replication::Follower { leader, follower } => Some(tokio::spawn(
+                follower.follow(leader, self.state.store.clone()),
+            )),
+            Replication::Cluster { runtime } => Some(tokio::spawn(runtime.run())),
+        }
+    }
+
+    /// 待ち受けているソケットアドレスを返す。
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener
+            .local_addr()
+            .expect("待ち受けているソケットはアドレスを持つ")
+    }
+
+    /// 永続化されたチケットをバックグラウンドで復元する。
+    ///
+    /// # 戻り値
+    ///
+    /// 復元に失敗した場合にエラーを受け取るレシーバー
+    fn spawn_replay(&self) -> oneshot::Receiver<PersistenceError> {
+        let (failed_tx, failed_rx) = oneshot::channel();
+        if self.state.health.is_storage_ready() {
+            return failed_rx;
+        }
+
+        let state = self.state.clone();
+        let config = self.config.clone();
+        let clock = Arc::clone(&self.clock);
+        let events = self.events.clone();
+        let data_dir = match (config.storage.backend, &config.storage.data_dir) {
+            (StorageBackend::File, Some(data_dir)) => Some(data_dir.clone()),
+            _ => None,
+        };
+        tokio::spawn(async move {
+            let started_at = std::time::Instant::now();
+            let opened = tokio::task::spawn_blocking(move || open_registry(&config, clock))
+                .await
+                .unwrap_or_else(|e| Err(io::Error::other(e).into()));
+            match opened {
+                Ok(store) => {
+                    if state
+                        .store
+                        .replace(store.with_events(events))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                    state.health.set_storage_state(StorageState::Ready);
+                    tracing::info!(
+                        elapsed_ms = started_at.elapsed().as_millis() as u64,
+                        "チケットの復元が完了しました。"
+                    );
+                    if state.encryption.is_enabled() {
+                        tokio::spawn(reencrypt(state, data_dir));
+                    }
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "チケットを復元できません。");
+                    state
+                        .health
+                        .set_storage_state(StorageState::Failed(e.to_string()));
+                    let _ = failed_tx.send(e);
+                }
+            }
+        });
+
+        failed_rx
+    }
+
+    /// 停止トリガーが完了するまでリクエストを処理する。
+    ///
+    /// 停止トリガーが完了すると新しい接続の受け付けを止め、処理中のリクエストの完了を停止猶予時間まで待つ。
+    /// その後、チケットストアをスナップショットに書き出す。
+    /// 永続化されたチケットの復元に失敗した場合も、同様に停止してエラーを返す。
+    ///
+    /// # 引数
+    ///
+    /// * `shutdown` - 停止トリガー
+    ///
+    /// # 戻り値
+    ///
+    /// 停止結果
+    pub async fn serve<F>(mut self, shutdown: F) -> ServerResult<Shutdown>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let addr = self.local_addr();
+        tracing::info!(%addr, "待ち受けを開始しました。");
+        let replay_failed = self.spawn_replay();
+        let replication = self.spawn_replication();
+        let (triggered_tx, triggered_rx) = oneshot::channel();
+        let (replay_error_tx, replay_error_rx) = oneshot::channel();
+        let feed = self.state.feed.clone();
+        let serve = axum::serve(self.listener, self.app).with_graceful_shutdown(async move {
+            tokio::select! {
+                _ = shutdown => {
+                    tracing::info!("停止要求を受け付けました。処理中のリクエストの完了を待ちます。");
+                }
+                Ok(e) = replay_failed => {
+                    let _ = replay_error_tx.send(e);
+                }
+            }
+            // 変更フィードの配信は終わらないため、閉じなければ処理中のリクエストが完了しない。
+            feed.close();
+            let _ = triggered_tx.send(());
+        });
+        let mut serve = tokio::spawn(serve.into_future());
+
+        let shutdown_timeout = self.config.server.shutdown_timeout();
+        let deadline = async {
+            if triggered_rx.await.is_err() {
+                std::future::pending::<()>().await;
+            }
+            tokio::time::sleep(shutdown_timeout).await;
+        };
+        let outcome = tokio::select! {
+            result = &mut serve => {
+                result
+                    .map_err(|e| ServerError::Serve(io::Error::other(e)))?
+                    .map_err(ServerError::Serve)?;
+                Shutdown::Drained
+            }
+            _ = deadline => {
+                serve.abort();
+                tracing::warn!(
+                    timeout_secs = shutdown_timeout.as_secs(),
+                    "停止猶予時間内に処理中のリクエストが完了しませんでした。"
+                );
+                Shutdown::DeadlineExceeded
+            }
+        };
+
+        if let Some(replication) = replication {
+            replication.abort();
+        }
+        if let Ok(e) = replay_error_rx.await {
+            return Err(e.into());
+        }
+        // 復元が完了していないチケットストアを書き出すと、永続化されたチケットが失われる。
+        if self.state.health.is_storage_ready() {
+            self.state.store.checkpoint().await?;
+        }
+        tracing::info!(?outcome, "サーバーを停止しました。");
+
+        Ok(outcome)
+    }
+}
+
+/// 現在の鍵で暗号化されていない永続化したファイルを、バックグラウンドで暗号化し直す。
+///
+/// 鍵のローテーションや暗号化を有効にした後の起動で、以前の鍵で暗号化されたファイルと暗号化されていないファイルを、
+/// 現在の鍵で暗号化し直す。
+/// プロジェクトは1つずつ、そのプロジェクトのチケットの書き込みロックを保持して暗号化し直すため、
+/// その間も他のプロジェクトのリクエストを処理できる。
+/// すべて暗号化し終えたら、以降は暗号化されていないファイルを読み込まないように、データディレクトリの暗号化を必須にする。
+///
+/// # 引数
+///
+/// * `state` - アプリケーションの状態
+/// * `data_dir` - `file`ストレージバックエンドの場合はデータディレクトリ
+async fn reencrypt(state: AppState, data_dir: Option<PathBuf>) {
+    let started_at = std::time::Instant::now();
+    let mut projects = 0;
+    loop {
+        match state.store.reencrypt_next().await {
+            Ok(Some(project_key)) => {
+                tracing::debug!(%project_key, "プロジェクトを暗号化し直しました。");
+                projects += 1;
+            }
+            Ok(None) => break,
+            Err(e) => {
+                tracing::error!(error = %e, "プロジェクトを暗号化し直せません。");
+                return;
+            }
+        }
+    }
+
+    let _share = state.blobs.share().await;
+    let webhooks = Arc::clone(&state.webhooks);
+    let saved_queries = Arc::clone(&state.saved_queries);
+    let blobs = Arc::clone(&state.blobs);
+    let reencrypted = tokio::task::spawn_blocking(move || -> PersistenceResult<usize> {
+        webhooks.reencrypt()?;
+        saved_queries.reencrypt()?;
+        let blobs = blobs.reencrypt()?;
+        if let Some(data_dir) = data_dir {
+            require_encryption(&data_dir)?;
+        }
+        Ok(blobs)
+    })
+    .await
+    .unwrap_or_else(|e| Err(io::Error::other(e).into()));
+    match reencrypted {
+        Ok(blobs) => tracing::info!(
+            projects,
+            blobs,
+            elapsed_ms = started_at.elapsed().as_millis() as u64,
+            "現在の鍵での暗号化が完了しました。"
+        ),
+        Err(e) => tracing::error!(error = %e, "ファイルを暗号化し直せません。"),
+    }
 }
 
-/// チケットをチケットストアに登録する。
+/// サーバーの停止結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shutdown {
+    /// 処理中のリクエストがすべて完了した。
+    Drained,
+    /// 停止猶予時間内に処理中のリクエストが完了しなかったため、打ち切った。
+    DeadlineExceeded,
+}
+
+/// `SIGINT`または`SIGTERM`を受信するまで待つ。
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!(error = %e, "SIGINTを待ち受けできません。");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "SIGTERMを待ち受けできません。");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// 設定に従ってサーバーを起動し、`SIGINT`または`SIGTERM`を受信したら停止する。
+///
+/// # 引数
+///
+/// * `config` - 検証済みの設定
+///
+/// # 戻り値
+///
+/// 停止結果
+pub async fn run(config: Config) -> ServerResult<Shutdown> {
+    Server::bind(&config).await?.serve(shutdown_signal()).await
+}
+
+/// サーバーエラー
+#[derive(Debug, thiserror::Error)]
+pub enum ServerError {
+    #[error("チケットストアを開けません: {0}")]
+    Storage(#[from] PersistenceError),
+    #[error("{addr}で待ち受けできません: {source}")]
+    Bind { addr: SocketAddr, source: io::Error },
+    #[error("サーバーの実行中にエラーが発生しました: {0}")]
+    Serve(#[source] io::Error),
+    #[error("チケットストアを書き出せません: {0}")]
+    Checkpoint(#[from] StoreError),
+}
+
+/// サーバー結果
+pub type ServerResult<T> = Result<T, ServerError>;
+
+/// ストレージの復元が完了するまで、`503 Service Unavailable`を返すミドルウェア
+async fn require_storage_ready(
+    State(health): State<Arc<Health>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if health.is_storage_ready() {
+        return next.run(request).await;
+    }
+
+    let body = Json(json!({"error": "チケットストアの準備ができていません。"}));
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, "1")],
+        body,
+    )
+        .into_response()
+}
+
+/// リクエストを処理できなかった理由
+enum Rejection {
+    /// 指定されたプロジェクトまたはチケットのURIが変わったため、変更後のURIにリダイレクトする。
+    Moved(String),
+    /// チケットストアでエラーが発生した。
+    Store(StoreError),
+    /// クエリが不正である。
+    Query(QueryError),
+    /// 保存したクエリを操作できない。
+    SavedQuery(SavedQueryError),
+    /// 添付ファイルを読み書きできない。
+    Attachment(AttachmentError),
+    /// レポートの条件が不正である。
+    Report(ReportError),
+    /// アーカイブから復元できない。
+    Backup(BackupError),
+}
+
+impl From<StoreError> for Rejection {
+    fn from(value: StoreError) -> Self {
+        Self::Store(value)
+    }
+}
+
+impl From<QueryError> for Rejection {
+    fn from(value: QueryError) -> Self {
+        Self::Query(value)
+    }
+}
+
+impl From<SavedQueryError> for Rejection {
+    fn from(value: SavedQueryError) -> Self {
+        Self::SavedQuery(value)
+    }
+}
+
+impl From<AttachmentError> for Rejection {
+    fn from(value: AttachmentError) -> Self {
+        Self::Attachment(value)
+    }
+}
+
+impl From<ReportError> for Rejection {
+    fn from(value: ReportError) -> Self {
+        Self::Report(value)
+    }
+}
+
+impl From<BackupError> for Rejection {
+    fn from(value: BackupError) -> Self {
+        Self::Backup(value)
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        match self {
+            // `308 Permanent Redirect`は、リダイレクト先にも同じメソッドとボディでリクエストさせる。
+            Self::Moved(location) => Redirect::permanent(&location).into_response(),
+            Self::Store(e) => e.into_response(),
+            Self::Query(e) => e.into_response(),
+            Self::SavedQuery(e) => e.into_response(),
+            Self::Attachment(e) => e.into_response(),
+            Self::Report(e) => e.into_response(),
+            Self::Backup(e) => e.into_response(),
+        }
+    }
+}
+
+/// ハンドラ結果
+type HandlerResult = Result<Response, Rejection>;
+
+/// `/projects/:project_key/tickets/:ticket_ref/...`形式のURIの、プロジェクトキーとチケットの指定を置き換える。
+///
+/// # 引数
+///
+/// * `uri` - リクエストされたURI
+/// * `project_key` - 置き換え後のプロジェクトキー
+/// * `ticket_key` - 置き換え後のチケットキー、チケットの指定を置き換えない場合は`None`
+///
+/// # 戻り値
+///
+/// 置き換えた後のURI
+fn relocate(uri: &Uri, project_key: &ProjectKey, ticket_key: Option<&TicketKey>) -> String {
+    let project_key = project_key.to_string();
+    let ticket_key = ticket_key.map(ToString::to_string);
+    let mut segments: Vec<_> = uri.path().split('/').collect();
+    if let Some(segment) = segments.get_mut(2) {
+        *segment = &project_key;
+    }
+    if let (Some(segment), Some(ticket_key)) = (segments.get_mut(4), &ticket_key) {
+        *segment = ticket_key;
+    }
+    let path = segments.join("/");
+
+    match uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path,
+    }
+}
+
+/// チケットストアのアクターのエラーを、リクエストを処理できなかった理由に変換する。
+///
+/// プロジェクトキーまたはチケットキーが変わっている場合は、リクエストされたURIを置き換えたURIにリダイレクトする。
+fn relocated(uri: &Uri) -> impl FnOnce(StoreError) -> Rejection + '_ {
+    move |error| match error {
+        StoreError::ProjectRenamed(current) => Rejection::Moved(relocate(uri, &current, None)),
+        StoreError::TicketMoved(key) => Rejection::Moved(relocate(uri, &key.project, Some(&key))),
+        e => e.into(),
+    }
+}
+
+/// プロジェクトの、条件に一致するチケットの一覧を取得する。
+async fn list_tickets(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key,)): Path<(ProjectKey,)>,
+    Query(query): Query<TicketQuery>,
+) -> HandlerResult {
+    let tickets = store
+        .list_tickets(project_key, user.0, query)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok(Json(tickets).into_response())
+}
+
+/// チケットをプロジェクトに登録する。
 async fn register_ticket(
-    State(state): State<SharedState>,
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key,)): Path<(ProjectKey,)>,
     Json(payload): Json<TicketDraft>,
-) -> impl IntoResponse {
-    let id = state.write().unwrap().add_ticket(payload);
+) -> HandlerResult {
+    let ticket = store
+        .create_ticket(project_key, user.0, payload)
+        .await
+        .map_err(relocated(&uri))?;
 
-    Json(json!({"id": id})).into_response()
+    Ok(Json(json!({"id": ticket.id, "key": ticket.key})).into_response())
 }
 
-/// チケットストアからチケットを取得する。
+/// プロジェクトのチケットを取得する。
+///
+/// チケットキーが変わっている場合は、変更後のチケットキーにリダイレクトする。
+/// `render=html`を指定した場合は、プロジェクトの書式に従って説明を変換したHTMLを`descriptionHtml`に含める。
 async fn retrieve_ticket(
-    State(state): State<SharedState>,
-    Path((ticket_id,)): Path<(u64,)>,
-) -> impl IntoResponse {
-    let ticket_id = TicketId(ticket_id);
-    match state.read().unwrap().get(ticket_id) {
-        Ok(ticket) => ticket.into_response(),
-        Err(e) => e.into_response(),
-    }
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key, ticket_ref)): Path<(ProjectKey, TicketRef)>,
+    Query(view): Query<TicketView>,
+) -> HandlerResult {
+    let ticket = store
+        .get_ticket(project_key, user.0.clone(), ticket_ref)
+        .await
+        .map_err(relocated(&uri))?;
+    let Some(DescriptionRendering::Html) = view.render else {
+        return Ok(ticket.into_response());
+    };
+    let project = store
+        .get_project(ticket.key.project.clone(), user.0)
+        .await
+        .map_err(relocated(&uri))?;
+    let description_html = markdown::render_html(
+        &ticket.description,
+        project.description_format,
+        &project.key,
+    );
+
+    Ok(Json(RenderedTicket {
+        ticket,
+        description_html,
+    })
+    .into_response())
 }
 
-/// チケットストアに登録されているチケットを更新する。
+/// プロジェクトのチケットを更新する。
+///
+/// チケットキーが変わっている場合は、変更後のチケットキーにリダイレクトする。
 async fn update_ticket(
-    State(state): State<SharedState>,
-    Path((ticket_id,)): Path<(u64,)>,
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key, ticket_ref)): Path<(ProjectKey, TicketRef)>,
     Json(payload): Json<TicketPatch>,
-) -> impl IntoResponse {
-    let id = TicketId(ticket_id);
-    match state.write().unwrap().update_ticket(id, payload) {
-        Ok(_) => StatusCode::OK.into_response(),
-        Err(e) => e.into_response(),
+) -> HandlerResult {
+    store
+        .update_ticket(project_key, user.0, ticket_ref, payload)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+/// チケットを他のプロジェクトに移動する。
+///
+/// 移動前のチケットキーは、移動後のチケットキーにリダイレクトする。
+async fn move_ticket(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key, ticket_ref)): Path<(ProjectKey, TicketRef)>,
+    Json(payload): Json<TicketMove>,
+) -> HandlerResult {
+    let key = store
+        .move_ticket(project_key, user.0, ticket_ref, payload.project)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok(Json(json!({"key": key})).into_response())
+}
+
+/// チケットの親チケットを設定する。
+async fn set_parent(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key, ticket_ref)): Path<(ProjectKey, TicketRef)>,
+    Json(payload): Json<TicketParent>,
+) -> HandlerResult {
+    store
+        .set_parent(project_key, user.0, ticket_ref, payload.parent)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+/// チケットにブロッカーを追加する。
+async fn add_blocker(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key, ticket_ref, blocker)): Path<(ProjectKey, TicketRef, TicketId)>,
+) -> HandlerResult {
+    store
+        .add_blocker(project_key, user.0, ticket_ref, blocker)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+/// チケットからブロッカーを取り除く。
+async fn remove_blocker(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key, ticket_ref, blocker)): Path<(ProjectKey, TicketRef, TicketId)>,
+) -> HandlerResult {
+    store
+        .remove_blocker(project_key, user.0, ticket_ref, blocker)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// チケットにラベルを追加する。
+async fn add_label(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key, ticket_ref, label)): Path<(ProjectKey, TicketRef, Label)>,
+) -> HandlerResult {
+    store
+        .add_label(project_key, user.0, ticket_ref, label)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+/// チケットからラベルを取り除く。
+async fn remove_label(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key, ticket_ref, label)): Path<(ProjectKey, TicketRef, Label)>,
+) -> HandlerResult {
+    store
+        .remove_label(project_key, user.0, ticket_ref, label)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// チケットの依存関係グラフを取得する。
+async fn retrieve_graph(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key, ticket_ref)): Path<(ProjectKey, TicketRef)>,
+) -> HandlerResult {
+    let graph = store
+        .dependency_graph(project_key, user.0, ticket_ref)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok(Json(graph).into_response())
+}
+
+/// チケットの添付ファイルの一覧を取得する。
+async fn list_attachments(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key, ticket_ref)): Path<(ProjectKey, TicketRef)>,
+) -> HandlerResult {
+    let ticket = store
+        .get_ticket(project_key, user.0, ticket_ref)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok(Json(ticket.attachments).into_response())
+}
+
+/// マルチパートの`file`フィールドのファイルを、チケットに添付する。
+///
+/// MIMEタイプは利用者の指定を信頼せず、内容から判定する。
+/// 同じ内容の添付ファイルは、保存されている内容を共有する。
+async fn upload_attachment(
+    State(store): State<StoreHandle>,
+    State(blobs): State<Arc<BlobStore>>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key, ticket_ref)): Path<(ProjectKey, TicketRef)>,
+    mut multipart: Multipart,
+) -> HandlerResult {
+    // 書き込みを許可されていない利用者の内容は、保存する前に拒否する。
+    store
+        .authorize(project_key.clone(), user.0.clone(), Access::Write)
+        .await
+        .map_err(relocated(&uri))?;
+    let (file_name, content) = loop {
+        let mut field = multipart
+            .next_field()
+            .await
+            .map_err(AttachmentError::from)?
+            .ok_or(AttachmentError::MissingFile)?;
+        if field.name() != Some("file") {
+            continue;
+        }
+        let file_name = blobs::sanitize_file_name(field.file_name().unwrap_or_default());
+        let mut content = vec![];
+        while let Some(chunk) = field.chunk().await.map_err(AttachmentError::from)? {
+            if (content.len() + chunk.len()) as u64 > blobs.max_bytes() {
+                return Err(AttachmentError::TooLarge(blobs.max_bytes()).into());
+            }
+            content.extend_from_slice(&chunk);
+        }
+        break (file_name, content);
+    };
+    let content_type = blobs::sniff_content_type(&content).to_string();
+    let size = content.len() as u64;
+
+    // 内容を保存してから添付ファイルを追加するまでの間に、参照されていない内容として削除させない。
+    let _shared = blobs.share().await;
+    let digest = {
+        let blobs = Arc::clone(&blobs);
+        tokio::task::spawn_blocking(move || blobs.put(&content))
+            .await
+            .map_err(io::Error::other)
+            .and_then(|result| result)
+            .map_err(AttachmentError::from)?
+    };
+    let draft = AttachmentDraft {
+        id: AttachmentId(Uuid::new_v4()),
+        file_name,
+        content_type,
+        size,
+        digest,
+    };
+    let attachment = store
+        .add_attachment(project_key, user.0, ticket_ref, draft)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok((StatusCode::CREATED, Json(attachment)).into_response())
+}
+
+/// チケットの添付ファイルをダウンロードする。
+///
+/// `Range`ヘッダで1つの範囲を指定した場合は、その範囲だけを`206 Partial Content`で返す。
+async fn download_attachment(
+    State(store): State<StoreHandle>,
+    State(blobs): State<Arc<BlobStore>>,
+    uri: Uri,
+    user: CurrentUser,
+    headers: HeaderMap,
+    Path((project_key, ticket_ref, attachment_id)): Path<(ProjectKey, TicketRef, AttachmentId)>,
+) -> HandlerResult {
+    let ticket = store
+        .get_ticket(project_key, user.0, ticket_ref)
+        .await
+        .map_err(relocated(&uri))?;
+    let attachment = ticket
+        .attachments
+        .into_iter()
+        .find(|attachment| attachment.id == attachment_id)
+        .ok_or(StoreError::Store(TicketStoreError::AttachmentNotFound))?;
+
+    let size = blobs
+        .size(&attachment.digest)
+        .map_err(AttachmentError::from)?
+        .ok_or(AttachmentError::MissingBlob)?;
+    let range = match headers.get(header::RANGE).and_then(|v| v.to_str().ok()) {
+        Some(value) => match blobs::parse_range(value, size) {
+            Ok(range) => Some(range),
+            Err(e) => {
+                let body = Json(json!({"error": format!("{e}")}));
+                return Ok((
+                    StatusCode::RANGE_NOT_SATISFIABLE,
+                    [(header::CONTENT_RANGE, format!("bytes */{size}"))],
+                    body,
+                )
+                    .into_response());
+            }
+        },
+        None => None,
+    };
+    let content = {
+        let blobs = Arc::clone(&blobs);
+        let digest = attachment.digest.clone();
+        let range = range.clone().unwrap_or(0..size);
+        tokio::task::spawn_blocking(move || blobs.read(&digest, range))
+            .await
+            .map_err(io::Error::other)
+            .and_then(|result| result)
+            .map_err(AttachmentError::from)?
+    };
+
+    let mut response = content.into_response();
+    let response_headers = response.headers_mut();
+    let header_value = |value: &str| {
+        HeaderValue::from_str(value).unwrap_or_else(|_| HeaderValue::from_static("attachment"))
+    };
+    response_headers.insert(header::CONTENT_TYPE, header_value(&attachment.content_type));
+    response_headers.insert(
+        header::CONTENT_DISPOSITION,
+        header_value(&blobs::content_disposition(&attachment.file_name)),
+    );
+    response_headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    response_headers.insert(
+        header::ETAG,
+        header_value(&format!("\"{}\"", attachment.digest)),
+    );
+    if let Some(range) = range {
+        *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+        response.headers_mut().insert(
+            header::CONTENT_RANGE,
+            header_value(&format!("bytes {}-{}/{size}", range.start, range.end - 1)),
+        );
     }
+
+    Ok(response)
 }
 
-impl IntoResponse for &Ticket {
+/// チケットから添付ファイルを取り除く。
+///
+/// どの添付ファイルからも参照されなくなった内容は、あわせて削除する。
+async fn delete_attachment(
+    State(store): State<StoreHandle>,
+    State(blobs): State<Arc<BlobStore>>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key, ticket_ref, attachment_id)): Path<(ProjectKey, TicketRef, AttachmentId)>,
+) -> HandlerResult {
+    let _exclusive = blobs.exclusive().await;
+    let removed = store
+        .remove_attachment(project_key, user.0, ticket_ref, attachment_id)
+        .await
+        .map_err(relocated(&uri))?;
+    if removed.references == 0 {
+        // チケットからは取り除いたため、内容を削除できなくても参照されていない内容として後で削除できる。
+        if let Err(e) = blobs.delete(&removed.attachment.digest) {
+            tracing::warn!(
+                error = %e,
+                digest = %removed.attachment.digest,
+                "添付ファイルの内容を削除できません。"
+            );
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// どの添付ファイルからも参照されていない内容の一覧を取得する。
+async fn list_orphaned_blobs(
+    State(store): State<StoreHandle>,
+    State(blobs): State<Arc<BlobStore>>,
+) -> HandlerResult {
+    let _exclusive = blobs.exclusive().await;
+    let referenced = store.referenced_blobs().await?;
+    let orphans = blobs.orphans(&referenced).map_err(AttachmentError::from)?;
+
+    Ok(Json(orphans).into_response())
+}
+
+/// どの添付ファイルからも参照されていない内容を削除する。
+///
+/// # 戻り値
+///
+/// 削除した内容の一覧
+async fn delete_orphaned_blobs(
+    State(store): State<StoreHandle>,
+    State(blobs): State<Arc<BlobStore>>,
+) -> HandlerResult {
+    let _exclusive = blobs.exclusive().await;
+    let referenced = store.referenced_blobs().await?;
+    let orphans = blobs.orphans(&referenced).map_err(AttachmentError::from)?;
+    for blob in &orphans {
+        blobs.delete(&blob.digest).map_err(AttachmentError::from)?;
+    }
+    tracing::info!(
+        count = orphans.len(),
+        "参照されていない添付ファイルの内容を削除しました。"
+    );
+
+    Ok(Json(orphans).into_response())
+}
+
+/// すべてのプロジェクトの同じ時点のバックアップを、アーカイブとして送信する。
+///
+/// アーカイブは書き出しながら送信するため、アーカイブ全体をメモリに保持しない。
+/// 暗号化が有効な場合は、アーカイブを現在の鍵で暗号化する。
+async fn backup_archive(
+    State(store): State<StoreHandle>,
+    State(encryption): State<Encryption>,
+) -> HandlerResult {
+    let backup = store.backup().await?;
+    let (extension, content_type) = if encryption.is_enabled() {
+        ("json.enc", "application/octet-stream")
+    } else {
+        ("json", "application/json")
+    };
+    let file_name = format!(
+        "ticket-store-{}.{extension}",
+        backup.created_at.format("%Y%m%dT%H%M%SZ")
+    );
+    let (sender, receiver) = mpsc::channel(BACKUP_CHANNEL_CAPACITY);
+    tokio::task::spawn_blocking(move || {
+        let mut writer = encryption.archive_writer(io::BufWriter::with_capacity(
+            BACKUP_CHUNK_BYTES,
+            ChunkWriter(sender),
+        ));
+        if let Err(e) = backup
+            .write(&mut writer)
+            .and_then(|()| writer.finish())
+            .and_then(|mut writer| writer.flush())
+        {
+            tracing::warn!(error = %e, "バックアップを送信できません。");
+        }
+    });
+    let chunks = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        let chunk = receiver.recv().await?;
+        Some((Ok::<_, io::Error>(chunk), receiver))
+    });
+    let headers = [
+        (header::CONTENT_TYPE, content_type.to_string()),
+        (
+            header::CONTENT_DISPOSITION,
+            blobs::content_disposition(&file_name),
+        ),
+    ];
+
+    Ok((headers, Body::from_stream(chunks)).into_response())
+}
+
+/// 書き込まれたバイト列を、チャネルに送信するライター
+///
+/// 受信側が停止した場合は、[`io::ErrorKind::BrokenPipe`]を返す。
+struct ChunkWriter(mpsc::Sender<Bytes>);
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Bytes::copy_from_slice(buf))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// アーカイブを検証して、すべてのプロジェクトをアーカイブから復元したプロジェクトに置き換える。
+///
+/// 以前の形式バージョンのアーカイブは、現在の形式バージョンに変換してから復元する。
+/// 暗号化されたアーカイブは、現在の鍵または以前の鍵で復号してから検証する。
+/// アーカイブ全体を検証してから置き換えるため、検証に失敗した場合はプロジェクトを変更しない。
+async fn restore_archive(
+    State(store): State<StoreHandle>,
+    State(encryption): State<Encryption>,
+    body: Bytes,
+) -> HandlerResult {
+    let backup = tokio::task::spawn_blocking(move || {
+        let archive = encryption
+            .open_archive(&body)
+            .map_err(BackupError::Decrypt)?;
+        Backup::read(&archive)
+    })
+    .await
+    .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))?;
+    let projects = backup.projects.len();
+    let tickets = backup.tickets();
+    store.restore(backup).await?;
+    tracing::info!(projects, tickets, "バックアップから復元しました。");
+
+    Ok(Json(json!({"projects": projects, "tickets": tickets})).into_response())
+}
+
+/// プロジェクトの完了していないチケットを、着手できる順番に取得する。
+async fn list_open_work(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key,)): Path<(ProjectKey,)>,
+) -> HandlerResult {
+    let tickets = store
+        .open_work(project_key, user.0)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok(Json(tickets).into_response())
+}
+
+/// `/tickets`以下のURIを、既定のプロジェクトの同じURIに置き換える。
+fn default_project_uri(uri: &Uri) -> Uri {
+    let path = uri
+        .path_and_query()
+        .map_or("/tickets", |path| path.as_str());
+
+    format!("/projects/{DEFAULT_PROJECT_KEY}{path}")
+        .parse()
+        .expect("既定のプロジェクトキーを加えたURIも正しいURIである")
+}
+
+/// チケットキーから、チケットのプロジェクトのURIにリダイレクトする。
+async fn locate_ticket(store: &StoreHandle, ticket_key: TicketKey) -> Rejection {
+    let project = match store.locate_project(ticket_key.project).await {
+        Ok(project) => project,
+        Err(e) => return e.into(),
+    };
+    let key = TicketKey {
+        project,
+        number: ticket_key.number,
+    };
+
+    Rejection::Moved(format!("/projects/{}/tickets/{key}", key.project))
+}
+
+/// 既定のプロジェクトにチケットを登録する。
+///
+/// プロジェクトを導入する前のクライアントのため、`/projects/TICKET/tickets`の別名として扱う。
+async fn register_default_ticket(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    payload: Json<TicketDraft>,
+) -> HandlerResult {
+    let uri = default_project_uri(&uri);
+    register_ticket(
+        State(store),
+        uri,
+        user,
+        Path((ProjectKey::default(),)),
+        payload,
+    )
+    .await
+}
+
+/// チケットIDで指定した既定のプロジェクトのチケットを取得する。
+///
+/// チケットキーで指定した場合は、チケットキーのプロジェクトのURIにリダイレクトする。
+async fn retrieve_default_ticket(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((ticket_ref,)): Path<(TicketRef,)>,
+    view: Query<TicketView>,
+) -> HandlerResult {
+    if let TicketRef::Key(key) = ticket_ref {
+        return Err(locate_ticket(&store, key).await);
+    }
+    let uri = default_project_uri(&uri);
+    let path = Path((ProjectKey::default(), ticket_ref));
+    retrieve_ticket(State(store), uri, user, path, view).await
+}
+
+/// チケットIDで指定した既定のプロジェクトのチケットを更新する。
+///
+/// チケットキーで指定した場合は、チケットキーのプロジェクトのURIにリダイレクトする。
+async fn update_default_ticket(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((ticket_ref,)): Path<(TicketRef,)>,
+    payload: Json<TicketPatch>,
+) -> HandlerResult {
+    if let TicketRef::Key(key) = ticket_ref {
+        return Err(locate_ticket(&store, key).await);
+    }
+    let uri = default_project_uri(&uri);
+    let path = Path((ProjectKey::default(), ticket_ref));
+    update_ticket(State(store), uri, user, path, payload).await
+}
+
+/// 利用者が読み込みを許可されたすべてのプロジェクトから、クエリの条件を満たすチケットを取得する。
+async fn query_tickets(
+    State(store): State<StoreHandle>,
+    user: CurrentUser,
+    Query(query): Query<TicketExpression>,
+) -> HandlerResult {
+    let expr = query::parse(&query.q)?;
+
+    Ok(Json(store.query(expr, user.0).await?).into_response())
+}
+
+/// チケットのタイトルと説明を全文検索する。
+///
+/// プロジェクトを指定しない場合は、利用者が読み込みを許可されたすべてのプロジェクトを検索する。
+async fn search_tickets(
+    State(store): State<StoreHandle>,
+    user: CurrentUser,
+    Query(query): Query<SearchQuery>,
+) -> HandlerResult {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .min(MAX_SEARCH_LIMIT);
+    let hits = store.search(query.q, query.project, user.0, limit).await?;
+
+    Ok(Json(hits).into_response())
+}
+
+/// ステータスごとのチケットの数を集計する。
+///
+/// 集計期間にかかわらず、現在のステータスを集計する。
+async fn report_summary(
+    State(store): State<StoreHandle>,
+    user: CurrentUser,
+    Query(query): Query<ReportQuery>,
+) -> HandlerResult {
+    report(store, user, query, |timelines, _| {
+        reports::summary(timelines)
+    })
+    .await
+}
+
+/// 集計期間に完了したチケットのサイクルタイムを集計する。
+async fn report_cycle_time(
+    State(store): State<StoreHandle>,
+    user: CurrentUser,
+    Query(query): Query<ReportQuery>,
+) -> HandlerResult {
+    report(store, user, query, reports::cycle_time).await
+}
+
+/// 集計期間に完了したチケットの数を、日または週ごとに集計する。
+async fn report_throughput(
+    State(store): State<StoreHandle>,
+    user: CurrentUser,
+    Query(query): Query<ReportQuery>,
+) -> HandlerResult {
+    let bucket = query.bucket;
+    report(store, user, query, move |timelines, window| {
+        reports::throughput(timelines, window, bucket)
+    })
+    .await
+}
+
+/// 利用者が読み込みを許可されたプロジェクトのステータスの遷移から、レポートを集計する。
+///
+/// ステータスの遷移はプロジェクトごとに読み込みロックを取得して複製し、集計はブロッキングスレッドで行うため、
+/// チケットストアのアクターとチケットの書き込みを妨げない。
+///
+/// # 引数
+///
+/// * `store` - チケットストアのアクターのハンドル
+/// * `user` - 利用者
+/// * `query` - レポートの条件
+/// * `compute` - ステータスの遷移と集計期間からレポートを集計する関数
+///
+/// # 戻り値
+///
+/// JSONまたはCSVのレポート
+async fn report<R, F>(
+    store: StoreHandle,
+    user: CurrentUser,
+    query: ReportQuery,
+    compute: F,
+) -> HandlerResult
+where
+    R: CsvReport + serde::Serialize + Send + 'static,
+    F: FnOnce(&[StatusTimeline], ReportWindow) -> R + Send + 'static,
+{
+    let sources = store.report_sources(query.project, user.0).await?;
+    let window = ReportWindow::new(query.from, query.to, sources.now.date_naive())?;
+    let report = tokio::task::spawn_blocking(move || {
+        let timelines: Vec<_> = sources
+            .stores
+            .iter()
+            .map(|store| read_lock(store).status_timeline())
+            .collect();
+        compute(&timelines, window)
+    })
+    .await
+    .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+
+    Ok(match query.format {
+        ReportFormat::Json => Json(report).into_response(),
+        ReportFormat::Csv => (
+            [(header::CONTENT_TYPE, "text/csv; charset=utf-8")],
+            report.to_csv(),
+        )
+            .into_response(),
+    })
+}
+
+/// チケットの変更フィードをServer-Sent Eventsで配信する。
+///
+/// 利用者が読み込みを許可されたプロジェクトのチケットイベントだけを配信する。
+/// 受信が遅れてチケットイベントが欠落した場合は、欠落した数を`lagged`イベントで通知する。
+async fn stream_events(
+    State(store): State<StoreHandle>,
+    State(feed): State<ChangeFeed>,
+    user: CurrentUser,
+    Query(filter): Query<EventFilter>,
+) -> HandlerResult {
+    if let Some(project_key) = &filter.project {
+        store
+            .authorize(project_key.clone(), user.0.clone(), Access::Read)
+            .await?;
+    }
+    let events = futures_util::stream::unfold(feed.subscribe(), move |mut receiver| {
+        let store = store.clone();
+        let user = user.clone();
+        let project = filter.project.clone();
+        async move {
+            loop {
+                let event = match receiver.recv().await? {
+                    Ok(event) => event,
+                    Err(skipped) => {
+                        let lagged = Event::default()
+                            .event("lagged")
+                            .data(json!({"skipped": skipped}).to_string());
+                        return Some((Ok::<_, axum::Error>(lagged), receiver));
+                    }
+                };
+                if is_visible(&store, &event, &user, project.as_ref()).await {
+                    let sse = Event::default()
+                        .event(event.kind.as_str())
+                        .json_data(&event);
+                    return Some((sse, receiver));
+                }
+            }
+        }
+    });
+
+    Ok(Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+/// チケットイベントを変更フィードの購読者に配信するか判定する。
+///
+/// 他のプロジェクトに移動したチケットのイベントは、移動元のプロジェクトを指定した購読者にも配信する。
+async fn is_visible(
+    store: &StoreHandle,
+    event: &TicketEvent,
+    user: &CurrentUser,
+    project: Option<&ProjectKey>,
+) -> bool {
+    let selected = project.is_none_or(|project| {
+        event.project == *project
+            || (event.kind == TicketEventKind::Moved
+                && event
+                    .ticket
+                    .previous_keys
+                    .iter()
+                    .any(|key| key.project == *project))
+    });
+    selected
+        && store
+            .can_read(event.project.clone(), user.0.clone())
+            .await
+            .is_ok()
+}
+
+/// 利用者が読み込みを許可されたプロジェクトの一覧を取得する。
+async fn list_projects(State(store): State<StoreHandle>, user: CurrentUser) -> HandlerResult {
+    Ok(Json(store.projects(user.0).await?).into_response())
+}
+
+/// プロジェクトを作成する。
+async fn register_project(
+    State(store): State<StoreHandle>,
+    Json(payload): Json<ProjectDraft>,
+) -> HandlerResult {
+    let key = store.create_project(payload).await?;
+
+    Ok(Json(json!({"key": key})).into_response())
+}
+
+/// プロジェクトを取得する。
+///
+/// 変更前のプロジェクトキーで指定された場合は、変更後のプロジェクトキーにリダイレクトする。
+async fn retrieve_project(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key,)): Path<(ProjectKey,)>,
+) -> HandlerResult {
+    let project = store
+        .get_project(project_key, user.0)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok(Json(project).into_response())
+}
+
+/// プロジェクトを更新する。
+///
+/// 変更前のプロジェクトキーで指定された場合は、変更後のプロジェクトキーにリダイレクトする。
+async fn update_project(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key,)): Path<(ProjectKey,)>,
+    Json(payload): Json<ProjectPatch>,
+) -> HandlerResult {
+    let key = store
+        .update_project(project_key, user.0, payload)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok(Json(json!({"key": key})).into_response())
+}
+
+/// チケットのないプロジェクトを削除する。
+async fn delete_project(
+    State(store): State<StoreHandle>,
+    uri: Uri,
+    user: CurrentUser,
+    Path((project_key,)): Path<(ProjectKey,)>,
+) -> HandlerResult {
+    store
+        .delete_project(project_key, user.0)
+        .await
+        .map_err(relocated(&uri))?;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// 利用者の保存したクエリの一覧を取得する。
+async fn list_saved_queries(
+    State(saved_queries): State<Arc<SavedQueries>>,
+    user: CurrentUser,
+) -> HandlerResult {
+    Ok(Json(saved_queries.list(user.name())?).into_response())
+}
+
+/// 利用者の保存したクエリを取得する。
+async fn retrieve_saved_query(
+    State(saved_queries): State<Arc<SavedQueries>>,
+    user: CurrentUser,
+    Path((name,)): Path<(SavedQueryName,)>,
+) -> HandlerResult {
+    Ok(Json(saved_queries.get(user.name(), &name)?).into_response())
+}
+
+/// クエリを利用者のクエリとして保存する。
+async fn save_query(
+    State(saved_queries): State<Arc<SavedQueries>>,
+    user: CurrentUser,
+    Path((name,)): Path<(SavedQueryName,)>,
+    Json(payload): Json<SavedQueryDraft>,
+) -> HandlerResult {
+    Ok(Json(saved_queries.save(user.name(), name, payload)?).into_response())
+}
+
+/// 利用者の保存したクエリを削除する。
+async fn delete_saved_query(
+    State(saved_queries): State<Arc<SavedQueries>>,
+    user: CurrentUser,
+    Path((name,)): Path<(SavedQueryName,)>,
+) -> HandlerResult {
+    saved_queries.delete(user.name(), &name)?;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// 利用者の保存したクエリの条件を満たすチケットを取得する。
+async fn run_saved_query(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path((name,)): Path<(SavedQueryName,)>,
+) -> HandlerResult {
+    let saved = state.saved_queries.get(user.name(), &name)?;
+    let expr = query::parse(&saved.query)?;
+
+    Ok(Json(state.store.query(expr, user.0).await?).into_response())
+}
+
+/// Webhookの購読の一覧を取得する。
+async fn list_webhooks(State(webhooks): State<Arc<Webhooks>>) -> impl IntoResponse {
+    Json(webhooks.list())
+}
+
+/// Webhookを購読する。
+async fn subscribe_webhook(
+    State(webhooks): State<Arc<Webhooks>>,
+    Json(payload): Json<WebhookDraft>,
+) -> Result<Response, WebhookError> {
+    let webhook = webhooks.subscribe(payload)?;
+
+    Ok((StatusCode::CREATED, Json(webhook)).into_response())
+}
+
+/// Webhookの購読を取得する。
+async fn retrieve_webhook(
+    State(webhooks): State<Arc<Webhooks>>,
+    Path((id,)): Path<(WebhookId,)>,
+) -> Result<Response, WebhookError> {
+    Ok(Json(webhooks.get(id)?).into_response())
+}
+
+/// Webhookの購読を解除する。
+async fn unsubscribe_webhook(
+    State(webhooks): State<Arc<Webhooks>>,
+    Path((id,)): Path<(WebhookId,)>,
+) -> Result<Response, WebhookError> {
+    webhooks.unsubscribe(id)?;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// デッドレターに移した配信の一覧を取得する。
+async fn list_dead_letters(State(webhooks): State<Arc<Webhooks>>) -> impl IntoResponse {
+    Json(webhooks.dead_letters())
+}
+
+/// デッドレターの配信を再配信する。
+async fn replay_delivery(
+    State(webhooks): State<Arc<Webhooks>>,
+    Path((id,)): Path<(DeliveryId,)>,
+) -> Result<Response, WebhookError> {
+    webhooks.replay(id)?;
+
+    Ok(StatusCode::ACCEPTED.into_response())
+}
+
+impl IntoResponse for Ticket {
     fn into_response(self) -> Response {
         (StatusCode::OK, Json(self)).into_response()
     }
 }
 
+/// ストアのエラーを、ステータスコードとエラーコードを含むレスポンスに変換する。
+///
+/// バージョンが一致しない更新は、ほかの更新との競合として`409 Conflict`を返す。
+/// クライアントは、チケットを取得し直してから更新を再試行する。
 impl IntoResponse for TicketStoreError {
     fn into_response(self) -> Response {
         let status_code = match self {
+            Self::NotFound | Self::AttachmentNotFound | Self::ProjectNotFound => {
+                StatusCode::NOT_FOUND
+            }
+            Self::TransitionNotAllowed { .. }
+            | Self::OpenDependencies
+            | Self::DoneDependents
+            | Self::DependencyCycle => StatusCode::BAD_REQUEST,
+            Self::VersionNotMatch
+            | Self::ProjectKeyConflict
+            | Self::ProjectNotEmpty
+            | Self::TicketLinked => StatusCode::CONFLICT,
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::Persistence(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let mut body = json!({"error": format!("{self}"), "code": self.code()});
+        if let Self::TransitionNotAllowed { from, to } = &self {
+            body["from"] = json!(from);
+            body["to"] = json!(to);
+        }
+        let body = Json(body);
+
+        (status_code, body).into_response()
+    }
+}
+
+impl IntoResponse for StoreError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Store(e) => e.into_response(),
+            Self::ProjectRenamed(key) => {
+                Redirect::permanent(&format!("/projects/{key}")).into_response()
+            }
+            Self::TicketMoved(key) => {
+                Redirect::permanent(&format!("/projects/{}/tickets/{key}", key.project))
+                    .into_response()
+            }
+            // キューが空くまで待たずに拒否するため、クライアントに再試行までの時間を伝える。
+            Self::Overloaded => {
+                let body = Json(json!({"error": format!("{self}"), "code": "overloaded"}));
+
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(header::RETRY_AFTER, "1")],
+                    body,
+                )
+                    .into_response()
+            }
+            Self::Stopped => {
+                let body = Json(json!({"error": format!("{self}"), "code": "unavailable"}));
+
+                (StatusCode::SERVICE_UNAVAILABLE, body).into_response()
+            }
+        }
+    }
+}
+
+impl IntoResponse for BackupError {
+    fn into_response(self) -> Response {
+        let status_code = match self {
+            Self::Malformed(_) | Self::MissingFormat | Self::Decrypt(_) => StatusCode::BAD_REQUEST,
+            Self::UnsupportedFormat(_)
+            | Self::MissingProject(_)
+            | Self::DuplicateKey(_)
+            | Self::Events { .. }
+            | Self::Inconsistent { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+        let body = Json(json!({"error": format!("{self}")}));
+
+        (status_code, body).into_response()
+    }
+}
+
+impl IntoResponse for ReportError {
+    fn into_response(self) -> Response {
+        let body = Json(json!({"error": format!("{self}")}));
+
+        (StatusCode::BAD_REQUEST, body).into_response()
+    }
+}
+
+impl IntoResponse for QueryError {
+    fn into_response(self) -> Response {
+        let body = Json(json!({"error": format!("{self}"), "position": self.span}));
+
+        (StatusCode::BAD_REQUEST, body).into_response()
+    }
+}
+
+impl IntoResponse for SavedQueryError {
+    fn into_response(self) -> Response {
+        let status_code = match self {
+            Self::Anonymous => StatusCode::UNAUTHORIZED,
             Self::NotFound => StatusCode::NOT_FOUND,
-            Self::VersionNotMatch => StatusCode::BAD_REQUEST,
+            Self::Invalid(e) => return e.into_response(),
+            Self::Persistence(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let body = Json(json!({"error": format!("{self}")}));
+
+        (status_code, body).into_response()
+    }
+}
+
+impl IntoResponse for AttachmentError {
+    fn into_response(self) -> Response {
+        let status_code = match &self {
+            Self::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::MissingFile => StatusCode::BAD_REQUEST,
+            Self::Multipart(e) => e.status(),
+            Self::Range(_) => StatusCode::RANGE_NOT_SATISFIABLE,
+            Self::MissingBlob | Self::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let body = Json(json!({"error": format!("{self}")}));
+
+        (status_code, body).into_response()
+    }
+}
+
+impl IntoResponse for WebhookError {
+    fn into_response(self) -> Response {
+        let status_code = match self {
+            Self::NotFound | Self::DeliveryNotFound => StatusCode::NOT_FOUND,
+            Self::Persistence(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
         let body = Json(json!({"error": format!("{self}")}));
 
@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::{Mutex, PoisonError, RwLock, RwLockWriteGuard};
+
+use crate::domain::RecordedEvent;
+use crate::models::{Ticket, TicketId};
+use crate::sync::{lock, read_lock, write_lock};
+
+/// チケットを分割して保持するシャードの数
+pub const SHARD_COUNT: usize = 16;
+
+/// 1つのシャードが保持するチケット
+pub type Shard = BTreeMap<TicketId, Ticket>;
+
+/// チケットIDによって、シャードに分割して保持したチケット
+///
+/// シャードごとにロックを持つため、異なるシャードのチケットの更新は互いに待たない。
+/// チケットの読み込みは読み込みロックだけを取得するため、どのチケットの読み込みも互いに待たない。
+/// ロックは`.await`をまたいで保持しない。
+#[derive(Debug)]
+pub struct ShardedTickets {
+    shards: Box<[RwLock<Shard>]>,
+}
+
+impl Default for ShardedTickets {
+    fn default() -> Self {
+        Self {
+            shards: (0..SHARD_COUNT).map(|_| RwLock::default()).collect(),
+        }
+    }
+}
+
+impl FromIterator<Ticket> for ShardedTickets {
+    fn from_iter<I: IntoIterator<Item = Ticket>>(tickets: I) -> Self {
+        let sharded = Self::default();
+        for ticket in tickets {
+            sharded.insert(ticket);
+        }
+        sharded
+    }
+}
+
+impl ShardedTickets {
+    /// チケットIDのチケットを保持するシャードの番号を返す。
+    ///
+    /// チケットIDは連番で割り当てるため、連続するチケットは異なるシャードに分散する。
+    fn index(id: TicketId) -> usize {
+        (id.0 % SHARD_COUNT as u64) as usize
+    }
+
+    /// チケットIDのチケットを保持するシャードを返す。
+    fn shard(&self, id: TicketId) -> &RwLock<Shard> {
+        &self.shards[Self::index(id)]
+    }
+
+    /// チケットIDのチケットを保持するシャードの書き込みロックを取得する。
+    ///
+    /// ロックを保持している間は、他のシャードのロックを取得してはならない。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - チケットID
+    ///
+    /// # 戻り値
+    ///
+    /// シャードの書き込みロック
+    pub fn write(&self, id: TicketId) -> RwLockWriteGuard<'_, Shard> {
+        write_lock(self.shard(id))
+    }
+
+    /// チケットIDを指定して、チケットの複製を取得する。
+    pub fn get(&self, id: TicketId) -> Option<Ticket> {
+        read_lock(self.shard(id)).get(&id).cloned()
+    }
+
+    /// チケットIDのチケットがあるか確認する。
+    pub fn contains(&self, id: TicketId) -> bool {
+        read_lock(self.shard(id)).contains_key(&id)
+    }
+
+    /// チケットを追加する。
+    pub fn insert(&self, ticket: Ticket) {
+        write_lock(self.shard(ticket.id)).insert(ticket.id, ticket);
+    }
+
+    /// チケットを取り除く。
+    pub fn remove(&self, id: TicketId) -> Option<Ticket> {
+        write_lock(self.shard(id)).remove(&id)
+    }
+
+    /// チケットの数を返す。
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| read_lock(shard).len()).sum()
+    }
+
+    /// チケットがないか確認する。
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| read_lock(shard).is_empty())
+    }
+
+    /// すべてのチケットの複製を、チケットID順に取得する。
+    ///
+    /// シャードのロックを1つずつ取得するため、他のシャードを更新している間に取得した結果は、
+    /// チケットごとには一貫しているが、全体としてある時点の状態とは限らない。
+    /// ある時点の状態が必要な場合は、チケットを更新できないようにしてから取得する。
+    pub fn collect(&self) -> Shard {
+        self.shards
+            .iter()
+            .flat_map(|shard| read_lock(shard).clone())
+            .collect()
+    }
+
+    /// チケットIDを指定して、チケットの可変参照を取得する。
+    pub fn get_mut(&mut self, id: TicketId) -> Option<&mut Ticket> {
+        self.shards[Self::index(id)]
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(&id)
+    }
+
+    /// すべてのチケットの可変参照を返す。
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Ticket> {
+        self.shards.iter_mut().flat_map(|shard| {
+            shard
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .values_mut()
+        })
+    }
+}
+
+/// 1つのシャードが保持する、追加した順番の連番を付けたドメインイベント
+type HistoryShard = Vec<(u64, RecordedEvent)>;
+
+/// チケットIDによって、シャードに分割して保持したドメインイベントの履歴
+///
+/// チケットと同じシャードに分割し、チケットのシャードの書き込みロックを保持したまま追加するため、
+/// 異なるシャードのチケットの変更は、履歴の追加でも互いに待たない。
+/// ドメインイベントには追加した順番の連番を付け、すべての履歴は連番の順に返す。
+/// 同じチケットのドメインイベントは同じシャードに追加した順に並ぶが、異なるチケットのドメインイベントの順番は、
+/// ファイルストレージに記録した順番と一致するとは限らない。
+#[derive(Debug)]
+pub struct ShardedHistory {
+    shards: Box<[Mutex<HistoryShard>]>,
+    /// 次に追加するドメインイベントの連番
+    next: AtomicU64,
+}
+
+impl Default for ShardedHistory {
+    fn default() -> Self {
+        Self {
+            shards: (0..SHARD_COUNT).map(|_| Mutex::default()).collect(),
+            next: AtomicU64::new(0),
+        }
+    }
+}
+
+impl FromIterator<RecordedEvent> for ShardedHistory {
+    fn from_iter<I: IntoIterator<Item = RecordedEvent>>(records: I) -> Self {
+        let history = Self::default();
+        for record in records {
+            history.extend(record.ticket, vec![record]);
+        }
+        history
+    }
+}
+
+impl ShardedHistory {
+    /// チケットのドメインイベントを、発生した順番に履歴に追加する。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - チケットID
+    /// * `records` - チケットのドメインイベント
+    pub fn extend(&self, id: TicketId, records: Vec<RecordedEvent>) {
+        let first = self
+            .next
+            .fetch_add(records.len() as u64, atomic::Ordering::Relaxed);
+        lock(&self.shards[ShardedTickets::index(id)]).extend((first..).zip(records));
+    }
+
+    /// 履歴にあるドメインイベントの数を返す。
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| lock(shard).len()).sum()
+    }
+
+    /// 履歴が空か確認する。
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| lock(shard).is_empty())
+    }
+
+    /// チケットのドメインイベントを、発生した順番に返す。
+    pub fn ticket(&self, id: TicketId) -> Vec<RecordedEvent> {
+        lock(&self.shards[ShardedTickets::index(id)])
+            .iter()
+            .filter(|(_, record)| record.ticket == id)
+            .map(|(_, record)| record.clone())
+            .collect()
+    }
+
+    /// すべてのドメインイベントの複製を、追加した順番に返す。
+    ///
+    /// [`ShardedTickets::collect`]と同じく、シャードのロックを1つずつ取得するため、
+    /// ある時点の履歴が必要な場合は、チケットを更新できないようにしてから取得する。
+    pub fn collect(&self) -> Vec<RecordedEvent> {
+        let mut records: Vec<_> = self
+            .shards
+            .iter()
+            .flat_map(|shard| lock(shard).clone())
+            .collect();
+        records.sort_by_key(|(sequence, _)| *sequence);
+        records.into_iter().map(|(_, record)| record).collect()
+    }
+}
@@ -1,18 +1,708 @@
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
 
-use crate::dto::{TicketDraft, TicketPatch};
-use crate::models::{Ticket, TicketId};
+use chrono::{Datelike, TimeDelta};
 
-/// チケットストア
-#[derive(Debug, Default)]
+use crate::backup::ProjectBackup;
+use crate::clock::{Clock, SystemClock};
+use crate::crypto::Encryption;
+use crate::domain::{
+    self, BlobReferences, BlockerIndex, DomainEvent, EventResult, Projection, RecordedEvent,
+    StatusIndex, SubtaskIndex,
+};
+use crate::dto::{
+    AttachmentDraft, DueFilter, ProjectPatch, SortKey, TicketDraft, TicketPatch, TicketQuery,
+};
+use crate::events::{EventSender, TicketEvent, TicketEventKind};
+use crate::graph::{self, DependencyGraph};
+use crate::models::{
+    Attachment, AttachmentId, BlobDigest, Label, MovedTicket, Project, Ticket, TicketId, TicketKey,
+    TicketRef, TicketStatus,
+};
+use crate::persistence::{
+    EncodedEvents, FileStorage, PersistenceError, PersistenceResult, Snapshot,
+};
+use crate::query::{self, Expr};
+use crate::reports::StatusTimeline;
+use crate::search::{self, SearchHit, SearchIndex, SearchText, TicketTerms};
+use crate::shards::{Shard, ShardedHistory, ShardedTickets};
+use crate::sync::{lock, read_lock, write_lock};
+
+/// 1つのプロジェクトのチケットストア
+///
+/// チケットIDはプロジェクトごとに1から割り当て、チケットキーの番号にもなる。
+/// チケットは直接変更せず、変更をドメインイベントとして記録してから、チケットと射影に適用する。
+/// ファイルストレージを持つ場合は、ドメインイベントとプロジェクトの変更をファイルストレージに記録する。
+///
+/// チケットはチケットIDによってシャードに分割して保持し、チケットIDはアトミックに割り当てる。
+/// チケットの追加と更新は`&self`で実行できるため、プロジェクトの読み込みロックを共有したまま、
+/// 異なるシャードのチケットを並行して更新できる。
+/// チケットの関連やプロジェクトの変更、チケットの移動元からの取り除き、チェックポイントは`&mut self`を必要とし、
+/// プロジェクトの書き込みロックによって他のすべての変更と排他的に実行する。
+#[derive(Debug)]
 pub struct TicketStore {
-    tickets: BTreeMap<TicketId, Ticket>,
-    next_id: u64,
+    project: Project,
+    tickets: ShardedTickets,
+    /// 次に割り当てるチケットID
+    next_id: AtomicU64,
+    /// 他のプロジェクトに移動したチケットの、移動先のチケットキー
+    moved: BTreeMap<TicketId, TicketKey>,
+    /// ファイルストレージ
+    ///
+    /// チケットの記録はシャードの書き込みロックを保持したまま行うため、同じチケットの変更は更新した順に記録される。
+    /// ロックは、ドメインイベントをJSONに変換した後、追記する間だけ保持する。
+    storage: Option<Mutex<FileStorage>>,
+    /// チケットのシャードごとに分割した、すべてのドメインイベント
+    history: ShardedHistory,
+    /// プロジェクトが削除されたか
+    deleted: bool,
+    clock: Arc<dyn Clock>,
+    /// チケットイベントの送信先
+    events: Option<EventSender>,
+    /// チケットのタイトルと説明の転置索引
+    ///
+    /// 索引のロックは、シャードのロックを保持したまま取得することがあるため、
+    /// 索引のロックを保持したままシャードのロックを取得してはならない。
+    index: RwLock<SearchIndex>,
+    /// チケットのステータスの索引
+    ///
+    /// ロックの順番は、チケットのタイトルと説明の転置索引と同じである。
+    statuses: RwLock<StatusIndex>,
+    /// 添付ファイルの内容の参照数
+    ///
+    /// ロックの順番は、チケットのタイトルと説明の転置索引と同じである。
+    blobs: RwLock<BlobReferences>,
+    /// チケットのステータスの遷移
+    ///
+    /// ロックの順番は、チケットのタイトルと説明の転置索引と同じである。
+    timeline: RwLock<StatusTimeline>,
+    /// 親チケットごとのサブタスクの索引
+    ///
+    /// ロックの順番は、チケットのタイトルと説明の転置索引と同じである。
+    subtasks: RwLock<SubtaskIndex>,
+    /// ブロッカーごとのブロックされているチケットの索引
+    ///
+    /// ロックの順番は、チケットのタイトルと説明の転置索引と同じである。
+    blockers: RwLock<BlockerIndex>,
+}
+
+impl Default for TicketStore {
+    /// 既定のプロジェクトのチケットストアを構築する。
+    fn default() -> Self {
+        Self::new(Project::default())
+    }
+}
+
+/// チケットの指定を解決した結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TicketLookup {
+    /// チケットが見つかった。
+    Found(TicketId),
+    /// プロジェクトキーの変更または他のプロジェクトへの移動によってチケットキーが変わったため、
+    /// 変更後のチケットキーで指定し直す必要がある。
+    Moved(TicketKey),
+}
+
+/// プロジェクトへのアクセスの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
 }
 
 impl TicketStore {
+    /// ファイルストレージを持たないチケットストアを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `project` - プロジェクト
+    ///
+    /// # 戻り値
+    ///
+    /// チケットストア
+    pub fn new(project: Project) -> Self {
+        Self::restore(project, Snapshot::default(), vec![], None)
+    }
+
+    /// スナップショットから、ファイルストレージを持たないチケットストアを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `project` - プロジェクト
+    /// * `snapshot` - 他のチケットストアのスナップショット
+    ///
+    /// # 戻り値
+    ///
+    /// スナップショットのチケットを復元したチケットストア
+    pub fn from_snapshot(project: Project, snapshot: Snapshot) -> Self {
+        Self::restore(project, snapshot, vec![], None)
+    }
+
+    /// バックアップから、ファイルストレージを持たないチケットストアを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `project` - プロジェクト
+    /// * `backup` - 検証したプロジェクトのバックアップ
+    ///
+    /// # 戻り値
+    ///
+    /// バックアップのチケットとドメインイベントの履歴を復元したチケットストア
+    pub fn from_backup(project: Project, backup: ProjectBackup) -> Self {
+        Self::restore(project, backup.snapshot, backup.history, None)
+    }
+
+    /// チケットの作成日時や更新日時、期限の判定に使用する時計を設定する。
+    ///
+    /// # 引数
+    ///
+    /// * `clock` - 時計
+    ///
+    /// # 戻り値
+    ///
+    /// 時計を設定したチケットストア
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// チケットを変更したときに、チケットイベントを送信する送信先を設定する。
+    ///
+    /// # 引数
+    ///
+    /// * `events` - チケットイベントの送信先、送信しない場合は`None`
+    pub fn set_events(&mut self, events: Option<EventSender>) {
+        self.events = events;
+    }
+
+    /// チケットイベントの送信先が設定されている場合は、チケットイベントを送信する。
+    ///
+    /// 受信側がすでに停止している場合は、イベントを破棄する。
+    fn emit(&self, kind: TicketEventKind, ticket: &Ticket) {
+        let Some(events) = &self.events else {
+            return;
+        };
+        let event = TicketEvent {
+            kind,
+            project: self.project.key.clone(),
+            ticket: ticket.clone(),
+            occurred_at: ticket.updated_at,
+        };
+        if events.send(event).is_err() {
+            tracing::debug!(
+                ticket_id = ticket.id.0,
+                "チケットイベントの受信側が停止しています。"
+            );
+        }
+    }
+
+    /// データディレクトリにファイルストレージを作成して、チケットストアを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - 空または存在しないデータディレクトリ
+    /// * `project` - プロジェクト
+    ///
+    /// # 戻り値
+    ///
+    /// チケットストア
+    pub fn create(data_dir: &Path, project: Project) -> PersistenceResult<Self> {
+        Self::create_encrypted(data_dir, project, &Encryption::default())
+    }
+
+    /// 暗号化を指定して、データディレクトリにファイルストレージを作成して、チケットストアを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - 空または存在しないデータディレクトリ
+    /// * `project` - プロジェクト
+    /// * `encryption` - 暗号化
+    ///
+    /// # 戻り値
+    ///
+    /// チケットストア
+    pub fn create_encrypted(
+        data_dir: &Path,
+        project: Project,
+        encryption: &Encryption,
+    ) -> PersistenceResult<Self> {
+        let (mut storage, snapshot, history) = FileStorage::open_encrypted(data_dir, encryption)?;
+        if snapshot.project.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{}にはプロジェクトが存在します。", data_dir.display()),
+            )
+            .into());
+        }
+        storage.record_project(&project)?;
+
+        Ok(Self::restore(project, snapshot, history, Some(storage)))
+    }
+
+    /// データディレクトリのファイルストレージから、チケットストアを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - [`TicketStore::create`]でファイルストレージを作成したデータディレクトリ
+    ///
+    /// # 戻り値
+    ///
+    /// 永続化されたチケットを復元したチケットストア
+    pub fn open(data_dir: &Path) -> PersistenceResult<Self> {
+        Self::open_encrypted(data_dir, &Encryption::default())
+    }
+
+    /// 暗号化を指定して、データディレクトリのファイルストレージから、チケットストアを構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - [`TicketStore::create_encrypted`]でファイルストレージを作成したデータディレクトリ
+    /// * `encryption` - 暗号化
+    ///
+    /// # 戻り値
+    ///
+    /// 永続化されたチケットを復元したチケットストア
+    pub fn open_encrypted(data_dir: &Path, encryption: &Encryption) -> PersistenceResult<Self> {
+        let (storage, mut snapshot, history) = FileStorage::open_encrypted(data_dir, encryption)?;
+        let project = snapshot
+            .project
+            .take()
+            .ok_or_else(|| PersistenceError::MissingProject(data_dir.into()))?;
+
+        Ok(Self::restore(project, snapshot, history, Some(storage)))
+    }
+
+    /// スナップショットとドメインイベントの履歴からチケットストアを構築する。
+    ///
+    /// スナップショットのチケットには、ドメインイベントの履歴をすべて適用済みでなければならない。
+    fn restore(
+        project: Project,
+        snapshot: Snapshot,
+        history: Vec<RecordedEvent>,
+        storage: Option<FileStorage>,
+    ) -> Self {
+        let store = Self {
+            project,
+            tickets: ShardedTickets::default(),
+            next_id: AtomicU64::new(snapshot.next_id),
+            moved: snapshot
+                .moved
+                .into_iter()
+                .map(|m| (m.id, m.moved_to))
+                .collect(),
+            storage: storage.map(Mutex::new),
+            history: history.into_iter().collect(),
+            deleted: false,
+            clock: Arc::new(SystemClock),
+            events: None,
+            index: RwLock::default(),
+            statuses: RwLock::default(),
+            blobs: RwLock::default(),
+            timeline: RwLock::default(),
+            subtasks: RwLock::default(),
+            blockers: RwLock::default(),
+        };
+        // ステータスの遷移の日時はスナップショットのチケットが持たないため、ドメインイベントの履歴から射影する。
+        if let Err(e) = domain::rebuild(
+            &store.history.collect(),
+            &mut [&mut *write_lock(&store.timeline)],
+        ) {
+            tracing::warn!(
+                project_key = %store.project.key,
+                error = %e,
+                "ステータスの遷移を射影できません。"
+            );
+        }
+        for mut ticket in snapshot.tickets {
+            // プロジェクトキーの変更より前に記録されたチケットは、変更前のチケットキーを持つ。
+            ticket.key = store.key_of(ticket.id);
+            write_lock(&store.index).insert(&ticket);
+            write_lock(&store.statuses).insert(&ticket);
+            write_lock(&store.blobs).insert(&ticket);
+            write_lock(&store.subtasks).insert(&ticket);
+            write_lock(&store.blockers).insert(&ticket);
+            store.tickets.insert(ticket);
+        }
+
+        store
+    }
+
+    /// プロジェクトを返す。
+    pub fn project(&self) -> &Project {
+        &self.project
+    }
+
+    /// チケットの数を返す。
+    pub fn len(&self) -> usize {
+        self.tickets.len()
+    }
+
+    /// チケットがないか確認する。
+    pub fn is_empty(&self) -> bool {
+        self.tickets.is_empty()
+    }
+
+    /// チケットストアの状態をスナップショットとして返す。
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            project: Some(self.project.clone()),
+            next_id: self.next_id.load(atomic::Ordering::Relaxed),
+            events: self.history.len() as u64,
+            tickets: self.tickets.collect().into_values().collect(),
+            moved: self
+                .moved
+                .iter()
+                .map(|(id, moved_to)| MovedTicket {
+                    id: *id,
+                    moved_to: moved_to.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// チケットストアの状態とドメインイベントの履歴を、バックアップとして返す。
+    ///
+    /// チケットの変更と同時に呼び出すと、スナップショットと履歴が一致しないことがあるため、
+    /// チケットを更新できないように、チケットストアの書き込みロックを保持して呼び出す。
+    pub fn backup(&self) -> ProjectBackup {
+        let history = self.history.collect();
+        let mut snapshot = self.snapshot();
+        snapshot.events = history.len() as u64;
+
+        ProjectBackup { snapshot, history }
+    }
+
+    /// ファイルストレージを持つ場合は、チケットストア全体をスナップショットに書き出す。
+    ///
+    /// スナップショットを書き出した後は、それまでのジャーナルを再生する必要がなくなる。
+    /// 書き出している間にチケットが更新されないように、`&mut self`を必要とする。
+    pub fn checkpoint(&mut self) -> PersistenceResult<()> {
+        let snapshot = self.snapshot();
+        match self.storage.as_mut() {
+            Some(storage) => storage
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .checkpoint(&snapshot),
+            None => Ok(()),
+        }
+    }
+
+    /// ファイルストレージが現在の鍵で暗号化し直す必要があるファイルを持つか確認する。
+    pub fn is_stale(&self) -> bool {
+        self.storage
+            .as_ref()
+            .is_some_and(|storage| lock(storage).is_stale())
+    }
+
+    /// ファイルストレージを持つ場合は、永続化したファイルを現在の鍵で暗号化し直す。
+    ///
+    /// イベントログを書き直してから、チケットストア全体をスナップショットに書き出すため、`&mut self`を必要とする。
+    pub fn reencrypt(&mut self) -> PersistenceResult<()> {
+        let snapshot = self.snapshot();
+        match self.storage.as_mut() {
+            Some(storage) => storage
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .reencrypt(&snapshot),
+            None => Ok(()),
+        }
+    }
+
+    /// プロジェクトを削除したものとして、ファイルストレージを持つ場合はデータディレクトリを削除する。
+    ///
+    /// 削除した後は、チケットを変更できない。
+    pub fn destroy(&mut self) -> PersistenceResult<()> {
+        self.deleted = true;
+        match self.storage.take() {
+            Some(storage) => storage
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner)
+                .destroy(),
+            None => Ok(()),
+        }
+    }
+
+    /// ファイルストレージを持つ場合は、チケットのドメインイベントをファイルストレージに記録する。
+    fn record_events(&self, records: &[RecordedEvent]) -> TicketStoreResult<()> {
+        if let (Some(storage), Some(record)) = (&self.storage, records.first()) {
+            EncodedEvents::new(records)
+                .and_then(|events| lock(storage).record_events(&events))
+                .map_err(|e| {
+                    tracing::error!(ticket_id = record.ticket.0, error = %e, "チケットのドメインイベントを記録できません。");
+                    TicketStoreError::Persistence(Arc::new(e))
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// チケットのドメインイベントを記録して、チケットと射影に適用する。
+    ///
+    /// チケットのシャードの書き込みロックを保持したまま呼び出す。
+    /// ファイルストレージに記録できなかった場合は、チケットも射影も変更しない。
+    ///
+    /// # 引数
+    ///
+    /// * `shard` - チケットのシャード
+    /// * `id` - チケットID
+    /// * `version` - イベントを適用した後のチケットのバージョン
+    /// * `events` - 発生した順番のドメインイベント
+    ///
+    /// # 戻り値
+    ///
+    /// イベントを適用した後のチケット、他のプロジェクトに移動した場合は`None`
+    fn commit(
+        &self,
+        shard: &mut Shard,
+        id: TicketId,
+        version: u64,
+        events: Vec<DomainEvent>,
+    ) -> TicketStoreResult<Option<Ticket>> {
+        let occurred_at = self.clock.now();
+        let records: Vec<_> = events
+            .into_iter()
+            .map(|event| RecordedEvent {
+                ticket: id,
+                version,
+                occurred_at,
+                event,
+            })
+            .collect();
+        let mut states = Vec::with_capacity(records.len());
+        let mut ticket = shard.get(&id).cloned();
+        for record in &records {
+            ticket = domain::apply(ticket, record).map_err(|e| {
+                tracing::error!(ticket_id = id.0, error = %e, "ドメインイベントを適用できません。");
+                TicketStoreError::NotFound
+            })?;
+            states.push(ticket.clone());
+        }
+        self.record_events(&records)?;
+
+        self.project_text(&records, &states);
+        project(&self.statuses, &records, &states);
+        project(&self.blobs, &records, &states);
+        project(&self.timeline, &records, &states);
+        project(&self.subtasks, &records, &states);
+        project(&self.blockers, &records, &states);
+        for (record, state) in records.iter().zip(&states) {
+            shard.project(record, state.as_ref());
+        }
+        self.history.extend(id, records);
+
+        Ok(ticket)
+    }
+
+    /// チケットのタイトルと説明の転置索引に、ドメインイベントを適用する。
+    ///
+    /// [`Projection::project`]と同じ結果になるように適用するが、語の出現回数は転置索引のロックを取得する前に数え、
+    /// ロックは索引を書き換える間だけ保持する。
+    fn project_text(&self, records: &[RecordedEvent], states: &[Option<Ticket>]) {
+        if !records
+            .iter()
+            .any(|record| SearchIndex::observes(&record.event))
+        {
+            return;
+        }
+        let changes: Vec<_> = records
+            .iter()
+            .zip(states)
+            .filter_map(|(record, state)| match state {
+                Some(ticket) if SearchIndex::observes(&record.event) => {
+                    Some((record.ticket, Some(TicketTerms::new(ticket))))
+                }
+                Some(_) => None,
+                None => Some((record.ticket, None)),
+            })
+            .collect();
+        let mut index = write_lock(&self.index);
+        for (id, terms) in changes {
+            match terms {
+                Some(terms) => index.insert_terms(terms),
+                None => index.remove(id),
+            }
+        }
+    }
+
+    /// チケットより先に完了しなければならないチケットのうち、完了していないチケットのチケットIDを返す。
+    ///
+    /// チケット自身のブロッカーと、サブタスクの索引にあるサブタスクだけを、ステータスの索引で調べる。
+    /// 他のシャードのロックを取得しないため、チケットのシャードの書き込みロックを保持したまま呼び出せる。
+    /// チケットの関連は`&mut self`でしか変更できないため、ロックを保持している間に変わることはない。
+    fn open_prerequisites(&self, ticket: &Ticket) -> Vec<TicketId> {
+        let subtasks = read_lock(&self.subtasks).ids(ticket.id);
+        let statuses = read_lock(&self.statuses);
+        let mut open: Vec<_> = ticket
+            .blocked_by
+            .iter()
+            .copied()
+            .chain(subtasks)
+            .filter(|&id| statuses.status(id).is_some_and(|s| s != TicketStatus::Done))
+            .collect();
+        open.sort();
+        open.dedup();
+        open
+    }
+
+    /// チケットより後に完了しなければならないチケットのうち、完了したチケットのチケットIDを返す。
+    ///
+    /// チケットの親チケットと、ブロッカーの索引にあるチケットがブロックしているチケットだけを、
+    /// ステータスの索引で調べる。[`TicketStore::open_prerequisites`]と同じく、
+    /// チケットのシャードの書き込みロックを保持したまま呼び出せる。
+    fn done_dependents(&self, ticket: &Ticket) -> Vec<TicketId> {
+        let blocked = read_lock(&self.blockers).ids(ticket.id);
+        let statuses = read_lock(&self.statuses);
+        let mut done: Vec<_> = ticket
+            .parent
+            .into_iter()
+            .chain(blocked)
+            .filter(|&id| statuses.status(id) == Some(TicketStatus::Done))
+            .collect();
+        done.sort();
+        done.dedup();
+        done
+    }
+
+    /// チケットのドメインイベントを記録して、適用した後のチケットを返す。
+    ///
+    /// [`TicketStore::commit`]と同じく、チケットのシャードの書き込みロックを保持したまま呼び出す。
+    fn commit_ticket(
+        &self,
+        shard: &mut Shard,
+        id: TicketId,
+        version: u64,
+        events: Vec<DomainEvent>,
+    ) -> TicketStoreResult<Ticket> {
+        self.commit(shard, id, version, events)?
+            .ok_or(TicketStoreError::NotFound)
+    }
+
+    /// ファイルストレージを持つ場合は、プロジェクトをファイルストレージに記録する。
+    fn record_project(&self, project: &Project) -> TicketStoreResult<()> {
+        if let Some(storage) = &self.storage {
+            lock(storage).record_project(project).map_err(|e| {
+                tracing::error!(project_key = %project.key, error = %e, "プロジェクトを記録できません。");
+                TicketStoreError::Persistence(Arc::new(e))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// ファイルストレージを持つ場合は、他のプロジェクトに移動したチケットをファイルストレージに記録する。
+    fn record_moved(&self, moved: &MovedTicket) -> TicketStoreResult<()> {
+        if let Some(storage) = &self.storage {
+            lock(storage).record_moved(moved).map_err(|e| {
+                tracing::error!(ticket_id = moved.id.0, error = %e, "チケットの移動を記録できません。");
+                TicketStoreError::Persistence(Arc::new(e))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// プロジェクトが削除されていないか確認する。
+    fn ensure_alive(&self) -> TicketStoreResult<()> {
+        if self.deleted {
+            return Err(TicketStoreError::ProjectNotFound);
+        }
+
+        Ok(())
+    }
+
+    /// 利用者がプロジェクトにアクセスできるか確認する。
+    ///
+    /// # 引数
+    ///
+    /// * `user` - 利用者、識別できない場合は`None`
+    /// * `access` - アクセスの種類
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn authorize(&self, user: Option<&str>, access: Access) -> TicketStoreResult<()> {
+        self.ensure_alive()?;
+        let permissions = &self.project.permissions;
+        let allowed = match access {
+            Access::Read => permissions.can_read(user),
+            Access::Write => permissions.can_write(user),
+        };
+        if !allowed {
+            tracing::info!(
+                project_key = %self.project.key,
+                user,
+                ?access,
+                "プロジェクトへのアクセスを拒否しました。"
+            );
+            return Err(TicketStoreError::Forbidden);
+        }
+
+        Ok(())
+    }
+
+    /// プロジェクトを更新する。
+    ///
+    /// プロジェクトキーを変更した場合は、チケットのチケットキーも変更する。
+    /// 変更後のプロジェクトキーが他のプロジェクトで使用されていないことは、呼び出し側で確認する。
+    ///
+    /// # 引数
+    ///
+    /// * `patch` - プロジェクトのパッチ
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn update_project(&mut self, patch: ProjectPatch) -> TicketStoreResult<()> {
+        self.ensure_alive()?;
+        let mut target = self.project.clone();
+        if let Some(name) = patch.name {
+            target.name = name;
+        }
+        if let Some(workflow) = patch.workflow {
+            target.workflow = workflow;
+        }
+        if let Some(permissions) = patch.permissions {
+            target.permissions = permissions;
+        }
+        if let Some(description_format) = patch.description_format {
+            target.description_format = description_format;
+        }
+        let renamed = patch.key.filter(|key| key != &target.key);
+        if let Some(key) = &renamed {
+            // 自身の変更前のプロジェクトキーに戻すことはできる。
+            target.previous_keys.retain(|k| k != key);
+            target.previous_keys.push(target.key.clone());
+            target.key = key.clone();
+        }
+        self.record_project(&target)?;
+        let previous_key = std::mem::replace(&mut self.project, target);
+        if renamed.is_some() {
+            for ticket in self.tickets.values_mut() {
+                ticket.key.project = self.project.key.clone();
+            }
+            tracing::info!(
+                previous_key = %previous_key.key,
+                project_key = %self.project.key,
+                "プロジェクトキーを変更しました。"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// チケットIDに対応する、現在のチケットキーを返す。
+    fn key_of(&self, id: TicketId) -> TicketKey {
+        TicketKey::new(self.project.key.clone(), id)
+    }
+
     /// チケットを追加する。
     ///
+    /// チケットIDはアトミックに割り当てるため、ファイルストレージに記録できなかったチケットのチケットIDは欠番になる。
+    ///
     /// # 引数
     ///
     /// * `draft` - 追加するチケットのドラフト
@@ -20,16 +710,171 @@ impl TicketStore {
     /// # 戻り値
     ///
     /// 追加したチケットのID
-    pub fn add_ticket(&mut self, draft: TicketDraft) -> TicketId {
-        let id = TicketId(self.next_id);
-        let ticket = Ticket::new(id, draft.title, draft.description);
-        self.next_id += 1;
-        self.tickets.insert(id, ticket);
+    pub fn add_ticket(&self, draft: TicketDraft) -> TicketStoreResult<TicketId> {
+        self.ensure_alive()?;
+        let id = self.allocate_id();
+        let key = self.key_of(id);
+        let created = DomainEvent::TicketCreated {
+            key: key.clone(),
+            title: draft.title,
+            description: draft.description,
+            priority: draft.priority,
+            due_date: draft.due_date,
+        };
+        let mut shard = self.tickets.write(id);
+        let ticket = self.commit_ticket(&mut shard, id, 0, vec![created])?;
+        self.emit(TicketEventKind::Created, &ticket);
+        tracing::info!(
+            ticket_id = id.0,
+            ticket_key = %key,
+            version = 0,
+            "チケットを追加しました。"
+        );
+
+        Ok(id)
+    }
+
+    /// 他のプロジェクトから移動したチケットを追加する。
+    ///
+    /// チケットには新しいチケットIDを割り当て、移動前のチケットキーを`previous_keys`に残す。
+    /// 作成日時やステータスなどはそのまま引き継ぎ、バージョンを1つ進める。
+    /// 移動元のドメインイベントの履歴は引き継がず、移動後の状態を取り込むドメインイベントから履歴を始める。
+    ///
+    /// # 引数
+    ///
+    /// * `ticket` - 移動元のプロジェクトのチケット
+    ///
+    /// # 戻り値
+    ///
+    /// 移動後のチケットキー
+    pub fn adopt_ticket(&self, mut ticket: Ticket) -> TicketStoreResult<TicketKey> {
+        self.ensure_alive()?;
+        let id = self.allocate_id();
+        let key = self.key_of(id);
+        let previous_key = std::mem::replace(&mut ticket.key, key.clone());
+        ticket.previous_keys.push(previous_key.clone());
+        let version = ticket.version + 1;
+        let imported = DomainEvent::TicketImported {
+            ticket: Box::new(ticket),
+        };
+        let mut shard = self.tickets.write(id);
+        let ticket = self.commit_ticket(&mut shard, id, version, vec![imported])?;
+        tracing::info!(
+            ticket_id = id.0,
+            ticket_key = %key,
+            previous_key = %previous_key,
+            version,
+            "他のプロジェクトからチケットを移動しました。"
+        );
+        self.emit(TicketEventKind::Moved, &ticket);
 
-        id
+        Ok(key)
     }
 
-    /// チケットIDを指定して、チケットの参照を取得する。
+    /// 次のチケットIDを割り当てる。
+    fn allocate_id(&self) -> TicketId {
+        TicketId(self.next_id.fetch_add(1, atomic::Ordering::Relaxed))
+    }
+
+    /// 他のプロジェクトに移動したチケットを取り除く。
+    ///
+    /// 取り除いたチケットのチケットIDは、移動先のチケットキーに解決する。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - 移動したチケットのチケットID
+    /// * `moved_to` - 移動先のチケットキー
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn release_ticket(&mut self, id: TicketId, moved_to: TicketKey) -> TicketStoreResult<()> {
+        self.ensure_alive()?;
+        self.ensure_unlinked(id)?;
+        let version = self.get(id)?.version + 1;
+        let moved = MovedTicket { id, moved_to };
+        self.record_moved(&moved)?;
+        let moved_out = DomainEvent::MovedOut {
+            moved_to: moved.moved_to.clone(),
+        };
+        let mut shard = self.tickets.write(id);
+        self.commit(&mut shard, id, version, vec![moved_out])?;
+        drop(shard);
+        tracing::info!(
+            ticket_id = id.0,
+            moved_to = %moved.moved_to,
+            "チケットを他のプロジェクトに移動しました。"
+        );
+        self.moved.insert(id, moved.moved_to);
+
+        Ok(())
+    }
+
+    /// チケットIDまたはチケットキーによるチケットの指定を解決する。
+    ///
+    /// チケットキーのプロジェクトキーは、このプロジェクトの現在または変更前のプロジェクトキーでなければならない。
+    ///
+    /// # 引数
+    ///
+    /// * `reference` - チケットの指定
+    ///
+    /// # 戻り値
+    ///
+    /// チケットの指定を解決した結果
+    pub fn resolve(&self, reference: &TicketRef) -> TicketStoreResult<TicketLookup> {
+        let (id, renamed) = match reference {
+            TicketRef::Id(id) => (Some(*id), false),
+            TicketRef::Key(key) => (
+                self.project.is_known_as(&key.project).then_some(key.id()),
+                key.project != self.project.key,
+            ),
+        };
+        let found = id.and_then(|id| {
+            if self.tickets.contains(id) {
+                Some(match renamed {
+                    true => TicketLookup::Moved(self.key_of(id)),
+                    false => TicketLookup::Found(id),
+                })
+            } else {
+                self.moved.get(&id).cloned().map(TicketLookup::Moved)
+            }
+        });
+
+        found.ok_or_else(|| {
+            tracing::debug!(?reference, "チケットが見つかりません。");
+            TicketStoreError::NotFound
+        })
+    }
+
+    /// チケットIDを指定して、チケットの複製を取得する。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - チケットID
+    ///
+    /// # 戻り値
+    ///
+    /// チケットの複製
+    pub fn get(&self, id: TicketId) -> TicketStoreResult<Ticket> {
+        match self.tickets.get(id) {
+            Some(ticket) => {
+                tracing::debug!(
+                    ticket_id = id.0,
+                    version = ticket.version,
+                    "チケットを取得しました。"
+                );
+                Ok(ticket)
+            }
+            None => {
+                tracing::debug!(ticket_id = id.0, "チケットが見つかりません。");
+                Err(TicketStoreError::NotFound)
+            }
+        }
+    }
+
+    /// チケットのドメインイベントを、記録された順番に取得する。
+    ///
+    /// 他のプロジェクトから移動したチケットの履歴は、移動後の状態を取り込むドメインイベントから始まる。
     ///
     /// # 引数
     ///
@@ -37,13 +882,92 @@ impl TicketStore {
     ///
     /// # 戻り値
     ///
-    /// チケットの参照
-    pub fn get(&self, id: TicketId) -> TicketStoreResult<&Ticket> {
-        self.tickets.get(&id).ok_or(TicketStoreError::NotFound)
+    /// チケットのドメインイベント
+    pub fn history(&self, id: TicketId) -> Vec<RecordedEvent> {
+        self.history.ticket(id)
+    }
+
+    /// すべてのドメインイベントを最初から適用して、射影を構築し直す。
+    ///
+    /// チケットストアの外で、ドメインイベントから独自の射影を構築するために使用する。
+    ///
+    /// # 引数
+    ///
+    /// * `projection` - 構築し直す射影
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn replay(&self, projection: &mut impl Projection) -> EventResult<()> {
+        domain::rebuild(&self.history.collect(), &mut [projection])
+    }
+
+    /// すべてのドメインイベントを最初から適用して、チケットと索引を構築し直す。
+    ///
+    /// 構築し直している間にチケットが変更されないように、`&mut self`を必要とする。
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn rebuild_projections(&mut self) -> EventResult<()> {
+        let mut tickets = Shard::new();
+        let mut index = SearchIndex::default();
+        let mut statuses = StatusIndex::default();
+        let mut blobs = BlobReferences::default();
+        let mut timeline = StatusTimeline::default();
+        let mut subtasks = SubtaskIndex::default();
+        let mut blockers = BlockerIndex::default();
+        domain::rebuild(
+            &self.history.collect(),
+            &mut [
+                &mut tickets,
+                &mut index,
+                &mut statuses,
+                &mut blobs,
+                &mut timeline,
+                &mut subtasks,
+                &mut blockers,
+            ],
+        )?;
+        self.tickets = tickets
+            .into_values()
+            .map(|mut ticket| {
+                // プロジェクトキーの変更より前に記録されたチケットは、変更前のチケットキーを持つ。
+                ticket.key = self.key_of(ticket.id);
+                ticket
+            })
+            .collect();
+        *self.index.get_mut().unwrap_or_else(PoisonError::into_inner) = index;
+        *self
+            .statuses
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = statuses;
+        *self.blobs.get_mut().unwrap_or_else(PoisonError::into_inner) = blobs;
+        *self
+            .timeline
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = timeline;
+        *self
+            .subtasks
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = subtasks;
+        *self
+            .blockers
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = blockers;
+        tracing::info!(
+            project_key = %self.project.key,
+            tickets = self.tickets.len(),
+            "ドメインイベントから射影を構築し直しました。"
+        );
+
+        Ok(())
     }
 
     /// チケットIDを指定して、チケットの可変参照を取得する。
     ///
+    /// 可変参照を通じた変更は、ファイルストレージや検索の索引、ドメインイベントの履歴に反映されない。
+    ///
     /// # 引数
     ///
     /// * `id` - チケットID
@@ -52,11 +976,16 @@ impl TicketStore {
     ///
     /// チケットの可変参照
     pub fn get_mut(&mut self, id: TicketId) -> TicketStoreResult<&mut Ticket> {
-        self.tickets.get_mut(&id).ok_or(TicketStoreError::NotFound)
+        self.tickets.get_mut(id).ok_or(TicketStoreError::NotFound)
     }
 
     /// チケットを更新する。
     ///
+    /// 更新するチケットのシャードの書き込みロックを保持している間に、バージョンを確認してから更新するため、
+    /// 同じバージョンを指定した並行する更新は、1つだけが成功する。
+    /// 完了に更新する場合は、同じロックを保持したまま、完了していないサブタスクとブロッカーがないことを確認する。
+    /// 完了から戻す場合は、同じく完了した親チケットとブロックしているチケットがないことを確認する。
+    ///
     /// # 引数
     ///
     /// * `id` - 更新するチケットのチケットID
@@ -65,24 +994,567 @@ impl TicketStore {
     /// # 戻り値
     ///
     /// `()`
-    pub fn update_ticket(&mut self, id: TicketId, patch: TicketPatch) -> TicketStoreResult<()> {
-        let target = self.get_mut(id)?;
+    pub fn update_ticket(&self, id: TicketId, patch: TicketPatch) -> TicketStoreResult<()> {
+        self.ensure_alive()?;
+        let mut shard = self.tickets.write(id);
+        let target = shard.get(&id).cloned().ok_or_else(|| {
+            tracing::info!(ticket_id = id.0, "更新するチケットが見つかりません。");
+            TicketStoreError::NotFound
+        })?;
         if patch.version != target.version {
+            tracing::info!(
+                ticket_id = id.0,
+                expected_version = target.version,
+                actual_version = patch.version,
+                "チケットのバージョンが一致しません。"
+            );
             return Err(TicketStoreError::VersionNotMatch);
         }
+        let mut events = vec![];
         if let Some(title) = patch.title {
-            target.title = title;
+            events.push(DomainEvent::TitleChanged { title });
         }
         if let Some(description) = patch.description {
-            target.description = description;
+            events.push(DomainEvent::DescriptionChanged { description });
         }
         if let Some(status) = patch.status {
-            target.status = status;
+            if !self.project.workflow.allows(target.status, status) {
+                tracing::info!(
+                    ticket_id = id.0,
+                    from = ?target.status,
+                    to = ?status,
+                    "ワークフローで許可されていないステータスの遷移です。"
+                );
+                return Err(TicketStoreError::TransitionNotAllowed {
+                    from: target.status,
+                    to: status,
+                });
+            }
+            let open = match status {
+                TicketStatus::Done if target.status != TicketStatus::Done => {
+                    self.open_prerequisites(&target)
+                }
+                _ => vec![],
+            };
+            if !open.is_empty() {
+                tracing::info!(
+                    ticket_id = id.0,
+                    open = ?open,
+                    "完了していないサブタスクまたはブロッカーがあります。"
+                );
+                return Err(TicketStoreError::OpenDependencies);
+            }
+            let done = match status {
+                TicketStatus::Done => vec![],
+                _ if target.status == TicketStatus::Done => self.done_dependents(&target),
+                _ => vec![],
+            };
+            if !done.is_empty() {
+                tracing::info!(
+                    ticket_id = id.0,
+                    done = ?done,
+                    "完了した親チケットまたはブロックしているチケットがあります。"
+                );
+                return Err(TicketStoreError::DoneDependents);
+            }
+            events.push(DomainEvent::StatusChanged {
+                from: target.status,
+                to: status,
+            });
+        }
+        if let Some(priority) = patch.priority {
+            events.push(DomainEvent::PriorityChanged { priority });
+        }
+        if let Some(due_date) = patch.due_date {
+            events.push(DomainEvent::DueDateChanged { due_date });
+        }
+        if events.is_empty() {
+            events.push(DomainEvent::Touched);
+        }
+        let updated = self.commit_ticket(&mut shard, id, target.version + 1, events)?;
+        tracing::info!(
+            ticket_id = id.0,
+            version = updated.version,
+            "チケットを更新しました。"
+        );
+        self.emit(TicketEventKind::Updated, &updated);
+
+        Ok(())
+    }
+
+    /// チケットに添付ファイルを追加する。
+    ///
+    /// 添付ファイルの内容は、呼び出す前に保存しておく。チケットのバージョンを1つ進める。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - チケットID
+    /// * `draft` - 追加する添付ファイル
+    ///
+    /// # 戻り値
+    ///
+    /// 追加した添付ファイル
+    pub fn add_attachment(
+        &self,
+        id: TicketId,
+        draft: AttachmentDraft,
+    ) -> TicketStoreResult<Attachment> {
+        self.ensure_alive()?;
+        let attachment = Attachment {
+            id: draft.id,
+            file_name: draft.file_name,
+            content_type: draft.content_type,
+            size: draft.size,
+            digest: draft.digest,
+            uploaded_at: self.clock.now(),
+        };
+        let mut shard = self.tickets.write(id);
+        let version = shard.get(&id).ok_or(TicketStoreError::NotFound)?.version + 1;
+        let added = DomainEvent::AttachmentAdded {
+            attachment: attachment.clone(),
+        };
+        let ticket = self.commit_ticket(&mut shard, id, version, vec![added])?;
+        drop(shard);
+        tracing::info!(
+            ticket_id = id.0,
+            attachment_id = %attachment.id.0,
+            digest = %attachment.digest,
+            size = attachment.size,
+            version = ticket.version,
+            "添付ファイルを追加しました。"
+        );
+        self.emit(TicketEventKind::Updated, &ticket);
+
+        Ok(attachment)
+    }
+
+    /// チケットから添付ファイルを削除する。
+    ///
+    /// 添付ファイルの内容は削除しない。チケットのバージョンを1つ進める。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - チケットID
+    /// * `attachment` - 削除する添付ファイルの添付ファイルID
+    ///
+    /// # 戻り値
+    ///
+    /// 削除した添付ファイル
+    pub fn remove_attachment(
+        &self,
+        id: TicketId,
+        attachment: AttachmentId,
+    ) -> TicketStoreResult<Attachment> {
+        self.ensure_alive()?;
+        let mut shard = self.tickets.write(id);
+        let target = shard.get(&id).ok_or(TicketStoreError::NotFound)?;
+        let version = target.version + 1;
+        let removed = target
+            .attachments
+            .iter()
+            .find(|a| a.id == attachment)
+            .cloned()
+            .ok_or_else(|| {
+                tracing::info!(
+                    ticket_id = id.0,
+                    attachment_id = %attachment.0,
+                    "添付ファイルが見つかりません。"
+                );
+                TicketStoreError::AttachmentNotFound
+            })?;
+        let event = DomainEvent::AttachmentRemoved { attachment };
+        let ticket = self.commit_ticket(&mut shard, id, version, vec![event])?;
+        drop(shard);
+        tracing::info!(
+            ticket_id = id.0,
+            attachment_id = %attachment.0,
+            version = ticket.version,
+            "添付ファイルを削除しました。"
+        );
+        self.emit(TicketEventKind::Updated, &ticket);
+
+        Ok(removed)
+    }
+
+    /// チケットにラベルを追加する。
+    ///
+    /// すでに同じラベルがある場合は、チケットを変更しない。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - チケットID
+    /// * `label` - 追加するラベル
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn add_label(&self, id: TicketId, label: Label) -> TicketStoreResult<()> {
+        self.ensure_alive()?;
+        let mut shard = self.tickets.write(id);
+        let target = shard.get(&id).ok_or(TicketStoreError::NotFound)?;
+        if target.labels.contains(&label) {
+            return Ok(());
+        }
+        let version = target.version + 1;
+        let event = DomainEvent::LabelAdded {
+            label: label.clone(),
+        };
+        let ticket = self.commit_ticket(&mut shard, id, version, vec![event])?;
+        drop(shard);
+        tracing::info!(
+            ticket_id = id.0,
+            %label,
+            version = ticket.version,
+            "ラベルを追加しました。"
+        );
+        self.emit(TicketEventKind::Updated, &ticket);
+
+        Ok(())
+    }
+
+    /// チケットからラベルを取り除く。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - チケットID
+    /// * `label` - 取り除くラベル
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn remove_label(&self, id: TicketId, label: Label) -> TicketStoreResult<()> {
+        self.ensure_alive()?;
+        let mut shard = self.tickets.write(id);
+        let target = shard.get(&id).ok_or(TicketStoreError::NotFound)?;
+        if !target.labels.contains(&label) {
+            return Err(TicketStoreError::NotFound);
         }
-        target.version += 1;
+        let version = target.version + 1;
+        let event = DomainEvent::LabelRemoved {
+            label: label.clone(),
+        };
+        let ticket = self.commit_ticket(&mut shard, id, version, vec![event])?;
+        drop(shard);
+        tracing::info!(
+            ticket_id = id.0,
+            %label,
+            version = ticket.version,
+            "ラベルを取り除きました。"
+        );
+        self.emit(TicketEventKind::Updated, &ticket);
 
         Ok(())
     }
+
+    /// 添付ファイルの内容を参照している、このプロジェクトの添付ファイルの数を返す。
+    pub fn blob_references(&self, digest: &BlobDigest) -> usize {
+        read_lock(&self.blobs).count(digest)
+    }
+
+    /// このプロジェクトの添付ファイルが参照している内容ごとの、参照している添付ファイルの数を返す。
+    pub fn referenced_blobs(&self) -> BTreeMap<BlobDigest, usize> {
+        read_lock(&self.blobs).counts().clone()
+    }
+
+    /// チケットのステータスの遷移を複製して返す。
+    ///
+    /// 射影の読み込みロックは複製する間だけ保持するため、レポートの集計はチケットの変更を妨げない。
+    pub fn status_timeline(&self) -> StatusTimeline {
+        read_lock(&self.timeline).clone()
+    }
+
+    /// チケットの親チケットを設定する。
+    ///
+    /// 完了した親チケットには、完了していないチケットをサブタスクとして追加できない。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - サブタスクにするチケットのチケットID
+    /// * `parent` - 親チケットのチケットID（`None`の場合は親チケットを解除）
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn set_parent(&mut self, id: TicketId, parent: Option<TicketId>) -> TicketStoreResult<()> {
+        self.ensure_alive()?;
+        let target = self.get(id)?;
+        if let Some(parent) = parent {
+            let parent_status = self.get(parent)?.status;
+            if target.parent != Some(parent)
+                && graph::would_cycle(&self.tickets.collect(), id, parent)
+            {
+                tracing::info!(
+                    ticket_id = id.0,
+                    parent = parent.0,
+                    "依存関係が循環するため、親チケットを設定できません。"
+                );
+                return Err(TicketStoreError::DependencyCycle);
+            }
+            if target.parent != Some(parent)
+                && target.status != TicketStatus::Done
+                && parent_status == TicketStatus::Done
+            {
+                tracing::info!(
+                    ticket_id = id.0,
+                    parent = parent.0,
+                    "完了した親チケットに、完了していないサブタスクを追加できません。"
+                );
+                return Err(TicketStoreError::DoneDependents);
+            }
+        }
+        self.save_links(id, DomainEvent::ParentChanged { parent })
+    }
+
+    /// チケットにブロッカーを追加する。
+    ///
+    /// 完了したチケットには、完了していないブロッカーを追加できない。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - ブロックされるチケットのチケットID
+    /// * `blocker` - ブロックするチケットのチケットID
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn add_blocker(&mut self, id: TicketId, blocker: TicketId) -> TicketStoreResult<()> {
+        self.ensure_alive()?;
+        let target = self.get(id)?;
+        let blocker_status = self.get(blocker)?.status;
+        if target.blocked_by.contains(&blocker) {
+            return Ok(());
+        }
+        if graph::would_cycle(&self.tickets.collect(), blocker, id) {
+            tracing::info!(
+                ticket_id = id.0,
+                blocker = blocker.0,
+                "依存関係が循環するため、ブロッカーを追加できません。"
+            );
+            return Err(TicketStoreError::DependencyCycle);
+        }
+        if target.status == TicketStatus::Done && blocker_status != TicketStatus::Done {
+            tracing::info!(
+                ticket_id = id.0,
+                blocker = blocker.0,
+                "完了したチケットに、完了していないブロッカーを追加できません。"
+            );
+            return Err(TicketStoreError::DoneDependents);
+        }
+        self.save_links(id, DomainEvent::BlockerAdded { blocker })
+    }
+
+    /// チケットからブロッカーを取り除く。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - ブロックされているチケットのチケットID
+    /// * `blocker` - 取り除くブロッカーのチケットID
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn remove_blocker(&mut self, id: TicketId, blocker: TicketId) -> TicketStoreResult<()> {
+        self.ensure_alive()?;
+        if !self.get(id)?.blocked_by.contains(&blocker) {
+            return Err(TicketStoreError::NotFound);
+        }
+        self.save_links(id, DomainEvent::BlockerRemoved { blocker })
+    }
+
+    /// チケットの関連を変更するドメインイベントを記録する。
+    fn save_links(&mut self, id: TicketId, event: DomainEvent) -> TicketStoreResult<()> {
+        let mut shard = self.tickets.write(id);
+        let version = shard.get(&id).ok_or(TicketStoreError::NotFound)?.version + 1;
+        let ticket = self.commit_ticket(&mut shard, id, version, vec![event])?;
+        drop(shard);
+        tracing::info!(
+            ticket_id = ticket.id.0,
+            parent = ticket.parent.map(|parent| parent.0),
+            blocked_by = ?ticket.blocked_by,
+            version = ticket.version,
+            "チケットの関連を更新しました。"
+        );
+        self.emit(TicketEventKind::Updated, &ticket);
+
+        Ok(())
+    }
+
+    /// チケットに関連するチケットがないことを確認する。
+    ///
+    /// 関連するチケットがあるチケットは、他のプロジェクトに移動できない。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - チケットID
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn ensure_unlinked(&self, id: TicketId) -> TicketStoreResult<()> {
+        self.get(id)?;
+        if graph::is_linked(&self.tickets.collect(), id) {
+            tracing::info!(ticket_id = id.0, "チケットに関連するチケットがあります。");
+            return Err(TicketStoreError::TicketLinked);
+        }
+        Ok(())
+    }
+
+    /// チケットの依存関係グラフを取得する。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - 起点のチケットのチケットID
+    ///
+    /// # 戻り値
+    ///
+    /// 依存関係グラフ
+    pub fn dependency_graph(&self, id: TicketId) -> TicketStoreResult<DependencyGraph> {
+        self.get(id)?;
+        Ok(graph::dependency_graph(&self.tickets.collect(), id))
+    }
+
+    /// 完了していないチケットを、先に完了しなければならないチケットが前に来るように取得する。
+    ///
+    /// # 戻り値
+    ///
+    /// チケット
+    pub fn open_work(&self) -> Vec<Ticket> {
+        let mut tickets = self.tickets.collect();
+        graph::open_work_order(&tickets)
+            .into_iter()
+            .filter_map(|id| tickets.remove(&id))
+            .collect()
+    }
+
+    /// タイトルまたは説明に検索語をすべて含むチケットを、関連度の高い順に検索する。
+    ///
+    /// # 引数
+    ///
+    /// * `text` - 検索文字列
+    ///
+    /// # 戻り値
+    ///
+    /// 検索結果のチケット
+    pub fn search(&self, text: &SearchText) -> Vec<SearchHit> {
+        let ranked = read_lock(&self.index).search(text);
+        ranked
+            .into_iter()
+            .filter_map(|(id, score)| Some(search::hit(&self.tickets.get(id)?, text, score)))
+            .collect()
+    }
+
+    /// クエリの条件を満たすチケットを、チケットID順に取得する。
+    ///
+    /// # 引数
+    ///
+    /// * `expr` - クエリの構文木
+    ///
+    /// # 戻り値
+    ///
+    /// チケット
+    pub fn query(&self, expr: &Expr) -> Vec<Ticket> {
+        let tickets = self.tickets.collect();
+        let index = read_lock(&self.index);
+        query::evaluate(expr, &self.project, &tickets, &index)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// 条件に一致するチケットを、指定した順番で取得する。
+    ///
+    /// 期限による絞り込みは、チケットストアの時計が示す今日（UTC）を基準にする。
+    /// 期限で並び替える場合、期限のないチケットは並び順にかかわらず末尾に置く。
+    ///
+    /// # 引数
+    ///
+    /// * `query` - 絞り込みと並び順
+    ///
+    /// # 戻り値
+    ///
+    /// チケット
+    pub fn list(&self, query: &TicketQuery) -> Vec<Ticket> {
+        let today = self.clock.today();
+        let week_start = today - TimeDelta::days(today.weekday().num_days_from_monday() as i64);
+        let week_end = week_start + TimeDelta::days(6);
+
+        let tickets: Vec<Ticket> = match query.status {
+            Some(status) => {
+                let ids = read_lock(&self.statuses).ids(status);
+                ids.into_iter()
+                    .filter_map(|id| self.tickets.get(id))
+                    .collect()
+            }
+            None => self.tickets.collect().into_values().collect(),
+        };
+        let mut tickets: Vec<_> = tickets
+            .into_iter()
+            // 索引を読み込んだ後に、ステータスが変更されたチケットを取り除く。
+            .filter(|t| query.status.is_none_or(|status| t.status == status))
+            .filter(|t| query.priority.is_none_or(|priority| t.priority == priority))
+            .filter(|t| {
+                query
+                    .updated_before
+                    .is_none_or(|before| t.updated_at < before)
+            })
+            .filter(|t| match query.due {
+                None => true,
+                Some(DueFilter::Overdue) => t.is_overdue(today),
+                Some(DueFilter::Today) => t.due_date == Some(today),
+                Some(DueFilter::ThisWeek) => t
+                    .due_date
+                    .is_some_and(|due| week_start <= due && due <= week_end),
+                Some(DueFilter::None) => t.due_date.is_none(),
+            })
+            .collect();
+
+        let sort = query.sort;
+        let directed = |ordering: Ordering| {
+            if sort.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        };
+        tickets.sort_by(|a, b| {
+            let ordering = match sort.key {
+                SortKey::Id => directed(a.id.cmp(&b.id)),
+                SortKey::Priority => directed(a.priority.cmp(&b.priority)),
+                SortKey::CreatedAt => directed(a.created_at.cmp(&b.created_at)),
+                SortKey::UpdatedAt => directed(a.updated_at.cmp(&b.updated_at)),
+                SortKey::DueDate => match (a.due_date, b.due_date) {
+                    (Some(a), Some(b)) => directed(a.cmp(&b)),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                },
+            };
+            ordering.then(a.id.cmp(&b.id))
+        });
+
+        tickets
+    }
+}
+
+/// ドメインイベントを、射影が変わるイベントがある場合だけ射影に反映する。
+///
+/// # 引数
+///
+/// * `projection` - 射影
+/// * `records` - 記録されたドメインイベント
+/// * `states` - それぞれのイベントを適用した後のチケット
+fn project<P: Projection>(
+    projection: &RwLock<P>,
+    records: &[RecordedEvent],
+    states: &[Option<Ticket>],
+) {
+    if !records.iter().any(|record| P::observes(&record.event)) {
+        return;
+    }
+    let mut projection = write_lock(projection);
+    for (record, state) in records.iter().zip(states) {
+        projection.project(record, state.as_ref());
+    }
 }
 
 /// チケットストアエラー
@@ -92,7 +1564,53 @@ pub enum TicketStoreError {
     NotFound,
     #[error("チケットのバージョンが一致しません。")]
     VersionNotMatch,
+    #[error("添付ファイルが見つかりません。")]
+    AttachmentNotFound,
+    #[error("ワークフローでは、チケットのステータスを`{from:?}`から`{to:?}`に変更できません。")]
+    TransitionNotAllowed {
+        from: TicketStatus,
+        to: TicketStatus,
+    },
+    #[error("完了していないサブタスクまたはブロッカーがあるチケットは完了にできません。")]
+    OpenDependencies,
+    #[error("完了したチケットのサブタスクまたはブロッカーは、完了していない状態にできません。")]
+    DoneDependents,
+    #[error("チケットの依存関係が循環します。")]
+    DependencyCycle,
+    #[error("サブタスクやブロッカーなどの関連があるチケットは移動できません。")]
+    TicketLinked,
+    #[error("プロジェクトが見つかりません。")]
+    ProjectNotFound,
+    #[error("プロジェクトキーが使用されています。")]
+    ProjectKeyConflict,
+    #[error("チケットがあるプロジェクトは削除できません。")]
+    ProjectNotEmpty,
+    #[error("プロジェクトへのアクセスが許可されていません。")]
+    Forbidden,
+    #[error("チケットを永続化できません。")]
+    Persistence(#[source] Arc<PersistenceError>),
+}
+
+impl TicketStoreError {
+    /// エラーの種類を識別する、レスポンスボディの`code`の値を返す。
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound => "notFound",
+            Self::VersionNotMatch => "versionNotMatch",
+            Self::AttachmentNotFound => "attachmentNotFound",
+            Self::TransitionNotAllowed { .. } => "transitionNotAllowed",
+            Self::OpenDependencies => "openDependencies",
+            Self::DoneDependents => "doneDependents",
+            Self::DependencyCycle => "dependencyCycle",
+            Self::TicketLinked => "ticketLinked",
+            Self::ProjectNotFound => "projectNotFound",
+            Self::ProjectKeyConflict => "projectKeyConflict",
+            Self::ProjectNotEmpty => "projectNotEmpty",
+            Self::Forbidden => "forbidden",
+            Self::Persistence(_) => "persistence",
+        }
+    }
 }
 
 /// チケットストア結果
-type TicketStoreResult<T> = Result<T, TicketStoreError>;
+pub type TicketStoreResult<T> = Result<T, TicketStoreError>;
@@ -0,0 +1,34 @@
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// 読み込みロックを取得する。
+///
+/// 書き込みロックを保持したスレッドがパニックしてロックが汚染されていた場合は、警告を記録して汚染を解除する。
+pub fn read_lock<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|poisoned| {
+        tracing::warn!("汚染されたロックを回復しました。");
+        lock.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// 書き込みロックを取得する。
+///
+/// 書き込みロックを保持したスレッドがパニックしてロックが汚染されていた場合は、警告を記録して汚染を解除する。
+pub fn write_lock<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|poisoned| {
+        tracing::warn!("汚染されたロックを回復しました。");
+        lock.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// ミューテックスのロックを取得する。
+///
+/// ロックを保持したスレッドがパニックしてロックが汚染されていた場合は、警告を記録して汚染を解除する。
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        tracing::warn!("汚染されたロックを回復しました。");
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
@@ -0,0 +1,75 @@
+use tracing_subscriber::EnvFilter;
+
+/// ログの出力形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// 人が読みやすい形式
+    #[default]
+    Pretty,
+    /// 1行1レコードのJSON形式
+    Json,
+}
+
+/// ログ出力形式エラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error(r#"ログの出力形式は、`"pretty"`または`"json"`のいずれかです。"#)]
+pub struct LogFormatError;
+
+/// 文字列からログの出力形式を構築する。
+///
+/// # 引数
+///
+/// * `s` - ログの出力形式を表現する文字列
+///
+/// # 戻り値
+///
+/// ログの出力形式
+fn log_format_from_str(s: &str) -> Result<LogFormat, LogFormatError> {
+    match s.trim().to_lowercase().as_str() {
+        "pretty" => Ok(LogFormat::Pretty),
+        "json" => Ok(LogFormat::Json),
+        _ => Err(LogFormatError),
+    }
+}
+
+impl TryFrom<String> for LogFormat {
+    type Error = LogFormatError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        log_format_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for LogFormat {
+    type Error = LogFormatError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        log_format_from_str(value)
+    }
+}
+
+/// ログのフィルタを指定する環境変数が設定されていない場合のフィルタ
+const DEFAULT_LOG_FILTER: &str = "info";
+
+/// トレーシングのサブスクライバを初期化する。
+///
+/// ログのフィルタは`RUST_LOG`環境変数で指定でき、指定されていない場合は`info`以上を出力する。
+///
+/// # 引数
+///
+/// * `format` - ログの出力形式
+pub fn init(format: LogFormat) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    match format {
+        LogFormat::Pretty => builder.pretty().init(),
+        LogFormat::Json => builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .init(),
+    }
+}
@@ -0,0 +1,799 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::{Arc, Mutex, RwLock};
+
+use axum::body::Bytes;
+use axum::http::{header, Request, Uri};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use http_body_util::Full;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use sha2::Sha256;
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+use crate::clock::Clock;
+use crate::config::WebhooksConfig;
+use crate::crypto::Encryption;
+use crate::dto::WebhookDraft;
+use crate::events::{self, EventReceiver, EventSender, TicketEvent, TicketEventKind};
+use crate::persistence::{self, PersistenceError, PersistenceResult};
+use crate::sync::{lock, read_lock, write_lock};
+
+/// Webhookの購読を永続化するファイル名
+const WEBHOOKS_FILE_NAME: &str = "webhooks.json";
+
+/// デッドレターの配信を永続化するファイル名
+const DEAD_LETTERS_FILE_NAME: &str = "dead-letters.json";
+
+/// 配信するチケットイベントの種類を示すヘッダ
+pub const EVENT_HEADER: &str = "x-ticket-store-event";
+
+/// 配信のIDを示すヘッダ
+///
+/// 再試行や手動の再配信でも同じIDを送るため、受信側は重複した配信を検出できる。
+pub const DELIVERY_HEADER: &str = "x-ticket-store-delivery";
+
+/// 配信を試行した日時を示すヘッダ
+///
+/// 値はUNIX時間の秒数である。署名に含めるため、受信側は古い日時の配信を再送として拒否できる。
+pub const TIMESTAMP_HEADER: &str = "x-ticket-store-timestamp";
+
+/// ボディの署名を示すヘッダ
+///
+/// 値は`sha256=`に続けて、Webhookの秘密鍵による`{タイムスタンプ}.{ボディ}`のHMAC-SHA256を
+/// 16進数で表現した文字列である。
+pub const SIGNATURE_HEADER: &str = "x-ticket-store-signature";
+
+/// WebhookのID
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub struct WebhookId(pub Uuid);
+
+/// 配信のID
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub struct DeliveryId(pub Uuid);
+
+/// Webhookの配信先URL
+///
+/// TLSには対応していないため、`http`スキームのURLだけを受け付ける。
+/// HTTPSで受信する場合は、TLSを終端するプロキシを経由させる。
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct WebhookUrl(Uri);
+
+impl WebhookUrl {
+    /// URLを返す。
+    pub fn uri(&self) -> &Uri {
+        &self.0
+    }
+}
+
+impl fmt::Display for WebhookUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<WebhookUrl> for String {
+    fn from(value: WebhookUrl) -> Self {
+        value.to_string()
+    }
+}
+
+/// Webhookの配信先URLエラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+pub enum WebhookUrlError {
+    #[error("Webhookの配信先URLの形式が誤っています。")]
+    Invalid,
+    #[error("Webhookの配信先URLは、`http`スキームでなければなりません。")]
+    UnsupportedScheme,
+}
+
+/// 文字列からWebhookの配信先URLを構築する。
+///
+/// # 引数
+///
+/// * `s` - URLを表現する文字列
+///
+/// # 戻り値
+///
+/// Webhookの配信先URL
+fn webhook_url_from_str(s: &str) -> Result<WebhookUrl, WebhookUrlError> {
+    let uri: Uri = s.trim().parse().map_err(|_| WebhookUrlError::Invalid)?;
+    if uri.authority().is_none() {
+        return Err(WebhookUrlError::Invalid);
+    }
+    if uri.scheme_str() != Some("http") {
+        return Err(WebhookUrlError::UnsupportedScheme);
+    }
+
+    Ok(WebhookUrl(uri))
+}
+
+impl TryFrom<String> for WebhookUrl {
+    type Error = WebhookUrlError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        webhook_url_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for WebhookUrl {
+    type Error = WebhookUrlError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        webhook_url_from_str(value)
+    }
+}
+
+/// Webhookの秘密鍵
+///
+/// 秘密鍵はAPIの応答やログに出力しない。
+#[derive(Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct WebhookSecret(String);
+
+impl WebhookSecret {
+    /// 秘密鍵のバイト列を返す。
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl fmt::Debug for WebhookSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WebhookSecret(..)")
+    }
+}
+
+/// Webhookの秘密鍵の最小文字数
+const WEBHOOK_SECRET_MIN_LENGTH: usize = 16;
+
+/// Webhookの秘密鍵エラー
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Webhookの秘密鍵は、{WEBHOOK_SECRET_MIN_LENGTH}文字以上です。")]
+pub struct WebhookSecretError;
+
+/// 文字列からWebhookの秘密鍵を構築する。
+///
+/// # 引数
+///
+/// * `s` - 秘密鍵
+///
+/// # 戻り値
+///
+/// Webhookの秘密鍵
+fn webhook_secret_from_str(s: &str) -> Result<WebhookSecret, WebhookSecretError> {
+    if s.chars().count() < WEBHOOK_SECRET_MIN_LENGTH {
+        return Err(WebhookSecretError);
+    }
+
+    Ok(WebhookSecret(s.into()))
+}
+
+impl TryFrom<String> for WebhookSecret {
+    type Error = WebhookSecretError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        webhook_secret_from_str(&value)
+    }
+}
+
+impl TryFrom<&str> for WebhookSecret {
+    type Error = WebhookSecretError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        webhook_secret_from_str(value)
+    }
+}
+
+/// Webhookの購読
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub id: WebhookId,
+    pub url: WebhookUrl,
+    /// 配信するチケットイベントの種類（空の場合はすべての種類）
+    pub events: BTreeSet<TicketEventKind>,
+    pub secret: WebhookSecret,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    /// チケットイベントを配信するか確認する。
+    pub fn accepts(&self, kind: TicketEventKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+}
+
+/// APIで返すWebhookの購読
+///
+/// 秘密鍵を含まない。
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookSummary {
+    pub id: WebhookId,
+    pub url: WebhookUrl,
+    pub events: BTreeSet<TicketEventKind>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<&Webhook> for WebhookSummary {
+    fn from(value: &Webhook) -> Self {
+        Self {
+            id: value.id,
+            url: value.url.clone(),
+            events: value.events.clone(),
+            created_at: value.created_at,
+        }
+    }
+}
+
+/// チケットイベントの配信
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+    pub id: DeliveryId,
+    pub webhook: WebhookId,
+    pub event: TicketEvent,
+    /// 配信を試行した回数
+    pub attempts: u32,
+    /// 最後に失敗した理由
+    pub last_error: Option<String>,
+    /// デッドレターに移した日時
+    pub failed_at: Option<DateTime<Utc>>,
+}
+
+/// タイムスタンプとボディの署名を計算する。
+///
+/// # 引数
+///
+/// * `secret` - Webhookの秘密鍵
+/// * `timestamp` - [`TIMESTAMP_HEADER`]ヘッダの値
+/// * `body` - 配信するボディ
+///
+/// # 戻り値
+///
+/// [`SIGNATURE_HEADER`]ヘッダの値
+pub fn sign(secret: &WebhookSecret, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMACは任意の長さの鍵を受け付ける");
+    mac.update(format!("{timestamp}.").as_bytes());
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Webhookの購読とチケットイベントの配信
+///
+/// チケットイベントはリクエストの処理とは別のタスクで配信するため、チケットの変更の応答時間は配信先に依存しない。
+/// 配信に失敗した場合は指数関数的に間隔を空けて再試行し、最大回数を超えた配信はデッドレターに移す。
+/// デッドレターは手動で再配信できる。
+/// デッドレターが最大数を超えた場合は、古い配信から破棄する。
+/// 同時に試行する配信は最大数までに制限し、超えた配信は試行中の配信が終わるまで開始を待つ。
+///
+/// 購読とデッドレターの配信はデータディレクトリに永続化するが、配信中の配信は永続化しない。
+#[derive(Debug)]
+pub struct Webhooks {
+    config: WebhooksConfig,
+    subscriptions: RwLock<Vec<Webhook>>,
+    /// デッドレターに移した順の配信
+    dead_letters: RwLock<Vec<Delivery>>,
+    /// デッドレターを変更するたびに増やす版
+    ///
+    /// デッドレターの書き込みロックを保持している間に増やす。
+    dead_letters_revision: AtomicU64,
+    /// ファイルに書き出したデッドレターの版
+    ///
+    /// デッドレターは、ロックを解放してから複製を書き出すため、書き出す順番は変更した順番と一致しない。
+    /// 書き出している間はこのロックを保持し、書き出したものより古い版は書き出さない。
+    /// デッドレターのロックを保持したまま、このロックを取得してはならない。
+    saved_dead_letters: Mutex<u64>,
+    /// 同時に試行する配信の数を制限するセマフォ
+    deliveries: Arc<Semaphore>,
+    /// 購読を永続化するファイルのパス
+    path: Option<PathBuf>,
+    /// デッドレターの配信を永続化するファイルのパス
+    dead_letters_path: Option<PathBuf>,
+    /// 購読とデッドレターの配信を永続化するファイルの暗号化
+    encryption: Encryption,
+    client: Client<HttpConnector, Full<Bytes>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Webhooks {
+    /// 購読を永続化しないWebhookの配信を構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `config` - Webhook設定
+    /// * `clock` - 購読や失敗の日時に使用する時計
+    ///
+    /// # 戻り値
+    ///
+    /// Webhookの配信
+    pub fn in_memory(config: &WebhooksConfig, clock: Arc<dyn Clock>) -> Arc<Self> {
+        Arc::new(Self::build(
+            config,
+            vec![],
+            vec![],
+            None,
+            Encryption::default(),
+            clock,
+        ))
+    }
+
+    /// データディレクトリから購読とデッドレターの配信を復元して、Webhookの配信を構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - データディレクトリ
+    /// * `config` - Webhook設定
+    /// * `clock` - 購読や失敗の日時に使用する時計
+    ///
+    /// # 戻り値
+    ///
+    /// Webhookの配信
+    pub fn open(
+        data_dir: &Path,
+        config: &WebhooksConfig,
+        clock: Arc<dyn Clock>,
+    ) -> PersistenceResult<Arc<Self>> {
+        Self::open_encrypted(data_dir, config, clock, &Encryption::default())
+    }
+
+    /// 暗号化を指定して、データディレクトリから購読とデッドレターの配信を復元して、Webhookの配信を構築する。
+    ///
+    /// # 引数
+    ///
+    /// * `data_dir` - データディレクトリ
+    /// * `config` - Webhook設定
+    /// * `clock` - 購読や失敗の日時に使用する時計
+    /// * `encryption` - 購読とデッドレターの配信を永続化するファイルの暗号化
+    ///
+    /// # 戻り値
+    ///
+    /// Webhookの配信
+    pub fn open_encrypted(
+        data_dir: &Path,
+        config: &WebhooksConfig,
+        clock: Arc<dyn Clock>,
+        encryption: &Encryption,
+    ) -> PersistenceResult<Arc<Self>> {
+        std::fs::create_dir_all(data_dir)?;
+        let path = data_dir.join(WEBHOOKS_FILE_NAME);
+        let subscriptions = match persistence::read_file(&path, encryption)? {
+            Some((content, _)) => {
+                serde_json::from_slice(&content).map_err(PersistenceError::CorruptedWebhooks)?
+            }
+            None => vec![],
+        };
+        let dead_letters_path = data_dir.join(DEAD_LETTERS_FILE_NAME);
+        let dead_letters = match persistence::read_file(&dead_letters_path, encryption)? {
+            Some((content, _)) => {
+                serde_json::from_slice(&content).map_err(PersistenceError::CorruptedDeadLetters)?
+            }
+            None => vec![],
+        };
+
+        Ok(Arc::new(Self::build(
+            config,
+            subscriptions,
+            dead_letters,
+            Some(data_dir),
+            encryption.clone(),
+            clock,
+        )))
+    }
+
+    fn build(
+        config: &WebhooksConfig,
+        subscriptions: Vec<Webhook>,
+        dead_letters: Vec<Delivery>,
+        data_dir: Option<&Path>,
+        encryption: Encryption,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            config: config.clone(),
+            subscriptions: RwLock::new(subscriptions),
+            dead_letters: RwLock::new(dead_letters),
+            dead_letters_revision: AtomicU64::new(0),
+            saved_dead_letters: Mutex::new(0),
+            deliveries: Arc::new(Semaphore::new(config.max_concurrent_deliveries)),
+            path: data_dir.map(|dir| dir.join(WEBHOOKS_FILE_NAME)),
+            dead_letters_path: data_dir.map(|dir| dir.join(DEAD_LETTERS_FILE_NAME)),
+            encryption,
+            client: Client::builder(TokioExecutor::new()).build_http(),
+            clock,
+        }
+    }
+
+    /// チケットイベントを受信して配信するタスクを起動する。
+    ///
+    /// タスクは、返した送信側とその複製がすべて破棄されると終了する。
+    ///
+    /// # 戻り値
+    ///
+    /// チケットイベントの送信側
+    pub fn start(self: &Arc<Self>) -> EventSender {
+        let (sender, receiver) = events::channel();
+        tokio::spawn(Arc::clone(self).dispatch(receiver));
+
+        sender
+    }
+
+    /// チケットイベントを受信するたびに、購読しているWebhookへの配信を開始する。
+    ///
+    /// 試行中の配信が最大数に達している場合は、いずれかの配信が終わるまで次の配信を開始しない。
+    /// その間に受信したチケットイベントは、チャネルに残る。
+    async fn dispatch(self: Arc<Self>, mut receiver: EventReceiver) {
+        while let Some(event) = receiver.recv().await {
+            let targets: Vec<_> = read_lock(&self.subscriptions)
+                .iter()
+                .filter(|webhook| webhook.accepts(event.kind))
+                .map(|webhook| webhook.id)
+                .collect();
+            for webhook in targets {
+                let delivery = Delivery {
+                    id: DeliveryId(Uuid::new_v4()),
+                    webhook,
+                    event: event.clone(),
+                    attempts: 0,
+                    last_error: None,
+                    failed_at: None,
+                };
+                let Ok(permit) = Arc::clone(&self.deliveries).acquire_owned().await else {
+                    return;
+                };
+                let webhooks = Arc::clone(&self);
+                tokio::spawn(async move {
+                    webhooks.deliver(delivery).await;
+                    drop(permit);
+                });
+            }
+        }
+        tracing::debug!("チケットイベントの配信を終了しました。");
+    }
+
+    /// 配信に成功するか最大回数に達するまで、配信を試行する。
+    ///
+    /// 最大回数に達した配信はデッドレターに移し、ブロッキングするタスクでファイルに書き出す。
+    async fn deliver(self: Arc<Self>, mut delivery: Delivery) {
+        loop {
+            let Some(webhook) = self.find(delivery.webhook) else {
+                tracing::info!(
+                    delivery_id = %delivery.id.0,
+                    webhook_id = %delivery.webhook.0,
+                    "購読が解除されたため、配信を取りやめました。"
+                );
+                return;
+            };
+            delivery.attempts += 1;
+            match self.send(&webhook, &delivery).await {
+                Ok(()) => {
+                    tracing::info!(
+                        delivery_id = %delivery.id.0,
+                        webhook_id = %webhook.id.0,
+                        event = delivery.event.kind.as_str(),
+                        attempts = delivery.attempts,
+                        "チケットイベントを配信しました。"
+                    );
+                    return;
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        delivery_id = %delivery.id.0,
+                        webhook_id = %webhook.id.0,
+                        attempts = delivery.attempts,
+                        error = %e,
+                        "チケットイベントを配信できません。"
+                    );
+                    delivery.last_error = Some(e);
+                }
+            }
+            if delivery.attempts >= self.config.max_attempts {
+                break;
+            }
+            tokio::time::sleep(self.config.backoff(delivery.attempts)).await;
+        }
+
+        tracing::error!(
+            delivery_id = %delivery.id.0,
+            webhook_id = %delivery.webhook.0,
+            attempts = delivery.attempts,
+            "配信の試行回数が上限に達したため、デッドレターに移しました。"
+        );
+        delivery.failed_at = Some(self.clock.now());
+        let (revision, dead_letters) = {
+            let mut dead_letters = write_lock(&self.dead_letters);
+            dead_letters.push(delivery);
+            let overflow = dead_letters
+                .len()
+                .saturating_sub(self.config.max_dead_letters);
+            if overflow > 0 {
+                dead_letters.drain(..overflow);
+                tracing::warn!(
+                    dropped = overflow,
+                    "デッドレターが最大数を超えたため、古い配信を破棄しました。"
+                );
+            }
+            (self.next_dead_letters_revision(), dead_letters.clone())
+        };
+        let webhooks = Arc::clone(&self);
+        let saved = tokio::task::spawn_blocking(move || {
+            webhooks.save_dead_letters(revision, &dead_letters);
+        })
+        .await;
+        if let Err(e) = saved {
+            tracing::error!(error = %e, "Webhookのデッドレターを書き出すタスクが失敗しました。");
+        }
+    }
+
+    /// 配信を1回試行する。
+    ///
+    /// # 戻り値
+    ///
+    /// 配信先が成功を示すステータスコードを返した場合は`Ok`、それ以外の場合は失敗した理由
+    async fn send(&self, webhook: &Webhook, delivery: &Delivery) -> Result<(), String> {
+        let body = serde_json::to_vec(&delivery.event).map_err(|e| e.to_string())?;
+        let timestamp = self.clock.now().timestamp();
+        let request = Request::post(webhook.url.uri())
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, delivery.event.kind.as_str())
+            .header(DELIVERY_HEADER, delivery.id.0.to_string())
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, sign(&webhook.secret, timestamp, &body))
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|e| e.to_string())?;
+
+        match tokio::time::timeout(self.config.timeout(), self.client.request(request)).await {
+            Ok(Ok(response)) if response.status().is_success() => Ok(()),
+            Ok(Ok(response)) => Err(format!(
+                "配信先がステータスコード{}を返しました。",
+                response.status().as_u16()
+            )),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err("配信がタイムアウトしました。".into()),
+        }
+    }
+
+    /// 購読を取得する。
+    fn find(&self, id: WebhookId) -> Option<Webhook> {
+        read_lock(&self.subscriptions)
+            .iter()
+            .find(|webhook| webhook.id == id)
+            .cloned()
+    }
+
+    /// 購読をファイルに書き出す。
+    fn save(&self, subscriptions: &[Webhook]) -> WebhookResult<()> {
+        match &self.path {
+            Some(path) => persistence::write_encrypted_json(path, &subscriptions, &self.encryption)
+                .map_err(|e| WebhookError::Persistence(Arc::new(e))),
+            None => Ok(()),
+        }
+    }
+
+    /// デッドレターの版を増やして、増やした後の版を返す。
+    ///
+    /// デッドレターの書き込みロックを保持したまま呼び出す。
+    fn next_dead_letters_revision(&self) -> u64 {
+        self.dead_letters_revision
+            .fetch_add(1, atomic::Ordering::Relaxed)
+            + 1
+    }
+
+    /// デッドレターの配信をファイルに書き出す。
+    ///
+    /// すでにより新しい版を書き出している場合は、何もしない。
+    /// 配信のタスクからも呼び出すため、書き出せない場合はログに記録して、メモリ上のデッドレターを保持する。
+    ///
+    /// # 引数
+    ///
+    /// * `revision` - デッドレターの版
+    /// * `dead_letters` - 版に対応するデッドレターの配信
+    fn save_dead_letters(&self, revision: u64, dead_letters: &[Delivery]) {
+        let Some(path) = &self.dead_letters_path else {
+            return;
+        };
+        let mut saved = lock(&self.saved_dead_letters);
+        if *saved >= revision {
+            return;
+        }
+        match persistence::write_encrypted_json(path, &dead_letters, &self.encryption) {
+            Ok(()) => *saved = revision,
+            Err(e) => tracing::error!(error = %e, "Webhookのデッドレターを永続化できません。"),
+        }
+    }
+
+    /// 購読とデッドレターの配信を永続化するファイルが現在の鍵で暗号化されていない場合は、暗号化し直す。
+    ///
+    /// # 戻り値
+    ///
+    /// 暗号化し直した場合は`true`
+    pub fn reencrypt(&self) -> PersistenceResult<bool> {
+        let (Some(path), Some(dead_letters_path)) = (&self.path, &self.dead_letters_path) else {
+            return Ok(false);
+        };
+        let subscriptions = write_lock(&self.subscriptions);
+        let dead_letters = write_lock(&self.dead_letters);
+        let mut reencrypted = false;
+        if let Some((_, sealing)) = persistence::read_file(path, &self.encryption)? {
+            if self.encryption.is_stale(sealing) {
+                persistence::write_encrypted_json(path, &*subscriptions, &self.encryption)?;
+                reencrypted = true;
+            }
+        }
+        if let Some((_, sealing)) = persistence::read_file(dead_letters_path, &self.encryption)? {
+            if self.encryption.is_stale(sealing) {
+                persistence::write_encrypted_json(
+                    dead_letters_path,
+                    &*dead_letters,
+                    &self.encryption,
+                )?;
+                reencrypted = true;
+            }
+        }
+
+        Ok(reencrypted)
+    }
+
+    /// Webhookを購読する。
+    ///
+    /// # 引数
+    ///
+    /// * `draft` - Webhookのドラフト
+    ///
+    /// # 戻り値
+    ///
+    /// 購読したWebhook
+    pub fn subscribe(&self, draft: WebhookDraft) -> WebhookResult<WebhookSummary> {
+        let webhook = Webhook {
+            id: WebhookId(Uuid::new_v4()),
+            url: draft.url,
+            events: draft.events,
+            secret: draft.secret,
+            created_at: self.clock.now(),
+        };
+        let mut subscriptions = write_lock(&self.subscriptions);
+        subscriptions.push(webhook.clone());
+        if let Err(e) = self.save(&subscriptions) {
+            subscriptions.pop();
+            return Err(e);
+        }
+        tracing::info!(
+            webhook_id = %webhook.id.0,
+            url = %webhook.url,
+            "Webhookを購読しました。"
+        );
+
+        Ok(WebhookSummary::from(&webhook))
+    }
+
+    /// 購読の一覧を取得する。
+    pub fn list(&self) -> Vec<WebhookSummary> {
+        read_lock(&self.subscriptions)
+            .iter()
+            .map(WebhookSummary::from)
+            .collect()
+    }
+
+    /// 購読を取得する。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - WebhookのID
+    ///
+    /// # 戻り値
+    ///
+    /// 購読したWebhook
+    pub fn get(&self, id: WebhookId) -> WebhookResult<WebhookSummary> {
+        self.find(id)
+            .map(|webhook| WebhookSummary::from(&webhook))
+            .ok_or(WebhookError::NotFound)
+    }
+
+    /// 購読を解除する。
+    ///
+    /// 配信中の配信は、次の試行で取りやめる。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - WebhookのID
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn unsubscribe(&self, id: WebhookId) -> WebhookResult<()> {
+        let mut subscriptions = write_lock(&self.subscriptions);
+        let index = subscriptions
+            .iter()
+            .position(|webhook| webhook.id == id)
+            .ok_or(WebhookError::NotFound)?;
+        let removed = subscriptions.remove(index);
+        if let Err(e) = self.save(&subscriptions) {
+            subscriptions.insert(index, removed);
+            return Err(e);
+        }
+        drop(subscriptions);
+        let changed = {
+            let mut dead_letters = write_lock(&self.dead_letters);
+            let len = dead_letters.len();
+            dead_letters.retain(|delivery| delivery.webhook != id);
+            (dead_letters.len() != len)
+                .then(|| (self.next_dead_letters_revision(), dead_letters.clone()))
+        };
+        if let Some((revision, dead_letters)) = changed {
+            self.save_dead_letters(revision, &dead_letters);
+        }
+        tracing::info!(webhook_id = %id.0, "Webhookの購読を解除しました。");
+
+        Ok(())
+    }
+
+    /// デッドレターに移した順の配信を取得する。
+    pub fn dead_letters(&self) -> Vec<Delivery> {
+        read_lock(&self.dead_letters).clone()
+    }
+
+    /// デッドレターの配信を、試行回数を戻して再配信する。
+    ///
+    /// # 引数
+    ///
+    /// * `id` - 配信のID
+    ///
+    /// # 戻り値
+    ///
+    /// `()`
+    pub fn replay(self: &Arc<Self>, id: DeliveryId) -> WebhookResult<()> {
+        let (mut delivery, revision, dead_letters) = {
+            let mut dead_letters = write_lock(&self.dead_letters);
+            let index = dead_letters
+                .iter()
+                .position(|delivery| delivery.id == id)
+                .ok_or(WebhookError::DeliveryNotFound)?;
+            let delivery = dead_letters.remove(index);
+            (
+                delivery,
+                self.next_dead_letters_revision(),
+                dead_letters.clone(),
+            )
+        };
+        self.save_dead_letters(revision, &dead_letters);
+        delivery.attempts = 0;
+        delivery.failed_at = None;
+        tracing::info!(delivery_id = %id.0, "デッドレターの配信を再配信します。");
+        let webhooks = Arc::clone(self);
+        tokio::spawn(async move {
+            let Ok(_permit) = Arc::clone(&webhooks.deliveries).acquire_owned().await else {
+                return;
+            };
+            webhooks.deliver(delivery).await;
+        });
+
+        Ok(())
+    }
+}
+
+/// Webhookエラー
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum WebhookError {
+    #[error("Webhookが見つかりません。")]
+    NotFound,
+    #[error("配信が見つかりません。")]
+    DeliveryNotFound,
+    #[error("Webhookの購読を永続化できません。")]
+    Persistence(#[source] Arc<PersistenceError>),
+}
+
+/// Webhook結果
+pub type WebhookResult<T> = Result<T, WebhookError>;
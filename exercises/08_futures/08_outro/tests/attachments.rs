@@ -0,0 +1,332 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::Router;
+use http_body_util::BodyExt;
+use serde_json::Value;
+use ticket_store::actor::StoreHandle;
+use ticket_store::blobs::{self, BlobStore};
+use ticket_store::clock::SystemClock;
+use ticket_store::config::Config;
+use ticket_store::crypto::Encryption;
+use ticket_store::dto::{AttachmentDraft, TicketDraft};
+use ticket_store::events::ChangeFeed;
+use ticket_store::health::Health;
+use ticket_store::middleware::AdminToken;
+use ticket_store::models::{
+    AttachmentId, BlobDigest, Priority, Project, TicketDescription, TicketTitle,
+};
+use ticket_store::registry::ProjectRegistry;
+use ticket_store::saved_queries::SavedQueries;
+use ticket_store::server::{app, AppState};
+use ticket_store::store::TicketStore;
+use ticket_store::webhooks::Webhooks;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+
+const BOUNDARY: &str = "attachment-boundary";
+
+/// 添付ファイルの内容のストアと、チケットを2つ登録したルーターを構築する。
+async fn router(config: &Config) -> (Arc<BlobStore>, Router) {
+    let store = StoreHandle::spawn(ProjectRegistry::default(), 1024);
+    let blobs = BlobStore::in_memory(&config.attachments);
+    let state = AppState {
+        store,
+        health: Arc::new(Health::new(config)),
+        webhooks: Webhooks::in_memory(&config.webhooks, Arc::new(SystemClock)),
+        saved_queries: SavedQueries::in_memory(Arc::new(SystemClock)),
+        feed: ChangeFeed::default(),
+        blobs: Arc::clone(&blobs),
+        encryption: Encryption::default(),
+        admin: config.admin.token.clone(),
+    };
+    let router = app(state, &config.limits);
+    for title in ["羅生門", "鼻"] {
+        let body = format!(r#"{{"title": "{title}", "description": "説明"}}"#);
+        let (status, ..) = send(&router, "POST", "/projects/TICKET/tickets", &[], body).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    (blobs, router)
+}
+
+async fn send(
+    router: &Router,
+    method: &str,
+    uri: &str,
+    headers: &[(&str, &str)],
+    body: impl Into<Body>,
+) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let mut request = Request::builder().method(method).uri(uri);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    if !headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+    {
+        request = request.header(header::CONTENT_TYPE, "application/json");
+    }
+    let response = router
+        .clone()
+        .oneshot(request.body(body.into()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, headers, body.to_vec())
+}
+
+/// `file`フィールドに添付ファイルを指定して、チケットにアップロードする。
+async fn upload(
+    router: &Router,
+    ticket: u64,
+    file_name: &str,
+    content: &[u8],
+    user: &str,
+) -> (StatusCode, Value) {
+    let mut body = format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{file_name}\"\r\n\
+         Content-Type: text/html\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend_from_slice(content);
+    body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+    let content_type = format!("multipart/form-data; boundary={BOUNDARY}");
+    let (status, _, body) = send(
+        router,
+        "POST",
+        &format!("/projects/TICKET/tickets/{ticket}/attachments"),
+        &[("content-type", &content_type), ("x-user", user)],
+        body,
+    )
+    .await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn attachment_uri(ticket: u64, attachment: &Value) -> String {
+    format!(
+        "/projects/TICKET/tickets/{ticket}/attachments/{}",
+        attachment["id"].as_str().unwrap()
+    )
+}
+
+#[tokio::test]
+async fn uploads_are_sniffed_and_share_identical_content() {
+    let config = Config::default();
+    let (blobs, router) = router(&config).await;
+
+    let (status, first) = upload(&router, 0, "../../screenshot.png", PNG, "alice").await;
+    assert_eq!(status, StatusCode::CREATED, "{first}");
+    assert_eq!(first["fileName"], "screenshot.png");
+    assert_eq!(first["contentType"], "image/png");
+    assert_eq!(first["size"], PNG.len());
+    assert_eq!(first["digest"], BlobDigest::of(PNG).as_str());
+
+    let (_, _, body) = send(&router, "GET", "/projects/TICKET/tickets/0", &[], "").await;
+    let ticket: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(ticket["version"], 1);
+    assert_eq!(ticket["attachments"][0]["id"], first["id"]);
+
+    let (status, second) = upload(&router, 1, "copy.png", PNG, "alice").await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(second["digest"], first["digest"]);
+    assert_ne!(second["id"], first["id"]);
+    assert_eq!(blobs.list().unwrap().len(), 1);
+
+    let (status, text) = upload(&router, 0, "notes.txt", "メモ".as_bytes(), "alice").await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(text["contentType"], "text/plain; charset=utf-8");
+    let (_, _, body) = send(
+        &router,
+        "GET",
+        "/projects/TICKET/tickets/0/attachments",
+        &[],
+        "",
+    )
+    .await;
+    let attachments: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(attachments.as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn downloads_support_single_byte_ranges() {
+    let config = Config::default();
+    let (_, router) = router(&config).await;
+    let (_, attachment) = upload(&router, 0, "数字.txt", b"0123456789", "alice").await;
+    let uri = attachment_uri(0, &attachment);
+
+    let (status, headers, body) = send(&router, "GET", &uri, &[], "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, b"0123456789");
+    assert_eq!(headers[header::CONTENT_TYPE], "text/plain; charset=utf-8");
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    assert_eq!(headers[header::ACCEPT_RANGES], "bytes");
+    assert_eq!(
+        headers[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"__.txt\"; filename*=UTF-8''%E6%95%B0%E5%AD%97.txt"
+    );
+
+    let (status, headers, body) = send(&router, "GET", &uri, &[("range", "bytes=2-4")], "").await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(body, b"234");
+    assert_eq!(headers[header::CONTENT_RANGE], "bytes 2-4/10");
+    let (status, _, body) = send(&router, "GET", &uri, &[("range", "bytes=-3")], "").await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(body, b"789");
+
+    let (status, headers, _) = send(&router, "GET", &uri, &[("range", "bytes=20-")], "").await;
+    assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(headers[header::CONTENT_RANGE], "bytes */10");
+
+    let missing = format!("/projects/TICKET/tickets/0/attachments/{}", Uuid::new_v4());
+    let (status, _, _) = send(&router, "GET", &missing, &[], "").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn shared_content_is_kept_until_the_last_reference_is_removed() {
+    let config = Config::default();
+    let (blobs, router) = router(&config).await;
+    let (_, first) = upload(&router, 0, "a.png", PNG, "alice").await;
+    let (_, second) = upload(&router, 1, "b.png", PNG, "alice").await;
+    let digest = BlobDigest::of(PNG);
+
+    let (status, _, _) = send(&router, "DELETE", &attachment_uri(0, &first), &[], "").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(blobs.size(&digest).unwrap().is_some());
+    let (status, _, body) = send(&router, "GET", &attachment_uri(1, &second), &[], "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, PNG);
+
+    let (status, _, _) = send(&router, "DELETE", &attachment_uri(1, &second), &[], "").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(blobs.size(&digest).unwrap().is_none());
+    let (status, _, _) = send(&router, "DELETE", &attachment_uri(1, &second), &[], "").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn oversized_and_unauthorized_uploads_are_not_stored() {
+    let mut config = Config::default();
+    config.attachments.max_bytes = 8;
+    let (blobs, router) = router(&config).await;
+
+    let (status, body) = upload(&router, 0, "big.bin", b"123456789", "alice").await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE, "{body}");
+    let (status, _) = upload(&router, 0, "fits.bin", b"12345678", "alice").await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let project =
+        r#"{"key": "WEB", "name": "ウェブサイト", "permissions": {"writers": ["alice"]}}"#;
+    let (status, ..) = send(&router, "POST", "/projects", &[], project).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, ..) = send(
+        &router,
+        "POST",
+        "/projects/WEB/tickets",
+        &[("x-user", "alice")],
+        r#"{"title": "芋粥", "description": "説明"}"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let body = format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"x.txt\"\r\n\r\nx\r\n--{BOUNDARY}--\r\n"
+    );
+    let content_type = format!("multipart/form-data; boundary={BOUNDARY}");
+    let (status, ..) = send(
+        &router,
+        "POST",
+        "/projects/WEB/tickets/0/attachments",
+        &[("content-type", &content_type), ("x-user", "bob")],
+        body,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(blobs.list().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn orphaned_blobs_are_listed_and_deleted() {
+    let mut config = Config::default();
+    config.admin.token = Some(AdminToken::try_from("s3cret-admin-token").unwrap());
+    let (blobs, router) = router(&config).await;
+    upload(&router, 0, "a.png", PNG, "alice").await;
+    // アップロードの途中で失敗すると、内容だけが残る。
+    let orphan = blobs.put(b"orphan").unwrap();
+
+    for method in ["GET", "DELETE"] {
+        for headers in [&[][..], &[("authorization", "Bearer wrong-token")][..]] {
+            let (status, ..) = send(&router, method, "/admin/orphaned-blobs", headers, "").await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{method} {headers:?}");
+        }
+    }
+    assert!(blobs.size(&orphan).unwrap().is_some());
+
+    let admin = [("authorization", "Bearer s3cret-admin-token")];
+    let (status, _, body) = send(&router, "GET", "/admin/orphaned-blobs", &admin, "").await;
+    assert_eq!(status, StatusCode::OK);
+    let orphans: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(
+        orphans,
+        serde_json::json!([{"digest": orphan.as_str(), "size": 6}])
+    );
+
+    let (status, _, body) = send(&router, "DELETE", "/admin/orphaned-blobs", &admin, "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), orphans);
+    assert!(blobs.size(&orphan).unwrap().is_none());
+    assert!(blobs.size(&BlobDigest::of(PNG)).unwrap().is_some());
+}
+
+#[tokio::test]
+async fn orphaned_blobs_are_kept_while_admin_api_is_disabled() {
+    let config = Config::default();
+    let (blobs, router) = router(&config).await;
+    let orphan = blobs.put(b"orphan").unwrap();
+
+    let admin = [("authorization", "Bearer s3cret-admin-token")];
+    let (status, _, body) = send(&router, "DELETE", "/admin/orphaned-blobs", &admin, "").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(
+        serde_json::from_slice::<Value>(&body).unwrap()["code"],
+        "adminDisabled"
+    );
+    assert!(blobs.size(&orphan).unwrap().is_some());
+}
+
+#[test]
+fn attachments_and_blobs_are_restored_from_data_dir() {
+    let config = Config::default();
+    let data_dir = tempfile::tempdir().unwrap();
+    let digest = {
+        let blobs = BlobStore::open(data_dir.path(), &config.attachments).unwrap();
+        let digest = blobs.put(PNG).unwrap();
+        let store = TicketStore::create(data_dir.path(), Project::default()).unwrap();
+        let id = store
+            .add_ticket(TicketDraft {
+                title: TicketTitle::try_from("羅生門").unwrap(),
+                description: TicketDescription::try_from("説明").unwrap(),
+                priority: Priority::default(),
+                due_date: None,
+            })
+            .unwrap();
+        let draft = AttachmentDraft {
+            id: AttachmentId(Uuid::new_v4()),
+            file_name: "a.png".into(),
+            content_type: blobs::sniff_content_type(PNG).into(),
+            size: PNG.len() as u64,
+            digest: digest.clone(),
+        };
+        store.add_attachment(id, draft).unwrap();
+        digest
+    };
+
+    let blobs = BlobStore::open(data_dir.path(), &config.attachments).unwrap();
+    assert_eq!(blobs.read(&digest, 0..4).unwrap(), &PNG[..4]);
+    let store = TicketStore::open(data_dir.path()).unwrap();
+    assert_eq!(store.blob_references(&digest), 1);
+}
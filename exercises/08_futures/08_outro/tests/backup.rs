@@ -0,0 +1,383 @@
+use std::sync::Arc;
+
+use axum::body::{Body, Bytes};
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use chrono::{TimeZone, Utc};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use ticket_store::actor::StoreHandle;
+use ticket_store::backup::{Backup, BackupError, ARCHIVE_FORMAT_VERSION};
+use ticket_store::blobs::BlobStore;
+use ticket_store::clock::{ManualClock, SystemClock};
+use ticket_store::config::{Config, LimitsConfig};
+use ticket_store::crypto::Encryption;
+use ticket_store::domain::DomainEvent;
+use ticket_store::dto::{TicketDraft, TicketPatch};
+use ticket_store::events::ChangeFeed;
+use ticket_store::health::Health;
+use ticket_store::middleware::AdminToken;
+use ticket_store::models::{
+    Priority, ProjectKey, TicketDescription, TicketId, TicketStatus, TicketTitle,
+};
+use ticket_store::registry::{ProjectRegistry, ProjectStore};
+use ticket_store::saved_queries::SavedQueries;
+use ticket_store::server::{app, AppState};
+use ticket_store::webhooks::Webhooks;
+use tower::ServiceExt;
+
+fn clock() -> Arc<ManualClock> {
+    Arc::new(ManualClock::new(
+        Utc.with_ymd_and_hms(2024, 7, 17, 12, 0, 0).unwrap(),
+    ))
+}
+
+fn add(store: &ProjectStore, title: &str) -> TicketId {
+    store
+        .read()
+        .unwrap()
+        .add_ticket(TicketDraft {
+            title: TicketTitle::try_from(title).unwrap(),
+            description: TicketDescription::try_from("説明").unwrap(),
+            priority: Priority::Medium,
+            due_date: None,
+        })
+        .unwrap()
+}
+
+fn complete(store: &ProjectStore, id: TicketId) {
+    let store = store.read().unwrap();
+    for status in [TicketStatus::InProgress, TicketStatus::Done] {
+        let version = store.get(id).unwrap().version;
+        store
+            .update_ticket(
+                id,
+                TicketPatch {
+                    title: None,
+                    description: None,
+                    status: Some(status),
+                    priority: None,
+                    due_date: None,
+                    version,
+                },
+            )
+            .unwrap();
+    }
+}
+
+/// 管理APIのトークン
+const ADMIN_TOKEN: &str = "s3cret-admin-token";
+
+fn router(registry: ProjectRegistry, limits: &LimitsConfig) -> Router {
+    router_with_admin(
+        registry,
+        limits,
+        Some(AdminToken::try_from(ADMIN_TOKEN).unwrap()),
+    )
+}
+
+fn router_with_admin(
+    registry: ProjectRegistry,
+    limits: &LimitsConfig,
+    admin: Option<AdminToken>,
+) -> Router {
+    let config = Config::default();
+    let state = AppState {
+        store: StoreHandle::spawn(registry, 1024),
+        health: Arc::new(Health::new(&config)),
+        webhooks: Webhooks::in_memory(&config.webhooks, Arc::new(SystemClock)),
+        saved_queries: SavedQueries::in_memory(Arc::new(SystemClock)),
+        feed: ChangeFeed::default(),
+        blobs: BlobStore::in_memory(&config.attachments),
+        encryption: Encryption::default(),
+        admin,
+    };
+    app(state, limits)
+}
+
+/// 管理APIのトークンを付けてリクエストを送信する。
+async fn send(
+    router: &Router,
+    method: &str,
+    uri: &str,
+    body: impl Into<Body>,
+) -> (StatusCode, header::HeaderMap, Bytes) {
+    send_with_token(router, method, uri, body, Some(ADMIN_TOKEN)).await
+}
+
+async fn send_with_token(
+    router: &Router,
+    method: &str,
+    uri: &str,
+    body: impl Into<Body>,
+    token: Option<&str>,
+) -> (StatusCode, header::HeaderMap, Bytes) {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let request = request.body(body.into()).unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, headers, body)
+}
+
+fn json(body: &Bytes) -> Value {
+    serde_json::from_slice(body).unwrap_or(Value::Null)
+}
+
+#[tokio::test]
+async fn backup_round_trips_through_restore() {
+    let clock = clock();
+    let registry = ProjectRegistry::in_memory(clock.clone());
+    let router = router(registry, &Config::default().limits);
+    let (status, _, _) = send(
+        &router,
+        "POST",
+        "/projects",
+        r#"{"key": "WEB", "name": "ウェブ"}"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    for title in ["一つ目", "二つ目", "三つ目"] {
+        let body = json!({"title": title, "description": "説明"}).to_string();
+        send(&router, "POST", "/projects/TICKET/tickets", body).await;
+    }
+    let (status, _, _) = send(
+        &router,
+        "POST",
+        "/projects/TICKET/tickets/2/move",
+        r#"{"project": "WEB"}"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    for (status, version) in [("InProgress", 0), ("Done", 1)] {
+        let body = json!({"status": status, "version": version}).to_string();
+        send(&router, "PATCH", "/projects/TICKET/tickets/0", body).await;
+    }
+
+    let (status, headers, archive) = send(&router, "POST", "/admin/backup", "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+    assert!(headers[header::CONTENT_DISPOSITION]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment; filename=\"ticket-store-20240717T120000Z.json\""));
+    assert_eq!(json(&archive)["format"], ARCHIVE_FORMAT_VERSION);
+
+    // バックアップした後の変更は、復元すると失われる。
+    let body = json!({"title": "四つ目", "description": "説明"}).to_string();
+    send(&router, "POST", "/projects/TICKET/tickets", body).await;
+    send(
+        &router,
+        "POST",
+        "/projects",
+        r#"{"key": "TMP", "name": "一時"}"#,
+    )
+    .await;
+
+    let (status, _, summary) = send(&router, "POST", "/admin/restore", archive.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json(&summary), json!({"projects": 2, "tickets": 3}));
+
+    let (_, _, projects) = send(&router, "GET", "/projects", "").await;
+    let keys: Vec<_> = json(&projects)
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["key"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(keys, vec!["TICKET", "WEB"]);
+    let (status, _, _) = send(&router, "GET", "/projects/TICKET/tickets/3", "").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, headers, _) = send(&router, "GET", "/projects/TICKET/tickets/2", "").await;
+    assert_eq!(status, StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(headers[header::LOCATION], "/projects/WEB/tickets/WEB-1");
+    let (_, _, summary) = send(&router, "GET", "/reports/summary?format=csv", "").await;
+    assert_eq!(summary, "status,count\nToDo,2\nInProgress,0\nDone,1\n");
+
+    // 復元した後のバックアップは、復元したバックアップと同じ内容である。
+    let (_, _, restored) = send(&router, "POST", "/admin/backup", "").await;
+    assert_eq!(json(&restored)["projects"], json(&archive)["projects"]);
+
+    // 次に割り当てるチケットIDも復元する。
+    let body = json!({"title": "四つ目", "description": "説明"}).to_string();
+    let (_, _, created) = send(&router, "POST", "/projects/TICKET/tickets", body).await;
+    assert_eq!(json(&created)["id"], 3);
+}
+
+#[test]
+fn file_backend_restore_survives_restart() {
+    let clock = clock();
+    let source = ProjectRegistry::in_memory(clock.clone());
+    let store = source.resolve(&ProjectKey::default()).unwrap();
+    let done = add(&store, "完了");
+    add(&store, "未着手");
+    complete(&store, done);
+    let mut archive = vec![];
+    source.backup().write(&mut archive).unwrap();
+
+    let data_dir = tempfile::tempdir().unwrap();
+    {
+        let mut registry = ProjectRegistry::open(data_dir.path(), clock.clone()).unwrap();
+        let store = registry.resolve(&ProjectKey::default()).unwrap();
+        for title in ["上書き1", "上書き2", "上書き3"] {
+            add(&store, title);
+        }
+        registry
+            .restore_backup(Backup::read(&archive).unwrap())
+            .unwrap();
+        let store = registry.resolve(&ProjectKey::default()).unwrap();
+        assert_eq!(store.read().unwrap().len(), 2);
+    }
+    for leftover in ["restoring", "projects.replaced"] {
+        assert!(!data_dir.path().join(leftover).exists(), "{leftover}");
+    }
+
+    let registry = ProjectRegistry::open(data_dir.path(), clock).unwrap();
+    let store = registry.resolve(&ProjectKey::default()).unwrap();
+    {
+        let store = store.read().unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get(done).unwrap().status, TicketStatus::Done);
+        assert_eq!(store.history(done).len(), 3);
+        assert_eq!(store.status_timeline().completions().len(), 1);
+    }
+    assert_eq!(add(&store, "三つ目"), TicketId(2));
+}
+
+#[test]
+fn older_archive_formats_are_migrated() {
+    let registry = ProjectRegistry::in_memory(clock());
+    let store = registry.resolve(&ProjectKey::default()).unwrap();
+    let done = add(&store, "完了");
+    complete(&store, done);
+    let archive = json!({
+        "format": 1,
+        "createdAt": "2024-07-17T12:00:00Z",
+        "projects": registry.snapshots(),
+    });
+
+    let backup = Backup::read(archive.to_string().as_bytes()).unwrap();
+    let mut restored = ProjectRegistry::in_memory(clock());
+    restored.restore_backup(backup.clone()).unwrap();
+    let store = restored.resolve(&ProjectKey::default()).unwrap();
+    {
+        let store = store.read().unwrap();
+        let ticket = store.get(done).unwrap();
+        assert_eq!((ticket.status, ticket.version), (TicketStatus::Done, 2));
+        let history = store.history(done);
+        assert_eq!(history.len(), 1);
+        assert!(matches!(
+            history[0].event,
+            DomainEvent::TicketImported { .. }
+        ));
+    }
+    assert_eq!(add(&store, "二つ目"), TicketId(1));
+
+    // 変換したバックアップは、現在の形式バージョンで書き出して読み込める。
+    let mut current = vec![];
+    backup.write(&mut current).unwrap();
+    let current: Value = serde_json::from_slice(&current).unwrap();
+    assert_eq!(current["format"], ARCHIVE_FORMAT_VERSION);
+    let reread = Backup::read(current.to_string().as_bytes()).unwrap();
+    assert_eq!(reread.tickets(), 1);
+    assert_eq!(reread.projects[0].history.len(), 1);
+}
+
+#[tokio::test]
+async fn invalid_archives_are_rejected_without_changes() {
+    let registry = ProjectRegistry::in_memory(clock());
+    let store = registry.resolve(&ProjectKey::default()).unwrap();
+    complete(&store, add(&store, "完了"));
+    let mut archive = vec![];
+    registry.backup().write(&mut archive).unwrap();
+    let archive: Value = serde_json::from_slice(&archive).unwrap();
+    let limits = LimitsConfig {
+        max_restore_bytes: 4096,
+        ..Config::default().limits
+    };
+    let router = router(ProjectRegistry::in_memory(clock()), &limits);
+    let body = json!({"title": "残る", "description": "説明"}).to_string();
+    send(&router, "POST", "/projects/TICKET/tickets", body).await;
+
+    let tampered = |edit: fn(&mut Value)| {
+        let mut archive = archive.clone();
+        edit(&mut archive);
+        archive.to_string()
+    };
+    let cases = [
+        ("{".to_string(), StatusCode::BAD_REQUEST),
+        (
+            tampered(|a| drop(a.as_object_mut().unwrap().remove("format"))),
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            tampered(|a| a["format"] = 99.into()),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+        (
+            tampered(|a| a["projects"][0]["snapshot"]["nextId"] = 0.into()),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+        (
+            tampered(|a| a["projects"][0]["snapshot"]["tickets"][0]["version"] = 1.into()),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+        (
+            tampered(|a| a["projects"][0]["events"][1]["ticket"] = 9.into()),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+        (
+            tampered(|a| {
+                let project = a["projects"][0].clone();
+                a["projects"].as_array_mut().unwrap().push(project);
+            }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+        ("x".repeat(8192), StatusCode::PAYLOAD_TOO_LARGE),
+    ];
+    for (archive, expected) in cases {
+        let (status, _, body) = send(&router, "POST", "/admin/restore", archive).await;
+        assert_eq!(status, expected, "{}", String::from_utf8_lossy(&body));
+    }
+
+    let (_, _, ticket) = send(&router, "GET", "/projects/TICKET/tickets/0", "").await;
+    assert_eq!(json(&ticket)["title"], "残る");
+    assert!(matches!(
+        Backup::read(b"{\"format\": 0}"),
+        Err(BackupError::UnsupportedFormat(0))
+    ));
+}
+
+#[tokio::test]
+async fn backup_and_restore_require_the_admin_token() {
+    let limits = Config::default().limits;
+    let router = router(ProjectRegistry::in_memory(clock()), &limits);
+    let body = json!({"title": "残る", "description": "説明"}).to_string();
+    send_with_token(&router, "POST", "/projects/TICKET/tickets", body, None).await;
+    let (_, _, archive) = send(&router, "POST", "/admin/backup", "").await;
+
+    for (uri, body) in [("/admin/backup", Bytes::new()), ("/admin/restore", archive)] {
+        for token in [None, Some("wrong-token")] {
+            let (status, headers, body) =
+                send_with_token(&router, "POST", uri, body.clone(), token).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{uri} {token:?}");
+            assert_eq!(headers[header::WWW_AUTHENTICATE], "Bearer");
+            assert_eq!(json(&body)["code"], "unauthorized");
+        }
+    }
+
+    // トークンを設定していない場合は、トークンを付けても管理APIを使用できない。
+    let router = router_with_admin(ProjectRegistry::in_memory(clock()), &limits, None);
+    for uri in ["/admin/backup", "/admin/restore"] {
+        let (status, _, body) = send(&router, "POST", uri, "").await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{uri}");
+        assert_eq!(json(&body)["code"], "adminDisabled");
+    }
+}
@@ -0,0 +1,659 @@
+mod common;
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use serde_json::Value;
+use ticket_store::clock::SystemClock;
+use ticket_store::cluster::{ClusterError, ClusterNode, Simulator, TicketCommand};
+use ticket_store::cluster_server::ClusterToken;
+use ticket_store::config::{ClusterAdvertise, ClusterPeer, Config};
+use ticket_store::crypto::Encryption;
+use ticket_store::dto::{TicketDraft, TicketPatch};
+use ticket_store::middleware::AdminToken;
+use ticket_store::models::TicketId;
+use ticket_store::persistence::{RaftStorage, Snapshot};
+use ticket_store::raft::{NodeId, RaftConfig, RaftError};
+use ticket_store::server::Server;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+
+use common::{local_config, send, send_with_headers, send_with_token, wait_until_ready};
+
+const SEED: u64 = 42;
+const CLUSTER_TOKEN: &str = "cluster-secret";
+const ADMIN_TOKEN: &str = "s3cret-admin-token";
+
+fn draft(title: &str) -> TicketDraft {
+    serde_json::from_value(serde_json::json!({"title": title, "description": "説明"})).unwrap()
+}
+
+fn patch(title: &str, version: u64) -> TicketPatch {
+    serde_json::from_value(serde_json::json!({"title": title, "version": version})).unwrap()
+}
+
+/// ノードのチケットストアのスナップショットを返す。
+///
+/// Raftのスナップショットから復元したノードは、それより前のドメインイベントの履歴を持たないため、履歴の数は比較しない。
+fn snapshot(sim: &Simulator, id: NodeId) -> Value {
+    let mut snapshot = serde_json::to_value(sim.node(id).unwrap().store().snapshot()).unwrap();
+    snapshot.as_object_mut().unwrap().remove("events");
+    snapshot
+}
+
+/// 4エントリを適用するたびにログを切り詰めるRaftの設定
+fn compacting() -> RaftConfig {
+    RaftConfig {
+        snapshot_entries: 4,
+        ..RaftConfig::default()
+    }
+}
+
+/// 動作しているすべてのノードのチケットストアが、同じ状態になるまで待つ。
+fn assert_converged(sim: &mut Simulator) {
+    let converged = sim.run_until(|sim| {
+        let nodes = sim.live_nodes();
+        nodes
+            .iter()
+            .all(|&id| snapshot(sim, id) == snapshot(sim, nodes[0]))
+    });
+    assert!(converged, "ノードのチケットストアが一致しませんでした。");
+}
+
+#[test]
+fn cluster_size_must_be_three_or_five() {
+    assert!(matches!(
+        Simulator::new(4, SEED),
+        Err(ClusterError::InvalidSize(4))
+    ));
+    assert!(Simulator::new(3, SEED).is_ok());
+    assert!(Simulator::new(5, SEED).is_ok());
+}
+
+#[test]
+fn commands_are_replicated_to_every_node() {
+    let mut sim = Simulator::new(3, SEED).unwrap();
+    let leader = sim.elect_leader().unwrap();
+
+    let id = sim.add_ticket(leader, draft("羅生門")).unwrap();
+    assert_eq!(id, TicketId(0));
+    sim.update_ticket(leader, id, patch("鼻", 0)).unwrap();
+    assert_converged(&mut sim);
+
+    for node in sim.live_nodes() {
+        let ticket = sim.node(node).unwrap().store().get(id).unwrap();
+        assert_eq!(ticket.title.0, "鼻");
+    }
+    let follower = sim
+        .live_nodes()
+        .into_iter()
+        .find(|&id| id != leader)
+        .unwrap();
+    assert!(matches!(
+        sim.add_ticket(follower, draft("蜘蛛の糸")),
+        Err(ClusterError::Raft(RaftError::NotLeader { leader: Some(l) })) if l == leader
+    ));
+}
+
+#[test]
+fn minority_leader_cannot_commit_and_is_superseded() {
+    let mut sim = Simulator::new(5, SEED).unwrap();
+    let old = sim.elect_leader().unwrap();
+    let id = sim.add_ticket(old, draft("羅生門")).unwrap();
+
+    let others: Vec<NodeId> = sim.live_nodes().into_iter().filter(|&n| n != old).collect();
+    sim.partition(&[&[old, others[0]], &others[1..]]);
+    let proposal = sim
+        .node_mut(old)
+        .unwrap()
+        .propose_update(id, patch("少数派", 0))
+        .unwrap();
+    let new = {
+        sim.run_until(|sim| sim.leader().is_some_and(|l| l != old));
+        sim.leader().unwrap()
+    };
+    assert!(others[1..].contains(&new));
+    sim.update_ticket(new, id, patch("多数派", 0)).unwrap();
+    assert!(sim.node_mut(old).unwrap().take_outcome(proposal).is_none());
+
+    sim.heal();
+    let superseded = sim.run_until(|sim| {
+        sim.node(old)
+            .unwrap()
+            .store()
+            .get(id)
+            .is_ok_and(|ticket| ticket.title.0 == "多数派")
+    });
+    assert!(superseded);
+    assert!(matches!(
+        sim.node_mut(old).unwrap().take_outcome(proposal),
+        Some(Err(ClusterError::Superseded))
+    ));
+    assert_converged(&mut sim);
+}
+
+#[test]
+fn restarted_node_catches_up() {
+    let mut sim = Simulator::new(3, SEED).unwrap();
+    let leader = sim.elect_leader().unwrap();
+    let id = sim.add_ticket(leader, draft("羅生門")).unwrap();
+    let crashed = sim
+        .live_nodes()
+        .into_iter()
+        .find(|&id| id != leader)
+        .unwrap();
+
+    sim.crash(crashed);
+    sim.add_ticket(leader, draft("鼻")).unwrap();
+    sim.update_ticket(leader, id, patch("芋粥", 0)).unwrap();
+    assert!(sim.node(crashed).is_none());
+
+    sim.restart(crashed);
+    assert_converged(&mut sim);
+    assert_eq!(sim.node(crashed).unwrap().store().len(), 2);
+}
+
+#[test]
+fn five_node_cluster_tolerates_two_crashes() {
+    let mut sim = Simulator::new(5, SEED).unwrap();
+    let leader = sim.elect_leader().unwrap();
+    sim.add_ticket(leader, draft("羅生門")).unwrap();
+
+    sim.crash(leader);
+    let survivor = sim.live_nodes()[0];
+    sim.crash(survivor);
+    let leader = sim.elect_leader().unwrap();
+    let id = sim.add_ticket(leader, draft("鼻")).unwrap();
+
+    assert_eq!(id, TicketId(1));
+    assert_converged(&mut sim);
+}
+
+#[test]
+fn linearizable_read_sees_latest_write() {
+    let mut sim = Simulator::new(3, SEED).unwrap();
+    let leader = sim.elect_leader().unwrap();
+    let id = sim.add_ticket(leader, draft("羅生門")).unwrap();
+    sim.update_ticket(leader, id, patch("鼻", 0)).unwrap();
+
+    let ticket = sim.read_ticket(leader, id).unwrap();
+    assert_eq!(ticket.title.0, "鼻");
+}
+
+#[test]
+fn partitioned_leader_cannot_serve_reads() {
+    let mut sim = Simulator::new(3, SEED).unwrap();
+    let old = sim.elect_leader().unwrap();
+    let id = sim.add_ticket(old, draft("羅生門")).unwrap();
+    let others: Vec<NodeId> = sim.live_nodes().into_iter().filter(|&n| n != old).collect();
+
+    sim.partition(&[&[old], &others]);
+    let read = sim.node_mut(old).unwrap().read_index().unwrap();
+    sim.run_until(|sim| sim.leader().is_some_and(|l| l != old));
+    let new = sim.leader().unwrap();
+    sim.update_ticket(new, id, patch("鼻", 0)).unwrap();
+
+    let result = loop {
+        if let Some(result) = sim.node_mut(old).unwrap().read_ticket(read, id) {
+            break result;
+        }
+        sim.tick();
+    };
+    assert!(matches!(result, Err(ClusterError::Raft(_))));
+    assert!(sim.read_ticket(old, id).is_err());
+    assert_eq!(sim.read_ticket(new, id).unwrap().title.0, "鼻");
+}
+
+#[test]
+fn membership_changes_add_and_remove_nodes() {
+    let mut sim = Simulator::new(3, SEED).unwrap();
+    let leader = sim.elect_leader().unwrap();
+    sim.add_ticket(leader, draft("羅生門")).unwrap();
+
+    sim.add_node(4).unwrap();
+    assert_converged(&mut sim);
+    assert_eq!(sim.node(4).unwrap().store().len(), 1);
+    assert_eq!(sim.node(4).unwrap().raft().members().len(), 4);
+
+    let removed = sim.live_nodes().into_iter().find(|&id| id != 4).unwrap();
+    sim.remove_node(removed).unwrap();
+    assert!(sim.node(removed).is_none());
+    let leader = sim.elect_leader().unwrap();
+    sim.add_ticket(leader, draft("鼻")).unwrap();
+    assert_converged(&mut sim);
+    for node in sim.live_nodes() {
+        assert_eq!(sim.node(node).unwrap().raft().members().len(), 3);
+    }
+}
+
+#[test]
+fn lagging_nodes_catch_up_from_a_snapshot() {
+    let mut sim = Simulator::with_config(3, SEED, compacting()).unwrap();
+    let leader = sim.elect_leader().unwrap();
+    let crashed = sim
+        .live_nodes()
+        .into_iter()
+        .find(|&id| id != leader)
+        .unwrap();
+
+    sim.crash(crashed);
+    for i in 0..10 {
+        sim.add_ticket(leader, draft(&format!("チケット{i}")))
+            .unwrap();
+    }
+    let raft = sim.node(leader).unwrap().raft();
+    assert!(raft.snapshot().index > 0);
+    assert!(raft.entries().len() < 4);
+
+    // 停止している間に切り詰めたエントリは、リーダーのスナップショットで受け取る。
+    sim.restart(crashed);
+    assert_converged(&mut sim);
+    assert_eq!(sim.node(crashed).unwrap().store().len(), 10);
+    assert!(sim.node(crashed).unwrap().raft().snapshot().index > 0);
+
+    sim.add_node(4).unwrap();
+    assert_converged(&mut sim);
+    assert_eq!(sim.node(4).unwrap().store().len(), 10);
+    let id = sim.add_ticket(leader, draft("鼻")).unwrap();
+    assert_eq!(id, TicketId(10));
+    assert_converged(&mut sim);
+}
+
+#[test]
+fn raft_storage_appends_entries_and_rewrites_the_log_when_compacted() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let encryption = Encryption::default();
+    let (mut storage, hard_state) =
+        RaftStorage::open::<TicketCommand, Snapshot>(data_dir.path(), &encryption).unwrap();
+    assert!(hard_state.is_none());
+
+    let mut sim = Simulator::with_config(3, SEED, compacting()).unwrap();
+    let leader = sim.elect_leader().unwrap();
+    let mut persist = |sim: &mut Simulator| {
+        let node = sim.node_mut(leader).unwrap();
+        if let Some(changes) = storage.changes(node.raft()) {
+            storage.write(&changes).unwrap();
+            node.stabilize(changes.last_index());
+        }
+        assert!(storage.changes(node.raft()).is_none());
+    };
+    for i in 0..10 {
+        sim.add_ticket(leader, draft(&format!("チケット{i}")))
+            .unwrap();
+        persist(&mut sim);
+    }
+
+    let node = sim.node(leader).unwrap();
+    let log = std::fs::read_to_string(data_dir.path().join("raft-log.jsonl")).unwrap();
+    assert_eq!(log.lines().count(), node.raft().entries().len());
+    let (_, hard_state) =
+        RaftStorage::open::<TicketCommand, Snapshot>(data_dir.path(), &encryption).unwrap();
+    let hard_state = hard_state.unwrap();
+    assert_eq!(
+        serde_json::to_value(&hard_state).unwrap(),
+        serde_json::to_value(node.hard_state()).unwrap()
+    );
+
+    // 再起動したノードは、スナップショットからチケットストアを復元する。
+    let recovered = ClusterNode::recover(
+        leader,
+        hard_state,
+        compacting(),
+        SEED,
+        std::sync::Arc::new(SystemClock),
+    );
+    assert_eq!(
+        recovered.store().len() as u64,
+        recovered.raft().snapshot().data.next_id
+    );
+    assert!(!recovered.store().is_empty());
+}
+
+/// 空いているポートのソケットアドレスを返す。
+fn free_addr() -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap()
+}
+
+/// ローカルホストで3ノードのクラスタを起動して、ノードIDごとのHTTPのソケットアドレスと停止トリガーを返す。
+async fn start_cluster() -> BTreeMap<NodeId, (SocketAddr, oneshot::Sender<()>)> {
+    let nodes: Vec<ClusterPeer> = (1..=3).map(|id| peer(id, free_addr())).collect();
+    let mut started = BTreeMap::new();
+    for node in &nodes {
+        let peers = nodes.iter().filter(|peer| peer.id != node.id).cloned();
+        let config = node_config(node, peers.collect());
+        started.insert(node.id, start_node(&config).await);
+    }
+
+    started
+}
+
+/// HTTPのソケットアドレスと、新しいノード間の通信のソケットアドレスを持つノードを返す。
+fn peer(id: NodeId, http: SocketAddr) -> ClusterPeer {
+    ClusterPeer {
+        id,
+        addr: free_addr(),
+        url: format!("http://{http}"),
+    }
+}
+
+/// ノードのHTTPのソケットアドレスを返す。
+fn http(node: &ClusterPeer) -> SocketAddr {
+    node.url.trim_start_matches("http://").parse().unwrap()
+}
+
+/// クラスタのノードの設定を返す。
+fn node_config(node: &ClusterPeer, peers: Vec<ClusterPeer>) -> Config {
+    let mut config = local_config();
+    config.server.port = http(node).port();
+    config.cluster.node_id = Some(node.id);
+    config.cluster.listen = Some(node.addr);
+    config.cluster.tick_ms = 10;
+    config.cluster.token = Some(ClusterToken::try_from(CLUSTER_TOKEN).unwrap());
+    config.cluster.advertise = Some(ClusterAdvertise {
+        addr: node.addr,
+        url: node.url.clone(),
+    });
+    config.cluster.peers = peers;
+    config.admin.token = Some(AdminToken::try_from(ADMIN_TOKEN).unwrap());
+    config
+}
+
+/// ノードを起動して、HTTPのソケットアドレスと停止を指示する送信側を返す。
+async fn start_node(config: &Config) -> (SocketAddr, oneshot::Sender<()>) {
+    let server = Server::bind(config).await.unwrap();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    tokio::spawn(server.serve(async {
+        let _ = shutdown_rx.await;
+    }));
+    let http = config.server.addr();
+    wait_until_ready(http).await;
+
+    (http, shutdown_tx)
+}
+
+/// ノードの状態を返す。
+async fn status_of(http: SocketAddr) -> Value {
+    let (_, body) = send(http, "GET", "/cluster", None).await;
+    serde_json::from_str(&body).unwrap()
+}
+
+/// 指定したノードのすべてが、停止したノード以外の同じリーダーを認めるまで待つ。
+async fn wait_for_leader(addrs: &[SocketAddr], stopped: Option<NodeId>) -> NodeId {
+    for _ in 0..500 {
+        let mut leaders = vec![];
+        for &addr in addrs {
+            let (_, body) = send(addr, "GET", "/cluster", None).await;
+            let status: Value = serde_json::from_str(&body).unwrap();
+            leaders.push(status["leader"].as_u64());
+        }
+        if let Some(leader) = leaders[0].filter(|&leader| Some(leader) != stopped) {
+            if leaders.iter().all(|l| *l == Some(leader)) {
+                return leader;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("リーダーが選ばれませんでした。");
+}
+
+#[tokio::test]
+async fn servers_replicate_writes_through_the_raft_log() {
+    let mut nodes = start_cluster().await;
+    let addrs: Vec<SocketAddr> = nodes.values().map(|(addr, _)| *addr).collect();
+    let leader = wait_for_leader(&addrs, None).await;
+    let leader_addr = nodes[&leader].0;
+    let follower = *nodes.keys().find(|&&id| id != leader).unwrap();
+    let follower_addr = nodes[&follower].0;
+
+    // リーダーでないノードは、書き込みをメソッドとボディを維持してリーダーにリダイレクトする。
+    let draft = r#"{"title": "羅生門", "description": "芥川龍之介の短編"}"#;
+    let (status, headers, _) = send_with_headers(
+        follower_addr,
+        "POST",
+        "/projects/TICKET/tickets",
+        Some(draft),
+    )
+    .await;
+    assert_eq!(status, 307);
+    let location = headers.iter().find(|(name, _)| name == "location").unwrap();
+    assert_eq!(
+        location.1,
+        format!("http://{leader_addr}/projects/TICKET/tickets")
+    );
+
+    let (status, body) = send(leader_addr, "POST", "/projects/TICKET/tickets", Some(draft)).await;
+    assert_eq!(status, 200, "{body}");
+    let created: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(created["id"], 0);
+    assert_eq!(created["key"], "TICKET-1");
+    let patch = r#"{"title": "鼻", "version": 0}"#;
+    let (status, _) = send(
+        leader_addr,
+        "PATCH",
+        "/projects/TICKET/tickets/0",
+        Some(patch),
+    )
+    .await;
+    assert_eq!(status, 200);
+    let (status, _) = send(
+        leader_addr,
+        "PATCH",
+        "/projects/TICKET/tickets/0",
+        Some(patch),
+    )
+    .await;
+    assert_eq!(status, 409);
+    let (status, _) = send(leader_addr, "POST", "/projects/WEB/tickets", Some(draft)).await;
+    assert_eq!(status, 404);
+
+    // リーダーを停止しても、残りの過半数のノードが新しいリーダーを選び、コミットした更新を読み込める。
+    let (_, shutdown) = nodes.remove(&leader).unwrap();
+    shutdown.send(()).unwrap();
+    let addrs: Vec<SocketAddr> = nodes.values().map(|(addr, _)| *addr).collect();
+    let new_leader = wait_for_leader(&addrs, Some(leader)).await;
+    let (status, body) = send(
+        nodes[&new_leader].0,
+        "GET",
+        "/projects/TICKET/tickets/TICKET-1",
+        None,
+    )
+    .await;
+    assert_eq!(status, 200, "{body}");
+    let ticket: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(ticket["title"], "鼻");
+}
+
+/// ノード間の通信のソケットアドレスに接続し、自己紹介とメッセージを送信する。
+async fn send_peer_messages(addr: SocketAddr, hello: Value, messages: &[Value]) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    for message in std::iter::once(&hello).chain(messages) {
+        let mut line = serde_json::to_vec(message).unwrap();
+        line.push(b'\n');
+        // 接続を閉じられた後の書き込みの失敗は無視する。
+        let _ = stream.write_all(&line).await;
+    }
+    let _ = stream.flush().await;
+}
+
+/// ノードの現在の項を返す。
+async fn term(http: SocketAddr) -> u64 {
+    status_of(http).await["term"].as_u64().unwrap()
+}
+
+#[tokio::test]
+async fn peer_connections_require_the_cluster_token() {
+    let node = peer(1, free_addr());
+    // 他のノードは起動しないため、このノードはリーダーになれず項だけが進む。
+    let config = node_config(&node, vec![peer(2, free_addr()), peer(3, free_addr())]);
+    let (http, shutdown) = start_node(&config).await;
+    let raft = node.addr;
+
+    let append = |from: NodeId| {
+        serde_json::json!({
+            "from": from,
+            "to": 1,
+            "message": {"AppendEntries": {
+                "term": 1000,
+                "prevLogIndex": 0,
+                "prevLogTerm": 0,
+                "entries": [],
+                "leaderCommit": 0,
+                "read": 0,
+            }},
+        })
+    };
+
+    // トークンが一致しない接続のメッセージは、ノードに渡さない。
+    let hello = serde_json::json!({"nodeId": 2, "token": "wrong-token"});
+    send_peer_messages(raft, hello, &[append(2)]).await;
+    // 自己紹介と異なるノードを送信元とするメッセージも、ノードに渡さない。
+    let hello = serde_json::json!({"nodeId": 2, "token": CLUSTER_TOKEN});
+    send_peer_messages(raft, hello, &[append(3)]).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(term(http).await < 1000);
+
+    let hello = serde_json::json!({"nodeId": 2, "token": CLUSTER_TOKEN});
+    send_peer_messages(raft, hello, &[append(2)]).await;
+    let mut accepted = false;
+    for _ in 0..100 {
+        if term(http).await >= 1000 {
+            accepted = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(accepted);
+
+    shutdown.send(()).unwrap();
+}
+
+/// 管理APIのトークンでメンバーの変更を送信する。選挙やコミットを待つ`503 Service Unavailable`の間は再試行する。
+async fn change_members(
+    http: SocketAddr,
+    method: &str,
+    path: &str,
+    body: Option<&str>,
+) -> (u16, String) {
+    for _ in 0..100 {
+        let (status, body) = send_with_token(http, method, path, ADMIN_TOKEN, body).await;
+        if status != 503 {
+            return (status, body);
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("メンバーを変更できませんでした。");
+}
+
+#[tokio::test]
+async fn admin_api_adds_and_removes_cluster_members() {
+    let nodes: Vec<ClusterPeer> = (1..=3).map(|id| peer(id, free_addr())).collect();
+    let mut shutdowns = vec![];
+    for node in &nodes {
+        let peers = nodes.iter().filter(|peer| peer.id != node.id).cloned();
+        let (_, shutdown) = start_node(&node_config(node, peers.collect())).await;
+        shutdowns.push(shutdown);
+    }
+    let addrs: Vec<SocketAddr> = nodes.iter().map(http).collect();
+    let leader = wait_for_leader(&addrs, None).await;
+    let leader_http = addrs[leader as usize - 1];
+    let follower_http = addrs[leader as usize % 3];
+    let joining = peer(4, free_addr());
+    let add = r#"{"id": 4}"#;
+
+    // メンバーの変更には、管理APIのトークンが必要である。
+    let (status, _) = send(leader_http, "POST", "/cluster/members", Some(add)).await;
+    assert_eq!(status, 401);
+    let (status, _) = send_with_token(
+        leader_http,
+        "POST",
+        "/cluster/members",
+        "wrong-token",
+        Some(add),
+    )
+    .await;
+    assert_eq!(status, 401);
+
+    // 接続してアドレスを知らせていないノードは追加できない。
+    let (status, body) = change_members(leader_http, "POST", "/cluster/members", Some(add)).await;
+    assert_eq!(status, 409, "{body}");
+    assert_eq!(
+        serde_json::from_str::<Value>(&body).unwrap()["code"],
+        "unknownPeer"
+    );
+
+    let mut config = node_config(&joining, nodes.clone());
+    config.cluster.join = true;
+    let (joining_http, joining_shutdown) = start_node(&config).await;
+    assert_eq!(
+        status_of(joining_http).await["members"],
+        serde_json::json!([])
+    );
+
+    // リーダーでないノードは、メンバーの変更をリーダーにリダイレクトする。
+    let (status, _) = send_with_token(
+        follower_http,
+        "POST",
+        "/cluster/members",
+        ADMIN_TOKEN,
+        Some(add),
+    )
+    .await;
+    assert_eq!(status, 307);
+
+    let mut added = None;
+    for _ in 0..100 {
+        let (status, body) =
+            change_members(leader_http, "POST", "/cluster/members", Some(add)).await;
+        if status != 409 {
+            added = Some((status, body));
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let (status, body) = added.expect("追加するノードのアドレスが登録されませんでした。");
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(
+        serde_json::from_str::<Value>(&body).unwrap(),
+        serde_json::json!({"members": [1, 2, 3, 4]})
+    );
+
+    // 追加したノードは、リーダーからログを受け取ってリーダーを認める。
+    let draft = r#"{"title": "羅生門", "description": "芥川龍之介の短編"}"#;
+    let (status, _) = send(leader_http, "POST", "/projects/TICKET/tickets", Some(draft)).await;
+    assert_eq!(status, 200);
+    let commit_index = status_of(leader_http).await["commitIndex"].clone();
+    let mut caught_up = false;
+    for _ in 0..100 {
+        let joined = status_of(joining_http).await;
+        if joined["members"] == serde_json::json!([1, 2, 3, 4])
+            && joined["commitIndex"] == commit_index
+        {
+            assert_eq!(joined["leader"], leader);
+            caught_up = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(caught_up);
+
+    let (status, body) = change_members(leader_http, "DELETE", "/cluster/members/4", None).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(
+        serde_json::from_str::<Value>(&body).unwrap(),
+        serde_json::json!({"members": [1, 2, 3]})
+    );
+    let (status, body) = change_members(leader_http, "DELETE", "/cluster/members/4", None).await;
+    assert_eq!(status, 409, "{body}");
+    assert_eq!(
+        serde_json::from_str::<Value>(&body).unwrap()["code"],
+        "invalidMembershipChange"
+    );
+
+    joining_shutdown.send(()).unwrap();
+    for shutdown in shutdowns {
+        shutdown.send(()).unwrap();
+    }
+}
@@ -0,0 +1,85 @@
+#![allow(dead_code)]
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use ticket_store::config::Config;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// ローカルホストの空いているポートで待ち受ける設定を返す。
+pub fn local_config() -> Config {
+    let mut config = Config::default();
+    config.server.host = Ipv4Addr::LOCALHOST.into();
+    config.server.port = 0;
+    config.server.shutdown_timeout_secs = 1;
+    config
+}
+
+/// HTTPリクエストを送信して、ステータスコードとボディを返す。
+pub async fn send(addr: SocketAddr, method: &str, path: &str, body: Option<&str>) -> (u16, String) {
+    let (status, _, body) = send_with_headers(addr, method, path, body).await;
+    (status, body)
+}
+
+/// HTTPリクエストを送信して、ステータスコード、小文字のヘッダ名とヘッダ値の組、ボディを返す。
+pub async fn send_with_headers(
+    addr: SocketAddr,
+    method: &str,
+    path: &str,
+    body: Option<&str>,
+) -> (u16, Vec<(String, String)>, String) {
+    exchange(addr, method, path, "", body).await
+}
+
+/// `Authorization: Bearer`ヘッダにトークンを付けてHTTPリクエストを送信し、ステータスコードとボディを返す。
+pub async fn send_with_token(
+    addr: SocketAddr,
+    method: &str,
+    path: &str,
+    token: &str,
+    body: Option<&str>,
+) -> (u16, String) {
+    let authorization = format!("Authorization: Bearer {token}\r\n");
+    let (status, _, body) = exchange(addr, method, path, &authorization, body).await;
+    (status, body)
+}
+
+async fn exchange(
+    addr: SocketAddr,
+    method: &str,
+    path: &str,
+    headers: &str,
+    body: Option<&str>,
+) -> (u16, Vec<(String, String)>, String) {
+    let body = body.unwrap_or_default();
+    let request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{headers}Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let status = response[9..12].parse().unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let headers = head
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+        .collect();
+    (status, headers, body.to_string())
+}
+
+/// レディネスプローブが成功するまで待つ。
+pub async fn wait_until_ready(addr: SocketAddr) {
+    for _ in 0..100 {
+        if send(addr, "GET", "/readyz", None).await.0 == 200 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("サーバーの準備ができませんでした。");
+}
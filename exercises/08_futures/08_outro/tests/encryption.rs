@@ -0,0 +1,345 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use ticket_store::blobs::BlobStore;
+use ticket_store::clock::SystemClock;
+use ticket_store::config::{Config, EncryptionConfig};
+use ticket_store::crypto::{DecryptError, Encryption, EncryptionKey};
+use ticket_store::dto::TicketDraft;
+use ticket_store::models::{
+    Priority, Project, ProjectKey, TicketDescription, TicketId, TicketTitle,
+};
+use ticket_store::persistence::{self, PersistenceError};
+use ticket_store::registry::ProjectRegistry;
+use ticket_store::store::TicketStore;
+
+const SECRET: &str = "吾輩は猫である";
+
+fn key(byte: u8) -> EncryptionKey {
+    EncryptionKey::try_from(format!("{byte:02x}").repeat(32)).unwrap()
+}
+
+fn encryption(key: EncryptionKey, previous_keys: Vec<EncryptionKey>) -> Encryption {
+    Encryption::new(&EncryptionConfig {
+        key: Some(key),
+        previous_keys,
+    })
+}
+
+fn draft(title: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from("説明").unwrap(),
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+/// ディレクトリ以下のすべてのファイルに、平文が含まれていないことを確かめる。
+fn assert_no_plaintext(dir: &Path, plaintext: &str) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            assert_no_plaintext(&path, plaintext);
+        } else {
+            let content = fs::read(&path).unwrap();
+            assert!(
+                !content
+                    .windows(plaintext.len())
+                    .any(|window| window == plaintext.as_bytes()),
+                "{}に平文が含まれています。",
+                path.display()
+            );
+        }
+    }
+}
+
+#[test]
+fn tickets_are_encrypted_at_rest() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let encryption = encryption(key(1), vec![]);
+    {
+        let mut store =
+            TicketStore::create_encrypted(data_dir.path(), Project::default(), &encryption)
+                .unwrap();
+        store.add_ticket(draft(SECRET)).unwrap();
+        store.checkpoint().unwrap();
+        store.add_ticket(draft(SECRET)).unwrap();
+    }
+    assert_no_plaintext(data_dir.path(), SECRET);
+
+    let store = TicketStore::open_encrypted(data_dir.path(), &encryption).unwrap();
+    assert_eq!(store.get(TicketId(1)).unwrap().title.0, SECRET);
+    assert!(!store.is_stale());
+}
+
+#[test]
+fn tampered_snapshot_is_detected() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let encryption = encryption(key(1), vec![]);
+    {
+        let mut store =
+            TicketStore::create_encrypted(data_dir.path(), Project::default(), &encryption)
+                .unwrap();
+        store.add_ticket(draft(SECRET)).unwrap();
+        store.checkpoint().unwrap();
+    }
+    let path = data_dir.path().join("snapshot.json");
+    let mut content = fs::read(&path).unwrap();
+    let last = content.len() - 1;
+    content[last] ^= 1;
+    fs::write(&path, content).unwrap();
+
+    assert!(matches!(
+        TicketStore::open_encrypted(data_dir.path(), &encryption),
+        Err(PersistenceError::Tampered(tampered)) if tampered == path
+    ));
+}
+
+#[test]
+fn duplicated_encrypted_record_is_detected() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let encryption = encryption(key(1), vec![]);
+    {
+        let store = TicketStore::create_encrypted(data_dir.path(), Project::default(), &encryption)
+            .unwrap();
+        store.add_ticket(draft(SECRET)).unwrap();
+    }
+    let path = data_dir.path().join("events.jsonl");
+    let first = fs::read_to_string(&path).unwrap();
+    let mut events = OpenOptions::new().append(true).open(&path).unwrap();
+    // 暗号化されたレコードを別の行に複製すると、行番号の認証に失敗する。
+    events.write_all(first.as_bytes()).unwrap();
+
+    assert!(matches!(
+        TicketStore::open_encrypted(data_dir.path(), &encryption),
+        Err(PersistenceError::Tampered(_))
+    ));
+}
+
+#[test]
+fn plaintext_is_rejected_once_encryption_is_required() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let config = EncryptionConfig {
+        key: Some(key(1)),
+        previous_keys: vec![],
+    };
+    {
+        let mut store = TicketStore::create_encrypted(
+            data_dir.path(),
+            Project::default(),
+            &Encryption::new(&config),
+        )
+        .unwrap();
+        store.add_ticket(draft(SECRET)).unwrap();
+        store.checkpoint().unwrap();
+    }
+    // 暗号化されていないスナップショットに差し替える。
+    let plain_dir = tempfile::tempdir().unwrap();
+    {
+        let mut store = TicketStore::create(plain_dir.path(), Project::default()).unwrap();
+        store.add_ticket(draft("差し込まれたチケット")).unwrap();
+        store.checkpoint().unwrap();
+    }
+    let path = data_dir.path().join("snapshot.json");
+    fs::copy(plain_dir.path().join("snapshot.json"), &path).unwrap();
+
+    // 暗号化を必須にする前は、暗号化を有効にする前のファイルとして読み込む。
+    let encryption = persistence::data_dir_encryption(data_dir.path(), &config).unwrap();
+    assert!(TicketStore::open_encrypted(data_dir.path(), &encryption).is_ok());
+
+    persistence::require_encryption(data_dir.path()).unwrap();
+    let encryption = persistence::data_dir_encryption(data_dir.path(), &config).unwrap();
+    assert!(matches!(
+        TicketStore::open_encrypted(data_dir.path(), &encryption),
+        Err(PersistenceError::Tampered(tampered)) if tampered == path
+    ));
+    assert!(matches!(
+        persistence::data_dir_encryption(data_dir.path(), &EncryptionConfig::default()),
+        Err(PersistenceError::EncryptionRequired(_))
+    ));
+}
+
+#[test]
+fn only_truncated_last_record_is_discarded() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let encryption = encryption(key(1), vec![]);
+    {
+        let store = TicketStore::create_encrypted(data_dir.path(), Project::default(), &encryption)
+            .unwrap();
+        store.add_ticket(draft(SECRET)).unwrap();
+        store.add_ticket(draft(SECRET)).unwrap();
+    }
+    let path = data_dir.path().join("events.jsonl");
+    let content = fs::read_to_string(&path).unwrap();
+    let second = content.trim_end_matches('\n').rfind('\n').unwrap() + 1;
+    let last = content[second..].trim_end_matches('\n');
+
+    // 改行で終わらない最終行でも、認証に失敗した場合は改ざんとして扱う。
+    let mut tampered = content.as_bytes()[..second].to_vec();
+    tampered.extend_from_slice(last.as_bytes());
+    let middle = second + last.len() / 2;
+    tampered[middle] = if tampered[middle] == b'A' { b'B' } else { b'A' };
+    fs::write(&path, &tampered).unwrap();
+    assert!(matches!(
+        TicketStore::open_encrypted(data_dir.path(), &encryption),
+        Err(PersistenceError::Tampered(_))
+    ));
+
+    // Base64として途切れた最終行は、書き込みが途中で途切れたものとして切り捨てる。
+    let truncated = &content[..second + ((last.len() / 2) | 1)];
+    fs::write(&path, truncated).unwrap();
+    let store = TicketStore::open_encrypted(data_dir.path(), &encryption).unwrap();
+    assert!(store.get(TicketId(0)).is_ok());
+    assert!(store.get(TicketId(1)).is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), &content[..second]);
+}
+
+#[test]
+fn encrypted_data_requires_key() {
+    let data_dir = tempfile::tempdir().unwrap();
+    {
+        let mut store = TicketStore::create_encrypted(
+            data_dir.path(),
+            Project::default(),
+            &encryption(key(1), vec![]),
+        )
+        .unwrap();
+        store.add_ticket(draft(SECRET)).unwrap();
+        store.checkpoint().unwrap();
+    }
+
+    assert!(matches!(
+        TicketStore::open(data_dir.path()),
+        Err(PersistenceError::Undecryptable {
+            source: DecryptError::KeyRequired,
+            ..
+        })
+    ));
+    assert!(matches!(
+        TicketStore::open_encrypted(data_dir.path(), &encryption(key(2), vec![])),
+        Err(PersistenceError::Undecryptable {
+            source: DecryptError::UnknownKey(_),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn projects_are_reencrypted_with_current_key() {
+    let data_dir = tempfile::tempdir().unwrap();
+    {
+        let registry = ProjectRegistry::open(data_dir.path(), Arc::new(SystemClock)).unwrap();
+        let store = registry.resolve(&ProjectKey::default()).unwrap();
+        store.write().unwrap().add_ticket(draft(SECRET)).unwrap();
+    }
+
+    // 暗号化を有効にすると、暗号化されていないファイルを暗号化し直す。
+    let first = encryption(key(1), vec![]);
+    let registry =
+        ProjectRegistry::open_encrypted(data_dir.path(), Arc::new(SystemClock), &first).unwrap();
+    assert_eq!(
+        registry.reencrypt_next().unwrap(),
+        Some(ProjectKey::default())
+    );
+    assert_eq!(registry.reencrypt_next().unwrap(), None);
+    drop(registry);
+    assert_no_plaintext(&data_dir.path().join("projects"), SECRET);
+
+    // 鍵をローテーションすると、以前の鍵で暗号化されたファイルを暗号化し直す。
+    let rotated = encryption(key(2), vec![key(1)]);
+    let registry =
+        ProjectRegistry::open_encrypted(data_dir.path(), Arc::new(SystemClock), &rotated).unwrap();
+    assert_eq!(
+        registry.reencrypt_next().unwrap(),
+        Some(ProjectKey::default())
+    );
+    assert_eq!(registry.reencrypt_next().unwrap(), None);
+    drop(registry);
+
+    let registry = ProjectRegistry::open_encrypted(
+        data_dir.path(),
+        Arc::new(SystemClock),
+        &encryption(key(2), vec![]),
+    )
+    .unwrap();
+    let store = registry.resolve(&ProjectKey::default()).unwrap();
+    assert_eq!(
+        store.read().unwrap().get(TicketId(0)).unwrap().title.0,
+        SECRET
+    );
+}
+
+#[test]
+fn blobs_are_encrypted_and_reencrypted() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let config = Config::default().attachments;
+    let content = SECRET.repeat(10);
+    let digest = {
+        let blobs =
+            BlobStore::open_encrypted(data_dir.path(), &config, &encryption(key(1), vec![]))
+                .unwrap();
+        let digest = blobs.put(content.as_bytes()).unwrap();
+        assert_eq!(blobs.size(&digest).unwrap(), Some(content.len() as u64));
+        assert_eq!(
+            blobs.read(&digest, 3..9).unwrap(),
+            &content.as_bytes()[3..9]
+        );
+        digest
+    };
+    assert_no_plaintext(data_dir.path(), SECRET);
+
+    let blobs =
+        BlobStore::open_encrypted(data_dir.path(), &config, &encryption(key(2), vec![key(1)]))
+            .unwrap();
+    assert_eq!(blobs.reencrypt().unwrap(), 1);
+    assert_eq!(blobs.reencrypt().unwrap(), 0);
+
+    let blobs =
+        BlobStore::open_encrypted(data_dir.path(), &config, &encryption(key(2), vec![])).unwrap();
+    let len = content.len() as u64;
+    assert_eq!(blobs.read(&digest, 0..len).unwrap(), content.as_bytes());
+}
+
+#[test]
+fn archive_round_trips_and_detects_tampering() {
+    let encryption = encryption(key(1), vec![]);
+    // 複数のチャンクに分かれる大きさのアーカイブ
+    let content = SECRET.repeat(10_000);
+    let mut writer = encryption.archive_writer(vec![]);
+    writer.write_all(content.as_bytes()).unwrap();
+    let archive = writer.finish().unwrap();
+    assert!(!archive
+        .windows(SECRET.len())
+        .any(|window| window == SECRET.as_bytes()));
+
+    assert_eq!(
+        encryption.open_archive(&archive).unwrap().as_ref(),
+        content.as_bytes()
+    );
+    assert_eq!(
+        Encryption::default()
+            .open_archive(content.as_bytes())
+            .unwrap()
+            .as_ref(),
+        content.as_bytes()
+    );
+
+    let mut tampered = archive.clone();
+    tampered[archive.len() / 2] ^= 1;
+    assert!(matches!(
+        encryption.open_archive(&tampered),
+        Err(DecryptError::Tampered)
+    ));
+    let truncated = &archive[..archive.len() - 100];
+    assert!(matches!(
+        encryption.open_archive(truncated),
+        Err(DecryptError::Tampered)
+    ));
+    assert!(matches!(
+        Encryption::default().open_archive(&archive),
+        Err(DecryptError::KeyRequired)
+    ));
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use http_body_util::BodyExt;
+use ticket_store::actor::StoreHandle;
+use ticket_store::blobs::BlobStore;
+use ticket_store::clock::SystemClock;
+use ticket_store::config::Config;
+use ticket_store::crypto::Encryption;
+use ticket_store::dto::{ProjectDraft, TicketDraft};
+use ticket_store::events::ChangeFeed;
+use ticket_store::health::Health;
+use ticket_store::models::{
+    DescriptionFormat, Permissions, Priority, ProjectKey, ProjectName, TicketDescription,
+    TicketTitle, Workflow,
+};
+use ticket_store::registry::ProjectRegistry;
+use ticket_store::saved_queries::SavedQueries;
+use ticket_store::server::{app, AppState};
+use ticket_store::webhooks::Webhooks;
+use tower::ServiceExt;
+
+fn project(key: &str) -> ProjectKey {
+    ProjectKey::try_from(key).unwrap()
+}
+
+fn draft(title: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from("説明").unwrap(),
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+/// 変更フィードを配信するルーターを構築する。
+///
+/// `SECRET`プロジェクトは、`bob`だけが読み込める。
+fn router(feed: &ChangeFeed) -> (StoreHandle, Router) {
+    let config = Config::default();
+    let mut registry = ProjectRegistry::default().with_events(feed.start(None));
+    registry
+        .create(ProjectDraft {
+            key: project("SECRET"),
+            name: ProjectName::try_from("機密").unwrap(),
+            workflow: Workflow::default(),
+            permissions: Permissions {
+                readers: vec!["bob".into()],
+                writers: vec![],
+            },
+            description_format: DescriptionFormat::default(),
+        })
+        .unwrap();
+    let store = StoreHandle::spawn(registry, 1024);
+    let state = AppState {
+        store: store.clone(),
+        health: Arc::new(Health::new(&config)),
+        webhooks: Webhooks::in_memory(&config.webhooks, Arc::new(SystemClock)),
+        saved_queries: SavedQueries::in_memory(Arc::new(SystemClock)),
+        feed: feed.clone(),
+        blobs: BlobStore::in_memory(&config.attachments),
+        encryption: Encryption::default(),
+        admin: None,
+    };
+
+    (store, app(state, &config.limits))
+}
+
+async fn add_ticket(store: &StoreHandle, key: &str, title: &str) {
+    store
+        .create_ticket(project(key), None, draft(title))
+        .await
+        .unwrap();
+}
+
+async fn subscribe(router: &Router, uri: &str, user: &str) -> (StatusCode, Body) {
+    let request = Request::get(uri)
+        .header("X-User", user)
+        .body(Body::empty())
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    (response.status(), response.into_body())
+}
+
+/// 空行で区切られた、次のServer-Sent Eventsのイベントを読み込む。
+async fn next_event(body: &mut Body, buffer: &mut String) -> String {
+    loop {
+        if let Some(end) = buffer.find("\n\n") {
+            let event = buffer[..end].to_string();
+            buffer.drain(..end + 2);
+            return event;
+        }
+        let frame = tokio::time::timeout(Duration::from_secs(5), body.frame())
+            .await
+            .expect("イベントを受信できません")
+            .unwrap()
+            .unwrap();
+        if let Ok(data) = frame.into_data() {
+            buffer.push_str(std::str::from_utf8(&data).unwrap());
+        }
+    }
+}
+
+#[tokio::test]
+async fn change_feed_streams_only_readable_events() {
+    let feed = ChangeFeed::default();
+    let (store, router) = router(&feed);
+
+    let (status, _) = subscribe(&router, "/events?project=SECRET", "alice").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = subscribe(&router, "/events?project=NOPE", "alice").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, mut alice) = subscribe(&router, "/events", "alice").await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, mut bob) = subscribe(&router, "/events?project=SECRET", "bob").await;
+
+    add_ticket(&store, "SECRET", "機密のチケット").await;
+    add_ticket(&store, "TICKET", "公開のチケット").await;
+
+    let mut buffer = String::new();
+    let event = next_event(&mut alice, &mut buffer).await;
+    let (name, data) = event.split_once('\n').unwrap();
+    assert_eq!(name, "event: ticket.created");
+    let data: serde_json::Value =
+        serde_json::from_str(data.strip_prefix("data: ").unwrap()).unwrap();
+    assert_eq!(data["project"], "TICKET");
+    assert_eq!(data["ticket"]["title"], "公開のチケット");
+
+    let mut buffer = String::new();
+    let event = next_event(&mut bob, &mut buffer).await;
+    assert!(event.contains("機密のチケット"), "{event}");
+}
+
+#[tokio::test]
+async fn lagging_subscribers_are_notified_and_closing_ends_streams() {
+    let feed = ChangeFeed::default();
+    let (store, router) = router(&feed);
+    let (_, mut body) = subscribe(&router, "/events?project=TICKET", "alice").await;
+
+    // 購読者が受信しないまま、保持できる数を超えるチケットイベントを送信する。
+    for i in 0..300 {
+        add_ticket(&store, "TICKET", &format!("チケット{i}")).await;
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let mut buffer = String::new();
+    let event = next_event(&mut body, &mut buffer).await;
+    assert_eq!(event, "event: lagged\ndata: {\"skipped\":44}");
+    let event = next_event(&mut body, &mut buffer).await;
+    assert!(event.contains("チケット44"), "{event}");
+
+    feed.close();
+    tokio::time::timeout(Duration::from_secs(5), body.collect())
+        .await
+        .expect("変更フィードを閉じても配信が終了しません")
+        .unwrap();
+}
@@ -0,0 +1,275 @@
+mod common;
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::response::Response;
+use http_body_util::BodyExt;
+use ticket_store::actor::StoreHandle;
+use ticket_store::blobs::BlobStore;
+use ticket_store::clock::SystemClock;
+use ticket_store::config::{Config, StorageBackend};
+use ticket_store::crypto::Encryption;
+use ticket_store::dto::{ProjectDraft, TicketDraft};
+use ticket_store::events::ChangeFeed;
+use ticket_store::health::Health;
+use ticket_store::models::{
+    DescriptionFormat, Permissions, Priority, ProjectKey, ProjectName, TicketDescription,
+    TicketRef, TicketTitle, Workflow,
+};
+use ticket_store::registry::ProjectRegistry;
+use ticket_store::saved_queries::SavedQueries;
+use ticket_store::server::{app, AppState, Server};
+use ticket_store::webhooks::Webhooks;
+use tokio::sync::oneshot;
+use tower::ServiceExt;
+
+use common::{local_config, send, wait_until_ready};
+
+fn app_state(config: &Config) -> AppState {
+    AppState {
+        store: StoreHandle::spawn(ProjectRegistry::default(), 1024),
+        health: Arc::new(Health::new(config)),
+        webhooks: Webhooks::in_memory(&config.webhooks, Arc::new(SystemClock)),
+        saved_queries: SavedQueries::in_memory(Arc::new(SystemClock)),
+        feed: ChangeFeed::default(),
+        blobs: BlobStore::in_memory(&config.attachments),
+        encryption: Encryption::default(),
+        admin: None,
+    }
+}
+
+async fn get_json(state: AppState, config: &Config, path: &str) -> (StatusCode, serde_json::Value) {
+    let request = Request::get(path).body(Body::empty()).unwrap();
+    let response = app(state, &config.limits).oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn liveness_reports_build_information() {
+    let config = Config::default();
+    let (status, body) = get_json(app_state(&config), &config, "/healthz").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["checks"]["process"]["status"], "ok");
+    assert_eq!(body["build"]["version"], env!("CARGO_PKG_VERSION"));
+}
+
+/// 既定のプロジェクトの書き込みロックを別のスレッドで保持し続け、チケットストアのアクターを止められるようにする。
+///
+/// 戻り値の送信側に送信するかドロップすると、ロックを解放する。
+fn stall_default_project(
+    registry: &ProjectRegistry,
+) -> (std::sync::mpsc::Sender<()>, std::thread::JoinHandle<()>) {
+    let store = registry
+        .resolve(&ProjectKey::try_from("TICKET").unwrap())
+        .unwrap();
+    let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+    let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    let holder = std::thread::spawn(move || {
+        let _guard = store.write().unwrap();
+        locked_tx.send(()).unwrap();
+        let _ = release_rx.recv();
+    });
+    locked_rx.recv().unwrap();
+
+    (release_tx, holder)
+}
+
+/// 既定のプロジェクトのチケットを取得するリクエストを、バックグラウンドで送信する。
+///
+/// アクターがコマンドを受信するまで少し待ってから戻る。
+async fn spawn_get_ticket(store: &StoreHandle) -> tokio::task::JoinHandle<()> {
+    let store = store.clone();
+    let task = tokio::spawn(async move {
+        let _ = store
+            .get_ticket(
+                ProjectKey::try_from("TICKET").unwrap(),
+                None,
+                TicketRef::try_from("1").unwrap(),
+            )
+            .await;
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+    task
+}
+
+/// 既定のプロジェクトにチケットを登録するリクエストを、バックグラウンドで送信する。
+///
+/// アクターがコマンドを受信するまで少し待ってから戻る。
+async fn spawn_create_ticket(store: &StoreHandle) -> tokio::task::JoinHandle<()> {
+    let store = store.clone();
+    let task = tokio::spawn(async move {
+        let draft = TicketDraft {
+            title: TicketTitle::try_from("止まったチケット").unwrap(),
+            description: TicketDescription::try_from("説明").unwrap(),
+            priority: Priority::default(),
+            due_date: None,
+        };
+        let _ = store
+            .create_ticket(ProjectKey::try_from("TICKET").unwrap(), None, draft)
+            .await;
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+    task
+}
+
+async fn post_ticket(state: AppState, config: &Config, project: &str) -> Response {
+    let request = Request::post(format!("/projects/{project}/tickets"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            r#"{"title": "チケット", "description": "説明"}"#,
+        ))
+        .unwrap();
+    app(state, &config.limits).oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn readiness_fails_while_store_is_stuck() {
+    let mut config = Config::default();
+    config.health.lock_timeout_ms = 20;
+    let registry = ProjectRegistry::default();
+    let (release_tx, holder) = stall_default_project(&registry);
+    let state = AppState {
+        store: StoreHandle::spawn(registry, 1024),
+        ..app_state(&config)
+    };
+
+    // アクターがロックを待ち続けるコマンドを送信する。
+    let stuck = spawn_get_ticket(&state.store).await;
+    let (status, body) = get_json(state.clone(), &config, "/readyz").await;
+    release_tx.send(()).unwrap();
+    holder.join().unwrap();
+    stuck.await.unwrap();
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["checks"]["storeLock"]["status"], "fail");
+    assert_eq!(body["checks"]["storage"]["status"], "ok");
+
+    let (status, _) = get_json(state, &config, "/readyz").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn requests_are_rejected_while_store_queue_is_full() {
+    let config = Config::default();
+    let mut registry = ProjectRegistry::default();
+    registry
+        .create(ProjectDraft {
+            key: ProjectKey::try_from("WEB").unwrap(),
+            name: ProjectName::try_from("ウェブサイト").unwrap(),
+            workflow: Workflow::default(),
+            permissions: Permissions::default(),
+            description_format: DescriptionFormat::default(),
+        })
+        .unwrap();
+    let (release_tx, holder) = stall_default_project(&registry);
+    let state = AppState {
+        store: StoreHandle::spawn(registry, 1),
+        ..app_state(&config)
+    };
+
+    // 1つ目のコマンドで既定のプロジェクトのアクターを止め、2つ目のコマンドでキューを埋める。
+    let stuck = [
+        spawn_create_ticket(&state.store).await,
+        spawn_create_ticket(&state.store).await,
+    ];
+    let response = post_ticket(state.clone(), &config, "TICKET").await;
+    // キューは既定のプロジェクトごとにあるため、他のプロジェクトのリクエストは処理できる。
+    let other = post_ticket(state.clone(), &config, "WEB").await;
+    let (listed, tickets) = get_json(state, &config, "/projects/WEB/tickets").await;
+    release_tx.send(()).unwrap();
+    holder.join().unwrap();
+    for task in stuck {
+        task.await.unwrap();
+    }
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], "overloaded");
+    assert_eq!(other.status(), StatusCode::OK);
+    assert_eq!(listed, StatusCode::OK);
+    assert_eq!(tickets.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn tickets_are_unavailable_until_storage_is_replayed() {
+    let mut config = Config::default();
+    config.storage.backend = StorageBackend::File;
+    config.storage.data_dir = Some("/nonexistent".into());
+    let state = app_state(&config);
+
+    let (status, body) = get_json(state.clone(), &config, "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["checks"]["storage"]["status"], "fail");
+
+    let request = Request::get("/tickets/0").body(Body::empty()).unwrap();
+    let response = app(state, &config.limits).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+}
+
+#[tokio::test]
+async fn readiness_checks_disk_headroom_of_data_dir() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let mut config = local_config();
+    config.storage.backend = StorageBackend::File;
+    config.storage.data_dir = Some(data_dir.path().into());
+    config.health.min_free_disk_bytes = u64::MAX;
+    let server = Server::bind(&config).await.unwrap();
+    let addr = server.local_addr();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let handle = tokio::spawn(server.serve(async {
+        let _ = shutdown_rx.await;
+    }));
+
+    let mut body = serde_json::Value::Null;
+    for _ in 0..100 {
+        let (_, response) = send(addr, "GET", "/readyz", None).await;
+        body = serde_json::from_str(&response).unwrap();
+        if body["checks"]["storage"]["status"] == "ok" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(body["status"], "fail");
+    assert_eq!(body["checks"]["disk"]["status"], "fail");
+
+    shutdown_tx.send(()).unwrap();
+    handle.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn readiness_succeeds_after_replay() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let mut config = local_config();
+    config.storage.backend = StorageBackend::File;
+    config.storage.data_dir = Some(data_dir.path().into());
+    config.health.min_free_disk_bytes = 0;
+    let server = Server::bind(&config).await.unwrap();
+    let addr = server.local_addr();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let handle = tokio::spawn(server.serve(async {
+        let _ = shutdown_rx.await;
+    }));
+
+    wait_until_ready(addr).await;
+    let (status, body) = send(addr, "GET", "/readyz", None).await;
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(status, 200);
+    for check in ["storage", "storeLock", "disk"] {
+        assert_eq!(body["checks"][check]["status"], "ok", "{check}");
+        assert!(body["checks"][check]["latencyMs"].is_f64());
+    }
+
+    shutdown_tx.send(()).unwrap();
+    handle.await.unwrap().unwrap();
+}
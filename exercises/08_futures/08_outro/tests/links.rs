@@ -0,0 +1,397 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use http_body_util::BodyExt;
+use ticket_store::actor::StoreHandle;
+use ticket_store::blobs::BlobStore;
+use ticket_store::clock::SystemClock;
+use ticket_store::config::Config;
+use ticket_store::crypto::Encryption;
+use ticket_store::dto::{ProjectDraft, TicketDraft, TicketPatch};
+use ticket_store::events::ChangeFeed;
+use ticket_store::graph::{GraphEdge, LinkKind};
+use ticket_store::health::Health;
+use ticket_store::models::{
+    DescriptionFormat, Permissions, Priority, Project, ProjectKey, ProjectName, TicketDescription,
+    TicketId, TicketStatus, TicketTitle, Workflow,
+};
+use ticket_store::registry::{self, ProjectRegistry};
+use ticket_store::saved_queries::SavedQueries;
+use ticket_store::server::{app, AppState};
+use ticket_store::store::{TicketStore, TicketStoreError};
+use ticket_store::webhooks::Webhooks;
+use tower::ServiceExt;
+
+fn draft(title: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from("説明").unwrap(),
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+fn done(store: &mut TicketStore, id: TicketId) -> Result<(), TicketStoreError> {
+    let version = store.get(id)?.version;
+    store.update_ticket(
+        id,
+        TicketPatch {
+            title: None,
+            description: None,
+            status: Some(TicketStatus::Done),
+            priority: None,
+            due_date: None,
+            version,
+        },
+    )
+}
+
+fn reopen(store: &mut TicketStore, id: TicketId) -> Result<(), TicketStoreError> {
+    let version = store.get(id)?.version;
+    store.update_ticket(
+        id,
+        TicketPatch {
+            title: None,
+            description: None,
+            status: Some(TicketStatus::ToDo),
+            priority: None,
+            due_date: None,
+            version,
+        },
+    )
+}
+
+/// チケットを指定した数だけ登録したチケットストアを構築する。
+fn store_with(count: u64) -> TicketStore {
+    let store = TicketStore::default();
+    for n in 1..=count {
+        store.add_ticket(draft(&format!("チケット{n}"))).unwrap();
+    }
+    store
+}
+
+fn order(store: &TicketStore) -> Vec<u64> {
+    store.open_work().iter().map(|t| t.id.0).collect()
+}
+
+#[test]
+fn links_that_would_create_a_cycle_are_rejected() {
+    let mut store = store_with(4);
+    // 1は0をブロックし、2は1をブロックする。
+    store.add_blocker(TicketId(0), TicketId(1)).unwrap();
+    store.add_blocker(TicketId(1), TicketId(2)).unwrap();
+
+    assert!(matches!(
+        store.add_blocker(TicketId(2), TicketId(0)),
+        Err(TicketStoreError::DependencyCycle)
+    ));
+    assert!(matches!(
+        store.add_blocker(TicketId(0), TicketId(0)),
+        Err(TicketStoreError::DependencyCycle)
+    ));
+    // 親チケットは、サブタスクより後に完了する。
+    assert!(matches!(
+        store.set_parent(TicketId(0), Some(TicketId(2))),
+        Err(TicketStoreError::DependencyCycle)
+    ));
+    store.set_parent(TicketId(0), Some(TicketId(3))).unwrap();
+    assert!(matches!(
+        store.set_parent(TicketId(3), Some(TicketId(2))),
+        Err(TicketStoreError::DependencyCycle)
+    ));
+    assert!(matches!(
+        store.add_blocker(TicketId(0), TicketId(8)),
+        Err(TicketStoreError::NotFound)
+    ));
+
+    let ticket = store.get(TicketId(0)).unwrap();
+    assert_eq!(ticket.parent, Some(TicketId(3)));
+    assert_eq!(
+        ticket.blocked_by.iter().copied().collect::<Vec<_>>(),
+        vec![TicketId(1)]
+    );
+    assert_eq!(ticket.version, 2);
+}
+
+#[test]
+fn tickets_with_open_blockers_or_children_cannot_be_done() {
+    let mut store = store_with(3);
+    store.add_blocker(TicketId(0), TicketId(1)).unwrap();
+    store.set_parent(TicketId(2), Some(TicketId(0))).unwrap();
+
+    assert!(matches!(
+        done(&mut store, TicketId(0)),
+        Err(TicketStoreError::OpenDependencies)
+    ));
+    done(&mut store, TicketId(1)).unwrap();
+    assert!(matches!(
+        done(&mut store, TicketId(0)),
+        Err(TicketStoreError::OpenDependencies)
+    ));
+    done(&mut store, TicketId(2)).unwrap();
+    done(&mut store, TicketId(0)).unwrap();
+
+    store.remove_blocker(TicketId(0), TicketId(1)).unwrap();
+    assert!(matches!(
+        store.remove_blocker(TicketId(0), TicketId(1)),
+        Err(TicketStoreError::NotFound)
+    ));
+}
+
+#[test]
+fn done_tickets_cannot_get_open_prerequisites() {
+    let data_dir = tempfile::tempdir().unwrap();
+    {
+        let mut store = TicketStore::create(data_dir.path(), Project::default()).unwrap();
+        for title in ["一つ目", "二つ目", "三つ目", "四つ目"] {
+            store.add_ticket(draft(title)).unwrap();
+        }
+        // 0のサブタスクは1、2は0をブロックする。
+        store.set_parent(TicketId(1), Some(TicketId(0))).unwrap();
+        store.add_blocker(TicketId(0), TicketId(2)).unwrap();
+        for id in [1, 2, 0] {
+            done(&mut store, TicketId(id)).unwrap();
+        }
+    }
+    let mut store = TicketStore::open(data_dir.path()).unwrap();
+
+    // 完了した親チケットのサブタスクは、完了から戻せない。
+    assert!(matches!(
+        reopen(&mut store, TicketId(1)),
+        Err(TicketStoreError::DoneDependents)
+    ));
+    // 完了したチケットのブロッカーも、完了から戻せない。
+    assert!(matches!(
+        reopen(&mut store, TicketId(2)),
+        Err(TicketStoreError::DoneDependents)
+    ));
+    // 完了した親チケットに、完了していないサブタスクを追加できない。
+    assert!(matches!(
+        store.set_parent(TicketId(3), Some(TicketId(0))),
+        Err(TicketStoreError::DoneDependents)
+    ));
+    // 完了したチケットに、完了していないブロッカーを追加できない。
+    assert!(matches!(
+        store.add_blocker(TicketId(0), TicketId(3)),
+        Err(TicketStoreError::DoneDependents)
+    ));
+    store.rebuild_projections().unwrap();
+    assert!(matches!(
+        reopen(&mut store, TicketId(2)),
+        Err(TicketStoreError::DoneDependents)
+    ));
+
+    // 依存するチケットを先に完了から戻せば、前提のチケットも戻せる。
+    reopen(&mut store, TicketId(0)).unwrap();
+    reopen(&mut store, TicketId(1)).unwrap();
+    reopen(&mut store, TicketId(2)).unwrap();
+    store.set_parent(TicketId(3), Some(TicketId(0))).unwrap();
+    store.add_blocker(TicketId(0), TicketId(3)).unwrap();
+}
+
+#[test]
+fn subtasks_are_checked_after_reparenting_and_reopening() {
+    let data_dir = tempfile::tempdir().unwrap();
+    {
+        let mut store = TicketStore::create(data_dir.path(), Project::default()).unwrap();
+        for title in ["一つ目", "二つ目", "三つ目"] {
+            store.add_ticket(draft(title)).unwrap();
+        }
+        store.set_parent(TicketId(2), Some(TicketId(0))).unwrap();
+        store.checkpoint().unwrap();
+        // スナップショットの後に、サブタスクを別の親チケットに移す。
+        store.set_parent(TicketId(2), Some(TicketId(1))).unwrap();
+    }
+
+    let mut store = TicketStore::open(data_dir.path()).unwrap();
+    done(&mut store, TicketId(0)).unwrap();
+    assert!(matches!(
+        done(&mut store, TicketId(1)),
+        Err(TicketStoreError::OpenDependencies)
+    ));
+    store.rebuild_projections().unwrap();
+    assert!(matches!(
+        done(&mut store, TicketId(1)),
+        Err(TicketStoreError::OpenDependencies)
+    ));
+    store.set_parent(TicketId(2), None).unwrap();
+    done(&mut store, TicketId(1)).unwrap();
+}
+
+#[test]
+fn open_work_puts_prerequisites_first() {
+    let mut store = store_with(5);
+    // 0のサブタスクは3と4、4は1をブロックする。
+    store.set_parent(TicketId(3), Some(TicketId(0))).unwrap();
+    store.set_parent(TicketId(4), Some(TicketId(0))).unwrap();
+    store.add_blocker(TicketId(1), TicketId(4)).unwrap();
+    assert_eq!(order(&store), vec![2, 3, 4, 0, 1]);
+
+    done(&mut store, TicketId(4)).unwrap();
+    assert_eq!(order(&store), vec![1, 2, 3, 0]);
+
+    let graph = store.dependency_graph(TicketId(1)).unwrap();
+    let nodes: Vec<_> = graph.nodes.iter().map(|n| n.id.0).collect();
+    assert_eq!(nodes, vec![0, 1, 3, 4]);
+    assert_eq!(
+        graph.edges,
+        vec![
+            GraphEdge {
+                from: TicketId(0),
+                to: TicketId(3),
+                kind: LinkKind::Subtask,
+            },
+            GraphEdge {
+                from: TicketId(0),
+                to: TicketId(4),
+                kind: LinkKind::Subtask,
+            },
+            GraphEdge {
+                from: TicketId(4),
+                to: TicketId(1),
+                kind: LinkKind::Blocks,
+            },
+        ]
+    );
+    assert_eq!(store.dependency_graph(TicketId(2)).unwrap().edges, vec![]);
+}
+
+#[test]
+fn linked_tickets_cannot_be_moved_and_links_are_restored() {
+    let data_dir = tempfile::tempdir().unwrap();
+    {
+        let mut store = TicketStore::create(data_dir.path(), Project::default()).unwrap();
+        store.add_ticket(draft("一つ目")).unwrap();
+        store.add_ticket(draft("二つ目")).unwrap();
+        store.add_blocker(TicketId(0), TicketId(1)).unwrap();
+    }
+    let store = TicketStore::open(data_dir.path()).unwrap();
+    assert_eq!(order(&store), vec![1, 0]);
+
+    let mut registry = ProjectRegistry::default();
+    registry
+        .create(ProjectDraft {
+            key: ProjectKey::try_from("WEB").unwrap(),
+            name: ProjectName::try_from("ウェブサイト").unwrap(),
+            workflow: Workflow::default(),
+            permissions: Permissions::default(),
+            description_format: DescriptionFormat::default(),
+        })
+        .unwrap();
+    let from = registry.resolve(&ProjectKey::default()).unwrap();
+    let to = registry
+        .resolve(&ProjectKey::try_from("WEB").unwrap())
+        .unwrap();
+    *from.write().unwrap() = store;
+    assert!(matches!(
+        registry::move_ticket(&from, TicketId(1), &to, None),
+        Err(TicketStoreError::TicketLinked)
+    ));
+    assert!(to.read().unwrap().is_empty());
+}
+
+async fn request(router: &Router, method: &str, uri: &str, body: &str) -> (StatusCode, String) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn link_routes_update_and_query_dependencies() {
+    let config = Config::default();
+    let mut store = store_with(3);
+    store.set_parent(TicketId(2), Some(TicketId(0))).unwrap();
+    let registry = ProjectRegistry::default();
+    *registry
+        .resolve(&ProjectKey::default())
+        .unwrap()
+        .write()
+        .unwrap() = store;
+    let state = AppState {
+        store: StoreHandle::spawn(registry, 1024),
+        health: Arc::new(Health::new(&config)),
+        webhooks: Webhooks::in_memory(&config.webhooks, Arc::new(SystemClock)),
+        saved_queries: SavedQueries::in_memory(Arc::new(SystemClock)),
+        feed: ChangeFeed::default(),
+        blobs: BlobStore::in_memory(&config.attachments),
+        encryption: Encryption::default(),
+        admin: None,
+    };
+    let router = app(state, &config.limits);
+
+    let (status, _) = request(
+        &router,
+        "PUT",
+        "/projects/TICKET/tickets/TICKET-1/blockers/1",
+        "",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = request(&router, "PUT", "/projects/TICKET/tickets/1/blockers/0", "").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    assert!(body.contains("循環"));
+    let (status, body) = request(
+        &router,
+        "PATCH",
+        "/projects/TICKET/tickets/0",
+        r#"{"status": "Done", "version": 1}"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("完了していないサブタスクまたはブロッカー"));
+
+    let (status, body) = request(&router, "GET", "/projects/TICKET/open-work", "").await;
+    assert_eq!(status, StatusCode::OK);
+    let tickets: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let keys: Vec<_> = tickets
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["key"].as_str().unwrap())
+        .collect();
+    assert_eq!(keys, vec!["TICKET-2", "TICKET-3", "TICKET-1"]);
+
+    let (status, body) = request(&router, "GET", "/projects/TICKET/tickets/2/graph", "").await;
+    assert_eq!(status, StatusCode::OK);
+    let graph: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(graph["root"], 2);
+    assert_eq!(graph["nodes"].as_array().unwrap().len(), 3);
+    assert_eq!(
+        graph["edges"][1],
+        serde_json::json!({"from": 1, "to": 0, "kind": "Blocks"})
+    );
+
+    let (status, _) = request(
+        &router,
+        "PUT",
+        "/projects/TICKET/tickets/2/parent",
+        r#"{"parent": null}"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = request(
+        &router,
+        "DELETE",
+        "/projects/TICKET/tickets/0/blockers/1",
+        "",
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = request(
+        &router,
+        "PATCH",
+        "/projects/TICKET/tickets/0",
+        r#"{"status": "Done", "version": 2}"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
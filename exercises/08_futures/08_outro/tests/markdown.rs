@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use http_body_util::BodyExt;
+use serde_json::Value;
+use ticket_store::actor::StoreHandle;
+use ticket_store::blobs::BlobStore;
+use ticket_store::clock::SystemClock;
+use ticket_store::config::Config;
+use ticket_store::crypto::Encryption;
+use ticket_store::events::ChangeFeed;
+use ticket_store::health::Health;
+use ticket_store::markdown::render_html;
+use ticket_store::models::{DescriptionFormat, ProjectKey, TicketDescription};
+use ticket_store::registry::ProjectRegistry;
+use ticket_store::saved_queries::SavedQueries;
+use ticket_store::server::{app, AppState};
+use ticket_store::webhooks::Webhooks;
+use tower::ServiceExt;
+
+fn render(description: &str, format: DescriptionFormat) -> String {
+    let description = TicketDescription::try_from(description).unwrap();
+    render_html(&description, format, &ProjectKey::default())
+}
+
+#[test]
+fn markdown_is_rendered_without_scripts_or_unsafe_links() {
+    let html = render(
+        "**重要** <script>alert(1)</script>\n\n[危険](javascript:alert(1)) [安全](https://example.com) <img src=x onerror=alert(1)>",
+        DescriptionFormat::Markdown,
+    );
+
+    assert!(html.contains("<strong>重要</strong>"), "{html}");
+    assert!(!html.contains("script"), "{html}");
+    assert!(!html.contains("javascript:"), "{html}");
+    assert!(!html.contains("onerror"), "{html}");
+    assert!(
+        html.contains(
+            r#"<a href="https://example.com" rel="noopener noreferrer nofollow">安全</a>"#
+        ),
+        "{html}"
+    );
+}
+
+#[test]
+fn ticket_references_are_linked_outside_code_and_links() {
+    let html = render(
+        "#12を参照、チケット#15も。`#13`、issue#14、[#17](https://example.com)\n\n```\n#16\n```",
+        DescriptionFormat::Markdown,
+    );
+
+    for linked in [12, 15] {
+        assert!(
+            html.contains(&format!(r#"href="/projects/TICKET/tickets/{linked}""#)),
+            "{html}"
+        );
+    }
+    for literal in [13, 14, 16, 17] {
+        assert!(!html.contains(&format!("/tickets/{literal}")), "{html}");
+    }
+}
+
+#[test]
+fn plain_descriptions_are_escaped() {
+    let html = render(
+        "<b>太字</b> **強調** #3\n二行目\n\n段落",
+        DescriptionFormat::Plain,
+    );
+
+    assert!(
+        html.starts_with(
+            "<p>&lt;b&gt;太字&lt;/b&gt; **強調** <a href=\"/projects/TICKET/tickets/3\""
+        ),
+        "{html}"
+    );
+    assert!(html.contains("<br>\n二行目</p>\n<p>段落</p>"), "{html}");
+}
+
+async fn send(router: &Router, method: &str, uri: &str, body: &str) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn description_html_is_returned_when_requested() {
+    let config = Config::default();
+    let state = AppState {
+        store: StoreHandle::spawn(ProjectRegistry::default(), 1024),
+        health: Arc::new(Health::new(&config)),
+        webhooks: Webhooks::in_memory(&config.webhooks, Arc::new(SystemClock)),
+        saved_queries: SavedQueries::in_memory(Arc::new(SystemClock)),
+        feed: ChangeFeed::default(),
+        blobs: BlobStore::in_memory(&config.attachments),
+        encryption: Encryption::default(),
+        admin: None,
+    };
+    let router = app(state, &config.limits);
+    let (status, _) = send(
+        &router,
+        "PATCH",
+        "/projects/TICKET",
+        r#"{"descriptionFormat": "Markdown"}"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    // 文字数の上限は、HTMLに変換する前の原文に適用する。
+    let description = "*a* ".repeat(125).trim_end().to_string();
+    let body = serde_json::json!({"title": "羅生門", "description": description});
+    let (status, _) = send(
+        &router,
+        "POST",
+        "/projects/TICKET/tickets",
+        &body.to_string(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, ticket) = send(&router, "GET", "/projects/TICKET/tickets/0", "").await;
+    assert_eq!(ticket["description"], description);
+    assert!(ticket.get("descriptionHtml").is_none());
+    let (status, ticket) = send(&router, "GET", "/projects/TICKET/tickets/0?render=html", "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ticket["description"], description);
+    assert!(ticket["descriptionHtml"]
+        .as_str()
+        .unwrap()
+        .starts_with("<p><em>a</em> <em>a</em>"));
+}
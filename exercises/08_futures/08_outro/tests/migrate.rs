@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use chrono::{DateTime, TimeZone, Utc};
+use clap::Parser;
+use ticket_store::clock::ManualClock;
+use ticket_store::config::{Args, Command};
+use ticket_store::dto::TicketDraft;
+use ticket_store::migrate::{self, MigrationReport, DATA_FORMAT_VERSION};
+use ticket_store::models::{
+    Priority, ProjectKey, TicketDescription, TicketId, TicketStatus, TicketTitle,
+};
+use ticket_store::persistence::PersistenceError;
+use ticket_store::registry::{ProjectLookup, ProjectRegistry};
+
+/// 移行した日時
+fn now() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 8, 1, 9, 0, 0).unwrap()
+}
+
+/// 形式バージョンのフィクスチャを、一時ディレクトリに複製する。
+fn fixture(version: u32) -> tempfile::TempDir {
+    let data_dir = tempfile::tempdir().unwrap();
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("data-v{version}"));
+    copy_dir(&fixture, data_dir.path());
+    data_dir
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let to = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &to);
+        } else {
+            fs::copy(entry.path(), to).unwrap();
+        }
+    }
+}
+
+/// ディレクトリのすべてのファイルの内容を、相対パスごとに返す。
+fn files(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+    fn collect(root: &Path, dir: &Path, files: &mut BTreeMap<PathBuf, Vec<u8>>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                collect(root, &path, files);
+            } else {
+                let content = fs::read(&path).unwrap();
+                files.insert(path.strip_prefix(root).unwrap().into(), content);
+            }
+        }
+    }
+    let mut files = BTreeMap::new();
+    collect(dir, dir, &mut files);
+    files
+}
+
+/// プロジェクトキーと、チケットID順のチケットID、チケットキー、タイトル、ステータス、バージョン
+type ProjectTickets = Vec<(String, Vec<(u64, String, String, TicketStatus, u64)>)>;
+
+fn tickets(registry: &ProjectRegistry) -> ProjectTickets {
+    registry
+        .snapshots()
+        .into_iter()
+        .map(|snapshot| {
+            let tickets = snapshot
+                .tickets
+                .iter()
+                .map(|t| {
+                    (
+                        t.id.0,
+                        t.key.to_string(),
+                        t.title.0.clone(),
+                        t.status,
+                        t.version,
+                    )
+                })
+                .collect();
+            (snapshot.project.unwrap().key.to_string(), tickets)
+        })
+        .collect()
+}
+
+fn draft(title: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from("説明").unwrap(),
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+/// 形式バージョンごとの、移行した後のプロジェクトとチケット
+fn expected(version: u32) -> ProjectTickets {
+    let ticket = |id, key: &str, title: &str, status, version| {
+        (id, key.to_string(), title.to_string(), status, version)
+    };
+    let default_project = (
+        "TICKET".to_string(),
+        vec![
+            ticket(0, "TICKET-1", "吾輩は猫である", TicketStatus::ToDo, 0),
+            ticket(1, "TICKET-2", "羅生門", TicketStatus::InProgress, 1),
+        ],
+    );
+    match version {
+        1 | 2 => vec![default_project],
+        3 => vec![
+            (
+                "SITE".to_string(),
+                vec![ticket(
+                    0,
+                    "SITE-1",
+                    "トップページの改修",
+                    TicketStatus::ToDo,
+                    0,
+                )],
+            ),
+            default_project,
+        ],
+        _ => vec![
+            (
+                "SITE".to_string(),
+                vec![
+                    ticket(0, "SITE-1", "トップページの改修", TicketStatus::ToDo, 0),
+                    ticket(1, "SITE-2", "吾輩は猫である", TicketStatus::ToDo, 1),
+                ],
+            ),
+            (
+                "TICKET".to_string(),
+                vec![ticket(1, "TICKET-2", "羅生門", TicketStatus::InProgress, 1)],
+            ),
+        ],
+    }
+}
+
+#[test]
+fn every_historical_format_is_migrated() {
+    for version in 1..DATA_FORMAT_VERSION {
+        let data_dir = fixture(version);
+        let clock = Arc::new(ManualClock::new(now()));
+        assert_eq!(migrate::detect(data_dir.path()).unwrap(), Some(version));
+        assert!(matches!(
+            ProjectRegistry::open(data_dir.path(), clock.clone()),
+            Err(PersistenceError::OutdatedFormat { found, current: DATA_FORMAT_VERSION }) if found == version
+        ));
+
+        let before = files(data_dir.path());
+        let report = migrate::migrate(data_dir.path(), now(), true).unwrap();
+        assert!(
+            matches!(
+                report,
+                MigrationReport::Migrated { from, backup_dir: None, .. } if from == version
+            ),
+            "v{version}: {report:?}"
+        );
+        assert_eq!(files(data_dir.path()), before, "v{version}");
+
+        let report = migrate::migrate(data_dir.path(), now(), false).unwrap();
+        let MigrationReport::Migrated {
+            backup_dir: Some(backup_dir),
+            tickets: migrated,
+            ..
+        } = report
+        else {
+            panic!("v{version}: {report:?}");
+        };
+        assert_eq!(files(&backup_dir), before, "v{version}");
+        assert_eq!(
+            migrate::detect(data_dir.path()).unwrap(),
+            Some(DATA_FORMAT_VERSION)
+        );
+        assert_eq!(
+            migrate::migrate(data_dir.path(), now(), false).unwrap(),
+            MigrationReport::UpToDate
+        );
+
+        // 移行したドメインイベントを再び取り込まないことを、開き直して確かめる。
+        for _ in 0..2 {
+            let registry = ProjectRegistry::open(data_dir.path(), clock.clone()).unwrap();
+            let tickets = tickets(&registry);
+            assert_eq!(tickets, expected(version), "v{version}");
+            let total: usize = tickets.iter().map(|(_, tickets)| tickets.len()).sum();
+            assert_eq!(total, migrated);
+        }
+        let registry = ProjectRegistry::open(data_dir.path(), clock.clone()).unwrap();
+        let store = registry.resolve(&ProjectKey::default()).unwrap();
+        let store = store.read().unwrap();
+        // 形式バージョン5より前のチケットは、取り込んだ状態から履歴を始める。
+        let history = if version < 5 { 1 } else { 2 };
+        assert_eq!(store.history(TicketId(1)).len(), history, "v{version}");
+        assert_eq!(store.add_ticket(draft("三つ目")).unwrap(), TicketId(2));
+    }
+}
+
+#[test]
+fn missing_fields_are_filled_in_by_migrations() {
+    for version in [1, 2] {
+        let data_dir = fixture(version);
+        migrate::migrate(data_dir.path(), now(), false).unwrap();
+        let registry =
+            ProjectRegistry::open(data_dir.path(), Arc::new(ManualClock::new(now()))).unwrap();
+        let store = registry.resolve(&ProjectKey::default()).unwrap();
+        let store = store.read().unwrap();
+        let ticket = store.get(TicketId(1)).unwrap();
+        if version == 1 {
+            assert_eq!(ticket.priority, Priority::Medium);
+            assert_eq!(ticket.due_date, None);
+            assert_eq!((ticket.created_at, ticket.updated_at), (now(), now()));
+        } else {
+            assert_eq!(ticket.priority, Priority::High);
+            assert_eq!(ticket.due_date.unwrap().to_string(), "2024-07-19");
+            assert_eq!(
+                ticket.updated_at,
+                Utc.with_ymd_and_hms(2024, 7, 16, 2, 32, 31).unwrap()
+            );
+        }
+    }
+
+    for version in [3, 4, 5] {
+        let data_dir = fixture(version);
+        migrate::migrate(data_dir.path(), now(), false).unwrap();
+        let registry =
+            ProjectRegistry::open(data_dir.path(), Arc::new(ManualClock::new(now()))).unwrap();
+        assert!(matches!(
+            registry.lookup(&ProjectKey::try_from("WEB").unwrap()),
+            Ok(ProjectLookup::Renamed(key)) if key.as_str() == "SITE"
+        ));
+        if version >= 4 {
+            let snapshot = &registry.snapshots()[1];
+            assert_eq!(snapshot.moved.len(), 1);
+            assert_eq!(snapshot.moved[0].moved_to.to_string(), "WEB-2");
+        }
+    }
+}
+
+#[test]
+fn empty_and_future_data_dirs_are_not_migrated() {
+    let data_dir = tempfile::tempdir().unwrap();
+    assert_eq!(migrate::detect(data_dir.path()).unwrap(), None);
+    assert_eq!(
+        migrate::migrate(data_dir.path(), now(), true).unwrap(),
+        MigrationReport::Initialized
+    );
+    assert!(files(data_dir.path()).is_empty());
+    assert_eq!(
+        migrate::migrate(data_dir.path(), now(), false).unwrap(),
+        MigrationReport::Initialized
+    );
+    assert_eq!(
+        migrate::detect(data_dir.path()).unwrap(),
+        Some(DATA_FORMAT_VERSION)
+    );
+
+    fs::write(data_dir.path().join("format.json"), r#"{"format":99}"#).unwrap();
+    assert!(matches!(
+        migrate::migrate(data_dir.path(), now(), false),
+        Err(PersistenceError::UnsupportedFormat(99))
+    ));
+    assert!(matches!(
+        ProjectRegistry::open(data_dir.path(), Arc::new(ManualClock::new(now()))),
+        Err(PersistenceError::UnsupportedFormat(99))
+    ));
+}
+
+#[test]
+fn interrupted_migration_is_rolled_back_and_retried() {
+    let data_dir = fixture(3);
+    let before = files(data_dir.path());
+    // 移行前の項目を退避し、移行した`projects`ディレクトリと入れ替えた後、形式バージョンを記録する前に停止した状態
+    let staging = data_dir.path().join("migrating");
+    let replaced = staging.join("pre-migration-v3");
+    fs::create_dir_all(&replaced).unwrap();
+    fs::write(staging.join("format.json"), r#"{"format":6}"#).unwrap();
+    for name in ["snapshot.json", "journal.jsonl"] {
+        fs::rename(data_dir.path().join(name), replaced.join(name)).unwrap();
+    }
+    fs::create_dir_all(data_dir.path().join("projects/TICKET")).unwrap();
+
+    let clock = Arc::new(ManualClock::new(now()));
+    assert!(matches!(
+        ProjectRegistry::open(data_dir.path(), clock.clone()),
+        Err(PersistenceError::MigrationInterrupted)
+    ));
+    assert!(matches!(
+        migrate::migrate(data_dir.path(), now(), true),
+        Err(PersistenceError::MigrationInterrupted)
+    ));
+
+    let report = migrate::migrate(data_dir.path(), now(), false).unwrap();
+    assert!(matches!(report, MigrationReport::Migrated { from: 3, .. }));
+    assert_eq!(files(&data_dir.path().join("pre-migration-v3")), before);
+    assert!(!staging.exists());
+    let registry = ProjectRegistry::open(data_dir.path(), clock).unwrap();
+    assert_eq!(tickets(&registry), expected(3));
+}
+
+#[test]
+fn migrate_subcommand_is_parsed() {
+    let args = Args::try_parse_from([
+        "ticket-store",
+        "--data-dir",
+        "/tmp/data",
+        "migrate",
+        "--dry-run",
+    ])
+    .unwrap();
+    assert_eq!(args.command, Some(Command::Migrate { dry_run: true }));
+    assert_eq!(args.data_dir.unwrap(), Path::new("/tmp/data"));
+
+    let args = Args::try_parse_from(["ticket-store"]).unwrap();
+    assert_eq!(args.command, None);
+}
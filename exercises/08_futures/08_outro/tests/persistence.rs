@@ -0,0 +1,163 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use chrono::Utc;
+use ticket_store::domain::{DomainEvent, EventError};
+use ticket_store::dto::{TicketDraft, TicketPatch};
+use ticket_store::models::{
+    Priority, Project, Ticket, TicketDescription, TicketId, TicketKey, TicketStatus, TicketTitle,
+};
+use ticket_store::persistence::PersistenceError;
+use ticket_store::store::TicketStore;
+
+fn draft(title: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from("説明").unwrap(),
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+#[test]
+fn tickets_are_restored_from_journal() {
+    let data_dir = tempfile::tempdir().unwrap();
+    {
+        let store = TicketStore::create(data_dir.path(), Project::default()).unwrap();
+        store.add_ticket(draft("一つ目")).unwrap();
+        let id = store.add_ticket(draft("二つ目")).unwrap();
+        let patch = TicketPatch {
+            title: None,
+            description: None,
+            status: Some(TicketStatus::Done),
+            priority: None,
+            due_date: None,
+            version: 0,
+        };
+        store.update_ticket(id, patch).unwrap();
+    }
+
+    let store = TicketStore::open(data_dir.path()).unwrap();
+    let ticket = store.get(TicketId(1)).unwrap();
+    assert_eq!(ticket.status, TicketStatus::Done);
+    assert_eq!(ticket.version, 1);
+    assert_eq!(store.add_ticket(draft("三つ目")).unwrap(), TicketId(2));
+}
+
+#[test]
+fn torn_last_record_is_discarded() {
+    let data_dir = tempfile::tempdir().unwrap();
+    {
+        let store = TicketStore::create(data_dir.path(), Project::default()).unwrap();
+        store.add_ticket(draft("一つ目")).unwrap();
+    }
+    let mut events = OpenOptions::new()
+        .append(true)
+        .open(data_dir.path().join("events.jsonl"))
+        .unwrap();
+    events.write_all(br#"{"schema":2,"ticket":1,"#).unwrap();
+
+    let store = TicketStore::open(data_dir.path()).unwrap();
+    assert!(store.get(TicketId(0)).is_ok());
+    assert_eq!(store.add_ticket(draft("二つ目")).unwrap(), TicketId(1));
+    drop(store);
+
+    let store = TicketStore::open(data_dir.path()).unwrap();
+    assert!(store.get(TicketId(1)).is_ok());
+}
+
+#[test]
+fn project_is_required_to_open_store() {
+    let data_dir = tempfile::tempdir().unwrap();
+
+    assert!(matches!(
+        TicketStore::open(data_dir.path()),
+        Err(PersistenceError::MissingProject(_))
+    ));
+    TicketStore::create(data_dir.path(), Project::default()).unwrap();
+    assert!(TicketStore::create(data_dir.path(), Project::default()).is_err());
+    assert!(TicketStore::open(data_dir.path()).is_ok());
+}
+
+#[test]
+fn legacy_journal_is_migrated_to_event_log() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let mut ticket = Ticket::new(
+        TicketId(0),
+        TicketKey::try_from("TICKET-1").unwrap(),
+        TicketTitle::try_from("移行前のチケット").unwrap(),
+        TicketDescription::try_from("説明").unwrap(),
+        Priority::High,
+        None,
+        Utc::now(),
+    );
+    ticket.version = 3;
+    let journal = [
+        serde_json::to_string(&Project::default()).unwrap(),
+        serde_json::to_string(&ticket).unwrap(),
+    ];
+    std::fs::write(
+        data_dir.path().join("journal.jsonl"),
+        journal.join("\n") + "\n",
+    )
+    .unwrap();
+
+    for _ in 0..2 {
+        let store = TicketStore::open(data_dir.path()).unwrap();
+        let restored = store.get(TicketId(0)).unwrap();
+        assert_eq!(restored.title.0, "移行前のチケット");
+        assert_eq!(restored.version, 3);
+        let history = store.history(TicketId(0));
+        assert_eq!(history.len(), 1);
+        assert!(matches!(
+            history[0].event,
+            DomainEvent::TicketImported { .. }
+        ));
+    }
+    let store = TicketStore::open(data_dir.path()).unwrap();
+    assert_eq!(store.add_ticket(draft("二つ目")).unwrap(), TicketId(1));
+}
+
+#[test]
+fn unsupported_event_schema_is_rejected() {
+    let data_dir = tempfile::tempdir().unwrap();
+    TicketStore::create(data_dir.path(), Project::default()).unwrap();
+    std::fs::write(
+        data_dir.path().join("events.jsonl"),
+        r#"{"schema":99,"ticket":1,"version":0,"occurredAt":"2024-01-01T00:00:00Z","event":{}}"#
+            .to_owned()
+            + "\n",
+    )
+    .unwrap();
+
+    assert!(matches!(
+        TicketStore::open(data_dir.path()),
+        Err(PersistenceError::Events(EventError::UnsupportedSchema(99)))
+    ));
+}
+
+#[test]
+fn event_log_survives_checkpoint() {
+    let data_dir = tempfile::tempdir().unwrap();
+    {
+        let mut store = TicketStore::create(data_dir.path(), Project::default()).unwrap();
+        let id = store.add_ticket(draft("一つ目")).unwrap();
+        store.checkpoint().unwrap();
+        let patch = TicketPatch {
+            title: Some(TicketTitle::try_from("チェックポイントの後").unwrap()),
+            description: None,
+            status: None,
+            priority: None,
+            due_date: None,
+            version: 0,
+        };
+        store.update_ticket(id, patch).unwrap();
+    }
+
+    let mut store = TicketStore::open(data_dir.path()).unwrap();
+    assert_eq!(store.history(TicketId(0)).len(), 2);
+    store.rebuild_projections().unwrap();
+    let ticket = store.get(TicketId(0)).unwrap();
+    assert_eq!(ticket.title.0, "チェックポイントの後");
+    assert_eq!(ticket.version, 1);
+}
@@ -0,0 +1,522 @@
+use std::sync::{Arc, RwLock};
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use http_body_util::BodyExt;
+use ticket_store::actor::StoreHandle;
+use ticket_store::blobs::BlobStore;
+use ticket_store::clock::SystemClock;
+use ticket_store::config::Config;
+use ticket_store::crypto::Encryption;
+use ticket_store::dto::{ProjectDraft, ProjectPatch, TicketDraft, TicketPatch};
+use ticket_store::events::ChangeFeed;
+use ticket_store::health::Health;
+use ticket_store::models::{
+    DescriptionFormat, Permissions, Priority, ProjectKey, ProjectName, StatusTransition,
+    TicketDescription, TicketId, TicketKey, TicketRef, TicketStatus, TicketTitle, Workflow,
+};
+use ticket_store::registry::{self, ProjectLookup, ProjectRegistry, ProjectStore};
+use ticket_store::saved_queries::SavedQueries;
+use ticket_store::server::{app, AppState};
+use ticket_store::store::{Access, TicketLookup, TicketStoreError};
+use ticket_store::webhooks::Webhooks;
+use tower::ServiceExt;
+
+fn key(s: &str) -> ProjectKey {
+    ProjectKey::try_from(s).unwrap()
+}
+
+fn ticket_key(s: &str) -> TicketKey {
+    TicketKey::try_from(s).unwrap()
+}
+
+fn project(project_key: &str) -> ProjectDraft {
+    ProjectDraft {
+        key: key(project_key),
+        name: ProjectName::try_from("プロジェクト").unwrap(),
+        workflow: Workflow::default(),
+        permissions: Permissions::default(),
+        description_format: DescriptionFormat::default(),
+    }
+}
+
+fn draft(title: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from("説明").unwrap(),
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+fn rename(project_key: &str) -> ProjectPatch {
+    ProjectPatch {
+        key: Some(key(project_key)),
+        ..ProjectPatch::default()
+    }
+}
+
+fn status(status: TicketStatus, version: u64) -> TicketPatch {
+    TicketPatch {
+        title: None,
+        description: None,
+        status: Some(status),
+        priority: None,
+        due_date: None,
+        version,
+    }
+}
+
+fn store(registry: &ProjectRegistry, project_key: &str) -> ProjectStore {
+    registry.resolve(&key(project_key)).unwrap()
+}
+
+fn add(store: &ProjectStore, title: &str) -> TicketId {
+    store.write().unwrap().add_ticket(draft(title)).unwrap()
+}
+
+#[test]
+fn keys_are_parsed_and_validated() {
+    assert_eq!(key("web").as_str(), "WEB");
+    assert!(ProjectKey::try_from("W").is_err());
+    assert!(ProjectKey::try_from("1WEB").is_err());
+    assert!(ProjectKey::try_from("WEB-1").is_err());
+
+    let parsed = ticket_key("web-42");
+    assert_eq!(parsed.project, key("WEB"));
+    assert_eq!(parsed.number, 42);
+    assert_eq!(parsed.to_string(), "WEB-42");
+    assert!(TicketKey::try_from("WEB-0").is_err());
+    assert!(TicketKey::try_from("WEB-042").is_err());
+    assert!(TicketKey::try_from("WEB").is_err());
+
+    assert_eq!(
+        TicketRef::try_from("7").unwrap(),
+        TicketRef::Id(TicketId(7))
+    );
+    assert_eq!(
+        TicketRef::try_from("WEB-7").unwrap(),
+        TicketRef::Key(ticket_key("WEB-7"))
+    );
+    assert!(TicketRef::try_from("WEB 7").is_err());
+}
+
+#[test]
+fn ticket_ids_are_allocated_per_project() {
+    let mut registry = ProjectRegistry::default();
+    registry.create(project("WEB")).unwrap();
+    registry.create(project("API")).unwrap();
+    let web = store(&registry, "WEB");
+    let api = store(&registry, "API");
+
+    assert_eq!(add(&web, "一つ目"), TicketId(0));
+    assert_eq!(add(&api, "一つ目"), TicketId(0));
+    assert_eq!(add(&web, "二つ目"), TicketId(1));
+
+    let web = web.read().unwrap();
+    assert_eq!(web.get(TicketId(1)).unwrap().key, ticket_key("WEB-2"));
+    assert_eq!(
+        web.resolve(&TicketRef::Key(ticket_key("WEB-2"))).unwrap(),
+        TicketLookup::Found(TicketId(1))
+    );
+    assert!(matches!(
+        web.resolve(&TicketRef::Key(ticket_key("API-1"))),
+        Err(TicketStoreError::NotFound)
+    ));
+
+    let keys: Vec<_> = registry
+        .projects(None)
+        .into_iter()
+        .map(|p| p.key.to_string())
+        .collect();
+    assert_eq!(keys, vec!["API", "TICKET", "WEB"]);
+    assert!(matches!(
+        registry.create(project("WEB")),
+        Err(TicketStoreError::ProjectKeyConflict)
+    ));
+}
+
+#[test]
+fn writes_in_one_project_do_not_block_another() {
+    let mut registry = ProjectRegistry::default();
+    registry.create(project("WEB")).unwrap();
+    registry.create(project("API")).unwrap();
+    let state = Arc::new(RwLock::new(registry));
+
+    let web = state.read().unwrap().resolve(&key("WEB")).unwrap();
+    let _web_guard = web.write().unwrap();
+
+    let api = state.read().unwrap().resolve(&key("API")).unwrap();
+    let api = api.try_write().unwrap();
+    assert_eq!(api.add_ticket(draft("一つ目")).unwrap(), TicketId(0));
+}
+
+#[test]
+fn renamed_project_keeps_old_keys_resolvable() {
+    let mut registry = ProjectRegistry::default();
+    registry.create(project("WEB")).unwrap();
+    let web = store(&registry, "WEB");
+    let id = add(&web, "一つ目");
+
+    assert_eq!(
+        registry.update(&key("WEB"), rename("SITE")).unwrap(),
+        key("SITE")
+    );
+    assert!(matches!(
+        registry.lookup(&key("WEB")).unwrap(),
+        ProjectLookup::Renamed(current) if current == key("SITE")
+    ));
+    {
+        let site = web.read().unwrap();
+        assert_eq!(site.get(id).unwrap().key, ticket_key("SITE-1"));
+        assert_eq!(
+            site.resolve(&TicketRef::Key(ticket_key("WEB-1"))).unwrap(),
+            TicketLookup::Moved(ticket_key("SITE-1"))
+        );
+    }
+
+    // 変更前のプロジェクトキーは、他のプロジェクトに使用させない。
+    assert!(matches!(
+        registry.create(project("WEB")),
+        Err(TicketStoreError::ProjectKeyConflict)
+    ));
+    assert!(matches!(
+        registry.update(&key("SITE"), rename("TICKET")),
+        Err(TicketStoreError::ProjectKeyConflict)
+    ));
+
+    registry.update(&key("SITE"), rename("PORTAL")).unwrap();
+    assert!(matches!(
+        registry.lookup(&key("WEB")).unwrap(),
+        ProjectLookup::Renamed(current) if current == key("PORTAL")
+    ));
+
+    // 自身の変更前のプロジェクトキーには戻せる。
+    registry.update(&key("PORTAL"), rename("WEB")).unwrap();
+    assert!(matches!(
+        registry.lookup(&key("WEB")).unwrap(),
+        ProjectLookup::Found(_)
+    ));
+    assert_eq!(
+        web.read().unwrap().project().previous_keys,
+        vec![key("SITE"), key("PORTAL")]
+    );
+}
+
+#[test]
+fn moved_ticket_keeps_its_history() {
+    let mut registry = ProjectRegistry::default();
+    registry.create(project("WEB")).unwrap();
+    registry.create(project("API")).unwrap();
+    let web = store(&registry, "WEB");
+    let api = store(&registry, "API");
+    add(&api, "一つ目");
+    let id = add(&web, "移動するチケット");
+    web.write()
+        .unwrap()
+        .update_ticket(id, status(TicketStatus::InProgress, 0))
+        .unwrap();
+    let created_at = web.read().unwrap().get(id).unwrap().created_at;
+
+    let moved_to = registry::move_ticket(&web, id, &api, None).unwrap();
+    assert_eq!(moved_to, ticket_key("API-2"));
+
+    let api = api.read().unwrap();
+    let ticket = api.get(TicketId(1)).unwrap();
+    assert_eq!(ticket.title.0, "移動するチケット");
+    assert_eq!(ticket.status, TicketStatus::InProgress);
+    assert_eq!(ticket.created_at, created_at);
+    assert_eq!(ticket.version, 2);
+    assert_eq!(ticket.previous_keys, vec![ticket_key("WEB-1")]);
+
+    let web = web.write().unwrap();
+    assert!(web.get(id).is_err());
+    assert_eq!(
+        web.resolve(&TicketRef::Key(ticket_key("WEB-1"))).unwrap(),
+        TicketLookup::Moved(ticket_key("API-2"))
+    );
+    // 移動したチケットのチケットIDは再利用しない。
+    assert_eq!(web.add_ticket(draft("二つ目")).unwrap(), TicketId(1));
+}
+
+#[test]
+fn workflow_and_permissions_are_enforced_per_project() {
+    let mut registry = ProjectRegistry::default();
+    registry
+        .create(ProjectDraft {
+            workflow: Workflow {
+                transitions: vec![
+                    StatusTransition {
+                        from: TicketStatus::ToDo,
+                        to: TicketStatus::InProgress,
+                    },
+                    StatusTransition {
+                        from: TicketStatus::InProgress,
+                        to: TicketStatus::Done,
+                    },
+                ],
+            },
+            permissions: Permissions {
+                readers: vec!["reader".into()],
+                writers: vec!["writer".into()],
+            },
+            ..project("WEB")
+        })
+        .unwrap();
+    let web = store(&registry, "WEB");
+    let id = add(&web, "一つ目");
+
+    let web = web.write().unwrap();
+    assert!(matches!(
+        web.update_ticket(id, status(TicketStatus::Done, 0)),
+        Err(TicketStoreError::TransitionNotAllowed { .. })
+    ));
+    web.update_ticket(id, status(TicketStatus::InProgress, 0))
+        .unwrap();
+    web.update_ticket(id, status(TicketStatus::Done, 1))
+        .unwrap();
+
+    assert!(web.authorize(Some("reader"), Access::Read).is_ok());
+    assert!(web.authorize(Some("writer"), Access::Read).is_ok());
+    assert!(web.authorize(Some("writer"), Access::Write).is_ok());
+    assert!(matches!(
+        web.authorize(Some("reader"), Access::Write),
+        Err(TicketStoreError::Forbidden)
+    ));
+    assert!(matches!(
+        web.authorize(None, Access::Read),
+        Err(TicketStoreError::Forbidden)
+    ));
+    drop(web);
+    assert_eq!(registry.projects(None).len(), 1);
+    assert_eq!(registry.projects(Some("reader")).len(), 2);
+}
+
+#[test]
+fn projects_are_restored_from_data_dir() {
+    let data_dir = tempfile::tempdir().unwrap();
+    {
+        let mut registry = ProjectRegistry::open(data_dir.path(), Arc::new(SystemClock)).unwrap();
+        registry.create(project("WEB")).unwrap();
+        registry.create(project("TMP")).unwrap();
+        let web = store(&registry, "WEB");
+        add(&web, "一つ目");
+        add(&web, "二つ目");
+        registry.update(&key("WEB"), rename("SITE")).unwrap();
+        let default = store(&registry, "TICKET");
+        registry::move_ticket(&web, TicketId(1), &default, None).unwrap();
+        registry.delete(&key("TMP")).unwrap();
+        assert!(matches!(
+            registry.delete(&key("SITE")),
+            Err(TicketStoreError::ProjectNotEmpty)
+        ));
+    }
+
+    let registry = ProjectRegistry::open(data_dir.path(), Arc::new(SystemClock)).unwrap();
+    let keys: Vec<_> = registry
+        .projects(None)
+        .into_iter()
+        .map(|p| p.key.to_string())
+        .collect();
+    assert_eq!(keys, vec!["SITE", "TICKET"]);
+    assert!(matches!(
+        registry.lookup(&key("WEB")).unwrap(),
+        ProjectLookup::Renamed(current) if current == key("SITE")
+    ));
+
+    let site = store(&registry, "SITE");
+    let site = site.read().unwrap();
+    assert_eq!(site.get(TicketId(0)).unwrap().key, ticket_key("SITE-1"));
+    assert_eq!(
+        site.resolve(&TicketRef::Id(TicketId(1))).unwrap(),
+        TicketLookup::Moved(ticket_key("TICKET-1"))
+    );
+    let default = store(&registry, "TICKET");
+    let default = default.read().unwrap();
+    assert_eq!(
+        default.get(TicketId(0)).unwrap().previous_keys,
+        vec![ticket_key("SITE-2")]
+    );
+}
+
+async fn request(
+    router: &Router,
+    method: &str,
+    uri: &str,
+    user: Option<&str>,
+    body: Option<&str>,
+) -> (StatusCode, Option<String>, serde_json::Value) {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(user) = user {
+        builder = builder.header("x-user", user);
+    }
+    let request = builder
+        .body(Body::from(body.unwrap_or_default().to_string()))
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let location = response
+        .headers()
+        .get(header::LOCATION)
+        .map(|value| value.to_str().unwrap().to_string());
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = serde_json::from_slice(&body).unwrap_or_default();
+    (status, location, body)
+}
+
+#[tokio::test]
+async fn project_routes_resolve_keys_and_redirect_moved_tickets() {
+    let config = Config::default();
+    let state = AppState {
+        store: StoreHandle::spawn(ProjectRegistry::default(), 1024),
+        health: Arc::new(Health::new(&config)),
+        webhooks: Webhooks::in_memory(&config.webhooks, Arc::new(SystemClock)),
+        saved_queries: SavedQueries::in_memory(Arc::new(SystemClock)),
+        feed: ChangeFeed::default(),
+        blobs: BlobStore::in_memory(&config.attachments),
+        encryption: Encryption::default(),
+        admin: None,
+    };
+    let router = app(state, &config.limits);
+    let ticket = r#"{"title": "題名", "description": "説明"}"#;
+
+    let (status, _, body) = request(
+        &router,
+        "POST",
+        "/projects",
+        None,
+        Some(r#"{"key": "web", "name": "ウェブサイト", "permissions": {"writers": ["alice"]}}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["key"], "WEB");
+
+    let (status, _, _) =
+        request(&router, "POST", "/projects/WEB/tickets", None, Some(ticket)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _, body) = request(
+        &router,
+        "POST",
+        "/projects/WEB/tickets",
+        Some("alice"),
+        Some(ticket),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["id"], 0);
+    assert_eq!(body["key"], "WEB-1");
+
+    for uri in ["/projects/WEB/tickets/0", "/projects/web/tickets/web-1"] {
+        let (status, _, body) = request(&router, "GET", uri, None, None).await;
+        assert_eq!(status, StatusCode::OK, "{uri}");
+        assert_eq!(body["key"], "WEB-1");
+    }
+    let (_, location, _) = request(&router, "GET", "/tickets/WEB-1", None, None).await;
+    assert_eq!(location.as_deref(), Some("/projects/WEB/tickets/WEB-1"));
+
+    let (status, _, _) = request(
+        &router,
+        "PATCH",
+        "/projects/WEB",
+        Some("alice"),
+        Some(r#"{"key": "SITE"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, location, _) =
+        request(&router, "GET", "/projects/WEB/tickets?sort=id", None, None).await;
+    assert_eq!(status, StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(location.as_deref(), Some("/projects/SITE/tickets?sort=id"));
+    let (_, location, _) =
+        request(&router, "GET", "/projects/SITE/tickets/WEB-1", None, None).await;
+    assert_eq!(location.as_deref(), Some("/projects/SITE/tickets/SITE-1"));
+    let (_, location, _) = request(&router, "GET", "/tickets/WEB-1", None, None).await;
+    assert_eq!(location.as_deref(), Some("/projects/SITE/tickets/SITE-1"));
+
+    let (status, _, body) = request(
+        &router,
+        "POST",
+        "/projects/SITE/tickets/SITE-1/move",
+        Some("alice"),
+        Some(r#"{"project": "TICKET"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["key"], "TICKET-1");
+    let (status, location, _) = request(
+        &router,
+        "PATCH",
+        "/projects/SITE/tickets/0",
+        Some("alice"),
+        Some(r#"{"status": "Done", "version": 1}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(
+        location.as_deref(),
+        Some("/projects/TICKET/tickets/TICKET-1")
+    );
+
+    let (status, _, _) = request(&router, "DELETE", "/projects/SITE", None, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _, _) = request(&router, "DELETE", "/projects/SITE", Some("alice"), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _, _) = request(&router, "GET", "/projects/SITE", None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn ticket_routes_without_project_use_the_default_project() {
+    let config = Config::default();
+    let state = AppState {
+        store: StoreHandle::spawn(ProjectRegistry::default(), 1024),
+        health: Arc::new(Health::new(&config)),
+        webhooks: Webhooks::in_memory(&config.webhooks, Arc::new(SystemClock)),
+        saved_queries: SavedQueries::in_memory(Arc::new(SystemClock)),
+        feed: ChangeFeed::default(),
+        blobs: BlobStore::in_memory(&config.attachments),
+        encryption: Encryption::default(),
+        admin: None,
+    };
+    let router = app(state, &config.limits);
+
+    let (status, _, body) = request(
+        &router,
+        "POST",
+        "/tickets",
+        None,
+        Some(r#"{"title": "題名", "description": "説明"}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["id"], 0);
+    assert_eq!(body["key"], "TICKET-1");
+
+    let (status, _, body) = request(&router, "GET", "/tickets/0", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["key"], "TICKET-1");
+    let (status, _, _) = request(
+        &router,
+        "PATCH",
+        "/tickets/0",
+        None,
+        Some(r#"{"status": "InProgress", "version": 0}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, _, body) = request(&router, "GET", "/tickets/0", None, None).await;
+    assert_eq!(body["status"], "InProgress");
+    let (status, _, _) = request(&router, "GET", "/tickets/1", None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, location, _) = request(&router, "GET", "/tickets/TICKET-1", None, None).await;
+    assert_eq!(status, StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(
+        location.as_deref(),
+        Some("/projects/TICKET/tickets/TICKET-1")
+    );
+}
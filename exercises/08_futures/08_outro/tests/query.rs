@@ -0,0 +1,437 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use chrono::NaiveDate;
+use http_body_util::BodyExt;
+use ticket_store::actor::StoreHandle;
+use ticket_store::blobs::BlobStore;
+use ticket_store::clock::SystemClock;
+use ticket_store::config::Config;
+use ticket_store::crypto::Encryption;
+use ticket_store::dto::{ProjectDraft, SavedQueryDraft, TicketDraft, TicketPatch};
+use ticket_store::events::ChangeFeed;
+use ticket_store::health::Health;
+use ticket_store::models::{
+    DescriptionFormat, Label, Permissions, Priority, ProjectKey, ProjectName, TicketDescription,
+    TicketId, TicketStatus, TicketTitle, Workflow,
+};
+use ticket_store::query::{self, QueryErrorKind, Span};
+use ticket_store::registry::ProjectRegistry;
+use ticket_store::saved_queries::{SavedQueries, SavedQueryError, SavedQueryName};
+use ticket_store::server::{app, AppState};
+use ticket_store::store::TicketStore;
+use ticket_store::webhooks::Webhooks;
+use tower::ServiceExt;
+
+fn draft(title: &str, priority: Priority, due_date: Option<NaiveDate>) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from("説明").unwrap(),
+        priority,
+        due_date,
+    }
+}
+
+fn start(store: &mut TicketStore, id: u64) {
+    let version = store.get(TicketId(id)).unwrap().version;
+    store
+        .update_ticket(
+            TicketId(id),
+            TicketPatch {
+                title: None,
+                description: None,
+                status: Some(TicketStatus::InProgress),
+                priority: None,
+                due_date: None,
+                version,
+            },
+        )
+        .unwrap();
+}
+
+/// クエリの条件を満たすチケットのチケットIDを返す。
+fn run(store: &TicketStore, query: &str) -> Vec<u64> {
+    let expr = query::parse(query).unwrap();
+    store.query(&expr).iter().map(|t| t.id.0).collect()
+}
+
+/// クエリを解析したときのエラーの種類と範囲を返す。
+fn parse_error(query: &str) -> (QueryErrorKind, Span) {
+    let e = query::parse(query).unwrap_err();
+    (e.kind, e.span)
+}
+
+#[test]
+fn parse_errors_point_at_the_offending_text() {
+    let query = r#"status:InProgress AND (title~"login" OR assignee:alice)"#;
+    let start = query.find("assignee").unwrap();
+    assert_eq!(
+        parse_error(query),
+        (
+            QueryErrorKind::UnknownField("assignee".into()),
+            Span {
+                start,
+                end: start + 8
+            }
+        )
+    );
+    assert!(query::parse(query)
+        .unwrap_err()
+        .to_string()
+        .starts_with("41文字目: `assignee`は不明なフィールドです。"));
+
+    assert_eq!(
+        parse_error("status<Done"),
+        (
+            QueryErrorKind::UnsupportedOperator {
+                field: "status",
+                operator: "<"
+            },
+            Span { start: 6, end: 7 }
+        )
+    );
+    assert_eq!(
+        parse_error("label~bug"),
+        (
+            QueryErrorKind::UnsupportedOperator {
+                field: "label",
+                operator: "~"
+            },
+            Span { start: 5, end: 6 }
+        )
+    );
+    assert!(matches!(
+        parse_error("label:バグ"),
+        (
+            QueryErrorKind::InvalidValue { field: "label", .. },
+            Span { start: 6, end: 8 }
+        )
+    ));
+    assert!(matches!(
+        parse_error("priority>=Huge"),
+        (
+            QueryErrorKind::InvalidValue {
+                field: "priority",
+                ..
+            },
+            Span { start: 10, end: 14 }
+        )
+    ));
+    assert_eq!(
+        parse_error(r#"title~"login"#),
+        (
+            QueryErrorKind::UnterminatedString,
+            Span { start: 6, end: 12 }
+        )
+    );
+    assert_eq!(
+        parse_error("(status:Done"),
+        (
+            QueryErrorKind::UnexpectedEnd("`)`"),
+            Span { start: 12, end: 12 }
+        )
+    );
+    assert_eq!(
+        parse_error("status:Done status:ToDo"),
+        (
+            QueryErrorKind::Unexpected {
+                expected: "`AND`または`OR`",
+                found: "status".into()
+            },
+            Span { start: 12, end: 18 }
+        )
+    );
+    assert_eq!(
+        parse_error("version>"),
+        (
+            QueryErrorKind::UnexpectedEnd("値"),
+            Span { start: 8, end: 8 }
+        )
+    );
+    assert_eq!(parse_error("  ").0, QueryErrorKind::Empty);
+    // 位置は文字単位で数える。
+    assert_eq!(
+        parse_error("title~猫 AND 担当:alice"),
+        (
+            QueryErrorKind::UnknownField("担当".into()),
+            Span { start: 12, end: 14 }
+        )
+    );
+    assert_eq!(
+        parse_error(&format!("{}status:Done", "NOT ".repeat(40))).0,
+        QueryErrorKind::TooDeep
+    );
+}
+
+#[test]
+fn queries_combine_typed_predicates() {
+    let due = NaiveDate::from_ymd_opt(2024, 7, 31).unwrap();
+    let mut store = TicketStore::default();
+    store
+        .add_ticket(draft("Login page is slow", Priority::Low, None))
+        .unwrap();
+    store
+        .add_ticket(draft("Fix login redirect", Priority::Medium, Some(due)))
+        .unwrap();
+    store
+        .add_ticket(draft("Outage in 決済", Priority::Urgent, None))
+        .unwrap();
+    store
+        .add_ticket(draft("Update the docs", Priority::High, Some(due)))
+        .unwrap();
+    for id in [0, 1, 2] {
+        start(&mut store, id);
+    }
+    start(&mut store, 1);
+    store.set_parent(TicketId(3), Some(TicketId(2))).unwrap();
+    let bug = Label::try_from("bug").unwrap();
+    store.add_label(TicketId(2), bug.clone()).unwrap();
+    store.add_label(TicketId(3), bug.clone()).unwrap();
+    store
+        .add_label(TicketId(2), Label::try_from("Payments").unwrap())
+        .unwrap();
+
+    assert_eq!(
+        run(
+            &store,
+            r#"status:InProgress AND (title~"login" OR priority>=High) AND version>0"#
+        ),
+        vec![0, 1, 2]
+    );
+    assert_eq!(run(&store, "title~login AND version>=2"), vec![1]);
+    assert_eq!(run(&store, "NOT status:InProgress"), vec![3]);
+    assert_eq!(run(&store, "status!=todo AND priority<=medium"), vec![0, 1]);
+    assert_eq!(run(&store, "due:none"), vec![0, 2]);
+    assert_eq!(run(&store, "due<2024-08-01"), vec![1, 3]);
+    assert_eq!(run(&store, "due>2024-07-31 OR due:2024-07-31"), vec![1, 3]);
+    assert_eq!(run(&store, "parent:2 OR parent!=none"), vec![3]);
+    assert_eq!(run(&store, "title~決済"), vec![2]);
+    assert_eq!(run(&store, "project:TICKET AND id>1"), vec![2, 3]);
+    assert_eq!(run(&store, "project:WEB"), Vec::<u64>::new());
+    assert_eq!(run(&store, "id!=0 AND id<=2"), vec![1, 2]);
+    assert_eq!(run(&store, "created>=2000-01-01"), vec![0, 1, 2, 3]);
+    assert_eq!(
+        run(
+            &store,
+            r#"status:InProgress AND (title~"login" OR label:bug)"#
+        ),
+        vec![0, 1, 2]
+    );
+    assert_eq!(run(&store, "label:bug AND labels=payments"), vec![2]);
+    assert_eq!(run(&store, "label!=bug"), vec![0, 1]);
+    assert_eq!(run(&store, "NOT label:bug"), vec![0, 1]);
+    // 索引を使用する場合と使用しない場合で、結果が変わらない。
+    for query in [
+        "title~login",
+        "text~\"Fix login\"",
+        "description~説明 AND id:3",
+    ] {
+        assert_eq!(
+            run(&store, query),
+            run(&store, &format!("NOT NOT ({query})")),
+            "{query}"
+        );
+    }
+}
+
+#[test]
+fn saved_queries_are_per_user_and_persisted() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let name = SavedQueryName::try_from("進行中").unwrap();
+    {
+        let saved = SavedQueries::open(data_dir.path(), Arc::new(SystemClock)).unwrap();
+        assert!(matches!(
+            saved.save(
+                None,
+                name.clone(),
+                SavedQueryDraft {
+                    query: "status:Done".into()
+                }
+            ),
+            Err(SavedQueryError::Anonymous)
+        ));
+        assert!(matches!(
+            saved.save(
+                Some("alice"),
+                name.clone(),
+                SavedQueryDraft {
+                    query: "status:Doing".into()
+                }
+            ),
+            Err(SavedQueryError::Invalid(_))
+        ));
+        saved
+            .save(
+                Some("alice"),
+                name.clone(),
+                SavedQueryDraft {
+                    query: "status:InProgress".into(),
+                },
+            )
+            .unwrap();
+    }
+
+    let saved = SavedQueries::open(data_dir.path(), Arc::new(SystemClock)).unwrap();
+    assert_eq!(
+        saved.get(Some("alice"), &name).unwrap().query,
+        "status:InProgress"
+    );
+    assert!(matches!(
+        saved.get(Some("bob"), &name),
+        Err(SavedQueryError::NotFound)
+    ));
+    assert!(saved.list(Some("bob")).unwrap().is_empty());
+    saved.delete(Some("alice"), &name).unwrap();
+    assert!(saved.list(Some("alice")).unwrap().is_empty());
+    assert!(SavedQueryName::try_from("a/b").is_err());
+}
+
+async fn request(
+    router: &Router,
+    method: &str,
+    uri: &str,
+    user: Option<&str>,
+    body: &str,
+) -> (StatusCode, serde_json::Value) {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(user) = user {
+        request = request.header("x-user", user);
+    }
+    let response = router
+        .clone()
+        .oneshot(request.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body = serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+    (status, body)
+}
+
+fn keys(body: &serde_json::Value) -> Vec<&str> {
+    body.as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["key"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn query_routes_filter_tickets_and_manage_saved_queries() {
+    let config = Config::default();
+    let mut registry = ProjectRegistry::default();
+    registry
+        .create(ProjectDraft {
+            key: ProjectKey::try_from("HR").unwrap(),
+            name: ProjectName::try_from("人事").unwrap(),
+            workflow: Workflow::default(),
+            permissions: Permissions {
+                readers: vec!["alice".into()],
+                writers: vec![],
+            },
+            description_format: DescriptionFormat::default(),
+        })
+        .unwrap();
+    for (project, title) in [("TICKET", "Login fails"), ("HR", "Login audit")] {
+        let store = registry
+            .resolve(&ProjectKey::try_from(project).unwrap())
+            .unwrap();
+        let mut store = store.write().unwrap();
+        store
+            .add_ticket(draft(title, Priority::High, None))
+            .unwrap();
+        start(&mut store, 0);
+    }
+    let state = AppState {
+        store: StoreHandle::spawn(registry, 1024),
+        health: Arc::new(Health::new(&config)),
+        webhooks: Webhooks::in_memory(&config.webhooks, Arc::new(SystemClock)),
+        saved_queries: SavedQueries::in_memory(Arc::new(SystemClock)),
+        feed: ChangeFeed::default(),
+        blobs: BlobStore::in_memory(&config.attachments),
+        encryption: Encryption::default(),
+        admin: None,
+    };
+    let router = app(state, &config.limits);
+
+    let uri = "/tickets?q=status%3AInProgress%20AND%20title~login";
+    let (status, body) = request(&router, "GET", uri, Some("alice"), "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(keys(&body), vec!["HR-1", "TICKET-1"]);
+    let (_, body) = request(&router, "GET", uri, None, "").await;
+    assert_eq!(keys(&body), vec!["TICKET-1"]);
+
+    let label = "/projects/TICKET/tickets/0/labels/Bug";
+    let (status, _) = request(&router, "PUT", label, None, "").await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = request(&router, "GET", "/projects/TICKET/tickets/0", None, "").await;
+    assert_eq!(body["labels"], serde_json::json!(["bug"]));
+    let (status, _) = request(
+        &router,
+        "PUT",
+        "/projects/TICKET/tickets/0/labels/a%20b",
+        None,
+        "",
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let uri = "/tickets?q=label%3Abug";
+    let (status, body) = request(&router, "GET", uri, Some("alice"), "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(keys(&body), vec!["TICKET-1"]);
+    let (status, _) = request(&router, "DELETE", label, None, "").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = request(&router, "DELETE", label, None, "").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, body) = request(&router, "GET", uri, Some("alice"), "").await;
+    assert!(keys(&body).is_empty());
+
+    let (status, body) = request(&router, "GET", "/tickets?q=assignee%3Aalice", None, "").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["position"], serde_json::json!({"start": 0, "end": 8}));
+    let (status, _) = request(&router, "GET", "/tickets", None, "").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let saved = "/saved-queries/mine";
+    let query = r#"{"query": "title~login AND priority>=High"}"#;
+    let (status, _) = request(&router, "PUT", saved, None, query).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = request(
+        &router,
+        "PUT",
+        saved,
+        Some("alice"),
+        r#"{"query": "title~login AND"}"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["position"]["start"], 15);
+    let (status, body) = request(&router, "PUT", saved, Some("alice"), query).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["name"], "mine");
+
+    let (_, body) = request(&router, "GET", "/saved-queries", Some("alice"), "").await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    let (status, body) = request(
+        &router,
+        "GET",
+        "/saved-queries/mine/tickets",
+        Some("alice"),
+        "",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(keys(&body), vec!["HR-1", "TICKET-1"]);
+    let (status, _) = request(&router, "GET", "/saved-queries/mine", Some("bob"), "").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = request(&router, "DELETE", saved, Some("alice"), "").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = request(&router, "GET", saved, Some("alice"), "").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
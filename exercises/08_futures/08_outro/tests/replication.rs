@@ -0,0 +1,165 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use serde_json::Value;
+use ticket_store::config::{Config, ReplicationRole};
+use ticket_store::server::Server;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+
+use common::{local_config, send, send_with_headers, wait_until_ready};
+
+const DRAFT: &str = r#"{"title": "羅生門", "description": "芥川龍之介の短編"}"#;
+
+/// サーバーを起動して、HTTPと複製のソケットアドレス、停止トリガーを返す。
+async fn start(config: Config) -> (SocketAddr, Option<SocketAddr>, oneshot::Sender<()>) {
+    let server = Server::bind(&config).await.unwrap();
+    let addr = server.local_addr();
+    let replication_addr = server.replication_addr();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    tokio::spawn(server.serve(async {
+        let _ = shutdown_rx.await;
+    }));
+    wait_until_ready(addr).await;
+
+    (addr, replication_addr, shutdown_tx)
+}
+
+async fn start_leader() -> (SocketAddr, SocketAddr, oneshot::Sender<()>) {
+    let mut config = local_config();
+    config.replication.role = ReplicationRole::Leader;
+    config.replication.listen = Some("127.0.0.1:0".parse().unwrap());
+    let (addr, replication_addr, shutdown) = start(config).await;
+
+    (addr, replication_addr.unwrap(), shutdown)
+}
+
+async fn start_follower(
+    leader: SocketAddr,
+    leader_replication: SocketAddr,
+) -> (SocketAddr, oneshot::Sender<()>) {
+    let mut config = local_config();
+    config.replication.role = ReplicationRole::Follower;
+    config.replication.leader_addr = Some(leader_replication);
+    config.replication.leader_url = Some(format!("http://{leader}/"));
+    let (addr, _, shutdown) = start(config).await;
+
+    (addr, shutdown)
+}
+
+/// フォロワーでリクエストが成功するまで待つ。
+async fn wait_for(addr: SocketAddr, path: &str) -> String {
+    for _ in 0..200 {
+        let (status, body) = send(addr, "GET", path, None).await;
+        if status == 200 {
+            return body;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("{path}が複製されませんでした。");
+}
+
+#[tokio::test]
+async fn follower_catches_up_and_serves_reads() {
+    let (leader, leader_replication, _leader_shutdown) = start_leader().await;
+    send(leader, "POST", "/projects/TICKET/tickets", Some(DRAFT)).await;
+    let (follower, _follower_shutdown) = start_follower(leader, leader_replication).await;
+
+    let first = wait_for(follower, "/projects/TICKET/tickets/0").await;
+    assert_eq!(
+        first,
+        send(leader, "GET", "/projects/TICKET/tickets/0", None)
+            .await
+            .1
+    );
+
+    send(
+        leader,
+        "POST",
+        "/projects",
+        Some(r#"{"key": "WEB", "name": "ウェブ"}"#),
+    )
+    .await;
+    send(leader, "POST", "/projects/TICKET/tickets", Some(DRAFT)).await;
+    let second = wait_for(follower, "/projects/TICKET/tickets/1").await;
+    let second: Value = serde_json::from_str(&second).unwrap();
+    assert_eq!(second["key"], "TICKET-2");
+    wait_for(follower, "/projects/WEB").await;
+
+    let (status, headers, _) =
+        send_with_headers(follower, "GET", "/projects/TICKET/tickets/1", None).await;
+    assert_eq!(status, 200);
+    assert!(headers
+        .iter()
+        .any(|(name, value)| name == "x-replication-lag" && value == "0"));
+}
+
+#[tokio::test]
+async fn writes_to_follower_are_redirected_to_leader() {
+    let (leader, leader_replication, _leader_shutdown) = start_leader().await;
+    let (follower, _follower_shutdown) = start_follower(leader, leader_replication).await;
+
+    let (status, headers, _) =
+        send_with_headers(follower, "POST", "/projects/TICKET/tickets", Some(DRAFT)).await;
+    assert_eq!(status, 307);
+    let location = format!("http://{leader}/projects/TICKET/tickets");
+    assert!(headers
+        .iter()
+        .any(|(name, value)| name == "location" && *value == location));
+    assert_eq!(
+        send(follower, "GET", "/projects/TICKET/tickets/0", None)
+            .await
+            .0,
+        404
+    );
+}
+
+/// 複製の購読要求を送信して、応答と指定した数の変更操作を受信する。
+async fn subscribe(addr: SocketAddr, request: Value, count: usize) -> (Value, Vec<Value>) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(format!("{request}\n").as_bytes())
+        .await
+        .unwrap();
+    let mut lines = BufReader::new(stream).lines();
+    let mut messages = vec![];
+    for _ in 0..=count {
+        let line = lines.next_line().await.unwrap().unwrap();
+        messages.push(serde_json::from_str(&line).unwrap());
+    }
+    let hello = messages.remove(0);
+
+    (hello, messages)
+}
+
+#[tokio::test]
+async fn leader_streams_operations_from_requested_offset() {
+    let (leader, leader_replication, _leader_shutdown) = start_leader().await;
+    send(leader, "POST", "/projects/TICKET/tickets", Some(DRAFT)).await;
+    send(leader, "POST", "/projects/TICKET/tickets", Some(DRAFT)).await;
+
+    let (hello, deliveries) = subscribe(
+        leader_replication,
+        serde_json::json!({"epoch": null, "offset": 2}),
+        3,
+    )
+    .await;
+    assert_eq!(hello["offset"], 0);
+    assert_eq!(deliveries[0]["entry"]["operation"]["type"], "Restore");
+    assert_eq!(deliveries[2]["entry"]["operation"]["type"], "CreateTicket");
+    assert_eq!(deliveries[2]["head"], 3);
+
+    let epoch = hello["epoch"].clone();
+    let (hello, deliveries) = subscribe(
+        leader_replication,
+        serde_json::json!({"epoch": epoch, "offset": 2}),
+        1,
+    )
+    .await;
+    assert_eq!(hello["offset"], 2);
+    assert_eq!(deliveries[0]["entry"]["offset"], 2);
+    assert_eq!(deliveries[0]["entry"]["operation"]["project"], "TICKET");
+}
@@ -0,0 +1,300 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use ticket_store::actor::StoreHandle;
+use ticket_store::blobs::BlobStore;
+use ticket_store::clock::{ManualClock, SystemClock};
+use ticket_store::config::Config;
+use ticket_store::crypto::Encryption;
+use ticket_store::dto::{ThroughputBucket, TicketDraft, TicketPatch};
+use ticket_store::events::ChangeFeed;
+use ticket_store::health::Health;
+use ticket_store::models::{
+    Priority, ProjectKey, TicketDescription, TicketId, TicketStatus, TicketTitle,
+};
+use ticket_store::registry::{ProjectRegistry, ProjectStore};
+use ticket_store::reports::{self, ReportError, ReportWindow, StatusTimeline};
+use ticket_store::saved_queries::SavedQueries;
+use ticket_store::server::{app, AppState};
+use ticket_store::webhooks::Webhooks;
+use tower::ServiceExt;
+
+/// 2024年7月の指定した日時（UTC）
+fn at(day: u32, hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 7, day, hour, 0, 0).unwrap()
+}
+
+fn date(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 7, day).unwrap()
+}
+
+fn add(store: &ProjectStore, title: &str) -> TicketId {
+    store
+        .read()
+        .unwrap()
+        .add_ticket(TicketDraft {
+            title: TicketTitle::try_from(title).unwrap(),
+            description: TicketDescription::try_from("説明").unwrap(),
+            priority: Priority::Medium,
+            due_date: None,
+        })
+        .unwrap()
+}
+
+fn transition(store: &ProjectStore, id: TicketId, status: TicketStatus) {
+    let store = store.read().unwrap();
+    let version = store.get(id).unwrap().version;
+    store
+        .update_ticket(
+            id,
+            TicketPatch {
+                title: None,
+                description: None,
+                status: Some(status),
+                priority: None,
+                due_date: None,
+                version,
+            },
+        )
+        .unwrap();
+}
+
+/// 7月15日（月曜日）から17日（水曜日）にかけて、チケットのステータスを遷移させる。
+///
+/// * 1 - 15日の9時から1時間で完了
+/// * 2 - 15日の10時から2日で完了
+/// * 3 - 作業を開始せずに17日に完了
+/// * 4 - 作業中
+/// * 5 - 未着手
+fn record_transitions(registry: &ProjectRegistry, clock: &ManualClock) {
+    let store = registry.resolve(&ProjectKey::default()).unwrap();
+    clock.set(at(15, 9));
+    let ids: Vec<_> = (1..=5).map(|n| add(&store, &format!("{n}"))).collect();
+    transition(&store, ids[0], TicketStatus::InProgress);
+    clock.set(at(15, 10));
+    transition(&store, ids[0], TicketStatus::Done);
+    transition(&store, ids[1], TicketStatus::InProgress);
+    clock.set(at(17, 10));
+    transition(&store, ids[1], TicketStatus::Done);
+    transition(&store, ids[2], TicketStatus::Done);
+    transition(&store, ids[3], TicketStatus::InProgress);
+}
+
+fn router(registry: ProjectRegistry) -> Router {
+    let config = Config::default();
+    let state = AppState {
+        store: StoreHandle::spawn(registry, 1024),
+        health: Arc::new(Health::new(&config)),
+        webhooks: Webhooks::in_memory(&config.webhooks, Arc::new(SystemClock)),
+        saved_queries: SavedQueries::in_memory(Arc::new(SystemClock)),
+        feed: ChangeFeed::default(),
+        blobs: BlobStore::in_memory(&config.attachments),
+        encryption: Encryption::default(),
+        admin: None,
+    };
+    app(state, &config.limits)
+}
+
+async fn get(router: &Router, uri: &str) -> (StatusCode, String, String) {
+    let request = Request::get(uri).body(Body::empty()).unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|value| value.to_str().unwrap().to_string())
+        .unwrap_or_default();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (
+        status,
+        content_type,
+        String::from_utf8(body.to_vec()).unwrap(),
+    )
+}
+
+async fn get_json(router: &Router, uri: &str) -> (StatusCode, Value) {
+    let (status, _, body) = get(router, uri).await;
+    (status, serde_json::from_str(&body).unwrap_or(Value::Null))
+}
+
+#[test]
+fn reopened_tickets_start_again_from_next_in_progress() {
+    let mut timeline = StatusTimeline::default();
+    let id = TicketId(0);
+    timeline.track(id, TicketStatus::ToDo);
+    timeline.transition(id, TicketStatus::InProgress, at(1, 9));
+    timeline.transition(id, TicketStatus::ToDo, at(1, 10));
+    timeline.transition(id, TicketStatus::InProgress, at(1, 11));
+    timeline.transition(id, TicketStatus::Done, at(1, 12));
+    timeline.transition(id, TicketStatus::ToDo, at(2, 9));
+    timeline.transition(id, TicketStatus::Done, at(2, 10));
+    timeline.transition(id, TicketStatus::InProgress, at(3, 9));
+    timeline.transition(id, TicketStatus::Done, at(3, 12));
+
+    let started: Vec<_> = timeline
+        .completions()
+        .iter()
+        .map(|completion| completion.started_at)
+        .collect();
+    assert_eq!(started, vec![Some(at(1, 9)), None, Some(at(3, 9))]);
+
+    let window = ReportWindow::new(Some(date(1)), Some(date(3)), date(3)).unwrap();
+    let cycle_time = reports::cycle_time(&[timeline], window);
+    assert_eq!(cycle_time.count, 2);
+    let seconds: Vec<_> = cycle_time.percentiles.iter().map(|p| p.seconds).collect();
+    assert_eq!(seconds, vec![3 * 3600, 3 * 3600, 3 * 3600, 3 * 3600]);
+}
+
+#[test]
+fn report_windows_are_validated() {
+    let today = date(17);
+    let window = ReportWindow::new(None, None, today).unwrap();
+    assert_eq!(
+        (window.from, window.to),
+        (NaiveDate::from_ymd_opt(2024, 6, 18).unwrap(), today)
+    );
+    assert_eq!(
+        ReportWindow::new(Some(date(17)), Some(date(16)), today),
+        Err(ReportError::InvalidWindow)
+    );
+    let from = NaiveDate::from_ymd_opt(2023, 7, 17).unwrap();
+    assert_eq!(
+        ReportWindow::new(Some(from), Some(today), today),
+        Err(ReportError::WindowTooLong)
+    );
+    assert!(ReportWindow::new(Some(from.succ_opt().unwrap()), Some(today), today).is_ok());
+}
+
+#[tokio::test]
+async fn reports_are_computed_from_status_transitions() {
+    let clock = Arc::new(ManualClock::new(at(15, 9)));
+    let registry = ProjectRegistry::in_memory(clock.clone());
+    record_transitions(&registry, &clock);
+    let router = router(registry);
+
+    let (status, summary) = get_json(&router, "/reports/summary").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        summary,
+        json!({
+            "total": 5,
+            "statuses": [
+                {"status": "ToDo", "count": 1},
+                {"status": "InProgress", "count": 1},
+                {"status": "Done", "count": 3},
+            ],
+        })
+    );
+
+    let (status, cycle_time) = get_json(&router, "/reports/cycle-time?project=TICKET").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cycle_time["from"], "2024-06-18");
+    assert_eq!(cycle_time["to"], "2024-07-17");
+    assert_eq!(cycle_time["count"], 2);
+    assert_eq!(
+        cycle_time["percentiles"],
+        json!([
+            {"percentile": 50, "seconds": 3600},
+            {"percentile": 75, "seconds": 172800},
+            {"percentile": 90, "seconds": 172800},
+            {"percentile": 95, "seconds": 172800},
+        ])
+    );
+
+    let (_, cycle_time) = get_json(&router, "/reports/cycle-time?from=2024-07-16").await;
+    assert_eq!(cycle_time["count"], 1);
+
+    let (status, throughput) =
+        get_json(&router, "/reports/throughput?from=2024-07-15&to=2024-07-17").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(throughput["bucket"], "day");
+    assert_eq!(
+        throughput["periods"],
+        json!([
+            {"start": "2024-07-15", "completed": 1},
+            {"start": "2024-07-16", "completed": 0},
+            {"start": "2024-07-17", "completed": 2},
+        ])
+    );
+
+    let (_, throughput) = get_json(
+        &router,
+        "/reports/throughput?from=2024-07-10&to=2024-07-17&bucket=week",
+    )
+    .await;
+    assert_eq!(
+        throughput["periods"],
+        json!([
+            {"start": "2024-07-08", "completed": 0},
+            {"start": "2024-07-15", "completed": 3},
+        ])
+    );
+}
+
+#[tokio::test]
+async fn reports_are_exported_as_csv() {
+    let clock = Arc::new(ManualClock::new(at(15, 9)));
+    let registry = ProjectRegistry::in_memory(clock.clone());
+    record_transitions(&registry, &clock);
+    let router = router(registry);
+
+    let (status, content_type, csv) = get(&router, "/reports/summary?format=csv").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "text/csv; charset=utf-8");
+    assert_eq!(csv, "status,count\nToDo,1\nInProgress,1\nDone,3\n");
+
+    let (_, _, csv) = get(
+        &router,
+        "/reports/throughput?from=2024-07-16&to=2024-07-17&format=csv",
+    )
+    .await;
+    assert_eq!(csv, "start,completed\n2024-07-16,0\n2024-07-17,2\n");
+}
+
+#[tokio::test]
+async fn invalid_report_requests_are_rejected() {
+    let router = router(ProjectRegistry::default());
+
+    for uri in [
+        "/reports/cycle-time?from=2024-07-17&to=2024-07-16",
+        "/reports/throughput?from=2023-01-01&to=2024-07-17",
+        "/reports/throughput?bucket=month",
+        "/reports/summary?format=xml",
+    ] {
+        let (status, _, _) = get(&router, uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+    }
+    let (status, _, _) = get(&router, "/reports/summary?project=NONE").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn status_timeline_is_restored_from_data_dir() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let clock = Arc::new(ManualClock::new(at(15, 9)));
+    let completions = {
+        let registry = ProjectRegistry::open(data_dir.path(), clock.clone()).unwrap();
+        record_transitions(&registry, &clock);
+        let store = registry.resolve(&ProjectKey::default()).unwrap();
+        let timeline = store.read().unwrap().status_timeline();
+        timeline.completions().to_vec()
+    };
+    assert_eq!(completions.len(), 3);
+
+    let registry = ProjectRegistry::open(data_dir.path(), clock).unwrap();
+    let store = registry.resolve(&ProjectKey::default()).unwrap();
+    let timeline = store.read().unwrap().status_timeline();
+    assert_eq!(timeline.completions(), completions.as_slice());
+    let throughput = reports::throughput(
+        &[timeline],
+        ReportWindow::new(Some(date(15)), Some(date(17)), date(17)).unwrap(),
+        ThroughputBucket::Week,
+    );
+    assert_eq!(throughput.periods.len(), 1);
+    assert_eq!(throughput.periods[0].completed, 3);
+}
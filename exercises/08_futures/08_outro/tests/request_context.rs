@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::routing::get;
+use axum::{middleware, Router};
+use http_body_util::BodyExt;
+use ticket_store::middleware::{request_context, request_timeout, X_REQUEST_ID};
+use tower::ServiceExt;
+
+async fn panicking() -> &'static str {
+    panic!("boom")
+}
+
+fn router() -> Router {
+    Router::new()
+        .route("/ok", get(|| async { "ok" }))
+        .route("/panic", get(panicking))
+        .layer(middleware::from_fn(request_context))
+}
+
+#[tokio::test]
+async fn request_id_is_propagated() {
+    let request = Request::get("/ok")
+        .header(&X_REQUEST_ID, "abc-123")
+        .body(Body::empty())
+        .unwrap();
+    let response = router().oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[&X_REQUEST_ID], "abc-123");
+}
+
+#[tokio::test]
+async fn request_id_is_generated_when_missing_or_invalid() {
+    for header in [None, Some(""), Some("has space")] {
+        let mut builder = Request::get("/ok");
+        if let Some(value) = header {
+            builder = builder.header(&X_REQUEST_ID, value);
+        }
+        let response = router()
+            .oneshot(builder.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let id = response.headers()[&X_REQUEST_ID].to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(id).is_ok());
+    }
+}
+
+#[tokio::test]
+async fn panic_is_returned_as_internal_server_error() {
+    let request = Request::get("/panic")
+        .header(&X_REQUEST_ID, "panic-1")
+        .body(Body::empty())
+        .unwrap();
+    let response = router().oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(response.headers()[&X_REQUEST_ID], "panic-1");
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["requestId"], "panic-1");
+}
+
+#[tokio::test]
+async fn slow_request_is_returned_as_service_unavailable() {
+    let router = Router::new()
+        .route(
+            "/slow",
+            get(|| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                "slow"
+            }),
+        )
+        .layer(middleware::from_fn_with_state(
+            Duration::from_millis(10),
+            request_timeout,
+        ));
+    let response = router
+        .oneshot(Request::get("/slow").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
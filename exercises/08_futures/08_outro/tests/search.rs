@@ -0,0 +1,258 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use http_body_util::BodyExt;
+use ticket_store::actor::StoreHandle;
+use ticket_store::blobs::BlobStore;
+use ticket_store::clock::SystemClock;
+use ticket_store::config::Config;
+use ticket_store::crypto::Encryption;
+use ticket_store::dto::{ProjectDraft, TicketDraft, TicketPatch};
+use ticket_store::events::ChangeFeed;
+use ticket_store::health::Health;
+use ticket_store::models::{
+    DescriptionFormat, Permissions, Priority, Project, ProjectKey, ProjectName, TicketDescription,
+    TicketId, TicketTitle, Workflow,
+};
+use ticket_store::registry::{self, ProjectRegistry};
+use ticket_store::saved_queries::SavedQueries;
+use ticket_store::search::{SearchText, SearchTextError};
+use ticket_store::server::{app, AppState};
+use ticket_store::store::TicketStore;
+use ticket_store::webhooks::Webhooks;
+use tower::ServiceExt;
+
+fn draft(title: &str, description: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from(description).unwrap(),
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+/// 検索結果のチケットIDを返す。
+fn search(store: &TicketStore, text: &str) -> Vec<u64> {
+    let text = SearchText::try_from(text).unwrap();
+    store.search(&text).iter().map(|hit| hit.id.0).collect()
+}
+
+#[test]
+fn english_and_japanese_text_is_searchable() {
+    let store = TicketStore::default();
+    store
+        .add_ticket(draft(
+            "Login fails on Safari",
+            "The session cookie is dropped after redirect.",
+        ))
+        .unwrap();
+    store
+        .add_ticket(draft("吾輩は猫である", "名前はまだ無い。"))
+        .unwrap();
+    store
+        .add_ticket(draft("東京都の猫", "猫の写真を集める"))
+        .unwrap();
+
+    assert_eq!(search(&store, "login"), vec![0]);
+    assert_eq!(search(&store, "ＳＡＦＡＲＩ　Cookie"), vec![0]);
+    assert_eq!(search(&store, "safari chrome"), Vec::<u64>::new());
+    assert_eq!(search(&store, "猫である"), vec![1]);
+    assert_eq!(search(&store, "名前"), vec![1]);
+    assert_eq!(search(&store, "犬"), Vec::<u64>::new());
+    // タイトルと説明の両方に出現するチケットが上位になる。
+    assert_eq!(search(&store, "猫"), vec![2, 1]);
+
+    assert_eq!(
+        SearchText::try_from("  、。!? ").unwrap_err(),
+        SearchTextError::Empty
+    );
+    assert_eq!(
+        SearchText::try_from("a".repeat(201)).unwrap_err(),
+        SearchTextError::TooLong
+    );
+}
+
+#[test]
+fn hits_highlight_matches_in_escaped_snippets() {
+    let store = TicketStore::default();
+    let prefix = "あ".repeat(50);
+    let suffix = "い".repeat(100);
+    store
+        .add_ticket(draft(
+            "<b>猫</b>の一覧",
+            &format!("{prefix}黒猫と<白猫>{suffix}"),
+        ))
+        .unwrap();
+
+    let text = SearchText::try_from("猫").unwrap();
+    let hits = store.search(&text);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].key.to_string(), "TICKET-1");
+    assert_eq!(hits[0].title, "&lt;b&gt;<mark>猫</mark>&lt;/b&gt;の一覧");
+    let snippet = &hits[0].snippet;
+    assert!(snippet.starts_with("…あ"), "{snippet}");
+    assert!(snippet.ends_with("い…"), "{snippet}");
+    assert!(
+        snippet.contains("黒<mark>猫</mark>と&lt;白<mark>猫</mark>&gt;"),
+        "{snippet}"
+    );
+    // 最初に一致した「猫」の前の20文字を含む。
+    assert_eq!(
+        snippet.chars().filter(|&c| c == 'あ').count(),
+        19,
+        "{snippet}"
+    );
+
+    let text = SearchText::try_from("黒猫").unwrap();
+    assert!(store.search(&text)[0]
+        .snippet
+        .contains("<mark>黒猫</mark>と&lt;白猫&gt;"));
+}
+
+#[test]
+fn index_follows_updates_moves_and_restores() {
+    let data_dir = tempfile::tempdir().unwrap();
+    {
+        let store = TicketStore::create(data_dir.path(), Project::default()).unwrap();
+        store
+            .add_ticket(draft("Broken search", "Results are empty"))
+            .unwrap();
+        store
+            .update_ticket(
+                TicketId(0),
+                TicketPatch {
+                    title: Some(TicketTitle::try_from("Slow search").unwrap()),
+                    description: None,
+                    status: None,
+                    priority: None,
+                    due_date: None,
+                    version: 0,
+                },
+            )
+            .unwrap();
+        assert_eq!(search(&store, "broken"), Vec::<u64>::new());
+        assert_eq!(search(&store, "slow"), vec![0]);
+    }
+
+    let store = TicketStore::open(data_dir.path()).unwrap();
+    assert_eq!(search(&store, "slow search"), vec![0]);
+
+    let mut registry = ProjectRegistry::default();
+    registry
+        .create(ProjectDraft {
+            key: ProjectKey::try_from("WEB").unwrap(),
+            name: ProjectName::try_from("ウェブサイト").unwrap(),
+            workflow: Workflow::default(),
+            permissions: Permissions::default(),
+            description_format: DescriptionFormat::default(),
+        })
+        .unwrap();
+    let from = registry.resolve(&ProjectKey::default()).unwrap();
+    let to = registry
+        .resolve(&ProjectKey::try_from("WEB").unwrap())
+        .unwrap();
+    *from.write().unwrap() = store;
+    registry::move_ticket(&from, TicketId(0), &to, None).unwrap();
+
+    assert_eq!(search(&from.read().unwrap(), "slow"), Vec::<u64>::new());
+    let text = SearchText::try_from("slow").unwrap();
+    let hits = registry.search(&text, None, 10);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].key.to_string(), "WEB-1");
+}
+
+async fn get(router: &axum::Router, uri: &str, user: Option<&str>) -> (StatusCode, String) {
+    let mut request = Request::builder().uri(uri);
+    if let Some(user) = user {
+        request = request.header("x-user", user);
+    }
+    let response = router
+        .clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+fn keys(body: &str) -> Vec<String> {
+    let hits: serde_json::Value = serde_json::from_str(body).unwrap();
+    hits.as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["key"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn search_route_ranks_hits_across_readable_projects() {
+    let config = Config::default();
+    let mut registry = ProjectRegistry::default();
+    registry
+        .create(ProjectDraft {
+            key: ProjectKey::try_from("HR").unwrap(),
+            name: ProjectName::try_from("人事").unwrap(),
+            workflow: Workflow::default(),
+            permissions: Permissions {
+                readers: vec!["alice".into()],
+                writers: vec![],
+            },
+            description_format: DescriptionFormat::default(),
+        })
+        .unwrap();
+    for (project, title, description) in [
+        ("TICKET", "請求書の発行", "月末に請求書を発行する"),
+        ("TICKET", "見積書の作成", "請求書の前に見積書を作る"),
+        ("HR", "請求書の承認", "経費の請求書を承認する"),
+    ] {
+        let store = registry
+            .resolve(&ProjectKey::try_from(project).unwrap())
+            .unwrap();
+        store
+            .write()
+            .unwrap()
+            .add_ticket(draft(title, description))
+            .unwrap();
+    }
+    let state = AppState {
+        store: StoreHandle::spawn(registry, 1024),
+        health: Arc::new(Health::new(&config)),
+        webhooks: Webhooks::in_memory(&config.webhooks, Arc::new(SystemClock)),
+        saved_queries: SavedQueries::in_memory(Arc::new(SystemClock)),
+        feed: ChangeFeed::default(),
+        blobs: BlobStore::in_memory(&config.attachments),
+        encryption: Encryption::default(),
+        admin: None,
+    };
+    let router = app(state, &config.limits);
+
+    let (status, body) = get(&router, "/search?q=%E8%AB%8B%E6%B1%82%E6%9B%B8", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(keys(&body), vec!["TICKET-1", "TICKET-2"]);
+    let hits: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(hits[0]["title"], "<mark>請求書</mark>の発行");
+    assert_eq!(hits[0]["project"], "TICKET");
+
+    let (_, body) = get(
+        &router,
+        "/search?q=%E8%AB%8B%E6%B1%82%E6%9B%B8&limit=2",
+        Some("alice"),
+    )
+    .await;
+    assert_eq!(keys(&body).len(), 2);
+    let (_, body) = get(
+        &router,
+        "/search?q=%E8%AB%8B%E6%B1%82%E6%9B%B8&project=HR",
+        Some("alice"),
+    )
+    .await;
+    assert_eq!(keys(&body), vec!["HR-1"]);
+    let (status, _) = get(&router, "/search?q=%E8%AB%8B%E6%B1%82&project=HR", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = get(&router, "/search?q=%20", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("検索語を指定してください。"), "{body}");
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use ticket_store::config::StorageBackend;
+use ticket_store::migrate;
+use ticket_store::models::TicketId;
+use ticket_store::server::{Server, ServerError, Shutdown};
+use ticket_store::store::TicketStore;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+
+use common::{local_config, send, wait_until_ready};
+
+const DRAFT: &str = r#"{"title": "羅生門", "description": "芥川龍之介の短編"}"#;
+
+#[tokio::test]
+async fn server_reports_bound_port_and_drains_on_shutdown() {
+    let server = Server::bind(&local_config()).await.unwrap();
+    let addr = server.local_addr();
+    assert_ne!(addr.port(), 0);
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let handle = tokio::spawn(server.serve(async {
+        let _ = shutdown_rx.await;
+    }));
+
+    let (status, _) = send(addr, "POST", "/tickets", Some(DRAFT)).await;
+    assert_eq!(status, 200);
+
+    shutdown_tx.send(()).unwrap();
+    assert_eq!(handle.await.unwrap().unwrap(), Shutdown::Drained);
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn store_is_checkpointed_on_shutdown() {
+    let data_dir = tempfile::tempdir().unwrap();
+    let mut config = local_config();
+    config.storage.backend = StorageBackend::File;
+    config.storage.data_dir = Some(data_dir.path().into());
+    let server = Server::bind(&config).await.unwrap();
+    let addr = server.local_addr();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let handle = tokio::spawn(server.serve(async {
+        let _ = shutdown_rx.await;
+    }));
+
+    wait_until_ready(addr).await;
+    send(addr, "POST", "/tickets", Some(DRAFT)).await;
+    shutdown_tx.send(()).unwrap();
+    handle.await.unwrap().unwrap();
+
+    let project_dir = data_dir.path().join("projects").join("TICKET");
+    assert!(project_dir.join("snapshot.json").exists());
+    let journal = std::fs::metadata(project_dir.join("journal.jsonl")).unwrap();
+    assert_eq!(journal.len(), 0);
+    let store = TicketStore::open(&project_dir).unwrap();
+    assert!(store.get(TicketId(0)).is_ok());
+}
+
+#[tokio::test]
+async fn unfinished_request_exceeds_shutdown_deadline() {
+    let server = Server::bind(&local_config()).await.unwrap();
+    let addr = server.local_addr();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let handle = tokio::spawn(server.serve(async {
+        let _ = shutdown_rx.await;
+    }));
+
+    // リクエストボディを送り切らずに、リクエストを処理中のままにする。
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"POST /tickets HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: 100\r\n\r\n{")
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    shutdown_tx.send(()).unwrap();
+    assert_eq!(handle.await.unwrap().unwrap(), Shutdown::DeadlineExceeded);
+}
+
+#[tokio::test]
+async fn corrupted_journal_stops_server_without_overwriting_it() {
+    let data_dir = tempfile::tempdir().unwrap();
+    migrate::ensure_current(data_dir.path()).unwrap();
+    let project_dir = data_dir.path().join("projects").join("TICKET");
+    std::fs::create_dir_all(&project_dir).unwrap();
+    let journal = project_dir.join("journal.jsonl");
+    std::fs::write(&journal, "壊れたレコード\n").unwrap();
+    let mut config = local_config();
+    config.storage.backend = StorageBackend::File;
+    config.storage.data_dir = Some(data_dir.path().into());
+
+    let server = Server::bind(&config).await.unwrap();
+    let result = server.serve(std::future::pending()).await;
+
+    assert!(matches!(result, Err(ServerError::Storage(_))));
+    assert_eq!(
+        std::fs::read_to_string(&journal).unwrap(),
+        "壊れたレコード\n"
+    );
+    assert!(!project_dir.join("snapshot.json").exists());
+}
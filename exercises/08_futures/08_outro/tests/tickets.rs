@@ -0,0 +1,405 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use chrono::{NaiveDate, TimeDelta, TimeZone, Utc};
+use http_body_util::BodyExt;
+use ticket_store::actor::StoreHandle;
+use ticket_store::blobs::BlobStore;
+use ticket_store::clock::{Clock, ManualClock, SystemClock};
+use ticket_store::config::Config;
+use ticket_store::crypto::Encryption;
+use ticket_store::domain::{self, DomainEvent, Projection, RecordedEvent};
+use ticket_store::dto::{DueFilter, TicketDraft, TicketPatch, TicketQuery, TicketSort};
+use ticket_store::events::ChangeFeed;
+use ticket_store::health::Health;
+use ticket_store::models::{
+    Priority, ProjectKey, Ticket, TicketDescription, TicketStatus, TicketTitle,
+};
+use ticket_store::registry::ProjectRegistry;
+use ticket_store::saved_queries::SavedQueries;
+use ticket_store::server::{app, AppState};
+use ticket_store::store::TicketStore;
+use ticket_store::webhooks::Webhooks;
+use tower::ServiceExt;
+
+/// 2024年7月17日（水曜日）の正午を指す時計
+fn clock() -> Arc<ManualClock> {
+    Arc::new(ManualClock::new(
+        Utc.with_ymd_and_hms(2024, 7, 17, 12, 0, 0).unwrap(),
+    ))
+}
+
+fn date(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 7, day).unwrap()
+}
+
+fn draft(title: &str, priority: Priority, due_date: Option<NaiveDate>) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from("説明").unwrap(),
+        priority,
+        due_date,
+    }
+}
+
+fn patch(version: u64) -> TicketPatch {
+    TicketPatch {
+        title: None,
+        description: None,
+        status: None,
+        priority: None,
+        due_date: None,
+        version,
+    }
+}
+
+fn ids(store: &TicketStore, query: &TicketQuery) -> Vec<u64> {
+    store.list(query).iter().map(|t| t.id.0).collect()
+}
+
+#[test]
+fn timestamps_are_assigned_by_store_clock() {
+    let clock = clock();
+    let store = TicketStore::default().with_clock(clock.clone());
+    let created_at = clock.now();
+    let id = store
+        .add_ticket(draft("一つ目", Priority::High, None))
+        .unwrap();
+
+    clock.advance(TimeDelta::hours(1));
+    store
+        .update_ticket(
+            id,
+            TicketPatch {
+                priority: Some(Priority::Low),
+                due_date: Some(Some(date(20))),
+                ..patch(0)
+            },
+        )
+        .unwrap();
+
+    let ticket = store.get(id).unwrap();
+    assert_eq!(ticket.created_at, created_at);
+    assert_eq!(ticket.updated_at, created_at + TimeDelta::hours(1));
+    assert_eq!(ticket.priority, Priority::Low);
+    assert_eq!(ticket.due_date, Some(date(20)));
+
+    store
+        .update_ticket(
+            id,
+            TicketPatch {
+                due_date: Some(None),
+                ..patch(1)
+            },
+        )
+        .unwrap();
+    assert_eq!(store.get(id).unwrap().due_date, None);
+}
+
+#[test]
+fn tickets_are_filtered_by_due_date() {
+    let store = TicketStore::default().with_clock(clock());
+    store
+        .add_ticket(draft("期限切れ", Priority::Medium, Some(date(10))))
+        .unwrap();
+    store
+        .add_ticket(draft("今日", Priority::Medium, Some(date(17))))
+        .unwrap();
+    store
+        .add_ticket(draft("今週", Priority::Medium, Some(date(21))))
+        .unwrap();
+    store
+        .add_ticket(draft("来週", Priority::Medium, Some(date(22))))
+        .unwrap();
+    store
+        .add_ticket(draft("期限なし", Priority::Medium, None))
+        .unwrap();
+    let done = store
+        .add_ticket(draft("完了", Priority::Medium, Some(date(1))))
+        .unwrap();
+    store
+        .update_ticket(
+            done,
+            TicketPatch {
+                status: Some(TicketStatus::Done),
+                ..patch(0)
+            },
+        )
+        .unwrap();
+
+    let query = |due| TicketQuery {
+        due: Some(due),
+        ..TicketQuery::default()
+    };
+    assert_eq!(ids(&store, &query(DueFilter::Overdue)), vec![0]);
+    assert_eq!(ids(&store, &query(DueFilter::Today)), vec![1]);
+    assert_eq!(ids(&store, &query(DueFilter::ThisWeek)), vec![1, 2]);
+    assert_eq!(ids(&store, &query(DueFilter::None)), vec![4]);
+}
+
+#[test]
+fn tickets_are_sorted_by_priority_and_due_date() {
+    let clock = clock();
+    let store = TicketStore::default().with_clock(clock.clone());
+    store
+        .add_ticket(draft("低", Priority::Low, Some(date(30))))
+        .unwrap();
+    store
+        .add_ticket(draft("緊急", Priority::Urgent, None))
+        .unwrap();
+    store
+        .add_ticket(draft("高", Priority::High, Some(date(18))))
+        .unwrap();
+
+    let sorted = |sort: &str| {
+        let query = TicketQuery {
+            sort: TicketSort::try_from(sort).unwrap(),
+            ..TicketQuery::default()
+        };
+        ids(&store, &query)
+    };
+    assert_eq!(sorted("-priority"), vec![1, 2, 0]);
+    assert_eq!(sorted("priority"), vec![0, 2, 1]);
+    assert_eq!(sorted("dueDate"), vec![2, 0, 1]);
+    assert_eq!(sorted("-dueDate"), vec![0, 2, 1]);
+    assert!(TicketSort::try_from("title").is_err());
+}
+
+#[test]
+fn stale_tickets_are_found_by_update_time() {
+    let clock = clock();
+    let store = TicketStore::default().with_clock(clock.clone());
+    store
+        .add_ticket(draft("古い", Priority::Medium, None))
+        .unwrap();
+    clock.advance(TimeDelta::days(30));
+    store
+        .add_ticket(draft("新しい", Priority::Medium, None))
+        .unwrap();
+
+    let query = TicketQuery {
+        updated_before: Some(clock.now() - TimeDelta::days(14)),
+        ..TicketQuery::default()
+    };
+    assert_eq!(ids(&store, &query), vec![0]);
+}
+
+#[tokio::test]
+async fn list_api_accepts_filters_and_sort() {
+    let config = Config::default();
+    let registry = ProjectRegistry::in_memory(clock());
+    {
+        let store = registry.resolve(&ProjectKey::default()).unwrap();
+        let store = store.write().unwrap();
+        store
+            .add_ticket(draft("低", Priority::Low, Some(date(10))))
+            .unwrap();
+        store
+            .add_ticket(draft("高", Priority::High, Some(date(12))))
+            .unwrap();
+        store
+            .add_ticket(draft("期限なし", Priority::Urgent, None))
+            .unwrap();
+    }
+    let state = AppState {
+        store: StoreHandle::spawn(registry, 1024),
+        health: Arc::new(Health::new(&config)),
+        webhooks: Webhooks::in_memory(&config.webhooks, Arc::new(SystemClock)),
+        saved_queries: SavedQueries::in_memory(Arc::new(SystemClock)),
+        feed: ChangeFeed::default(),
+        blobs: BlobStore::in_memory(&config.attachments),
+        encryption: Encryption::default(),
+        admin: None,
+    };
+
+    let request = Request::get("/projects/TICKET/tickets?due=overdue&sort=-priority")
+        .body(Body::empty())
+        .unwrap();
+    let response = app(state.clone(), &config.limits)
+        .oneshot(request)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let tickets: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let titles: Vec<_> = tickets
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, vec!["高", "低"]);
+    assert_eq!(tickets[0]["dueDate"], "2024-07-12");
+    assert_eq!(tickets[0]["createdAt"], "2024-07-17T12:00:00Z");
+
+    let request = Request::get("/projects/TICKET/tickets?priority=critical")
+        .body(Body::empty())
+        .unwrap();
+    let response = app(state.clone(), &config.limits)
+        .oneshot(request)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // バージョンが一致しない更新は、ほかの更新と競合したものとして409を返す。
+    let request = Request::patch("/projects/TICKET/tickets/1")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"status": "InProgress", "version": 7}"#))
+        .unwrap();
+    let response = app(state, &config.limits).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["code"], "versionNotMatch");
+}
+
+#[test]
+fn priority_is_parsed_case_insensitively() {
+    assert_eq!(Priority::try_from(" urgent ").unwrap(), Priority::Urgent);
+    assert!(Priority::try_from("critical").is_err());
+    let draft: TicketDraft =
+        serde_json::from_str(r#"{"title": "題名", "description": "説明", "priority": "high"}"#)
+            .unwrap();
+    assert_eq!(draft.priority, Priority::High);
+    assert_eq!(draft.due_date, None);
+}
+
+#[test]
+fn concurrent_updates_keep_version_semantics() {
+    let store = TicketStore::default();
+    let ids: Vec<_> = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let store = &store;
+                s.spawn(move || {
+                    store
+                        .add_ticket(draft(&format!("チケット{i}"), Priority::Medium, None))
+                        .unwrap()
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    let mut numbers: Vec<_> = ids.iter().map(|id| id.0).collect();
+    numbers.sort();
+    assert_eq!(numbers, (0..8).collect::<Vec<_>>());
+
+    // 同じバージョンを指定した並行する更新は、1つだけが成功する。
+    let succeeded = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..8)
+            .map(|_| s.spawn(|| store.update_ticket(ids[0], patch(0)).is_ok()))
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .filter(|ok| *ok)
+            .count()
+    });
+    assert_eq!(succeeded, 1);
+    assert_eq!(store.get(ids[0]).unwrap().version, 1);
+
+    // 異なるチケットの更新は、互いに失敗させない。
+    std::thread::scope(|s| {
+        for &id in &ids {
+            let store = &store;
+            s.spawn(move || {
+                let start = store.get(id).unwrap().version;
+                for version in start..start + 100 {
+                    store.update_ticket(id, patch(version)).unwrap();
+                }
+            });
+        }
+    });
+    assert_eq!(store.get(ids[0]).unwrap().version, 101);
+    assert_eq!(store.get(ids[7]).unwrap().version, 100);
+}
+
+/// ステータスごとのチケットの数を数える射影
+#[derive(Default)]
+struct StatusCounts(std::collections::BTreeMap<TicketStatus, usize>);
+
+impl Projection for StatusCounts {
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    fn project(&mut self, record: &RecordedEvent, ticket: Option<&Ticket>) {
+        if let DomainEvent::StatusChanged { from, .. } = record.event {
+            *self.0.entry(from).or_default() -= 1;
+        }
+        if let (DomainEvent::TicketCreated { .. } | DomainEvent::StatusChanged { .. }, Some(t)) =
+            (&record.event, ticket)
+        {
+            *self.0.entry(t.status).or_default() += 1;
+        }
+    }
+}
+
+#[test]
+fn tickets_are_rebuilt_from_domain_events() {
+    let mut store = TicketStore::default();
+    let first = store
+        .add_ticket(draft("一つ目", Priority::Low, None))
+        .unwrap();
+    let second = store
+        .add_ticket(draft("二つ目", Priority::Low, None))
+        .unwrap();
+    store
+        .update_ticket(
+            first,
+            TicketPatch {
+                title: Some(TicketTitle::try_from("更新した一つ目").unwrap()),
+                status: Some(TicketStatus::InProgress),
+                priority: Some(Priority::High),
+                ..patch(0)
+            },
+        )
+        .unwrap();
+    store.add_blocker(second, first).unwrap();
+    store.update_ticket(first, patch(1)).unwrap();
+
+    // 1つのパッチで変更したフィールドごとにイベントを記録し、同じバージョンを付ける。
+    let history = store.history(first);
+    let kinds: Vec<_> = history
+        .iter()
+        .map(|record| {
+            (
+                serde_json::to_value(&record.event).unwrap()["type"].clone(),
+                record.version,
+            )
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ("TicketCreated".into(), 0),
+            ("TitleChanged".into(), 1),
+            ("StatusChanged".into(), 1),
+            ("PriorityChanged".into(), 1),
+            ("Touched".into(), 2),
+        ]
+    );
+    let replayed = domain::replay(&history).unwrap().unwrap();
+    assert_eq!(
+        serde_json::to_value(replayed).unwrap(),
+        serde_json::to_value(store.get(first).unwrap()).unwrap()
+    );
+
+    let in_progress = TicketQuery {
+        status: Some(TicketStatus::InProgress),
+        ..TicketQuery::default()
+    };
+    assert_eq!(ids(&store, &in_progress), vec![first.0]);
+    let mut counts = StatusCounts::default();
+    store.replay(&mut counts).unwrap();
+    assert_eq!(counts.0[&TicketStatus::ToDo], 1);
+    assert_eq!(counts.0[&TicketStatus::InProgress], 1);
+
+    // 可変参照を通じた変更は、射影を構築し直すと失われる。
+    store.get_mut(second).unwrap().status = TicketStatus::Done;
+    store.rebuild_projections().unwrap();
+    assert_eq!(store.get(second).unwrap().status, TicketStatus::ToDo);
+    assert_eq!(store.get(second).unwrap().blocked_by, [first].into());
+    assert_eq!(ids(&store, &in_progress), vec![first.0]);
+}
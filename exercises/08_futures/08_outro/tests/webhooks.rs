@@ -0,0 +1,454 @@
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use http_body_util::BodyExt;
+use ticket_store::actor::StoreHandle;
+use ticket_store::blobs::BlobStore;
+use ticket_store::clock::SystemClock;
+use ticket_store::config::{Config, WebhooksConfig};
+use ticket_store::crypto::Encryption;
+use ticket_store::dto::{TicketDraft, TicketPatch, WebhookDraft};
+use ticket_store::events::{ChangeFeed, TicketEventKind};
+use ticket_store::health::Health;
+use ticket_store::middleware::AdminToken;
+use ticket_store::models::{Priority, TicketDescription, TicketId, TicketStatus, TicketTitle};
+use ticket_store::registry::ProjectRegistry;
+use ticket_store::saved_queries::SavedQueries;
+use ticket_store::server::{app, AppState};
+use ticket_store::store::TicketStore;
+use ticket_store::webhooks::{self, WebhookSecret, WebhookUrl, Webhooks};
+use tokio::sync::mpsc;
+use tower::ServiceExt;
+
+const SECRET: &str = "0123456789abcdef";
+
+/// 管理APIのトークン
+const ADMIN_TOKEN: &str = "s3cret-admin-token";
+
+/// 受信側が受け取った配信
+#[derive(Debug)]
+struct Received {
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+/// 受信側の振る舞い
+#[derive(Clone)]
+struct Receiver {
+    /// 失敗を返す残りの回数
+    failures: Arc<AtomicUsize>,
+    /// 応答するまでの時間
+    delay: Duration,
+    received: mpsc::UnboundedSender<Received>,
+}
+
+async fn receive(State(receiver): State<Receiver>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    tokio::time::sleep(receiver.delay).await;
+    let _ = receiver.received.send(Received { headers, body });
+    let failed = receiver
+        .failures
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+        .is_ok();
+    match failed {
+        true => StatusCode::INTERNAL_SERVER_ERROR,
+        false => StatusCode::NO_CONTENT,
+    }
+}
+
+/// 指定した回数だけ失敗を返す受信側を起動する。
+async fn spawn_receiver(
+    failures: usize,
+    delay: Duration,
+) -> (String, mpsc::UnboundedReceiver<Received>) {
+    let (sender, received) = mpsc::unbounded_channel();
+    let receiver = Receiver {
+        failures: Arc::new(AtomicUsize::new(failures)),
+        delay,
+        received: sender,
+    };
+    let router = Router::new()
+        .route("/hook", post(receive))
+        .with_state(receiver);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await });
+
+    (format!("http://{addr}/hook"), received)
+}
+
+async fn next(received: &mut mpsc::UnboundedReceiver<Received>) -> Received {
+    tokio::time::timeout(Duration::from_secs(5), received.recv())
+        .await
+        .expect("配信を受信できません。")
+        .unwrap()
+}
+
+fn config(max_attempts: u32) -> WebhooksConfig {
+    WebhooksConfig {
+        max_attempts,
+        initial_backoff_ms: 10,
+        timeout_secs: 5,
+        max_dead_letters: 2,
+        max_concurrent_deliveries: 64,
+    }
+}
+
+fn subscription(url: &str, events: &[TicketEventKind]) -> WebhookDraft {
+    WebhookDraft {
+        url: WebhookUrl::try_from(url).unwrap(),
+        events: events.iter().copied().collect(),
+        secret: WebhookSecret::try_from(SECRET).unwrap(),
+    }
+}
+
+fn draft(title: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from("説明").unwrap(),
+        priority: Priority::default(),
+        due_date: None,
+    }
+}
+
+/// Webhookの配信に接続したチケットストアを構築する。
+fn store(webhooks: &Arc<Webhooks>) -> TicketStore {
+    let mut store = TicketStore::default();
+    store.set_events(Some(webhooks.start()));
+    store
+}
+
+async fn wait_for_dead_letter(webhooks: &Webhooks) {
+    let started_at = Instant::now();
+    while webhooks.dead_letters().is_empty() {
+        assert!(started_at.elapsed() < Duration::from_secs(5));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[test]
+fn urls_and_secrets_are_validated() {
+    assert!(WebhookUrl::try_from("http://localhost:8080/hook").is_ok());
+    assert!(WebhookUrl::try_from("https://example.com/hook").is_err());
+    assert!(WebhookUrl::try_from("/hook").is_err());
+    assert!(WebhookSecret::try_from("short").is_err());
+
+    let secret = WebhookSecret::try_from(SECRET).unwrap();
+    assert_eq!(
+        webhooks::sign(&secret, 1, b"{}"),
+        webhooks::sign(&secret, 1, b"{}")
+    );
+    assert_ne!(
+        webhooks::sign(&secret, 1, b"{}"),
+        webhooks::sign(&secret, 1, b"[]")
+    );
+    assert_ne!(
+        webhooks::sign(&secret, 1, b"{}"),
+        webhooks::sign(&secret, 2, b"{}")
+    );
+    assert!(!format!("{secret:?}").contains(SECRET));
+}
+
+#[tokio::test]
+async fn signed_events_are_delivered_off_the_request_path() {
+    let (url, mut received) = spawn_receiver(0, Duration::from_millis(500)).await;
+    let webhooks = Webhooks::in_memory(&config(3), Arc::new(SystemClock));
+    webhooks.subscribe(subscription(&url, &[])).unwrap();
+    let store = store(&webhooks);
+
+    let started_at = Instant::now();
+    let id = store.add_ticket(draft("一つ目")).unwrap();
+    assert!(started_at.elapsed() < Duration::from_millis(100));
+
+    let delivery = next(&mut received).await;
+    assert_eq!(delivery.headers[webhooks::EVENT_HEADER], "ticket.created");
+    assert_eq!(delivery.headers[header::CONTENT_TYPE], "application/json");
+    let secret = WebhookSecret::try_from(SECRET).unwrap();
+    let timestamp: i64 = delivery.headers[webhooks::TIMESTAMP_HEADER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((chrono::Utc::now().timestamp() - timestamp).abs() < 60);
+    assert_eq!(
+        delivery.headers[webhooks::SIGNATURE_HEADER],
+        webhooks::sign(&secret, timestamp, &delivery.body).as_str()
+    );
+    let event: serde_json::Value = serde_json::from_slice(&delivery.body).unwrap();
+    assert_eq!(event["kind"], "ticket.created");
+    assert_eq!(event["project"], "TICKET");
+    assert_eq!(event["ticket"]["id"], id.0);
+    assert_eq!(event["ticket"]["key"], "TICKET-1");
+}
+
+#[tokio::test]
+async fn concurrent_deliveries_are_capped() {
+    let (url, mut received) = spawn_receiver(0, Duration::from_millis(300)).await;
+    let webhooks = Webhooks::in_memory(
+        &WebhooksConfig {
+            max_concurrent_deliveries: 1,
+            ..config(3)
+        },
+        Arc::new(SystemClock),
+    );
+    webhooks.subscribe(subscription(&url, &[])).unwrap();
+    let store = store(&webhooks);
+    for title in ["一つ目", "二つ目", "三つ目"] {
+        store.add_ticket(draft(title)).unwrap();
+    }
+
+    // 配信は1つずつ試行するため、前の配信に応答するまで次の配信は届かない。
+    for _ in 0..3 {
+        next(&mut received).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(received.try_recv().is_err());
+    }
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_with_the_same_delivery_id() {
+    let (url, mut received) = spawn_receiver(2, Duration::ZERO).await;
+    let webhooks = Webhooks::in_memory(&config(5), Arc::new(SystemClock));
+    webhooks.subscribe(subscription(&url, &[])).unwrap();
+    let store = store(&webhooks);
+    store.add_ticket(draft("一つ目")).unwrap();
+
+    let mut delivery_ids = BTreeSet::new();
+    for _ in 0..3 {
+        let delivery = next(&mut received).await;
+        delivery_ids.insert(delivery.headers[webhooks::DELIVERY_HEADER].clone());
+    }
+    assert_eq!(delivery_ids.len(), 1);
+    assert!(webhooks.dead_letters().is_empty());
+}
+
+#[tokio::test]
+async fn exhausted_deliveries_are_dead_lettered_and_replayable() {
+    let (url, mut received) = spawn_receiver(2, Duration::ZERO).await;
+    let webhooks = Webhooks::in_memory(&config(2), Arc::new(SystemClock));
+    webhooks.subscribe(subscription(&url, &[])).unwrap();
+    let store = store(&webhooks);
+    store.add_ticket(draft("一つ目")).unwrap();
+
+    wait_for_dead_letter(&webhooks).await;
+    let dead_letters = webhooks.dead_letters();
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0].attempts, 2);
+    assert!(dead_letters[0].last_error.as_ref().unwrap().contains("500"));
+    assert!(dead_letters[0].failed_at.is_some());
+    next(&mut received).await;
+    next(&mut received).await;
+
+    webhooks.replay(dead_letters[0].id).unwrap();
+    let delivery = next(&mut received).await;
+    assert_eq!(
+        delivery.headers[webhooks::DELIVERY_HEADER],
+        dead_letters[0].id.0.to_string().as_str()
+    );
+    assert!(webhooks.dead_letters().is_empty());
+    assert!(webhooks.replay(dead_letters[0].id).is_err());
+}
+
+#[tokio::test]
+async fn dead_letters_are_capped_and_persisted() {
+    let (url, _received) = spawn_receiver(usize::MAX, Duration::ZERO).await;
+    let data_dir = tempfile::tempdir().unwrap();
+    {
+        let webhooks = Webhooks::open(data_dir.path(), &config(1), Arc::new(SystemClock)).unwrap();
+        webhooks.subscribe(subscription(&url, &[])).unwrap();
+        let store = store(&webhooks);
+        for title in ["一つ目", "二つ目", "三つ目"] {
+            store.add_ticket(draft(title)).unwrap();
+        }
+        let started_at = Instant::now();
+        while webhooks.dead_letters().len() < 2 {
+            assert!(started_at.elapsed() < Duration::from_secs(5));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(webhooks.dead_letters().len(), 2);
+    }
+
+    let webhooks = Webhooks::open(data_dir.path(), &config(1), Arc::new(SystemClock)).unwrap();
+    let dead_letters = webhooks.dead_letters();
+    assert_eq!(dead_letters.len(), 2);
+    assert!(dead_letters.iter().all(|delivery| delivery.attempts == 1));
+    webhooks.replay(dead_letters[0].id).unwrap();
+    let webhooks = Webhooks::open(data_dir.path(), &config(1), Arc::new(SystemClock)).unwrap();
+    assert!(webhooks
+        .dead_letters()
+        .iter()
+        .all(|delivery| delivery.id != dead_letters[0].id));
+}
+
+#[tokio::test]
+async fn subscriptions_filter_events_and_are_persisted() {
+    let (url, mut received) = spawn_receiver(0, Duration::ZERO).await;
+    let data_dir = tempfile::tempdir().unwrap();
+    {
+        let webhooks = Webhooks::open(data_dir.path(), &config(3), Arc::new(SystemClock)).unwrap();
+        webhooks
+            .subscribe(subscription(&url, &[TicketEventKind::Updated]))
+            .unwrap();
+    }
+
+    let webhooks = Webhooks::open(data_dir.path(), &config(3), Arc::new(SystemClock)).unwrap();
+    assert_eq!(webhooks.list().len(), 1);
+    let store = store(&webhooks);
+    let id = store.add_ticket(draft("一つ目")).unwrap();
+    store
+        .update_ticket(
+            id,
+            TicketPatch {
+                title: None,
+                description: None,
+                status: Some(TicketStatus::InProgress),
+                priority: None,
+                due_date: None,
+                version: 0,
+            },
+        )
+        .unwrap();
+
+    let delivery = next(&mut received).await;
+    assert_eq!(delivery.headers[webhooks::EVENT_HEADER], "ticket.updated");
+    let event: serde_json::Value = serde_json::from_slice(&delivery.body).unwrap();
+    assert_eq!(event["ticket"]["status"], "InProgress");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(received.try_recv().is_err());
+
+    let id = webhooks.list()[0].id;
+    webhooks.unsubscribe(id).unwrap();
+    let webhooks = Webhooks::open(data_dir.path(), &config(3), Arc::new(SystemClock)).unwrap();
+    assert!(webhooks.list().is_empty());
+    assert_eq!(TicketId(0), store.get(TicketId(0)).unwrap().id);
+}
+
+fn router(admin: Option<AdminToken>) -> Router {
+    let config = Config::default();
+    let state = AppState {
+        store: StoreHandle::spawn(ProjectRegistry::default(), 1024),
+        health: Arc::new(Health::new(&config)),
+        webhooks: Webhooks::in_memory(&config.webhooks, Arc::new(SystemClock)),
+        saved_queries: SavedQueries::in_memory(Arc::new(SystemClock)),
+        feed: ChangeFeed::default(),
+        blobs: BlobStore::in_memory(&config.attachments),
+        encryption: Encryption::default(),
+        admin,
+    };
+    app(state, &config.limits)
+}
+
+/// 管理APIのトークンを付けてリクエストを送信する。
+async fn request(router: &Router, method: &str, uri: &str, body: &str) -> (StatusCode, String) {
+    request_with_token(router, method, uri, body, Some(ADMIN_TOKEN)).await
+}
+
+async fn request_with_token(
+    router: &Router,
+    method: &str,
+    uri: &str,
+    body: &str,
+    token: Option<&str>,
+) -> (StatusCode, String) {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(token) = token {
+        builder = builder.header(header::AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let request = builder.body(Body::from(body.to_string())).unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn webhook_routes_manage_subscriptions_without_exposing_secrets() {
+    let router = router(Some(AdminToken::try_from(ADMIN_TOKEN).unwrap()));
+
+    let (status, _) = request(
+        &router,
+        "POST",
+        "/webhooks",
+        &format!(r#"{{"url": "https://example.com/hook", "secret": "{SECRET}"}}"#),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, body) = request(
+        &router,
+        "POST",
+        "/webhooks",
+        &format!(
+            r#"{{"url": "http://localhost:9/hook", "events": ["ticket.moved"], "secret": "{SECRET}"}}"#
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(!body.contains(SECRET));
+    let webhook: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(webhook["events"], serde_json::json!(["ticket.moved"]));
+    let uri = format!("/webhooks/{}", webhook["id"].as_str().unwrap());
+
+    let (status, body) = request(&router, "GET", "/webhooks", "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!body.contains(SECRET));
+    assert_eq!(request(&router, "GET", &uri, "").await.0, StatusCode::OK);
+    assert_eq!(
+        request(&router, "DELETE", &uri, "").await.0,
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        request(&router, "GET", &uri, "").await.0,
+        StatusCode::NOT_FOUND
+    );
+
+    let (status, body) = request(&router, "GET", "/webhook-deliveries/dead-letters", "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "[]");
+    let (status, _) = request(
+        &router,
+        "POST",
+        &format!("/webhook-deliveries/{}/replay", uuid::Uuid::new_v4()),
+        "",
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn webhook_routes_require_the_admin_token() {
+    let subscription = format!(r#"{{"url": "http://localhost:9/hook", "secret": "{SECRET}"}}"#);
+    let replay = format!("/webhook-deliveries/{}/replay", uuid::Uuid::new_v4());
+    let routes = [
+        ("POST", "/webhooks", subscription.as_str()),
+        ("GET", "/webhooks", ""),
+        ("GET", "/webhook-deliveries/dead-letters", ""),
+        ("POST", replay.as_str(), ""),
+    ];
+
+    let disabled = router(None);
+    let router = router(Some(AdminToken::try_from(ADMIN_TOKEN).unwrap()));
+    for (method, uri, body) in routes {
+        for token in [None, Some("wrong-token")] {
+            let (status, _) = request_with_token(&router, method, uri, body, token).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{method} {uri} {token:?}");
+        }
+    }
+    let (_, body) = request(&router, "GET", "/webhooks", "").await;
+    assert_eq!(body, "[]");
+
+    // トークンを設定していない場合は、Webhookを購読できない。
+    for (method, uri, body) in routes {
+        let (status, _) = request(&disabled, method, uri, body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{method} {uri}");
+    }
+}